@@ -0,0 +1,23 @@
+function! gnvim#cmdline#set_position(position)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'CmdlinePosition',
+                \ a:position)
+endfunction
+
+function! gnvim#cmdline#set_search_position(position)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'CmdlineSearchPosition',
+                \ a:position)
+endfunction
+
+function! gnvim#cmdline#set_wildmenu_layout(layout)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'WildmenuLayout',
+                \ a:layout)
+endfunction
@@ -0,0 +1,3 @@
+function! gnvim#command_palette#show()
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'CommandPaletteShow')
+endfunction
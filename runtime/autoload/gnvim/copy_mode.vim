@@ -0,0 +1,5 @@
+" Starts copy mode, where the text of the messages (or the cmdline block)
+" is selected and copied with the keyboard.
+function! gnvim#copy_mode#enter()
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'CopyModeEnter')
+endfunction
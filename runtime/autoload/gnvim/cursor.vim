@@ -5,3 +5,30 @@ function! gnvim#cursor#enable_animations(enable)
                 \ 'EnableCursorAnimations',
                 \ a:enable == 1)
 endfunction
+
+function! gnvim#cursor#set_blink_curve(curve)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'CursorBlinkCurve',
+                \ a:curve)
+endfunction
+
+function! gnvim#cursor#enable_hollow_unfocused(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'EnableCursorHollowUnfocused',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#cursor#set_crosshair(opts)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'SetCrosshair',
+                \ get(a:opts, 'line', 0),
+                \ get(a:opts, 'column', 0),
+                \ get(a:opts, 'color', ''),
+                \ get(a:opts, 'opacity', 0.1))
+endfunction
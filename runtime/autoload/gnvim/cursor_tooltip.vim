@@ -37,3 +37,8 @@ endfunction
 function! gnvim#cursor_tooltip#load_style(path)
     call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'CursorTooltipLoadStyle', a:path)
 endfunction
+
+function! gnvim#cursor_tooltip#scroll(lines)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'CursorTooltipScroll', a:lines)
+    return ''
+endfunction
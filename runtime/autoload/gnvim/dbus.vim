@@ -0,0 +1,5 @@
+" Opens `path` for the D-Bus `OpenFile` method. The path is an argument
+" instead of a part of a command, so that it's never evaluated.
+function! gnvim#dbus#open_file(path)
+    execute 'drop' fnameescape(a:path)
+endfunction
@@ -0,0 +1,19 @@
+function! gnvim#debug#damage_overlay(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'EnableDamageOverlay',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#debug#toggle_perf_overlay()
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'TogglePerfOverlay')
+endfunction
+
+function! gnvim#debug#show_logs()
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'LogViewerShow')
+endfunction
+
+function! gnvim#debug#dump_grids(dir)
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'DumpGrids', a:dir)
+endfunction
@@ -0,0 +1,63 @@
+" Callbacks of the open file dialogs, by their ids.
+let s:callbacks = {}
+let s:next_id = 1
+
+function! s:show(event, callback, opts)
+    let l:id = s:next_id
+    let s:next_id += 1
+    let s:callbacks[l:id] = a:callback
+
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', a:event, l:id, a:opts)
+endfunction
+
+" Shows the native file chooser for opening a file. `callback` is called
+" with the chosen path, or v:null if the dialog is canceled. The optional
+" dict can have `title`, `folder` (where the dialog starts) and `patterns`
+" (a list of glob patterns of the files that are shown).
+function! gnvim#dialog#open_file(callback, ...)
+    call s:show('FileOpenDialog', a:callback, get(a:000, 0, {}))
+endfunction
+
+" Like gnvim#dialog#open_file(), for saving a file. The options can also
+" have `name`, the suggested file name.
+function! gnvim#dialog#save_file(callback, ...)
+    call s:show('FileSaveDialog', a:callback, get(a:000, 0, {}))
+endfunction
+
+" Called by gnvim with the path chosen in dialog `id`.
+function! gnvim#dialog#respond(id, path)
+    if !has_key(s:callbacks, a:id)
+        return
+    endif
+
+    let l:Callback = remove(s:callbacks, a:id)
+    call call(l:Callback, [a:path])
+endfunction
+
+function! s:edit(path)
+    if a:path isnot v:null
+        execute 'drop' fnameescape(a:path)
+    endif
+endfunction
+
+function! s:saveas(path)
+    if a:path isnot v:null
+        " The dialog already asked about overwriting.
+        execute 'saveas!' fnameescape(a:path)
+    endif
+endfunction
+
+" Picks a file with the file chooser, and opens it.
+function! gnvim#dialog#open()
+    call gnvim#dialog#open_file(function('s:edit'),
+                \ {'folder': expand('%:p:h')})
+endfunction
+
+" Picks a path with the file chooser, and saves the current buffer there
+" (see :saveas).
+function! gnvim#dialog#save_as()
+    call gnvim#dialog#save_file(function('s:saveas'), {
+                \ 'folder': expand('%:p:h'),
+                \ 'name': expand('%:t'),
+                \ })
+endfunction
@@ -0,0 +1,70 @@
+" Highlights of the syntax ids, so that each is looked up once per export.
+let s:hl_cache = {}
+
+function! s:highlight(id)
+    if a:id == 0
+        return {}
+    endif
+    if !has_key(s:hl_cache, a:id)
+        let s:hl_cache[a:id] = nvim_get_hl_by_id(a:id, v:true)
+    endif
+    return s:hl_cache[a:id]
+endfunction
+
+" Returns line `lnum` of the current buffer as [text, highlight] pairs,
+" with the highlights of the syntax items in the line.
+function! s:line(lnum)
+    let l:text = getline(a:lnum)
+    if l:text ==# ''
+        return []
+    endif
+    " Without syntax highlighting, there's nothing to look up.
+    if &syntax ==# ''
+        return [[l:text, {}]]
+    endif
+
+    let l:segments = []
+    let l:start = 0
+    let l:col = 0
+    let l:id = 0
+
+    " Look up the syntax once per character, rather than once per byte.
+    for l:char in split(l:text, '\zs')
+        let l:next = synIDtrans(synID(a:lnum, l:col + 1, 1))
+        if l:col > 0 && l:next != l:id
+            call add(l:segments, [strpart(l:text, l:start, l:col - l:start),
+                        \ s:highlight(l:id)])
+            let l:start = l:col
+        endif
+        let l:id = l:next
+        let l:col += len(l:char)
+    endfor
+
+    call add(l:segments, [strpart(l:text, l:start), s:highlight(l:id)])
+
+    return l:segments
+endfunction
+
+" Returns lines from `line1` to `line2` of the current buffer as lists of
+" [text, highlight] pairs, with the highlights of the syntax items.
+function! gnvim#export#lines(line1, line2)
+    let s:hl_cache = {}
+    return map(range(str2nr(a:line1), str2nr(a:line2)),
+                \ {_, lnum -> s:line(lnum)})
+endfunction
+
+" Exports the current window, or lines from `line1` to `line2` of the
+" current buffer, to `path` as `format` (pdf, svg or html).
+function! gnvim#export#export(format, path, ...)
+    let l:args = [g:gnvim_channel_id, 'Gnvim', 'Export', a:format,
+                \ expand(a:path)]
+
+    if a:0 == 2
+        call add(l:args, gnvim#export#lines(a:1, a:2))
+    elseif a:0 != 0
+        echoerr 'Gnvim export: usage: {format} {path} [{line1} {line2}]'
+        return
+    endif
+
+    call call('rpcnotify', l:args)
+endfunction
@@ -0,0 +1,26 @@
+let s:enabled = 0
+
+" Shows the files of the current directory in the file tree.
+function! gnvim#file_tree#update()
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'FileTreeShow', getcwd())
+endfunction
+
+" Shows the files of the current directory in a sidebar next to the
+" windows, or hides the sidebar. The sidebar follows the current directory
+" as it changes.
+function! gnvim#file_tree#toggle()
+    let s:enabled = !s:enabled
+
+    augroup gnvim_file_tree
+        autocmd!
+        if s:enabled
+            autocmd DirChanged,TabEnter * call gnvim#file_tree#update()
+        endif
+    augroup END
+
+    if s:enabled
+        call gnvim#file_tree#update()
+    else
+        call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'FileTreeHide')
+    endif
+endfunction
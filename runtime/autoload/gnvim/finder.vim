@@ -0,0 +1,3 @@
+function! gnvim#finder#show()
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'FinderShow')
+endfunction
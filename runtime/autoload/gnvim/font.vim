@@ -0,0 +1,3 @@
+function! gnvim#font#picker()
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'FontPickerShow')
+endfunction
@@ -0,0 +1,26 @@
+" Highlight groups that style gnvim's widgets, but that nvim doesn't send
+" with the UI's highlights.
+let s:groups = ['DiagnosticError', 'DiagnosticWarn', 'DiagnosticInfo',
+            \ 'DiagnosticHint']
+
+function! gnvim#highlight#update()
+    let l:groups = {}
+    for l:name in filter(copy(s:groups), 'hlexists(v:val)')
+        let l:groups[l:name] = nvim_get_hl_by_name(l:name, v:true)
+    endfor
+
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SetHlGroups', l:groups)
+endfunction
+
+" Draws the text in `groups` (e.g. the highlight groups of inline git blame
+" or inlay hints) as virtual text.
+function! gnvim#highlight#set_virtual_text(groups, ...)
+    let l:opts = get(a:000, 0, {})
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'SetVirtualText',
+                \ a:groups,
+                \ get(l:opts, 'opacity', 0.6),
+                \ get(l:opts, 'proportional', 0))
+endfunction
@@ -0,0 +1,38 @@
+let s:next_id = 1
+
+" Returns an id for a new image.
+function! gnvim#image#next_id()
+    let l:id = s:next_id
+    let s:next_id += 1
+    return l:id
+endfunction
+
+function! gnvim#image#show(path, row, col, rows, cols)
+    let l:id = gnvim#image#next_id()
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'ImageShow', l:id,
+                \ expand(a:path), str2nr(a:row) - 1, str2nr(a:col) - 1,
+                \ str2nr(a:rows), str2nr(a:cols))
+    return l:id
+endfunction
+
+function! gnvim#image#hide(id)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'ImageHide', str2nr(a:id))
+endfunction
+
+function! gnvim#image#clear()
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'ImageClear')
+endfunction
+
+" Handles `:Gnvim image {show|hide|clear} ...`.
+function! gnvim#image#command(cmd, ...)
+    if a:cmd ==# 'show' && a:0 == 5
+        echo call('gnvim#image#show', a:000)
+    elseif a:cmd ==# 'hide' && a:0 == 1
+        call gnvim#image#hide(a:1)
+    elseif a:cmd ==# 'clear' && a:0 == 0
+        call gnvim#image#clear()
+    else
+        echoerr 'Gnvim image: usage: show {path} {row} {col} {rows} {cols}'
+                    \ . ' | hide {id} | clear'
+    endif
+endfunction
@@ -0,0 +1,15 @@
+function! gnvim#input#enable_alt_as_meta(enable)
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'EnableAltAsMeta', a:enable)
+endfunction
+
+function! gnvim#input#enable_shortcut(name, enable)
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'EnableShortcut', a:name, a:enable)
+endfunction
+
+function! gnvim#input#enable_gui_selection(enable)
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'EnableGuiSelection', a:enable)
+endfunction
+
+function! gnvim#input#set_queue_size(size)
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'InputSetQueueSize', a:size)
+endfunction
@@ -0,0 +1,24 @@
+" Sets the patterns (rust regexes) of the URLs and the file paths in the
+" grids, which are underlined under the mouse and opened with ctrl + click.
+" An empty pattern disables the links of its kind.
+function! gnvim#links#set_patterns(url, path)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SetLinkPatterns', a:url,
+                \ a:path)
+endfunction
+
+" Opens file `path`, and moves the cursor to `line` and `col` if they're
+" not zero. Called when a path is clicked.
+function! gnvim#links#open_path(path, line, col)
+    let l:path = expand(a:path)
+    if !filereadable(l:path) && !isdirectory(l:path)
+        echohl WarningMsg
+        echo 'Gnvim: no such file: ' . a:path
+        echohl None
+        return
+    endif
+
+    execute 'edit' fnameescape(l:path)
+    if a:line > 0
+        call cursor(a:line, max([a:col, 1]))
+    endif
+endfunction
@@ -0,0 +1,5 @@
+function! gnvim#notify#send(title, body, ...)
+    let l:unfocused_only = get(a:, 1, 0) == 1
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'DesktopNotify',
+                \ a:title, a:body, l:unfocused_only)
+endfunction
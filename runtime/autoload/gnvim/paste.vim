@@ -0,0 +1,5 @@
+" Sets the size (in bytes) above which pastes are confirmed first. 0 never
+" asks.
+function! gnvim#paste#set_confirm_threshold(bytes)
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'PasteSetConfirmThreshold', a:bytes)
+endfunction
@@ -15,3 +15,29 @@ function! gnvim#popupmenu#show_menu_on_all_items(bool)
     call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'PopupmenuShowMenuOnAllItems', a:bool)
     return ''
 endfunction
+
+function! gnvim#popupmenu#set_max_height(height)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'PopupmenuSetMaxHeight', a:height)
+    return ''
+endfunction
+
+function! gnvim#popupmenu#set_max_rows(rows)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'PopupmenuSetMaxRows', a:rows)
+endfunction
+
+function! gnvim#popupmenu#set_position(position)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'PopupmenuSetPosition', a:position)
+endfunction
+
+function! gnvim#popupmenu#set_details_side(side)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'PopupmenuSetDetailsSide', a:side)
+endfunction
+
+function! gnvim#popupmenu#scroll_details(lines)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'PopupmenuScrollDetails', a:lines)
+    return ''
+endfunction
+
+function! gnvim#popupmenu#set_kind_glyph(kind, glyph)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'PopupmenuKindGlyph', a:kind, a:glyph)
+endfunction
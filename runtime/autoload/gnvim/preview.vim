@@ -0,0 +1,49 @@
+let s:enabled = 0
+" Timer of the pending update while typing.
+let s:timer = -1
+
+" Sends the current buffer to the preview, if it's a markdown buffer (or
+" `force` is set).
+function! gnvim#preview#update(...)
+    let l:force = get(a:000, 0, 0)
+    if !l:force && &filetype !=# 'markdown'
+        return
+    endif
+
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'PreviewUpdate',
+                \ join(getline(1, '$'), "\n"), win_getid())
+endfunction
+
+function! s:debounced_update(timer)
+    let s:timer = -1
+    call gnvim#preview#update()
+endfunction
+
+" Updates the preview once typing pauses for 200ms, instead of on every
+" change.
+function! s:debounce_update()
+    call timer_stop(s:timer)
+    let s:timer = timer_start(200, function('s:debounced_update'))
+endfunction
+
+" Shows the current buffer rendered as markdown in a pane next to the
+" windows, or hides the pane. The preview follows the markdown buffers as
+" they're edited and entered, and scrolls with their windows.
+function! gnvim#preview#toggle()
+    let s:enabled = !s:enabled
+
+    augroup gnvim_preview
+        autocmd!
+        if s:enabled
+            autocmd BufEnter,WinEnter,BufWritePost,TextChanged *
+                        \ call gnvim#preview#update()
+            autocmd TextChangedI * call s:debounce_update()
+        endif
+    augroup END
+
+    if s:enabled
+        call gnvim#preview#update(1)
+    else
+        call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'PreviewHide')
+    endif
+endfunction
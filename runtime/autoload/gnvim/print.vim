@@ -0,0 +1,16 @@
+" Opens the print dialog for printing the current buffer, or lines from
+" `line1` to `line2` of it.
+function! gnvim#print#print(...)
+    if a:0 != 0 && a:0 != 2
+        echoerr 'Gnvim print: usage: [{line1} {line2}]'
+        return
+    endif
+
+    let l:line1 = a:0 == 2 ? str2nr(a:1) : 1
+    let l:line2 = a:0 == 2 ? str2nr(a:2) : line('$')
+    let l:title = expand('%:~:.')
+
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'Print',
+                \ empty(l:title) ? '[No Name]' : l:title, l:line1,
+                \ gnvim#export#lines(l:line1, l:line2))
+endfunction
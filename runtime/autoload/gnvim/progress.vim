@@ -0,0 +1,7 @@
+function! gnvim#progress#set(progress)
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SetProgress', a:progress)
+endfunction
+
+function! gnvim#progress#clear()
+    return gnvim#progress#set(v:null)
+endfunction
@@ -0,0 +1,25 @@
+" Takes a screenshot of `target`: 'window', 'grid' (the current window) or
+" 'cells' followed by the window line and column (1-based) and the number
+" of lines and columns. The screenshot is saved as a png to the optional
+" last argument, or copied to the clipboard.
+function! gnvim#screenshot#take(target, ...)
+    let l:args = copy(a:000)
+    let l:count = a:target ==# 'cells' ? 4 : 0
+    let l:path = len(l:args) > l:count ? expand(remove(l:args, -1)) : ''
+
+    if index(['window', 'grid', 'cells'], a:target) == -1
+                \ || len(l:args) != l:count
+        echoerr 'Gnvim screenshot: usage: {window|grid'
+                    \ . '|cells {row} {col} {rows} {cols}} [{path}]'
+        return
+    endif
+
+    call map(l:args, 'str2nr(v:val)')
+    if a:target ==# 'cells'
+        let l:args[0] -= 1
+        let l:args[1] -= 1
+    endif
+
+    call call('rpcnotify', [g:gnvim_channel_id, 'Gnvim', 'Screenshot',
+                \ l:path, a:target] + l:args)
+endfunction
@@ -0,0 +1,7 @@
+function! gnvim#scroll#enable_kinetic(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'EnableKineticScrolling',
+                \ a:enable == 1)
+endfunction
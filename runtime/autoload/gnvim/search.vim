@@ -0,0 +1,85 @@
+" Cursor position when the search bar's pattern was last changed. Typing
+" searches from here, like 'incsearch' does.
+let s:start = []
+
+" Opens the search bar.
+function! gnvim#search#show()
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SearchBarShow')
+endfunction
+
+" Returns the matches of the last search pattern, like searchcount().
+function! gnvim#search#count()
+    if empty(@/)
+        return {}
+    endif
+
+    return searchcount({'maxcount': 999, 'timeout': 100})
+endfunction
+
+" Moves the cursor to the first match of `pattern` from where the search
+" started. The search bar sets `pattern` as the last search pattern.
+function! gnvim#search#update(pattern)
+    if empty(s:start)
+        let s:start = getcurpos()
+    endif
+    call setpos('.', s:start)
+
+    if empty(a:pattern)
+        return {}
+    endif
+
+    try
+        call search(a:pattern, 'cw')
+    catch /^Vim\%((\a\+)\)\=:E/
+        " The pattern isn't complete yet.
+        return {}
+    endtry
+
+    return gnvim#search#count()
+endfunction
+
+" Moves the cursor to the next match, or the previous one if `forward` is
+" 0.
+function! gnvim#search#next(forward)
+    if empty(@/)
+        return {}
+    endif
+
+    call search(@/, a:forward ? 'w' : 'bw')
+    let s:start = getcurpos()
+
+    return gnvim#search#count()
+endfunction
+
+" Replaces the match under the cursor with `text` and moves to the next
+" match. If `all` is 1, replaces all the matches in the buffer instead.
+function! gnvim#search#replace(text, all)
+    if empty(@/)
+        return {}
+    endif
+
+    if a:all
+        execute 'keeppatterns %s//' . escape(a:text, '/\&~') . '/ge'
+    else
+        let [l:lnum, l:start] = searchpos(@/, 'cnW')
+        let [l:end_lnum, l:end] = searchpos(@/, 'cenW')
+
+        if l:lnum == line('.') && l:start == col('.') && l:end_lnum == l:lnum
+            let l:line = getline(l:lnum)
+            " The end is the first byte of the match's last character.
+            let l:end += len(matchstr(l:line[l:end - 1:], '.')) - 1
+            call setline(l:lnum, strpart(l:line, 0, l:start - 1) . a:text
+                        \ . strpart(l:line, l:end))
+        endif
+
+        call search(@/, 'w')
+    endif
+
+    let s:start = getcurpos()
+    return gnvim#search#count()
+endfunction
+
+" Called when the search bar is closed.
+function! gnvim#search#done()
+    let s:start = []
+endfunction
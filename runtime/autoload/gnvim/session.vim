@@ -0,0 +1,29 @@
+" The state (see s:state()) of the latest saved session.
+let s:saved_state = ''
+
+" Returns what the session depends on: the window layouts, the listed
+" buffers and their changes, and the current directory.
+function! s:state()
+    let l:layouts = map(range(1, tabpagenr('$')), 'winlayout(v:val)')
+    let l:buffers = map(getbufinfo({'buflisted': 1}),
+                \ '[v:val.name, v:val.changedtick]')
+    return string([l:layouts, l:buffers, getcwd()])
+endfunction
+
+" Saves the session, if the windows or buffers have changed since the last
+" save.
+function! gnvim#session#save()
+    if !exists('g:gnvim_session_file')
+        return
+    endif
+
+    let l:state = s:state()
+    if l:state ==# s:saved_state
+        return
+    endif
+    let s:saved_state = l:state
+
+    let l:this_session = v:this_session
+    execute 'silent! mksession! ' . fnameescape(g:gnvim_session_file)
+    let v:this_session = l:this_session
+endfunction
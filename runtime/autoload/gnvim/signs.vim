@@ -0,0 +1,92 @@
+" Signs of the windows by window id, as [lnum, kind]. Kept for sending the
+" signs again when the windows scroll.
+let s:signs = {}
+
+" Returns the first column and the width of window `winid`'s sign column,
+" which the gutter covers. The sign column is between the fold column and
+" the number column, and the gutter takes (at most) its last two cells.
+function! s:sign_column(winid)
+    let l:info = getwininfo(a:winid)
+    if empty(l:info)
+        return [0, 0]
+    endif
+
+    let l:signcolumn = getwinvar(a:winid, '&signcolumn')
+    let l:number = getwinvar(a:winid, '&number')
+                \ || getwinvar(a:winid, '&relativenumber')
+    let l:numberwidth = 0
+    if l:number
+        let l:lines = getwinvar(a:winid, '&number')
+                    \ ? line('$', a:winid) : winheight(a:winid)
+        let l:numberwidth = max([getwinvar(a:winid, '&numberwidth'),
+                    \ len(string(l:lines)) + 1])
+    endif
+
+    let l:end = l:info[0].textoff - l:numberwidth
+    if l:signcolumn ==# 'no'
+        return [0, 0]
+    elseif l:signcolumn ==# 'number' && l:number
+        " The signs are drawn in the number column, before its space.
+        return [l:end, l:numberwidth - 1]
+    endif
+
+    " The width of an "auto" fold column isn't known, but it's rarely used
+    " with a sign column.
+    let l:foldcolumn = getwinvar(a:winid, '&foldcolumn')
+    let l:foldwidth = l:foldcolumn =~# '^\d\+$' ? str2nr(l:foldcolumn) : 0
+    let l:width = max([min([2, l:end - l:foldwidth]), 0])
+    return [l:end - l:width, l:width]
+endfunction
+
+" Sends the signs of window `winid` as the rows of the window.
+function! s:send(winid)
+    let l:top = win_screenpos(a:winid)[0]
+    let l:rows = []
+    for [l:lnum, l:kind] in get(s:signs, a:winid, [])
+        let l:row = screenpos(a:winid, l:lnum, 1).row
+        " Not visible (scrolled out or folded).
+        if l:row > 0
+            call add(l:rows, [l:row - l:top, l:kind])
+        endif
+    endfor
+
+    let [l:col, l:width] = s:sign_column(a:winid)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SetSigns', a:winid,
+                \ l:col, l:width, l:rows)
+endfunction
+
+function! s:update()
+    for l:winid in keys(s:signs)
+        call s:send(str2nr(l:winid))
+    endfor
+endfunction
+
+" Sets the signs in the gutter of window `winid` (the current window by
+" default). `signs` is a list of [lnum, kind], where kind is 'error',
+" 'warn', 'info', 'hint', 'git_add', 'git_change' or 'git_delete'.
+function! gnvim#signs#set(signs, ...)
+    let l:winid = get(a:000, 0, win_getid())
+    if empty(a:signs)
+        if has_key(s:signs, l:winid)
+            call remove(s:signs, l:winid)
+        endif
+    else
+        let s:signs[l:winid] = a:signs
+    endif
+
+    augroup gnvim_signs
+        autocmd!
+        if !empty(s:signs)
+            autocmd WinScrolled,VimResized * call s:update()
+            autocmd OptionSet signcolumn,foldcolumn,number,relativenumber,
+                        \numberwidth call s:update()
+            autocmd WinClosed * silent! call remove(s:signs, expand('<amatch>'))
+        endif
+    augroup END
+
+    call s:send(l:winid)
+endfunction
+
+function! gnvim#signs#clear(...)
+    call gnvim#signs#set([], get(a:000, 0, win_getid()))
+endfunction
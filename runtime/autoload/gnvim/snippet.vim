@@ -0,0 +1,68 @@
+" State of the snippet, kept for sending the placeholders again when the
+" window scrolls.
+let s:winid = 0
+let s:bufnr = 0
+let s:current = 0
+let s:total = 0
+let s:placeholders = []
+
+" Converts placeholder [index, lnum, col, end_col] of the snippet's buffer
+" to [index, row, col, len] in the cells of the snippet's window. Returns []
+" if the placeholder isn't visible.
+function! s:to_cells(placeholder)
+    let [l:index, l:lnum, l:col, l:end_col] = a:placeholder
+    let l:win = s:winid
+    let l:start = screenpos(l:win, l:lnum, l:col)
+    if l:start.row == 0 && l:col > 1
+        " Empty placeholder at the end of the line.
+        let l:prev = screenpos(l:win, l:lnum, l:col - 1)
+        let l:start = {'row': l:prev.row, 'col': l:prev.endcol + 1}
+    endif
+    if l:start.row == 0
+        return []
+    endif
+
+    let [l:win_row, l:win_col] = win_screenpos(l:win)
+    let l:cell_col = l:start.col - l:win_col
+    let l:len = 0
+    if l:end_col > l:col
+        let l:end = screenpos(l:win, l:lnum, l:end_col - 1)
+        " Wrapped placeholders are only tinted to the end of the row.
+        let l:len = l:end.row == l:start.row
+                    \ ? l:end.endcol - l:start.col + 1
+                    \ : winwidth(l:win) - l:cell_col
+    endif
+
+    return [l:index, l:start.row - l:win_row, l:cell_col, l:len]
+endfunction
+
+function! s:send()
+    " The window might show another buffer by now.
+    let l:placeholders = winbufnr(s:winid) == s:bufnr
+                \ ? map(copy(s:placeholders), {_, p -> s:to_cells(p)})
+                \ : []
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SnippetState', s:winid,
+                \ s:current, s:total,
+                \ filter(l:placeholders, {_, p -> !empty(p)}))
+endfunction
+
+function! gnvim#snippet#set_state(current, total, placeholders)
+    let s:winid = win_getid()
+    let s:bufnr = bufnr('%')
+    let s:current = a:current
+    let s:total = a:total
+    let s:placeholders = a:placeholders
+
+    augroup gnvim_snippet
+        autocmd!
+        if s:total > 0
+            autocmd WinScrolled,VimResized,BufWinEnter * call s:send()
+        endif
+    augroup END
+
+    call s:send()
+endfunction
+
+function! gnvim#snippet#clear()
+    call gnvim#snippet#set_state(0, 0, [])
+endfunction
@@ -0,0 +1,167 @@
+" Names of the modes, by the first character of mode().
+let s:modes = {
+            \ 'n': 'NORMAL',
+            \ 'i': 'INSERT',
+            \ 'R': 'REPLACE',
+            \ 'v': 'VISUAL',
+            \ 'V': 'V-LINE',
+            \ "\<C-v>": 'V-BLOCK',
+            \ 's': 'SELECT',
+            \ 'S': 'S-LINE',
+            \ "\<C-s>": 'S-BLOCK',
+            \ 'c': 'COMMAND',
+            \ 't': 'TERMINAL',
+            \ }
+
+" Highlight groups looked up by s:highlight, cleared on colorscheme changes.
+let s:hl_cache = {}
+
+" Returns highlight group `name` as a map for gnvim.
+function! s:highlight(name)
+    if empty(a:name)
+        return {}
+    endif
+    if !has_key(s:hl_cache, a:name)
+        let s:hl_cache[a:name] = nvim_get_hl_by_name(a:name, v:true)
+    endif
+    return s:hl_cache[a:name]
+endfunction
+
+" Shows `segments` in the GUI statusbar below the grids. Segments are dicts
+" with `text`, and optionally `hl` (a highlight group), `icon` (an icon
+" name), `align` (`left` or `right`) and `click` (a function called with
+" the mouse button when the segment is clicked). Without segments, hides
+" the statusbar.
+function! gnvim#statusline#set(segments)
+    let l:segments = map(copy(a:segments), {_, s -> {
+                \ 'text': get(s, 'text', ''),
+                \ 'hl': s:highlight(get(s, 'hl', '')),
+                \ 'icon': get(s, 'icon', ''),
+                \ 'align': get(s, 'align', 'left'),
+                \ 'click': get(s, 'click', ''),
+                \ }})
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SetStatusline', l:segments)
+endfunction
+
+" Returns the git branch of the current buffer, cached in the buffer.
+function! s:git_branch()
+    if exists('*FugitiveHead')
+        return FugitiveHead()
+    elseif exists('b:gitsigns_head')
+        return b:gitsigns_head
+    endif
+
+    if !exists('b:gnvim_git_branch')
+        let l:dir = expand('%:p:h')
+        let l:branch = systemlist(['git', '-C', isdirectory(l:dir)
+                    \ ? l:dir : getcwd(), 'rev-parse', '--abbrev-ref', 'HEAD'])
+        let b:gnvim_git_branch = v:shell_error ? '' : get(l:branch, 0, '')
+    endif
+    return b:gnvim_git_branch
+endfunction
+
+" Returns the number of errors and warnings of the current buffer. The
+" counts are cached in the buffer until its diagnostics change.
+function! s:diagnostics()
+    if !has('nvim-0.6')
+        return [0, 0]
+    endif
+    if type(get(b:, 'gnvim_diagnostics')) != v:t_list
+        let b:gnvim_diagnostics = luaeval('{'
+                    \ . '#vim.diagnostic.get(0, {severity = 1}),'
+                    \ . '#vim.diagnostic.get(0, {severity = 2})}')
+    endif
+    return b:gnvim_diagnostics
+endfunction
+
+" Shows the default segments: the mode, the file, the git branch, the
+" diagnostics counts and the cursor position.
+function! gnvim#statusline#update()
+    let l:name = expand('%:~:.')
+    let l:segments = [
+                \ {'text': get(s:modes, mode()[0], mode()), 'hl': 'ModeMsg'},
+                \ {'text': (empty(l:name) ? '[No Name]' : l:name)
+                \   . (&modified ? ' [+]' : '')},
+                \ ]
+
+    let l:branch = s:git_branch()
+    if !empty(l:branch)
+        call add(l:segments, {'text': l:branch, 'icon': 'vcs-branch-symbolic'})
+    endif
+
+    let [l:errors, l:warnings] = s:diagnostics()
+    if l:errors > 0
+        call add(l:segments, {'text': string(l:errors), 'align': 'right',
+                    \ 'icon': 'dialog-error-symbolic'})
+    endif
+    if l:warnings > 0
+        call add(l:segments, {'text': string(l:warnings), 'align': 'right',
+                    \ 'icon': 'dialog-warning-symbolic'})
+    endif
+
+    call add(l:segments, {'align': 'right',
+                \ 'text': printf('%d:%d  %d%%', line('.'), col('.'),
+                \   line('.') * 100 / line('$'))})
+
+    call gnvim#statusline#set(l:segments)
+endfunction
+
+" Timer of the pending throttled update.
+let s:timer = -1
+
+function! s:throttled_update(timer)
+    let s:timer = -1
+    call gnvim#statusline#update()
+endfunction
+
+" Updates the statusbar at most every 100ms, for frequent events like
+" cursor movement.
+function! s:throttle_update()
+    if s:timer == -1
+        let s:timer = timer_start(100, function('s:throttled_update'))
+    endif
+endfunction
+
+" Enables (or disables, with 0) the GUI statusbar with the default
+" segments, in place of nvim's statusline.
+function! gnvim#statusline#enable(...)
+    let l:enable = str2nr(get(a:000, 0, 1))
+
+    augroup GnvimStatusline
+        autocmd!
+        if l:enable
+            autocmd BufEnter,WinEnter,BufWritePost,InsertEnter,InsertLeave,
+                        \CmdlineEnter,CmdlineLeave *
+                        \ call gnvim#statusline#update()
+            autocmd CursorMoved,CursorMovedI * call s:throttle_update()
+            autocmd DirChanged * unlet! b:gnvim_git_branch
+            autocmd ColorScheme * let s:hl_cache = {}
+            if exists('##ModeChanged')
+                autocmd ModeChanged * call gnvim#statusline#update()
+            endif
+            if exists('##BufModifiedSet')
+                autocmd BufModifiedSet * call gnvim#statusline#update()
+            endif
+            if exists('##DiagnosticChanged')
+                autocmd DiagnosticChanged *
+                            \ call setbufvar(str2nr(expand('<abuf>')),
+                            \   'gnvim_diagnostics', v:null)
+                            \ | call gnvim#statusline#update()
+            endif
+        endif
+    augroup END
+
+    if l:enable
+        if !exists('s:laststatus')
+            let s:laststatus = &laststatus
+        endif
+        set laststatus=0
+        call gnvim#statusline#update()
+    else
+        if exists('s:laststatus')
+            let &laststatus = s:laststatus
+            unlet s:laststatus
+        endif
+        call gnvim#statusline#set([])
+    endif
+endfunction
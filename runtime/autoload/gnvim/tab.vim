@@ -0,0 +1,107 @@
+" Detached tab pages by their ids, with the windows that show them.
+let s:detached = {}
+let s:next_id = 1
+
+" Returns the view (see winsaveview()) of window `winid`.
+function! s:view(winid)
+    let l:view = {}
+    call win_execute(a:winid, 'let l:view = winsaveview()')
+    return l:view
+endfunction
+
+" Moves the windows of tab page `tabnr` (the current tab page by default)
+" to a window of their own, at the same positions. The windows become
+" external windows of the tab page that is current after the detach, so
+" they're hidden while another tab page is shown.
+function! gnvim#tab#detach(...)
+    let l:tabnr = str2nr(get(a:000, 0, tabpagenr()))
+    if tabpagenr('$') < 2 || l:tabnr < 1 || l:tabnr > tabpagenr('$')
+        " The tabline might have taken the tab out already.
+        redrawtabline
+        echoerr 'Gnvim detach-tab: invalid tab page: ' . l:tabnr
+        return
+    endif
+
+    let l:current = tabpagenr()
+    execute l:tabnr . 'tabnext'
+
+    let l:title = fnamemodify(bufname(), ':t')
+    let l:windows = []
+    for l:winid in gettabinfo(l:tabnr)[0].windows
+        if !empty(nvim_win_get_config(l:winid).relative)
+            continue
+        endif
+
+        let [l:row, l:col] = win_screenpos(l:winid)
+        call add(l:windows, {
+                    \ 'buf': winbufnr(l:winid),
+                    \ 'row': l:row - 1,
+                    \ 'col': l:col - 1,
+                    \ 'width': winwidth(l:winid),
+                    \ 'height': winheight(l:winid),
+                    \ 'view': s:view(l:winid),
+                    \ })
+    endfor
+
+    " The external windows are opened in the tab page that stays.
+    if l:current == l:tabnr
+        execute (l:tabnr % tabpagenr('$') + 1) . 'tabnext'
+    else
+        execute l:current . 'tabnext'
+    endif
+
+    let l:positions = []
+    for l:win in l:windows
+        let l:win.winid = nvim_open_win(l:win.buf, v:false, {
+                    \ 'external': v:true,
+                    \ 'width': l:win.width,
+                    \ 'height': l:win.height,
+                    \ })
+        call win_execute(l:win.winid,
+                    \ 'call winrestview(' . string(l:win.view) . ')')
+        call add(l:positions, [l:win.winid, l:win.row, l:win.col])
+    endfor
+
+    execute 'tabclose' l:tabnr
+
+    let l:id = s:next_id
+    let s:next_id += 1
+    let s:detached[l:id] = l:windows
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'DetachTab', l:id,
+                \ empty(l:title) ? '[No Name]' : l:title, l:positions)
+endfunction
+
+" Moves the windows of detached tab `id` back to a new tab page. The
+" windows are laid out in columns by their positions.
+function! gnvim#tab#attach(id)
+    let l:windows = filter(get(s:detached, a:id, []),
+                \ {_, w -> nvim_win_is_valid(w.winid)})
+    if has_key(s:detached, a:id)
+        call remove(s:detached, a:id)
+    endif
+    if empty(l:windows)
+        return
+    endif
+
+    call sort(l:windows, {a, b -> a.col == b.col
+                \ ? a.row - b.row : a.col - b.col})
+
+    let l:col = -1
+    for l:win in l:windows
+        if l:col == -1
+            tab split
+        elseif l:win.col != l:col
+            botright vsplit
+        else
+            belowright split
+        endif
+        let l:col = l:win.col
+
+        execute 'buffer' winbufnr(l:win.winid)
+        call winrestview(s:view(l:win.winid))
+    endfor
+
+    for l:win in l:windows
+        call nvim_win_close(l:win.winid, v:true)
+    endfor
+endfunction
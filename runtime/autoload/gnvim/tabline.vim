@@ -0,0 +1,59 @@
+" Lists the tab pages (`tabs`) or the listed buffers (`buffers`) in the
+" tabline.
+function! gnvim#tabline#set_mode(mode)
+    augroup GnvimTablineBuffers
+        autocmd!
+        if a:mode ==# 'buffers'
+            autocmd BufAdd,BufEnter,BufFilePost * call gnvim#tabline#update()
+            autocmd BufDelete,BufWipeout *
+                        \ call gnvim#tabline#update(str2nr(expand('<abuf>')))
+            if exists('##BufModifiedSet')
+                autocmd BufModifiedSet * call gnvim#tabline#update()
+            else
+                " Only the modified flag is shown, so the buffers aren't
+                " sent on every change.
+                autocmd BufWritePost,TextChanged,TextChangedI *
+                            \ call s:update_modified()
+            endif
+        endif
+    augroup END
+
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'TablineMode', a:mode)
+
+    if a:mode ==# 'buffers'
+        call gnvim#tabline#update()
+    else
+        " Nvim sends the tab pages again.
+        redrawtabline
+    endif
+endfunction
+
+" Updates the tabline when the current buffer's modified flag changed.
+function! s:update_modified()
+    if get(b:, 'gnvim_tabline_modified', -1) != &modified
+        let b:gnvim_tabline_modified = &modified
+        call gnvim#tabline#update()
+    endif
+endfunction
+
+" Sends the listed buffers to the tabline. Accepts the number of a buffer
+" that is left out, because it's being deleted.
+function! gnvim#tabline#update(...)
+    let l:deleted = get(a:000, 0, 0)
+    let l:buffers = []
+    for l:buf in nvim_list_bufs()
+        if !buflisted(l:buf) || l:buf == l:deleted
+            continue
+        endif
+
+        let l:name = bufname(l:buf)
+        call add(l:buffers, [
+                    \ l:buf,
+                    \ empty(l:name) ? '[No Name]' : fnamemodify(l:name, ':t'),
+                    \ getbufvar(l:buf, '&modified') ? 1 : 0,
+                    \ ])
+    endfor
+
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'TablineBuffers',
+                \ bufnr('%'), l:buffers)
+endfunction
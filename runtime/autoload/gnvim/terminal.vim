@@ -0,0 +1,21 @@
+function! gnvim#terminal#update_palette()
+    let colors = map(range(16), 'get(g:, "terminal_color_" . v:val, "")')
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'TerminalPalette', colors)
+endfunction
+
+" Passes the image sequences (kitty's graphics protocol and iTerm2's inline
+" images) of the program running in the current window's terminal to gnvim.
+" Called on |TermRequest|.
+function! gnvim#terminal#graphics(buf)
+    let l:event = get(v:, 'event', {})
+    let l:sequence = get(l:event, 'sequence', get(v:, 'termrequest', ''))
+    if l:sequence !~# '^\e\=\(_G\|]1337;File=\)'
+                \ || bufwinid(a:buf) != win_getid()
+        return
+    endif
+
+    let l:cursor = get(l:event, 'cursor', [line('.'), col('.') - 1])
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'TerminalGraphics',
+                \ gnvim#image#next_id(), l:sequence,
+                \ max([l:cursor[0] - line('w0'), 0]), l:cursor[1])
+endfunction
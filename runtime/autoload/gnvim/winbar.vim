@@ -0,0 +1,28 @@
+" Returns highlight group `name` as a map for gnvim.
+function! s:highlight(name)
+    return empty(a:name) ? {} : nvim_get_hl_by_name(a:name, v:true)
+endfunction
+
+" Shows `segments` in the winbar of window `winid` (the current window by
+" default), drawn by gnvim on top of the window's first row. Segments are
+" dicts with `text`, and optionally `hl` (a highlight group) and `click`
+" (a function called with the mouse button and the window when the segment
+" is clicked). Without segments, removes the winbar. Requires nvim 0.8 or
+" newer, since the row is reserved with 'winbar'.
+function! gnvim#winbar#set(segments, ...)
+    if !exists('+winbar')
+        echoerr 'Gnvim winbar: requires nvim 0.8 or newer'
+        return
+    endif
+
+    let l:winid = get(a:000, 0, win_getid())
+    call setwinvar(l:winid, '&winbar', empty(a:segments) ? '' : ' ')
+
+    let l:segments = map(copy(a:segments), {_, s -> {
+                \ 'text': get(s, 'text', ''),
+                \ 'hl': s:highlight(get(s, 'hl', '')),
+                \ 'click': get(s, 'click', ''),
+                \ }})
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SetWinbar', l:winid,
+                \ l:segments)
+endfunction
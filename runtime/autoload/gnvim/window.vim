@@ -0,0 +1,160 @@
+function! gnvim#window#set_background_opacity(opacity)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'SetBackgroundOpacity',
+                \ a:opacity)
+endfunction
+
+function! gnvim#window#set_device_scale(scale)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'SetDeviceScale',
+                \ a:scale)
+endfunction
+
+function! gnvim#window#follow_color_scheme(enable)
+    augroup GnvimFollowColorScheme
+        autocmd!
+        if a:enable
+            autocmd User GnvimColorScheme call s:set_background()
+        endif
+    augroup END
+
+    if a:enable
+        call s:set_background()
+    endif
+endfunction
+
+function! s:set_background()
+    let l:scheme = get(g:, 'gnvim_color_scheme', '')
+    if l:scheme != '' && &background != l:scheme
+        let &background = l:scheme
+    endif
+endfunction
+
+function! gnvim#window#set_background_image(path, ...)
+    let l:opts = get(a:, 1, {})
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'SetBackgroundImage',
+                \ empty(a:path) ? '' : expand(a:path),
+                \ get(l:opts, 'blur', 0),
+                \ get(l:opts, 'dim', 0.0))
+endfunction
+
+function! gnvim#window#set_custom_css(path)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'SetCustomCss',
+                \ empty(a:path) ? '' : expand(a:path))
+endfunction
+
+function! gnvim#window#enable_scrollbar(enable)
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'EnableScrollbar', a:enable)
+endfunction
+
+function! gnvim#window#enable_minimap(enable)
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'EnableMinimap', a:enable)
+endfunction
+
+function! gnvim#window#enable_float_drag(enable)
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'EnableFloatDrag', a:enable)
+endfunction
+
+function! gnvim#window#set_float_style(opts)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'SetFloatStyle',
+                \ get(a:opts, 'radius', 0),
+                \ get(a:opts, 'shadow', 0),
+                \ get(a:opts, 'border', 0),
+                \ get(a:opts, 'win', 0))
+endfunction
+
+function! gnvim#window#set_animations(opts)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'SetAnimations',
+                \ get(a:opts, 'enable', 1),
+                \ get(a:opts, 'float_open', 150),
+                \ get(a:opts, 'window_close', 120))
+endfunction
+
+function! gnvim#window#fullscreen(...)
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SetFullscreen', get(a:, 1, -1))
+endfunction
+
+function! gnvim#window#maximize(...)
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SetMaximized', get(a:, 1, -1))
+endfunction
+
+function! gnvim#window#keep_above(...)
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SetKeepAbove', get(a:, 1, -1))
+endfunction
+
+function! gnvim#window#set_decorations(decorations)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'SetDecorations',
+                \ a:decorations)
+endfunction
+
+function! gnvim#window#set_indent_guides(opts)
+    let l:enable = get(a:opts, 'enable', 1)
+
+    " The windows' indent info is fetched again only when their buffers or
+    " the options it depends on change.
+    augroup gnvim_indent_guides
+        autocmd!
+        if l:enable == 1
+            autocmd BufWinEnter * call s:indent_info_changed()
+            autocmd OptionSet
+                        \ shiftwidth,tabstop,number,relativenumber,numberwidth,signcolumn,foldcolumn
+                        \ call s:indent_info_changed()
+        endif
+    augroup END
+
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'SetIndentGuides',
+                \ l:enable,
+                \ get(a:opts, 'color', ''),
+                \ get(a:opts, 'opacity', 0.2))
+endfunction
+
+function! s:indent_info_changed()
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'IndentInfoChanged')
+endfunction
+
+function! gnvim#window#indent_info(win)
+    let l:info = getwininfo(a:win)
+    if empty(l:info)
+        return [0, 0]
+    endif
+
+    let l:buf = l:info[0].bufnr
+    let l:width = getbufvar(l:buf, '&shiftwidth')
+    if l:width == 0
+        let l:width = getbufvar(l:buf, '&tabstop')
+    endif
+    return [l:info[0].textoff, l:width]
+endfunction
+
+" Writes the modified buffers when gnvim loses the focus.
+function! gnvim#window#enable_autosave(enable)
+    augroup gnvim_autosave
+        autocmd!
+        if a:enable == 1
+            " Nested, so that e.g. formatters run on BufWritePre.
+            autocmd FocusLost * nested silent! wall
+        endif
+    augroup END
+endfunction
@@ -6,5 +6,158 @@ let g:gnvim_runtime_loaded = 1
 
 inoremap <expr> <C-s> gnvim#popupmenu#toggle_details()
 
+" Colors of the popupmenu's kind icons. Colorschemes clear these, so set
+" them again when the colorscheme changes.
+function! s:pmenu_kind_highlights()
+    hi default link GnvimPmenuKindClass Type
+    hi default link GnvimPmenuKindColor Constant
+    hi default link GnvimPmenuKindConstant Constant
+    hi default link GnvimPmenuKindConstructor Function
+    hi default link GnvimPmenuKindEnum Type
+    hi default link GnvimPmenuKindEnumMember Constant
+    hi default link GnvimPmenuKindEvent Special
+    hi default link GnvimPmenuKindFunction Function
+    hi default link GnvimPmenuKindFile Directory
+    hi default link GnvimPmenuKindFolder Directory
+    hi default link GnvimPmenuKindField Identifier
+    hi default link GnvimPmenuKindInterface Type
+    hi default link GnvimPmenuKindKeyword Keyword
+    hi default link GnvimPmenuKindMethod Function
+    hi default link GnvimPmenuKindModule Include
+    hi default link GnvimPmenuKindOperator Operator
+    hi default link GnvimPmenuKindProperty Identifier
+    hi default link GnvimPmenuKindReference Special
+    hi default link GnvimPmenuKindSnippet Special
+    hi default link GnvimPmenuKindStruct Structure
+    hi default link GnvimPmenuKindTypeParameter Type
+    hi default link GnvimPmenuKindUnit Number
+    hi default link GnvimPmenuKindValue Constant
+    hi default link GnvimPmenuKindVariable Identifier
+endfunction
+
+call s:pmenu_kind_highlights()
+
+augroup gnvim_pmenu_kind
+    autocmd!
+    autocmd ColorScheme * call s:pmenu_kind_highlights()
+augroup END
+
+" Colorschemes set the terminal palette (g:terminal_color_{n}).
+augroup gnvim_terminal_palette
+    autocmd!
+    autocmd VimEnter,ColorScheme * call gnvim#terminal#update_palette()
+augroup END
+
+" Highlight groups of the widgets that nvim doesn't send to the UI.
+augroup gnvim_highlights
+    autocmd!
+    autocmd VimEnter,ColorScheme * call gnvim#highlight#update()
+augroup END
+
+" Titles of the external windows, which are named after their buffers.
+augroup gnvim_external_windows
+    autocmd!
+    autocmd BufWinEnter,BufFilePost *
+                \ call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'WindowBufferChanged')
+augroup END
+
+" Images of the programs running in terminals. Requires nvim 0.10 or newer.
+if exists('##TermRequest')
+    augroup gnvim_terminal_graphics
+        autocmd!
+        autocmd TermRequest * call gnvim#terminal#graphics(str2nr(expand('<abuf>')))
+    augroup END
+endif
+
 command! -nargs=1 GnvimCursorEnableAnimations
             \ call gnvim#cursor#enable_animations(<q-args>)
+
+command! -nargs=0 GnvimFontPicker call gnvim#font#picker()
+
+command! -nargs=0 GnvimCommandPalette call gnvim#command_palette#show()
+
+function! s:notify(title, ...)
+    call gnvim#notify#send(a:title, join(a:000, ' '))
+endfunction
+
+" Subcommands of :Gnvim.
+let s:commands = {
+            \ 'copy': function('gnvim#copy_mode#enter'),
+            \ 'decorations': function('gnvim#window#set_decorations'),
+            \ 'detach-tab': function('gnvim#tab#detach'),
+            \ 'export': function('gnvim#export#export'),
+            \ 'finder': function('gnvim#finder#show'),
+            \ 'fullscreen': function('gnvim#window#fullscreen'),
+            \ 'image': function('gnvim#image#command'),
+            \ 'logs': function('gnvim#debug#show_logs'),
+            \ 'maximize': function('gnvim#window#maximize'),
+            \ 'notify': function('s:notify'),
+            \ 'ontop': function('gnvim#window#keep_above'),
+            \ 'open': function('gnvim#dialog#open'),
+            \ 'palette': function('gnvim#command_palette#show'),
+            \ 'preview': function('gnvim#preview#toggle'),
+            \ 'print': function('gnvim#print#print'),
+            \ 'saveas': function('gnvim#dialog#save_as'),
+            \ 'screenshot': function('gnvim#screenshot#take'),
+            \ 'search': function('gnvim#search#show'),
+            \ 'statusline': function('gnvim#statusline#enable'),
+            \ 'tabline': function('gnvim#tabline#set_mode'),
+            \ 'tree': function('gnvim#file_tree#toggle'),
+            \ }
+
+function! s:complete(arglead, cmdline, cursorpos)
+    return filter(sort(keys(s:commands)), 'v:val =~# "^" . a:arglead')
+endfunction
+
+function! s:gnvim(cmd, ...)
+    if !has_key(s:commands, a:cmd)
+        echoerr 'Gnvim: unknown command: ' . a:cmd
+        return
+    endif
+
+    call call(s:commands[a:cmd], a:000)
+endfunction
+
+command! -nargs=+ -complete=customlist,s:complete Gnvim call s:gnvim(<f-args>)
+
+" Events for which a desktop notification is sent when gnvim's window
+" doesn't have the focus.
+let g:gnvim_notify_events = get(g:, 'gnvim_notify_events', [])
+
+function! s:notify_make(list)
+    if index(g:gnvim_notify_events, 'make') == -1
+        return
+    endif
+
+    let l:count = len(filter(a:list, 'v:val.valid'))
+    call gnvim#notify#send('make finished',
+                \ l:count . (l:count == 1 ? ' error' : ' errors'), 1)
+endfunction
+
+function! s:notify_job(status)
+    if index(g:gnvim_notify_events, 'job') == -1
+        return
+    endif
+
+    call gnvim#notify#send('Job finished',
+                \ expand('<afile>') . ' exited with status ' . a:status, 1)
+endfunction
+
+augroup gnvim_notify
+    autocmd!
+    autocmd QuickFixCmdPost make call s:notify_make(getqflist())
+    autocmd QuickFixCmdPost lmake call s:notify_make(getloclist(0))
+    autocmd TermClose * call s:notify_job(v:event.status)
+augroup END
+
+" Save the session while idle, so that it can be restored if nvim crashes.
+" It's only saved when the windows or buffers have changed.
+augroup gnvim_session
+    autocmd!
+    autocmd CursorHold * call gnvim#session#save()
+augroup END
+
+command! -nargs=? -complete=file GnvimBackgroundImage
+            \ call gnvim#window#set_background_image(<q-args>)
+
+anoremenu <silent> Edit.Select\ Font\.\.\. :call gnvim#font#picker()<CR>
@@ -0,0 +1,295 @@
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt::{self, Write as _};
+use std::panic::PanicHookInfo;
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+
+use gio::prelude::*;
+use gtk::prelude::*;
+
+use log::error;
+
+/// How many of the latest redraw events are kept for crash reports.
+const RECENT_EVENTS: usize = 200;
+/// How much of each event is kept, in bytes. The rest (e.g. the cells of
+/// a long grid line) isn't even formatted, since events are recorded on
+/// every redraw.
+const EVENT_LEN: usize = 160;
+
+thread_local! {
+    /// Events of the running UI, if any.
+    static EVENTS: RefCell<Weak<RefCell<VecDeque<String>>>> =
+        const { RefCell::new(Weak::new()) };
+    /// Version of the running nvim.
+    static NVIM_VERSION: RefCell<Option<String>> =
+        const { RefCell::new(None) };
+}
+
+/// The latest redraw events, which are included in crash reports.
+#[derive(Clone, Default)]
+pub struct RecentEvents {
+    events: Rc<RefCell<VecDeque<String>>>,
+}
+
+impl RecentEvents {
+    /// Creates the event buffer of the UI, which is the one that ends up in
+    /// the crash reports.
+    pub fn new() -> Self {
+        let events = RecentEvents::default();
+        EVENTS.with(|e| e.replace(Rc::downgrade(&events.events)));
+        events
+    }
+
+    /// Records the start of `event`'s debug formatting (see `EVENT_LEN`).
+    pub fn push<T: fmt::Debug>(&self, event: &T) {
+        let mut events = self.events.borrow_mut();
+        // Reuse the oldest event's buffer.
+        let mut buf = if events.len() >= RECENT_EVENTS {
+            events.pop_front().unwrap_or_default()
+        } else {
+            String::new()
+        };
+        buf.clear();
+
+        let mut writer = Truncated {
+            buf: &mut buf,
+            left: EVENT_LEN,
+        };
+        if write!(writer, "{:?}", event).is_err() {
+            buf.push_str("...");
+        }
+        events.push_back(buf);
+    }
+}
+
+/// Writer that fails once `left` bytes have been written, which stops the
+/// formatting.
+struct Truncated<'a> {
+    buf: &'a mut String,
+    left: usize,
+}
+
+impl fmt::Write for Truncated<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if s.len() <= self.left {
+            self.buf.push_str(s);
+            self.left -= s.len();
+            return Ok(());
+        }
+
+        let mut end = self.left;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.buf.push_str(&s[..end]);
+        self.left = 0;
+        Err(fmt::Error)
+    }
+}
+
+/// Sets the nvim version for crash reports.
+pub fn set_nvim_version(version: String) {
+    NVIM_VERSION.with(|v| v.replace(Some(version)));
+}
+
+/// Installs a panic hook that writes a crash report and shows it to the
+/// user, instead of gnvim just vanishing. The report is shown by another
+/// gnvim process (see `show_dialog`), since the panicking one can't run gtk
+/// anymore.
+pub fn install_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        let report = report(info, &Backtrace::force_capture());
+        let path = match write_report(&report) {
+            Ok(path) => path,
+            Err(err) => {
+                error!("Failed to write crash report: {}", err);
+                return;
+            }
+        };
+
+        error!("Crash report written to {}", path.display());
+
+        if let Err(err) = std::env::current_exe().and_then(|exe| {
+            std::process::Command::new(exe)
+                .arg("--crash-report")
+                .arg(&path)
+                .spawn()
+        }) {
+            error!("Failed to show crash report: {}", err);
+        }
+    }));
+}
+
+/// Crash report of the panic `info`.
+fn report(info: &PanicHookInfo, backtrace: &Backtrace) -> String {
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_default();
+    let location = info
+        .location()
+        .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()))
+        .unwrap_or_default();
+
+    let nvim_version =
+        NVIM_VERSION.try_with(|v| v.borrow().clone()).ok().flatten();
+    let events = EVENTS
+        .try_with(|e| {
+            e.borrow().upgrade().and_then(|events| {
+                events
+                    .try_borrow()
+                    .ok()
+                    .map(|e| e.iter().cloned().collect())
+            })
+        })
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+
+    format_report(
+        &message,
+        &location,
+        &backtrace.to_string(),
+        nvim_version.as_deref(),
+        &events,
+    )
+}
+
+fn format_report(
+    message: &str,
+    location: &str,
+    backtrace: &str,
+    nvim_version: Option<&str>,
+    events: &[String],
+) -> String {
+    let mut report = String::new();
+
+    let _ = writeln!(report, "gnvim {} crashed", crate::VERSION);
+    let _ = writeln!(report, "panic: {}", message);
+    let _ = writeln!(report, "at: {}", location);
+    let _ =
+        writeln!(report, "nvim: {}", nvim_version.unwrap_or("(not started)"));
+    let _ = writeln!(
+        report,
+        "gtk: {}.{}.{}",
+        gtk::get_major_version(),
+        gtk::get_minor_version(),
+        gtk::get_micro_version()
+    );
+
+    let _ = writeln!(report, "\nbacktrace:\n{}", backtrace);
+
+    let _ = writeln!(report, "\nlatest redraw events ({}):", events.len());
+    for event in events {
+        let _ = writeln!(report, "{}", event);
+    }
+
+    report
+}
+
+/// Writes `report` to a new file in the cache directory.
+fn write_report(report: &str) -> std::io::Result<PathBuf> {
+    let dir = glib::get_user_cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("gnvim");
+    std::fs::create_dir_all(&dir)?;
+
+    let time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let path = dir.join(format!("crash-{}.txt", time));
+    std::fs::write(&path, report)?;
+
+    Ok(path)
+}
+
+/// Tells the user that gnvim crashed, and offers to open the report at
+/// `path`. Run by the process started with `--crash-report`.
+pub fn show_dialog(path: &Path) {
+    let dialog = gtk::MessageDialog::new(
+        None::<&gtk::Window>,
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Error,
+        gtk::ButtonsType::None,
+        "Gnvim crashed",
+    );
+    dialog.set_property_secondary_text(Some(&format!(
+        "A crash report was written to {}. Please attach it to a bug \
+         report.",
+        path.display()
+    )));
+    dialog.add_buttons(&[
+        ("Close", gtk::ResponseType::Close),
+        ("Open report", gtk::ResponseType::Accept),
+    ]);
+    dialog.set_default_response(gtk::ResponseType::Accept);
+
+    if dialog.run() == gtk::ResponseType::Accept {
+        let uri = gio::File::new_for_path(path).get_uri();
+        if let Err(err) = gio::AppInfo::launch_default_for_uri(
+            &uri,
+            None::<&gio::AppLaunchContext>,
+        ) {
+            error!("Failed to open crash report: {}", err);
+        }
+    }
+
+    dialog.close();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_events() {
+        let events = RecentEvents::default();
+        for i in 0..RECENT_EVENTS + 10 {
+            events.push(&i);
+        }
+
+        let events = events.events.borrow();
+        assert_eq!(events.len(), RECENT_EVENTS);
+        assert_eq!(events.front().map(String::as_str), Some("10"));
+        assert_eq!(events.back().map(String::as_str), Some("209"));
+    }
+
+    #[test]
+    fn test_recent_events_truncated() {
+        let events = RecentEvents::default();
+        events.push(&vec!["ä"; EVENT_LEN]);
+
+        let events = events.events.borrow();
+        let event = events.front().unwrap();
+        assert!(event.starts_with("[\"ä\", \"ä\""));
+        assert!(event.ends_with("..."));
+        assert!(event.len() <= EVENT_LEN + 3);
+    }
+
+    #[test]
+    fn test_format_report() {
+        let report = format_report(
+            "grid not found",
+            "src/ui/state.rs:1:2",
+            "0: main",
+            Some("0.5.0"),
+            &["GridLine".to_string(), "Flush".to_string()],
+        );
+
+        assert!(report.contains("panic: grid not found\n"));
+        assert!(report.contains("at: src/ui/state.rs:1:2\n"));
+        assert!(report.contains("nvim: 0.5.0\n"));
+        assert!(report.contains("\nbacktrace:\n0: main\n"));
+        assert!(
+            report.contains("\nlatest redraw events (2):\nGridLine\nFlush\n")
+        );
+    }
+}
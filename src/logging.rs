@@ -0,0 +1,236 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// How many of the latest log lines are kept for the log viewer.
+const RECENT_LINES: usize = 5000;
+
+/// Path of gnvim's root module, in the log targets.
+const GNVIM_PREFIX: &str = "gnvim::";
+
+/// Latest log lines, and the number of lines logged before them.
+static RECENT: Mutex<(VecDeque<String>, usize)> =
+    Mutex::new((VecDeque::new(), 0));
+
+/// Log levels, per module.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogSpec {
+    /// Level of the modules that aren't in `modules`.
+    default: LevelFilter,
+    /// Levels of modules (and their submodules), by the module's path.
+    /// Gnvim's modules are without the `gnvim::` prefix.
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Default for LogSpec {
+    fn default() -> Self {
+        LogSpec {
+            default: LevelFilter::Error,
+            modules: vec![],
+        }
+    }
+}
+
+impl LogSpec {
+    /// Parses a comma separated list of levels (e.g. `warn`) and module
+    /// levels (e.g. `ui::grid=trace`). The modules are gnvim's, unless
+    /// they're of some other crate (e.g. `nvim_rs=debug`).
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut ret = LogSpec::default();
+
+        for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (module, level) = match part.find('=') {
+                Some(i) => (Some(&part[..i]), &part[i + 1..]),
+                None => (None, part),
+            };
+
+            let level = level
+                .parse::<LevelFilter>()
+                .map_err(|_| format!("invalid log level '{}'", level))?;
+
+            match module {
+                Some(module) => ret.modules.push((module_path(module), level)),
+                None => ret.default = level,
+            }
+        }
+
+        // Longest (most specific) paths first.
+        ret.modules.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+
+        Ok(ret)
+    }
+
+    /// Level of the logs of `target` (a module path).
+    fn level(&self, target: &str) -> LevelFilter {
+        let relative = target.strip_prefix(GNVIM_PREFIX);
+        self.modules
+            .iter()
+            .find(|(module, _)| {
+                in_module(target, module)
+                    || relative.map_or(false, |t| in_module(t, module))
+            })
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, std::cmp::max)
+    }
+}
+
+/// Path of `module`, without the `gnvim::` prefix. Paths that aren't of
+/// another crate are resolved against gnvim's root module when matching
+/// the log targets.
+fn module_path(module: &str) -> String {
+    let module = module.trim().trim_start_matches("::");
+    module
+        .strip_prefix(GNVIM_PREFIX)
+        .unwrap_or(module)
+        .to_string()
+}
+
+/// If `target` is `module`, or one of its submodules.
+fn in_module(target: &str, module: &str) -> bool {
+    target == module
+        || (target.starts_with(module)
+            && target[module.len()..].starts_with("::"))
+}
+
+/// Writes the logs to stderr and, optionally, to a file. The latest lines
+/// are also kept for the log viewer (see `recent`).
+struct Logger {
+    spec: LogSpec,
+    file: Option<Mutex<File>>,
+    start: Instant,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.spec.level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!(
+            "[{:>9.3} {:<5} {}] {}",
+            self.start.elapsed().as_secs_f64(),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        eprintln!("{}", line);
+
+        if let Some(ref file) = self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = writeln!(file, "{}", line);
+            }
+        }
+
+        if let Ok(mut recent) = RECENT.lock() {
+            let (lines, dropped) = &mut *recent;
+            if lines.len() >= RECENT_LINES {
+                lines.pop_front();
+                *dropped += 1;
+            }
+            lines.push_back(line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(ref file) = self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+/// Starts logging with the levels of `spec`, also to `file` if given.
+pub fn init(spec: LogSpec, file: Option<&Path>) -> Result<(), String> {
+    let file = match file {
+        Some(path) => Some(Mutex::new(File::create(path).map_err(|err| {
+            format!("failed to open log file '{}': {}", path.display(), err)
+        })?)),
+        None => None,
+    };
+
+    let max_level = spec.max_level();
+    let logger = Box::new(Logger {
+        spec,
+        file,
+        start: Instant::now(),
+    });
+
+    log::set_logger(Box::leak(logger)).map_err(|err| err.to_string())?;
+    log::set_max_level(max_level);
+
+    Ok(())
+}
+
+/// The log lines after the first `from` lines, and the number of lines
+/// logged so far. Only the latest lines are kept.
+pub fn recent(from: usize) -> (Vec<String>, usize) {
+    let recent = match RECENT.lock() {
+        Ok(recent) => recent,
+        Err(_) => return (vec![], from),
+    };
+
+    let (lines, dropped) = &*recent;
+    let total = dropped + lines.len();
+    let skip = from.saturating_sub(*dropped);
+
+    (lines.iter().skip(skip).cloned().collect(), total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_spec_parse() {
+        let spec =
+            LogSpec::parse("info, ui::grid=trace,nvim_rs=debug").unwrap();
+        assert_eq!(spec.default, LevelFilter::Info);
+        assert_eq!(
+            spec.modules,
+            vec![
+                ("ui::grid".to_string(), LevelFilter::Trace),
+                ("nvim_rs".to_string(), LevelFilter::Debug),
+            ]
+        );
+        assert_eq!(spec.max_level(), LevelFilter::Trace);
+
+        assert_eq!(LogSpec::parse("").unwrap(), LogSpec::default());
+        assert!(LogSpec::parse("ui=loud").is_err());
+    }
+
+    #[test]
+    fn test_log_spec_level() {
+        let spec = LogSpec::parse("warn,ui=info,ui::grid=trace").unwrap();
+        assert_eq!(spec.level("gnvim::ui::grid::row"), LevelFilter::Trace);
+        assert_eq!(spec.level("gnvim::ui::grid"), LevelFilter::Trace);
+        assert_eq!(spec.level("gnvim::ui::gridx"), LevelFilter::Info);
+        assert_eq!(spec.level("gnvim::ui::state"), LevelFilter::Info);
+        assert_eq!(spec.level("gnvim::nvim_gio"), LevelFilter::Warn);
+        assert_eq!(spec.level("glib"), LevelFilter::Warn);
+
+        let spec =
+            LogSpec::parse("crash=debug,gnvim::nvim_gio=info,glib=trace")
+                .unwrap();
+        assert_eq!(spec.level("gnvim::crash"), LevelFilter::Debug);
+        assert_eq!(spec.level("gnvim::nvim_gio::watchdog"), LevelFilter::Info);
+        assert_eq!(spec.level("glib::source"), LevelFilter::Trace);
+        assert_eq!(spec.level("gnvim::ui"), LevelFilter::Error);
+    }
+}
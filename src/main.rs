@@ -1,38 +1,42 @@
 #![cfg_attr(feature = "unstable", feature(test))]
 
-#[cfg(feature = "libwebkit2gtk")]
-#[macro_use]
-extern crate lazy_static;
-#[cfg(feature = "libwebkit2gtk")]
-extern crate ammonia;
-#[cfg(feature = "libwebkit2gtk")]
-extern crate pulldown_cmark;
 extern crate structopt;
-#[cfg(feature = "libwebkit2gtk")]
 extern crate syntect;
 
 extern crate cairo;
 extern crate gdk;
 extern crate gdk_pixbuf;
 extern crate gio;
+#[cfg(feature = "opengl")]
+extern crate gl;
 extern crate glib;
 extern crate gtk;
 extern crate log;
 extern crate pango;
 extern crate pangocairo;
-#[cfg(feature = "libwebkit2gtk")]
-extern crate webkit2gtk;
+#[cfg(feature = "opengl")]
+extern crate shared_library;
+
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 use gio::prelude::*;
+use gtk::prelude::*;
 
 use log::error;
 
 use structopt::{clap, StructOpt};
 
+use crate::nvim_bridge::recording;
+
 include!(concat!(env!("OUT_DIR"), "/gnvim_version.rs"));
 
+mod crash;
+mod logging;
 mod nvim_bridge;
 mod nvim_gio;
+mod runtime_path;
 mod thread_guard;
 mod ui;
 
@@ -62,17 +66,32 @@ struct Options {
     #[structopt(long = "print-nvim-cmd")]
     print_nvim_cmd: bool,
 
+    /// Opens the files in the already running gnvim (started with this
+    /// flag too), instead of starting a new one.
+    #[structopt(long = "single-instance")]
+    single_instance: bool,
+
+    /// Prints the version of the neovim that was started.
+    #[structopt(long = "print-nvim-version")]
+    print_nvim_version: bool,
+
     /// Path to neovim binary.
     #[structopt(long = "nvim", name = "BIN", default_value = "nvim")]
     nvim_path: String,
 
-    /// Path for gnvim runtime files.
+    /// Working directory for neovim. Files to open are relative to it.
+    #[structopt(long = "cwd", name = "DIR", parse(from_os_str))]
+    cwd: Option<PathBuf>,
+
+    /// Path for gnvim runtime files. By default, they're looked for next
+    /// to the executable, in the XDG data directories and in the install
+    /// prefix.
     #[structopt(
         long = "gnvim-rtp",
-        default_value = "/usr/local/share/gnvim/runtime",
-        env = "GNVIM_RUNTIME_PATH"
+        env = "GNVIM_RUNTIME_PATH",
+        parse(from_os_str)
     )]
-    gnvim_rtp: String,
+    gnvim_rtp: Option<PathBuf>,
 
     /// Files to open.
     #[structopt(value_name = "FILES")]
@@ -94,6 +113,20 @@ struct Options {
     #[structopt(long = "disable-ext-tabline")]
     disable_ext_tabline: bool,
 
+    /// Enables externalized messages, which are shown as notifications
+    #[structopt(long = "ext-messages")]
+    ext_messages: bool,
+
+    /// Enables externalized terminal colors, where default colors without
+    /// an rgb color come from the terminal palette
+    #[structopt(long = "ext-termcolors")]
+    ext_termcolors: bool,
+
+    /// Exports the D-Bus control interface (org.gnvim.Control), so that
+    /// other programs on the session bus can open files in gnvim
+    #[structopt(long = "dbus")]
+    dbus: bool,
+
     /// Enables dark theme
     #[structopt(long = "prefer-dark-theme")]
     prefer_dark_theme: bool,
@@ -101,11 +134,92 @@ struct Options {
     /// Geometry of the window in widthxheight form
     #[structopt(long = "geometry", parse(try_from_str = parse_geometry), default_value = "1280x720")]
     geometry: (i32, i32),
+
+    /// Starts with the window in fullscreen
+    #[structopt(long = "fullscreen")]
+    fullscreen: bool,
+
+    /// Starts with the window maximized
+    #[structopt(long = "maximized")]
+    maximized: bool,
+
+    /// Renderer used to draw the grids (cairo or gl). The gl renderer
+    /// requires gnvim to be built with the `opengl` feature.
+    #[structopt(long = "renderer", default_value = "cairo")]
+    renderer: ui::RendererKind,
+
+    /// Number of threads that rasterize text off the main thread. With 0,
+    /// text is rasterized on the main thread.
+    #[structopt(long = "render-threads", default_value = "0")]
+    render_threads: usize,
+
+    /// Opacity of the window's background (from 0.0 to 1.0). Requires a
+    /// compositing window manager.
+    #[structopt(long = "background-opacity", default_value = "1.0")]
+    background_opacity: f64,
+
+    /// CSS file for restyling gnvim's widgets (see `:h gnvim-css`)
+    #[structopt(long = "custom-css", name = "FILE", parse(from_os_str))]
+    custom_css: Option<PathBuf>,
+
+    /// File to write the logs to, in addition to stderr.
+    #[structopt(long = "log-file", name = "LOG_FILE", parse(from_os_str))]
+    log_file: Option<PathBuf>,
+
+    /// Log levels, e.g. `info` or `warn,ui::grid=trace,nvim_rs=debug`.
+    /// Defaults to `error`.
+    #[structopt(
+        long = "log-level",
+        name = "LEVELS",
+        env = "RUST_LOG",
+        parse(try_from_str = logging::LogSpec::parse),
+        default_value = ""
+    )]
+    log_level: logging::LogSpec,
+
+    /// Records nvim's redraw events to a file, for reproducing rendering
+    /// bugs with `--replay`.
+    #[structopt(long = "record", name = "RECORD_FILE", parse(from_os_str))]
+    record: Option<PathBuf>,
+
+    /// Replays redraw events recorded with `--record`, instead of showing
+    /// the ones of the started nvim.
+    #[structopt(long = "replay", name = "REPLAY_FILE", parse(from_os_str))]
+    replay: Option<PathBuf>,
+
+    /// Speed of the replay, relative to the recording. With 0, the events
+    /// are replayed as fast as possible.
+    #[structopt(long = "replay-speed", default_value = "1.0")]
+    replay_speed: f64,
+
+    /// Replays the events without the UI, and prints how many there were
+    /// and how long parsing them took.
+    #[structopt(long = "replay-headless", requires = "REPLAY_FILE")]
+    replay_headless: bool,
+
+    /// Runs without showing the window, and writes images and the text of
+    /// the grids to OFFSCREEN_DIR when nvim exits. For integration tests.
+    #[structopt(
+        long = "offscreen",
+        name = "OFFSCREEN_DIR",
+        parse(from_os_str)
+    )]
+    offscreen: Option<PathBuf>,
+
+    /// Shows the crash report at REPORT_FILE. Used by the panic hook.
+    #[structopt(
+        long = "crash-report",
+        name = "REPORT_FILE",
+        parse(from_os_str),
+        hidden = true
+    )]
+    crash_report: Option<PathBuf>,
 }
 
 enum Error {
     Start(nvim_gio::Error),
-    Call(Box<nvim_rs::error::CallError>),
+    Call(nvim_gio::RequestError<Box<nvim_rs::error::CallError>>),
+    Replay(String),
 }
 
 impl std::fmt::Display for Error {
@@ -113,6 +227,7 @@ impl std::fmt::Display for Error {
         match self {
             Error::Start(e) => write!(fmt, "Failed to start nvim: {}", e),
             Error::Call(e) => write!(fmt, "Call to nvim failed: {}", e),
+            Error::Replay(e) => write!(fmt, "Failed to replay: {}", e),
         }
     }
 }
@@ -123,17 +238,107 @@ impl From<nvim_gio::Error> for Error {
     }
 }
 
-impl From<Box<nvim_rs::error::CallError>> for Error {
-    fn from(arg: Box<nvim_rs::error::CallError>) -> Self {
+impl From<nvim_gio::RequestError<Box<nvim_rs::error::CallError>>> for Error {
+    fn from(
+        arg: nvim_gio::RequestError<Box<nvim_rs::error::CallError>>,
+    ) -> Self {
         Error::Call(arg)
     }
 }
 
-async fn build(app: &gtk::Application, opts: &Options) -> Result<(), Error> {
+/// Formats the version from nvim's api metadata (see `:h api-metadata`).
+fn nvim_version(metadata: &nvim_rs::Value) -> String {
+    let get = |key: &str| {
+        metadata
+            .as_map()
+            .and_then(|map| {
+                map.iter().find(|(k, _)| k.as_str() == Some("version"))
+            })
+            .and_then(|(_, version)| version.as_map())
+            .and_then(|version| {
+                version.iter().find(|(k, _)| k.as_str() == Some(key))
+            })
+            .map(|(_, v)| v.clone())
+    };
+
+    let num = |key| get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+    let prerelease = get("prerelease").and_then(|v| v.as_bool());
+
+    format!(
+        "v{}.{}.{}{}",
+        num("major"),
+        num("minor"),
+        num("patch"),
+        if prerelease == Some(true) { "-dev" } else { "" }
+    )
+}
+
+/// File where nvim's session is saved, so that it can be restored if nvim
+/// crashes. The file is sourced when the session is restored, so it's kept
+/// in a directory that only the user can access.
+fn session_file() -> PathBuf {
+    // Unlike the temp directory, both of these belong to the user.
+    let dir = glib::get_user_runtime_dir()
+        .or_else(glib::get_user_cache_dir)
+        .unwrap_or_default()
+        .join("gnvim");
+    if let Err(err) = std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&dir)
+    {
+        error!("Failed to create {}: {}", dir.display(), err);
+    }
+
+    dir.join(format!("session-{}.vim", std::process::id()))
+}
+
+/// Starts nvim and the UI. With `session`, the session is restored instead
+/// of opening the files given in `opts`.
+fn start(app: &gtk::Application, opts: Rc<Options>, session: Option<PathBuf>) {
+    let c = glib::MainContext::default();
+    c.block_on(async move {
+        if let Err(err) = build(app, opts, session).await {
+            error!("Failed to build UI: {}", err);
+        }
+    });
+}
+
+async fn build(
+    app: &gtk::Application,
+    opts: Rc<Options>,
+    session: Option<PathBuf>,
+) -> Result<(), Error> {
     let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
-    let bridge = nvim_bridge::NvimBridge::new(tx.clone());
+    let mut bridge = nvim_bridge::NvimBridge::new(tx.clone());
+
+    if let Some(ref path) = opts.record {
+        match recording::Recorder::new(path) {
+            Ok(recorder) => bridge = bridge.with_recorder(recorder),
+            Err(err) => error!(
+                "Failed to start recording to '{}': {}",
+                path.display(),
+                err
+            ),
+        }
+    }
 
-    let rtp = format!("let &rtp.=',{}'", opts.gnvim_rtp);
+    // When replaying, the UI shows the recorded events instead of nvim's.
+    let replay = opts
+        .replay
+        .as_deref()
+        .map(recording::read)
+        .transpose()
+        .map_err(Error::Replay)?;
+    let replay_tx = tx.clone();
+
+    let rtp = runtime_path::rtp_cmd(
+        &opts.gnvim_rtp.clone().unwrap_or_else(runtime_path::find),
+    );
+    let session_var = format!(
+        "let g:gnvim_session_file='{}'",
+        session_file().to_string_lossy().replace('\'', "''")
+    );
     let mut args: Vec<&str> = vec![
         &opts.nvim_path,
         "--embed",
@@ -143,6 +348,8 @@ async fn build(app: &gtk::Application, opts: &Options) -> Result<(), Error> {
         "set termguicolors",
         "--cmd",
         &rtp,
+        "--cmd",
+        &session_var,
     ];
 
     // Pass arguments from cli to nvim.
@@ -150,9 +357,16 @@ async fn build(app: &gtk::Application, opts: &Options) -> Result<(), Error> {
         args.push(arg);
     }
 
-    // Open files "normally" through nvim.
-    for file in opts.open_files.iter() {
-        args.push(file);
+    let session = session.map(|s| s.to_string_lossy().into_owned());
+    if let Some(ref session) = session {
+        // The session has the files that were open.
+        args.push("-S");
+        args.push(session);
+    } else {
+        // Open files "normally" through nvim. Stdin ("-") is read by us.
+        for file in opts.open_files.iter().filter(|f| *f != "-") {
+            args.push(file);
+        }
     }
 
     // Print the nvim cmd which is executed if asked.
@@ -163,6 +377,7 @@ async fn build(app: &gtk::Application, opts: &Options) -> Result<(), Error> {
     let mut nvim = nvim_gio::new_child(
         bridge,
         args.iter().map(|a| std::ffi::OsStr::new(a)).collect(),
+        opts.cwd.as_deref(),
         tx,
     )
     .map_err(Error::from)?;
@@ -170,36 +385,117 @@ async fn build(app: &gtk::Application, opts: &Options) -> Result<(), Error> {
     nvim.subscribe("Gnvim").await.map_err(Error::from)?;
 
     let api_info = nvim.get_api_info().await.map_err(Error::from)?;
+
+    crash::set_nvim_version(nvim_version(&api_info[1]));
+
+    if opts.print_nvim_version {
+        let path = glib::find_program_in_path(&opts.nvim_path)
+            .unwrap_or_else(|| PathBuf::from(&opts.nvim_path));
+        println!(
+            "nvim version: {} ({})",
+            nvim_version(&api_info[1]),
+            path.display()
+        );
+    }
+
     nvim.set_var("gnvim_channel_id", api_info[0].clone())
         .await
         .map_err(Error::from)?;
 
-    let mut ui_opts = nvim_rs::UiAttachOptions::new();
-    ui_opts.set_rgb(true);
-    ui_opts.set_linegrid_external(true);
-    ui_opts.set_multigrid_external(true);
-    ui_opts.set_popupmenu_external(!opts.disable_ext_popupmenu);
-    ui_opts.set_tabline_external(!opts.disable_ext_tabline);
-    ui_opts.set_cmdline_external(!opts.disable_ext_cmdline);
+    // Without attaching, nvim doesn't send redraw events that would mix
+    // with the replayed ones.
+    if replay.is_none() {
+        let mut ui_opts = nvim_rs::UiAttachOptions::new();
+        ui_opts.set_rgb(true);
+        ui_opts.set_linegrid_external(true);
+        ui_opts.set_multigrid_external(true);
+        ui_opts.set_popupmenu_external(!opts.disable_ext_popupmenu);
+        ui_opts.set_tabline_external(!opts.disable_ext_tabline);
+        ui_opts.set_cmdline_external(!opts.disable_ext_cmdline);
+        ui_opts.set_termcolors_external(opts.ext_termcolors);
+
+        nvim.ui_attach(80, 30, &ui_opts)
+            .await
+            .map_err(Error::from)?;
+
+        if opts.ext_messages {
+            nvim.ui_set_option("ext_messages", true.into())
+                .await
+                .map_err(Error::from)?;
+        }
+    }
 
-    nvim.ui_attach(80, 30, &ui_opts)
-        .await
-        .map_err(Error::from)?;
+    if session.is_none() && opts.open_files.iter().any(|f| f == "-") {
+        if nvim_gio::stdin::is_readable() {
+            // Like with nvim, stdin is read into the current buffer, and
+            // the other files are only in the argument list.
+            let buf = if opts.open_files.len() > 1 {
+                let buf = nvim.create_buf(true, false).await?;
+                nvim.set_current_buf(&buf).await?;
+                buf
+            } else {
+                nvim.get_current_buf().await?
+            };
+
+            glib::MainContext::default()
+                .spawn_local(nvim_gio::stdin::read_into(nvim.clone(), buf));
+        } else {
+            error!("Not reading stdin, it's a terminal");
+        }
+    }
+
+    let ui = ui::UI::init(
+        app,
+        rx,
+        nvim,
+        ui::UIOptions {
+            window_size: opts.geometry,
+            toplevel: ui::ToplevelState {
+                fullscreen: opts.fullscreen,
+                maximized: opts.maximized,
+                ..Default::default()
+            },
+            renderer: opts.renderer,
+            render_threads: opts.render_threads,
+            background_opacity: opts.background_opacity,
+            custom_css: opts.custom_css.clone(),
+            dbus: opts.dbus,
+            offscreen_dir: opts.offscreen.clone(),
+        },
+    );
+
+    if let Some(entries) = replay {
+        recording::replay(entries, replay_tx, opts.replay_speed);
+    }
 
-    let ui = ui::UI::init(app, rx, opts.geometry, nvim);
-    ui.start();
+    let app = app.clone();
+    ui.start(session_file(), move |session| {
+        start(&app, opts.clone(), session)
+    });
 
     Ok(())
 }
 
-fn main() {
-    env_logger::init();
+/// Replays the recording at `path` without the UI, and prints a summary of
+/// it.
+fn replay_headless(path: &Path) {
+    let entries = match recording::read(path) {
+        Ok(entries) => entries,
+        Err(err) => {
+            error!("Failed to replay: {}", err);
+            std::process::exit(1);
+        }
+    };
 
-    if let Err(err) = gtk::init() {
-        error!("Failed to initialize gtk: {}", err);
-        return;
+    let summary = recording::replay_headless(entries);
+    println!("notifications: {}", summary.notifications);
+    for (event, count) in summary.events.iter() {
+        println!("{}: {}", event, count);
     }
+    println!("parse time: {:?}", summary.parse_time);
+}
 
+fn main() {
     let opts = Options::clap();
     let opts = Options::from_clap(&opts.get_matches_safe().unwrap_or_else(
         |mut err| {
@@ -217,8 +513,39 @@ fn main() {
         },
     ));
 
+    if let Err(err) =
+        logging::init(opts.log_level.clone(), opts.log_file.as_deref())
+    {
+        eprintln!("Failed to initialize logging: {}", err);
+    }
+
+    if opts.replay_headless {
+        // Clap makes sure that the file is given.
+        if let Some(ref path) = opts.replay {
+            replay_headless(path);
+        }
+        return;
+    }
+
+    if let Some(ref path) = opts.crash_report {
+        match gtk::init() {
+            Ok(()) => crash::show_dialog(path),
+            Err(err) => error!("Failed to initialize gtk: {}", err),
+        }
+        return;
+    }
+
+    crash::install_hook();
+
+    if let Err(err) = gtk::init() {
+        error!("Failed to initialize gtk: {}", err);
+        return;
+    }
+
     let mut flags = gio::ApplicationFlags::empty();
-    flags.insert(gio::ApplicationFlags::NON_UNIQUE);
+    if !opts.single_instance {
+        flags.insert(gio::ApplicationFlags::NON_UNIQUE);
+    }
     flags.insert(gio::ApplicationFlags::HANDLES_OPEN);
     let app = gtk::Application::new(Some("com.github.vhakulinen.gnvim"), flags)
         .unwrap();
@@ -238,15 +565,38 @@ fn main() {
         }
     }
 
-    app.connect_activate(move |app| {
-        let opts = &opts;
-        let c = glib::MainContext::default();
-        c.block_on(async move {
-            if let Err(err) = build(app, opts).await {
-                error!("Failed to build UI: {}", err);
-            }
-        });
+    // In single instance mode, the files are given to the application, which
+    // forwards them to the running instance (if there is one).
+    let args = if opts.single_instance {
+        std::env::args()
+            .take(1)
+            .chain(opts.open_files.iter().cloned())
+            .collect()
+    } else {
+        vec![]
+    };
+
+    let opts = Rc::new(opts);
+    app.connect_activate(clone!(opts => move |app| {
+        // Running instance is activated when it's started again without
+        // files.
+        if let Some(window) = app.get_active_window() {
+            window.present();
+            return;
+        }
+
+        start(app, opts.clone(), None)
+    }));
+    // The files are opened by the UI if it's running already, otherwise
+    // nvim opens them on start.
+    app.connect_open(move |app, _, _| {
+        if app.get_windows().is_empty() {
+            start(app, opts.clone(), None)
+        }
     });
 
-    app.run(&[]);
+    app.run(&args);
+
+    // Remove the session file, if nvim has saved one.
+    let _ = std::fs::remove_file(session_file());
 }
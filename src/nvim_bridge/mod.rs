@@ -10,10 +10,18 @@ use futures::future::Future;
 use nvim_rs::{create::Spawner, neovim::Neovim, Handler};
 use rmpv::Value;
 
+use crate::nvim_bridge::recording::Recorder;
 use crate::nvim_gio::GioWriter;
 use crate::thread_guard::ThreadGuard;
-use crate::ui::color::{Color, Highlight};
-
+use crate::ui::color::{Color, Highlight, VirtualText};
+use crate::ui::{
+    BlinkCurve, CmdlinePosition, Crosshair, Decorations, ExportFormat,
+    FloatOptions, IndentGuides, PopupmenuDetailsSide, PopupmenuPosition,
+    ScreenshotTarget, SignKind, SnippetPlaceholder, SnippetState, TablineMode,
+    WildmenuLayout,
+};
+
+pub mod recording;
 #[cfg(test)]
 mod tests;
 
@@ -73,6 +81,23 @@ macro_rules! try_u64 {
     };
 }
 
+macro_rules! try_i64 {
+    ($val:expr, $msg:expr) => {
+        $val.as_i64()
+            .ok_or(format!("Value is not an i64: {}", $msg))?
+    };
+}
+
+// Accepts integers too, so that e.g. `0` and `1` work from vimscript.
+macro_rules! try_f64 {
+    ($val:expr, $msg:expr) => {{
+        let val = $val;
+        val.as_f64()
+            .or_else(|| val.as_i64().map(|v| v as f64))
+            .ok_or(format!("Value is not a number: {}", $msg))?
+    }};
+}
+
 impl Highlight {
     fn from_map_val(map: &[(Value, Value)]) -> Self {
         let mut hl = Highlight::default();
@@ -120,6 +145,20 @@ impl Highlight {
             "undercurl" => {
                 self.undercurl = unwrap_bool!(val);
             }
+            // Older nvim versions use `underlineline`, `underdash` and
+            // `underdot`.
+            "underdouble" | "underlineline" => {
+                self.underdouble = unwrap_bool!(val);
+            }
+            "underdashed" | "underdash" => {
+                self.underdashed = unwrap_bool!(val);
+            }
+            "underdotted" | "underdot" => {
+                self.underdotted = unwrap_bool!(val);
+            }
+            "blend" => {
+                self.blend = val.as_u64().unwrap_or(0).min(100) as u8;
+            }
             "cterm_fg" => {}
             "cterm_bg" => {}
             _ => {
@@ -167,20 +206,35 @@ impl Default for CursorShape {
 
 #[derive(Default, Clone, Debug, PartialEq)]
 pub struct ModeInfo {
-    /// The cursor blinking period (in ms)
+    /// Delay before the cursor starts blinking (in ms)
+    pub blink_wait: u64,
+    /// The time that the cursor is shown when blinking (in ms)
     pub blink_on: u64,
+    /// The time that the cursor is hidden when blinking (in ms)
+    pub blink_off: u64,
     pub cursor_shape: CursorShape,
     /// The cursor's width (in percentages, from 0..1).
     pub cell_percentage: f64,
+    /// Highlight id of the cursor.
+    pub attr_id: u64,
     // TODO(ville): Implement the rest.
 }
 
 impl ModeInfo {
     fn set(&mut self, prop: &str, val: Value) {
         match prop {
+            "blinkwait" => {
+                self.blink_wait = unwrap_u64!(val);
+            }
             "blinkon" => {
                 self.blink_on = unwrap_u64!(val);
             }
+            "blinkoff" => {
+                self.blink_off = unwrap_u64!(val);
+            }
+            "attr_id" => {
+                self.attr_id = unwrap_u64!(val);
+            }
             "cursor_shape" => {
                 self.cursor_shape = CursorShape::from_string(unwrap_str!(val))
             }
@@ -212,6 +266,8 @@ pub enum OptionSet {
     GuiFont(String),
     /// Space between lines.
     LineSpace(i64),
+    /// Transparency of the popupmenu, from 0 to 100.
+    PumBlend(i64),
     /// Event name.
     NotSupported(String),
 }
@@ -229,12 +285,16 @@ impl From<Value> for OptionSet {
                 let val = unwrap_i64!(args[1]);
                 OptionSet::LineSpace(val)
             }
+            "pumblend" => {
+                let val = unwrap_i64!(args[1]);
+                OptionSet::PumBlend(val)
+            }
             _ => OptionSet::NotSupported(String::from(name)),
         }
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum CompletionItemKind {
     Class,
     Color,
@@ -308,12 +368,79 @@ impl From<&str> for CompletionItemKind {
 }
 
 impl CompletionItemKind {
+    /// All the kinds.
+    pub const ALL: [CompletionItemKind; 26] = [
+        CompletionItemKind::Class,
+        CompletionItemKind::Color,
+        CompletionItemKind::Constant,
+        CompletionItemKind::Constructor,
+        CompletionItemKind::Enum,
+        CompletionItemKind::EnumMember,
+        CompletionItemKind::Event,
+        CompletionItemKind::Function,
+        CompletionItemKind::File,
+        CompletionItemKind::Folder,
+        CompletionItemKind::Field,
+        CompletionItemKind::Interface,
+        CompletionItemKind::Keyword,
+        CompletionItemKind::Method,
+        CompletionItemKind::Module,
+        CompletionItemKind::Operator,
+        CompletionItemKind::Property,
+        CompletionItemKind::Reference,
+        CompletionItemKind::Snippet,
+        CompletionItemKind::Struct,
+        CompletionItemKind::Text,
+        CompletionItemKind::TypeParameter,
+        CompletionItemKind::Unit,
+        CompletionItemKind::Unknown,
+        CompletionItemKind::Value,
+        CompletionItemKind::Variable,
+    ];
+
     pub fn is_unknown(&self) -> bool {
         match self {
             CompletionItemKind::Unknown => true,
             _ => false,
         }
     }
+
+    /// Name of the kind, e.g. `EnumMember`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CompletionItemKind::Class => "Class",
+            CompletionItemKind::Color => "Color",
+            CompletionItemKind::Constant => "Constant",
+            CompletionItemKind::Constructor => "Constructor",
+            CompletionItemKind::Enum => "Enum",
+            CompletionItemKind::EnumMember => "EnumMember",
+            CompletionItemKind::Event => "Event",
+            CompletionItemKind::Function => "Function",
+            CompletionItemKind::File => "File",
+            CompletionItemKind::Folder => "Folder",
+            CompletionItemKind::Field => "Field",
+            CompletionItemKind::Interface => "Interface",
+            CompletionItemKind::Keyword => "Keyword",
+            CompletionItemKind::Method => "Method",
+            CompletionItemKind::Module => "Module",
+            CompletionItemKind::Operator => "Operator",
+            CompletionItemKind::Property => "Property",
+            CompletionItemKind::Reference => "Reference",
+            CompletionItemKind::Snippet => "Snippet",
+            CompletionItemKind::Struct => "Struct",
+            CompletionItemKind::Text => "Text",
+            CompletionItemKind::TypeParameter => "TypeParameter",
+            CompletionItemKind::Unit => "Unit",
+            CompletionItemKind::Unknown => "Unknown",
+            CompletionItemKind::Value => "Value",
+            CompletionItemKind::Variable => "Variable",
+        }
+    }
+
+    /// Returns the kind named `name` (see `name`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().find(|kind| kind.name() == name).cloned()
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -531,23 +658,51 @@ impl From<Value> for GridScroll {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct DefaultColorsSet {
-    pub fg: Color,
-    pub bg: Color,
+    /// Foreground, or `None` if nvim doesn't have one (e.g. without a
+    /// colorscheme), in which case the color scheme's fallback is used.
+    pub fg: Option<Color>,
+    /// Background, or `None` if nvim doesn't have one.
+    pub bg: Option<Color>,
     pub sp: Color,
+    /// Terminal palette index of the foreground, when it has no rgb color
+    /// but has a terminal color (with `ext_termcolors`).
+    pub cterm_fg: Option<u64>,
+    /// Terminal palette index of the background, when it has no rgb color
+    /// but has a terminal color (with `ext_termcolors`).
+    pub cterm_bg: Option<u64>,
 }
 
 impl From<Value> for DefaultColorsSet {
     fn from(args: Value) -> Self {
         let args = unwrap_array!(args);
 
-        let fg = Color::from_u64(args[0].as_u64().unwrap_or(0));
-        let bg = Color::from_u64(args[1].as_u64().unwrap_or(std::u64::MAX));
+        let fg = args[0].as_u64().map(Color::from_u64);
+        let bg = args[1].as_u64().map(Color::from_u64);
         // Default to red.
         let sp = Color::from_u64(args[2].as_u64().unwrap_or(16711680));
 
-        DefaultColorsSet { fg, bg, sp }
+        let cterm = |rgb: usize, cterm: usize| {
+            if args[rgb].as_u64().is_some() {
+                return None;
+            }
+            // Zero is the terminal's own default color, and the others
+            // are the palette index plus one.
+            args.get(cterm)
+                .and_then(Value::as_u64)
+                .and_then(|c| c.checked_sub(1))
+        };
+        let cterm_fg = cterm(0, 3);
+        let cterm_bg = cterm(1, 4);
+
+        DefaultColorsSet {
+            fg,
+            bg,
+            sp,
+            cterm_fg,
+            cterm_bg,
+        }
     }
 }
 
@@ -555,6 +710,10 @@ impl From<Value> for DefaultColorsSet {
 pub struct HlAttrDefine {
     pub id: u64,
     pub hl: Highlight,
+    /// Target of a hyperlink (e.g. OSC 8 links in terminals).
+    pub url: Option<String>,
+    /// Names of the highlight groups the highlight is made of.
+    pub groups: Vec<String>,
 }
 
 impl From<Value> for HlAttrDefine {
@@ -564,8 +723,35 @@ impl From<Value> for HlAttrDefine {
         let map = unwrap_map!(args[1]);
 
         let hl = Highlight::from_map_val(map);
+        let url = map
+            .iter()
+            .find(|(key, _)| key.as_str() == Some("url"))
+            .and_then(|(_, val)| val.as_str())
+            .map(String::from);
+
+        // With `ext_hlstate`, the highlight groups the highlight is made of.
+        let groups = args
+            .get(3)
+            .and_then(Value::as_array)
+            .map(|info| {
+                info.iter()
+                    .filter_map(Value::as_map)
+                    .filter_map(|item| {
+                        item.iter()
+                            .find(|(key, _)| key.as_str() == Some("hi_name"))
+                            .and_then(|(_, val)| val.as_str())
+                            .map(String::from)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
 
-        HlAttrDefine { id, hl }
+        HlAttrDefine {
+            id,
+            hl,
+            url,
+            groups,
+        }
     }
 }
 
@@ -693,6 +879,10 @@ impl From<Value> for CmdlineBlockAppend {
 pub struct TablineUpdate {
     pub current: Value,
     pub tabs: Vec<(Value, String)>,
+    /// Current buffer. Only sent by nvim 0.5 and newer.
+    pub current_buffer: Option<Value>,
+    /// Listed buffers and their names. Only sent by nvim 0.5 and newer.
+    pub buffers: Vec<(Value, String)>,
 }
 
 impl From<Value> for TablineUpdate {
@@ -709,7 +899,31 @@ impl From<Value> for TablineUpdate {
             })
             .collect();
 
-        Self { current, tabs }
+        let args = unwrap_array!(args);
+        let current_buffer = args.get(2).cloned();
+        let buffers = args
+            .get(3)
+            .and_then(Value::as_array)
+            .map(|buffers| {
+                buffers
+                    .iter()
+                    .map(|item| {
+                        let m = map_to_hash(&item);
+                        (
+                            (*m.get("buffer").unwrap()).clone(),
+                            unwrap_str!(m.get("name").unwrap()).to_string(),
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            current,
+            tabs,
+            current_buffer,
+            buffers,
+        }
     }
 }
 
@@ -763,7 +977,7 @@ impl From<Value> for WindowPos {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Anchor {
     NW,
     NE,
@@ -800,7 +1014,7 @@ impl From<Value> for Anchor {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct WindowFloatPos {
     pub grid: i64,
     pub win: Value,
@@ -809,6 +1023,9 @@ pub struct WindowFloatPos {
     pub anchor_row: f64,
     pub anchor_col: f64,
     pub focusable: bool,
+    /// Stacking order of the window. Sent by nvim 0.5.1 and newer,
+    /// defaults to 50 (nvim's default).
+    pub zindex: u64,
 }
 
 impl From<Value> for WindowFloatPos {
@@ -822,6 +1039,7 @@ impl From<Value> for WindowFloatPos {
             anchor_row: unwrap_f64!(args[4]),
             anchor_col: unwrap_f64!(args[5]),
             focusable: unwrap_bool!(args[6]),
+            zindex: args.get(7).and_then(Value::as_u64).unwrap_or(50),
         }
     }
 }
@@ -842,6 +1060,194 @@ impl From<Value> for WindowExternalPos {
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub struct WindowViewport {
+    pub grid: i64,
+    pub win: Value,
+    pub topline: u64,
+    pub botline: u64,
+    pub curline: u64,
+    pub curcol: u64,
+    /// Number of lines in the buffer. Sent by nvim 0.6 and newer.
+    pub line_count: Option<u64>,
+}
+
+impl From<Value> for WindowViewport {
+    fn from(args: Value) -> Self {
+        let args = unwrap_array!(args);
+        Self {
+            grid: unwrap_i64!(args[0]),
+            win: args[1].clone(),
+            topline: unwrap_u64!(args[2]),
+            botline: unwrap_u64!(args[3]),
+            curline: unwrap_u64!(args[4]),
+            curcol: unwrap_u64!(args[5]),
+            line_count: args.get(6).and_then(Value::as_u64),
+        }
+    }
+}
+
+impl WindowViewport {
+    /// Returns the window's handle (e.g. `win_getid()`), decoded from its
+    /// ext value.
+    pub fn win_handle(&self) -> Option<i64> {
+        window_handle(&self.win)
+    }
+}
+
+/// Returns the handle (e.g. `win_getid()`) of window `win`, decoded from
+/// its ext value.
+pub fn window_handle(win: &Value) -> Option<i64> {
+    match win {
+        Value::Ext(_, ref data) => {
+            rmpv::decode::read_value(&mut &data[..]).ok()?.as_i64()
+        }
+        _ => None,
+    }
+}
+
+/// Part of a window's winbar, see `GnvimEvent::SetWinbar`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WinbarSegment {
+    pub text: String,
+    pub hl: Highlight,
+    /// Name of the function called when the segment is clicked.
+    pub click: Option<String>,
+}
+
+impl WinbarSegment {
+    fn from_value(val: &Value) -> Result<Self, String> {
+        val.as_map().ok_or("Value is not a map: winbar segment")?;
+        let map = map_to_hash(val);
+
+        let text = try_str!(map.get("text").ok_or("text missing")?, "text");
+        let hl = match map.get("hl") {
+            Some(hl) => Highlight::from_map_val(
+                hl.as_map().ok_or("Value is not a map: winbar highlight")?,
+            ),
+            None => Highlight::default(),
+        };
+        let click = match map.get("click") {
+            Some(click) => Some(try_str!(click, "click")),
+            None => None,
+        }
+        .filter(|click| !click.is_empty())
+        .map(String::from);
+
+        Ok(WinbarSegment {
+            text: String::from(text),
+            hl,
+            click,
+        })
+    }
+}
+
+/// Part of the GUI statusbar, see `GnvimEvent::SetStatusline`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatuslineSegment {
+    pub text: String,
+    pub hl: Highlight,
+    /// Name of an icon (from the icon theme) shown before the text.
+    pub icon: Option<String>,
+    /// Name of the function called when the segment is clicked.
+    pub click: Option<String>,
+    /// If the segment is on the right side of the statusbar.
+    pub right: bool,
+}
+
+impl StatuslineSegment {
+    fn from_value(val: &Value) -> Result<Self, String> {
+        val.as_map()
+            .ok_or("Value is not a map: statusline segment")?;
+        let map = map_to_hash(val);
+
+        let text = try_str!(map.get("text").ok_or("text missing")?, "text");
+        let hl = match map.get("hl") {
+            Some(hl) => Highlight::from_map_val(
+                hl.as_map()
+                    .ok_or("Value is not a map: statusline highlight")?,
+            ),
+            None => Highlight::default(),
+        };
+        let optional_str = |key: &str| -> Result<Option<String>, String> {
+            Ok(match map.get(key) {
+                Some(val) => Some(try_str!(val, key)),
+                None => None,
+            }
+            .filter(|val| !val.is_empty())
+            .map(String::from))
+        };
+        let right = match optional_str("align")?.as_deref() {
+            None | Some("left") => false,
+            Some("right") => true,
+            Some(align) => {
+                return Err(format!("Invalid statusline align: {}", align))
+            }
+        };
+
+        Ok(StatuslineSegment {
+            text: String::from(text),
+            hl,
+            icon: optional_str("icon")?,
+            click: optional_str("click")?,
+            right,
+        })
+    }
+}
+
+/// Native file dialog, see `GnvimEvent::FileOpenDialog`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FileDialog {
+    /// Passed back to nvim with the chosen path.
+    pub id: u64,
+    pub title: Option<String>,
+    /// Folder where the dialog starts.
+    pub folder: Option<String>,
+    /// Suggested file name, for save dialogs.
+    pub name: Option<String>,
+    /// Glob patterns of the files that are shown, e.g. `*.md`.
+    pub patterns: Vec<String>,
+}
+
+impl FileDialog {
+    fn from_args(args: &[Value]) -> Result<Self, String> {
+        let id = try_u64!(args.get(1).ok_or("dialog id missing")?, "dialog id");
+        let opts = match args.get(2) {
+            Some(opts) => {
+                opts.as_map().ok_or("Value is not a map: dialog options")?;
+                map_to_hash(opts)
+            }
+            None => HashMap::new(),
+        };
+
+        let optional_str = |key: &str| -> Result<Option<String>, String> {
+            Ok(match opts.get(key) {
+                Some(val) => Some(try_str!(val, key)),
+                None => None,
+            }
+            .filter(|val| !val.is_empty())
+            .map(String::from))
+        };
+        let patterns = match opts.get("patterns") {
+            Some(patterns) => patterns
+                .as_array()
+                .ok_or("Value is not an array: dialog patterns")?
+                .iter()
+                .map(|p| Ok(String::from(try_str!(p, "dialog pattern"))))
+                .collect::<Result<_, String>>()?,
+            None => vec![],
+        };
+
+        Ok(FileDialog {
+            id,
+            title: optional_str("title")?,
+            folder: optional_str("folder")?,
+            name: optional_str("name")?,
+            patterns,
+        })
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct MsgSetPos {
     pub grid: i64,
@@ -862,6 +1268,61 @@ impl From<Value> for MsgSetPos {
     }
 }
 
+/// Parses message content (a list of `[attr_id, text]` chunks).
+fn parse_msg_content(content: &Value) -> Vec<(u64, String)> {
+    unwrap_array!(content)
+        .iter()
+        .map(|v| (unwrap_u64!(v[0]), String::from(unwrap_str!(v[1]))))
+        .collect()
+}
+
+/// Parses the arguments of `msg_showmode`, `msg_showcmd` and `msg_ruler`.
+fn parse_msg_status(args: &Value) -> Vec<(u64, String)> {
+    let args = unwrap_array!(args);
+    parse_msg_content(&args[0])
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MsgShow {
+    pub kind: String,
+    pub content: Vec<(u64, String)>,
+    pub replace_last: bool,
+}
+
+impl From<Value> for MsgShow {
+    fn from(args: Value) -> Self {
+        let args = unwrap_array!(args);
+        Self {
+            kind: unwrap_str!(args[0]).to_string(),
+            content: parse_msg_content(&args[1]),
+            replace_last: unwrap_bool!(args[2]),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MsgHistoryShow {
+    /// Kind and content of the messages.
+    pub entries: Vec<(String, Vec<(u64, String)>)>,
+}
+
+impl From<Value> for MsgHistoryShow {
+    fn from(args: Value) -> Self {
+        let args = unwrap_array!(args);
+        Self {
+            entries: unwrap_array!(args[0])
+                .iter()
+                .map(|entry| {
+                    (
+                        unwrap_str!(entry[0]).to_string(),
+                        parse_msg_content(&entry[1]),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum RedrawEvent {
     SetTitle(Vec<String>),
@@ -901,7 +1362,17 @@ pub enum RedrawEvent {
     WindowExternalPos(Vec<WindowExternalPos>),
     WindowHide(Vec<i64>),
     WindowClose(Vec<i64>),
+    WindowViewport(Vec<WindowViewport>),
     MsgSetPos(Vec<MsgSetPos>),
+    MsgShow(Vec<MsgShow>),
+    MsgClear(),
+    MsgHistoryShow(Vec<MsgHistoryShow>),
+    /// Contents of the mode message (e.g. "-- INSERT --").
+    MsgShowmode(Vec<Vec<(u64, String)>>),
+    /// Contents of 'showcmd'.
+    MsgShowcmd(Vec<Vec<(u64, String)>>),
+    /// Contents of 'ruler'.
+    MsgRuler(Vec<Vec<(u64, String)>>),
 
     Ignored(String),
     Unknown(String),
@@ -954,7 +1425,14 @@ impl fmt::Display for RedrawEvent {
             }
             RedrawEvent::WindowHide(..) => write!(fmt, "WindowHide"),
             RedrawEvent::WindowClose(..) => write!(fmt, "WindowClose"),
+            RedrawEvent::WindowViewport(..) => write!(fmt, "WindowViewport"),
             RedrawEvent::MsgSetPos(..) => write!(fmt, "MsgSetPos"),
+            RedrawEvent::MsgShow(..) => write!(fmt, "MsgShow"),
+            RedrawEvent::MsgClear(..) => write!(fmt, "MsgClear"),
+            RedrawEvent::MsgHistoryShow(..) => write!(fmt, "MsgHistoryShow"),
+            RedrawEvent::MsgShowmode(..) => write!(fmt, "MsgShowmode"),
+            RedrawEvent::MsgShowcmd(..) => write!(fmt, "MsgShowcmd"),
+            RedrawEvent::MsgRuler(..) => write!(fmt, "MsgRuler"),
 
             RedrawEvent::Ignored(..) => write!(fmt, "Ignored"),
             RedrawEvent::Unknown(e) => write!(fmt, "Unknown({})", e),
@@ -970,12 +1448,149 @@ pub enum GnvimEvent {
     CursorTooltipShow(String, u64, u64),
     CursorTooltipHide,
     CursorTooltipSetStyle(String),
+    CursorTooltipScroll(i64),
 
     PopupmenuWidth(u64),
     PopupmenuWidthDetails(u64),
     PopupmenuShowMenuOnAllItems(bool),
+    PopupmenuMaxHeight(u64),
+    /// Maximum number of visible items, 0 for no limit.
+    PopupmenuMaxRows(u64),
+    PopupmenuPosition(PopupmenuPosition),
+    PopupmenuDetailsSide(PopupmenuDetailsSide),
+    PopupmenuScrollDetails(i64),
+    PopupmenuKindGlyph(CompletionItemKind, String),
+
+    CmdlinePosition(CmdlinePosition),
+    CmdlineSearchPosition(Option<CmdlinePosition>),
+    WildmenuLayout(WildmenuLayout),
 
     EnableCursorAnimations(bool),
+    CursorBlinkCurve(BlinkCurve),
+    EnableCursorHollowUnfocused(bool),
+    SetCrosshair(Crosshair),
+    SetIndentGuides(IndentGuides),
+    IndentInfoChanged,
+    /// A window's buffer (or the buffer's name) changed, so the titles of
+    /// the external windows are updated.
+    WindowBufferChanged,
+    SetBackgroundOpacity(f64),
+    /// Scale of the device pixels, `None` to use gtk's scale factor.
+    SetDeviceScale(Option<f64>),
+    SetBackgroundImage(String, u64, f64),
+    /// Path of the user's CSS file, or empty to remove the CSS.
+    SetCustomCss(String),
+    ImageShow(u64, String, (u64, u64), (u64, u64)),
+    ImageHide(u64),
+    ImageClear,
+    /// Snippet that is being expanded in a window, by its handle.
+    SnippetState(i64, SnippetState),
+    EnableDamageOverlay(bool),
+    TogglePerfOverlay,
+    /// Opens the log viewer.
+    LogViewerShow,
+    /// Writes images and the text of the grids to a directory.
+    DumpGrids(String),
+    EnableKineticScrolling(bool),
+    EnableGuiSelection(bool),
+    SetFullscreen(Option<bool>),
+    SetMaximized(Option<bool>),
+    SetKeepAbove(Option<bool>),
+    SetDecorations(Decorations),
+    SetProgress(Option<f64>),
+    EnableScrollbar(bool),
+    EnableMinimap(bool),
+    EnableFloatDrag(bool),
+    /// Decorations of a floating window (by its handle), or of all of
+    /// them.
+    SetFloatStyle(Option<i64>, FloatOptions),
+    /// Enables the window animations, and sets their durations (in
+    /// milliseconds).
+    SetAnimations {
+        enable: bool,
+        float_open: u64,
+        window_close: u64,
+    },
+    TerminalPalette(Vec<Option<Color>>),
+    /// Highlight groups that nvim doesn't send with `hl_group_set`, by name.
+    SetHlGroups(Vec<(String, Highlight)>),
+    /// Highlight groups of virtual text (e.g. inline git blame), and the
+    /// style it's drawn in.
+    SetVirtualText(Vec<String>, VirtualText),
+    TerminalGraphics(u64, String, (u64, u64)),
+    PreviewUpdate(String, i64),
+    PreviewHide,
+    /// Segments of the winbar of a window (by its handle, since plugins
+    /// don't know the grids).
+    SetWinbar(i64, Vec<WinbarSegment>),
+    /// Signs (row, kind) in the gutter of a window (by its handle), which
+    /// covers `width` cells of the window's sign column from `col`.
+    SetSigns {
+        win: i64,
+        col: u64,
+        width: u64,
+        signs: Vec<(u64, SignKind)>,
+    },
+    /// Tab page detached to its own OS window, with the handles of its
+    /// windows and their positions (row, col) in the tab page.
+    DetachTab {
+        id: u64,
+        title: String,
+        windows: Vec<(i64, u64, u64)>,
+    },
+    /// Segments of the GUI statusbar. Without segments, the statusbar is
+    /// hidden.
+    SetStatusline(Vec<StatuslineSegment>),
+    /// Lists the tab pages or the buffers in the tabline.
+    TablineMode(TablineMode),
+    /// Current buffer and the listed buffers (number, name, modified),
+    /// for the tabline's buffer mode.
+    TablineBuffers {
+        current: i64,
+        buffers: Vec<(i64, String, bool)>,
+    },
+    /// Shows the file tree with the files in a directory.
+    FileTreeShow(String),
+    FileTreeHide,
+    /// Patterns of the URLs and the file paths in the grids that can be
+    /// clicked.
+    SetLinkPatterns {
+        url: String,
+        path: String,
+    },
+    /// Format, path and the lines to export. Without lines, the current
+    /// grid is exported.
+    Export(ExportFormat, String, Option<Vec<Vec<(String, Highlight)>>>),
+    /// Title (buffer name), line number of the first line and the lines
+    /// to print.
+    Print(String, u64, Vec<Vec<(String, Highlight)>>),
+    /// Saves a png of `target` to `path`, or copies it to the clipboard
+    /// if there's no path.
+    Screenshot {
+        target: ScreenshotTarget,
+        path: Option<String>,
+    },
+    EnableAltAsMeta(bool),
+    EnableShortcut(String, bool),
+
+    FontPickerShow,
+    /// Shows the native file chooser for opening a file. The chosen path
+    /// is passed to `gnvim#dialog#respond`.
+    FileOpenDialog(FileDialog),
+    /// Like `FileOpenDialog`, for saving a file.
+    FileSaveDialog(FileDialog),
+    CommandPaletteShow,
+    FinderShow,
+    SearchBarShow,
+    /// Starts copy mode in the cmdline block or the message grid.
+    CopyModeEnter,
+    /// Pastes bigger than this (in bytes) are confirmed first. 0 disables
+    /// the confirmation.
+    PasteConfirmThreshold(u64),
+    /// How many inputs are queued while nvim is busy. 0 disables the
+    /// queueing.
+    InputQueueSize(u64),
+    DesktopNotify(String, String, bool),
 
     Unknown(String),
 }
@@ -990,8 +1605,11 @@ pub enum Message {
     Notify(Notify),
     /// RPC Request (see `: rpcrequest()`).
     Request(Sender<Result<Value, Value>>, Request),
-    /// Nvim went away or reading from the rcp connection failed.
+    /// Nvim exited normally.
     Close,
+    /// Nvim exited unexpectedly (or reading from the rpc connection failed),
+    /// with the reason.
+    Crash(String),
 }
 
 #[derive(Clone)]
@@ -1005,6 +1623,9 @@ pub struct NvimBridge {
     request_tx: Arc<ThreadGuard<Sender<Result<Value, Value>>>>,
     /// Receiving end of `request_tx`.
     request_rx: Arc<ThreadGuard<Receiver<Result<Value, Value>>>>,
+
+    /// Recorder of the redraw notifications, if they're recorded.
+    recorder: Option<Arc<Recorder>>,
 }
 
 impl NvimBridge {
@@ -1015,8 +1636,15 @@ impl NvimBridge {
             tx: Arc::new(ThreadGuard::new(tx)),
             request_tx: Arc::new(ThreadGuard::new(request_tx)),
             request_rx: Arc::new(ThreadGuard::new(request_rx)),
+            recorder: None,
         }
     }
+
+    /// Records the redraw notifications with `recorder`.
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(Arc::new(recorder));
+        self
+    }
 }
 
 #[async_trait]
@@ -1056,6 +1684,10 @@ impl Handler for NvimBridge {
         args: Vec<Value>,
         _neovim: Neovim<<Self as Handler>::Writer>,
     ) {
+        if let (Some(recorder), "redraw") = (&self.recorder, name.as_str()) {
+            recorder.record(&args);
+        }
+
         if let Some(notify) = parse_notify(&name, args) {
             let tx = self.tx.borrow_mut();
             tx.send(Message::Notify(notify)).unwrap();
@@ -1193,9 +1825,29 @@ fn parse_single_redraw_event(cmd: &str, args: Vec<Value>) -> RedrawEvent {
                 })
                 .collect(),
         ),
+        "win_viewport" => RedrawEvent::WindowViewport(
+            args.into_iter().map(WindowViewport::from).collect(),
+        ),
         "msg_set_pos" => RedrawEvent::MsgSetPos(
             args.into_iter().map(MsgSetPos::from).collect(),
         ),
+        "msg_show" => {
+            RedrawEvent::MsgShow(args.into_iter().map(MsgShow::from).collect())
+        }
+        "msg_clear" => RedrawEvent::MsgClear(),
+        "msg_history_show" => RedrawEvent::MsgHistoryShow(
+            args.into_iter().map(MsgHistoryShow::from).collect(),
+        ),
+
+        "msg_showmode" => RedrawEvent::MsgShowmode(
+            args.into_iter().map(|v| parse_msg_status(&v)).collect(),
+        ),
+        "msg_showcmd" => RedrawEvent::MsgShowcmd(
+            args.into_iter().map(|v| parse_msg_status(&v)).collect(),
+        ),
+        "msg_ruler" => RedrawEvent::MsgRuler(
+            args.into_iter().map(|v| parse_msg_status(&v)).collect(),
+        ),
 
         "mouse_on" | "mouse_off" => RedrawEvent::Ignored(cmd.to_string()),
         _ => RedrawEvent::Unknown(cmd.to_string()),
@@ -1242,6 +1894,13 @@ pub(crate) fn parse_gnvim_event(
             );
             GnvimEvent::CursorTooltipSetStyle(style.to_string())
         }
+        "CursorTooltipScroll" => {
+            let lines = try_i64!(
+                args.get(1).ok_or("lines missing")?,
+                "cursor tooltip scroll lines"
+            );
+            GnvimEvent::CursorTooltipScroll(lines)
+        }
         "PopupmenuSetWidth" => {
             let w =
                 try_u64!(args.get(1).ok_or("width missing")?, "pmenu width");
@@ -1260,18 +1919,779 @@ pub(crate) fn parse_gnvim_event(
 
             GnvimEvent::PopupmenuShowMenuOnAllItems(b != 0)
         }
+        "PopupmenuSetMaxHeight" => {
+            let h = try_u64!(
+                args.get(1).ok_or("height missing")?,
+                "pmenu max height"
+            );
+            GnvimEvent::PopupmenuMaxHeight(h)
+        }
+        "PopupmenuSetMaxRows" => {
+            let rows =
+                try_u64!(args.get(1).ok_or("rows missing")?, "pmenu max rows");
+            GnvimEvent::PopupmenuMaxRows(rows)
+        }
+        "PopupmenuSetPosition" => {
+            let position = try_str!(
+                args.get(1).ok_or("position missing")?,
+                "pmenu position"
+            );
+            GnvimEvent::PopupmenuPosition(
+                PopupmenuPosition::from_string(position).ok_or_else(|| {
+                    format!("Invalid popupmenu position: {}", position)
+                })?,
+            )
+        }
+        "PopupmenuSetDetailsSide" => {
+            let side = try_str!(
+                args.get(1).ok_or("side missing")?,
+                "pmenu details side"
+            );
+            GnvimEvent::PopupmenuDetailsSide(
+                PopupmenuDetailsSide::from_string(side).ok_or_else(|| {
+                    format!("Invalid popupmenu details side: {}", side)
+                })?,
+            )
+        }
+        "PopupmenuScrollDetails" => {
+            let lines = try_i64!(
+                args.get(1).ok_or("lines missing")?,
+                "pmenu scroll details lines"
+            );
+            GnvimEvent::PopupmenuScrollDetails(lines)
+        }
+        "PopupmenuKindGlyph" => {
+            let kind = try_str!(
+                args.get(1).ok_or("kind missing")?,
+                "pmenu kind glyph kind"
+            );
+            let glyph = try_str!(
+                args.get(2).ok_or("glyph missing")?,
+                "pmenu kind glyph"
+            );
+            GnvimEvent::PopupmenuKindGlyph(
+                CompletionItemKind::from_name(kind).ok_or_else(|| {
+                    format!("Invalid completion item kind: {}", kind)
+                })?,
+                String::from(glyph),
+            )
+        }
+        "CmdlinePosition" => {
+            let position = try_str!(
+                args.get(1).ok_or("position missing")?,
+                "cmdline position"
+            );
+            GnvimEvent::CmdlinePosition(
+                CmdlinePosition::from_string(position).ok_or_else(|| {
+                    format!("Invalid cmdline position: {}", position)
+                })?,
+            )
+        }
+        "CmdlineSearchPosition" => {
+            let position = try_str!(
+                args.get(1).ok_or("position missing")?,
+                "cmdline search position"
+            );
+            GnvimEvent::CmdlineSearchPosition(if position.is_empty() {
+                None
+            } else {
+                Some(CmdlinePosition::from_string(position).ok_or_else(
+                    || format!("Invalid cmdline position: {}", position),
+                )?)
+            })
+        }
+        "WildmenuLayout" => {
+            let layout = try_str!(
+                args.get(1).ok_or("layout missing")?,
+                "wildmenu layout"
+            );
+            GnvimEvent::WildmenuLayout(
+                WildmenuLayout::from_string(layout).ok_or_else(|| {
+                    format!("Invalid wildmenu layout: {}", layout)
+                })?,
+            )
+        }
         "EnableCursorAnimations" => GnvimEvent::EnableCursorAnimations(
             try_u64!(
                 args.get(1).ok_or("argument missing")?,
                 "failed to parse enable cursor animations argument"
             ) == 1,
         ),
+        "CursorBlinkCurve" => {
+            let curve = try_str!(
+                args.get(1).ok_or("curve missing")?,
+                "cursor blink curve"
+            );
+            GnvimEvent::CursorBlinkCurve(
+                BlinkCurve::from_string(curve)
+                    .ok_or_else(|| format!("Invalid blink curve: {}", curve))?,
+            )
+        }
+        "EnableCursorHollowUnfocused" => {
+            GnvimEvent::EnableCursorHollowUnfocused(
+                try_u64!(
+                    args.get(1).ok_or("argument missing")?,
+                    "failed to parse enable cursor hollow unfocused argument"
+                ) == 1,
+            )
+        }
+        "SetCrosshair" => {
+            let line =
+                try_u64!(args.get(1).ok_or("line missing")?, "crosshair line");
+            let column = try_u64!(
+                args.get(2).ok_or("column missing")?,
+                "crosshair column"
+            );
+            let color = match try_str!(
+                args.get(3).ok_or("color missing")?,
+                "crosshair color"
+            ) {
+                "" => None,
+                hex => Some(Color::from_hex_string(String::from(hex))?),
+            };
+            let opacity = try_f64!(
+                args.get(4).ok_or("opacity missing")?,
+                "crosshair opacity"
+            );
+            GnvimEvent::SetCrosshair(Crosshair {
+                line: line == 1,
+                column: column == 1,
+                color,
+                opacity: opacity.max(0.0).min(1.0),
+            })
+        }
+        "SetIndentGuides" => {
+            let enable = try_u64!(
+                args.get(1).ok_or("enable missing")?,
+                "indent guides enable"
+            );
+            let color = match try_str!(
+                args.get(2).ok_or("color missing")?,
+                "indent guides color"
+            ) {
+                "" => None,
+                hex => Some(Color::from_hex_string(String::from(hex))?),
+            };
+            let opacity = try_f64!(
+                args.get(3).ok_or("opacity missing")?,
+                "indent guides opacity"
+            );
+            GnvimEvent::SetIndentGuides(IndentGuides {
+                enabled: enable == 1,
+                color,
+                opacity: opacity.max(0.0).min(1.0),
+            })
+        }
+        "IndentInfoChanged" => GnvimEvent::IndentInfoChanged,
+        "WindowBufferChanged" => GnvimEvent::WindowBufferChanged,
+        "SetBackgroundOpacity" => {
+            let opacity = try_f64!(
+                args.get(1).ok_or("opacity missing")?,
+                "background opacity"
+            );
+            GnvimEvent::SetBackgroundOpacity(opacity)
+        }
+        "SetDeviceScale" => {
+            let scale =
+                try_f64!(args.get(1).ok_or("scale missing")?, "device scale");
+            GnvimEvent::SetDeviceScale(Some(scale).filter(|s| *s > 0.0))
+        }
+        "SetBackgroundImage" => {
+            let path = try_str!(
+                args.get(1).ok_or("path missing")?,
+                "background image path"
+            );
+            let blur =
+                try_u64!(args.get(2).ok_or("blur missing")?, "background blur");
+            let dim =
+                try_f64!(args.get(3).ok_or("dim missing")?, "background dim");
+            GnvimEvent::SetBackgroundImage(String::from(path), blur, dim)
+        }
+        "SetCustomCss" => GnvimEvent::SetCustomCss(String::from(try_str!(
+            args.get(1).ok_or("path missing")?,
+            "css path"
+        ))),
+        "ImageShow" => {
+            let id = try_u64!(args.get(1).ok_or("id missing")?, "image id");
+            let path =
+                try_str!(args.get(2).ok_or("path missing")?, "image path");
+            let row = try_u64!(args.get(3).ok_or("row missing")?, "image row");
+            let col = try_u64!(args.get(4).ok_or("col missing")?, "image col");
+            let rows =
+                try_u64!(args.get(5).ok_or("rows missing")?, "image rows");
+            let cols =
+                try_u64!(args.get(6).ok_or("cols missing")?, "image cols");
+            GnvimEvent::ImageShow(
+                id,
+                String::from(path),
+                (row, col),
+                (rows, cols),
+            )
+        }
+        "ImageHide" => GnvimEvent::ImageHide(try_u64!(
+            args.get(1).ok_or("id missing")?,
+            "image id"
+        )),
+        "ImageClear" => GnvimEvent::ImageClear,
+        "SnippetState" => {
+            let win = try_i64!(
+                args.get(1).ok_or("window missing")?,
+                "snippet window"
+            );
+            let current = try_u64!(
+                args.get(2).ok_or("current missing")?,
+                "snippet current"
+            );
+            let total =
+                try_u64!(args.get(3).ok_or("total missing")?, "snippet total");
+            let placeholders = args
+                .get(4)
+                .ok_or("placeholders missing")?
+                .as_array()
+                .ok_or("Value is not an array: snippet placeholders")?
+                .iter()
+                .map(parse_snippet_placeholder)
+                .collect::<Result<Vec<_>, String>>()?;
+            GnvimEvent::SnippetState(
+                win,
+                SnippetState {
+                    current,
+                    total,
+                    placeholders,
+                },
+            )
+        }
+        "SetFloatStyle" => {
+            let radius =
+                try_u64!(args.get(1).ok_or("radius missing")?, "float radius");
+            let shadow =
+                try_u64!(args.get(2).ok_or("shadow missing")?, "float shadow");
+            let border =
+                try_u64!(args.get(3).ok_or("border missing")?, "float border");
+            // Window 0 is all the windows.
+            let win = match args.get(4) {
+                Some(win) => match try_i64!(win, "float window") {
+                    0 => None,
+                    win => Some(win),
+                },
+                None => None,
+            };
+            GnvimEvent::SetFloatStyle(
+                win,
+                FloatOptions {
+                    radius,
+                    shadow: shadow == 1,
+                    border: border == 1,
+                },
+            )
+        }
+        "SetAnimations" => GnvimEvent::SetAnimations {
+            enable: try_u64!(
+                args.get(1).ok_or("enable missing")?,
+                "animations enable"
+            ) == 1,
+            float_open: try_u64!(
+                args.get(2).ok_or("float open duration missing")?,
+                "float open duration"
+            ),
+            window_close: try_u64!(
+                args.get(3).ok_or("window close duration missing")?,
+                "window close duration"
+            ),
+        },
+        "EnableAltAsMeta" => GnvimEvent::EnableAltAsMeta(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse enable alt as meta argument"
+            ) == 1,
+        ),
+        "EnableShortcut" => GnvimEvent::EnableShortcut(
+            try_str!(
+                args.get(1).ok_or("shortcut name missing")?,
+                "shortcut name"
+            )
+            .to_string(),
+            try_u64!(
+                args.get(2).ok_or("argument missing")?,
+                "failed to parse enable shortcut argument"
+            ) == 1,
+        ),
+        "TerminalPalette" => {
+            let colors = args
+                .get(1)
+                .ok_or("colors missing")?
+                .as_array()
+                .ok_or("Value is not an array: terminal palette")?;
+            let palette = colors
+                .iter()
+                .map(|color| match try_str!(color, "terminal palette color") {
+                    "" => Ok(None),
+                    hex => Color::from_hex_string(String::from(hex)).map(Some),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            GnvimEvent::TerminalPalette(palette)
+        }
+        "SetHlGroups" => {
+            let groups = args
+                .get(1)
+                .ok_or("groups missing")?
+                .as_map()
+                .ok_or("Value is not a map: highlight groups")?;
+            let groups = groups
+                .iter()
+                .map(|(name, hl)| {
+                    let name = try_str!(name, "highlight group name");
+                    let hl = hl
+                        .as_map()
+                        .ok_or("Value is not a map: highlight group")?;
+                    Ok((String::from(name), Highlight::from_map_val(hl)))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            GnvimEvent::SetHlGroups(groups)
+        }
+        "SetVirtualText" => {
+            let groups = args
+                .get(1)
+                .ok_or("groups missing")?
+                .as_array()
+                .ok_or("Value is not an array: virtual text groups")?
+                .iter()
+                .map(|group| {
+                    Ok(String::from(try_str!(group, "virtual text group")))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            let opacity = try_f64!(
+                args.get(2).ok_or("opacity missing")?,
+                "virtual text opacity"
+            );
+            let proportional = try_u64!(
+                args.get(3).ok_or("proportional missing")?,
+                "virtual text proportional"
+            );
+            GnvimEvent::SetVirtualText(
+                groups,
+                VirtualText {
+                    opacity: opacity.max(0.0).min(1.0),
+                    proportional: proportional == 1,
+                },
+            )
+        }
+        "TerminalGraphics" => {
+            let id = try_u64!(args.get(1).ok_or("id missing")?, "image id");
+            let sequence = try_str!(
+                args.get(2).ok_or("sequence missing")?,
+                "terminal graphics sequence"
+            );
+            let row = try_u64!(args.get(3).ok_or("row missing")?, "image row");
+            let col = try_u64!(args.get(4).ok_or("col missing")?, "image col");
+            GnvimEvent::TerminalGraphics(id, String::from(sequence), (row, col))
+        }
+        "PreviewUpdate" => {
+            let text =
+                try_str!(args.get(1).ok_or("text missing")?, "preview text");
+            let win = try_i64!(
+                args.get(2).ok_or("window missing")?,
+                "preview window"
+            );
+            GnvimEvent::PreviewUpdate(String::from(text), win)
+        }
+        "PreviewHide" => GnvimEvent::PreviewHide,
+        "SetWinbar" => {
+            let win =
+                try_i64!(args.get(1).ok_or("window missing")?, "winbar window");
+            let segments = args
+                .get(2)
+                .ok_or("segments missing")?
+                .as_array()
+                .ok_or("Value is not an array: winbar segments")?
+                .iter()
+                .map(WinbarSegment::from_value)
+                .collect::<Result<_, _>>()?;
+            GnvimEvent::SetWinbar(win, segments)
+        }
+        "SetSigns" => {
+            let win =
+                try_i64!(args.get(1).ok_or("window missing")?, "signs window");
+            let col =
+                try_u64!(args.get(2).ok_or("column missing")?, "signs column");
+            let width =
+                try_u64!(args.get(3).ok_or("width missing")?, "signs width");
+            let signs = args
+                .get(4)
+                .ok_or("signs missing")?
+                .as_array()
+                .ok_or("Value is not an array: signs")?
+                .iter()
+                .map(|sign| {
+                    let sign =
+                        sign.as_array().ok_or("Value is not an array: sign")?;
+                    let row = try_u64!(
+                        sign.get(0).ok_or("sign row missing")?,
+                        "sign row"
+                    );
+                    let kind = try_str!(
+                        sign.get(1).ok_or("sign kind missing")?,
+                        "sign kind"
+                    );
+                    let kind =
+                        SignKind::from_string(kind).ok_or_else(|| {
+                            format!("Invalid sign kind: {}", kind)
+                        })?;
+                    Ok((row, kind))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            GnvimEvent::SetSigns {
+                win,
+                col,
+                width,
+                signs,
+            }
+        }
+        "DetachTab" => {
+            let id = try_u64!(args.get(1).ok_or("id missing")?, "tab id");
+            let title =
+                try_str!(args.get(2).ok_or("title missing")?, "tab title");
+            let windows = args
+                .get(3)
+                .ok_or("windows missing")?
+                .as_array()
+                .ok_or("Value is not an array: tab windows")?
+                .iter()
+                .map(|win| {
+                    let win = win
+                        .as_array()
+                        .ok_or("Value is not an array: tab window")?;
+                    Ok((
+                        try_i64!(win.get(0).ok_or("window missing")?, "window"),
+                        try_u64!(win.get(1).ok_or("row missing")?, "row"),
+                        try_u64!(win.get(2).ok_or("col missing")?, "col"),
+                    ))
+                })
+                .collect::<Result<_, String>>()?;
+            GnvimEvent::DetachTab {
+                id,
+                title: String::from(title),
+                windows,
+            }
+        }
+        "SetStatusline" => GnvimEvent::SetStatusline(
+            args.get(1)
+                .ok_or("segments missing")?
+                .as_array()
+                .ok_or("Value is not an array: statusline segments")?
+                .iter()
+                .map(StatuslineSegment::from_value)
+                .collect::<Result<_, _>>()?,
+        ),
+        "TablineMode" => {
+            let mode =
+                try_str!(args.get(1).ok_or("mode missing")?, "tabline mode");
+            GnvimEvent::TablineMode(
+                TablineMode::from_string(mode)
+                    .ok_or_else(|| format!("Invalid tabline mode: {}", mode))?,
+            )
+        }
+        "TablineBuffers" => {
+            let current = try_i64!(
+                args.get(1).ok_or("current buffer missing")?,
+                "current buffer"
+            );
+            let buffers = args
+                .get(2)
+                .ok_or("buffers missing")?
+                .as_array()
+                .ok_or("Value is not an array: tabline buffers")?
+                .iter()
+                .map(|buf| {
+                    let buf = buf
+                        .as_array()
+                        .ok_or("Value is not an array: tabline buffer")?;
+                    Ok((
+                        try_i64!(buf.get(0).ok_or("buffer missing")?, "buffer"),
+                        String::from(try_str!(
+                            buf.get(1).ok_or("name missing")?,
+                            "buffer name"
+                        )),
+                        try_u64!(
+                            buf.get(2).ok_or("modified missing")?,
+                            "buffer modified"
+                        ) == 1,
+                    ))
+                })
+                .collect::<Result<_, String>>()?;
+            GnvimEvent::TablineBuffers { current, buffers }
+        }
+        "FileTreeShow" => {
+            let root = try_str!(
+                args.get(1).ok_or("directory missing")?,
+                "file tree directory"
+            );
+            GnvimEvent::FileTreeShow(String::from(root))
+        }
+        "FileTreeHide" => GnvimEvent::FileTreeHide,
+        "SetLinkPatterns" => GnvimEvent::SetLinkPatterns {
+            url: String::from(try_str!(
+                args.get(1).ok_or("url pattern missing")?,
+                "url pattern"
+            )),
+            path: String::from(try_str!(
+                args.get(2).ok_or("path pattern missing")?,
+                "path pattern"
+            )),
+        },
+        "Export" => {
+            let format =
+                try_str!(args.get(1).ok_or("format missing")?, "export format");
+            let format = ExportFormat::from_string(format)
+                .ok_or_else(|| format!("Invalid export format: {}", format))?;
+            let path =
+                try_str!(args.get(2).ok_or("path missing")?, "export path");
+            let lines = match args.get(3) {
+                None | Some(Value::Nil) => None,
+                Some(lines) => Some(
+                    lines
+                        .as_array()
+                        .ok_or("Value is not an array: export lines")?
+                        .iter()
+                        .map(parse_export_line)
+                        .collect::<Result<Vec<_>, String>>()?,
+                ),
+            };
+            GnvimEvent::Export(format, String::from(path), lines)
+        }
+        "Print" => {
+            let title =
+                try_str!(args.get(1).ok_or("title missing")?, "print title");
+            let first =
+                try_u64!(args.get(2).ok_or("line missing")?, "print line");
+            let lines = args
+                .get(3)
+                .ok_or("lines missing")?
+                .as_array()
+                .ok_or("Value is not an array: print lines")?
+                .iter()
+                .map(parse_export_line)
+                .collect::<Result<Vec<_>, String>>()?;
+            GnvimEvent::Print(String::from(title), first, lines)
+        }
+        "Screenshot" => {
+            let path =
+                try_str!(args.get(1).ok_or("path missing")?, "screenshot path");
+            let target = try_str!(
+                args.get(2).ok_or("target missing")?,
+                "screenshot target"
+            );
+            let number = |i: usize, name: &str| -> Result<u64, String> {
+                Ok(try_u64!(
+                    args.get(i).ok_or(format!("{} missing", name))?,
+                    name
+                ))
+            };
+            let target = match target {
+                "window" => ScreenshotTarget::Window,
+                "grid" => ScreenshotTarget::Grid(match args.get(3) {
+                    Some(id) => Some(try_i64!(id, "screenshot grid")),
+                    None => None,
+                }),
+                "cells" => ScreenshotTarget::Cells(
+                    number(3, "row")?,
+                    number(4, "col")?,
+                    number(5, "rows")?,
+                    number(6, "cols")?,
+                ),
+                _ => {
+                    return Err(format!(
+                        "Invalid screenshot target: {}",
+                        target
+                    ))
+                }
+            };
+            GnvimEvent::Screenshot {
+                target,
+                path: if path.is_empty() {
+                    None
+                } else {
+                    Some(String::from(path))
+                },
+            }
+        }
+        "EnableDamageOverlay" => GnvimEvent::EnableDamageOverlay(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse enable damage overlay argument"
+            ) == 1,
+        ),
+        "TogglePerfOverlay" => GnvimEvent::TogglePerfOverlay,
+        "LogViewerShow" => GnvimEvent::LogViewerShow,
+        "DumpGrids" => {
+            let dir =
+                try_str!(args.get(1).ok_or("dir missing")?, "grid dump dir");
+            GnvimEvent::DumpGrids(dir.to_string())
+        }
+        "FontPickerShow" => GnvimEvent::FontPickerShow,
+        "FileOpenDialog" => {
+            GnvimEvent::FileOpenDialog(FileDialog::from_args(&args)?)
+        }
+        "FileSaveDialog" => {
+            GnvimEvent::FileSaveDialog(FileDialog::from_args(&args)?)
+        }
+        "CommandPaletteShow" => GnvimEvent::CommandPaletteShow,
+        "SearchBarShow" => GnvimEvent::SearchBarShow,
+        "FinderShow" => GnvimEvent::FinderShow,
+        "CopyModeEnter" => GnvimEvent::CopyModeEnter,
+        "PasteSetConfirmThreshold" => {
+            let bytes = try_u64!(
+                args.get(1).ok_or("bytes missing")?,
+                "paste confirm threshold"
+            );
+            GnvimEvent::PasteConfirmThreshold(bytes)
+        }
+        "InputSetQueueSize" => {
+            let size = try_u64!(
+                args.get(1).ok_or("size missing")?,
+                "input queue size"
+            );
+            GnvimEvent::InputQueueSize(size)
+        }
+        "DesktopNotify" => {
+            let title = try_str!(
+                args.get(1).ok_or("title missing")?,
+                "notification title"
+            );
+            let body = try_str!(
+                args.get(2).ok_or("body missing")?,
+                "notification body"
+            );
+            let unfocused_only = match args.get(3) {
+                Some(arg) => try_u64!(arg, "notification unfocused only") == 1,
+                None => false,
+            };
+            GnvimEvent::DesktopNotify(
+                String::from(title),
+                String::from(body),
+                unfocused_only,
+            )
+        }
+        "SetFullscreen" => GnvimEvent::SetFullscreen(parse_toggle(
+            args.get(1),
+            "failed to parse fullscreen argument",
+        )?),
+        "SetMaximized" => GnvimEvent::SetMaximized(parse_toggle(
+            args.get(1),
+            "failed to parse maximized argument",
+        )?),
+        "SetKeepAbove" => GnvimEvent::SetKeepAbove(parse_toggle(
+            args.get(1),
+            "failed to parse keep above argument",
+        )?),
+        "SetDecorations" => {
+            let decorations = try_str!(
+                args.get(1).ok_or("decorations missing")?,
+                "window decorations"
+            );
+            GnvimEvent::SetDecorations(
+                Decorations::from_string(decorations).ok_or_else(|| {
+                    format!("Invalid decorations: {}", decorations)
+                })?,
+            )
+        }
+        "SetProgress" => GnvimEvent::SetProgress(match args.get(1) {
+            None | Some(Value::Nil) => None,
+            Some(progress) => Some(try_f64!(progress, "progress")),
+        }),
+        "EnableGuiSelection" => GnvimEvent::EnableGuiSelection(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse enable gui selection argument"
+            ) == 1,
+        ),
+        "EnableKineticScrolling" => GnvimEvent::EnableKineticScrolling(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse enable kinetic scrolling argument"
+            ) == 1,
+        ),
+        "EnableScrollbar" => GnvimEvent::EnableScrollbar(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse enable scrollbar argument"
+            ) == 1,
+        ),
+        "EnableMinimap" => GnvimEvent::EnableMinimap(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse enable minimap argument"
+            ) == 1,
+        ),
+        "EnableFloatDrag" => GnvimEvent::EnableFloatDrag(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse enable float drag argument"
+            ) == 1,
+        ),
         _ => GnvimEvent::Unknown(String::from(cmd)),
     };
 
     Ok(res)
 }
 
+/// Parses an optional on/off argument. A missing (or negative) argument
+/// means toggle, and is returned as `None`.
+fn parse_toggle(
+    arg: Option<&Value>,
+    msg: &str,
+) -> Result<Option<bool>, String> {
+    match arg {
+        Some(arg) => {
+            let value = try_i64!(arg, msg);
+            Ok(if value < 0 { None } else { Some(value == 1) })
+        }
+        None => Ok(None),
+    }
+}
+
+/// Parses a line of `Export` (and `Print`) as [text, highlight] pairs, where
+/// the highlight is a map like `nvim_get_hl_by_id()` returns.
+fn parse_export_line(line: &Value) -> Result<Vec<(String, Highlight)>, String> {
+    line.as_array()
+        .ok_or("Value is not an array: export line")?
+        .iter()
+        .map(|segment| {
+            let segment = segment
+                .as_array()
+                .ok_or("Value is not an array: export segment")?;
+            let text =
+                try_str!(segment.get(0).ok_or("text missing")?, "export text");
+            let hl = segment
+                .get(1)
+                .and_then(Value::as_map)
+                .ok_or("Value is not a map: export highlight")?;
+            Ok((String::from(text), Highlight::from_map_val(hl)))
+        })
+        .collect()
+}
+
+/// Parses a snippet placeholder, `[index, row, col, len]`.
+fn parse_snippet_placeholder(
+    placeholder: &Value,
+) -> Result<SnippetPlaceholder, String> {
+    let placeholder = placeholder
+        .as_array()
+        .ok_or("Value is not an array: snippet placeholder")?;
+    let get = |i: usize, name: &str| {
+        placeholder.get(i).and_then(Value::as_u64).ok_or_else(|| {
+            format!("Value is not a number: placeholder {}", name)
+        })
+    };
+
+    Ok(SnippetPlaceholder {
+        index: get(0, "index")?,
+        row: get(1, "row")?,
+        col: get(2, "col")?,
+        len: get(3, "len")?,
+    })
+}
+
 fn map_to_hash<'a>(val: &'a Value) -> HashMap<&'a str, &'a Value> {
     let mut h = HashMap::new();
     for (prop, val) in unwrap_map!(val) {
@@ -0,0 +1,196 @@
+//! Recording of nvim's redraw notifications, and replaying them later. Used
+//! for reproducing rendering bugs and for comparing the performance of the
+//! event handling between builds.
+//!
+//! A recording is a sequence of msgpack arrays, one per redraw notification:
+//! `[milliseconds since the start, args]`.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::error;
+use rmpv::Value;
+
+use crate::nvim_bridge::{parse_redraw_event, Message, Notify};
+
+/// Writes redraw notifications to a file, as they come in.
+pub struct Recorder {
+    file: Mutex<File>,
+    start: Instant,
+}
+
+impl Recorder {
+    pub fn new(path: &Path) -> io::Result<Self> {
+        Ok(Recorder {
+            file: Mutex::new(File::create(path)?),
+            start: Instant::now(),
+        })
+    }
+
+    /// Records the args of a redraw notification.
+    pub fn record(&self, args: &[Value]) {
+        let entry = Value::Array(vec![
+            Value::from(self.start.elapsed().as_millis() as u64),
+            Value::Array(args.to_vec()),
+        ]);
+
+        let mut buf = vec![];
+        if let Err(err) = rmpv::encode::write_value(&mut buf, &entry) {
+            error!("Failed to encode redraw event recording: {}", err);
+            return;
+        }
+
+        // Written right away, so that the recording is there even if gnvim
+        // crashes.
+        if let Ok(mut file) = self.file.lock() {
+            if let Err(err) = file.write_all(&buf) {
+                error!("Failed to write redraw event recording: {}", err);
+            }
+        }
+    }
+}
+
+/// A recorded redraw notification.
+pub struct Entry {
+    /// When the notification came, since the start of the recording.
+    pub time: Duration,
+    pub args: Vec<Value>,
+}
+
+/// Reads the recording at `path`.
+pub fn read(path: &Path) -> Result<Vec<Entry>, String> {
+    let data = std::fs::read(path).map_err(|err| {
+        format!("failed to read '{}': {}", path.display(), err)
+    })?;
+
+    parse(&data)
+}
+
+fn parse(mut data: &[u8]) -> Result<Vec<Entry>, String> {
+    let mut entries = vec![];
+
+    while !data.is_empty() {
+        let value = rmpv::decode::read_value(&mut data)
+            .map_err(|err| format!("invalid recording: {}", err))?;
+        let entry = match value {
+            Value::Array(mut entry) if entry.len() == 2 => {
+                let args = entry.pop().unwrap();
+                let time = entry.pop().unwrap();
+                match (time.as_u64(), args) {
+                    (Some(time), Value::Array(args)) => Entry {
+                        time: Duration::from_millis(time),
+                        args,
+                    },
+                    _ => return Err("invalid recording entry".to_string()),
+                }
+            }
+            _ => return Err("invalid recording entry".to_string()),
+        };
+
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// Replays `entries` to the UI through `tx`, with the recorded delays
+/// between them divided by `speed`. With a speed of 0, there are no delays.
+pub fn replay(entries: Vec<Entry>, tx: glib::Sender<Message>, speed: f64) {
+    let c = glib::MainContext::default();
+    c.spawn_local(async move {
+        let mut prev = Duration::default();
+        for entry in entries {
+            if speed > 0.0 {
+                let delay = entry.time.saturating_sub(prev).div_f64(speed);
+                if delay.as_millis() > 0 {
+                    glib::timeout_future(delay.as_millis() as u32).await;
+                }
+            }
+            prev = entry.time;
+
+            let events = parse_redraw_event(entry.args);
+            let msg = Message::Notify(Notify::RedrawEvent(events));
+            if tx.send(msg).is_err() {
+                // The UI is gone.
+                return;
+            }
+        }
+    });
+}
+
+/// Counts of the events of a recording, and how long parsing them took.
+#[derive(Default)]
+pub struct Summary {
+    pub notifications: usize,
+    pub events: BTreeMap<String, usize>,
+    pub parse_time: Duration,
+}
+
+/// Replays `entries` without the UI, only parsing the events.
+pub fn replay_headless(entries: Vec<Entry>) -> Summary {
+    let mut summary = Summary::default();
+
+    for entry in entries {
+        let start = Instant::now();
+        let events = parse_redraw_event(entry.args);
+        summary.parse_time += start.elapsed();
+
+        summary.notifications += 1;
+        for event in events {
+            *summary.events.entry(event.to_string()).or_default() += 1;
+        }
+    }
+
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flush() -> Value {
+        Value::Array(vec![Value::from("flush"), Value::Array(vec![])])
+    }
+
+    #[test]
+    fn test_parse() {
+        let mut data = vec![];
+        for time in &[0u64, 16] {
+            let entry = Value::Array(vec![
+                Value::from(*time),
+                Value::Array(vec![flush()]),
+            ]);
+            rmpv::encode::write_value(&mut data, &entry).unwrap();
+        }
+
+        let entries = parse(&data).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].time, Duration::from_millis(16));
+        assert_eq!(entries[1].args, vec![flush()]);
+
+        assert!(parse(&data[..data.len() - 1]).is_err());
+        assert!(parse(&[0xc0]).is_err());
+    }
+
+    #[test]
+    fn test_replay_headless() {
+        let entries = vec![
+            Entry {
+                time: Duration::default(),
+                args: vec![flush(), flush()],
+            },
+            Entry {
+                time: Duration::from_millis(10),
+                args: vec![flush()],
+            },
+        ];
+
+        let summary = replay_headless(entries);
+        assert_eq!(summary.notifications, 2);
+        assert_eq!(summary.events.get("Flush"), Some(&3));
+    }
+}
@@ -16,13 +16,14 @@ mod parse_redraw_event_tests {
 
     use crate::nvim_bridge;
     use crate::nvim_bridge::{
-        Cell, CmdlineBlockAppend, CmdlinePos, CmdlineShow, CmdlineSpecialChar,
-        CompletionItem, CompletionItemKind, CursorShape, DefaultColorsSet,
-        GridCursorGoto, GridLineSegment, GridResize, GridScroll, HlAttrDefine,
-        ModeChange, ModeInfo, ModeInfoSet, OptionSet, PopupmenuShow,
-        RedrawEvent, TablineUpdate,
+        Anchor, Cell, CmdlineBlockAppend, CmdlinePos, CmdlineShow,
+        CmdlineSpecialChar, CompletionItem, CompletionItemKind, CursorShape,
+        DefaultColorsSet, GridCursorGoto, GridLineSegment, GridResize,
+        GridScroll, HlAttrDefine, ModeChange, ModeInfo, ModeInfoSet,
+        MsgHistoryShow, MsgShow, OptionSet, PopupmenuShow, RedrawEvent,
+        TablineUpdate, WindowFloatPos, WindowViewport,
     };
-    use crate::ui::color::{Color, Highlight};
+    use crate::ui::color::{Color, Highlight, VirtualText};
     use rmpv::Value;
 
     #[test]
@@ -194,9 +195,11 @@ mod parse_redraw_event_tests {
     fn default_colors_set() {
         let expected =
             vec![RedrawEvent::DefaultColorsSet(vec![DefaultColorsSet {
-                fg: Color::from_u64(321921),
-                bg: Color::from_u64(94921),
+                fg: Some(Color::from_u64(321921)),
+                bg: Some(Color::from_u64(94921)),
                 sp: Color::from_u64(983821232),
+                cterm_fg: None,
+                cterm_bg: None,
             }])];
 
         let res = nvim_bridge::parse_redraw_event(args!(
@@ -212,9 +215,11 @@ mod parse_redraw_event_tests {
     fn default_colors_set2() {
         let expected =
             vec![RedrawEvent::DefaultColorsSet(vec![DefaultColorsSet {
-                fg: Color::from_u64(0),
-                bg: Color::from_u64(std::u64::MAX),
+                fg: None,
+                bg: None,
                 sp: Color::from_u64(16711680),
+                cterm_fg: None,
+                cterm_bg: None,
             }])];
 
         let res = nvim_bridge::parse_redraw_event(args!(
@@ -229,11 +234,64 @@ mod parse_redraw_event_tests {
         assert_eq!(expected, res);
     }
 
+    /// Test terminal palette colors (with `ext_termcolors`).
+    #[test]
+    fn default_colors_set_cterm() {
+        let expected =
+            vec![RedrawEvent::DefaultColorsSet(vec![DefaultColorsSet {
+                fg: Some(Color::from_u64(321921)),
+                bg: None,
+                sp: Color::from_u64(16711680),
+                cterm_fg: None,
+                cterm_bg: Some(3),
+            }])];
+
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "default_colors_set".into(),
+            Value::Array(vec!(
+                321921.into(),
+                (-1 as i64).into(),
+                (-1 as i64).into(),
+                7.into(),
+                4.into(),
+            ))
+        ));
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn default_colors_set_cterm_unset() {
+        let expected =
+            vec![RedrawEvent::DefaultColorsSet(vec![DefaultColorsSet {
+                fg: None,
+                bg: None,
+                sp: Color::from_u64(16711680),
+                cterm_fg: None,
+                cterm_bg: Some(0),
+            }])];
+
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "default_colors_set".into(),
+            Value::Array(vec!(
+                (-1 as i64).into(),
+                (-1 as i64).into(),
+                (-1 as i64).into(),
+                0.into(),
+                1.into(),
+            ))
+        ));
+
+        assert_eq!(expected, res);
+    }
+
     #[test]
     fn hl_attr_define() {
         let expected = vec![RedrawEvent::HlAttrDefine(vec![
             HlAttrDefine {
                 id: 1,
+                url: None,
+                groups: vec![],
                 hl: Highlight {
                     foreground: Some(Color::from_u64(3215)),
                     background: Some(Color::from_u64(214)),
@@ -243,10 +301,17 @@ mod parse_redraw_event_tests {
                     bold: true,
                     underline: true,
                     undercurl: false,
+                    underdouble: false,
+                    underdashed: false,
+                    underdotted: false,
+                    blend: 0,
+                    virtual_text: None,
                 },
             },
             HlAttrDefine {
                 id: 42,
+                url: None,
+                groups: vec![],
                 hl: Highlight {
                     foreground: Some(Color::from_u64(3215)),
                     background: None,
@@ -256,10 +321,17 @@ mod parse_redraw_event_tests {
                     bold: true,
                     underline: false,
                     undercurl: true,
+                    underdouble: false,
+                    underdashed: false,
+                    underdotted: false,
+                    blend: 0,
+                    virtual_text: None,
                 },
             },
             HlAttrDefine {
                 id: 32,
+                url: None,
+                groups: vec![],
                 hl: Highlight {
                     foreground: Some(Color::from_u64(215)),
                     background: Some(Color::from_u64(315)),
@@ -269,10 +341,17 @@ mod parse_redraw_event_tests {
                     bold: true,
                     underline: false,
                     undercurl: true,
+                    underdouble: false,
+                    underdashed: false,
+                    underdotted: false,
+                    blend: 0,
+                    virtual_text: None,
                 },
             },
             HlAttrDefine {
                 id: 3,
+                url: None,
+                groups: vec![],
                 hl: Highlight {
                     foreground: None,
                     background: None,
@@ -282,6 +361,11 @@ mod parse_redraw_event_tests {
                     bold: false,
                     underline: false,
                     undercurl: false,
+                    underdouble: false,
+                    underdashed: false,
+                    underdotted: false,
+                    blend: 0,
+                    virtual_text: None,
                 },
             },
         ])];
@@ -328,17 +412,193 @@ mod parse_redraw_event_tests {
         assert_eq!(expected, res);
     }
 
+    #[test]
+    fn hl_attr_define_underline_styles() {
+        let hl = |underdouble, underdashed, underdotted| Highlight {
+            special: Some(Color::from_u64(2019092)),
+            underdouble,
+            underdashed,
+            underdotted,
+            ..Highlight::default()
+        };
+
+        let expected = vec![RedrawEvent::HlAttrDefine(vec![
+            HlAttrDefine {
+                id: 1,
+                url: None,
+                groups: vec![],
+                hl: hl(true, false, false),
+            },
+            HlAttrDefine {
+                id: 2,
+                url: None,
+                groups: vec![],
+                hl: hl(false, true, false),
+            },
+            HlAttrDefine {
+                id: 3,
+                url: None,
+                groups: vec![],
+                hl: hl(false, false, true),
+            },
+            HlAttrDefine {
+                id: 4,
+                url: None,
+                groups: vec![],
+                hl: hl(true, true, true),
+            },
+        ])];
+
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "hl_attr_define".into(),
+            Value::Array(vec!(
+                1.into(),
+                Value::Map(vec!(
+                    ("special".into(), 2019092.into()),
+                    ("underdouble".into(), true.into()),
+                )),
+            )),
+            Value::Array(vec!(
+                2.into(),
+                Value::Map(vec!(
+                    ("special".into(), 2019092.into()),
+                    ("underdashed".into(), true.into()),
+                )),
+            )),
+            Value::Array(vec!(
+                3.into(),
+                Value::Map(vec!(
+                    ("special".into(), 2019092.into()),
+                    ("underdotted".into(), true.into()),
+                )),
+            )),
+            Value::Array(vec!(
+                4.into(),
+                Value::Map(vec!(
+                    ("special".into(), 2019092.into()),
+                    ("underlineline".into(), true.into()),
+                    ("underdash".into(), true.into()),
+                    ("underdot".into(), true.into()),
+                )),
+            ))
+        ));
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn hl_attr_define_blend() {
+        let expected = vec![RedrawEvent::HlAttrDefine(vec![
+            HlAttrDefine {
+                id: 1,
+                url: None,
+                groups: vec![],
+                hl: Highlight {
+                    background: Some(Color::from_u64(315)),
+                    blend: 30,
+                    ..Highlight::default()
+                },
+            },
+            HlAttrDefine {
+                id: 2,
+                url: None,
+                groups: vec![],
+                hl: Highlight {
+                    blend: 100,
+                    ..Highlight::default()
+                },
+            },
+        ])];
+
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "hl_attr_define".into(),
+            Value::Array(vec!(
+                1.into(),
+                Value::Map(vec!(
+                    ("background".into(), 315.into()),
+                    ("blend".into(), 30.into()),
+                )),
+            )),
+            Value::Array(vec!(
+                2.into(),
+                Value::Map(vec!(("blend".into(), 200.into()))),
+            ))
+        ));
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn hl_attr_define_url() {
+        let expected = vec![RedrawEvent::HlAttrDefine(vec![HlAttrDefine {
+            id: 1,
+            url: Some("https://neovim.io".into()),
+            groups: vec![],
+            hl: Highlight {
+                underline: true,
+                ..Highlight::default()
+            },
+        }])];
+
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "hl_attr_define".into(),
+            Value::Array(vec!(
+                1.into(),
+                Value::Map(vec!(
+                    ("underline".into(), true.into()),
+                    ("url".into(), "https://neovim.io".into()),
+                )),
+            ))
+        ));
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn hl_attr_define_groups() {
+        let expected = vec![RedrawEvent::HlAttrDefine(vec![HlAttrDefine {
+            id: 1,
+            url: None,
+            groups: vec!["Comment".into(), "GitBlame".into()],
+            hl: Highlight {
+                italic: true,
+                ..Highlight::default()
+            },
+        }])];
+
+        let info = |name: &str| {
+            Value::Map(vec![
+                ("kind".into(), "syntax".into()),
+                ("hi_name".into(), name.into()),
+                ("id".into(), 10.into()),
+            ])
+        };
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "hl_attr_define".into(),
+            Value::Array(vec!(
+                1.into(),
+                Value::Map(vec!(("italic".into(), true.into()))),
+                Value::Map(vec!()),
+                Value::Array(vec!(info("Comment"), info("GitBlame"))),
+            ))
+        ));
+
+        assert_eq!(expected, res);
+    }
+
     #[test]
     fn option_set() {
         let expected = vec![RedrawEvent::OptionSet(vec![
             OptionSet::GuiFont("my awesome font:h32".into()),
             OptionSet::LineSpace(32),
+            OptionSet::PumBlend(20),
         ])];
 
         let res = nvim_bridge::parse_redraw_event(args!(
             "option_set".into(),
             Value::Array(vec!("guifont".into(), "my awesome font:h32".into(),)),
-            Value::Array(vec!("linespace".into(), 32.into()))
+            Value::Array(vec!("linespace".into(), 32.into())),
+            Value::Array(vec!("pumblend".into(), 20.into()))
         ));
 
         assert_eq!(expected, res);
@@ -350,14 +610,20 @@ mod parse_redraw_event_tests {
             cursor_shape_enabled: true,
             mode_info: vec![
                 ModeInfo {
+                    blink_wait: 0,
                     blink_on: 32,
+                    blink_off: 0,
                     cursor_shape: CursorShape::Horizontal,
                     cell_percentage: 0.32,
+                    attr_id: 0,
                 },
                 ModeInfo {
+                    blink_wait: 700,
                     blink_on: 1,
+                    blink_off: 400,
                     cursor_shape: CursorShape::Block,
                     cell_percentage: 1.0,
+                    attr_id: 42,
                 },
             ],
         }])];
@@ -373,9 +639,12 @@ mod parse_redraw_event_tests {
                         ("cell_percentage".into(), 32.into()),
                     )),
                     Value::Map(vec!(
+                        ("blinkwait".into(), 700.into()),
                         ("blinkon".into(), 1.into()),
+                        ("blinkoff".into(), 400.into()),
                         ("cursor_shape".into(), "block".into()),
                         ("cell_percentage".into(), 100.into()),
+                        ("attr_id".into(), 42.into()),
                     )),
                 )),
             ))
@@ -508,6 +777,8 @@ mod parse_redraw_event_tests {
                 ("bar".into(), "bar_name".into()),
                 ("ugh".into(), "ugh_name".into()),
             ],
+            current_buffer: None,
+            buffers: vec![],
         }])];
 
         let res = nvim_bridge::parse_redraw_event(args!(
@@ -530,6 +801,43 @@ mod parse_redraw_event_tests {
         assert_eq!(expected, res);
     }
 
+    #[test]
+    fn tabline_update_with_buffers() {
+        let expected = vec![RedrawEvent::TablineUpdate(vec![TablineUpdate {
+            current: "foo".into(),
+            tabs: vec![("foo".into(), "foo_name".into())],
+            current_buffer: Some(2.into()),
+            buffers: vec![
+                (1.into(), "buf_one".into()),
+                (2.into(), "buf_two".into()),
+            ],
+        }])];
+
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "tabline_update".into(),
+            Value::Array(vec!(
+                "foo".into(),
+                Value::Array(vec!(Value::Map(vec!(
+                    ("tab".into(), "foo".into()),
+                    ("name".into(), "foo_name".into()),
+                )),)),
+                2.into(),
+                Value::Array(vec!(
+                    Value::Map(vec!(
+                        ("buffer".into(), 1.into()),
+                        ("name".into(), "buf_one".into()),
+                    )),
+                    Value::Map(vec!(
+                        ("buffer".into(), 2.into()),
+                        ("name".into(), "buf_two".into()),
+                    )),
+                )),
+            ))
+        ));
+
+        assert_eq!(expected, res);
+    }
+
     #[test]
     fn cmdline_show() {
         let expected = vec![RedrawEvent::CmdlineShow(vec![CmdlineShow {
@@ -645,95 +953,313 @@ mod parse_redraw_event_tests {
 
         assert_eq!(expected, res);
     }
-}
 
-mod parse_gnvim_event_tests {
+    #[test]
+    fn msg_show() {
+        let expected = vec![RedrawEvent::MsgShow(vec![MsgShow {
+            kind: "emsg".to_string(),
+            content: vec![(3, "E492: Not an editor command".to_string())],
+            replace_last: true,
+        }])];
 
-    use crate::nvim_bridge;
-    use crate::nvim_bridge::GnvimEvent;
-    use rmpv::Value;
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "msg_show".into(),
+            Value::Array(vec!(
+                "emsg".into(),
+                Value::Array(vec!(Value::Array(vec!(
+                    3.into(),
+                    "E492: Not an editor command".into(),
+                )),)),
+                true.into(),
+            ))
+        ));
+
+        assert_eq!(expected, res);
+    }
 
     #[test]
-    fn completion_menu_toggle_info() {
-        let expected: Result<GnvimEvent, String> =
-            Ok(GnvimEvent::CompletionMenuToggleInfo);
+    fn msg_clear() {
+        let expected = vec![RedrawEvent::MsgClear()];
 
-        let res = nvim_bridge::parse_gnvim_event(vec![
-            "CompletionMenuToggleInfo".into(),
-        ]);
+        let res = nvim_bridge::parse_redraw_event(args!("msg_clear".into()));
 
         assert_eq!(expected, res);
     }
 
     #[test]
-    fn cursor_tooltip_load_style() {
-        let expected: Result<GnvimEvent, String> =
-            Ok(GnvimEvent::CursorTooltipLoadStyle("foobar".to_owned()));
+    fn msg_showmode() {
+        let expected = vec![RedrawEvent::MsgShowmode(vec![vec![(
+            5,
+            "-- INSERT --".to_string(),
+        )]])];
 
-        let res = nvim_bridge::parse_gnvim_event(vec![
-            "CursorTooltipLoadStyle".into(),
-            "foobar".into(),
-        ]);
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "msg_showmode".into(),
+            Value::Array(vec!(Value::Array(vec!(Value::Array(vec!(
+                5.into(),
+                "-- INSERT --".into(),
+            )),)),))
+        ));
 
         assert_eq!(expected, res);
     }
 
     #[test]
-    fn cursor_tooltip_show() {
-        let expected: Result<GnvimEvent, String> =
-            Ok(GnvimEvent::CursorTooltipShow("foobar".to_owned(), 3, 6));
+    fn msg_showcmd() {
+        let expected = vec![RedrawEvent::MsgShowcmd(vec![vec![]])];
 
-        let res = nvim_bridge::parse_gnvim_event(vec![
-            "CursorTooltipShow".into(),
-            "foobar".into(),
-            3.into(),
-            6.into(),
-        ]);
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "msg_showcmd".into(),
+            Value::Array(vec!(Value::Array(vec!()),))
+        ));
 
         assert_eq!(expected, res);
     }
 
     #[test]
-    fn cursor_tooltip_hide() {
-        let expected: Result<GnvimEvent, String> =
-            Ok(GnvimEvent::CursorTooltipHide);
+    fn msg_ruler() {
+        let expected = vec![RedrawEvent::MsgRuler(vec![vec![(
+            0,
+            "12,5           All".to_string(),
+        )]])];
 
-        let res =
-            nvim_bridge::parse_gnvim_event(vec!["CursorTooltipHide".into()]);
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "msg_ruler".into(),
+            Value::Array(vec!(Value::Array(vec!(Value::Array(vec!(
+                0.into(),
+                "12,5           All".into(),
+            )),)),))
+        ));
 
         assert_eq!(expected, res);
     }
 
     #[test]
-    fn popupmenu_set_width() {
-        let expected: Result<GnvimEvent, String> =
-            Ok(GnvimEvent::PopupmenuWidth(432));
+    fn msg_history_show() {
+        let expected =
+            vec![RedrawEvent::MsgHistoryShow(vec![MsgHistoryShow {
+                entries: vec![
+                    ("echomsg".to_string(), vec![(0, "foo".to_string())]),
+                    ("wmsg".to_string(), vec![(4, "bar".to_string())]),
+                ],
+            }])];
 
-        let res = nvim_bridge::parse_gnvim_event(vec![
-            "PopupmenuSetWidth".into(),
-            432.into(),
-        ]);
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "msg_history_show".into(),
+            Value::Array(vec!(Value::Array(vec!(
+                Value::Array(vec!(
+                    "echomsg".into(),
+                    Value::Array(vec!(Value::Array(vec!(
+                        0.into(),
+                        "foo".into()
+                    )),)),
+                )),
+                Value::Array(vec!(
+                    "wmsg".into(),
+                    Value::Array(vec!(Value::Array(vec!(
+                        4.into(),
+                        "bar".into()
+                    )),)),
+                )),
+            )),))
+        ));
 
         assert_eq!(expected, res);
     }
 
     #[test]
-    fn popupmenu_set_width_details() {
-        let expected: Result<GnvimEvent, String> =
-            Ok(GnvimEvent::PopupmenuWidthDetails(929));
+    fn win_float_pos() {
+        let expected = vec![RedrawEvent::WindowFloatPos(vec![
+            WindowFloatPos {
+                grid: 4,
+                win: 1002.into(),
+                anchor: Anchor::NE,
+                anchor_grid: 2,
+                anchor_row: 3.0,
+                anchor_col: 5.5,
+                focusable: true,
+                zindex: 200,
+            },
+            WindowFloatPos {
+                grid: 5,
+                win: 1003.into(),
+                anchor: Anchor::NW,
+                anchor_grid: 1,
+                anchor_row: 0.0,
+                anchor_col: 0.0,
+                focusable: false,
+                zindex: 50,
+            },
+        ])];
 
-        let res = nvim_bridge::parse_gnvim_event(vec![
-            "PopupmenuSetWidthDetails".into(),
-            929.into(),
-        ]);
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "win_float_pos".into(),
+            Value::Array(vec!(
+                4.into(),
+                1002.into(),
+                "NE".into(),
+                2.into(),
+                3.0.into(),
+                5.5.into(),
+                true.into(),
+                200.into(),
+            )),
+            Value::Array(vec!(
+                5.into(),
+                1003.into(),
+                "NW".into(),
+                1.into(),
+                0.0.into(),
+                0.0.into(),
+                false.into(),
+            ))
+        ));
 
         assert_eq!(expected, res);
     }
 
     #[test]
-    fn popupmenu_menu_on_all_items() {
-        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
-            (
+    fn win_viewport() {
+        let expected = vec![RedrawEvent::WindowViewport(vec![
+            WindowViewport {
+                grid: 2,
+                win: 1000.into(),
+                topline: 10,
+                botline: 40,
+                curline: 12,
+                curcol: 4,
+                line_count: Some(120),
+            },
+            WindowViewport {
+                grid: 3,
+                win: 1001.into(),
+                topline: 0,
+                botline: 5,
+                curline: 0,
+                curcol: 0,
+                line_count: None,
+            },
+        ])];
+
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "win_viewport".into(),
+            Value::Array(vec!(
+                2.into(),
+                1000.into(),
+                10.into(),
+                40.into(),
+                12.into(),
+                4.into(),
+                120.into(),
+            )),
+            Value::Array(vec!(
+                3.into(),
+                1001.into(),
+                0.into(),
+                5.into(),
+                0.into(),
+                0.into(),
+            ))
+        ));
+
+        assert_eq!(expected, res);
+    }
+}
+
+mod parse_gnvim_event_tests {
+
+    use crate::nvim_bridge;
+    use crate::nvim_bridge::{CompletionItemKind, GnvimEvent};
+    use crate::ui::color::{Color, Highlight, VirtualText};
+    use crate::ui::{
+        BlinkCurve, CmdlinePosition, Crosshair, Decorations, ExportFormat,
+        FloatOptions, IndentGuides, PopupmenuDetailsSide, PopupmenuPosition,
+        ScreenshotTarget, SignKind, SnippetPlaceholder, SnippetState,
+        TablineMode, WildmenuLayout,
+    };
+    use rmpv::Value;
+
+    #[test]
+    fn completion_menu_toggle_info() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::CompletionMenuToggleInfo);
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "CompletionMenuToggleInfo".into(),
+        ]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn cursor_tooltip_load_style() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::CursorTooltipLoadStyle("foobar".to_owned()));
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "CursorTooltipLoadStyle".into(),
+            "foobar".into(),
+        ]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn cursor_tooltip_show() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::CursorTooltipShow("foobar".to_owned(), 3, 6));
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "CursorTooltipShow".into(),
+            "foobar".into(),
+            3.into(),
+            6.into(),
+        ]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn cursor_tooltip_hide() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::CursorTooltipHide);
+
+        let res =
+            nvim_bridge::parse_gnvim_event(vec!["CursorTooltipHide".into()]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn popupmenu_set_width() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::PopupmenuWidth(432));
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "PopupmenuSetWidth".into(),
+            432.into(),
+        ]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn popupmenu_set_width_details() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::PopupmenuWidthDetails(929));
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "PopupmenuSetWidthDetails".into(),
+            929.into(),
+        ]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn popupmenu_menu_on_all_items() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
                 Ok(GnvimEvent::PopupmenuShowMenuOnAllItems(true)),
                 vec!["PopupmenuShowMenuOnAllItems".into(), 1.into()],
             ),
@@ -749,4 +1275,1842 @@ mod parse_gnvim_event_tests {
             assert_eq!(expected, res);
         }
     }
+
+    #[test]
+    fn popupmenu_set_max_height() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::PopupmenuMaxHeight(300));
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "PopupmenuSetMaxHeight".into(),
+            300.into(),
+        ]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn popupmenu_set_max_rows() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::PopupmenuMaxRows(10));
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "PopupmenuSetMaxRows".into(),
+            10.into(),
+        ]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn popupmenu_set_position() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::PopupmenuPosition(PopupmenuPosition::Above)),
+                vec!["PopupmenuSetPosition".into(), "above".into()],
+            ),
+            (
+                Ok(GnvimEvent::PopupmenuPosition(PopupmenuPosition::Below)),
+                vec!["PopupmenuSetPosition".into(), "below".into()],
+            ),
+            (
+                Err("Invalid popupmenu position: left".to_string()),
+                vec!["PopupmenuSetPosition".into(), "left".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn popupmenu_set_details_side() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::PopupmenuDetailsSide(
+                    PopupmenuDetailsSide::Left,
+                )),
+                vec!["PopupmenuSetDetailsSide".into(), "left".into()],
+            ),
+            (
+                Ok(GnvimEvent::PopupmenuDetailsSide(
+                    PopupmenuDetailsSide::Auto,
+                )),
+                vec!["PopupmenuSetDetailsSide".into(), "auto".into()],
+            ),
+            (
+                Err("Invalid popupmenu details side: above".to_string()),
+                vec!["PopupmenuSetDetailsSide".into(), "above".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn popupmenu_scroll_details() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::PopupmenuScrollDetails(3)),
+                vec!["PopupmenuScrollDetails".into(), 3.into()],
+            ),
+            (
+                Ok(GnvimEvent::PopupmenuScrollDetails(-3)),
+                vec!["PopupmenuScrollDetails".into(), (-3).into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_damage_overlay() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableDamageOverlay(true)),
+                vec!["EnableDamageOverlay".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableDamageOverlay(false)),
+                vec!["EnableDamageOverlay".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_kinetic_scrolling() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableKineticScrolling(true)),
+                vec!["EnableKineticScrolling".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableKineticScrolling(false)),
+                vec!["EnableKineticScrolling".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn font_picker_show() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::FontPickerShow);
+
+        let res = nvim_bridge::parse_gnvim_event(vec!["FontPickerShow".into()]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn cursor_blink_curve() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::CursorBlinkCurve(BlinkCurve::EaseInOut)),
+                vec!["CursorBlinkCurve".into(), "ease".into()],
+            ),
+            (
+                Ok(GnvimEvent::CursorBlinkCurve(BlinkCurve::Step)),
+                vec!["CursorBlinkCurve".into(), "step".into()],
+            ),
+            (
+                Err("Invalid blink curve: foo".into()),
+                vec!["CursorBlinkCurve".into(), "foo".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_cursor_hollow_unfocused() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableCursorHollowUnfocused(true)),
+                vec!["EnableCursorHollowUnfocused".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableCursorHollowUnfocused(false)),
+                vec!["EnableCursorHollowUnfocused".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn set_background_opacity() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::SetBackgroundOpacity(0.8)),
+                vec!["SetBackgroundOpacity".into(), 0.8.into()],
+            ),
+            (
+                Ok(GnvimEvent::SetBackgroundOpacity(1.0)),
+                vec!["SetBackgroundOpacity".into(), 1.into()],
+            ),
+            (
+                Err("Value is not a number: background opacity".into()),
+                vec!["SetBackgroundOpacity".into(), "foo".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn set_background_image() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::SetBackgroundImage(
+                    "/tmp/bg.png".into(),
+                    8,
+                    0.5,
+                )),
+                vec![
+                    "SetBackgroundImage".into(),
+                    "/tmp/bg.png".into(),
+                    8.into(),
+                    0.5.into(),
+                ],
+            ),
+            (
+                Ok(GnvimEvent::SetBackgroundImage("".into(), 0, 0.0)),
+                vec![
+                    "SetBackgroundImage".into(),
+                    "".into(),
+                    0.into(),
+                    0.into(),
+                ],
+            ),
+            (
+                Err("Value is not a number: background dim".into()),
+                vec![
+                    "SetBackgroundImage".into(),
+                    "/tmp/bg.png".into(),
+                    0.into(),
+                    "foo".into(),
+                ],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn popupmenu_kind_glyph() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::PopupmenuKindGlyph(
+                    CompletionItemKind::EnumMember,
+                    "\u{f02b}".into(),
+                )),
+                vec![
+                    "PopupmenuKindGlyph".into(),
+                    "EnumMember".into(),
+                    "\u{f02b}".into(),
+                ],
+            ),
+            (
+                Ok(GnvimEvent::PopupmenuKindGlyph(
+                    CompletionItemKind::Function,
+                    "".into(),
+                )),
+                vec!["PopupmenuKindGlyph".into(), "Function".into(), "".into()],
+            ),
+            (
+                Err("Invalid completion item kind: foo".into()),
+                vec!["PopupmenuKindGlyph".into(), "foo".into(), "f".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn cmdline_position() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::CmdlinePosition(CmdlinePosition::Cursor)),
+                vec!["CmdlinePosition".into(), "cursor".into()],
+            ),
+            (
+                Ok(GnvimEvent::CmdlinePosition(CmdlinePosition::Bottom)),
+                vec!["CmdlinePosition".into(), "bottom".into()],
+            ),
+            (
+                Err("Invalid cmdline position: left".into()),
+                vec!["CmdlinePosition".into(), "left".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn command_palette_show() {
+        let expected = Ok(GnvimEvent::CommandPaletteShow);
+
+        let res =
+            nvim_bridge::parse_gnvim_event(vec!["CommandPaletteShow".into()]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn finder_show() {
+        let expected = Ok(GnvimEvent::FinderShow);
+
+        let res = nvim_bridge::parse_gnvim_event(vec!["FinderShow".into()]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn desktop_notify() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::DesktopNotify(
+                    "make".into(),
+                    "3 errors".into(),
+                    false,
+                )),
+                vec!["DesktopNotify".into(), "make".into(), "3 errors".into()],
+            ),
+            (
+                Ok(GnvimEvent::DesktopNotify(
+                    "make".into(),
+                    "3 errors".into(),
+                    true,
+                )),
+                vec![
+                    "DesktopNotify".into(),
+                    "make".into(),
+                    "3 errors".into(),
+                    1.into(),
+                ],
+            ),
+            (
+                Err("body missing".into()),
+                vec!["DesktopNotify".into(), "make".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_scrollbar() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableScrollbar(true)),
+                vec!["EnableScrollbar".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableScrollbar(false)),
+                vec!["EnableScrollbar".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_float_drag() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableFloatDrag(true)),
+                vec!["EnableFloatDrag".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableFloatDrag(false)),
+                vec!["EnableFloatDrag".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_minimap() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableMinimap(true)),
+                vec!["EnableMinimap".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableMinimap(false)),
+                vec!["EnableMinimap".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn set_float_style() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::SetFloatStyle(
+                    None,
+                    FloatOptions {
+                        radius: 6,
+                        shadow: true,
+                        border: false,
+                    },
+                )),
+                vec!["SetFloatStyle".into(), 6.into(), 1.into(), 0.into()],
+            ),
+            (
+                Ok(GnvimEvent::SetFloatStyle(
+                    Some(1001),
+                    FloatOptions {
+                        radius: 0,
+                        shadow: false,
+                        border: true,
+                    },
+                )),
+                vec![
+                    "SetFloatStyle".into(),
+                    0.into(),
+                    0.into(),
+                    1.into(),
+                    1001.into(),
+                ],
+            ),
+            (
+                Ok(GnvimEvent::SetFloatStyle(None, FloatOptions::default())),
+                vec![
+                    "SetFloatStyle".into(),
+                    0.into(),
+                    0.into(),
+                    0.into(),
+                    0.into(),
+                ],
+            ),
+            (
+                Err("border missing".into()),
+                vec!["SetFloatStyle".into(), 6.into(), 1.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn terminal_palette() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::TerminalPalette(vec![
+                    Some(Color::from_u64(0x1d1f21)),
+                    None,
+                ])),
+                vec![
+                    "TerminalPalette".into(),
+                    Value::Array(vec!["#1d1f21".into(), "".into()]),
+                ],
+            ),
+            (Err("colors missing".into()), vec!["TerminalPalette".into()]),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_alt_as_meta() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableAltAsMeta(true)),
+                vec!["EnableAltAsMeta".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableAltAsMeta(false)),
+                vec!["EnableAltAsMeta".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_shortcut() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableShortcut(String::from("paste"), true)),
+                vec!["EnableShortcut".into(), "paste".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableShortcut(String::from("zoom"), false)),
+                vec!["EnableShortcut".into(), "zoom".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_gui_selection() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableGuiSelection(true)),
+                vec!["EnableGuiSelection".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableGuiSelection(false)),
+                vec!["EnableGuiSelection".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn set_fullscreen() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::SetFullscreen(Some(true))),
+                vec!["SetFullscreen".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::SetFullscreen(Some(false))),
+                vec!["SetFullscreen".into(), 0.into()],
+            ),
+            (
+                Ok(GnvimEvent::SetFullscreen(None)),
+                vec!["SetFullscreen".into(), (-1).into()],
+            ),
+            (
+                Ok(GnvimEvent::SetFullscreen(None)),
+                vec!["SetFullscreen".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn set_decorations() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::SetDecorations(Decorations::Client)),
+                vec!["SetDecorations".into(), "client".into()],
+            ),
+            (
+                Ok(GnvimEvent::SetDecorations(Decorations::Tabline)),
+                vec!["SetDecorations".into(), "tabline".into()],
+            ),
+            (
+                Ok(GnvimEvent::SetDecorations(Decorations::None)),
+                vec!["SetDecorations".into(), "none".into()],
+            ),
+            (
+                Err(String::from("Invalid decorations: foo")),
+                vec!["SetDecorations".into(), "foo".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn set_progress() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::SetProgress(Some(0.5))),
+                vec!["SetProgress".into(), 0.5.into()],
+            ),
+            (
+                Ok(GnvimEvent::SetProgress(Some(-1.0))),
+                vec!["SetProgress".into(), (-1).into()],
+            ),
+            (
+                Ok(GnvimEvent::SetProgress(None)),
+                vec!["SetProgress".into(), Value::Nil],
+            ),
+            (
+                Err(String::from("Value is not a number: progress")),
+                vec!["SetProgress".into(), "foo".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn set_crosshair() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::SetCrosshair(Crosshair {
+                    line: true,
+                    column: false,
+                    color: None,
+                    opacity: 0.2,
+                })),
+                vec![
+                    "SetCrosshair".into(),
+                    1.into(),
+                    0.into(),
+                    "".into(),
+                    0.2.into(),
+                ],
+            ),
+            (
+                Ok(GnvimEvent::SetCrosshair(Crosshair {
+                    line: true,
+                    column: true,
+                    color: Some(Color::from_u64(0xff0000)),
+                    opacity: 1.0,
+                })),
+                vec![
+                    "SetCrosshair".into(),
+                    1.into(),
+                    1.into(),
+                    "#ff0000".into(),
+                    1.into(),
+                ],
+            ),
+            (
+                Err(String::from("Value is not a number: crosshair opacity")),
+                vec![
+                    "SetCrosshair".into(),
+                    1.into(),
+                    1.into(),
+                    "".into(),
+                    "foo".into(),
+                ],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn set_indent_guides() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::SetIndentGuides(IndentGuides {
+                    enabled: true,
+                    color: None,
+                    opacity: 0.2,
+                })),
+                vec!["SetIndentGuides".into(), 1.into(), "".into(), 0.2.into()],
+            ),
+            (
+                Ok(GnvimEvent::SetIndentGuides(IndentGuides {
+                    enabled: false,
+                    color: Some(Color::from_u64(0x00ff00)),
+                    opacity: 1.0,
+                })),
+                vec![
+                    "SetIndentGuides".into(),
+                    0.into(),
+                    "#00ff00".into(),
+                    1.into(),
+                ],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn indent_info_changed() {
+        let res =
+            nvim_bridge::parse_gnvim_event(vec!["IndentInfoChanged".into()]);
+
+        assert_eq!(Ok(GnvimEvent::IndentInfoChanged), res);
+    }
+
+    #[test]
+    fn window_buffer_changed() {
+        let res =
+            nvim_bridge::parse_gnvim_event(vec!["WindowBufferChanged".into()]);
+
+        assert_eq!(Ok(GnvimEvent::WindowBufferChanged), res);
+    }
+
+    #[test]
+    fn toggle_perf_overlay() {
+        let res =
+            nvim_bridge::parse_gnvim_event(vec!["TogglePerfOverlay".into()]);
+
+        assert_eq!(Ok(GnvimEvent::TogglePerfOverlay), res);
+    }
+
+    #[test]
+    fn log_viewer_show() {
+        let res = nvim_bridge::parse_gnvim_event(vec!["LogViewerShow".into()]);
+
+        assert_eq!(Ok(GnvimEvent::LogViewerShow), res);
+    }
+
+    #[test]
+    fn dump_grids() {
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "DumpGrids".into(),
+            "/tmp/grids".into(),
+        ]);
+        assert_eq!(Ok(GnvimEvent::DumpGrids("/tmp/grids".to_string())), res);
+
+        let res = nvim_bridge::parse_gnvim_event(vec!["DumpGrids".into()]);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn snippet_state() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::SnippetState(
+                    1000,
+                    SnippetState {
+                        current: 1,
+                        total: 3,
+                        placeholders: vec![
+                            SnippetPlaceholder {
+                                index: 1,
+                                row: 2,
+                                col: 4,
+                                len: 3,
+                            },
+                            SnippetPlaceholder {
+                                index: 3,
+                                row: 5,
+                                col: 0,
+                                len: 0,
+                            },
+                        ],
+                    },
+                )),
+                vec![
+                    "SnippetState".into(),
+                    1000.into(),
+                    1.into(),
+                    3.into(),
+                    Value::Array(vec![
+                        Value::Array(vec![
+                            1.into(),
+                            2.into(),
+                            4.into(),
+                            3.into(),
+                        ]),
+                        Value::Array(vec![
+                            3.into(),
+                            5.into(),
+                            0.into(),
+                            0.into(),
+                        ]),
+                    ]),
+                ],
+            ),
+            (
+                Ok(GnvimEvent::SnippetState(1000, SnippetState::default())),
+                vec![
+                    "SnippetState".into(),
+                    1000.into(),
+                    0.into(),
+                    0.into(),
+                    Value::Array(vec![]),
+                ],
+            ),
+            (
+                Err("Value is not a number: placeholder len".into()),
+                vec![
+                    "SnippetState".into(),
+                    1000.into(),
+                    1.into(),
+                    1.into(),
+                    Value::Array(vec![Value::Array(vec![
+                        1.into(),
+                        2.into(),
+                        4.into(),
+                    ])]),
+                ],
+            ),
+            (
+                Err("placeholders missing".into()),
+                vec!["SnippetState".into(), 1000.into(), 1.into(), 3.into()],
+            ),
+            (Err("window missing".into()), vec!["SnippetState".into()]),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            assert_eq!(expected, nvim_bridge::parse_gnvim_event(input));
+        }
+    }
+
+    #[test]
+    fn set_virtual_text() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::SetVirtualText(
+                    vec!["GitBlame".into(), "LspInlayHint".into()],
+                    VirtualText {
+                        opacity: 0.6,
+                        proportional: true,
+                    },
+                )),
+                vec![
+                    "SetVirtualText".into(),
+                    Value::Array(vec![
+                        "GitBlame".into(),
+                        "LspInlayHint".into(),
+                    ]),
+                    0.6.into(),
+                    1.into(),
+                ],
+            ),
+            (
+                Ok(GnvimEvent::SetVirtualText(
+                    vec![],
+                    VirtualText {
+                        opacity: 1.0,
+                        proportional: false,
+                    },
+                )),
+                vec![
+                    "SetVirtualText".into(),
+                    Value::Array(vec![]),
+                    1.into(),
+                    0.into(),
+                ],
+            ),
+            (
+                Err("Value is not an str: virtual text group".into()),
+                vec![
+                    "SetVirtualText".into(),
+                    Value::Array(vec![1.into()]),
+                    0.6.into(),
+                    0.into(),
+                ],
+            ),
+            (
+                Err("proportional missing".into()),
+                vec!["SetVirtualText".into(), Value::Array(vec![]), 0.6.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            assert_eq!(expected, nvim_bridge::parse_gnvim_event(input));
+        }
+    }
+
+    #[test]
+    fn cmdline_search_position() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::CmdlineSearchPosition(Some(
+                    CmdlinePosition::WindowBottom,
+                ))),
+                vec!["CmdlineSearchPosition".into(), "window_bottom".into()],
+            ),
+            (
+                Ok(GnvimEvent::CmdlineSearchPosition(None)),
+                vec!["CmdlineSearchPosition".into(), "".into()],
+            ),
+            (
+                Err("Invalid cmdline position: left".into()),
+                vec!["CmdlineSearchPosition".into(), "left".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn wildmenu_layout() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::WildmenuLayout(WildmenuLayout::Grid)),
+                vec!["WildmenuLayout".into(), "grid".into()],
+            ),
+            (
+                Ok(GnvimEvent::WildmenuLayout(WildmenuLayout::List)),
+                vec!["WildmenuLayout".into(), "list".into()],
+            ),
+            (
+                Err("Invalid wildmenu layout: table".into()),
+                vec!["WildmenuLayout".into(), "table".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn cursor_tooltip_scroll() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::CursorTooltipScroll(5)),
+                vec!["CursorTooltipScroll".into(), 5.into()],
+            ),
+            (
+                Ok(GnvimEvent::CursorTooltipScroll(-5)),
+                vec!["CursorTooltipScroll".into(), (-5).into()],
+            ),
+            (
+                Err("lines missing".into()),
+                vec!["CursorTooltipScroll".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn image() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::ImageShow(
+                    1,
+                    String::from("/tmp/foo.png"),
+                    (2, 3),
+                    (4, 5),
+                )),
+                vec![
+                    "ImageShow".into(),
+                    1.into(),
+                    "/tmp/foo.png".into(),
+                    2.into(),
+                    3.into(),
+                    4.into(),
+                    5.into(),
+                ],
+            ),
+            (
+                Err("cols missing".into()),
+                vec![
+                    "ImageShow".into(),
+                    1.into(),
+                    "/tmp/foo.png".into(),
+                    2.into(),
+                    3.into(),
+                    4.into(),
+                ],
+            ),
+            (
+                Ok(GnvimEvent::ImageHide(1)),
+                vec!["ImageHide".into(), 1.into()],
+            ),
+            (Ok(GnvimEvent::ImageClear), vec!["ImageClear".into()]),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn terminal_graphics() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::TerminalGraphics(
+                    1,
+                    String::from("\x1b_Ga=d\x1b\\"),
+                    (2, 3),
+                )),
+                vec![
+                    "TerminalGraphics".into(),
+                    1.into(),
+                    "\x1b_Ga=d\x1b\\".into(),
+                    2.into(),
+                    3.into(),
+                ],
+            ),
+            (
+                Err("col missing".into()),
+                vec![
+                    "TerminalGraphics".into(),
+                    1.into(),
+                    "\x1b_Ga=d\x1b\\".into(),
+                    2.into(),
+                ],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn preview() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::PreviewUpdate(String::from("# foo"), 1000)),
+                vec!["PreviewUpdate".into(), "# foo".into(), 1000.into()],
+            ),
+            (
+                Err("window missing".into()),
+                vec!["PreviewUpdate".into(), "# foo".into()],
+            ),
+            (Ok(GnvimEvent::PreviewHide), vec!["PreviewHide".into()]),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn window_viewport_win_handle() {
+        let viewport = |win| nvim_bridge::WindowViewport {
+            grid: 2,
+            win,
+            topline: 0,
+            botline: 5,
+            curline: 0,
+            curcol: 0,
+            line_count: None,
+        };
+
+        // Window 1000 (0xcd 0x03 0xe8 in msgpack).
+        assert_eq!(
+            viewport(Value::Ext(1, vec![0xcd, 0x03, 0xe8])).win_handle(),
+            Some(1000)
+        );
+        assert_eq!(viewport(1000.into()).win_handle(), None);
+    }
+
+    #[test]
+    fn export() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::Export(
+                    ExportFormat::Pdf,
+                    String::from("/tmp/foo.pdf"),
+                    None,
+                )),
+                vec!["Export".into(), "pdf".into(), "/tmp/foo.pdf".into()],
+            ),
+            (
+                Ok(GnvimEvent::Export(
+                    ExportFormat::Html,
+                    String::from("/tmp/foo.html"),
+                    Some(vec![
+                        vec![
+                            (
+                                String::from("fn"),
+                                Highlight {
+                                    foreground: Some(Color::from_u64(0xff00ff)),
+                                    bold: true,
+                                    ..Highlight::default()
+                                },
+                            ),
+                            (String::from(" main"), Highlight::default()),
+                        ],
+                        vec![],
+                    ]),
+                )),
+                vec![
+                    "Export".into(),
+                    "html".into(),
+                    "/tmp/foo.html".into(),
+                    Value::Array(vec![
+                        Value::Array(vec![
+                            Value::Array(vec![
+                                "fn".into(),
+                                Value::Map(vec![
+                                    ("foreground".into(), 0xff00ff.into()),
+                                    ("bold".into(), true.into()),
+                                ]),
+                            ]),
+                            Value::Array(vec![
+                                " main".into(),
+                                Value::Map(vec![]),
+                            ]),
+                        ]),
+                        Value::Array(vec![]),
+                    ]),
+                ],
+            ),
+            (
+                Err("Invalid export format: png".into()),
+                vec!["Export".into(), "png".into(), "/tmp/foo.png".into()],
+            ),
+            (
+                Err("path missing".into()),
+                vec!["Export".into(), "svg".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn screenshot() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::Screenshot {
+                    target: ScreenshotTarget::Window,
+                    path: Some(String::from("/tmp/foo.png")),
+                }),
+                vec![
+                    "Screenshot".into(),
+                    "/tmp/foo.png".into(),
+                    "window".into(),
+                ],
+            ),
+            (
+                Ok(GnvimEvent::Screenshot {
+                    target: ScreenshotTarget::Grid(None),
+                    path: None,
+                }),
+                vec!["Screenshot".into(), "".into(), "grid".into()],
+            ),
+            (
+                Ok(GnvimEvent::Screenshot {
+                    target: ScreenshotTarget::Grid(Some(2)),
+                    path: None,
+                }),
+                vec!["Screenshot".into(), "".into(), "grid".into(), 2.into()],
+            ),
+            (
+                Ok(GnvimEvent::Screenshot {
+                    target: ScreenshotTarget::Cells(1, 2, 3, 4),
+                    path: None,
+                }),
+                vec![
+                    "Screenshot".into(),
+                    "".into(),
+                    "cells".into(),
+                    1.into(),
+                    2.into(),
+                    3.into(),
+                    4.into(),
+                ],
+            ),
+            (
+                Err("cols missing".into()),
+                vec![
+                    "Screenshot".into(),
+                    "".into(),
+                    "cells".into(),
+                    1.into(),
+                    2.into(),
+                    3.into(),
+                ],
+            ),
+            (
+                Err("Invalid screenshot target: tab".into()),
+                vec!["Screenshot".into(), "".into(), "tab".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn print() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::Print(
+                    String::from("foo.rs"),
+                    3,
+                    vec![vec![(String::from("fn"), Highlight::default())]],
+                )),
+                vec![
+                    "Print".into(),
+                    "foo.rs".into(),
+                    3.into(),
+                    Value::Array(vec![Value::Array(vec![Value::Array(vec![
+                        "fn".into(),
+                        Value::Map(vec![]),
+                    ])])]),
+                ],
+            ),
+            (
+                Err("lines missing".into()),
+                vec!["Print".into(), "foo.rs".into(), 3.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn search_bar_show() {
+        let expected = Ok(GnvimEvent::SearchBarShow);
+
+        let res = nvim_bridge::parse_gnvim_event(vec!["SearchBarShow".into()]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn copy_mode_enter() {
+        let expected = Ok(GnvimEvent::CopyModeEnter);
+
+        let res = nvim_bridge::parse_gnvim_event(vec!["CopyModeEnter".into()]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn paste_set_confirm_threshold() {
+        let expected = Ok(GnvimEvent::PasteConfirmThreshold(2048));
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "PasteSetConfirmThreshold".into(),
+            2048.into(),
+        ]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn input_set_queue_size() {
+        let expected = Ok(GnvimEvent::InputQueueSize(64));
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "InputSetQueueSize".into(),
+            64.into(),
+        ]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn file_tree() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::FileTreeShow(String::from("/home/foo"))),
+                vec!["FileTreeShow".into(), "/home/foo".into()],
+            ),
+            (Err("directory missing".into()), vec!["FileTreeShow".into()]),
+            (Ok(GnvimEvent::FileTreeHide), vec!["FileTreeHide".into()]),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn set_winbar() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::SetWinbar(
+                    1000,
+                    vec![
+                        nvim_bridge::WinbarSegment {
+                            text: String::from("src"),
+                            hl: Highlight {
+                                bold: true,
+                                ..Highlight::default()
+                            },
+                            click: Some(String::from("Click")),
+                        },
+                        nvim_bridge::WinbarSegment {
+                            text: String::from(" > "),
+                            hl: Highlight::default(),
+                            click: None,
+                        },
+                    ],
+                )),
+                vec![
+                    "SetWinbar".into(),
+                    1000.into(),
+                    Value::Array(vec![
+                        Value::Map(vec![
+                            ("text".into(), "src".into()),
+                            (
+                                "hl".into(),
+                                Value::Map(vec![("bold".into(), true.into())]),
+                            ),
+                            ("click".into(), "Click".into()),
+                        ]),
+                        Value::Map(vec![
+                            ("text".into(), " > ".into()),
+                            ("click".into(), "".into()),
+                        ]),
+                    ]),
+                ],
+            ),
+            (
+                Ok(GnvimEvent::SetWinbar(1000, vec![])),
+                vec!["SetWinbar".into(), 1000.into(), Value::Array(vec![])],
+            ),
+            (
+                Err("text missing".into()),
+                vec![
+                    "SetWinbar".into(),
+                    1000.into(),
+                    Value::Array(vec![Value::Map(vec![])]),
+                ],
+            ),
+            (
+                Err("segments missing".into()),
+                vec!["SetWinbar".into(), 1000.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn set_signs() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::SetSigns {
+                    win: 1000,
+                    col: 1,
+                    width: 2,
+                    signs: vec![(0, SignKind::Error), (4, SignKind::GitChange)],
+                }),
+                vec![
+                    "SetSigns".into(),
+                    1000.into(),
+                    1.into(),
+                    2.into(),
+                    Value::Array(vec![
+                        Value::Array(vec![0.into(), "error".into()]),
+                        Value::Array(vec![4.into(), "git_change".into()]),
+                    ]),
+                ],
+            ),
+            (
+                Ok(GnvimEvent::SetSigns {
+                    win: 1000,
+                    col: 0,
+                    width: 0,
+                    signs: vec![],
+                }),
+                vec![
+                    "SetSigns".into(),
+                    1000.into(),
+                    0.into(),
+                    0.into(),
+                    Value::Array(vec![]),
+                ],
+            ),
+            (
+                Err("Invalid sign kind: bug".into()),
+                vec![
+                    "SetSigns".into(),
+                    1000.into(),
+                    0.into(),
+                    2.into(),
+                    Value::Array(vec![Value::Array(vec![
+                        0.into(),
+                        "bug".into(),
+                    ])]),
+                ],
+            ),
+            (
+                Err("signs missing".into()),
+                vec!["SetSigns".into(), 1000.into(), 0.into(), 2.into()],
+            ),
+            (
+                Err("width missing".into()),
+                vec!["SetSigns".into(), 1000.into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            assert_eq!(expected, nvim_bridge::parse_gnvim_event(input));
+        }
+    }
+
+    #[test]
+    fn detach_tab() {
+        let data = vec![
+            (
+                Ok(GnvimEvent::DetachTab {
+                    id: 1,
+                    title: "main.rs".into(),
+                    windows: vec![(1000, 0, 0), (1001, 0, 41)],
+                }),
+                vec![
+                    "DetachTab".into(),
+                    1.into(),
+                    "main.rs".into(),
+                    Value::Array(vec![
+                        Value::Array(vec![1000.into(), 0.into(), 0.into()]),
+                        Value::Array(vec![1001.into(), 0.into(), 41.into()]),
+                    ]),
+                ],
+            ),
+            (
+                Err("col missing".into()),
+                vec![
+                    "DetachTab".into(),
+                    1.into(),
+                    "main.rs".into(),
+                    Value::Array(vec![Value::Array(vec![
+                        1000.into(),
+                        0.into(),
+                    ])]),
+                ],
+            ),
+            (
+                Err("windows missing".into()),
+                vec!["DetachTab".into(), 1.into(), "main.rs".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn set_link_patterns() {
+        let data = vec![
+            (
+                Ok(GnvimEvent::SetLinkPatterns {
+                    url: "https://\\S+".into(),
+                    path: "".into(),
+                }),
+                vec![
+                    "SetLinkPatterns".into(),
+                    "https://\\S+".into(),
+                    "".into(),
+                ],
+            ),
+            (
+                Err("path pattern missing".into()),
+                vec!["SetLinkPatterns".into(), "https://\\S+".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn set_animations() {
+        let data = vec![
+            (
+                Ok(GnvimEvent::SetAnimations {
+                    enable: true,
+                    float_open: 200,
+                    window_close: 0,
+                }),
+                vec!["SetAnimations".into(), 1.into(), 200.into(), 0.into()],
+            ),
+            (
+                Err("window close duration missing".into()),
+                vec!["SetAnimations".into(), 0.into(), 200.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn tabline_mode() {
+        let data = vec![
+            (
+                Ok(GnvimEvent::TablineMode(TablineMode::Buffers)),
+                vec!["TablineMode".into(), "buffers".into()],
+            ),
+            (
+                Err("Invalid tabline mode: windows".into()),
+                vec!["TablineMode".into(), "windows".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn tabline_buffers() {
+        let data = vec![
+            (
+                Ok(GnvimEvent::TablineBuffers {
+                    current: 3,
+                    buffers: vec![
+                        (1, "main.rs".into(), false),
+                        (3, "[No Name]".into(), true),
+                    ],
+                }),
+                vec![
+                    "TablineBuffers".into(),
+                    3.into(),
+                    Value::Array(vec![
+                        Value::Array(vec![
+                            1.into(),
+                            "main.rs".into(),
+                            0.into(),
+                        ]),
+                        Value::Array(vec![
+                            3.into(),
+                            "[No Name]".into(),
+                            1.into(),
+                        ]),
+                    ]),
+                ],
+            ),
+            (
+                Err("modified missing".into()),
+                vec![
+                    "TablineBuffers".into(),
+                    1.into(),
+                    Value::Array(vec![Value::Array(vec![
+                        1.into(),
+                        "main.rs".into(),
+                    ])]),
+                ],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn set_statusline() {
+        let data = vec![
+            (
+                Ok(GnvimEvent::SetStatusline(vec![
+                    nvim_bridge::StatuslineSegment {
+                        text: String::from("NORMAL"),
+                        hl: Highlight {
+                            bold: true,
+                            ..Highlight::default()
+                        },
+                        icon: None,
+                        click: None,
+                        right: false,
+                    },
+                    nvim_bridge::StatuslineSegment {
+                        text: String::from("2"),
+                        hl: Highlight::default(),
+                        icon: Some(String::from("dialog-error-symbolic")),
+                        click: Some(String::from("Click")),
+                        right: true,
+                    },
+                ])),
+                vec![
+                    "SetStatusline".into(),
+                    Value::Array(vec![
+                        Value::Map(vec![
+                            ("text".into(), "NORMAL".into()),
+                            (
+                                "hl".into(),
+                                Value::Map(vec![("bold".into(), true.into())]),
+                            ),
+                            ("icon".into(), "".into()),
+                        ]),
+                        Value::Map(vec![
+                            ("text".into(), "2".into()),
+                            ("icon".into(), "dialog-error-symbolic".into()),
+                            ("click".into(), "Click".into()),
+                            ("align".into(), "right".into()),
+                        ]),
+                    ]),
+                ],
+            ),
+            (
+                Err("Invalid statusline align: center".into()),
+                vec![
+                    "SetStatusline".into(),
+                    Value::Array(vec![Value::Map(vec![
+                        ("text".into(), "NORMAL".into()),
+                        ("align".into(), "center".into()),
+                    ])]),
+                ],
+            ),
+            (Err("segments missing".into()), vec!["SetStatusline".into()]),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn file_dialog() {
+        let data = vec![
+            (
+                Ok(GnvimEvent::FileOpenDialog(nvim_bridge::FileDialog {
+                    id: 3,
+                    ..Default::default()
+                })),
+                vec!["FileOpenDialog".into(), 3.into()],
+            ),
+            (
+                Ok(GnvimEvent::FileSaveDialog(nvim_bridge::FileDialog {
+                    id: 4,
+                    title: Some(String::from("Save notes")),
+                    folder: None,
+                    name: Some(String::from("notes.md")),
+                    patterns: vec![String::from("*.md")],
+                })),
+                vec![
+                    "FileSaveDialog".into(),
+                    4.into(),
+                    Value::Map(vec![
+                        ("title".into(), "Save notes".into()),
+                        ("folder".into(), "".into()),
+                        ("name".into(), "notes.md".into()),
+                        ("patterns".into(), Value::Array(vec!["*.md".into()])),
+                    ]),
+                ],
+            ),
+            (
+                Err("Value is not a map: dialog options".into()),
+                vec!["FileOpenDialog".into(), 3.into(), "foo".into()],
+            ),
+            (
+                Err("dialog id missing".into()),
+                vec!["FileOpenDialog".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn set_device_scale() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::SetDeviceScale(Some(1.25))),
+                vec!["SetDeviceScale".into(), 1.25.into()],
+            ),
+            (
+                Ok(GnvimEvent::SetDeviceScale(Some(2.0))),
+                vec!["SetDeviceScale".into(), 2.into()],
+            ),
+            (
+                Ok(GnvimEvent::SetDeviceScale(None)),
+                vec!["SetDeviceScale".into(), 0.into()],
+            ),
+            (
+                Err("Value is not a number: device scale".into()),
+                vec!["SetDeviceScale".into(), "foo".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn set_custom_css() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::SetCustomCss("/tmp/gnvim.css".into())),
+                vec!["SetCustomCss".into(), "/tmp/gnvim.css".into()],
+            ),
+            (Err("path missing".into()), vec!["SetCustomCss".into()]),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn set_hl_groups() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::SetHlGroups(vec![
+                    (
+                        String::from("DiagnosticError"),
+                        Highlight {
+                            foreground: Some(Color::from_u64(0xff0000)),
+                            ..Highlight::default()
+                        },
+                    ),
+                    (String::from("DiagnosticWarn"), Highlight::default()),
+                ])),
+                vec![
+                    "SetHlGroups".into(),
+                    Value::Map(vec![
+                        (
+                            "DiagnosticError".into(),
+                            Value::Map(vec![(
+                                "foreground".into(),
+                                0xff0000.into(),
+                            )]),
+                        ),
+                        ("DiagnosticWarn".into(), Value::Map(vec![])),
+                    ]),
+                ],
+            ),
+            (
+                Err("Value is not a map: highlight group".into()),
+                vec![
+                    "SetHlGroups".into(),
+                    Value::Map(vec![("DiagnosticError".into(), 1.into())]),
+                ],
+            ),
+            (
+                Err("Value is not a map: highlight groups".into()),
+                vec!["SetHlGroups".into(), "foo".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
 }
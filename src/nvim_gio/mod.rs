@@ -7,10 +7,15 @@ use nvim_rs::{create::Spawner, neovim::Neovim, Handler};
 use crate::nvim_bridge;
 
 pub mod compat;
+mod neovim;
+pub mod stdin;
+mod watchdog;
+
+pub use self::neovim::{GioNeovim, RequestResult};
+pub use self::watchdog::{RequestError, Watchdog};
 
 pub type GioWriter =
     Compat<gio::OutputStreamAsyncWrite<gio::PollableOutputStream>>;
-pub type GioNeovim = Neovim<GioWriter>;
 
 #[derive(Debug)]
 pub enum Error {
@@ -48,6 +53,7 @@ use compat::Compat;
 pub fn new_child<H>(
     handler: H,
     args: Vec<&std::ffi::OsStr>,
+    cwd: Option<&std::path::Path>,
     tx: glib::Sender<nvim_bridge::Message>,
 ) -> Result<GioNeovim, Error>
 where
@@ -58,7 +64,14 @@ where
     flags.insert(gio::SubprocessFlags::STDOUT_PIPE);
     flags.insert(gio::SubprocessFlags::STDERR_PIPE);
 
-    let p = gio::Subprocess::newv(&args, flags).map_err(Error::from)?;
+    let launcher = gio::SubprocessLauncher::new(flags);
+    if let Some(cwd) = cwd {
+        launcher.set_cwd(cwd);
+    }
+
+    let p = launcher.spawnv(&args).map_err(Error::from)?;
+    let watchdog = Watchdog::default();
+    watchdog.set_process(p.clone());
 
     let input = p
         .get_stdin_pipe()
@@ -82,12 +95,32 @@ where
 
     let c = glib::MainContext::default();
 
-    c.spawn(async move {
-        let _ = io.await;
-        if let Err(err) = tx.send(nvim_bridge::Message::Close) {
+    c.spawn_local(async move {
+        if let Err(err) = io.await {
+            // Reading from nvim failed, but the process might still be
+            // running.
+            if !err.is_channel_closed() {
+                error!("Connection to nvim failed: {}", err);
+                p.force_exit();
+            }
+        }
+
+        let msg = match p.wait_async_future().await {
+            Ok(()) if p.get_successful() => nvim_bridge::Message::Close,
+            Ok(()) if p.get_if_signaled() => nvim_bridge::Message::Crash(
+                format!("Killed by signal {}", p.get_term_sig()),
+            ),
+            Ok(()) => nvim_bridge::Message::Crash(format!(
+                "Exited with status {}",
+                p.get_exit_status()
+            )),
+            Err(err) => nvim_bridge::Message::Crash(err.to_string()),
+        };
+
+        if let Err(err) = tx.send(msg) {
             error!("Failed to send close message to the gui: {}", err)
         }
     });
 
-    Ok(neovim)
+    Ok(GioNeovim::new(neovim, watchdog))
 }
@@ -0,0 +1,202 @@
+use nvim_rs::error::CallError;
+use nvim_rs::neovim::Neovim;
+use nvim_rs::{Buffer, Tabpage, UiAttachOptions, Value, Window};
+
+use crate::nvim_gio::{GioWriter, RequestError, Watchdog};
+
+pub type RequestResult<T> = Result<T, RequestError<Box<CallError>>>;
+
+impl RequestError<Box<CallError>> {
+    /// If the request failed because nvim has exited.
+    pub fn is_channel_closed(&self) -> bool {
+        matches!(self, RequestError::Failed(err) if err.is_channel_closed())
+    }
+}
+
+/// Nvim, whose requests are all waited for through the watchdog, so that
+/// an nvim that stops answering doesn't keep us waiting forever.
+#[derive(Clone)]
+pub struct GioNeovim {
+    nvim: Neovim<GioWriter>,
+    watchdog: Watchdog,
+}
+
+impl GioNeovim {
+    pub fn new(nvim: Neovim<GioWriter>, watchdog: Watchdog) -> Self {
+        GioNeovim { nvim, watchdog }
+    }
+
+    pub fn watchdog(&self) -> &Watchdog {
+        &self.watchdog
+    }
+
+    /// Waits for `fut`, a request made through one of nvim's buffers,
+    /// windows or tabpages.
+    pub async fn request<F, T, E>(&self, fut: F) -> Result<T, RequestError<E>>
+    where
+        F: std::future::Future<Output = Result<T, E>>,
+    {
+        self.watchdog.request(fut).await
+    }
+
+    /// Returns the nvim window of `handle`.
+    pub fn window(&self, handle: Value) -> Window<GioWriter> {
+        Window::new(handle, self.nvim.clone())
+    }
+
+    /// Returns the nvim tabpage of `handle`.
+    pub fn tabpage(&self, handle: Value) -> Tabpage<GioWriter> {
+        Tabpage::new(handle, self.nvim.clone())
+    }
+
+    /// Calls api function `method`, which nvim-rs doesn't have (e.g.
+    /// because it's newer). Nvim's errors are returned as they are.
+    pub async fn call(
+        &self,
+        method: &str,
+        args: Vec<Value>,
+    ) -> Result<Value, RequestError<String>> {
+        match self.request(self.nvim.call(method, args)).await {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(err)) => Err(RequestError::Failed(err.to_string())),
+            Err(RequestError::Failed(err)) => {
+                Err(RequestError::Failed(err.to_string()))
+            }
+            Err(RequestError::TimedOut) => Err(RequestError::TimedOut),
+        }
+    }
+
+    pub async fn command(&self, command: &str) -> RequestResult<()> {
+        self.request(self.nvim.command(command)).await
+    }
+
+    pub async fn call_function(
+        &self,
+        fname: &str,
+        args: Vec<Value>,
+    ) -> RequestResult<Value> {
+        self.request(self.nvim.call_function(fname, args)).await
+    }
+
+    pub async fn eval(&self, expr: &str) -> RequestResult<Value> {
+        self.request(self.nvim.eval(expr)).await
+    }
+
+    pub async fn input(&self, keys: &str) -> RequestResult<i64> {
+        self.request(self.nvim.input(keys)).await
+    }
+
+    pub async fn input_mouse(
+        &self,
+        button: &str,
+        action: &str,
+        modifier: &str,
+        grid: i64,
+        row: i64,
+        col: i64,
+    ) -> RequestResult<()> {
+        self.request(
+            self.nvim
+                .input_mouse(button, action, modifier, grid, row, col),
+        )
+        .await
+    }
+
+    pub async fn paste(
+        &self,
+        data: &str,
+        crlf: bool,
+        phase: i64,
+    ) -> RequestResult<bool> {
+        self.request(self.nvim.paste(data, crlf, phase)).await
+    }
+
+    pub async fn err_writeln(&self, msg: &str) -> RequestResult<()> {
+        self.request(self.nvim.err_writeln(msg)).await
+    }
+
+    pub async fn set_option(
+        &self,
+        name: &str,
+        value: Value,
+    ) -> RequestResult<()> {
+        self.request(self.nvim.set_option(name, value)).await
+    }
+
+    pub async fn set_var(&self, name: &str, value: Value) -> RequestResult<()> {
+        self.request(self.nvim.set_var(name, value)).await
+    }
+
+    pub async fn get_mode(&self) -> RequestResult<Vec<(Value, Value)>> {
+        self.request(self.nvim.get_mode()).await
+    }
+
+    pub async fn get_api_info(&self) -> RequestResult<Vec<Value>> {
+        self.request(self.nvim.get_api_info()).await
+    }
+
+    pub async fn subscribe(&self, event: &str) -> RequestResult<()> {
+        self.request(self.nvim.subscribe(event)).await
+    }
+
+    pub async fn get_current_buf(&self) -> RequestResult<Buffer<GioWriter>> {
+        self.request(self.nvim.get_current_buf()).await
+    }
+
+    pub async fn set_current_buf(
+        &self,
+        buffer: &Buffer<GioWriter>,
+    ) -> RequestResult<()> {
+        self.request(self.nvim.set_current_buf(buffer)).await
+    }
+
+    pub async fn create_buf(
+        &self,
+        listed: bool,
+        scratch: bool,
+    ) -> RequestResult<Buffer<GioWriter>> {
+        self.request(self.nvim.create_buf(listed, scratch)).await
+    }
+
+    pub async fn set_current_tabpage(
+        &self,
+        tabpage: &Tabpage<GioWriter>,
+    ) -> RequestResult<()> {
+        self.request(self.nvim.set_current_tabpage(tabpage)).await
+    }
+
+    pub async fn ui_attach(
+        &mut self,
+        width: i64,
+        height: i64,
+        opts: &UiAttachOptions,
+    ) -> RequestResult<()> {
+        self.request(self.nvim.ui_attach(width, height, opts)).await
+    }
+
+    pub async fn ui_set_option(
+        &self,
+        name: &str,
+        value: Value,
+    ) -> RequestResult<()> {
+        self.request(self.nvim.ui_set_option(name, value)).await
+    }
+
+    pub async fn ui_try_resize(
+        &self,
+        width: i64,
+        height: i64,
+    ) -> RequestResult<()> {
+        self.request(self.nvim.ui_try_resize(width, height)).await
+    }
+
+    pub async fn ui_try_resize_grid(
+        &self,
+        grid: i64,
+        width: i64,
+        height: i64,
+    ) -> RequestResult<()> {
+        self.request(self.nvim.ui_try_resize_grid(grid, width, height))
+            .await
+    }
+}
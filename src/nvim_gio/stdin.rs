@@ -0,0 +1,113 @@
+use std::io::IsTerminal;
+
+use gio::prelude::*;
+
+use log::error;
+
+use nvim_rs::Buffer;
+
+use crate::nvim_gio::{GioNeovim, GioWriter};
+
+/// Size of the chunks read from stdin.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Splits text that is read in chunks into lines. A chunk's last line might
+/// continue in the next chunk.
+#[derive(Default)]
+struct Lines {
+    partial: Vec<u8>,
+}
+
+impl Lines {
+    /// Returns the complete lines, after adding `chunk`.
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.partial.extend_from_slice(chunk);
+
+        let end = match self.partial.iter().rposition(|b| *b == b'\n') {
+            Some(end) => end,
+            None => return vec![],
+        };
+
+        let rest = self.partial.split_off(end + 1);
+        let complete = std::mem::replace(&mut self.partial, rest);
+        complete[..end]
+            .split(|b| *b == b'\n')
+            .map(|line| String::from_utf8_lossy(line).into_owned())
+            .collect()
+    }
+
+    /// Returns the last line, if it doesn't end with a newline.
+    fn finish(self) -> Option<String> {
+        if self.partial.is_empty() {
+            None
+        } else {
+            Some(String::from_utf8_lossy(&self.partial).into_owned())
+        }
+    }
+}
+
+/// Returns true if gnvim's stdin can be read into a buffer (e.g. it's a
+/// pipe), instead of being a terminal.
+pub fn is_readable() -> bool {
+    !std::io::stdin().is_terminal()
+}
+
+/// Reads gnvim's stdin into `buf`, as it arrives. Nvim's own stdin is used
+/// for the rpc connection, so it can't read gnvim's stdin itself.
+pub async fn read_into(nvim: GioNeovim, buf: Buffer<GioWriter>) {
+    // Stdin is used only here, so the stream can own it.
+    let stream = unsafe { gio::UnixInputStream::new(0) };
+    let mut lines = Lines::default();
+    // The first lines replace the buffer's empty line.
+    let mut start = 0;
+
+    loop {
+        let chunk = match stream
+            .read_bytes_async_future(CHUNK_SIZE, glib::PRIORITY_DEFAULT)
+            .await
+        {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                error!("Failed to read stdin: {}", err);
+                return;
+            }
+        };
+
+        let new = if chunk.is_empty() {
+            std::mem::take(&mut lines).finish().into_iter().collect()
+        } else {
+            lines.push(&chunk)
+        };
+
+        if !new.is_empty() {
+            let res = nvim.request(buf.set_lines(start, -1, false, new));
+            if let Err(err) = res.await {
+                error!("Failed to add stdin to the buffer: {}", err);
+                return;
+            }
+            start = -1;
+        }
+
+        if chunk.is_empty() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines() {
+        let mut lines = Lines::default();
+        assert_eq!(lines.push(b"foo"), Vec::<String>::new());
+        assert_eq!(lines.push(b"bar\nbaz\n\nqu"), vec!["foobar", "baz", ""]);
+        assert_eq!(lines.push(b"ux\n"), vec!["quux"]);
+        assert_eq!(lines.finish(), None);
+
+        let mut lines = Lines::default();
+        assert_eq!(lines.push(b"foo\nbar"), vec!["foo"]);
+        assert_eq!(lines.finish(), Some(String::from("bar")));
+    }
+}
@@ -0,0 +1,159 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use futures::future::{self, Either};
+
+/// How long (in milliseconds) a request is waited for before giving up.
+const REQUEST_TIMEOUT: u32 = 10_000;
+
+#[derive(Debug)]
+pub enum RequestError<E> {
+    /// Nvim didn't answer in time.
+    TimedOut,
+    /// Nvim answered with an error (or the request couldn't be sent).
+    Failed(E),
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for RequestError<E> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RequestError::TimedOut => write!(fmt, "Request timed out"),
+            RequestError::Failed(err) => write!(fmt, "{}", err),
+        }
+    }
+}
+
+/// Requests that are waiting for nvim.
+#[derive(Default)]
+struct Requests {
+    next_id: u64,
+    /// Start times of the pending requests.
+    pending: HashMap<u64, Instant>,
+    /// Start time of the oldest request that timed out, since nvim last
+    /// answered anything.
+    unanswered: Option<Instant>,
+}
+
+impl Requests {
+    fn start(&mut self, now: Instant) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.insert(id, now);
+        id
+    }
+
+    /// Marks request `id` done. `answered` is false if it timed out.
+    fn finish(&mut self, id: u64, answered: bool) {
+        let start = match self.pending.remove(&id) {
+            Some(start) => start,
+            None => return,
+        };
+
+        if answered {
+            self.unanswered = None;
+        } else {
+            self.unanswered =
+                Some(self.unanswered.map_or(start, |t| t.min(start)));
+        }
+    }
+
+    /// Since when nvim has kept us waiting, if it has.
+    fn waiting_since(&self) -> Option<Instant> {
+        self.pending
+            .values()
+            .chain(self.unanswered.iter())
+            .min()
+            .copied()
+    }
+}
+
+/// Keeps track of the requests sent to nvim, so we notice when nvim stops
+/// answering (e.g. it's stuck in a loop), and can kill it.
+#[derive(Clone, Default)]
+pub struct Watchdog {
+    requests: Rc<RefCell<Requests>>,
+    process: Rc<RefCell<Option<gio::Subprocess>>>,
+}
+
+impl Watchdog {
+    pub fn set_process(&self, process: gio::Subprocess) {
+        self.process.replace(Some(process));
+    }
+
+    /// Waits for the nvim request `fut`, for a limited time.
+    pub async fn request<F, T, E>(&self, fut: F) -> Result<T, RequestError<E>>
+    where
+        F: Future<Output = Result<T, E>>,
+    {
+        let id = self.requests.borrow_mut().start(Instant::now());
+
+        futures::pin_mut!(fut);
+        match future::select(fut, glib::timeout_future(REQUEST_TIMEOUT)).await {
+            Either::Left((res, _)) => {
+                self.requests.borrow_mut().finish(id, true);
+                res.map_err(RequestError::Failed)
+            }
+            Either::Right(_) => {
+                self.requests.borrow_mut().finish(id, false);
+                Err(RequestError::TimedOut)
+            }
+        }
+    }
+
+    /// How long nvim has kept us waiting, if it has.
+    pub fn stalled_for(&self) -> Option<Duration> {
+        self.requests
+            .borrow()
+            .waiting_since()
+            .map(|since| since.elapsed())
+    }
+
+    /// Kills nvim.
+    pub fn kill(&self) {
+        if let Some(ref process) = *self.process.borrow() {
+            process.force_exit();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_waiting_since() {
+        let t0 = Instant::now();
+        let t1 = t0 + Duration::from_secs(1);
+        let mut requests = Requests::default();
+        assert_eq!(requests.waiting_since(), None);
+
+        let a = requests.start(t0);
+        let b = requests.start(t1);
+        assert_eq!(requests.waiting_since(), Some(t0));
+
+        requests.finish(a, true);
+        assert_eq!(requests.waiting_since(), Some(t1));
+        requests.finish(b, true);
+        assert_eq!(requests.waiting_since(), None);
+    }
+
+    #[test]
+    fn test_requests_timed_out() {
+        let t0 = Instant::now();
+        let t1 = t0 + Duration::from_secs(1);
+        let mut requests = Requests::default();
+
+        // A timed out request keeps us waiting until nvim answers again.
+        let a = requests.start(t0);
+        requests.finish(a, false);
+        assert_eq!(requests.waiting_since(), Some(t0));
+
+        let b = requests.start(t1);
+        assert_eq!(requests.waiting_since(), Some(t0));
+        requests.finish(b, true);
+        assert_eq!(requests.waiting_since(), None);
+    }
+}
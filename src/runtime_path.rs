@@ -0,0 +1,121 @@
+use std::path::{Path, PathBuf};
+
+use log::{debug, warn};
+
+/// Install prefix that gnvim was built for (see the Makefile).
+fn prefix() -> &'static str {
+    option_env!("GNVIM_PREFIX").unwrap_or("/usr/local")
+}
+
+/// Returns the directories where gnvim's runtime files might be, in the
+/// order they're looked for.
+fn candidates(
+    exe: Option<&Path>,
+    data_home: Option<PathBuf>,
+    data_dirs: Vec<PathBuf>,
+    prefix: &str,
+) -> Vec<PathBuf> {
+    let mut dirs = vec![];
+
+    // Portable builds (e.g. AppImages) have the runtime files next to the
+    // executable, or in the share directory of the executable's prefix.
+    if let Some(bin) = exe.and_then(Path::parent) {
+        dirs.push(bin.join("runtime"));
+        if let Some(prefix) = bin.parent() {
+            dirs.push(prefix.join("share/gnvim/runtime"));
+        }
+    }
+
+    // In Flatpak, the data directories include /app/share.
+    dirs.extend(
+        data_home
+            .into_iter()
+            .chain(data_dirs)
+            .map(|dir| dir.join("gnvim/runtime")),
+    );
+
+    dirs.push(Path::new(prefix).join("share/gnvim/runtime"));
+    dirs
+}
+
+/// Returns the first of `dirs` that has gnvim's runtime files.
+fn find_in(dirs: &[PathBuf]) -> Option<&PathBuf> {
+    dirs.iter()
+        .find(|dir| dir.join("plugin/gnvim.vim").is_file())
+}
+
+/// Finds gnvim's runtime files. If they aren't found, returns the path in
+/// the install prefix.
+pub fn find() -> PathBuf {
+    let exe = std::env::current_exe().ok();
+    let dirs = candidates(
+        exe.as_deref(),
+        glib::get_user_data_dir(),
+        glib::get_system_data_dirs(),
+        prefix(),
+    );
+
+    match find_in(&dirs) {
+        Some(dir) => {
+            debug!("Found gnvim runtime files in {}", dir.display());
+            dir.clone()
+        }
+        None => {
+            warn!(
+                "Gnvim runtime files not found (looked in: {:?}), set them \
+                 with --gnvim-rtp",
+                dirs
+            );
+            dirs.last().cloned().unwrap_or_default()
+        }
+    }
+}
+
+/// Returns the command that adds `dir` to nvim's 'runtimepath'.
+pub fn rtp_cmd(dir: &Path) -> String {
+    // Commas separate the directories in 'runtimepath'.
+    let dir = dir.to_string_lossy().replace(',', "\\,");
+    format!("let &rtp.=',{}'", dir.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates() {
+        let dirs = candidates(
+            Some(Path::new("/opt/gnvim/bin/gnvim")),
+            Some(PathBuf::from("/home/user/.local/share")),
+            vec![PathBuf::from("/app/share"), PathBuf::from("/usr/share")],
+            "/usr/local",
+        );
+
+        assert_eq!(
+            dirs,
+            vec![
+                PathBuf::from("/opt/gnvim/bin/runtime"),
+                PathBuf::from("/opt/gnvim/share/gnvim/runtime"),
+                PathBuf::from("/home/user/.local/share/gnvim/runtime"),
+                PathBuf::from("/app/share/gnvim/runtime"),
+                PathBuf::from("/usr/share/gnvim/runtime"),
+                PathBuf::from("/usr/local/share/gnvim/runtime"),
+            ]
+        );
+
+        let dirs = candidates(None, None, vec![], "/usr");
+        assert_eq!(dirs, vec![PathBuf::from("/usr/share/gnvim/runtime")]);
+    }
+
+    #[test]
+    fn test_rtp_cmd() {
+        assert_eq!(
+            rtp_cmd(Path::new("/usr/share/gnvim/runtime")),
+            "let &rtp.=',/usr/share/gnvim/runtime'"
+        );
+        assert_eq!(
+            rtp_cmd(Path::new("/home/it's,here/runtime")),
+            "let &rtp.=',/home/it''s\\,here/runtime'"
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use gdk::prelude::GdkContextExt;
+use gtk::prelude::*;
+
+/// Scale of the windows when their open animation starts, and when their
+/// close animation ends.
+const START_SCALE: f64 = 0.95;
+
+/// Settings of the window animations, set with `gnvim#window#set_animations`.
+/// The animations are off until they're set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Animations {
+    pub enabled: bool,
+    /// Duration of the floating windows' open animation, in milliseconds.
+    pub float_open: u64,
+    /// Duration of the windows' close animation, in milliseconds.
+    pub window_close: u64,
+}
+
+impl Default for Animations {
+    fn default() -> Self {
+        Animations {
+            enabled: false,
+            float_open: 150,
+            window_close: 120,
+        }
+    }
+}
+
+impl Animations {
+    /// Duration of the floating windows' open animation, if it's enabled.
+    pub fn float_open(&self) -> Option<u64> {
+        Some(self.float_open).filter(|d| self.enabled && *d > 0)
+    }
+
+    /// Duration of the windows' close animation, if it's enabled.
+    pub fn window_close(&self) -> Option<u64> {
+        Some(self.window_close).filter(|d| self.enabled && *d > 0)
+    }
+}
+
+/// Calls `f` on each frame of `widget` with the progress of an animation
+/// that lasts `duration` milliseconds, until the progress reaches 1.0.
+pub fn animate<W, F>(widget: &W, duration: u64, f: F)
+where
+    W: IsA<gtk::Widget>,
+    F: Fn(f64) + 'static,
+{
+    let start = Cell::new(None);
+    widget.add_tick_callback(move |_, clock| {
+        let now = clock.get_frame_time();
+        if start.get().is_none() {
+            start.set(Some(now));
+        }
+
+        let t = progress(now - start.get().unwrap_or(now), duration);
+        f(t);
+        glib::Continue(t < 1.0)
+    });
+}
+
+/// Returns the scale of a window at progress `t` of its open animation.
+pub fn open_scale(t: f64) -> f64 {
+    START_SCALE + (1.0 - START_SCALE) * t
+}
+
+/// Fades out (and shrinks) `pixbuf`, which is a snapshot of a closed
+/// window at `rect` (x, y, width, height) in `fixed`.
+pub fn fade_out(
+    fixed: &gtk::Fixed,
+    pixbuf: gdk_pixbuf::Pixbuf,
+    rect: (f64, f64, f64, f64),
+    duration: u64,
+) {
+    let (x, y, w, h) = rect;
+    let t = Rc::new(Cell::new(0.0));
+
+    let da = gtk::DrawingArea::new();
+    da.set_size_request(w.ceil() as i32, h.ceil() as i32);
+    // The window is gone, so is its input.
+    da.set_sensitive(false);
+    da.connect_draw(clone!(t => move |_, cr| {
+        let t = t.get();
+        let scale = open_scale(1.0 - t);
+
+        cr.translate(w / 2.0, h / 2.0);
+        cr.scale(scale, scale);
+        cr.translate(-w / 2.0, -h / 2.0);
+        // The snapshot is scaled by the screen's scale factor.
+        cr.scale(
+            w / f64::from(pixbuf.get_width()),
+            h / f64::from(pixbuf.get_height()),
+        );
+        cr.set_source_pixbuf(&pixbuf, 0.0, 0.0);
+        cr.paint_with_alpha(1.0 - t);
+
+        Inhibit(false)
+    }));
+
+    fixed.put(&da, x as i32, y as i32);
+    da.show();
+
+    animate(
+        &da,
+        duration,
+        clone!(fixed, da => move |progress| {
+            t.set(progress);
+            if progress < 1.0 {
+                da.queue_draw();
+            } else {
+                fixed.remove(&da);
+            }
+        }),
+    );
+}
+
+/// Returns the (eased) progress of an animation of `duration` milliseconds,
+/// after `elapsed` microseconds.
+fn progress(elapsed: i64, duration: u64) -> f64 {
+    if duration == 0 {
+        return 1.0;
+    }
+
+    let t = (elapsed as f64 / (duration * 1000) as f64)
+        .min(1.0)
+        .max(0.0);
+    ease_out_cubic(t)
+}
+
+/// From clutter-easing.c, based on Robert Penner's
+/// infamous easing equations, MIT license.
+pub fn ease_out_cubic(t: f64) -> f64 {
+    let p = t - 1f64;
+    p * p * p + 1f64
+}
+
+/// From clutter-easing.c, based on Robert Penner's
+/// infamous easing equations, MIT license.
+pub fn ease_in_out_cubic(t: f64) -> f64 {
+    let p = t * 2f64;
+
+    if p < 1f64 {
+        0.5 * p * p * p
+    } else {
+        let p = p - 2f64;
+        0.5 * (p * p * p + 2f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress() {
+        assert_eq!(progress(0, 100), 0.0);
+        assert_eq!(progress(50_000, 100), 0.875);
+        assert_eq!(progress(100_000, 100), 1.0);
+        assert_eq!(progress(200_000, 100), 1.0);
+        assert_eq!(progress(-10, 100), 0.0);
+        assert_eq!(progress(10, 0), 1.0);
+    }
+
+    #[test]
+    fn test_durations() {
+        let mut animations = Animations::default();
+        assert_eq!(animations.float_open(), None);
+
+        animations.enabled = true;
+        assert_eq!(animations.float_open(), Some(150));
+
+        animations.window_close = 0;
+        assert_eq!(animations.window_close(), None);
+
+        animations.enabled = false;
+        assert_eq!(animations.float_open(), None);
+    }
+}
@@ -0,0 +1,220 @@
+use gdk::prelude::GdkContextExt;
+
+use crate::ui::color::Color;
+
+/// Image that is drawn behind all the grids.
+pub struct Background {
+    pixbuf: gdk_pixbuf::Pixbuf,
+    /// Radius (in pixels) of the blur applied to the image.
+    blur: u32,
+    /// How much the image is dimmed towards the default background color,
+    /// from 0 (not at all) to 1 (completely).
+    dim: f64,
+
+    /// The image scaled (and blurred) to the size it was last drawn with.
+    cache: Option<cairo::ImageSurface>,
+}
+
+impl Background {
+    pub fn new(path: &str, blur: u32, dim: f64) -> Result<Self, glib::Error> {
+        Ok(Background {
+            pixbuf: gdk_pixbuf::Pixbuf::from_file(path)?,
+            blur,
+            dim: dim.max(0.0).min(1.0),
+            cache: None,
+        })
+    }
+
+    /// Draws the background to `cr` with `opacity`, covering an area of
+    /// `width` and `height`.
+    pub fn draw(
+        &mut self,
+        cr: &cairo::Context,
+        width: i32,
+        height: i32,
+        bg: &Color,
+        opacity: f64,
+    ) {
+        let surface = match self.cache {
+            Some(ref s)
+                if s.get_width() == width && s.get_height() == height =>
+            {
+                s.clone()
+            }
+            _ => {
+                // Drop the old surface first, so we don't keep two of them
+                // around.
+                self.cache = None;
+                let s = self.render(width, height);
+                self.cache = Some(s.clone());
+                s
+            }
+        };
+
+        cr.save();
+        cr.push_group();
+        cr.set_source_surface(&surface, 0.0, 0.0);
+        cr.paint();
+
+        if self.dim > 0.0 {
+            cr.set_source_rgba(bg.r, bg.g, bg.b, self.dim);
+            cr.paint();
+        }
+
+        cr.pop_group_to_source();
+        cr.paint_with_alpha(opacity);
+        cr.restore();
+    }
+
+    /// Renders the image scaled to cover `width` and `height`, keeping
+    /// its aspect ratio. Parts that don't fit are cropped equally from
+    /// both sides.
+    fn render(&self, width: i32, height: i32) -> cairo::ImageSurface {
+        let mut surface = cairo::ImageSurface::create(
+            cairo::Format::Rgb24,
+            width.max(1),
+            height.max(1),
+        )
+        .unwrap();
+
+        {
+            let cr = cairo::Context::new(&surface);
+            let iw = f64::from(self.pixbuf.get_width());
+            let ih = f64::from(self.pixbuf.get_height());
+            let scale = (f64::from(width) / iw).max(f64::from(height) / ih);
+
+            cr.translate(
+                (f64::from(width) - iw * scale) / 2.0,
+                (f64::from(height) - ih * scale) / 2.0,
+            );
+            cr.scale(scale, scale);
+            cr.set_source_pixbuf(&self.pixbuf, 0.0, 0.0);
+            cr.paint();
+        }
+
+        if self.blur > 0 {
+            let w = surface.get_width() as usize;
+            let h = surface.get_height() as usize;
+            let stride = surface.get_stride() as usize;
+            if let Ok(mut data) = surface.get_data() {
+                box_blur(&mut data, w, h, stride, self.blur as usize);
+            }
+        }
+
+        surface
+    }
+}
+
+/// Blurs `data` (4 bytes per pixel, each row `stride` bytes long) with a
+/// box blur of `radius` pixels. Pixels outside of the image are treated
+/// as copies of the closest edge pixel.
+fn box_blur(
+    data: &mut [u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+    radius: usize,
+) {
+    if radius == 0 || width == 0 || height == 0 {
+        return;
+    }
+
+    let mut src = vec![[0; 4]; width.max(height)];
+    let mut dst = src.clone();
+
+    let mut pass =
+        |len: usize, index: &dyn Fn(usize, usize) -> usize, lines| {
+            for line in 0..lines {
+                for (i, px) in src[..len].iter_mut().enumerate() {
+                    let idx = index(line, i);
+                    px.copy_from_slice(&data[idx..idx + 4]);
+                }
+
+                blur_line(&src[..len], &mut dst[..len], radius);
+
+                for (i, px) in dst[..len].iter().enumerate() {
+                    let idx = index(line, i);
+                    data[idx..idx + 4].copy_from_slice(px);
+                }
+            }
+        };
+
+    // Horizontally, and then vertically.
+    pass(width, &|row, col| row * stride + col * 4, height);
+    pass(height, &|col, row| row * stride + col * 4, width);
+}
+
+/// Box blurs a single line of pixels from `src` to `dst`.
+fn blur_line(src: &[[u8; 4]], dst: &mut [[u8; 4]], radius: usize) {
+    let last = src.len() as isize - 1;
+    let at = |i: isize| src[i.max(0).min(last) as usize];
+    let size = radius as u32 * 2 + 1;
+    let radius = radius as isize;
+
+    let mut sum = [0u32; 4];
+    for i in -radius..=radius {
+        for (s, c) in sum.iter_mut().zip(at(i).iter()) {
+            *s += u32::from(*c);
+        }
+    }
+
+    for (i, px) in dst.iter_mut().enumerate() {
+        for (c, s) in px.iter_mut().zip(sum.iter()) {
+            *c = (s / size) as u8;
+        }
+
+        let add = at(i as isize + radius + 1);
+        let sub = at(i as isize - radius);
+        for ((s, a), b) in sum.iter_mut().zip(add.iter()).zip(sub.iter()) {
+            *s = *s + u32::from(*a) - u32::from(*b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_box_blur_uniform() {
+        let mut data = vec![100; 4 * 4 * 3];
+        box_blur(&mut data, 4, 3, 16, 2);
+        assert!(data.iter().all(|v| *v == 100));
+    }
+
+    #[test]
+    fn test_box_blur_spreads_pixel() {
+        // 3x3 image with a single lit pixel in the middle.
+        let mut data = vec![0; 4 * 3 * 3];
+        data[16..20].copy_from_slice(&[90, 90, 90, 90]);
+
+        box_blur(&mut data, 3, 3, 12, 1);
+
+        for px in data.chunks(4) {
+            assert_eq!(px, &[10, 10, 10, 10]);
+        }
+    }
+
+    #[test]
+    fn test_box_blur_respects_stride() {
+        // 2x2 image, with 4 bytes of padding on each row.
+        let mut data = vec![0, 0, 0, 0, 40, 40, 40, 40, 7, 7, 7, 7];
+        data.extend_from_slice(&[0, 0, 0, 0, 40, 40, 40, 40, 7, 7, 7, 7]);
+
+        box_blur(&mut data, 2, 2, 12, 1);
+
+        for row in data.chunks(12) {
+            // Edge pixels are repeated, so the left pixel is (0+0+40)/3
+            // and the right one (0+40+40)/3.
+            assert_eq!(&row[..8], &[13, 13, 13, 13, 26, 26, 26, 26]);
+            assert_eq!(&row[8..], &[7, 7, 7, 7]);
+        }
+    }
+
+    #[test]
+    fn test_box_blur_zero_radius() {
+        let mut data = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        box_blur(&mut data, 2, 1, 8, 0);
+        assert_eq!(data, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+}
@@ -1,13 +1,91 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use gdk::keys::constants as key;
 use gtk::prelude::*;
 
+use log::debug;
+use nvim_rs::Value;
+
 use crate::nvim_bridge;
 use crate::nvim_gio::GioNeovim;
+use crate::ui::cmdline_syntax::{self, Syntax};
 use crate::ui::color::{Color, HlDefs, HlGroup};
-use crate::ui::common::calc_line_space;
+use crate::ui::common::{calc_line_space, spawn_local};
 use crate::ui::font::{Font, FontUnit};
-use crate::ui::wildmenu::Wildmenu;
+use crate::ui::wildmenu::{Wildmenu, WildmenuLayout};
 
 const MAX_WIDTH: i32 = 650;
+/// Maximum number of search matches counted. `gnvim#search#count()` uses
+/// the same maximum.
+const SEARCH_MAX_COUNT: u64 = 999;
+/// How long (in milliseconds) searching for matches can take.
+const SEARCH_TIMEOUT: u64 = 100;
+
+/// Where the cmdline is placed in the window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CmdlinePosition {
+    /// At the top of the window, horizontally centered.
+    Top,
+    /// At the center of the window.
+    Center,
+    /// At the bottom of the window, horizontally centered.
+    Bottom,
+    /// Below the cursor, or above it if there is no room below.
+    Cursor,
+    /// At the top of the focused window, as wide as the window.
+    WindowTop,
+    /// At the bottom of the focused window, as wide as the window.
+    WindowBottom,
+}
+
+impl CmdlinePosition {
+    pub fn from_string(position: &str) -> Option<Self> {
+        match position {
+            "top" => Some(CmdlinePosition::Top),
+            "center" => Some(CmdlinePosition::Center),
+            "bottom" => Some(CmdlinePosition::Bottom),
+            "cursor" => Some(CmdlinePosition::Cursor),
+            "window_top" => Some(CmdlinePosition::WindowTop),
+            "window_bottom" => Some(CmdlinePosition::WindowBottom),
+            _ => None,
+        }
+    }
+}
+
+impl Default for CmdlinePosition {
+    fn default() -> Self {
+        CmdlinePosition::Top
+    }
+}
+
+#[derive(Default)]
+struct CmdlineLayout {
+    position: CmdlinePosition,
+    /// Position of search cmdlines (`/` and `?`). Defaults to `position`.
+    search_position: Option<CmdlinePosition>,
+    /// If the shown cmdline is a search.
+    search: bool,
+    /// Rectangle of the cursor when the cmdline was shown, relative to the
+    /// base grid.
+    cursor: Option<gdk::Rectangle>,
+    /// Rectangle of the focused window when the cmdline was shown, relative
+    /// to the base grid.
+    window: Option<gdk::Rectangle>,
+    /// Where the cmdline currently is.
+    current: Option<(i32, i32)>,
+}
+
+impl CmdlineLayout {
+    /// Returns the position of the shown cmdline.
+    fn position(&self) -> CmdlinePosition {
+        match self.search_position {
+            Some(position) if self.search => position,
+            _ => self.position,
+        }
+    }
+}
 
 #[derive(Default)]
 pub struct CmdlineColors {
@@ -21,6 +99,9 @@ struct CmdlineBlock {
     scrolledwindow: gtk::ScrolledWindow,
 
     textview: gtk::TextView,
+    /// If the block is in copy mode, where the keys go to the text view
+    /// instead of nvim.
+    copy_mode: Rc<Cell<bool>>,
 
     css_provider: gtk::CssProvider,
 }
@@ -30,6 +111,33 @@ impl CmdlineBlock {
         let css_provider = gtk::CssProvider::new();
 
         let textview = gtk::TextView::new();
+        textview.set_editable(false);
+        textview.set_cursor_visible(false);
+
+        // In copy mode, the text is selected with the text view's own keys
+        // (e.g. shift + arrows). `y` or enter copies the selection.
+        let copy_mode = Rc::new(Cell::new(false));
+        textview.connect_key_press_event(clone!(copy_mode => move |tv, e| {
+            if !copy_mode.get() {
+                return Inhibit(false);
+            }
+
+            match e.get_keyval() {
+                key::y | key::Return | key::KP_Enter => {
+                    let buffer = tv.get_buffer().unwrap();
+                    let clipboard =
+                        gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD);
+                    buffer.copy_clipboard(&clipboard);
+                    leave_copy_mode(tv, &copy_mode);
+                }
+                key::Escape | key::q => {
+                    leave_copy_mode(tv, &copy_mode);
+                }
+                _ => return Inhibit(false),
+            }
+
+            Inhibit(true)
+        }));
 
         let scrolledwindow = gtk::ScrolledWindow::new(
             None::<&gtk::Adjustment>,
@@ -70,10 +178,23 @@ impl CmdlineBlock {
             frame,
             scrolledwindow,
             textview,
+            copy_mode,
             css_provider,
         }
     }
 
+    /// Moves the keyboard focus to the block's text, so that it can be
+    /// selected and copied with the keyboard.
+    fn enter_copy_mode(&self) {
+        self.copy_mode.set(true);
+        self.textview.set_cursor_visible(true);
+        self.textview.grab_focus();
+    }
+
+    fn in_copy_mode(&self) -> bool {
+        self.copy_mode.get() && self.textview.has_focus()
+    }
+
     fn set_line_space(&self, space: i64) {
         let (above, below) = calc_line_space(space);
         self.textview.set_pixels_above_lines(above as i32);
@@ -147,6 +268,9 @@ impl CmdlineBlock {
     }
 
     fn hide(&self) {
+        if self.copy_mode.get() {
+            leave_copy_mode(&self.textview, &self.copy_mode);
+        }
         self.frame.hide();
         self.scrolledwindow.set_size_request(-1, -1);
         self.scrolledwindow
@@ -207,9 +331,25 @@ impl CmdlineBlock {
     }
 }
 
+/// Leaves the copy mode of the cmdline block's `textview`. The keys go to
+/// nvim again.
+fn leave_copy_mode(textview: &gtk::TextView, copy_mode: &Cell<bool>) {
+    copy_mode.set(false);
+    textview.set_cursor_visible(false);
+
+    if let Some(window) = textview
+        .get_toplevel()
+        .and_then(|w| w.downcast::<gtk::Window>().ok())
+    {
+        window.set_focus(None::<&gtk::Widget>);
+    }
+}
+
 struct CmdlineInput {
     frame: gtk::Frame,
     textview: gtk::TextView,
+    /// Number of matches, shown for searches.
+    count: gtk::Label,
     css_provider: gtk::CssProvider,
 
     /// Content, excluding prompt, firstc etc.
@@ -241,15 +381,23 @@ impl CmdlineInput {
         scroll.set_policy(gtk::PolicyType::Automatic, gtk::PolicyType::Never);
         scroll.add(&textview);
 
+        let count = gtk::Label::new(None);
+        count.set_no_show_all(true);
+
+        let box_ = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        box_.pack_start(&scroll, true, true, 0);
+        box_.pack_end(&count, false, false, 0);
+
         // Wrap the textview into a frame, mainly to add some padding (with css).
         let frame = gtk::Frame::new(None);
-        frame.add(&scroll);
+        frame.add(&box_);
 
-        add_css_provider!(&css_provider, frame, textview);
+        add_css_provider!(&css_provider, frame, textview, count);
 
         CmdlineInput {
             frame,
             textview,
+            count,
             css_provider,
 
             content: String::new(),
@@ -332,7 +480,7 @@ impl CmdlineInput {
                 border-radius: 0;
             }}
 
-            GtkTextView {{
+            GtkTextView, GtkLabel {{
                 color: #{fg};
                 background: #{bg};
             }}",
@@ -358,6 +506,10 @@ impl CmdlineInput {
                 caret-color: #{fg};
                 color: #{fg};
                 background: #{bg};
+            }}
+
+            label {{
+                color: #{fg};
             }}",
             fg = colors.fg.unwrap_or(hl_defs.default_fg).to_hex(),
             bg = colors.bg.unwrap_or(hl_defs.default_bg).to_hex()
@@ -398,6 +550,9 @@ impl CmdlineInput {
 pub struct Cmdline {
     css_provider: gtk::CssProvider,
     fixed: gtk::Fixed,
+    box_: gtk::Box,
+
+    layout: Rc<RefCell<CmdlineLayout>>,
 
     input: CmdlineInput,
     block: CmdlineBlock,
@@ -412,6 +567,16 @@ pub struct Cmdline {
     /// Our font. This is inherited to input, block and wildmenu through our
     /// styles.
     font: Font,
+
+    nvim: GioNeovim,
+    /// Id of the latest search count request, so that outdated responses
+    /// are ignored.
+    search_count_id: Rc<Cell<u64>>,
+    /// Id of the latest syntax highlighting request.
+    syntax_id: Rc<Cell<u64>>,
+    /// Colors of the syntax items. Fetched from nvim when needed, and
+    /// cleared when the colors change.
+    syntax_colors: Rc<RefCell<Option<HashMap<Syntax, Color>>>>,
 }
 
 impl Cmdline {
@@ -431,10 +596,11 @@ impl Cmdline {
         let frame = gtk::Frame::new(None);
         frame.add(&inner_box);
 
-        let wildmenu = Wildmenu::new(nvim);
+        let wildmenu = Wildmenu::new(nvim.clone());
 
         // box_ is the actual container for cmdline and wildmenu.
         let box_ = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        box_.set_widget_name("cmdline");
         box_.pack_start(&frame, true, true, 0);
         box_.pack_start(&wildmenu.widget(), true, true, 0);
 
@@ -445,18 +611,29 @@ impl Cmdline {
 
         parent.add_overlay(&fixed);
 
-        parent.connect_size_allocate(clone!(fixed, box_ => move |_, alloc| {
-            // Make sure we'll fit to the available space.
-            let width = MAX_WIDTH.min(alloc.width);
-            box_.set_size_request(width, -1);
+        let layout = Rc::new(RefCell::new(CmdlineLayout::default()));
+
+        parent.connect_size_allocate(
+            clone!(fixed, box_, layout => move |_, alloc| {
+                relayout(&fixed, &box_, &layout, (alloc.width, alloc.height));
+            }),
+        );
 
-            let x = alloc.width / 2 - width / 2;
-            fixed.move_(&box_, x, 0);
+        // Our height changes when the block or the wildmenu is shown or
+        // hidden, which might require us to move.
+        let fixed_weak = fixed.downgrade();
+        box_.connect_size_allocate(clone!(layout => move |box_, _| {
+            let fixed = upgrade_weak!(fixed_weak);
+            let area =
+                (fixed.get_allocated_width(), fixed.get_allocated_height());
+            relayout(&fixed, box_, &layout, area);
         }));
 
         Cmdline {
             css_provider,
             fixed,
+            box_,
+            layout,
             input,
             block,
             wildmenu,
@@ -464,6 +641,10 @@ impl Cmdline {
             show_wildmenu: false,
             font: Font::default(),
             colors: CmdlineColors::default(),
+            nvim,
+            search_count_id: Rc::new(Cell::new(0)),
+            syntax_id: Rc::new(Cell::new(0)),
+            syntax_colors: Rc::new(RefCell::new(None)),
         }
     }
 
@@ -479,15 +660,21 @@ impl Cmdline {
                 .cloned()
                 .unwrap_or_default()
                 .foreground,
+            // Like the floating windows' border, if the colorscheme sets
+            // `FloatBorder`.
             border: hl_defs
-                .get_hl_group(&HlGroup::CmdlineBorder)
-                .cloned()
-                .unwrap_or_default()
-                .background,
+                .get_hl_group(&HlGroup::FloatBorder)
+                .and_then(|hl| hl.foreground)
+                .or_else(|| {
+                    hl_defs
+                        .get_hl_group(&HlGroup::CmdlineBorder)
+                        .and_then(|hl| hl.background)
+                }),
         };
 
         self.input.set_colors(&self.colors, hl_defs);
         self.block.set_colors(&self.colors, hl_defs);
+        self.syntax_colors.replace(None);
 
         self.set_styles(hl_defs);
     }
@@ -520,6 +707,10 @@ impl Cmdline {
             frame > box {{
                 box-shadow: none;
             }}
+
+            box.compact > frame {{
+                padding: 2px;
+            }}
             ",
             font_wild = self.font.as_wild_css(FontUnit::Point),
             bg = self.colors.border.unwrap_or(hl_defs.default_bg).to_hex()
@@ -545,6 +736,10 @@ impl Cmdline {
                 padding: 6px;
                 border: none;
                 border-radius: 0;
+            }}
+
+            GtkBox.compact > GtkFrame {{
+                padding: 2px;
             }}",
             font_wild = self.font.as_wild_css(FontUnit::Pixel),
             bg = self.colors.border.unwrap_or(hl_defs.default_bg).to_hex()
@@ -557,14 +752,70 @@ impl Cmdline {
         self.fixed.hide();
     }
 
+    /// Shows the cmdline. `cursor` and `window` are the rectangles of the
+    /// cursor and the focused window, which are used for placing the
+    /// cmdline with `CmdlinePosition::Cursor` and the window positions.
     pub fn show(
         &mut self,
         content: nvim_bridge::CmdlineShow,
+        cursor: Option<gdk::Rectangle>,
+        window: Option<gdk::Rectangle>,
         hl_defs: &HlDefs,
     ) {
+        let search = content.firstc == "/" || content.firstc == "?";
+
+        // Keep our position while we're visible, even if the cursor moves.
+        if !self.fixed.is_visible() {
+            let mut layout = self.layout.borrow_mut();
+            layout.cursor = cursor;
+            layout.window = window;
+            layout.search = search;
+            drop(layout);
+            self.relayout();
+        }
+
+        let (position, search_position) = {
+            let layout = self.layout.borrow();
+            (layout.position(), layout.search_position)
+        };
+
+        if search && search_position.is_some() {
+            let pattern = content
+                .content
+                .iter()
+                .map(|c| c.1.as_str())
+                .collect::<String>();
+            self.update_search_count(pattern);
+        } else {
+            self.input.count.hide();
+        }
+
+        let style = self.box_.get_style_context();
+        match position {
+            CmdlinePosition::WindowTop | CmdlinePosition::WindowBottom => {
+                style.add_class("compact")
+            }
+            _ => style.remove_class("compact"),
+        }
+
+        // Highlight commands, unless nvim has colored the text already (e.g.
+        // with g:Nvim_color_cmdline).
+        let highlight = content.firstc == ":"
+            && content.prompt.is_empty()
+            && content.content.iter().all(|c| c.0 == 0);
+        let text = if highlight {
+            Some(content.content.iter().map(|c| c.1.as_str()).collect())
+        } else {
+            None
+        };
+
         self.input.set_text(content, hl_defs);
         self.fixed.show_all();
 
+        if let Some(text) = text {
+            self.highlight_syntax(text);
+        }
+
         if !self.show_block {
             self.block.hide();
         }
@@ -597,6 +848,151 @@ impl Cmdline {
         });
     }
 
+    pub fn set_position(&mut self, position: CmdlinePosition) {
+        self.layout.borrow_mut().position = position;
+        self.relayout();
+    }
+
+    /// Sets the position of search cmdlines, which also shows the number
+    /// of matches. `None` places them like the other cmdlines.
+    pub fn set_search_position(&mut self, position: Option<CmdlinePosition>) {
+        self.layout.borrow_mut().search_position = position;
+        self.relayout();
+    }
+
+    /// Highlights the command `text` in the input. Nvim parses the command,
+    /// so the highlighting is applied later.
+    fn highlight_syntax(&self, text: String) {
+        let id = self.syntax_id.get() + 1;
+        self.syntax_id.set(id);
+
+        if text.trim().is_empty() {
+            return;
+        }
+
+        let ids = self.syntax_id.clone();
+        let colors = self.syntax_colors.clone();
+        let buffer = self.input.textview.get_buffer().unwrap();
+        let offset = self.input.prompt_len;
+        let nvim = self.nvim.clone();
+        spawn_local(async move {
+            let parsed = nvim
+                .call_function(
+                    "nvim_parse_cmd",
+                    vec![text.as_str().into(), Value::Map(vec![])],
+                )
+                .await;
+            let fetched = if colors.borrow().is_none() {
+                Some(fetch_syntax_colors(&nvim).await)
+            } else {
+                None
+            };
+
+            // The command has changed already.
+            if ids.get() != id {
+                return;
+            }
+
+            if let Some(fetched) = fetched {
+                colors.replace(Some(fetched));
+            }
+
+            // Invalid (e.g. incomplete) commands fail to parse.
+            let name = parsed.ok().and_then(|cmd| {
+                cmd.as_map()?
+                    .iter()
+                    .find(|(k, _)| k.as_str() == Some("cmd"))
+                    .and_then(|(_, v)| v.as_str().map(String::from))
+            });
+
+            let colors = colors.borrow();
+            let colors = match colors.as_ref() {
+                Some(colors) => colors,
+                None => return,
+            };
+
+            let table = buffer.get_tag_table().unwrap();
+            for (syntax, start, end) in
+                cmdline_syntax::spans(&text, name.as_deref())
+            {
+                let color = match colors.get(&syntax) {
+                    Some(color) => color,
+                    None => continue,
+                };
+
+                let tag_name = format!("syntax-{}", syntax.hl_group());
+                let tag = table.lookup(&tag_name).unwrap_or_else(|| {
+                    let tag = gtk::TextTag::new(Some(&tag_name));
+                    table.add(&tag);
+                    tag
+                });
+                tag.set_property_foreground_rgba(Some(&gdk::RGBA {
+                    red: color.r,
+                    green: color.g,
+                    blue: color.b,
+                    alpha: 1.0,
+                }));
+                // Override the tags from the content's markup.
+                tag.set_priority(table.get_size() - 1);
+
+                let iter = |i: usize| {
+                    let chars = text[..i].chars().count() as i32;
+                    buffer.get_iter_at_offset(offset + chars)
+                };
+                buffer.apply_tag(&tag, &iter(start), &iter(end));
+            }
+        });
+    }
+
+    /// Shows the number of matches for `pattern`. Nvim is asked for the
+    /// count, so the label is updated later.
+    fn update_search_count(&self, pattern: String) {
+        let id = self.search_count_id.get() + 1;
+        self.search_count_id.set(id);
+
+        if pattern.is_empty() {
+            self.input.count.hide();
+            return;
+        }
+
+        let ids = self.search_count_id.clone();
+        let label = self.input.count.clone();
+        let nvim = self.nvim.clone();
+        spawn_local(async move {
+            let opts = Value::Map(vec![
+                ("pattern".into(), pattern.into()),
+                ("maxcount".into(), SEARCH_MAX_COUNT.into()),
+                ("timeout".into(), SEARCH_TIMEOUT.into()),
+            ]);
+            let res = nvim.call_function("searchcount", vec![opts]).await;
+
+            // The pattern has changed already.
+            if ids.get() != id {
+                return;
+            }
+
+            let count = match res {
+                Ok(count) => count,
+                Err(err) => {
+                    debug!("Failed to get search count: {}", err);
+                    label.hide();
+                    return;
+                }
+            };
+
+            label.set_text(&search_count_from_value(&count));
+            label.show();
+        });
+    }
+
+    fn relayout(&self) {
+        let area = (
+            self.fixed.get_allocated_width(),
+            self.fixed.get_allocated_height(),
+        );
+        relayout(&self.fixed, &self.box_, &self.layout, area);
+    }
+
     pub fn set_pos(&mut self, pos: u64, level: u64) {
         self.input.set_cursor(pos as usize, level);
     }
@@ -615,6 +1011,23 @@ impl Cmdline {
         self.show_block = false;
     }
 
+    /// Starts copy mode in the block, if it's shown. Returns false if it
+    /// isn't.
+    pub fn enter_block_copy_mode(&self) -> bool {
+        if !self.show_block {
+            return false;
+        }
+
+        self.block.enter_copy_mode();
+        true
+    }
+
+    /// Returns true if the block is in copy mode, so that the keys should
+    /// go to it instead of nvim.
+    pub fn block_in_copy_mode(&self) -> bool {
+        self.block.in_copy_mode()
+    }
+
     pub fn block_append(
         &mut self,
         line: nvim_bridge::CmdlineBlockAppend,
@@ -625,7 +1038,8 @@ impl Cmdline {
 
     pub fn wildmenu_show(&mut self, items: &[nvim_bridge::CompletionItem]) {
         self.show_wildmenu = true;
-        self.wildmenu.set_items(items);
+        let width = self.box_.get_allocated_width();
+        self.wildmenu.set_items(items, width);
         self.wildmenu.show();
 
         self.fixed.check_resize();
@@ -643,7 +1057,291 @@ impl Cmdline {
         self.wildmenu.select(item_num);
     }
 
+    pub fn wildmenu_set_layout(&mut self, layout: WildmenuLayout) {
+        self.wildmenu.set_layout(layout);
+    }
+
     pub fn wildmenu_set_colors(&self, hl_defs: &HlDefs) {
         self.wildmenu.set_colors(hl_defs);
     }
 }
+
+/// Moves `box_` to where `layout` wants it to be, in an `area` of the given
+/// size.
+fn relayout(
+    fixed: &gtk::Fixed,
+    box_: &gtk::Box,
+    layout: &RefCell<CmdlineLayout>,
+    area: (i32, i32),
+) {
+    let mut layout = layout.borrow_mut();
+    let position = layout.position();
+
+    // Make sure we'll fit to the available space.
+    let width = cmdline_width(position, area, layout.window.as_ref());
+    if box_.get_size_request().0 != width {
+        box_.set_size_request(width, -1);
+    }
+
+    let size = (width, box_.get_allocated_height());
+    let pos = cmdline_position(
+        position,
+        area,
+        size,
+        layout.cursor.as_ref(),
+        layout.window.as_ref(),
+    );
+
+    // Moving the box queues a resize, so only move it when needed.
+    if layout.current != Some(pos) {
+        layout.current = Some(pos);
+        fixed.move_(box_, pos.0, pos.1);
+    }
+}
+
+/// Returns the width of a cmdline in an `area`, placed according to
+/// `position`. `window` is the focused window.
+fn cmdline_width(
+    position: CmdlinePosition,
+    area: (i32, i32),
+    window: Option<&gdk::Rectangle>,
+) -> i32 {
+    match (position, window) {
+        (CmdlinePosition::WindowTop, Some(window))
+        | (CmdlinePosition::WindowBottom, Some(window)) => {
+            window.width.min(area.0)
+        }
+        _ => MAX_WIDTH.min(area.0),
+    }
+}
+
+/// Returns the position of a cmdline of `size` in an `area`, placed
+/// according to `position`. Without `cursor`, `CmdlinePosition::Cursor`
+/// behaves like `CmdlinePosition::Top`. Similarly, without `window` (the
+/// focused window), the window positions behave like `CmdlinePosition::Top`
+/// and `CmdlinePosition::Bottom`.
+fn cmdline_position(
+    position: CmdlinePosition,
+    area: (i32, i32),
+    size: (i32, i32),
+    cursor: Option<&gdk::Rectangle>,
+    window: Option<&gdk::Rectangle>,
+) -> (i32, i32) {
+    let (width, height) = size;
+    let center_x = (area.0 - width) / 2;
+
+    let (x, y) = match (position, cursor, window) {
+        (CmdlinePosition::Top, _, _)
+        | (CmdlinePosition::Cursor, None, _)
+        | (CmdlinePosition::WindowTop, _, None) => (center_x, 0),
+        (CmdlinePosition::Center, _, _) => (center_x, (area.1 - height) / 2),
+        (CmdlinePosition::Bottom, _, _)
+        | (CmdlinePosition::WindowBottom, _, None) => {
+            (center_x, area.1 - height)
+        }
+        (CmdlinePosition::WindowTop, _, Some(window)) => {
+            (window.x.min(area.0 - width), window.y)
+        }
+        (CmdlinePosition::WindowBottom, _, Some(window)) => (
+            window.x.min(area.0 - width),
+            (window.y + window.height).min(area.1) - height,
+        ),
+        (CmdlinePosition::Cursor, Some(cursor), _) => {
+            let below = cursor.y + cursor.height;
+            let y = if below + height <= area.1 {
+                below
+            } else {
+                cursor.y - height
+            };
+            (cursor.x.min(area.0 - width), y)
+        }
+    };
+
+    (x.max(0), y.max(0))
+}
+
+/// Returns the colors of the syntax items' highlight groups. Groups without
+/// a foreground color are left out.
+async fn fetch_syntax_colors(nvim: &GioNeovim) -> HashMap<Syntax, Color> {
+    let groups = Syntax::ALL
+        .iter()
+        .map(|syntax| format!("'{}'", syntax.hl_group()))
+        .collect::<Vec<_>>()
+        .join(",");
+    let expr = format!(
+        "map([{}], {{_, g -> synIDattr(synIDtrans(hlID(g)), 'fg#')}})",
+        groups
+    );
+
+    let colors = match nvim.eval(&expr).await {
+        Ok(Value::Array(colors)) => colors,
+        Ok(_) => return HashMap::new(),
+        Err(err) => {
+            debug!("Failed to get cmdline syntax colors: {}", err);
+            return HashMap::new();
+        }
+    };
+
+    Syntax::ALL
+        .iter()
+        .zip(colors)
+        .filter_map(|(syntax, color)| {
+            let color = Color::from_hex_string(color.as_str()?.into()).ok()?;
+            Some((*syntax, color))
+        })
+        .collect()
+}
+
+/// Formats the result of `searchcount()` with `search_count_text`.
+pub fn search_count_from_value(count: &Value) -> String {
+    let get = |key: &str| {
+        count
+            .as_map()
+            .and_then(|map| map.iter().find(|(k, _)| k.as_str() == Some(key)))
+            .and_then(|(_, v)| v.as_u64())
+            .unwrap_or(0)
+    };
+
+    search_count_text(get("current"), get("total"), get("incomplete"))
+}
+
+/// Formats the number of search matches like nvim does with 'shortmess'
+/// not containing `S`. `incomplete` is from `searchcount()`.
+fn search_count_text(current: u64, total: u64, incomplete: u64) -> String {
+    match incomplete {
+        // Counting timed out.
+        1 => String::from("[?/??]"),
+        // There are more than SEARCH_MAX_COUNT matches.
+        2 if current > SEARCH_MAX_COUNT => {
+            format!("[>{}/>{}]", SEARCH_MAX_COUNT, SEARCH_MAX_COUNT)
+        }
+        2 => format!("[{}/>{}]", current, SEARCH_MAX_COUNT),
+        _ => format!("[{}/{}]", current, total),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(x: i32, y: i32) -> gdk::Rectangle {
+        gdk::Rectangle {
+            x,
+            y,
+            width: 10,
+            height: 20,
+        }
+    }
+
+    #[test]
+    fn test_cmdline_position_fixed() {
+        let area = (1000, 800);
+        let size = (600, 100);
+
+        assert_eq!(
+            cmdline_position(CmdlinePosition::Top, area, size, None, None),
+            (200, 0)
+        );
+        assert_eq!(
+            cmdline_position(CmdlinePosition::Center, area, size, None, None),
+            (200, 350)
+        );
+        assert_eq!(
+            cmdline_position(CmdlinePosition::Bottom, area, size, None, None),
+            (200, 700)
+        );
+        // Without a cursor, falls back to the top.
+        assert_eq!(
+            cmdline_position(CmdlinePosition::Cursor, area, size, None, None),
+            (200, 0)
+        );
+    }
+
+    #[test]
+    fn test_cmdline_position_cursor() {
+        let area = (1000, 800);
+        let size = (600, 100);
+        let pos = |c| {
+            cmdline_position(
+                CmdlinePosition::Cursor,
+                area,
+                size,
+                Some(&c),
+                None,
+            )
+        };
+
+        // Below the cursor.
+        assert_eq!(pos(cursor(100, 200)), (100, 220));
+        // No room below, so above the cursor.
+        assert_eq!(pos(cursor(100, 700)), (100, 600));
+        // Kept inside the area horizontally.
+        assert_eq!(pos(cursor(900, 200)), (400, 220));
+    }
+
+    #[test]
+    fn test_cmdline_position_small_area() {
+        // The cmdline doesn't fit, so it's kept at the top left corner.
+        let size = (600, 100);
+
+        assert_eq!(
+            cmdline_position(
+                CmdlinePosition::Bottom,
+                (500, 50),
+                size,
+                None,
+                None
+            ),
+            (0, 0)
+        );
+        assert_eq!(
+            cmdline_position(
+                CmdlinePosition::Cursor,
+                (500, 50),
+                size,
+                Some(&cursor(100, 20)),
+                None
+            ),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    fn test_cmdline_position_window() {
+        let area = (1000, 800);
+        let window = gdk::Rectangle {
+            x: 500,
+            y: 100,
+            width: 400,
+            height: 300,
+        };
+        let width =
+            cmdline_width(CmdlinePosition::WindowTop, area, Some(&window));
+        assert_eq!(width, 400);
+
+        let size = (width, 30);
+        let pos = |position| {
+            cmdline_position(position, area, size, None, Some(&window))
+        };
+        assert_eq!(pos(CmdlinePosition::WindowTop), (500, 100));
+        assert_eq!(pos(CmdlinePosition::WindowBottom), (500, 370));
+
+        // Without a window, falls back to the top and bottom of the area.
+        let width = cmdline_width(CmdlinePosition::WindowTop, area, None);
+        assert_eq!(width, 650);
+
+        let size = (width, 30);
+        let pos = |position| cmdline_position(position, area, size, None, None);
+        assert_eq!(pos(CmdlinePosition::WindowTop), (175, 0));
+        assert_eq!(pos(CmdlinePosition::WindowBottom), (175, 770));
+    }
+
+    #[test]
+    fn test_search_count_text() {
+        assert_eq!(search_count_text(3, 10, 0), "[3/10]");
+        assert_eq!(search_count_text(0, 0, 0), "[0/0]");
+        assert_eq!(search_count_text(0, 0, 1), "[?/??]");
+        assert_eq!(search_count_text(5, 999, 2), "[5/>999]");
+        assert_eq!(search_count_text(1000, 999, 2), "[>999/>999]");
+    }
+}
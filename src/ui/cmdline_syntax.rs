@@ -0,0 +1,287 @@
+/// Commands whose arguments are expressions, so their strings are
+/// highlighted. These are the full names that `nvim_parse_cmd()` returns.
+const EXPRESSION_COMMANDS: &[&str] = &[
+    "call", "const", "echo", "echoerr", "echomsg", "echon", "elseif", "eval",
+    "execute", "for", "if", "let", "return", "while",
+];
+
+/// Syntax items of a typed ex command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Syntax {
+    Range,
+    Command,
+    String,
+}
+
+impl Syntax {
+    pub const ALL: [Syntax; 3] =
+        [Syntax::Range, Syntax::Command, Syntax::String];
+
+    /// Highlight group that colors the item.
+    pub fn hl_group(self) -> &'static str {
+        match self {
+            Syntax::Range => "Number",
+            Syntax::Command => "Statement",
+            Syntax::String => "String",
+        }
+    }
+}
+
+/// Parts of a typed ex command, as byte ranges (start, end exclusive).
+#[derive(Debug, Default, PartialEq)]
+pub struct Command {
+    pub range: Option<(usize, usize)>,
+    /// Command's name, including a bang.
+    pub name: Option<(usize, usize)>,
+    /// Where the arguments start.
+    pub args: usize,
+}
+
+/// Splits `text` (without the leading `:`) into its range, name and
+/// arguments. Nvim tells if the command is valid, this only finds where
+/// the parts are.
+pub fn lex(text: &str) -> Command {
+    let bytes = text.as_bytes();
+    let skip = |mut i: usize, chars: &[u8]| {
+        while i < bytes.len() && chars.contains(&bytes[i]) {
+            i += 1;
+        }
+        i
+    };
+
+    let start = skip(0, b" \t:");
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'0'..=b'9'
+            | b'.'
+            | b'$'
+            | b'%'
+            | b','
+            | b';'
+            | b'+'
+            | b'-'
+            | b' '
+            | b'\t' => i += 1,
+            // Marks.
+            b'\'' => i = next_char(text, i + 1),
+            b'\\'
+                if matches!(
+                    bytes.get(i + 1),
+                    Some(b'/') | Some(b'?') | Some(b'&')
+                ) =>
+            {
+                i += 2
+            }
+            // Patterns.
+            b'/' | b'?' => {
+                let delim = bytes[i];
+                i += 1;
+                while i < bytes.len() && bytes[i] != delim {
+                    i = next_char(
+                        text,
+                        if bytes[i] == b'\\' { i + 1 } else { i },
+                    );
+                }
+                i = (i + 1).min(bytes.len());
+            }
+            _ => break,
+        }
+    }
+
+    let range_end = start + text[start..i].trim_end().len();
+    let range = if range_end > start {
+        Some((start, range_end))
+    } else {
+        None
+    };
+
+    let name_start = skip(i, b" \t:");
+    let mut name_end = match bytes.get(name_start) {
+        Some(b) if b.is_ascii_alphabetic() => {
+            name_start
+                + bytes[name_start..]
+                    .iter()
+                    .take_while(|b| b.is_ascii_alphabetic())
+                    .count()
+        }
+        Some(b) if b"!&<>=~#@*".contains(b) => name_start + 1,
+        _ => name_start,
+    };
+    if name_end > name_start && bytes.get(name_end) == Some(&b'!') {
+        name_end += 1;
+    }
+
+    Command {
+        range,
+        name: if name_end > name_start {
+            Some((name_start, name_end))
+        } else {
+            None
+        },
+        args: name_end,
+    }
+}
+
+/// Returns the index after the character at `i`.
+fn next_char(text: &str, i: usize) -> usize {
+    text.get(i..)
+        .and_then(|rest| rest.chars().next())
+        .map(|c| i + c.len_utf8())
+        .unwrap_or_else(|| text.len())
+}
+
+/// Returns the string literals of `text`, starting from `from`. Unterminated
+/// strings continue to the end.
+fn strings(text: &str, from: usize) -> Vec<(usize, usize)> {
+    let bytes = text.as_bytes();
+    let mut strings = vec![];
+    let mut i = from;
+
+    while i < bytes.len() {
+        let quote = bytes[i];
+        if quote != b'"' && quote != b'\'' {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        i += 1;
+        loop {
+            match bytes.get(i) {
+                None => break,
+                // Single quotes are escaped by doubling them.
+                Some(b'\'')
+                    if quote == b'\'' && bytes.get(i + 1) == Some(&b'\'') =>
+                {
+                    i += 2
+                }
+                Some(b'\\') if quote == b'"' => i += 2,
+                Some(b) if *b == quote => {
+                    i += 1;
+                    break;
+                }
+                _ => i += 1,
+            }
+        }
+
+        i = i.min(bytes.len());
+        strings.push((start, i));
+    }
+
+    strings
+}
+
+/// Returns the highlighted parts of `text` as byte ranges. `name` is the
+/// command's full name from nvim, or `None` if the command isn't valid.
+pub fn spans(text: &str, name: Option<&str>) -> Vec<(Syntax, usize, usize)> {
+    let cmd = lex(text);
+    let mut spans = vec![];
+
+    if let Some((start, end)) = cmd.range {
+        spans.push((Syntax::Range, start, end));
+    }
+
+    let name = match name {
+        Some(name) => name,
+        None => return spans,
+    };
+
+    if let Some((start, end)) = cmd.name {
+        spans.push((Syntax::Command, start, end));
+    }
+
+    if EXPRESSION_COMMANDS.contains(&name) {
+        spans.extend(
+            strings(text, cmd.args)
+                .into_iter()
+                .map(|(start, end)| (Syntax::String, start, end)),
+        );
+    }
+
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lex() {
+        assert_eq!(
+            lex("edit foo"),
+            Command {
+                range: None,
+                name: Some((0, 4)),
+                args: 4,
+            }
+        );
+        assert_eq!(
+            lex("1,$s/a/b/"),
+            Command {
+                range: Some((0, 3)),
+                name: Some((3, 4)),
+                args: 4,
+            }
+        );
+        assert_eq!(
+            lex(" 'a,'b w! out"),
+            Command {
+                range: Some((1, 6)),
+                name: Some((7, 9)),
+                args: 9,
+            }
+        );
+        assert_eq!(
+            lex("/fo\\/o/,?bar?d"),
+            Command {
+                range: Some((0, 13)),
+                name: Some((13, 14)),
+                args: 14,
+            }
+        );
+        assert_eq!(
+            lex("%>"),
+            Command {
+                range: Some((0, 1)),
+                name: Some((1, 2)),
+                args: 2,
+            }
+        );
+        assert_eq!(
+            lex("12"),
+            Command {
+                range: Some((0, 2)),
+                name: None,
+                args: 2,
+            }
+        );
+        assert_eq!(lex(""), Command::default());
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_eq!(
+            strings(r#"echo "a\"b" 'c''d'"#, 4),
+            vec![(5, 11), (12, 18)]
+        );
+        assert_eq!(strings(r#"echo 'a" "b"#, 4), vec![(5, 11)]);
+        assert_eq!(strings("echo 1", 4), vec![]);
+    }
+
+    #[test]
+    fn test_spans() {
+        assert_eq!(
+            spans("2echo 'a'", Some("echo")),
+            vec![
+                (Syntax::Range, 0, 1),
+                (Syntax::Command, 1, 5),
+                (Syntax::String, 6, 9),
+            ]
+        );
+        // Strings are highlighted only in expressions.
+        assert_eq!(spans("e 'a'", Some("edit")), vec![(Syntax::Command, 0, 1)]);
+        // Invalid commands get only their range highlighted.
+        assert_eq!(spans("1foo 'a'", None), vec![(Syntax::Range, 0, 1)]);
+    }
+}
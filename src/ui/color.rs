@@ -1,4 +1,6 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
 
 #[derive(Hash, PartialEq, Eq)]
 pub enum HlGroup {
@@ -16,6 +18,30 @@ pub enum HlGroup {
     WildmenuSel,
 
     MsgSeparator,
+    VertSplit,
+    StatusLine,
+
+    Toast,
+    NormalFloat,
+    FloatBorder,
+    ErrorMsg,
+    WarningMsg,
+
+    CursorLine,
+    Visual,
+    Search,
+    PmenuSbar,
+    PmenuThumb,
+    SignColumn,
+    DiffAdd,
+    DiffChange,
+    DiffDelete,
+
+    // Not sent by nvim, see `HlDefs::set_hl_group_highlight`.
+    DiagnosticError,
+    DiagnosticWarn,
+    DiagnosticInfo,
+    DiagnosticHint,
 }
 
 #[derive(Default)]
@@ -23,6 +49,23 @@ pub struct HlDefs {
     hl_defs: HashMap<u64, Highlight>,
 
     hl_groups: HashMap<HlGroup, u64>,
+    /// Highlights of the groups that nvim doesn't send with
+    /// `hl_group_set`, so gnvim's runtime sends them instead.
+    hl_group_highlights: HashMap<HlGroup, Highlight>,
+
+    /// Hyperlinks of the highlights that have one. Shared with the grids,
+    /// which make the links clickable.
+    urls: Rc<RefCell<HashMap<u64, String>>>,
+
+    /// Names of the highlight groups that each highlight is made of (sent
+    /// by nvim with `ext_hlstate`).
+    names: HashMap<u64, Vec<String>>,
+    /// Highlight groups of virtual text, and the style it's drawn in. See
+    /// `set_virtual_text`.
+    virtual_text: Option<(Vec<String>, VirtualText)>,
+
+    /// The 16 color terminal palette.
+    pub terminal_palette: TerminalPalette,
 
     pub default_fg: Color,
     pub default_bg: Color,
@@ -38,20 +81,111 @@ impl HlDefs {
         self.hl_defs.get(id)
     }
 
-    pub fn insert(&mut self, id: u64, hl: Highlight) -> Option<Highlight> {
+    pub fn insert(&mut self, id: u64, mut hl: Highlight) -> Option<Highlight> {
+        hl.virtual_text = self.virtual_text_of(id);
         self.hl_defs.insert(id, hl)
     }
 
+    /// Sets the names of the highlight groups that highlight `id` is made
+    /// of. Set before the highlight is inserted.
+    pub fn set_names(&mut self, id: u64, names: Vec<String>) {
+        if names.is_empty() {
+            self.names.remove(&id);
+        } else {
+            self.names.insert(id, names);
+        }
+    }
+
+    /// Draws the highlights that are made of any of `groups` as virtual
+    /// text in `style`. Without groups, no highlight is drawn as virtual
+    /// text. Returns the ids of the highlights that changed.
+    pub fn set_virtual_text(
+        &mut self,
+        groups: Vec<String>,
+        style: VirtualText,
+    ) -> Vec<u64> {
+        self.virtual_text =
+            Some((groups, style)).filter(|(groups, _)| !groups.is_empty());
+
+        let ids = self.hl_defs.keys().copied().collect::<Vec<_>>();
+        ids.into_iter()
+            .filter(|id| {
+                let virtual_text = self.virtual_text_of(*id);
+                let hl = self.hl_defs.get_mut(id).unwrap();
+                let changed = hl.virtual_text != virtual_text;
+                hl.virtual_text = virtual_text;
+                changed
+            })
+            .collect()
+    }
+
+    fn virtual_text_of(&self, id: u64) -> Option<VirtualText> {
+        let (groups, style) = self.virtual_text.as_ref()?;
+        let names = self.names.get(&id)?;
+        Some(*style).filter(|_| names.iter().any(|name| groups.contains(name)))
+    }
+
     pub fn set_hl_group(&mut self, group: HlGroup, id: u64) -> Option<u64> {
         self.hl_groups.insert(group, id)
     }
 
+    pub fn set_hl_group_highlight(&mut self, group: HlGroup, hl: Highlight) {
+        self.hl_group_highlights.insert(group, hl);
+    }
+
+    /// Removes the highlights set with `set_hl_group_highlight`.
+    pub fn clear_hl_group_highlights(&mut self) {
+        self.hl_group_highlights.clear();
+    }
+
+    /// Sets (or removes) the hyperlink of highlight `id`.
+    pub fn set_url(&mut self, id: u64, url: Option<String>) {
+        let mut urls = self.urls.borrow_mut();
+        match url {
+            Some(url) => urls.insert(id, url),
+            None => urls.remove(&id),
+        };
+    }
+
+    /// Returns the hyperlinks of the highlights.
+    pub fn urls(&self) -> Rc<RefCell<HashMap<u64, String>>> {
+        self.urls.clone()
+    }
+
     pub fn get_hl_group(&self, group: &HlGroup) -> Option<&Highlight> {
         if let Some(id) = self.hl_groups.get(group) {
             return self.hl_defs.get(id);
         }
 
-        None
+        self.hl_group_highlights.get(group)
+    }
+}
+
+/// The 16 color palette of terminals. Used for colors that are given as
+/// palette indices instead of rgb colors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerminalPalette(pub [Color; 16]);
+
+impl Default for TerminalPalette {
+    /// Same palette that nvim's terminal uses by default.
+    fn default() -> Self {
+        let mut palette = [Color::default(); 16];
+        let colors = [
+            0x000000, 0xe00000, 0x00e000, 0xe0e000, 0x0000e0, 0xe000e0,
+            0x00e0e0, 0xe0e0e0, 0x808080, 0xff4040, 0x40ff40, 0xffff40,
+            0x4040ff, 0xff40ff, 0x40ffff, 0xffffff,
+        ];
+        for (color, v) in palette.iter_mut().zip(colors.iter()) {
+            *color = Color::from_u64(*v);
+        }
+
+        TerminalPalette(palette)
+    }
+}
+
+impl TerminalPalette {
+    pub fn get(&self, index: u64) -> Option<Color> {
+        self.0.get(index as usize).copied()
     }
 }
 
@@ -66,6 +200,28 @@ pub struct Highlight {
     pub bold: bool,
     pub underline: bool,
     pub undercurl: bool,
+    pub underdouble: bool,
+    pub underdashed: bool,
+    pub underdotted: bool,
+
+    /// Blend level of the background, from 0 (opaque) to 100 (fully
+    /// transparent). Set by nvim for `winblend` and `pumblend`.
+    pub blend: u8,
+
+    /// Set by gnvim (instead of nvim) for the highlights of virtual text.
+    pub virtual_text: Option<VirtualText>,
+}
+
+/// Style of virtual text (e.g. inline git blame or inlay hints), which is
+/// drawn differently from the buffer's text. Plugins tell gnvim which
+/// highlight groups are virtual text, see `HlDefs::set_virtual_text`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VirtualText {
+    /// Opacity of the text, from 0.0 to 1.0.
+    pub opacity: f64,
+    /// If the text is drawn in a proportional font instead of the grid's
+    /// font.
+    pub proportional: bool,
 }
 
 impl Highlight {
@@ -81,10 +237,14 @@ impl Highlight {
         let sp = self.special.as_ref().unwrap_or(default_sp);
 
         let weight = if self.bold { "bold" } else { "normal" };
+        // Pango doesn't have dashed or dotted underlines, so those are
+        // rendered as single underlines.
         let underline = if self.undercurl {
             "error"
-        } else if self.underline {
-            "underline"
+        } else if self.underdouble {
+            "double"
+        } else if self.underline || self.underdashed || self.underdotted {
+            "single"
         } else {
             "none"
         };
@@ -118,7 +278,6 @@ pub struct Color {
 }
 
 impl Color {
-    #[allow(unused)]
     pub fn from_hex_string(mut hex: String) -> Result<Color, String> {
         let l = hex.chars().count();
         if l == 7 {
@@ -156,4 +315,53 @@ impl Color {
             (self.b * 255.0) as u8
         )
     }
+
+    /// Returns the color as a css `rgba()` value with `alpha`.
+    pub fn to_css_rgba(&self, alpha: f64) -> String {
+        format!(
+            "rgba({}, {}, {}, {})",
+            (self.r * 255.0) as u8,
+            (self.g * 255.0) as u8,
+            (self.b * 255.0) as u8,
+            alpha
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hl_defs_virtual_text() {
+        let mut hl_defs = HlDefs::default();
+        let style = VirtualText {
+            opacity: 0.5,
+            proportional: true,
+        };
+
+        hl_defs.set_names(1, vec![String::from("Comment")]);
+        hl_defs.insert(1, Highlight::default());
+        hl_defs.set_names(
+            2,
+            vec![String::from("CursorLine"), String::from("GitBlame")],
+        );
+        hl_defs.insert(2, Highlight::default());
+        hl_defs.insert(3, Highlight::default());
+
+        let changed = hl_defs.set_virtual_text(vec!["GitBlame".into()], style);
+        assert_eq!(changed, vec![2]);
+        assert_eq!(hl_defs.get(&1).unwrap().virtual_text, None);
+        assert_eq!(hl_defs.get(&2).unwrap().virtual_text, Some(style));
+
+        // Highlights defined later get the style too.
+        hl_defs.set_names(4, vec![String::from("GitBlame")]);
+        hl_defs.insert(4, Highlight::default());
+        assert_eq!(hl_defs.get(&4).unwrap().virtual_text, Some(style));
+
+        let mut changed = hl_defs.set_virtual_text(vec![], style);
+        changed.sort_unstable();
+        assert_eq!(changed, vec![2, 4]);
+        assert_eq!(hl_defs.get(&4).unwrap().virtual_text, None);
+    }
 }
@@ -0,0 +1,210 @@
+use std::rc::Rc;
+
+use glib::ToVariant;
+use gtk::SettingsExt;
+use log::debug;
+
+use crate::ui::color::Color;
+use crate::ui::common::spawn_local;
+use crate::ui::variant::{child, tuple};
+
+const PORTAL_NAME: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const PORTAL_INTERFACE: &str = "org.freedesktop.portal.Settings";
+const NAMESPACE: &str = "org.freedesktop.appearance";
+const KEY: &str = "color-scheme";
+
+/// Color scheme preferred by the desktop.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorScheme {
+    NoPreference,
+    Dark,
+    Light,
+}
+
+impl Default for ColorScheme {
+    fn default() -> Self {
+        ColorScheme::NoPreference
+    }
+}
+
+impl ColorScheme {
+    /// Value of the color scheme in the `background` option, or an empty
+    /// string if there's no preference.
+    pub fn as_background(self) -> &'static str {
+        match self {
+            ColorScheme::NoPreference => "",
+            ColorScheme::Dark => "dark",
+            ColorScheme::Light => "light",
+        }
+    }
+
+    /// Default foreground and background for when nvim has no colors of
+    /// its own. Without a preference, it's black on white.
+    pub fn fallback_colors(self) -> (Color, Color) {
+        match self {
+            ColorScheme::Dark => {
+                (Color::from_u64(0xe0e2ea), Color::from_u64(0x14161b))
+            }
+            ColorScheme::Light => {
+                (Color::from_u64(0x14161b), Color::from_u64(0xe0e2ea))
+            }
+            ColorScheme::NoPreference => {
+                (Color::from_u64(0), Color::from_u64(0xffffff))
+            }
+        }
+    }
+
+    /// Parses the portal's `color-scheme` setting.
+    fn from_portal(value: u32) -> Self {
+        match value {
+            1 => ColorScheme::Dark,
+            2 => ColorScheme::Light,
+            _ => ColorScheme::NoPreference,
+        }
+    }
+
+    /// Guesses the color scheme from the name of a gtk theme, e.g.
+    /// "Adwaita-dark" or "Adwaita:dark".
+    fn from_theme_name(name: &str) -> Self {
+        let name = name.to_lowercase();
+        if name.ends_with("-dark") || name.ends_with(":dark") {
+            ColorScheme::Dark
+        } else {
+            ColorScheme::Light
+        }
+    }
+}
+
+/// Calls `f` with the desktop's color scheme, and again whenever it
+/// changes. The color scheme comes from the settings portal (which is
+/// also available outside of Flatpak), or from the gtk theme if there's
+/// no portal.
+pub fn watch<F: Fn(ColorScheme) + 'static>(f: F) {
+    let f = Rc::new(f);
+    spawn_local(async move {
+        if let Err(err) = watch_portal(f.clone()).await {
+            debug!("Color scheme not available from the portal: {}", err);
+            watch_theme(f);
+        }
+    });
+}
+
+async fn watch_portal(f: Rc<dyn Fn(ColorScheme)>) -> Result<(), glib::Error> {
+    let conn = gio::bus_get_future(gio::BusType::Session).await?;
+    let reply = conn
+        .call_future(
+            Some(PORTAL_NAME),
+            PORTAL_PATH,
+            PORTAL_INTERFACE,
+            "Read",
+            Some(&tuple(&[NAMESPACE.to_variant(), KEY.to_variant()])),
+            None,
+            gio::DBusCallFlags::NONE,
+            -1,
+        )
+        .await?;
+    f(parse_setting(&reply));
+
+    // The subscription lasts as long as gnvim runs.
+    conn.signal_subscribe(
+        Some(PORTAL_NAME),
+        Some(PORTAL_INTERFACE),
+        Some("SettingChanged"),
+        Some(PORTAL_PATH),
+        Some(NAMESPACE),
+        gio::DBusSignalFlags::NONE,
+        move |_, _, _, _, _, params| {
+            if child(params, 1)
+                .and_then(|key| key.get::<String>())
+                .as_deref()
+                == Some(KEY)
+            {
+                f(parse_setting(params));
+            }
+        },
+    );
+
+    Ok(())
+}
+
+/// Parses the value of the `color-scheme` setting from the reply of
+/// `Read`, or from the arguments of `SettingChanged`. The setting is the
+/// last item, and it's wrapped in (one or two) variants.
+fn parse_setting(params: &glib::Variant) -> ColorScheme {
+    let mut value = (0..3).rev().find_map(|i| child(params, i));
+    while let Some(v) = value.clone().filter(|v| v.type_().to_str() == "v") {
+        value = child(&v, 0);
+    }
+
+    value
+        .and_then(|v| v.get::<u32>())
+        .map_or(ColorScheme::NoPreference, ColorScheme::from_portal)
+}
+
+fn watch_theme(f: Rc<dyn Fn(ColorScheme)>) {
+    let settings = match gtk::Settings::get_default() {
+        Some(settings) => settings,
+        None => return,
+    };
+
+    let theme_scheme = |settings: &gtk::Settings| {
+        settings
+            .get_property_gtk_theme_name()
+            .map_or(ColorScheme::NoPreference, |name| {
+                ColorScheme::from_theme_name(&name)
+            })
+    };
+
+    f(theme_scheme(&settings));
+    settings.connect_property_gtk_theme_name_notify(move |settings| {
+        f(theme_scheme(settings))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use glib::glib_sys;
+    use glib::translate::{from_glib_none, ToGlibPtr};
+
+    use super::*;
+
+    /// Wraps `value` in a variant.
+    fn boxed(value: glib::Variant) -> glib::Variant {
+        unsafe {
+            from_glib_none(glib_sys::g_variant_new_variant(
+                value.to_glib_none().0,
+            ))
+        }
+    }
+
+    #[test]
+    fn test_from_theme_name() {
+        assert_eq!(
+            ColorScheme::from_theme_name("Adwaita-dark"),
+            ColorScheme::Dark
+        );
+        assert_eq!(
+            ColorScheme::from_theme_name("Adwaita:dark"),
+            ColorScheme::Dark
+        );
+        assert_eq!(ColorScheme::from_theme_name("Adwaita"), ColorScheme::Light);
+    }
+
+    #[test]
+    fn test_parse_setting() {
+        // Reply of `Read`, which wraps the value twice.
+        let reply = tuple(&[boxed(boxed(1u32.to_variant()))]);
+        assert_eq!(parse_setting(&reply), ColorScheme::Dark);
+
+        let changed = tuple(&[
+            NAMESPACE.to_variant(),
+            KEY.to_variant(),
+            boxed(2u32.to_variant()),
+        ]);
+        assert_eq!(parse_setting(&changed), ColorScheme::Light);
+
+        let reply = tuple(&[boxed("foo".to_variant())]);
+        assert_eq!(parse_setting(&reply), ColorScheme::NoPreference);
+    }
+}
@@ -0,0 +1,222 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gdk::keys::constants as key;
+use gtk::prelude::*;
+use log::error;
+
+use crate::nvim_gio::GioNeovim;
+use crate::ui::color::HlDefs;
+use crate::ui::common::spawn_local;
+use crate::ui::font::Font;
+use crate::ui::matcher;
+use crate::ui::picker::Picker;
+
+const WIDTH: i32 = 600;
+const HEIGHT: i32 = 400;
+/// Maximum number of entries listed at once.
+const MAX_ITEMS: usize = 100;
+
+/// Expression that evaluates to the cmdline history, most recent first.
+const HISTORY_EXPR: &str =
+    "map(range(histnr(':'), 1, -1), {_, nr -> histget(':', nr)})";
+
+#[derive(Default)]
+struct State {
+    /// Cmdline history, most recent first.
+    history: Vec<String>,
+    /// Indices (to `history`) of the listed entries.
+    shown: Vec<usize>,
+}
+
+/// Window for fuzzy searching the cmdline history. Picking an entry
+/// executes it (or, with tab, puts it to the cmdline for editing).
+pub struct CommandPalette {
+    picker: Picker,
+
+    state: Rc<RefCell<State>>,
+
+    nvim: GioNeovim,
+}
+
+impl CommandPalette {
+    pub fn new(parent: &gtk::Window, nvim: GioNeovim) -> Self {
+        let picker =
+            Picker::new(parent, WIDTH, HEIGHT, pango::EllipsizeMode::End);
+
+        let state = Rc::new(RefCell::new(State::default()));
+
+        picker.entry().connect_search_changed(
+            clone!(picker, state => move |entry| {
+                let query = entry.get_text();
+                update_list(&picker, &state, query.as_str());
+            }),
+        );
+
+        picker.list().connect_row_activated(
+            clone!(picker, state, nvim => move |_, row| {
+                pick(&picker, &state, &nvim, row.get_index(), true);
+            }),
+        );
+
+        picker.window().connect_key_press_event(
+            clone!(picker, state, nvim => move |_, e| {
+                let execute = match e.get_keyval() {
+                    key::Return | key::KP_Enter => true,
+                    key::Tab => false,
+                    _ => return Inhibit(false),
+                };
+
+                if let Some(index) = picker.selected() {
+                    pick(&picker, &state, &nvim, index, execute);
+                }
+
+                Inhibit(true)
+            }),
+        );
+
+        CommandPalette {
+            picker,
+            state,
+            nvim,
+        }
+    }
+
+    /// Fetches the cmdline history from nvim and shows it.
+    pub fn show(&self) {
+        let nvim = self.nvim.clone();
+        let picker = self.picker.clone();
+        let state = self.state.clone();
+
+        spawn_local(async move {
+            let history = match nvim.eval(HISTORY_EXPR).await {
+                Ok(history) => history,
+                Err(err) => {
+                    error!("Failed to get cmdline history: {}", err);
+                    return;
+                }
+            };
+
+            state.borrow_mut().history = history
+                .as_array()
+                .map(|history| {
+                    history
+                        .iter()
+                        .filter_map(|e| e.as_str())
+                        .filter(|e| !e.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            update_list(&picker, &state, "");
+            picker.show();
+        });
+    }
+
+    pub fn set_font(&mut self, font: Font, hl_defs: &HlDefs) {
+        self.picker.set_font(font, hl_defs);
+    }
+
+    pub fn set_colors(&self, hl_defs: &HlDefs) {
+        self.picker.set_colors(hl_defs);
+    }
+}
+
+/// Lists the history entries matching `query` in the picker, and selects
+/// the first one.
+fn update_list(picker: &Picker, state: &RefCell<State>, query: &str) {
+    let mut state = state.borrow_mut();
+    let matches = filter_history(&state.history, query);
+
+    picker.set_items(matches.iter().map(|(index, indices)| {
+        (state.history[*index].as_str(), indices.as_slice())
+    }));
+
+    state.shown = matches.into_iter().map(|(index, _)| index).collect();
+}
+
+/// Hides the palette and feeds the listed entry at `index` to nvim. When
+/// `execute` is false, the entry is only put to the cmdline.
+fn pick(
+    picker: &Picker,
+    state: &RefCell<State>,
+    nvim: &GioNeovim,
+    index: i32,
+    execute: bool,
+) {
+    picker.hide();
+
+    let state = state.borrow();
+    let entry = match state.shown.get(index as usize) {
+        Some(index) => &state.history[*index],
+        None => return,
+    };
+
+    let input = history_input(entry, execute);
+    let nvim = nvim.clone();
+    spawn_local(async move {
+        if let Err(err) = nvim.input(&input).await {
+            error!("Failed to input cmdline history entry: {}", err);
+        }
+    });
+}
+
+/// Returns the entries of `history` that fuzzy match `query`, as their
+/// index and the indices of the matched chars. At most `MAX_ITEMS`
+/// entries are returned.
+fn filter_history(history: &[String], query: &str) -> Vec<(usize, Vec<usize>)> {
+    history
+        .iter()
+        .enumerate()
+        .filter_map(|(i, entry)| {
+            matcher::fuzzy_match(query, entry).map(|indices| (i, indices))
+        })
+        .take(MAX_ITEMS)
+        .collect()
+}
+
+/// Returns the nvim input that puts `entry` to the cmdline (from any mode),
+/// and executes it if `execute` is true.
+fn history_input(entry: &str, execute: bool) -> String {
+    format!(
+        "<C-\\><C-n>:{}{}",
+        entry.replace("<", "<lt>"),
+        if execute { "<CR>" } else { "" }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_history() {
+        let history: Vec<String> = vec!["write", "wqa", "set wrap", "qa"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        assert_eq!(filter_history(&history, "").len(), 4);
+        assert_eq!(
+            filter_history(&history, "wa"),
+            vec![(1, vec![0, 2]), (2, vec![4, 6])]
+        );
+        assert_eq!(filter_history(&history, "xyz"), vec![]);
+    }
+
+    #[test]
+    fn test_filter_history_max_items() {
+        let history = vec![String::from("e foo"); MAX_ITEMS + 10];
+        assert_eq!(filter_history(&history, "e").len(), MAX_ITEMS);
+    }
+
+    #[test]
+    fn test_history_input() {
+        assert_eq!(history_input("w", true), "<C-\\><C-n>:w<CR>");
+        assert_eq!(
+            history_input("nmap <C-a> gg", false),
+            "<C-\\><C-n>:nmap <lt>C-a> gg"
+        );
+    }
+}
@@ -1,10 +1,25 @@
 use futures::future::Future;
+use log::error;
+
+use crate::nvim_gio::GioNeovim;
+use crate::ui::color::Color;
+use crate::ui::font::{Font, FontUnit};
 
 pub fn spawn_local<F: Future<Output = ()> + 'static>(f: F) {
     let c = glib::MainContext::default();
     c.spawn_local(f);
 }
 
+/// Shows `msg` as an error message in nvim.
+pub fn nvim_error(nvim: &GioNeovim, msg: String) {
+    let nvim = nvim.clone();
+    spawn_local(async move {
+        if let Err(err) = nvim.err_writeln(&msg).await {
+            error!("Failed to write error to nvim: {}", err)
+        }
+    });
+}
+
 pub fn calc_line_space(space: i64) -> (i32, i32) {
     let half = space as f64 / 2.0;
     if half as f64 % 2.0 != 0.0 {
@@ -62,6 +77,141 @@ pub fn get_preferred_vertical_position(
     (y, height)
 }
 
+/// Calculate the preferred height and y-position, preferring to be below
+/// `pos`. Goes above `pos` if there is more room there.
+pub fn get_preferred_vertical_position_below(
+    area: &gdk::Rectangle,
+    pos: &gdk::Rectangle,
+    height: i32,
+) -> (i32, i32) {
+    let y = pos.y + pos.height;
+    let max_below = (area.y + area.height - y).max(0);
+    if height <= max_below {
+        return (y, height);
+    }
+
+    let max_above = pos.y - area.y;
+    if max_above > max_below {
+        let height = height.min(max_above);
+        (pos.y - height, height)
+    } else {
+        (y, max_below)
+    }
+}
+
+/// Returns the styles of scrollbars that use the `PmenuSbar` (`sbar`) and
+/// `PmenuThumb` (`thumb`) backgrounds. Without them, scrollbars use the
+/// theme's colors.
+pub fn scrollbar_css(sbar: Option<Color>, thumb: Option<Color>) -> String {
+    let mut css = String::new();
+    if let Some(sbar) = sbar {
+        css.push_str(&format!(
+            "scrollbar, scrollbar trough {{
+                background-color: #{};
+                border: none;
+            }}
+            ",
+            sbar.to_hex()
+        ));
+    }
+    if let Some(thumb) = thumb {
+        css.push_str(&format!(
+            "scrollbar slider {{
+                background-color: #{};
+            }}
+            ",
+            thumb.to_hex()
+        ));
+    }
+    css
+}
+
+/// Colors of the popups with entries (the pickers and the search bar).
+#[derive(Clone, Copy)]
+pub struct PopupColors {
+    pub fg: Color,
+    pub bg: Color,
+    /// Colors of the selected rows and text.
+    pub sel_fg: Color,
+    pub sel_bg: Color,
+}
+
+/// Returns the styles of a popup with entries. `extra` has the popup's own
+/// rules, whose selectors (e.g. widget names) work on all gtk versions.
+pub fn popup_css(font: &Font, colors: PopupColors, extra: &str) -> String {
+    let css = if gtk::get_minor_version() < 20 {
+        format!(
+            "{font_wild}
+
+            GtkWindow, GtkListBox, GtkFrame {{
+                background-color: #{bg};
+            }}
+
+            GtkFrame {{
+                border: 1px solid #{fg};
+            }}
+
+            GtkEntry, GtkLabel, GtkButton {{
+                color: #{fg};
+                background: #{bg};
+            }}
+
+            GtkListBoxRow {{
+                padding: 6px;
+                color: #{fg};
+                background-color: #{bg};
+                outline: none;
+            }}
+
+            GtkListBoxRow:selected, GtkListBoxRow:selected > GtkLabel {{
+                color: #{sel_fg};
+                background: #{sel_bg};
+            }}",
+            font_wild = font.as_wild_css(FontUnit::Pixel),
+            fg = colors.fg.to_hex(),
+            bg = colors.bg.to_hex(),
+            sel_fg = colors.sel_fg.to_hex(),
+            sel_bg = colors.sel_bg.to_hex(),
+        )
+    } else {
+        format!(
+            "{font_wild}
+
+            window, list, frame {{
+                background-color: #{bg};
+            }}
+
+            frame > border {{
+                border: 1px solid #{fg};
+            }}
+
+            entry, label, button {{
+                color: #{fg};
+                background: #{bg};
+            }}
+
+            row {{
+                padding: 6px;
+                color: #{fg};
+                background-color: #{bg};
+                outline: none;
+            }}
+
+            entry selection, row:selected, row:selected > label {{
+                color: #{sel_fg};
+                background: #{sel_bg};
+            }}",
+            font_wild = font.as_wild_css(FontUnit::Point),
+            fg = colors.fg.to_hex(),
+            bg = colors.bg.to_hex(),
+            sel_fg = colors.sel_fg.to_hex(),
+            sel_bg = colors.sel_bg.to_hex(),
+        )
+    };
+
+    format!("{}\n\n{}", css, extra)
+}
+
 #[cfg(test)]
 mod test {
 
@@ -161,6 +311,73 @@ mod test {
         assert_eq!(h, 30);
     }
 
+    #[test]
+    fn test_get_preferred_vertical_position_below1() {
+        // Case 1: there is room below the `pos`.
+        let area = gdk::Rectangle {
+            x: 0,
+            y: 0,
+            width: 300,
+            height: 300,
+        };
+        let pos = gdk::Rectangle {
+            x: 0,
+            y: 30,
+            width: 300,
+            height: 15,
+        };
+        let (y, h) = get_preferred_vertical_position_below(&area, &pos, 30);
+        assert_eq!(y, 45);
+        assert_eq!(h, 30);
+    }
+
+    #[test]
+    fn test_get_preferred_vertical_position_below2() {
+        // Case 2: there is no room below the `pos`, but there's more above
+        // it, so we should flip above.
+        let area = gdk::Rectangle {
+            x: 0,
+            y: 0,
+            width: 300,
+            height: 300,
+        };
+        let pos = gdk::Rectangle {
+            x: 0,
+            y: 270,
+            width: 300,
+            height: 15,
+        };
+        let (y, h) = get_preferred_vertical_position_below(&area, &pos, 100);
+        assert_eq!(y, 170);
+        assert_eq!(h, 100);
+
+        // Truncated to the room above.
+        let (y, h) = get_preferred_vertical_position_below(&area, &pos, 400);
+        assert_eq!(y, 0);
+        assert_eq!(h, 270);
+    }
+
+    #[test]
+    fn test_get_preferred_vertical_position_below3() {
+        // Case 3: there is more room below the `pos`, but not enough, so we
+        // should stay below and truncate our height.
+        let area = gdk::Rectangle {
+            x: 0,
+            y: 0,
+            width: 300,
+            height: 100,
+        };
+        let pos = gdk::Rectangle {
+            x: 0,
+            y: 20,
+            width: 300,
+            height: 15,
+        };
+        let (y, h) = get_preferred_vertical_position_below(&area, &pos, 80);
+        assert_eq!(y, 35);
+        assert_eq!(h, 65);
+    }
+
     #[test]
     fn test_get_preferred_horizontal_position1() {
         // Case 1: Everything fits.
@@ -0,0 +1,196 @@
+use crate::ui::grid::Selection;
+
+/// What the UI should do after a key press in copy mode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CopyModeAction {
+    /// The cursor or the selection changed.
+    Moved,
+    /// Copy `CopyMode::copied` to the clipboard and leave copy mode.
+    Copy,
+    /// Leave copy mode without copying anything.
+    Exit,
+    /// The key doesn't do anything in copy mode.
+    None,
+}
+
+/// Keyboard driven selection of the text of the message grid, which isn't
+/// in any nvim buffer. Works like a small subset of nvim's visual mode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CopyMode {
+    /// Cursor (row, col).
+    pub cursor: (u64, u64),
+    /// Where the selection started, if selecting.
+    pub anchor: Option<(u64, u64)>,
+    /// If the selection is made of whole lines (`V`).
+    pub linewise: bool,
+
+    /// Size of the grid.
+    rows: u64,
+    cols: u64,
+}
+
+impl CopyMode {
+    /// Starts copy mode with the cursor at the start of `row`, in a grid of
+    /// `rows` and `cols`.
+    pub fn new(row: u64, rows: u64, cols: u64) -> Self {
+        Self {
+            cursor: (row.min(rows.saturating_sub(1)), 0),
+            anchor: None,
+            linewise: false,
+            rows,
+            cols,
+        }
+    }
+
+    /// Handles a key press, `keyname` being the name of the key's keyval.
+    pub fn handle_key(&mut self, keyname: &str) -> CopyModeAction {
+        let (row, col) = self.cursor;
+        let last_row = self.rows.saturating_sub(1);
+        let last_col = self.cols.saturating_sub(1);
+
+        self.cursor = match keyname {
+            "h" | "Left" => (row, col.saturating_sub(1)),
+            "l" | "Right" => (row, (col + 1).min(last_col)),
+            "k" | "Up" => (row.saturating_sub(1), col),
+            "j" | "Down" => ((row + 1).min(last_row), col),
+            "0" | "Home" => (row, 0),
+            "dollar" | "End" => (row, last_col),
+            "g" => (0, col),
+            "G" => (last_row, col),
+            "v" | "V" => {
+                let linewise = keyname == "V";
+                if self.anchor.is_some() && self.linewise == linewise {
+                    self.anchor = None;
+                } else {
+                    self.anchor = self.anchor.or(Some(self.cursor));
+                    self.linewise = linewise;
+                }
+                return CopyModeAction::Moved;
+            }
+            "y" | "Return" | "KP_Enter" => return CopyModeAction::Copy,
+            "Escape" | "q" => return CopyModeAction::Exit,
+            _ => return CopyModeAction::None,
+        };
+
+        CopyModeAction::Moved
+    }
+
+    /// The selection to show: the selected cells, or the cell under the
+    /// cursor if nothing is selected.
+    pub fn selection(&self) -> Selection {
+        let (anchor, end) = match self.anchor {
+            Some(anchor) => self.span(anchor),
+            None => (self.cursor, self.cursor),
+        };
+
+        Selection {
+            anchor,
+            end,
+            time: 0,
+            active: false,
+            keyboard: true,
+        }
+    }
+
+    /// The selection to copy. Without a selection, the cursor's line is
+    /// copied (like `yy`).
+    pub fn copied(&self) -> Selection {
+        let (anchor, end) = match self.anchor {
+            Some(anchor) => self.span(anchor),
+            None => ((self.cursor.0, 0), (self.cursor.0, self.cols)),
+        };
+
+        Selection {
+            anchor,
+            end,
+            time: 0,
+            active: false,
+            keyboard: true,
+        }
+    }
+
+    /// Start and end of the selection from `anchor` to the cursor.
+    fn span(&self, anchor: (u64, u64)) -> ((u64, u64), (u64, u64)) {
+        if !self.linewise {
+            return (anchor, self.cursor);
+        }
+
+        let (top, bot) = if anchor.0 <= self.cursor.0 {
+            (anchor.0, self.cursor.0)
+        } else {
+            (self.cursor.0, anchor.0)
+        };
+        ((top, 0), (bot, self.cols))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_mode_movement() {
+        let mut mode = CopyMode::new(2, 5, 10);
+        assert_eq!(mode.cursor, (2, 0));
+
+        assert_eq!(mode.handle_key("h"), CopyModeAction::Moved);
+        assert_eq!(mode.cursor, (2, 0));
+        mode.handle_key("l");
+        mode.handle_key("Right");
+        assert_eq!(mode.cursor, (2, 2));
+        mode.handle_key("dollar");
+        assert_eq!(mode.cursor, (2, 9));
+        mode.handle_key("l");
+        assert_eq!(mode.cursor, (2, 9));
+        mode.handle_key("j");
+        mode.handle_key("j");
+        mode.handle_key("j");
+        assert_eq!(mode.cursor, (4, 9));
+        mode.handle_key("g");
+        assert_eq!(mode.cursor, (0, 9));
+        mode.handle_key("0");
+        assert_eq!(mode.cursor, (0, 0));
+        mode.handle_key("G");
+        assert_eq!(mode.cursor, (4, 0));
+
+        assert_eq!(mode.handle_key("x"), CopyModeAction::None);
+        assert_eq!(mode.handle_key("y"), CopyModeAction::Copy);
+        assert_eq!(mode.handle_key("Escape"), CopyModeAction::Exit);
+    }
+
+    #[test]
+    fn test_copy_mode_selection() {
+        let mut mode = CopyMode::new(1, 5, 10);
+        mode.handle_key("l");
+
+        // Nothing selected, the cursor's cell is shown and its line is
+        // copied.
+        assert_eq!(mode.selection().anchor, (1, 1));
+        assert_eq!(mode.selection().end, (1, 1));
+        assert_eq!(mode.copied().anchor, (1, 0));
+        assert_eq!(mode.copied().end, (1, 10));
+
+        mode.handle_key("v");
+        mode.handle_key("j");
+        mode.handle_key("l");
+        assert_eq!(mode.selection().anchor, (1, 1));
+        assert_eq!(mode.selection().end, (2, 2));
+        assert_eq!(mode.copied(), mode.selection());
+
+        // Linewise.
+        mode.handle_key("V");
+        assert_eq!(mode.selection().anchor, (1, 0));
+        assert_eq!(mode.selection().end, (2, 10));
+
+        // Toggles the selection off.
+        mode.handle_key("V");
+        assert_eq!(mode.anchor, None);
+    }
+
+    #[test]
+    fn test_copy_mode_new() {
+        // The row is kept inside the grid.
+        assert_eq!(CopyMode::new(10, 5, 10).cursor, (4, 0));
+        assert_eq!(CopyMode::new(0, 0, 0).cursor, (0, 0));
+    }
+}
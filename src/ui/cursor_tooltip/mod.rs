@@ -0,0 +1,387 @@
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+
+use syntect::dumps::from_binary;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::SyntaxSet;
+
+use crate::ui::color::Color;
+use crate::ui::common::get_preferred_vertical_position;
+use crate::ui::font::{Font, FontUnit};
+
+use crate::ui::markdown::{highlight_code, markdown_to_markup};
+
+const MAX_WIDTH: i32 = 700;
+const MAX_HEIGHT: i32 = 300;
+/// Padding around the content.
+const PADDING: i32 = 8;
+/// Width of the frame's border.
+const BORDER: i32 = 1;
+
+pub enum Gravity {
+    Up,
+    Down,
+}
+
+struct State {
+    anchor: gdk::Rectangle,
+    available_area: gdk::Rectangle,
+    force_gravity: Option<Gravity>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            anchor: gdk::Rectangle {
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 0,
+            },
+            available_area: gdk::Rectangle {
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 0,
+            },
+            force_gravity: None,
+        }
+    }
+}
+
+/// Cursor tooltip to display markdown documents on given grid position.
+/// Renders the documents with pango markup, and uses `syntect` to highlight
+/// code blocks.
+pub struct CursorTooltip {
+    css_provider: gtk::CssProvider,
+    content_css_provider: gtk::CssProvider,
+    frame: gtk::Frame,
+    fixed: gtk::Fixed,
+    scroll: gtk::ScrolledWindow,
+    label: gtk::Label,
+    state: Rc<RefCell<State>>,
+
+    fg: Color,
+    bg: Color,
+    font: Font,
+
+    /// Our syntax set.
+    syntax_set: SyntaxSet,
+    /// Our current theme set.
+    theme_set: ThemeSet,
+    /// Currently selected theme.
+    current_theme: Theme,
+}
+
+impl CursorTooltip {
+    pub fn new(parent: &gtk::Overlay) -> Self {
+        let css_provider = gtk::CssProvider::new();
+        let content_css_provider = gtk::CssProvider::new();
+
+        let label = gtk::Label::new(None);
+        label.set_line_wrap(true);
+        label.set_line_wrap_mode(pango::WrapMode::WordChar);
+        label.set_xalign(0.0);
+        label.set_yalign(0.0);
+        label.set_margin_start(PADDING);
+        label.set_margin_end(PADDING);
+        label.set_margin_top(PADDING);
+        label.set_margin_bottom(PADDING);
+
+        let scroll = gtk::ScrolledWindow::new(
+            None::<&gtk::Adjustment>,
+            None::<&gtk::Adjustment>,
+        );
+        scroll.set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+        scroll.add(&label);
+
+        let frame = gtk::Frame::new(None);
+        frame.add(&scroll);
+
+        add_css_provider!(&css_provider, frame);
+        add_css_provider!(&content_css_provider, scroll, label);
+        if let Some(viewport) = scroll.get_child() {
+            add_css_provider!(&content_css_provider, viewport);
+        }
+
+        let fixed = gtk::Fixed::new();
+        fixed.put(&frame, 0, 0);
+
+        parent.add_overlay(&fixed);
+        parent.set_overlay_pass_through(&fixed, true);
+
+        fixed.show_all();
+
+        let state = Rc::new(RefCell::new(State::default()));
+
+        fixed.connect_size_allocate(clone!(state => move |_, alloc| {
+            state.borrow_mut().available_area = *alloc;
+        }));
+
+        let syntax_set: SyntaxSet =
+            from_binary(include_bytes!("../../../sublime-syntaxes/all.pack"));
+        let theme_set = ThemeSet::load_defaults();
+        let current_theme = theme_set.themes["base16-ocean.dark"].clone();
+
+        CursorTooltip {
+            css_provider,
+            content_css_provider,
+            frame,
+            fixed,
+            scroll,
+            label,
+            state,
+
+            fg: Color::default(),
+            bg: Color::default(),
+            font: Font::default(),
+
+            syntax_set,
+            theme_set,
+            current_theme,
+        }
+    }
+
+    pub fn set_colors(&mut self, fg: Color, bg: Color) {
+        self.fg = fg;
+        self.bg = bg;
+
+        let css = format!(
+            "* {{
+            border: {border}px solid #{fg};
+            border-radius: 0;
+        }}",
+            border = BORDER,
+            fg = fg.to_hex()
+        );
+        CssProviderExt::load_from_data(&self.css_provider, css.as_bytes())
+            .unwrap();
+
+        self.load_content_css();
+    }
+
+    fn load_content_css(&self) {
+        let css = format!(
+            "{font}
+
+            * {{
+                color: #{fg};
+                background-color: #{bg};
+            }}",
+            font = self.font.as_wild_css(FontUnit::Point),
+            fg = self.fg.to_hex(),
+            bg = self.bg.to_hex(),
+        );
+        CssProviderExt::load_from_data(
+            &self.content_css_provider,
+            css.as_bytes(),
+        )
+        .unwrap();
+    }
+
+    /// Get list of available code highlighting styles.
+    pub fn get_styles(&self) -> Vec<String> {
+        self.theme_set.themes.keys().cloned().collect()
+    }
+
+    /// Set the current code highlighting style.
+    pub fn set_style(&mut self, style: &str) {
+        if let Some(theme) = self.theme_set.themes.get(style) {
+            self.current_theme = theme.clone();
+        }
+    }
+
+    pub fn set_font(&mut self, font: Font) {
+        self.font = font;
+        self.load_content_css();
+    }
+
+    pub fn hide(&self) {
+        self.frame.hide();
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.frame.is_visible()
+    }
+
+    pub fn load_style(&mut self, path: String) -> Result<(), &str> {
+        let path = Path::new(&path);
+        let theme =
+            ThemeSet::get_theme(&path).or(Err("Failed to load theme file"))?;
+
+        let name = if let Some(name) = theme.clone().name {
+            name
+        } else {
+            return Err("Failed to get theme name");
+        };
+        self.theme_set.themes.insert(name, theme);
+
+        Ok(())
+    }
+
+    /// Highlights `code` written in `lang` into pango markup, with the
+    /// current style.
+    pub fn highlight(&self, lang: &str, code: &str) -> String {
+        highlight_code(&self.syntax_set, &self.current_theme, lang, code)
+    }
+
+    pub fn show(&mut self, content: String) {
+        let markup = markdown_to_markup(&content, |lang, code| {
+            self.highlight(lang, code)
+        });
+        self.label.set_markup(&markup);
+
+        let state = self.state.borrow();
+        let (max_width, _) =
+            available_width(&state.available_area, &state.anchor);
+
+        // Measure the content, wrapped to the width available.
+        let extra = 2 * (PADDING + BORDER);
+        let layout = self.label.create_pango_layout(None);
+        layout.set_markup(&markup);
+        layout.set_wrap(pango::WrapMode::WordChar);
+        layout.set_width((max_width - extra).max(1) * pango::SCALE);
+        let (width, height) = layout.get_pixel_size();
+
+        self.frame.show();
+        self.scroll.get_vadjustment().unwrap().set_value(0.0);
+
+        set_position(
+            &self.frame,
+            &self.fixed,
+            &state,
+            (width + extra).min(max_width),
+            (height + extra).min(MAX_HEIGHT),
+        );
+    }
+
+    /// Scrolls the content by `lines`. Negative value scrolls up.
+    pub fn scroll(&self, lines: i64) {
+        let adj = self.scroll.get_vadjustment().unwrap();
+        adj.set_value(adj.get_value() + lines as f64 * self.font.height as f64);
+    }
+
+    pub fn move_to(&mut self, rect: &gdk::Rectangle) {
+        let mut state = self.state.borrow_mut();
+        state.anchor = *rect;
+    }
+
+    /// Forces the gravity of the tooltip to be above or below of current
+    /// anchor position.
+    pub fn force_gravity(&mut self, gravity: Option<Gravity>) {
+        let mut state = self.state.borrow_mut();
+        state.force_gravity = gravity;
+    }
+
+    /// Refreshes the position of the tooltip element.
+    pub fn refresh_position(&self) {
+        let alloc = self.frame.get_allocation();
+        let state = self.state.borrow();
+
+        set_position(
+            &self.frame,
+            &self.fixed,
+            &state,
+            alloc.width,
+            alloc.height,
+        );
+    }
+}
+
+/// Ensures the correct `frame` position and size inside `fixed`.
+fn set_position(
+    frame: &gtk::Frame,
+    fixed: &gtk::Fixed,
+    state: &State,
+    width: i32,
+    height: i32,
+) {
+    let mut available_area = state.available_area;
+
+    match state.force_gravity {
+        Some(Gravity::Up) => {
+            available_area.height = state.anchor.y;
+        }
+        Some(Gravity::Down) => {
+            available_area.y = state.anchor.y + state.anchor.height;
+        }
+        _ => {}
+    }
+
+    let (x, width) = horizontal_position(&available_area, &state.anchor, width);
+    let (y, height) =
+        get_preferred_vertical_position(&available_area, &state.anchor, height);
+
+    fixed.move_(frame, x, y);
+
+    frame.set_size_request(width, height);
+}
+
+/// Returns the maximum width of the tooltip next to `anchor` in `area`, and
+/// whether the tooltip extends to the right (or to the left) of the anchor.
+/// The side with more room is used, so that the content can wrap instead
+/// of overflowing.
+fn available_width(
+    area: &gdk::Rectangle,
+    anchor: &gdk::Rectangle,
+) -> (i32, bool) {
+    let right = area.width - anchor.x;
+    let left = anchor.x + anchor.width;
+
+    if right >= MAX_WIDTH || right >= left {
+        (right.min(MAX_WIDTH).max(0), true)
+    } else {
+        (left.min(MAX_WIDTH).max(0), false)
+    }
+}
+
+/// Returns the x position and width of a tooltip of `width` next to
+/// `anchor` in `area`.
+fn horizontal_position(
+    area: &gdk::Rectangle,
+    anchor: &gdk::Rectangle,
+    width: i32,
+) -> (i32, i32) {
+    let (max_width, right) = available_width(area, anchor);
+    let width = width.min(max_width);
+
+    if right {
+        (anchor.x, width)
+    } else {
+        (anchor.x + anchor.width - width, width)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, width: i32) -> gdk::Rectangle {
+        gdk::Rectangle {
+            x,
+            y: 0,
+            width,
+            height: 20,
+        }
+    }
+
+    #[test]
+    fn test_horizontal_position() {
+        let area = rect(0, 1000);
+
+        // Room on the right.
+        assert_eq!(horizontal_position(&area, &rect(100, 10), 500), (100, 500));
+        assert_eq!(
+            horizontal_position(&area, &rect(100, 10), 900),
+            (100, MAX_WIDTH)
+        );
+        // More room on the left, so the tooltip ends at the cursor.
+        assert_eq!(horizontal_position(&area, &rect(800, 10), 500), (310, 500));
+        // Wraps to the room on the right.
+        let area = rect(0, 600);
+        assert_eq!(horizontal_position(&area, &rect(250, 10), 500), (250, 350));
+    }
+}
@@ -0,0 +1,201 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use glib::ToVariant;
+use gtk::prelude::*;
+use log::{debug, error, warn};
+use rmpv::Value;
+
+use crate::nvim_gio::GioNeovim;
+use crate::thread_guard::ThreadGuard;
+use crate::ui::common::spawn_local;
+use crate::ui::variant::{child, tuple};
+
+/// Bus name of the first gnvim instance. Every instance also owns
+/// `org.gnvim.Control.instance<pid>`.
+const NAME: &str = "org.gnvim.Control";
+const PATH: &str = "/org/gnvim/Control";
+const INTERFACE: &str = "org.gnvim.Control";
+/// D-Bus error returned when nvim fails to handle a method call.
+const ERROR_FAILED: &str = "org.gnvim.Control.Error.Failed";
+
+const INTROSPECTION: &str = r#"
+<node>
+  <interface name="org.gnvim.Control">
+    <method name="OpenFile">
+      <arg type="s" name="path" direction="in"/>
+    </method>
+    <method name="FocusWindow"/>
+    <property type="s" name="Mode" access="read"/>
+    <signal name="ModeChanged">
+      <arg type="s" name="mode"/>
+    </signal>
+  </interface>
+</node>
+"#;
+
+/// Control object registered on the session bus.
+struct Service {
+    conn: gio::DBusConnection,
+    registration: gio::RegistrationId,
+    owners: Vec<gio::OwnerId>,
+}
+
+impl Service {
+    fn unregister(self) {
+        for owner in self.owners {
+            gio::bus_unown_name(owner);
+        }
+        if let Err(err) = self.conn.unregister_object(self.registration) {
+            error!("Failed to unregister D-Bus object: {}", err);
+        }
+    }
+}
+
+/// D-Bus service (`org.gnvim.Control`) that lets desktop tools, launchers
+/// and scripts control gnvim without going through nvim's socket.
+pub struct DbusControl {
+    service: Rc<RefCell<Option<Service>>>,
+    /// Name of the current mode, e.g. "normal".
+    mode: Rc<RefCell<String>>,
+}
+
+impl DbusControl {
+    pub fn new(window: &gtk::ApplicationWindow, nvim: GioNeovim) -> Self {
+        let service = Rc::new(RefCell::new(None));
+        let mode = Rc::new(RefCell::new(String::new()));
+
+        let weak = Rc::downgrade(&service);
+        let window = window.clone();
+        let target = (window, nvim, mode.clone());
+        spawn_local(async move {
+            let registered = match register(target).await {
+                Ok(registered) => registered,
+                Err(err) => {
+                    warn!("Failed to register D-Bus service: {}", err);
+                    return;
+                }
+            };
+
+            // The UI might be gone already.
+            match weak.upgrade() {
+                Some(service) => {
+                    service.replace(Some(registered));
+                }
+                None => registered.unregister(),
+            }
+        });
+
+        DbusControl { service, mode }
+    }
+
+    /// Sets the current mode, and emits the `ModeChanged` signal.
+    pub fn set_mode(&self, mode: &str) {
+        if *self.mode.borrow() == mode {
+            return;
+        }
+        self.mode.replace(mode.to_string());
+
+        if let Some(ref service) = *self.service.borrow() {
+            if let Err(err) = service.conn.emit_signal(
+                None,
+                PATH,
+                INTERFACE,
+                "ModeChanged",
+                Some(&tuple(&[mode.to_variant()])),
+            ) {
+                error!("Failed to emit ModeChanged: {}", err);
+            }
+        }
+    }
+}
+
+impl Drop for DbusControl {
+    fn drop(&mut self) {
+        if let Some(service) = self.service.borrow_mut().take() {
+            service.unregister();
+        }
+    }
+}
+
+async fn register(
+    target: (gtk::ApplicationWindow, GioNeovim, Rc<RefCell<String>>),
+) -> Result<Service, glib::Error> {
+    let conn = gio::bus_get_future(gio::BusType::Session).await?;
+    let info = gio::DBusNodeInfo::new_for_xml(INTROSPECTION)?
+        .lookup_interface(INTERFACE)
+        .expect("interface missing from introspection data");
+
+    // The callbacks are called on the main thread.
+    let target = ThreadGuard::new(target);
+    let mode = ThreadGuard::new(target.borrow().2.clone());
+    let registration = conn.register_object(
+        PATH,
+        &info,
+        move |_, _, _, _, method, params, invocation| {
+            let target = target.borrow();
+            call_method(&target.0, &target.1, method, &params, invocation);
+        },
+        move |_, _, _, _, _| mode.borrow().borrow().to_variant(),
+        // The only property is read only.
+        |_, _, _, _, _, _| false,
+    )?;
+
+    let names = vec![
+        NAME.to_string(),
+        format!("{}.instance{}", NAME, std::process::id()),
+    ];
+    let owners = names
+        .iter()
+        .map(|name| {
+            gio::bus_own_name_on_connection(
+                &conn,
+                name,
+                gio::BusNameOwnerFlags::DO_NOT_QUEUE,
+                |_, name| debug!("Acquired D-Bus name {}", name),
+                |_, name| debug!("Lost D-Bus name {}", name),
+            )
+        })
+        .collect();
+
+    Ok(Service {
+        conn,
+        registration,
+        owners,
+    })
+}
+
+fn call_method(
+    window: &gtk::ApplicationWindow,
+    nvim: &GioNeovim,
+    method: &str,
+    params: &glib::Variant,
+    invocation: gio::DBusMethodInvocation,
+) {
+    // The arguments are checked against the introspection data already.
+    let arg = child(params, 0)
+        .and_then(|arg| arg.get_str().map(String::from))
+        .unwrap_or_default();
+
+    match method {
+        "OpenFile" => {
+            let nvim = nvim.clone();
+            spawn_local(async move {
+                let args = vec![Value::from(arg)];
+                match nvim.call_function("gnvim#dbus#open_file", args).await {
+                    Ok(_) => invocation.return_value(None),
+                    Err(err) => invocation
+                        .return_dbus_error(ERROR_FAILED, &err.to_string()),
+                }
+            });
+        }
+        "FocusWindow" => {
+            window.present();
+            invocation.return_value(None);
+        }
+        _ => invocation.return_dbus_error(
+            "org.freedesktop.DBus.Error.UnknownMethod",
+            &format!("Unknown method: {}", method),
+        ),
+    }
+}
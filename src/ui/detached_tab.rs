@@ -0,0 +1,102 @@
+use gtk::prelude::*;
+use log::error;
+
+use crate::nvim_gio::GioNeovim;
+use crate::ui::common::spawn_local;
+
+/// OS window of a tab page that was detached from the tabline. The tab
+/// page's windows are external windows in nvim, and they're shown in this
+/// window at their positions in the tab page (see `Window::set_detached`).
+pub struct DetachedTab {
+    window: gtk::Window,
+    fixed: gtk::Fixed,
+    /// Handles of the nvim windows, with their positions (row, col) in the
+    /// grid.
+    windows: Vec<(i64, u64, u64)>,
+    /// Handler of the window's delete event, which is disconnected for
+    /// closing the window when the tab is gone.
+    delete_signal: Option<glib::SignalHandlerId>,
+}
+
+impl DetachedTab {
+    pub fn new(
+        parent: &gtk::Window,
+        id: u64,
+        title: &str,
+        windows: Vec<(i64, u64, u64)>,
+        nvim: &GioNeovim,
+    ) -> Self {
+        let window = gtk::Window::new(gtk::WindowType::Toplevel);
+        window.set_title(title);
+        window.set_attached_to(Some(parent));
+        // Keys are still typed to the main window.
+        window.set_accept_focus(false);
+        window.set_resizable(false);
+
+        let fixed = gtk::Fixed::new();
+        window.add(&fixed);
+
+        // Closing the window moves its nvim windows back to a tab page.
+        // The window is destroyed once they're closed.
+        let nvim = nvim.clone();
+        let delete_signal = window.connect_delete_event(move |_, _| {
+            let nvim = nvim.clone();
+            spawn_local(async move {
+                if let Err(err) = nvim
+                    .call_function("gnvim#tab#attach", vec![id.into()])
+                    .await
+                {
+                    error!("Failed to attach tab: {}", err);
+                }
+            });
+
+            Inhibit(true)
+        });
+
+        DetachedTab {
+            window,
+            fixed,
+            windows,
+            delete_signal: Some(delete_signal),
+        }
+    }
+
+    /// Returns the container and the position (row, col) of nvim window
+    /// `win`, if it's in this tab.
+    pub fn get_position(&self, win: i64) -> Option<(&gtk::Fixed, u64, u64)> {
+        self.windows
+            .iter()
+            .find(|(handle, _, _)| *handle == win)
+            .map(|(_, row, col)| (&self.fixed, *row, *col))
+    }
+
+    /// Shows the window if any of its nvim windows is shown, or hides it
+    /// (e.g. while another tab page is shown in nvim).
+    pub fn update_visibility(&self) {
+        let visible = self.fixed.get_children().iter().any(|c| c.is_visible());
+        if visible {
+            self.fixed.show();
+            self.window.show();
+        } else {
+            self.window.hide();
+        }
+    }
+
+    /// If all the nvim windows are closed.
+    pub fn is_empty(&self) -> bool {
+        self.fixed.get_children().is_empty()
+    }
+
+    pub fn set_keep_above(&self, keep_above: bool) {
+        self.window.set_keep_above(keep_above);
+    }
+}
+
+impl Drop for DetachedTab {
+    fn drop(&mut self) {
+        if let Some(signal) = self.delete_signal.take() {
+            self.window.disconnect(signal);
+        }
+        self.window.close();
+    }
+}
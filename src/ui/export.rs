@@ -0,0 +1,370 @@
+use crate::ui::color::{Color, Highlight, HlDefs};
+use crate::ui::font::{Font, FontUnit};
+
+/// Space around the text, in points (pdf and svg) or pixels (html).
+const MARGIN: f64 = 24.0;
+/// Size of a pdf page (A4), in points. Long exports are split to pages
+/// for printing, and wide ones are scaled down to the page's width.
+const PAGE_WIDTH: f64 = 595.0;
+const PAGE_HEIGHT: f64 = 842.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Pdf,
+    Svg,
+    Html,
+}
+
+impl ExportFormat {
+    pub fn from_string(format: &str) -> Option<Self> {
+        match format {
+            "pdf" => Some(ExportFormat::Pdf),
+            "svg" => Some(ExportFormat::Svg),
+            "html" => Some(ExportFormat::Html),
+            _ => None,
+        }
+    }
+}
+
+/// Line of text to export, as runs of text with the same highlight.
+pub type Line = Vec<(String, Highlight)>;
+
+/// Size of the pages, and how many lines fit on each.
+struct Pages {
+    width: f64,
+    height: f64,
+    line_height: f64,
+    lines: usize,
+    /// Scale of the text, so that it fits the page's width.
+    scale: f64,
+}
+
+/// Writes `lines` to `path` in `format`. Colors missing from the lines'
+/// highlights are taken from `hl_defs`' default colors.
+pub fn export(
+    lines: &[Line],
+    hl_defs: &HlDefs,
+    font: &Font,
+    format: ExportFormat,
+    path: &str,
+) -> Result<(), String> {
+    match format {
+        ExportFormat::Html => {
+            std::fs::write(path, to_html(lines, hl_defs, font))
+                .map_err(|err| err.to_string())
+        }
+        ExportFormat::Pdf | ExportFormat::Svg => {
+            render(lines, hl_defs, font, format, path)
+        }
+    }
+}
+
+/// Returns the foreground and background colors of `hl`, with reverse
+/// applied.
+fn colors(hl: &Highlight, hl_defs: &HlDefs) -> (Color, Color) {
+    let fg = hl.foreground.unwrap_or(hl_defs.default_fg);
+    let bg = hl.background.unwrap_or(hl_defs.default_bg);
+
+    if hl.reverse {
+        (bg, fg)
+    } else {
+        (fg, bg)
+    }
+}
+
+fn render(
+    lines: &[Line],
+    hl_defs: &HlDefs,
+    font: &Font,
+    format: ExportFormat,
+    path: &str,
+) -> Result<(), String> {
+    let mut markup = lines
+        .iter()
+        .map(|line| {
+            line.iter()
+                .map(|(text, hl)| {
+                    let (fg, bg) = colors(hl, hl_defs);
+                    let hl = Highlight {
+                        foreground: Some(fg),
+                        background: Some(bg),
+                        reverse: false,
+                        ..*hl
+                    };
+                    hl.pango_markup(
+                        text,
+                        &hl_defs.default_fg,
+                        &hl_defs.default_bg,
+                        &hl_defs.default_sp,
+                    )
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>();
+    // Always have at least one (empty) page.
+    if markup.is_empty() {
+        markup.push(String::new());
+    }
+
+    // Measure the text with a throwaway surface.
+    let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 1, 1)
+        .map_err(|err| err.to_string())?;
+    let layout = create_layout(&cairo::Context::new(&surface), font)?;
+    let (width, line_height) =
+        markup.iter().fold((0, 0), |(width, height), markup| {
+            layout.set_markup(markup);
+            let (w, h) = layout.get_pixel_size();
+            (width.max(w), height.max(h))
+        });
+    let line_height = f64::from(line_height.max(1));
+
+    let width = f64::from(width);
+    let pages = match format {
+        ExportFormat::Pdf => {
+            let scale = ((PAGE_WIDTH - 2.0 * MARGIN) / width).min(1.0);
+            Pages {
+                width: PAGE_WIDTH,
+                height: PAGE_HEIGHT,
+                line_height,
+                lines: pdf_lines_per_page(line_height * scale),
+                scale,
+            }
+        }
+        _ => Pages {
+            width: width + 2.0 * MARGIN,
+            height: markup.len() as f64 * line_height + 2.0 * MARGIN,
+            line_height,
+            lines: markup.len(),
+            scale: 1.0,
+        },
+    };
+
+    match format {
+        ExportFormat::Pdf => {
+            let surface =
+                cairo::PdfSurface::new(pages.width, pages.height, path)
+                    .map_err(|err| err.to_string())?;
+            paint(
+                &cairo::Context::new(&surface),
+                &markup,
+                hl_defs,
+                font,
+                &pages,
+            )?;
+            surface.finish();
+        }
+        _ => {
+            let surface =
+                cairo::SvgSurface::new(pages.width, pages.height, Some(path))
+                    .map_err(|err| err.to_string())?;
+            paint(
+                &cairo::Context::new(&surface),
+                &markup,
+                hl_defs,
+                font,
+                &pages,
+            )?;
+            surface.finish();
+        }
+    }
+
+    Ok(())
+}
+
+/// Paints `markup`, one line per item, to `pages`.
+fn paint(
+    cr: &cairo::Context,
+    markup: &[String],
+    hl_defs: &HlDefs,
+    font: &Font,
+    pages: &Pages,
+) -> Result<(), String> {
+    let layout = create_layout(cr, font)?;
+    let bg = &hl_defs.default_bg;
+
+    for (i, page) in markup.chunks(pages.lines).enumerate() {
+        if i > 0 {
+            cr.show_page();
+        }
+
+        cr.set_source_rgb(bg.r, bg.g, bg.b);
+        cr.paint();
+
+        cr.save();
+        cr.translate(MARGIN, MARGIN);
+        cr.scale(pages.scale, pages.scale);
+        for (row, line) in page.iter().enumerate() {
+            layout.set_markup(line);
+            cr.move_to(0.0, row as f64 * pages.line_height);
+            pangocairo::functions::show_layout(cr, &layout);
+        }
+        cr.restore();
+    }
+
+    Ok(())
+}
+
+/// Returns how many lines of `line_height` (in points) fit on a pdf page.
+fn pdf_lines_per_page(line_height: f64) -> usize {
+    ((PAGE_HEIGHT - 2.0 * MARGIN) / line_height)
+        .floor()
+        .max(1.0) as usize
+}
+
+fn create_layout(
+    cr: &cairo::Context,
+    font: &Font,
+) -> Result<pango::Layout, String> {
+    let layout = pangocairo::functions::create_layout(cr)
+        .ok_or("Failed to create a pango layout")?;
+
+    // Keep the font's size in points, which are the units of pdf and svg
+    // surfaces.
+    let ctx = layout
+        .get_context()
+        .ok_or("Failed to get a pango context")?;
+    pangocairo::functions::context_set_resolution(&ctx, 72.0);
+    layout.context_changed();
+    layout.set_font_description(Some(&font.as_pango_font()));
+
+    Ok(layout)
+}
+
+/// Returns `lines` as a standalone html document.
+fn to_html(lines: &[Line], hl_defs: &HlDefs, font: &Font) -> String {
+    let body = lines
+        .iter()
+        .map(|line| {
+            line.iter()
+                .map(|(text, hl)| html_span(text, hl, hl_defs))
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<style>
+{font}
+body {{ margin: 0; background-color: #{bg}; }}
+pre {{ margin: 0; padding: {margin}px; color: #{fg}; }}
+</style>
+</head>
+<body>
+<pre>{body}</pre>
+</body>
+</html>
+",
+        font = font.as_wild_css(FontUnit::Point),
+        fg = hl_defs.default_fg.to_hex(),
+        bg = hl_defs.default_bg.to_hex(),
+        margin = MARGIN,
+        body = body,
+    )
+}
+
+fn html_span(text: &str, hl: &Highlight, hl_defs: &HlDefs) -> String {
+    let (fg, bg) = colors(hl, hl_defs);
+    let mut style = format!(
+        "color: #{}; background-color: #{};",
+        fg.to_hex(),
+        bg.to_hex()
+    );
+
+    if hl.bold {
+        style.push_str(" font-weight: bold;");
+    }
+    if hl.italic {
+        style.push_str(" font-style: italic;");
+    }
+    if hl.undercurl
+        || hl.underline
+        || hl.underdouble
+        || hl.underdashed
+        || hl.underdotted
+    {
+        let line = if hl.undercurl {
+            "wavy"
+        } else if hl.underdouble {
+            "double"
+        } else if hl.underdashed {
+            "dashed"
+        } else if hl.underdotted {
+            "dotted"
+        } else {
+            "solid"
+        };
+        let sp = hl.special.unwrap_or(hl_defs.default_sp);
+        style.push_str(&format!(
+            " text-decoration: underline {} #{};",
+            line,
+            sp.to_hex()
+        ));
+    }
+
+    format!("<span style=\"{}\">{}</span>", style, escape_html(text))
+}
+
+fn escape_html(text: &str) -> String {
+    text.chars().fold(String::new(), |mut s, c| {
+        match c {
+            '&' => s.push_str("&amp;"),
+            '<' => s.push_str("&lt;"),
+            '>' => s.push_str("&gt;"),
+            '"' => s.push_str("&quot;"),
+            c => s.push(c),
+        }
+        s
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_format_from_string() {
+        assert_eq!(ExportFormat::from_string("pdf"), Some(ExportFormat::Pdf));
+        assert_eq!(ExportFormat::from_string("svg"), Some(ExportFormat::Svg));
+        assert_eq!(ExportFormat::from_string("html"), Some(ExportFormat::Html));
+        assert_eq!(ExportFormat::from_string("png"), None);
+    }
+
+    #[test]
+    fn test_pdf_lines_per_page() {
+        assert_eq!(pdf_lines_per_page(10.0), 79);
+        assert_eq!(pdf_lines_per_page(5.0), 158);
+        assert_eq!(pdf_lines_per_page(1000.0), 1);
+    }
+
+    #[test]
+    fn test_html_span() {
+        let mut hl_defs = HlDefs::default();
+        hl_defs.default_fg = Color::from_u64(0xffffff);
+        hl_defs.default_bg = Color::from_u64(0x000000);
+        hl_defs.default_sp = Color::from_u64(0xff0000);
+
+        assert_eq!(
+            html_span("a < b", &Highlight::default(), &hl_defs),
+            "<span style=\"color: #ffffff; background-color: #000000;\">\
+             a &lt; b</span>"
+        );
+
+        let hl = Highlight {
+            foreground: Some(Color::from_u64(0x00ff00)),
+            reverse: true,
+            bold: true,
+            undercurl: true,
+            ..Highlight::default()
+        };
+        assert_eq!(
+            html_span("x", &hl, &hl_defs),
+            "<span style=\"color: #000000; background-color: #00ff00; \
+             font-weight: bold; text-decoration: underline wavy #ff0000;\">\
+             x</span>"
+        );
+    }
+}
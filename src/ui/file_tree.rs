@@ -0,0 +1,503 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use gdk::keys::constants as key;
+use gio::prelude::*;
+use gtk::prelude::*;
+use log::error;
+
+use crate::nvim_gio::GioNeovim;
+use crate::ui::color::{Color, HlDefs, HlGroup};
+use crate::ui::common::spawn_local;
+use crate::ui::font::{Font, FontUnit};
+
+/// Width of the sidebar when it's shown.
+const WIDTH: i32 = 250;
+
+const COLUMN_NAME: u32 = 0;
+const COLUMN_PATH: u32 = 1;
+const COLUMN_IS_DIR: u32 = 2;
+const COLUMN_ICON: u32 = 3;
+
+/// Rows of the listed directories, which are kept up to date with file
+/// monitors. The contents of a directory are listed when it's expanded.
+/// Until then, it has an empty placeholder row so that it can be expanded.
+struct Tree {
+    store: gtk::TreeStore,
+    /// Directory listed at the top level.
+    root: RefCell<Option<PathBuf>>,
+    /// Monitors of the listed directories.
+    monitors: RefCell<HashMap<PathBuf, gio::FileMonitor>>,
+}
+
+impl Tree {
+    fn set_root(self: &Rc<Self>, root: Option<PathBuf>) {
+        self.store.clear();
+        for (_, monitor) in self.monitors.borrow_mut().drain() {
+            monitor.cancel();
+        }
+
+        if let Some(ref root) = root {
+            self.load(None, root);
+        }
+        self.root.replace(root);
+    }
+
+    /// Lists the contents of `dir` under `parent` (or at the top level),
+    /// and keeps them up to date.
+    fn load(self: &Rc<Self>, parent: Option<&gtk::TreeIter>, dir: &Path) {
+        self.sync(parent, dir);
+
+        let row = match parent {
+            Some(iter) => {
+                let row = self.store.get_path(iter).and_then(|path| {
+                    gtk::TreeRowReference::new(&self.store, &path)
+                });
+                match row {
+                    Some(row) => Some(row),
+                    None => return,
+                }
+            }
+            None => None,
+        };
+
+        let monitor = match gio::File::new_for_path(dir).monitor_directory(
+            gio::FileMonitorFlags::WATCH_MOVES,
+            None::<&gio::Cancellable>,
+        ) {
+            Ok(monitor) => monitor,
+            Err(err) => {
+                error!("Failed to watch {}: {}", dir.display(), err);
+                return;
+            }
+        };
+
+        let tree = Rc::downgrade(self);
+        let path = dir.to_path_buf();
+        monitor.connect_changed(move |_, _, _, event| {
+            let tree = upgrade_weak!(tree);
+            match event {
+                gio::FileMonitorEvent::Created
+                | gio::FileMonitorEvent::Deleted
+                | gio::FileMonitorEvent::Moved
+                | gio::FileMonitorEvent::Renamed
+                | gio::FileMonitorEvent::MovedIn
+                | gio::FileMonitorEvent::MovedOut => {}
+                _ => return,
+            }
+
+            match row {
+                Some(ref row) => {
+                    let iter =
+                        row.get_path().and_then(|p| tree.store.get_iter(&p));
+                    if let Some(iter) = iter {
+                        tree.sync(Some(&iter), &path);
+                    }
+                }
+                None => tree.sync(None, &path),
+            }
+        });
+
+        self.monitors
+            .borrow_mut()
+            .insert(dir.to_path_buf(), monitor);
+    }
+
+    /// Removes the contents of `dir` from under `iter`, and stops watching
+    /// it.
+    fn unload(&self, iter: &gtk::TreeIter, dir: &Path) {
+        while let Some(child) = self.store.iter_children(Some(iter)) {
+            self.store.remove(&child);
+        }
+        self.insert(Some(iter), None, "", "", false);
+        self.unwatch(dir);
+    }
+
+    /// Updates the rows under `parent` to match the contents of `dir`.
+    fn sync(&self, parent: Option<&gtk::TreeIter>, dir: &Path) {
+        let entries = list_dir(dir);
+
+        // Remove the rows of the removed files, which leaves the rows in
+        // the same order as the entries.
+        if let Some(child) = self.store.iter_children(parent) {
+            loop {
+                let name = self.get_string(&child, COLUMN_NAME);
+                let is_dir = self.is_dir(&child);
+                let exists =
+                    entries.iter().any(|e| e.0 == name && e.1 == is_dir);
+                let more = if exists {
+                    self.store.iter_next(&child)
+                } else {
+                    if is_dir {
+                        self.unwatch(&dir.join(&name));
+                    }
+                    self.store.remove(&child)
+                };
+
+                if !more {
+                    break;
+                }
+            }
+        }
+
+        for (i, (name, is_dir)) in entries.iter().enumerate() {
+            let child = self.store.iter_nth_child(parent, i as i32);
+            let listed = child.map_or(false, |child| {
+                self.get_string(&child, COLUMN_NAME) == *name
+                    && self.is_dir(&child) == *is_dir
+            });
+
+            if !listed {
+                let path = dir.join(name);
+                let iter = self.insert(
+                    parent,
+                    Some(i),
+                    name,
+                    &path.to_string_lossy(),
+                    *is_dir,
+                );
+                if *is_dir {
+                    self.insert(Some(&iter), None, "", "", false);
+                }
+            }
+        }
+    }
+
+    fn insert(
+        &self,
+        parent: Option<&gtk::TreeIter>,
+        position: Option<usize>,
+        name: &str,
+        path: &str,
+        is_dir: bool,
+    ) -> gtk::TreeIter {
+        let icon = if is_dir { "folder" } else { "text-x-generic" };
+        self.store.insert_with_values(
+            parent,
+            position.map(|p| p as u32),
+            &[COLUMN_NAME, COLUMN_PATH, COLUMN_IS_DIR, COLUMN_ICON],
+            &[&name, &path, &is_dir, &icon],
+        )
+    }
+
+    /// Stops watching `dir` and the directories under it.
+    fn unwatch(&self, dir: &Path) {
+        self.monitors.borrow_mut().retain(|path, monitor| {
+            let keep = !path.starts_with(dir);
+            if !keep {
+                monitor.cancel();
+            }
+            keep
+        });
+    }
+
+    fn get_string(&self, iter: &gtk::TreeIter, column: u32) -> String {
+        self.store
+            .get_value(iter, column as i32)
+            .get::<String>()
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+    }
+
+    fn is_dir(&self, iter: &gtk::TreeIter) -> bool {
+        self.store
+            .get_value(iter, COLUMN_IS_DIR as i32)
+            .get_some::<bool>()
+            .unwrap_or(false)
+    }
+
+    /// Returns true if `iter` is the placeholder of a directory that isn't
+    /// listed yet.
+    fn is_placeholder(&self, iter: &gtk::TreeIter) -> bool {
+        self.get_string(iter, COLUMN_PATH).is_empty()
+    }
+}
+
+/// Sidebar that lists the files in nvim's current directory, and opens
+/// them in nvim.
+pub struct FileTree {
+    paned: gtk::Paned,
+    css_provider: gtk::CssProvider,
+    scroll: gtk::ScrolledWindow,
+    view: gtk::TreeView,
+
+    tree: Rc<Tree>,
+
+    fg: Color,
+    bg: Color,
+    /// Background of the selected row.
+    selected_bg: Option<Color>,
+    font: Font,
+}
+
+impl FileTree {
+    /// Creates the file tree as the first child of `paned`.
+    pub fn new(paned: &gtk::Paned, nvim: GioNeovim) -> Self {
+        let css_provider = gtk::CssProvider::new();
+
+        let store = gtk::TreeStore::new(&[
+            glib::Type::String,
+            glib::Type::String,
+            glib::Type::Bool,
+            glib::Type::String,
+        ]);
+        let tree = Rc::new(Tree {
+            store,
+            root: RefCell::new(None),
+            monitors: RefCell::new(HashMap::new()),
+        });
+
+        let view = gtk::TreeView::with_model(&tree.store);
+        view.set_headers_visible(false);
+        view.set_enable_search(true);
+        view.set_search_column(COLUMN_NAME as i32);
+
+        let column = gtk::TreeViewColumn::new();
+        let icon = gtk::CellRendererPixbuf::new();
+        column.pack_start(&icon, false);
+        column.add_attribute(&icon, "icon-name", COLUMN_ICON as i32);
+        let name = gtk::CellRendererText::new();
+        column.pack_start(&name, true);
+        column.add_attribute(&name, "text", COLUMN_NAME as i32);
+        view.append_column(&column);
+
+        let scroll = gtk::ScrolledWindow::new(
+            None::<&gtk::Adjustment>,
+            None::<&gtk::Adjustment>,
+        );
+        scroll
+            .set_policy(gtk::PolicyType::Automatic, gtk::PolicyType::Automatic);
+        scroll.add(&view);
+        scroll.set_no_show_all(true);
+
+        add_css_provider!(&css_provider, scroll, view);
+
+        paned.pack1(&scroll, false, false);
+
+        view.connect_test_expand_row(clone!(tree => move |_, iter, _| {
+            let loaded = tree
+                .store
+                .iter_children(Some(iter))
+                .map_or(true, |child| !tree.is_placeholder(&child));
+            if !loaded {
+                let child = tree.store.iter_children(Some(iter)).unwrap();
+                tree.store.remove(&child);
+                let dir = PathBuf::from(tree.get_string(iter, COLUMN_PATH));
+                tree.load(Some(iter), &dir);
+            }
+
+            Inhibit(false)
+        }));
+
+        view.connect_row_collapsed(clone!(tree => move |_, iter, _| {
+            let dir = PathBuf::from(tree.get_string(iter, COLUMN_PATH));
+            tree.unload(iter, &dir);
+        }));
+
+        view.connect_row_activated(clone!(tree => move |view, path, _| {
+            let iter = match tree.store.get_iter(path) {
+                Some(iter) => iter,
+                None => return,
+            };
+
+            if tree.is_dir(&iter) {
+                if view.row_expanded(path) {
+                    view.collapse_row(path);
+                } else {
+                    view.expand_row(path, false);
+                }
+            } else if !tree.is_placeholder(&iter) {
+                open(&nvim, &tree.get_string(&iter, COLUMN_PATH));
+                give_focus_back(view);
+            }
+        }));
+
+        view.connect_key_press_event(|view, e| {
+            if e.get_keyval() == key::Escape {
+                give_focus_back(view);
+                Inhibit(true)
+            } else {
+                Inhibit(false)
+            }
+        });
+
+        FileTree {
+            paned: paned.clone(),
+            css_provider,
+            scroll,
+            view,
+
+            tree,
+
+            fg: Color::default(),
+            bg: Color::default(),
+            selected_bg: None,
+            font: Font::default(),
+        }
+    }
+
+    /// Shows the sidebar with the files in `root`.
+    pub fn show(&mut self, root: &str) {
+        let root = PathBuf::from(root);
+        if self.tree.root.borrow().as_ref() != Some(&root) {
+            self.tree.set_root(Some(root));
+        }
+
+        if !self.scroll.is_visible() {
+            self.scroll.show_all();
+            self.paned.set_position(WIDTH);
+        }
+    }
+
+    pub fn hide(&mut self) {
+        self.scroll.hide();
+        self.tree.set_root(None);
+    }
+
+    /// Returns true if the keyboard focus is in the file tree, so that the
+    /// keys should go to it instead of nvim.
+    pub fn has_focus(&self) -> bool {
+        self.scroll.is_visible() && self.view.has_focus()
+    }
+
+    /// Sets the colors from the default colors. The selected row uses
+    /// `CursorLine`'s background, if it has one.
+    pub fn set_colors(&mut self, hl_defs: &HlDefs) {
+        self.fg = hl_defs.default_fg;
+        self.bg = hl_defs.default_bg;
+        self.selected_bg = hl_defs
+            .get_hl_group(&HlGroup::CursorLine)
+            .and_then(|hl| hl.background);
+        self.load_css();
+    }
+
+    pub fn set_font(&mut self, font: Font) {
+        self.font = font;
+        self.load_css();
+    }
+
+    fn load_css(&self) {
+        let (treeview, selected) = if gtk::get_minor_version() < 20 {
+            ("GtkTreeView", "GtkTreeView:selected")
+        } else {
+            ("treeview", "treeview:selected")
+        };
+        let unit = if gtk::get_minor_version() < 20 {
+            FontUnit::Pixel
+        } else {
+            FontUnit::Point
+        };
+
+        let css = format!(
+            "{font}
+
+            {treeview} {{
+                color: #{fg};
+                background-color: #{bg};
+            }}
+
+            {selected} {{
+                color: #{selected_fg};
+                background-color: #{selected_bg};
+            }}",
+            font = self.font.as_wild_css(unit),
+            treeview = treeview,
+            selected = selected,
+            fg = self.fg.to_hex(),
+            bg = self.bg.to_hex(),
+            // Without a `CursorLine` background, the colors are inverted.
+            selected_fg = match self.selected_bg {
+                Some(_) => self.fg.to_hex(),
+                None => self.bg.to_hex(),
+            },
+            selected_bg = self.selected_bg.unwrap_or(self.fg).to_hex(),
+        );
+        CssProviderExt::load_from_data(&self.css_provider, css.as_bytes())
+            .unwrap();
+    }
+}
+
+/// Opens the file at `path` in nvim's current window.
+fn open(nvim: &GioNeovim, path: &str) {
+    let cmd =
+        format!("execute 'edit' fnameescape('{}')", path.replace("'", "''"));
+    let nvim = nvim.clone();
+    spawn_local(async move {
+        if let Err(err) = nvim.command(&cmd).await {
+            error!("Failed to open file: {}", err);
+        }
+    });
+}
+
+/// Moves the keyboard focus away from the file tree, so that the keys go
+/// to nvim again.
+fn give_focus_back(view: &gtk::TreeView) {
+    if let Some(window) = view
+        .get_toplevel()
+        .and_then(|w| w.downcast::<gtk::Window>().ok())
+    {
+        window.set_focus(None::<&gtk::Widget>);
+    }
+}
+
+/// Lists the names of the files in `dir`, and if they're directories.
+/// Directories come first, and hidden files are skipped.
+fn list_dir(dir: &Path) -> Vec<(String, bool)> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+
+    let mut files = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                return None;
+            }
+
+            // Follow symlinks, so that links to directories can be expanded.
+            let is_dir = entry.path().is_dir();
+            Some((name, is_dir))
+        })
+        .collect::<Vec<_>>();
+
+    files.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_dir() {
+        let root = std::env::temp_dir()
+            .join(format!("gnvim-file-tree-test-{}", std::process::id()));
+        fs::create_dir_all(root.join("src/ui")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("benches")).unwrap();
+        fs::write(root.join("Cargo.toml"), "").unwrap();
+        fs::write(root.join("build.rs"), "").unwrap();
+        fs::write(root.join(".hidden"), "").unwrap();
+
+        let files = list_dir(&root);
+        let missing = list_dir(&root.join("missing"));
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            files,
+            vec![
+                (String::from("benches"), true),
+                (String::from("src"), true),
+                (String::from("Cargo.toml"), false),
+                (String::from("build.rs"), false),
+            ]
+        );
+        assert_eq!(missing, vec![]);
+    }
+}
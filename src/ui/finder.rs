@@ -0,0 +1,348 @@
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+
+use gdk::keys::constants as key;
+use gtk::prelude::*;
+use log::error;
+
+use crate::nvim_gio::GioNeovim;
+use crate::ui::color::HlDefs;
+use crate::ui::common::spawn_local;
+use crate::ui::font::Font;
+use crate::ui::matcher;
+use crate::ui::picker::Picker;
+
+const WIDTH: i32 = 700;
+const HEIGHT: i32 = 450;
+/// Maximum number of files listed at once.
+const MAX_ITEMS: usize = 100;
+/// Maximum number of files searched.
+const MAX_FILES: usize = 100_000;
+
+/// Files matching a query, with the indices of the matched chars.
+type Matches = Vec<(String, Vec<usize>)>;
+
+#[derive(Default)]
+struct State {
+    /// Directory the files are listed from.
+    root: PathBuf,
+    /// Listed files, relative to `root`.
+    shown: Vec<String>,
+    /// Sends queries to the thread that filters the files.
+    queries: Option<mpsc::Sender<String>>,
+}
+
+/// Window for fuzzy searching the files in nvim's current directory and
+/// opening them.
+pub struct Finder {
+    picker: Picker,
+
+    state: Rc<RefCell<State>>,
+
+    nvim: GioNeovim,
+}
+
+impl Finder {
+    pub fn new(parent: &gtk::Window, nvim: GioNeovim) -> Self {
+        let picker =
+            Picker::new(parent, WIDTH, HEIGHT, pango::EllipsizeMode::Start);
+
+        let state = Rc::new(RefCell::new(State::default()));
+
+        // Filtering is done in a thread, so typing doesn't lag with lots
+        // of files.
+        picker
+            .entry()
+            .connect_search_changed(clone!(state => move |entry| {
+                if let Some(ref queries) = state.borrow().queries {
+                    let _ = queries.send(entry.get_text().to_string());
+                }
+            }));
+
+        picker.list().connect_row_activated(
+            clone!(picker, state, nvim => move |_, row| {
+                open(&picker, &state, &nvim, row.get_index(), "edit");
+            }),
+        );
+
+        picker.window().connect_key_press_event(
+            clone!(picker, state, nvim => move |_, e| {
+                let ctrl =
+                    e.get_state().contains(gdk::ModifierType::CONTROL_MASK);
+
+                let cmd = match e.get_keyval() {
+                    key::Return | key::KP_Enter => "edit",
+                    key::x if ctrl => "split",
+                    key::v if ctrl => "vsplit",
+                    key::t if ctrl => "tabedit",
+                    _ => return Inhibit(false),
+                };
+
+                if let Some(index) = picker.selected() {
+                    open(&picker, &state, &nvim, index, cmd);
+                }
+
+                Inhibit(true)
+            }),
+        );
+
+        // Stop the filtering thread when we're closed.
+        picker.window().connect_hide(clone!(state => move |_| {
+            state.borrow_mut().queries = None;
+        }));
+
+        Finder {
+            picker,
+            state,
+            nvim,
+        }
+    }
+
+    /// Shows the finder with the files in nvim's current directory.
+    pub fn show(&self) {
+        let nvim = self.nvim.clone();
+        let picker = self.picker.clone();
+        let state = self.state.clone();
+
+        spawn_local(async move {
+            let cwd = match nvim.eval("getcwd()").await {
+                Ok(cwd) => cwd,
+                Err(err) => {
+                    error!("Failed to get cwd: {}", err);
+                    return;
+                }
+            };
+            let root = match cwd.as_str() {
+                Some(cwd) => PathBuf::from(cwd),
+                None => return,
+            };
+
+            let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+            rx.attach(
+                None,
+                clone!(picker, state => move |res| {
+                    let (query, matches): (String, Matches) = res;
+                    // Skip results that are already outdated.
+                    if picker.entry().get_text() == query {
+                        update_list(&picker, &state, matches);
+                    }
+                    Continue(true)
+                }),
+            );
+
+            let queries = spawn_filter_thread(root.clone(), tx);
+            // Get the initial list.
+            let _ = queries.send(String::new());
+
+            {
+                let mut state = state.borrow_mut();
+                state.root = root;
+                state.shown.clear();
+                state.queries = Some(queries);
+            }
+
+            picker.set_items(std::iter::empty());
+            picker.show();
+        });
+    }
+
+    pub fn set_font(&mut self, font: Font, hl_defs: &HlDefs) {
+        self.picker.set_font(font, hl_defs);
+    }
+
+    pub fn set_colors(&self, hl_defs: &HlDefs) {
+        self.picker.set_colors(hl_defs);
+    }
+}
+
+/// Lists `matches` in the picker, and selects the first one.
+fn update_list(picker: &Picker, state: &RefCell<State>, matches: Matches) {
+    picker.set_items(
+        matches
+            .iter()
+            .map(|(file, indices)| (file.as_str(), indices.as_slice())),
+    );
+
+    state.borrow_mut().shown =
+        matches.into_iter().map(|(file, _)| file).collect();
+}
+
+/// Hides the finder and opens the listed file at `index` with `cmd` (e.g.
+/// `edit` or `split`).
+fn open(
+    picker: &Picker,
+    state: &RefCell<State>,
+    nvim: &GioNeovim,
+    index: i32,
+    cmd: &str,
+) {
+    let path = {
+        let state = state.borrow();
+        match state.shown.get(index as usize) {
+            Some(file) => state.root.join(file),
+            None => return,
+        }
+    };
+
+    picker.hide();
+
+    let cmd = format!(
+        "execute '{}' fnameescape('{}')",
+        cmd,
+        path.to_string_lossy().replace("'", "''")
+    );
+    let nvim = nvim.clone();
+    spawn_local(async move {
+        if let Err(err) = nvim.command(&cmd).await {
+            error!("Failed to open file: {}", err);
+        }
+    });
+}
+
+/// Spawns a thread that lists the files under `root`, and then filters
+/// them with the queries sent to the returned sender. The results are sent
+/// to `tx`, along with their query. The thread stops when the returned
+/// sender is dropped.
+fn spawn_filter_thread(
+    root: PathBuf,
+    tx: glib::Sender<(String, Matches)>,
+) -> mpsc::Sender<String> {
+    let (queries, rx) = mpsc::channel::<String>();
+
+    thread::spawn(move || {
+        let files = list_files(&root);
+
+        while let Ok(mut query) = rx.recv() {
+            // Only the latest query matters.
+            while let Ok(q) = rx.try_recv() {
+                query = q;
+            }
+
+            let matches = filter_files(&files, &query)
+                .into_iter()
+                .map(|(i, indices)| (files[i].clone(), indices))
+                .collect();
+
+            if tx.send((query, matches)).is_err() {
+                break;
+            }
+        }
+    });
+
+    queries
+}
+
+/// Lists the files under `root` (relative to it), skipping hidden files and
+/// directories. At most `MAX_FILES` files are listed.
+fn list_files(root: &Path) -> Vec<String> {
+    let mut files = vec![];
+    let mut dirs = vec![root.to_path_buf()];
+
+    'dirs: while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for entry in entries.filter_map(Result::ok) {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(t) if t.is_dir() => dirs.push(path),
+                Ok(_) => {
+                    if let Ok(file) = path.strip_prefix(root) {
+                        files.push(file.to_string_lossy().into_owned());
+                    }
+                }
+                Err(_) => {}
+            }
+
+            if files.len() >= MAX_FILES {
+                break 'dirs;
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+/// Returns the files that fuzzy match `query`, as their index and the
+/// indices of the matched chars. Files where the matched chars are closer
+/// to each other come first, and shorter paths before longer ones. At most
+/// `MAX_ITEMS` files are returned.
+fn filter_files(files: &[String], query: &str) -> Vec<(usize, Vec<usize>)> {
+    let mut matches: Vec<_> = files
+        .iter()
+        .enumerate()
+        .filter_map(|(i, file)| {
+            matcher::fuzzy_match(query, file).map(|indices| (i, indices))
+        })
+        .collect();
+
+    matches.sort_by_key(|(i, indices)| {
+        let span = match (indices.first(), indices.last()) {
+            (Some(first), Some(last)) => last - first,
+            _ => 0,
+        };
+        (span, files[*i].len())
+    });
+    matches.truncate(MAX_ITEMS);
+
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_files() {
+        let files: Vec<String> =
+            vec!["src/ui/state.rs", "src/main.rs", "Cargo.toml", "README.md"]
+                .into_iter()
+                .map(String::from)
+                .collect();
+
+        assert_eq!(
+            filter_files(&files, ""),
+            vec![(3, vec![]), (2, vec![]), (1, vec![]), (0, vec![])]
+        );
+        assert_eq!(filter_files(&files, "main"), vec![(1, vec![4, 5, 6, 7])]);
+        assert_eq!(
+            filter_files(&files, "srs"),
+            vec![(0, vec![0, 1, 7]), (1, vec![0, 1, 10])]
+        );
+        assert_eq!(filter_files(&files, "xyz"), vec![]);
+    }
+
+    #[test]
+    fn test_filter_files_max_items() {
+        let files = vec![String::from("foo.rs"); MAX_ITEMS + 10];
+        assert_eq!(filter_files(&files, "f").len(), MAX_ITEMS);
+    }
+
+    #[test]
+    fn test_list_files() {
+        let root = std::env::temp_dir()
+            .join(format!("gnvim-finder-test-{}", std::process::id()));
+        fs::create_dir_all(root.join("src/ui")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join("Cargo.toml"), "").unwrap();
+        fs::write(root.join("src/ui/mod.rs"), "").unwrap();
+        fs::write(root.join(".git/HEAD"), "").unwrap();
+        fs::write(root.join(".hidden"), "").unwrap();
+
+        let files = list_files(&root);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(files, vec!["Cargo.toml", "src/ui/mod.rs"]);
+    }
+}
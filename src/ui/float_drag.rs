@@ -0,0 +1,405 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use gtk::prelude::*;
+use log::error;
+use nvim_rs::Window as NvimWindow;
+use rmpv::Value;
+
+use crate::nvim_gio::{GioNeovim, GioWriter};
+use crate::ui::color::Color;
+use crate::ui::common::spawn_local;
+use crate::ui::separator_drag::dragged_size;
+
+/// Height of the grab area on top of floating windows, in pixels.
+const GRAB_HEIGHT: f64 = 8.0;
+/// Width of the resize handles on the right and bottom edges of floating
+/// windows, in pixels.
+const EDGE_WIDTH: f64 = 6.0;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FloatDragKind {
+    Move,
+    ResizeWidth,
+    ResizeHeight,
+    Resize,
+}
+
+/// Position and size of a floating window, in pixels.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct FloatRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Floating window under the pointer, found with `hit_test`.
+pub struct FloatTarget {
+    pub win: NvimWindow<GioWriter>,
+    pub kind: FloatDragKind,
+    pub rect: FloatRect,
+    /// Size of the window in cells (cols, rows).
+    pub size: (u64, u64),
+    /// Size of the base grid's cells (width, height).
+    pub cell: (f64, f64),
+}
+
+impl FloatTarget {
+    /// Returns the window's position (row, col) on the base grid, or its
+    /// size (cols, rows) when resizing, after dragging by `dx`, `dy`.
+    fn dragged(&self, dx: f64, dy: f64) -> (u64, u64) {
+        let (cw, ch) = self.cell;
+        let (cols, rows) = self.size;
+
+        match self.kind {
+            FloatDragKind::Move => (
+                ((self.rect.y + dy) / ch).round().max(0.0) as u64,
+                ((self.rect.x + dx) / cw).round().max(0.0) as u64,
+            ),
+            FloatDragKind::ResizeWidth => (dragged_size(cols, dx, cw), rows),
+            FloatDragKind::ResizeHeight => (cols, dragged_size(rows, dy, ch)),
+            FloatDragKind::Resize => {
+                (dragged_size(cols, dx, cw), dragged_size(rows, dy, ch))
+            }
+        }
+    }
+
+    /// Returns where the window ends up with `dragged` (see `dragged`).
+    fn snapped_rect(&self, dragged: (u64, u64)) -> FloatRect {
+        let (cw, ch) = self.cell;
+
+        match self.kind {
+            FloatDragKind::Move => FloatRect {
+                x: dragged.1 as f64 * cw,
+                y: dragged.0 as f64 * ch,
+                ..self.rect
+            },
+            _ => FloatRect {
+                width: dragged.0 as f64 * cw,
+                height: dragged.1 as f64 * ch,
+                ..self.rect
+            },
+        }
+    }
+}
+
+/// Drag in progress.
+struct Drag {
+    target: FloatTarget,
+    /// Last position or size sent to nvim.
+    dragged: (u64, u64),
+    /// Config of the window when the drag started, once nvim has sent it.
+    /// The window is moved relative to it, so that it stays anchored the
+    /// way it was.
+    config: Rc<RefCell<Option<Vec<(Value, Value)>>>>,
+}
+
+/// Moves floating windows by dragging their top edge, and resizes them by
+/// dragging their right and bottom edges. While dragging, guides show
+/// where the window snaps to on the base grid.
+pub struct FloatDrag {
+    nvim: GioNeovim,
+    gesture: gtk::GestureDrag,
+    guides: gtk::DrawingArea,
+    /// Where the dragged window snaps to.
+    snapped: Rc<Cell<Option<FloatRect>>>,
+    color: Rc<Cell<Color>>,
+    /// If floating windows can be dragged. Disabled by default.
+    enabled: Rc<Cell<bool>>,
+}
+
+impl FloatDrag {
+    pub fn new(overlay: &gtk::Overlay, nvim: GioNeovim) -> Self {
+        let color = Rc::new(Cell::new(Color::default()));
+        let snapped: Rc<Cell<Option<FloatRect>>> = Rc::new(Cell::new(None));
+
+        let guides = gtk::DrawingArea::new();
+        guides.set_no_show_all(true);
+        guides.connect_draw(clone!(color, snapped => move |da, cr| {
+            let rect = match snapped.get() {
+                Some(rect) => rect,
+                None => return Inhibit(false),
+            };
+            let color = color.get();
+            let w = f64::from(da.get_allocated_width());
+            let h = f64::from(da.get_allocated_height());
+
+            // Lines across the grid along the window's edges.
+            cr.set_source_rgba(color.r, color.g, color.b, 0.4);
+            cr.set_line_width(1.0);
+            cr.set_dash(&[4.0, 4.0], 0.0);
+            for x in &[rect.x, rect.x + rect.width] {
+                cr.move_to(x.floor() + 0.5, 0.0);
+                cr.line_to(x.floor() + 0.5, h);
+            }
+            for y in &[rect.y, rect.y + rect.height] {
+                cr.move_to(0.0, y.floor() + 0.5);
+                cr.line_to(w, y.floor() + 0.5);
+            }
+            cr.stroke();
+
+            cr.set_source_rgba(color.r, color.g, color.b, 0.8);
+            cr.set_line_width(2.0);
+            cr.set_dash(&[], 0.0);
+            cr.rectangle(
+                rect.x.floor() + 1.0,
+                rect.y.floor() + 1.0,
+                rect.width.ceil() - 2.0,
+                rect.height.ceil() - 2.0,
+            );
+            cr.stroke();
+
+            Inhibit(false)
+        }));
+        overlay.add_overlay(&guides);
+        overlay.set_overlay_pass_through(&guides, true);
+
+        // Like the separator drag, the gesture runs before the grids get
+        // the events.
+        let gesture = gtk::GestureDrag::new(overlay);
+        gesture.set_button(1);
+        gesture.set_propagation_phase(gtk::PropagationPhase::Capture);
+
+        FloatDrag {
+            nvim,
+            gesture,
+            guides,
+            snapped,
+            color,
+            enabled: Rc::new(Cell::new(false)),
+        }
+    }
+
+    pub fn enable(&self, enable: bool) {
+        self.enabled.set(enable);
+    }
+
+    /// Connects the drag handlers. `hit_test` returns the floating window
+    /// at a position (x, y) of the base grid, if the position is on the
+    /// window's grab area or edges.
+    pub fn connect_hit_test<F>(&self, hit_test: F)
+    where
+        F: Fn(f64, f64) -> Option<FloatTarget> + 'static,
+    {
+        let nvim = &self.nvim;
+        let guides = &self.guides;
+        let snapped = &self.snapped;
+        let enabled = &self.enabled;
+        let drag: Rc<RefCell<Option<Drag>>> = Rc::new(RefCell::new(None));
+
+        self.gesture.connect_drag_begin(
+            clone!(nvim, drag, guides, snapped, enabled => move |gesture, x, y| {
+                let target = match hit_test(x, y) {
+                    Some(target) if enabled.get() => target,
+                    _ => {
+                        gesture.set_state(gtk::EventSequenceState::Denied);
+                        return;
+                    }
+                };
+
+                gesture.set_state(gtk::EventSequenceState::Claimed);
+                let dragged = target.dragged(0.0, 0.0);
+                snapped.set(Some(target.snapped_rect(dragged)));
+                guides.show();
+                guides.queue_draw();
+
+                let config = Rc::new(RefCell::new(None));
+                if target.kind == FloatDragKind::Move {
+                    get_config(&nvim, &target.win, config.clone());
+                }
+
+                drag.replace(Some(Drag {
+                    target,
+                    dragged,
+                    config,
+                }));
+            }),
+        );
+
+        self.gesture.connect_drag_update(
+            clone!(nvim, drag, guides, snapped => move |_, dx, dy| {
+                let mut drag = drag.borrow_mut();
+                let drag = match *drag {
+                    Some(ref mut drag) => drag,
+                    None => return,
+                };
+
+                let dragged = drag.target.dragged(dx, dy);
+                if dragged == drag.dragged {
+                    return;
+                }
+
+                let target = &drag.target;
+                let config = match target.kind {
+                    FloatDragKind::Move => {
+                        // Without the window's config, we don't know how to
+                        // move it yet.
+                        let start = target.dragged(0.0, 0.0);
+                        match *drag.config.borrow() {
+                            Some(ref config) => moved_config(
+                                config,
+                                dragged.0 as f64 - start.0 as f64,
+                                dragged.1 as f64 - start.1 as f64,
+                            ),
+                            None => return,
+                        }
+                    }
+                    _ => vec![
+                        ("width".into(), dragged.0.into()),
+                        ("height".into(), dragged.1.into()),
+                    ],
+                };
+
+                drag.dragged = dragged;
+                snapped.set(Some(target.snapped_rect(dragged)));
+                guides.queue_draw();
+                set_config(&nvim, &target.win, config);
+            }),
+        );
+
+        self.gesture.connect_drag_end(
+            clone!(drag, guides, snapped => move |_, _, _| {
+                drag.replace(None);
+                snapped.set(None);
+                guides.hide();
+            }),
+        );
+    }
+
+    /// Sets the color of the guides.
+    pub fn set_color(&self, color: Color) {
+        self.color.set(color);
+        self.guides.queue_draw();
+    }
+}
+
+/// Gets the config of window `win` to `config`.
+fn get_config(
+    nvim: &GioNeovim,
+    win: &NvimWindow<GioWriter>,
+    config: Rc<RefCell<Option<Vec<(Value, Value)>>>>,
+) {
+    let nvim = nvim.clone();
+    let win = win.clone();
+    spawn_local(async move {
+        match nvim.request(win.get_config()).await {
+            Ok(c) => {
+                config.replace(Some(c));
+            }
+            Err(err) => error!("Failed to get floating window config: {}", err),
+        }
+    });
+}
+
+fn set_config(
+    nvim: &GioNeovim,
+    win: &NvimWindow<GioWriter>,
+    config: Vec<(Value, Value)>,
+) {
+    let nvim = nvim.clone();
+    let win = win.clone();
+    spawn_local(async move {
+        if let Err(err) = nvim.request(win.set_config(config)).await {
+            error!("Failed to move floating window: {}", err);
+        }
+    });
+}
+
+/// Returns the config that moves a floating window with `config` by `rows`
+/// and `cols`. The window keeps its `relative`, `anchor` and what it's
+/// relative to, since nvim wants them along with the new position.
+fn moved_config(
+    config: &[(Value, Value)],
+    rows: f64,
+    cols: f64,
+) -> Vec<(Value, Value)> {
+    config
+        .iter()
+        .filter_map(|(key, value)| {
+            let value = match key.as_str()? {
+                "relative" | "anchor" | "win" | "bufpos" => value.clone(),
+                "row" => Value::from(value.as_f64()? + rows),
+                "col" => Value::from(value.as_f64()? + cols),
+                _ => return None,
+            };
+            Some((key.clone(), value))
+        })
+        .collect()
+}
+
+/// Returns what dragging at `x`, `y` does to a floating window at `rect`:
+/// the top edge moves it, and the right and bottom edges resize it.
+pub fn hit_test(rect: &FloatRect, x: f64, y: f64) -> Option<FloatDragKind> {
+    let right = rect.x + rect.width;
+    let bottom = rect.y + rect.height;
+    if x < rect.x || x >= right || y < rect.y || y >= bottom {
+        return None;
+    }
+
+    let on_right = x >= right - EDGE_WIDTH;
+    let on_bottom = y >= bottom - EDGE_WIDTH;
+    match (on_right, on_bottom) {
+        (true, true) => Some(FloatDragKind::Resize),
+        (true, false) => Some(FloatDragKind::ResizeWidth),
+        (false, true) => Some(FloatDragKind::ResizeHeight),
+        _ if y < rect.y + GRAB_HEIGHT => Some(FloatDragKind::Move),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_moved_config() {
+        let config: Vec<(Value, Value)> = vec![
+            ("relative".into(), "win".into()),
+            ("win".into(), 1001.into()),
+            ("anchor".into(), "SE".into()),
+            ("row".into(), 10.0.into()),
+            ("col".into(), 5.0.into()),
+            ("width".into(), 20.into()),
+            ("height".into(), 3.into()),
+            ("focusable".into(), true.into()),
+        ];
+
+        assert_eq!(
+            moved_config(&config, 2.0, -3.0),
+            vec![
+                ("relative".into(), "win".into()),
+                ("win".into(), 1001.into()),
+                ("anchor".into(), "SE".into()),
+                ("row".into(), 12.0.into()),
+                ("col".into(), 2.0.into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hit_test() {
+        let rect = FloatRect {
+            x: 100.0,
+            y: 100.0,
+            width: 200.0,
+            height: 100.0,
+        };
+
+        assert_eq!(hit_test(&rect, 150.0, 102.0), Some(FloatDragKind::Move));
+        assert_eq!(
+            hit_test(&rect, 298.0, 150.0),
+            Some(FloatDragKind::ResizeWidth)
+        );
+        assert_eq!(
+            hit_test(&rect, 150.0, 198.0),
+            Some(FloatDragKind::ResizeHeight)
+        );
+        assert_eq!(hit_test(&rect, 298.0, 198.0), Some(FloatDragKind::Resize));
+        // Text.
+        assert_eq!(hit_test(&rect, 150.0, 150.0), None);
+        // Outside.
+        assert_eq!(hit_test(&rect, 50.0, 102.0), None);
+        assert_eq!(hit_test(&rect, 300.0, 150.0), None);
+    }
+}
@@ -2,6 +2,10 @@ use std::fmt;
 use std::fmt::Display;
 
 const DEFAULT_HEIGHT: f32 = 14.0;
+/// Smallest font height that can be set by zooming.
+pub const MIN_HEIGHT: f32 = 4.0;
+/// Largest font height that can be set by zooming.
+pub const MAX_HEIGHT: f32 = 72.0;
 
 pub enum FontUnit {
     Pixel,
@@ -20,15 +24,21 @@ impl Display for FontUnit {
 #[derive(Clone, Debug)]
 pub struct Font {
     name: String,
+    /// Fonts to use for the glyphs that `name` doesn't have.
+    fallbacks: Vec<String>,
     pub height: f32,
 }
 
 impl Font {
-    /// Parses nvim `guifont` option.
+    /// Parses nvim `guifont` option. Multiple fonts can be separated with
+    /// a comma, in which case the rest of the fonts are used as fallbacks
+    /// for the glyphs missing from the first one. Options (e.g. height) are
+    /// only read from the first font.
     ///
     /// If invalid height is specified, defaults to `DEFAULT_HEIGHT`.
     pub fn from_guifont(guifont: &str) -> Result<Self, ()> {
-        let mut parts = guifont.split(':');
+        let mut fonts = guifont.split(',');
+        let mut parts = fonts.next().ok_or(())?.split(':');
 
         let name = parts.next().ok_or(())?;
 
@@ -36,8 +46,16 @@ impl Font {
             return Err(());
         }
 
+        let fallbacks = fonts
+            .filter_map(|font| font.split(':').next())
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(String::from)
+            .collect();
+
         let mut font = Font {
             name: name.to_string(),
+            fallbacks,
             height: DEFAULT_HEIGHT,
         };
 
@@ -64,6 +82,35 @@ impl Font {
         Ok(font)
     }
 
+    /// Creates a font from a pango font description (e.g. from a font
+    /// chooser).
+    pub fn from_pango_font(desc: &pango::FontDescription) -> Self {
+        let height = desc.get_size() as f32 / pango::SCALE as f32;
+
+        Font {
+            name: desc
+                .get_family()
+                .map(|family| family.to_string())
+                .unwrap_or_else(|| Font::default().name),
+            fallbacks: vec![],
+            height: if height > 0.0 { height } else { DEFAULT_HEIGHT },
+        }
+    }
+
+    /// Returns the font families of self, the primary one first.
+    fn families(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.name.as_str())
+            .chain(self.fallbacks.iter().map(String::as_str))
+    }
+
+    /// Returns a `guifont` representation of self.
+    pub fn as_guifont(&self) -> String {
+        std::iter::once(format!("{}:h{}", self.name, self.height))
+            .chain(self.fallbacks.iter().cloned())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
     /// Returns a CSS representation of self for a wild (`*`) CSS selector.
     /// On gtk version below 3.20 unit needs to be `FontUnit::Pixel` and
     /// with version 3.20 and up, unit needs to be `FontUnit::Point`. This is
@@ -71,20 +118,27 @@ impl Font {
     pub fn as_wild_css(&self, unit: FontUnit) -> String {
         format!(
             "* {{ \
-             font-family: \"{font_family}\"; \
+             font-family: {font_family}; \
              font-size: {font_size}{font_unit}; \
              }}",
-            font_family = self.name,
+            font_family = self
+                .families()
+                .map(|family| format!("\"{}\"", family))
+                .collect::<Vec<_>>()
+                .join(", "),
             font_size = self.height,
             font_unit = unit,
         )
     }
 
-    /// Returns a pango::FontDescription version of self.
+    /// Returns a pango::FontDescription version of self. Pango uses the
+    /// fallback fonts (listed in the family) when itemizing text, so each
+    /// glyph is rendered with the first font that has it.
     pub fn as_pango_font(&self) -> pango::FontDescription {
         let mut font_desc = pango::FontDescription::from_string(&format!(
             "{} {}",
-            self.name, self.height
+            self.families().collect::<Vec<_>>().join(","),
+            self.height
         ));
 
         // Make sure we dont have a font with size of 0, otherwise we'll
@@ -101,6 +155,7 @@ impl Default for Font {
     fn default() -> Self {
         Font {
             name: String::from("Monospace"),
+            fallbacks: vec![],
             height: DEFAULT_HEIGHT,
         }
     }
@@ -114,6 +169,7 @@ mod tests {
     fn test_as_wild_css() {
         let font = Font {
             name: "foo".to_string(),
+            fallbacks: vec![],
             height: 10.0,
         };
 
@@ -162,4 +218,49 @@ mod tests {
         assert_eq!(f.name, "bar");
         assert_eq!(f.height, DEFAULT_HEIGHT);
     }
+
+    #[test]
+    fn test_from_guifont_with_fallbacks() {
+        let f =
+            Font::from_guifont("Fira Code:h11,Noto Color Emoji, Symbols:h9,")
+                .unwrap();
+        assert_eq!(f.name, "Fira Code");
+        assert_eq!(f.fallbacks, vec!["Noto Color Emoji", "Symbols"]);
+        assert_eq!(f.height, 11.0);
+
+        assert_eq!(f.as_guifont(), "Fira Code:h11,Noto Color Emoji,Symbols");
+        assert_eq!(
+            f.as_wild_css(FontUnit::Point),
+            "* { \
+             font-family: \"Fira Code\", \"Noto Color Emoji\", \"Symbols\"; \
+             font-size: 11pt; \
+             }"
+        );
+        assert_eq!(
+            f.as_pango_font().get_family().unwrap().as_str(),
+            "Fira Code,Noto Color Emoji,Symbols"
+        );
+    }
+
+    #[test]
+    fn test_from_pango_font() {
+        let desc = pango::FontDescription::from_string("Fira Code 11");
+        let f = Font::from_pango_font(&desc);
+        assert_eq!(f.name, "Fira Code");
+        assert_eq!(f.height, 11.0);
+
+        // Font without size.
+        let desc = pango::FontDescription::from_string("Fira Code");
+        let f = Font::from_pango_font(&desc);
+        assert_eq!(f.height, DEFAULT_HEIGHT);
+    }
+
+    #[test]
+    fn test_as_guifont() {
+        let f = Font::from_guifont("Fira Code:h11").unwrap();
+        assert_eq!(f.as_guifont(), "Fira Code:h11");
+
+        let f = Font::from_guifont("foo:h10.5").unwrap();
+        assert_eq!(f.as_guifont(), "foo:h10.5");
+    }
 }
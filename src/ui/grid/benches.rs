@@ -0,0 +1,163 @@
+//! Benchmarks of the grid's hot paths with synthetic redraw events. Gtk
+//! needs a display for creating the grid, even though the grid isn't shown.
+extern crate test;
+use self::test::Bencher;
+
+use gtk::prelude::*;
+
+use crate::nvim_bridge::{Cell, GridLineSegment};
+use crate::ui::color::{Color, Highlight, HlDefs};
+use crate::ui::font::Font;
+use crate::ui::grid::{Grid, RendererKind};
+
+const COLS: usize = 120;
+const ROWS: usize = 40;
+/// Number of highlights that the lines use.
+const HIGHLIGHTS: u64 = 64;
+
+fn hl_defs() -> HlDefs {
+    let mut hl_defs = HlDefs::default();
+    hl_defs.default_fg = Color::from_u64(0xdddddd);
+    hl_defs.default_bg = Color::from_u64(0x222222);
+
+    for id in 0..HIGHLIGHTS {
+        hl_defs.insert(
+            id,
+            Highlight {
+                foreground: Some(Color::from_u64(id * 0x030303)),
+                background: Some(Color::from_u64(0xffffff - id * 0x030303))
+                    .filter(|_| id % 4 == 0),
+                bold: id % 3 == 0,
+                italic: id % 5 == 0,
+                underline: id % 7 == 0,
+                ..Highlight::default()
+            },
+        );
+    }
+
+    hl_defs
+}
+
+/// Creates a `COLS` x `ROWS` grid. The window has to be kept around for
+/// as long as the grid is used.
+fn grid(hl_defs: &HlDefs) -> (gtk::OffscreenWindow, Grid) {
+    gtk::init().expect("Failed to initialize gtk");
+
+    let window = gtk::OffscreenWindow::new();
+    window.show();
+    let win = window.get_window().unwrap();
+
+    let grid = Grid::new(
+        1,
+        &win,
+        Font::default(),
+        0,
+        COLS,
+        ROWS,
+        hl_defs,
+        false,
+        None,
+        RendererKind::Cairo,
+        None,
+    );
+    grid.resize(&win, COLS as u64, ROWS as u64, hl_defs);
+
+    (window, grid)
+}
+
+/// Line that fills `row`, with `run` cells long runs of text in the same
+/// highlight. `seed` changes the text and the highlights.
+fn line(row: usize, run: usize, seed: usize) -> GridLineSegment {
+    let cells = (0..COLS / run)
+        .map(|i| Cell {
+            text: ((b'a' + ((i + row + seed) % 26) as u8) as char).to_string(),
+            hl_id: ((i + seed) as u64) % HIGHLIGHTS,
+            repeat: run as u64,
+            double_width: false,
+        })
+        .collect();
+
+    GridLineSegment {
+        grid: 1,
+        row: row as u64,
+        col_start: 0,
+        cells,
+    }
+}
+
+/// Fills the whole grid, and renders it.
+fn fill(grid: &Grid, hl_defs: &HlDefs, run: usize, seed: usize) {
+    for row in 0..ROWS {
+        grid.put_line(line(row, run, seed));
+    }
+    grid.flush(hl_defs);
+}
+
+#[bench]
+fn bench_put_line(b: &mut Bencher) {
+    let hl_defs = hl_defs();
+    let (_window, grid) = grid(&hl_defs);
+
+    let mut seed = 0;
+    b.iter(|| {
+        seed += 1;
+        for row in 0..ROWS {
+            grid.put_line(line(row, 8, seed));
+        }
+    });
+}
+
+#[bench]
+fn bench_put_line_flush(b: &mut Bencher) {
+    let hl_defs = hl_defs();
+    let (_window, grid) = grid(&hl_defs);
+
+    let mut seed = 0;
+    b.iter(|| {
+        seed += 1;
+        fill(&grid, &hl_defs, 8, seed);
+    });
+}
+
+#[bench]
+fn bench_scroll(b: &mut Bencher) {
+    let hl_defs = hl_defs();
+    let (_window, grid) = grid(&hl_defs);
+    fill(&grid, &hl_defs, 8, 0);
+
+    let reg = [0, ROWS as u64, 0, COLS as u64];
+    b.iter(|| {
+        grid.scroll(reg, 1, 0, &hl_defs);
+        grid.put_line(line(ROWS - 1, 8, 0));
+        grid.flush(&hl_defs);
+    });
+}
+
+#[bench]
+fn bench_scroll_region(b: &mut Bencher) {
+    let hl_defs = hl_defs();
+    let (_window, grid) = grid(&hl_defs);
+    fill(&grid, &hl_defs, 8, 0);
+
+    // Like scrolling a window of a vertical split.
+    let reg = [1, ROWS as u64 - 2, COLS as u64 / 2, COLS as u64];
+    let mut count = 3;
+    b.iter(|| {
+        count = -count;
+        grid.scroll(reg, count, 0, &hl_defs);
+        grid.flush(&hl_defs);
+    });
+}
+
+#[bench]
+fn bench_redraw_highlights(b: &mut Bencher) {
+    let hl_defs = hl_defs();
+    let (_window, grid) = grid(&hl_defs);
+    // Every cell in a highlight of its own, like with semantic highlights.
+    fill(&grid, &hl_defs, 1, 0);
+
+    b.iter(|| {
+        grid.mark_stale(|id| id % 2 == 0);
+        grid.redraw_stale(&hl_defs, usize::MAX)
+    });
+}
@@ -1,16 +1,31 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::rc::Rc;
+
+use gdk::WindowExt;
 use gtk::prelude::*;
-use gtk::DrawingArea;
 
-use crate::ui::color::HlDefs;
+use crate::nvim_bridge::CursorShape;
+use crate::ui::color::{Color, HlDefs};
 use crate::ui::font::Font;
+use crate::ui::grid::crosshair::Crosshair;
 use crate::ui::grid::cursor::Cursor;
+use crate::ui::grid::damage::Damage;
+use crate::ui::grid::images::Image;
+use crate::ui::grid::indent_guides::{self, IndentGuides, IndentInfo};
+use crate::ui::grid::links::{self, Link, LinkMatcher};
+use crate::ui::grid::rasterizer::AsyncRows;
 use crate::ui::grid::render;
+use crate::ui::grid::renderer::{self, Layer, Renderer};
 use crate::ui::grid::row::{Cell, Row};
+use crate::ui::grid::selection::Selection;
+use crate::ui::grid::snippet::{self, SnippetIndicator, SnippetState};
 
 /// Context is manipulated by Grid.
 pub struct Context {
-    /// Our cairo context, that is evetually drawn to the screen.
-    pub cairo_context: cairo::Context,
+    /// Our renderer, which holds the contents that are eventually drawn to
+    /// the screen.
+    pub renderer: Box<dyn Renderer>,
     /// Our cell metrics.
     pub cell_metrics: CellMetrics,
     /// Cell metrics to be updated.
@@ -18,10 +33,26 @@ pub struct Context {
 
     /// Internal grid.
     pub rows: Vec<Row>,
+    /// Cells (start, end) of each row that have changed, but haven't been
+    /// rendered yet.
+    pub pending_rows: BTreeMap<usize, (usize, usize)>,
+    /// Rows being rasterized off the main thread, if that is enabled.
+    pub async_rows: Option<AsyncRows>,
+    /// Rows whose highlights have changed (e.g. with the colorscheme), but
+    /// that haven't been rendered again yet. Rendered a few at a time by
+    /// `render::redraw_stale`, so that we don't block on big changes.
+    pub stale_rows: BTreeSet<usize>,
 
     pub cursor: Cursor,
     /// Cairo context for cursor.
     pub cursor_context: cairo::Context,
+    /// Cairo context for the contents of the cursor (e.g. the cell under
+    /// the cursor with the cursor's colors). Painted to `cursor_context`
+    /// on each tick, so the cursor can fade when blinking.
+    pub cursor_cell_context: cairo::Context,
+    /// Input method's preedit text (e.g. composition text of CJK input).
+    /// Drawn on top of the grid at the cursor's position.
+    pub preedit: Option<Preedit>,
 
     /// If the current status is busy or not. When busy, the cursor is not
     /// drawn (like when in terminal mode in inserting text).
@@ -29,15 +60,74 @@ pub struct Context {
 
     /// If the grid that this context belongs to is active or not.
     pub active: bool,
+    /// If our window has the focus.
+    pub focused: bool,
+    /// If the cursor should be drawn as hollow (outline only) when our
+    /// window doesn't have the focus.
+    pub hollow_unfocused: bool,
 
     /// Areas to call queue_draw_area on the drawing area on flush.
     pub queue_draw_area: Vec<(f64, f64, f64, f64)>,
+    /// Cells that have changed since the last flush.
+    pub damage: Damage,
+    /// Opacity of the default background color.
+    pub background_opacity: f64,
+    /// Areas that were damaged on the last flush. Drawn on top of the grid
+    /// for debugging purposes. `None` if the overlay is disabled.
+    pub damage_overlay: Option<Vec<(f64, f64, f64, f64)>>,
+
+    /// If mouse selections are handled by gnvim instead of nvim.
+    pub gui_selection: bool,
+    /// Current mouse selection, when `gui_selection` is enabled.
+    pub selection: Option<Selection>,
+    /// Color of the selection (the default foreground color).
+    pub selection_color: Color,
+
+    /// Highlight of the cursor's row and column.
+    pub crosshair: Crosshair,
+
+    /// Snippet being expanded in this grid, set by snippet plugins.
+    pub snippet: SnippetState,
+    /// Rendered indicator of `snippet`, drawn next to the cursor.
+    pub snippet_indicator: Option<SnippetIndicator>,
+    /// Color of the placeholders' tint (the default foreground color).
+    pub snippet_color: Color,
+
+    pub indent_guides: IndentGuides,
+    /// Layout of the text of our window, if we're showing one.
+    pub indent_info: Option<IndentInfo>,
+    /// Areas of the indent guides. Updated on flush.
+    pub indent_guide_rects: Vec<(f64, f64, f64, f64)>,
+    /// Color of the indent guides, when not set (the default foreground
+    /// color).
+    pub indent_guide_color: Color,
+
+    /// Images drawn on top of the grid's cells.
+    pub images: Vec<Image>,
+
+    /// Finds the links (URLs and paths) in the grid's text.
+    pub link_matcher: Rc<LinkMatcher>,
+    /// Hyperlinks of the highlights (see `HlDefs::urls`).
+    pub urls: Rc<RefCell<HashMap<u64, String>>>,
+    /// Scale of the device pixels set by the user (see `window_scale`).
+    pub device_scale: Option<f64>,
+    /// Cell (row, col) under the pointer, if the pointer is on the grid.
+    pub pointer: Option<(u64, u64)>,
+    /// Link under the pointer and its row, which is underlined.
+    pub hovered_link: Option<(u64, Link)>,
+    /// Color of the underline of `hovered_link` (the default foreground
+    /// color).
+    pub link_color: Color,
+    /// If the text under the pointer has changed since `hovered_link` was
+    /// found, so it's found again on flush.
+    pub hovered_link_stale: bool,
 }
 
 impl Context {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
-        da: &DrawingArea,
+        da: &gtk::Widget,
+        mut renderer: Box<dyn Renderer>,
         win: &gdk::Window,
         font: Font,
         line_space: i64,
@@ -45,6 +135,7 @@ impl Context {
         rows: usize,
         hl_defs: &HlDefs,
         enable_cursor_animations: bool,
+        device_scale: Option<f64>,
     ) -> Self {
         let pango_context = da.get_pango_context();
 
@@ -54,40 +145,13 @@ impl Context {
         let mut cell_metrics = CellMetrics::default();
         cell_metrics.font = font;
         cell_metrics.line_space = line_space;
+        cell_metrics.scale = window_scale(win, device_scale);
         cell_metrics.update(&pango_context);
 
-        let w = cell_metrics.width * cols as f64;
-        let h = cell_metrics.height * rows as f64;
-        let surface = win
-            .create_similar_surface(
-                cairo::Content::Color,
-                w.ceil() as i32,
-                h.ceil() as i32,
-            )
-            .unwrap();
-
-        let cairo_context = cairo::Context::new(&surface);
+        renderer.resize(win, &cell_metrics, cols, rows, &hl_defs.default_bg);
 
-        // Fill the context with default bg color.
-        cairo_context.save();
-        cairo_context.set_source_rgb(
-            hl_defs.default_bg.r,
-            hl_defs.default_bg.g,
-            hl_defs.default_bg.b,
-        );
-        cairo_context.paint();
-        cairo_context.restore();
-
-        let cursor_context = {
-            let surface = win
-                .create_similar_surface(
-                    cairo::Content::ColorAlpha,
-                    (cell_metrics.width * 2.0) as i32, // times two for double width chars.
-                    (cell_metrics.height + cell_metrics.ascent).ceil() as i32,
-                )
-                .unwrap();
-            cairo::Context::new(&surface)
-        };
+        let cursor_context = create_cursor_context(win, &cell_metrics);
+        let cursor_cell_context = create_cursor_context(win, &cell_metrics);
 
         let cursor = Cursor {
             disable_animation: !enable_cursor_animations,
@@ -95,33 +159,65 @@ impl Context {
         };
 
         Context {
-            cairo_context,
+            renderer,
             cell_metrics,
             cell_metrics_update: None,
             rows: vec![],
+            pending_rows: BTreeMap::new(),
+            async_rows: None,
+            stale_rows: BTreeSet::new(),
 
             cursor,
             cursor_context,
+            cursor_cell_context,
+            preedit: None,
 
             busy: false,
             active: false,
+            focused: true,
+            hollow_unfocused: true,
 
             queue_draw_area: vec![],
+            damage: Damage::new(cols, rows),
+            background_opacity: 1.0,
+            damage_overlay: None,
+
+            gui_selection: false,
+            selection: None,
+            selection_color: hl_defs.default_fg,
+
+            crosshair: Crosshair::default(),
+
+            snippet: SnippetState::default(),
+            snippet_indicator: None,
+            snippet_color: hl_defs.default_fg,
+
+            indent_guides: IndentGuides::default(),
+            indent_info: None,
+            indent_guide_rects: vec![],
+            indent_guide_color: hl_defs.default_fg,
+
+            images: vec![],
+
+            link_matcher: Rc::new(LinkMatcher::default()),
+            urls: hl_defs.urls(),
+            device_scale,
+            pointer: None,
+            hovered_link: None,
+            link_color: hl_defs.default_fg,
+            hovered_link_stale: false,
         }
     }
 
     /// Updates internals that are dependant on the drawing area.
     pub fn resize(
         &mut self,
-        da: &DrawingArea,
+        da: &gtk::Widget,
         win: &gdk::Window,
         cols: usize,
         rows: usize,
         hl_defs: &HlDefs,
     ) {
-        let prev_rows = self.rows.len();
-        let prev_cols = self.rows.get(0).map(|r| r.len()).unwrap_or(0);
-
         if self.rows.len() != rows {
             self.rows.resize_with(rows, || Row::new(cols));
         }
@@ -135,49 +231,29 @@ impl Context {
         let pctx = da.get_pango_context();
         pctx.set_font_description(&self.cell_metrics.font.as_pango_font());
 
+        self.cell_metrics.scale = window_scale(win, self.device_scale);
         self.cell_metrics.update(&pctx);
 
-        let w = self.cell_metrics.width * cols as f64;
-        let h = self.cell_metrics.height * rows as f64;
-        let surface = win
-            .create_similar_surface(
-                cairo::Content::Color,
-                w.ceil() as i32,
-                h.ceil() as i32,
-            )
-            .unwrap();
-        let ctx = cairo::Context::new(&surface);
-
-        // Fill the context with default bg color.
-        ctx.save();
-        ctx.set_source_rgb(
-            hl_defs.default_bg.r,
-            hl_defs.default_bg.g,
-            hl_defs.default_bg.b,
-        );
-        ctx.paint();
-        ctx.restore();
-
-        let s = self.cairo_context.get_target();
-        self.cairo_context.save();
-        ctx.set_source_surface(&s, 0.0, 0.0);
-        ctx.set_operator(cairo::Operator::Source);
-        // Make sure we only paint the area that _was_ visible before this update
-        // so we don't undo the bg color paint we did earlier. Note that we're
-        // calculating the used area based on the current cell metrics. This is
-        // becuase if font changes that might reduce the area we "have available".
-        // Otherwise, when changing to smaller font, we might draw our "old" surface
-        // on a area that wont be cleared by nvim (e.g. over "fresh" whitespace).
-        ctx.rectangle(
-            0.0,
-            0.0,
-            self.cell_metrics.width * prev_cols as f64,
-            self.cell_metrics.height * prev_rows as f64,
+        self.renderer.resize(
+            win,
+            &self.cell_metrics,
+            cols,
+            rows,
+            &hl_defs.default_bg,
         );
-        ctx.fill();
-        self.cairo_context.restore();
+        self.damage.resize(cols, rows);
+        // Everything is rendered below.
+        self.pending_rows.clear();
+        self.stale_rows.clear();
+        if let Some(ref mut async_rows) = self.async_rows {
+            async_rows.cancel();
+        }
 
-        self.cairo_context = ctx;
+        // The renderer's old contents might not be valid anymore (e.g. if
+        // the cell metrics changed), so render everything again. Rows that
+        // nvim sends after the resize are then only rendered if they
+        // actually changed.
+        render::redraw(self, &pctx, hl_defs, |_| true);
     }
 
     /// Sets the cell metrics to be updated. If font or line_space is None,
@@ -187,7 +263,7 @@ impl Context {
         &mut self,
         font: Font,
         line_space: i64,
-        da: &gtk::DrawingArea,
+        da: &gtk::Widget,
         win: &gdk::Window,
     ) {
         let pango_context = da.get_pango_context();
@@ -195,31 +271,39 @@ impl Context {
 
         self.cell_metrics.font = font;
         self.cell_metrics.line_space = line_space;
+        self.cell_metrics.scale = window_scale(win, self.device_scale);
         self.cell_metrics.update(&pango_context);
 
-        self.cursor_context = {
-            let surface = win
-                .create_similar_surface(
-                    cairo::Content::ColorAlpha,
-                    (self.cell_metrics.width * 2.0).ceil() as i32, // times two for double width chars.
-                    (self.cell_metrics.height + self.cell_metrics.ascent).ceil()
-                        as i32,
-                )
-                .unwrap();
-            cairo::Context::new(&surface)
-        };
+        self.cursor_context = create_cursor_context(win, &self.cell_metrics);
+        self.cursor_cell_context =
+            create_cursor_context(win, &self.cell_metrics);
+    }
+
+    /// If the cursor should be drawn as hollow.
+    fn is_cursor_hollow(&self) -> bool {
+        self.hollow_unfocused && !self.focused
     }
 
     /// Returns x, y, width and height for cursor position on the screen (e.g. might be in middle
     /// of an animation).
     pub fn get_cursor_rect(&self) -> (i32, i32, i32, i32) {
+        // Dont use cursor.get_position here, because we want to use the position on the screen.
+        self.cursor_rect_at(self.cursor.pos)
+    }
+
+    /// Rectangle of the cell the cursor is moving to, instead of where the
+    /// cursor is drawn during its animation.
+    pub fn get_cursor_target_rect(&self) -> (i32, i32, i32, i32) {
+        self.cursor_rect_at(self.cursor.get_position())
+    }
+
+    fn cursor_rect_at(&self, pos: Option<(f64, f64)>) -> (i32, i32, i32, i32) {
         let double_width = self
             .cell_at_cursor()
             .map(|cell| cell.double_width)
             .unwrap_or(false);
 
-        // Dont use cursor.get_position here, because we want to use the position on the screen.
-        let pos = self.cursor.pos.unwrap_or((0.0, 0.0));
+        let pos = pos.unwrap_or((0.0, 0.0));
 
         let cm = &self.cell_metrics;
         let (x, y) = render::get_coords(cm.height, cm.width, pos.0, pos.1);
@@ -237,18 +321,27 @@ impl Context {
 
     pub fn cursor_goto(&mut self, row: u64, col: u64, clock: &gdk::FrameClock) {
         // Clear old cursor position.
-        let (x, y, w, h) = self.get_cursor_rect();
+        let (x, y, w, h) = self
+            .get_preedit_rect()
+            .unwrap_or_else(|| self.get_cursor_rect());
         self.queue_draw_area.push((
             f64::from(x),
             f64::from(y),
             f64::from(w),
             f64::from(h),
         ));
+        let decorations = self.cursor_decoration_rects();
+        self.queue_draw_area.extend(decorations);
         self.cursor
             .goto(row as f64, col as f64, clock.get_frame_time());
 
+        let decorations = self.cursor_decoration_rects();
+        self.queue_draw_area.extend(decorations);
+
         // Mark the new cursor position to be drawn.
-        let (x, y, w, h) = self.get_cursor_rect();
+        let (x, y, w, h) = self
+            .get_preedit_rect()
+            .unwrap_or_else(|| self.get_cursor_rect());
         self.queue_draw_area.push((
             f64::from(x),
             f64::from(y),
@@ -257,36 +350,454 @@ impl Context {
         ));
     }
 
-    pub fn tick(&mut self, da: &DrawingArea, clock: &gdk::FrameClock) {
+    pub fn tick(&mut self, da: &gtk::Widget, clock: &gdk::FrameClock) {
         let (x, y, w, h) = self.get_cursor_rect();
         da.queue_draw_area(x, y, w, h);
+        if let Some((x, y, w, h)) = self.get_preedit_rect() {
+            da.queue_draw_area(x, y, w, h);
+        }
 
+        let decorations = self.cursor_decoration_rects();
         self.cursor.tick(clock.get_frame_time());
 
-        let (x, y, w, h) = self.get_cursor_rect();
-
-        let mut alpha = self.cursor.alpha;
-        if alpha > 1.0 {
-            alpha = 2.0 - alpha;
+        // The crosshair and the snippet indicator only need to be redrawn
+        // when the cursor moves.
+        let new_decorations = self.cursor_decoration_rects();
+        if decorations != new_decorations {
+            for (x, y, w, h) in decorations.into_iter().chain(new_decorations) {
+                da.queue_draw_area(
+                    x.floor() as i32,
+                    y.floor() as i32,
+                    w.ceil() as i32,
+                    h.ceil() as i32,
+                );
+            }
         }
 
+        let (x, y, w, h) = self.get_cursor_rect();
+
         let cr = &self.cursor_context;
         cr.save();
-        cr.rectangle(0.0, 0.0, 100.0, 100.0);
         cr.set_operator(cairo::Operator::Source);
-        cr.set_source_rgba(
-            self.cursor.color.r,
-            self.cursor.color.g,
-            self.cursor.color.b,
-            alpha,
-        );
-        cr.fill();
+        if self.is_cursor_hollow() {
+            // Hollow cursor doesn't blink.
+            cr.set_source_rgba(0.0, 0.0, 0.0, 0.0);
+            cr.paint();
+
+            let color = &self.cursor.color;
+            cr.set_source_rgb(color.r, color.g, color.b);
+            cr.set_line_width(1.0);
+            cr.rectangle(0.5, 0.5, f64::from(w) - 1.0, f64::from(h) - 1.0);
+            cr.stroke();
+        } else {
+            // Don't blink when we're not focused.
+            let alpha = if self.focused { self.cursor.alpha } else { 1.0 };
+            cr.set_source_surface(
+                &self.cursor_cell_context.get_target(),
+                0.0,
+                0.0,
+            );
+            cr.paint_with_alpha(alpha);
+        }
         cr.restore();
 
         // Don't use the queue_draw_area, because those draws will only
         // happen once nvim sends 'flush' event. This draw needs to happen
         // on each tick so the cursor blinks.
         da.queue_draw_area(x, y, w, h);
+        if let Some((x, y, w, h)) = self.get_preedit_rect() {
+            da.queue_draw_area(x, y, w, h);
+        }
+    }
+
+    /// Returns x, y, width and height for the preedit on the screen.
+    pub fn get_preedit_rect(&self) -> Option<(i32, i32, i32, i32)> {
+        self.preedit.as_ref().map(|preedit| {
+            let (x, y, _, h) = self.get_cursor_rect();
+            (x, y, preedit.width.ceil() as i32, h)
+        })
+    }
+
+    /// Draws the grid (and the cursor) to the screen.
+    pub fn draw(&mut self, cr: Option<&cairo::Context>) {
+        let mut layers = self.image_layers();
+        layers.extend(self.indent_guide_layers());
+        layers.extend(self.crosshair_layers());
+        layers.extend(self.snippet_layers());
+        layers.extend(self.selection_layers());
+        layers.extend(self.link_layers());
+
+        if let Some(ref preedit) = self.preedit {
+            // Preedit has its own cursor, so ours is not drawn.
+            let (x, y, w, h) = self.get_preedit_rect().unwrap();
+            layers.push(Layer {
+                rect: (f64::from(x), f64::from(y), f64::from(w), f64::from(h)),
+                surface: preedit.surface.clone(),
+            });
+        } else if !self.busy && self.active {
+            // If we're not "busy", draw the cursor.
+            let (x, y, w, h) = self.get_cursor_rect();
+            let (x, y, w, h) =
+                (f64::from(x), f64::from(y), f64::from(w), f64::from(h));
+            let p = self.cursor.cell_percentage;
+            let rect = match self.cursor.shape {
+                _ if self.is_cursor_hollow() => (x, y, w, h),
+                CursorShape::Horizontal => {
+                    let ch = (h * p).ceil();
+                    (x, y + h - ch, w, ch)
+                }
+                CursorShape::Vertical => (x, y, (w * p).ceil(), h),
+                _ => (x, y, w * p, h),
+            };
+            layers.push(Layer {
+                rect,
+                surface: self.cursor_context.get_target(),
+            });
+        }
+
+        let overlay = self.damage_overlay.as_deref().unwrap_or(&[]);
+        self.renderer.draw(cr, layers, overlay);
+    }
+
+    /// Paints the grid to `cr` without the cursor, the selection and other
+    /// interactive decorations, for screenshots.
+    pub fn snapshot(&mut self, cr: &cairo::Context) {
+        let mut layers = self.image_layers();
+        layers.extend(self.indent_guide_layers());
+
+        self.renderer.snapshot(cr);
+        renderer::paint_layers(cr, layers);
+    }
+
+    /// Updates the areas of the indent guides from the grid's contents, and
+    /// queues the changed areas to be drawn.
+    pub fn update_indent_guides(&mut self) {
+        let rects = match self.indent_info {
+            Some(info) if self.indent_guides.enabled => {
+                let cm = &self.cell_metrics;
+                let indents = self
+                    .rows
+                    .iter()
+                    .map(|row| indent_guides::row_indent(row, info.text_offset))
+                    .collect::<Vec<_>>();
+
+                indent_guides::guides(&indents, info.width)
+                    .into_iter()
+                    .map(|(col, start, end)| {
+                        let (x1, y1, _, y2) = render::get_rect(
+                            cm.height,
+                            cm.width,
+                            start as f64,
+                            end as f64,
+                            (info.text_offset + col) as f64,
+                            (info.text_offset + col) as f64,
+                        );
+                        // Align to pixels, so that the guides stay crisp.
+                        (x1.floor(), y1.floor(), 1.0, y2.ceil() - y1.floor())
+                    })
+                    .collect()
+            }
+            _ => vec![],
+        };
+
+        if rects != self.indent_guide_rects {
+            let old = std::mem::replace(&mut self.indent_guide_rects, rects);
+            self.queue_draw_area.extend(old);
+            self.queue_draw_area.extend(self.indent_guide_rects.clone());
+        }
+    }
+
+    /// Returns the layers that draw the images.
+    fn image_layers(&mut self) -> Vec<Layer> {
+        let cm = &self.cell_metrics;
+
+        self.images
+            .iter_mut()
+            .map(|image| {
+                let (x, y) = render::get_coords(
+                    cm.height,
+                    cm.width,
+                    image.pos.0 as f64,
+                    image.pos.1 as f64,
+                );
+                let surface = image.surface(
+                    image.size.1 as f64 * cm.width,
+                    image.size.0 as f64 * cm.height,
+                );
+                let w = f64::from(surface.get_width());
+                let h = f64::from(surface.get_height());
+
+                Layer {
+                    rect: (x, y, w, h),
+                    surface: (*surface).clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the layers that draw the indent guides.
+    fn indent_guide_layers(&self) -> Vec<Layer> {
+        let color = self.indent_guides.color.unwrap_or(self.indent_guide_color);
+        let opacity = self.indent_guides.opacity.min(1.0);
+
+        self.indent_guide_rects
+            .iter()
+            .filter_map(|&(x, y, w, h)| {
+                let surface = cairo::ImageSurface::create(
+                    cairo::Format::ARgb32,
+                    w.ceil() as i32,
+                    h.ceil() as i32,
+                )
+                .ok()?;
+                let cr = cairo::Context::new(&surface);
+                cr.set_source_rgba(color.r, color.g, color.b, opacity);
+                cr.paint();
+
+                Some(Layer {
+                    rect: (x, y, w, h),
+                    surface: cr.get_target(),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the areas of the crosshair, if it's drawn.
+    fn crosshair_rects(&self) -> Vec<(f64, f64, f64, f64)> {
+        if self.busy || !self.active {
+            return vec![];
+        }
+
+        let cm = &self.cell_metrics;
+        let cols = self.rows.get(0).map(|row| row.len).unwrap_or(0);
+        let size = (cols as f64 * cm.width, self.rows.len() as f64 * cm.height);
+
+        let (x, y, w, h) = self.get_cursor_rect();
+        let cursor = (f64::from(x), f64::from(y), f64::from(w), f64::from(h));
+
+        self.crosshair.rects(cursor, size)
+    }
+
+    /// Returns the layers that draw the crosshair.
+    fn crosshair_layers(&self) -> Vec<Layer> {
+        let color = self.crosshair.color.unwrap_or(self.cursor.color);
+        let opacity = self.crosshair.opacity.min(1.0);
+
+        self.crosshair_rects()
+            .into_iter()
+            .filter_map(|(x, y, w, h)| {
+                let surface = cairo::ImageSurface::create(
+                    cairo::Format::ARgb32,
+                    w.ceil() as i32,
+                    h.ceil() as i32,
+                )
+                .ok()?;
+                let cr = cairo::Context::new(&surface);
+                cr.set_source_rgba(color.r, color.g, color.b, opacity);
+                cr.paint();
+
+                Some(Layer {
+                    rect: (x, y, w, h),
+                    surface: cr.get_target(),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the areas that follow the cursor: the crosshair and the
+    /// snippet indicator.
+    fn cursor_decoration_rects(&self) -> Vec<(f64, f64, f64, f64)> {
+        let mut rects = self.crosshair_rects();
+        rects.extend(self.snippet_indicator_rect());
+        rects
+    }
+
+    /// Returns the area of the snippet indicator, if it's drawn.
+    fn snippet_indicator_rect(&self) -> Option<(f64, f64, f64, f64)> {
+        let indicator = self.snippet_indicator.as_ref()?;
+        if self.busy || !self.active {
+            return None;
+        }
+
+        let cm = &self.cell_metrics;
+        let cols = self.rows.get(0).map(|row| row.len).unwrap_or(0);
+        let (x, y, w, h) = self.get_cursor_rect();
+        let cursor = (f64::from(x), f64::from(y), f64::from(w), f64::from(h));
+        let size = (indicator.width.ceil(), cm.height.ceil());
+
+        let (x, y) =
+            snippet::indicator_position(cursor, size, cols as f64 * cm.width);
+        Some((x, y, size.0, size.1))
+    }
+
+    /// Returns the layers that tint the snippet's placeholders, and draw
+    /// its indicator.
+    fn snippet_layers(&self) -> Vec<Layer> {
+        if !self.snippet.is_active() {
+            return vec![];
+        }
+
+        let cm = &self.cell_metrics;
+        let cols = self.rows.get(0).map(|row| row.len).unwrap_or(0) as u64;
+        let color = &self.snippet_color;
+
+        let mut layers: Vec<Layer> = self
+            .snippet
+            .rects((cm.width, cm.height), cols)
+            .into_iter()
+            .filter_map(|((x, y, w, h), current)| {
+                let surface = cairo::ImageSurface::create(
+                    cairo::Format::ARgb32,
+                    w.ceil() as i32,
+                    h.ceil() as i32,
+                )
+                .ok()?;
+                let cr = cairo::Context::new(&surface);
+                let opacity = if current { 0.18 } else { 0.08 };
+                cr.set_source_rgba(color.r, color.g, color.b, opacity);
+                cr.paint();
+
+                Some(Layer {
+                    rect: (x, y, w, h),
+                    surface: cr.get_target(),
+                })
+            })
+            .collect();
+
+        if let (Some(indicator), Some(rect)) =
+            (&self.snippet_indicator, self.snippet_indicator_rect())
+        {
+            layers.push(Layer {
+                rect,
+                surface: indicator.surface.clone(),
+            });
+        }
+
+        layers
+    }
+
+    /// Returns the layers that highlight the selected cells.
+    fn selection_layers(&self) -> Vec<Layer> {
+        let selection = match self.selection {
+            Some(ref selection) => selection,
+            None => return vec![],
+        };
+
+        let cm = &self.cell_metrics;
+        let cols = self.rows.get(0).map(|row| row.len).unwrap_or(0) as u64;
+        let color = &self.selection_color;
+
+        selection
+            .rows(cols)
+            .into_iter()
+            .filter_map(|(row, from, to)| {
+                let (x1, y1, x2, y2) = render::get_rect(
+                    cm.height,
+                    cm.width,
+                    row as f64,
+                    (row + 1) as f64,
+                    from as f64,
+                    to as f64,
+                );
+                let (w, h) = (x2 - x1, y2 - y1);
+
+                let surface = cairo::ImageSurface::create(
+                    cairo::Format::ARgb32,
+                    w.ceil() as i32,
+                    h.ceil() as i32,
+                )
+                .ok()?;
+                let cr = cairo::Context::new(&surface);
+                cr.set_source_rgba(color.r, color.g, color.b, 0.3);
+                cr.paint();
+
+                Some(Layer {
+                    rect: (x1, y1, w, h),
+                    surface: cr.get_target(),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the link at `row`, `col`. Hyperlinks of the cells'
+    /// highlights are preferred over the links found in the text.
+    pub fn link_at(&self, row: u64, col: u64) -> Option<Link> {
+        let row = self.rows.get(row as usize)?;
+        let cells = (0..row.len())
+            .filter_map(|i| row.cell_at(i))
+            .collect::<Vec<_>>();
+
+        let urls = self.urls.borrow();
+        if !urls.is_empty() {
+            let cell_urls = cells
+                .iter()
+                .map(|cell| urls.get(&cell.hl_id).map(String::as_str))
+                .collect::<Vec<_>>();
+            if let Some(link) = links::find_url(&cell_urls, col as usize) {
+                return Some(link);
+            }
+        }
+
+        let cells = cells
+            .iter()
+            .map(|cell| cell.text.as_str())
+            .collect::<Vec<_>>();
+        self.link_matcher.find(&cells, col as usize)
+    }
+
+    /// Updates `hovered_link` for the pointer's position and the grid's
+    /// contents. Returns the areas that need to be drawn again.
+    pub fn update_hovered_link(&mut self) -> Vec<(f64, f64, f64, f64)> {
+        let link = self
+            .pointer
+            .and_then(|(row, col)| Some((row, self.link_at(row, col)?)));
+        if link == self.hovered_link {
+            return vec![];
+        }
+
+        let old = std::mem::replace(&mut self.hovered_link, link);
+        old.iter()
+            .chain(self.hovered_link.iter())
+            .map(|(row, link)| self.link_rect(*row, link))
+            .collect()
+    }
+
+    /// Returns the area of the underline of `link`.
+    fn link_rect(&self, row: u64, link: &Link) -> (f64, f64, f64, f64) {
+        let cm = &self.cell_metrics;
+        let thickness = (cm.height / 16.0).ceil().max(1.0);
+        (
+            link.start as f64 * cm.width,
+            (row + 1) as f64 * cm.height - thickness,
+            (link.end - link.start) as f64 * cm.width,
+            thickness,
+        )
+    }
+
+    /// Returns the layer that underlines the link under the pointer.
+    fn link_layers(&self) -> Vec<Layer> {
+        let (row, link) = match self.hovered_link {
+            Some((row, ref link)) => (row, link),
+            None => return vec![],
+        };
+
+        let (x, y, w, h) = self.link_rect(row, link);
+        let surface = match cairo::ImageSurface::create(
+            cairo::Format::ARgb32,
+            w.ceil() as i32,
+            h.ceil() as i32,
+        ) {
+            Ok(surface) => surface,
+            Err(_) => return vec![],
+        };
+        let cr = cairo::Context::new(&surface);
+        let color = self.link_color;
+        cr.set_source_rgb(color.r, color.g, color.b);
+        cr.paint();
+
+        vec![Layer {
+            rect: (x, y, w, h),
+            surface: cr.get_target(),
+        }]
     }
 
     pub fn cell_at_cursor(&self) -> Option<&Cell> {
@@ -298,6 +809,28 @@ impl Context {
     }
 }
 
+/// Creates a context for the cursor (big enough for double width cells).
+fn create_cursor_context(
+    win: &gdk::Window,
+    cm: &CellMetrics,
+) -> cairo::Context {
+    let surface = create_surface(
+        win,
+        cairo::Content::ColorAlpha,
+        cm.width * 2.0, // times two for double width chars.
+        cm.height + cm.ascent,
+        cm.scale,
+    );
+    cairo::Context::new(&surface)
+}
+
+/// Rendered preedit text of an input method.
+pub struct Preedit {
+    pub surface: cairo::Surface,
+    /// Width of the text.
+    pub width: f64,
+}
+
 /// Cell metrics tells the size (and other metrics) of the cells in a grid.
 #[derive(Default, Debug, Clone)]
 pub struct CellMetrics {
@@ -310,6 +843,11 @@ pub struct CellMetrics {
 
     pub line_space: i64,
     pub font: Font,
+    /// Device pixels per pixel (see `window_scale`). The cells' sizes and
+    /// the underline are rounded to whole device pixels, so that the cells
+    /// line up with the screen's pixels also on fractional scales. The
+    /// grid's surfaces are rendered at this scale too.
+    pub scale: f64,
 }
 
 impl CellMetrics {
@@ -319,15 +857,139 @@ impl CellMetrics {
             .unwrap();
         let extra = self.line_space as f64 / 2.0;
         let scale = f64::from(pango::SCALE);
-        self.ascent = (f64::from(fm.get_ascent()) / scale + extra).ceil();
-        self.decent = (f64::from(fm.get_descent()) / scale + extra).ceil();
+        self.ascent = snap(
+            f64::from(fm.get_ascent()) / scale + extra,
+            self.scale,
+            f64::ceil,
+        );
+        self.decent = snap(
+            f64::from(fm.get_descent()) / scale + extra,
+            self.scale,
+            f64::ceil,
+        );
         self.height = self.ascent + self.decent;
-        self.width = f64::from(fm.get_approximate_char_width()) / scale;
+        self.width = snap(
+            f64::from(fm.get_approximate_char_width()) / scale,
+            self.scale,
+            f64::round,
+        )
+        .max(snap(1.0, self.scale, f64::ceil));
 
-        self.underline_position =
-            f64::from(fm.get_underline_position()) / scale - extra;
+        self.underline_position = snap(
+            f64::from(fm.get_underline_position()) / scale - extra,
+            self.scale,
+            f64::round,
+        );
         // TODO(ville): make the underline thickness a bit thicker (one 10th of the cell height?).
-        self.underline_thickness =
-            f64::from(fm.get_underline_thickness()) / scale * 2.0;
+        self.underline_thickness = snap(
+            f64::from(fm.get_underline_thickness()) / scale * 2.0,
+            self.scale,
+            f64::ceil,
+        );
+    }
+
+    /// Rounds `pos` down to whole device pixels.
+    pub fn floor(&self, pos: f64) -> f64 {
+        snap(pos, self.scale, f64::floor)
+    }
+
+    /// Rounds `len` up to whole device pixels.
+    pub fn ceil(&self, len: f64) -> f64 {
+        snap(len, self.scale, f64::ceil)
+    }
+
+    /// Returns the cell (row, col) at position `x`, `y` of a grid.
+    pub fn cell_at(&self, x: f64, y: f64) -> (u64, u64) {
+        (cell_index(y, self.height), cell_index(x, self.width))
+    }
+
+    /// Returns the size (cols, rows) of a grid that fits in `width` and
+    /// `height`.
+    pub fn grid_size(&self, width: f64, height: f64) -> (u64, u64) {
+        (
+            cell_index(width, self.width),
+            cell_index(height, self.height),
+        )
+    }
+}
+
+/// Returns the scale of `win`'s device pixels: `scale` if it's set (gtk
+/// only knows integer scales, and draws fractional scales at the next
+/// integer scale), or gtk's scale factor.
+pub fn window_scale(win: &gdk::Window, scale: Option<f64>) -> f64 {
+    scale.unwrap_or_else(|| f64::from(win.get_scale_factor()))
+}
+
+/// Rounds `len` to whole device pixels of `scale` with `round` (e.g.
+/// `f64::ceil`).
+fn snap(len: f64, scale: f64, round: fn(f64) -> f64) -> f64 {
+    let scale = if scale > 0.0 { scale } else { 1.0 };
+    round(len * scale) / scale
+}
+
+/// Creates a surface of `width` and `height` for drawing to `win`, with
+/// `scale` device pixels per pixel. At gtk's own scale factor, it's similar
+/// to `win`'s surface, otherwise it's an image surface at the (fractional)
+/// device scale.
+pub fn create_surface(
+    win: &gdk::Window,
+    content: cairo::Content,
+    width: f64,
+    height: f64,
+    scale: f64,
+) -> cairo::Surface {
+    if scale <= 0.0 || (scale - f64::from(win.get_scale_factor())).abs() < 1e-6
+    {
+        return win
+            .create_similar_surface(
+                content,
+                width.ceil() as i32,
+                height.ceil() as i32,
+            )
+            .unwrap();
+    }
+
+    let format = match content {
+        cairo::Content::Color => cairo::Format::Rgb24,
+        _ => cairo::Format::ARgb32,
+    };
+    let surface = cairo::ImageSurface::create(
+        format,
+        (width * scale).ceil() as i32,
+        (height * scale).ceil() as i32,
+    )
+    .unwrap();
+    surface.set_device_scale(scale, scale);
+    (*surface).clone()
+}
+
+/// Returns the index of the cell of `size` at `pos`. With fractional cell
+/// sizes, a position on a cell's edge (e.g. `row * height`) can fall short
+/// of the cell by a rounding error, which is ignored.
+pub fn cell_index(pos: f64, size: f64) -> u64 {
+    (pos / size + 1e-6).floor().max(0.0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snap() {
+        assert_eq!(snap(10.3, 1.0, f64::ceil), 11.0);
+        assert_eq!(snap(10.3, 2.0, f64::ceil), 10.5);
+        assert_eq!(snap(10.3, 1.25, f64::ceil), 10.4);
+        assert_eq!(snap(10.3, 0.0, f64::ceil), 11.0);
+        assert_eq!(snap(10.3, 1.25, f64::floor), 9.6);
+        assert_eq!(snap(10.3, 1.5, f64::round), 10.0);
+    }
+
+    #[test]
+    fn test_cell_index() {
+        let height = snap(17.1, 1.25, f64::ceil);
+        assert_eq!(cell_index(3.0 * height, height), 3);
+        assert_eq!(cell_index(3.0 * height - 0.5, height), 2);
+        assert_eq!(cell_index(0.0, height), 0);
+        assert_eq!(cell_index(-1.0, height), 0);
     }
 }
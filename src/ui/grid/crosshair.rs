@@ -0,0 +1,113 @@
+use crate::ui::color::Color;
+
+/// Highlight of the cursor's row and column, drawn by gnvim on top of the
+/// grid. Unlike nvim's 'cursorline' and 'cursorcolumn', it follows the
+/// cursor's position on the screen (e.g. while the cursor is animating).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Crosshair {
+    /// If the cursor's row is highlighted.
+    pub line: bool,
+    /// If the cursor's column is highlighted.
+    pub column: bool,
+    /// Color of the highlight. Defaults to the cursor's color.
+    pub color: Option<Color>,
+    /// Opacity of the highlight, from 0.0 to 1.0.
+    pub opacity: f64,
+}
+
+impl Default for Crosshair {
+    fn default() -> Self {
+        Self {
+            line: false,
+            column: false,
+            color: None,
+            opacity: 0.1,
+        }
+    }
+}
+
+impl Crosshair {
+    pub fn is_enabled(&self) -> bool {
+        (self.line || self.column) && self.opacity > 0.0
+    }
+
+    /// Returns the highlighted areas as (x, y, width, height), for the
+    /// cursor at `cursor` (x, y, width, height) in a grid of `size`
+    /// (width, height). The areas don't overlap, so that the cursor's cell
+    /// isn't highlighted twice.
+    pub fn rects(
+        &self,
+        cursor: (f64, f64, f64, f64),
+        size: (f64, f64),
+    ) -> Vec<(f64, f64, f64, f64)> {
+        if !self.is_enabled() {
+            return vec![];
+        }
+
+        let (x, y, w, h) = cursor;
+        let (width, height) = size;
+        let mut rects = vec![];
+
+        if self.line {
+            rects.push((0.0, y, width, h));
+        }
+
+        if self.column {
+            if self.line {
+                rects.push((x, 0.0, w, y));
+                rects.push((x, y + h, w, height - y - h));
+            } else {
+                rects.push((x, 0.0, w, height));
+            }
+        }
+
+        rects
+            .into_iter()
+            .filter(|r| r.2 > 0.0 && r.3 > 0.0)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crosshair_rects() {
+        let mut crosshair = Crosshair::default();
+        let cursor = (20.0, 30.0, 10.0, 15.0);
+        let size = (100.0, 60.0);
+        assert_eq!(crosshair.rects(cursor, size), vec![]);
+
+        crosshair.line = true;
+        assert_eq!(
+            crosshair.rects(cursor, size),
+            vec![(0.0, 30.0, 100.0, 15.0)]
+        );
+
+        crosshair.column = true;
+        assert_eq!(
+            crosshair.rects(cursor, size),
+            vec![
+                (0.0, 30.0, 100.0, 15.0),
+                (20.0, 0.0, 10.0, 30.0),
+                (20.0, 45.0, 10.0, 15.0),
+            ]
+        );
+
+        // Cursor on the first row.
+        assert_eq!(
+            crosshair.rects((20.0, 0.0, 10.0, 15.0), size),
+            vec![(0.0, 0.0, 100.0, 15.0), (20.0, 15.0, 10.0, 45.0)]
+        );
+
+        crosshair.line = false;
+        assert_eq!(
+            crosshair.rects(cursor, size),
+            vec![(20.0, 0.0, 10.0, 60.0)]
+        );
+
+        crosshair.opacity = 0.0;
+        assert_eq!(crosshair.rects(cursor, size), vec![]);
+    }
+}
@@ -1,4 +1,44 @@
-use crate::ui::color::Color;
+use crate::nvim_bridge::CursorShape;
+use crate::ui::animation::{ease_in_out_cubic, ease_out_cubic};
+use crate::ui::color::{Color, Highlight};
+
+/// Curve of the cursor's blink animation. The cursor fades out during the
+/// `blinkoff` time and fades in during the `blinkon` time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlinkCurve {
+    /// No fading, the cursor is either shown or hidden.
+    Step,
+    /// Fades linearly.
+    Linear,
+    /// Fades slowly at the start and the end of the fade.
+    EaseInOut,
+}
+
+impl BlinkCurve {
+    pub fn from_string(curve: &str) -> Option<Self> {
+        match curve {
+            "step" => Some(BlinkCurve::Step),
+            "linear" => Some(BlinkCurve::Linear),
+            "ease" => Some(BlinkCurve::EaseInOut),
+            _ => None,
+        }
+    }
+
+    /// Returns the progress of a fade at `t` (from 0 to 1).
+    fn apply(self, t: f64) -> f64 {
+        match self {
+            BlinkCurve::Step => 1.0,
+            BlinkCurve::Linear => t,
+            BlinkCurve::EaseInOut => ease_in_out_cubic(t),
+        }
+    }
+}
+
+impl Default for BlinkCurve {
+    fn default() -> Self {
+        BlinkCurve::Linear
+    }
+}
 
 #[derive(Default)]
 pub struct Animation {
@@ -18,16 +58,31 @@ pub struct Cursor {
 
     /// Alpha color. Used to make the cursor blink.
     pub alpha: f64,
-    /// The duration of the blink.
+    /// Delay (in ms) before the cursor starts to blink.
+    pub blink_wait: u64,
+    /// The time (in ms) that the cursor is shown when blinking.
     pub blink_on: u64,
-    /// Width of the cursor.
+    /// The time (in ms) that the cursor is hidden when blinking.
+    pub blink_off: u64,
+    pub blink_curve: BlinkCurve,
+    /// Frame time when the blinking (re)started.
+    pub blink_start: Option<i64>,
+    /// Shape of the cursor.
+    pub shape: CursorShape,
+    /// Width (or height, with horizontal shape) of the cursor.
     pub cell_percentage: f64,
+    /// Highlight id of the cursor. Zero means that the cursor uses the
+    /// cell's colors, inverted.
+    pub hl_id: u64,
     /// Color of the cursor.
     pub color: Color,
 }
 
 impl Cursor {
     pub fn goto(&mut self, row: f64, col: f64, frame_time: i64) {
+        // Moving the cursor restarts the blinking.
+        self.blink_start = Some(frame_time);
+
         // When we get our first cursor_goto, set the position directly.
         if self.pos.is_none() {
             self.pos = Some((row, col));
@@ -49,22 +104,43 @@ impl Cursor {
     }
 
     pub fn tick(&mut self, frame_time: i64) {
-        self.blink();
+        self.blink(frame_time);
         self.animate_position(frame_time);
     }
 
-    fn blink(&mut self) {
-        // If we dont need to blink, return.
+    /// Sets the blinking times (in ms), as in `guicursor`. If `off` is
+    /// zero, `on` is used for it too. Restarts the blinking.
+    pub fn set_blink(&mut self, wait: u64, on: u64, off: u64) {
+        self.blink_wait = wait;
+        self.blink_on = on;
+        self.blink_off = if off == 0 { on } else { off };
+        self.blink_start = None;
+    }
+
+    fn blink(&mut self, frame_time: i64) {
+        let start = *self.blink_start.get_or_insert(frame_time);
+
+        // If we dont need to blink, show the cursor.
         if self.blink_on == 0 {
+            self.alpha = 1.0;
             return;
         }
 
-        // Assuming a 60hz framerate
-        self.alpha += 100.0 / (6.0 * self.blink_on as f64);
-
-        if self.alpha > 2.0 {
-            self.alpha = 0.0;
+        // Frame time is in microseconds.
+        let elapsed = ((frame_time - start) / 1000).max(0) as u64;
+        if elapsed < self.blink_wait {
+            self.alpha = 1.0;
+            return;
         }
+
+        // After the wait, the cursor is first hidden and then shown again.
+        let t = (elapsed - self.blink_wait) % (self.blink_off + self.blink_on);
+        self.alpha = if t < self.blink_off {
+            1.0 - self.blink_curve.apply(t as f64 / self.blink_off as f64)
+        } else {
+            let t = t - self.blink_off;
+            self.blink_curve.apply(t as f64 / self.blink_on as f64)
+        };
     }
 
     fn animate_position(&mut self, frame_time: i64) {
@@ -104,11 +180,31 @@ impl Cursor {
     }
 }
 
-/// From clutter-easing.c, based on Robert Penner's
-/// infamous easing equations, MIT license.
-fn ease_out_cubic(t: f64) -> f64 {
-    let p = t - 1f64;
-    p * p * p + 1f64
+/// Returns the colors (text, cursor) of a block cursor on a cell with `cell`
+/// highlight. Colors of the cursor's own highlight (`cursor`) are used when
+/// available, and the cell's colors inverted otherwise.
+pub fn cursor_colors(
+    cell: &Highlight,
+    cursor: Option<&Highlight>,
+    default_fg: Color,
+    default_bg: Color,
+) -> (Color, Color) {
+    let fg = cell.foreground.unwrap_or(default_fg);
+    let bg = cell.background.unwrap_or(default_bg);
+    let (mut text, mut color) = if cell.reverse { (fg, bg) } else { (bg, fg) };
+
+    if let Some(hl) = cursor {
+        let (fg, bg) = if hl.reverse {
+            (hl.background, hl.foreground)
+        } else {
+            (hl.foreground, hl.background)
+        };
+
+        text = fg.unwrap_or(text);
+        color = bg.unwrap_or(color);
+    }
+
+    (text, color)
 }
 
 #[cfg(test)]
@@ -118,23 +214,104 @@ mod tests {
     #[test]
     fn test_cursor_blink100() {
         let mut cursor = Cursor::default();
-        cursor.blink_on = 100;
-        cursor.alpha = 1.0;
+        cursor.set_blink(0, 100, 0);
 
-        cursor.blink();
-        assert_eq!(cursor.alpha, 1.1666666666666667);
+        cursor.blink(0);
+        assert_eq!(cursor.alpha, 1.0);
+        // Fading out...
+        cursor.blink(25_000);
+        assert_eq!(cursor.alpha, 0.75);
+        // ...and in again.
+        cursor.blink(150_000);
+        assert_eq!(cursor.alpha, 0.5);
     }
 
     #[test]
     fn test_cursor_blink0() {
         let mut cursor = Cursor::default();
-        cursor.blink_on = 0;
-        cursor.alpha = 1.0;
+        cursor.set_blink(0, 0, 0);
+        cursor.alpha = 0.5;
+
+        cursor.blink(0);
+        cursor.blink(25_000);
+        assert_eq!(cursor.alpha, 1.0);
+    }
+
+    #[test]
+    fn test_cursor_blink_wait() {
+        let mut cursor = Cursor {
+            blink_curve: BlinkCurve::Step,
+            ..Cursor::default()
+        };
+        cursor.set_blink(500, 200, 100);
+
+        cursor.blink(0);
+        cursor.blink(499_000);
+        assert_eq!(cursor.alpha, 1.0);
+        cursor.blink(500_000);
+        assert_eq!(cursor.alpha, 0.0);
+        cursor.blink(599_000);
+        assert_eq!(cursor.alpha, 0.0);
+        cursor.blink(600_000);
+        assert_eq!(cursor.alpha, 1.0);
+        cursor.blink(800_000);
+        assert_eq!(cursor.alpha, 0.0);
 
-        cursor.blink();
+        // Moving the cursor restarts the wait.
+        cursor.goto(1.0, 1.0, 800_000);
+        cursor.blink(900_000);
         assert_eq!(cursor.alpha, 1.0);
     }
 
+    #[test]
+    fn test_blink_curve() {
+        assert_eq!(
+            BlinkCurve::from_string("ease"),
+            Some(BlinkCurve::EaseInOut)
+        );
+        assert_eq!(BlinkCurve::from_string("foo"), None);
+
+        assert_eq!(BlinkCurve::EaseInOut.apply(0.0), 0.0);
+        assert_eq!(BlinkCurve::EaseInOut.apply(0.5), 0.5);
+        assert_eq!(BlinkCurve::EaseInOut.apply(1.0), 1.0);
+        assert!(BlinkCurve::EaseInOut.apply(0.25) < 0.25);
+    }
+
+    #[test]
+    fn test_cursor_colors() {
+        let red = Color::from_u64(0xff0000);
+        let green = Color::from_u64(0x00ff00);
+        let blue = Color::from_u64(0x0000ff);
+        let white = Color::from_u64(0xffffff);
+
+        let cell = Highlight {
+            foreground: Some(red),
+            ..Highlight::default()
+        };
+
+        // Without cursor highlight, cell's colors are inverted.
+        assert_eq!(cursor_colors(&cell, None, white, blue), (blue, red));
+
+        let cursor = Highlight {
+            background: Some(green),
+            ..Highlight::default()
+        };
+        assert_eq!(
+            cursor_colors(&cell, Some(&cursor), white, blue),
+            (blue, green)
+        );
+
+        let cursor = Highlight {
+            foreground: Some(green),
+            reverse: true,
+            ..Highlight::default()
+        };
+        assert_eq!(
+            cursor_colors(&cell, Some(&cursor), white, blue),
+            (blue, green)
+        );
+    }
+
     #[test]
     fn test_first_position() {
         let mut cursor = Cursor::default();
@@ -0,0 +1,153 @@
+/// Keeps track of the cells of a grid that have been changed (damaged)
+/// since the last flush, so that only those parts of the grid need to be
+/// drawn to the screen.
+pub struct Damage {
+    cols: usize,
+    rows: usize,
+    /// Damaged state for each cell, row by row.
+    cells: Vec<bool>,
+}
+
+impl Damage {
+    pub fn new(cols: usize, rows: usize) -> Self {
+        Damage {
+            cols,
+            rows,
+            cells: vec![false; cols * rows],
+        }
+    }
+
+    /// Resizes the damage to `cols` and `rows`. Everything is marked as
+    /// damaged.
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+        self.cols = cols;
+        self.rows = rows;
+        self.cells = vec![true; cols * rows];
+    }
+
+    /// Marks cells from `from` to `to` (exclusive) on `row` as damaged.
+    /// Cells outside of the grid are ignored.
+    pub fn mark(&mut self, row: usize, from: usize, to: usize) {
+        if row >= self.rows {
+            return;
+        }
+
+        let to = to.min(self.cols);
+        let offset = row * self.cols;
+        for i in from.min(to)..to {
+            self.cells[offset + i] = true;
+        }
+    }
+
+    /// Marks region from `top` to `bot` and from `left` to `right` (both
+    /// exclusive) as damaged.
+    pub fn mark_region(
+        &mut self,
+        top: usize,
+        bot: usize,
+        left: usize,
+        right: usize,
+    ) {
+        for row in top..bot {
+            self.mark(row, left, right);
+        }
+    }
+
+    /// Marks the whole grid as damaged.
+    pub fn mark_all(&mut self) {
+        self.cells.iter_mut().for_each(|c| *c = true);
+    }
+
+    /// Returns the damaged areas as rectangles (top, bot, left, right) and
+    /// resets the damage. Damaged cells are coalesced to as few rectangles
+    /// as possible by joining consecutive cells on a row, and then
+    /// consecutive rows with identical spans.
+    pub fn take_rects(&mut self) -> Vec<(usize, usize, usize, usize)> {
+        let mut rects: Vec<(usize, usize, usize, usize)> = vec![];
+        // Rects that can still be extended by the next row.
+        let mut open: Vec<usize> = vec![];
+
+        for row in 0..self.rows {
+            let cells = &mut self.cells[row * self.cols..(row + 1) * self.cols];
+            let mut next_open = vec![];
+
+            let mut col = 0;
+            while col < self.cols {
+                if !cells[col] {
+                    col += 1;
+                    continue;
+                }
+
+                let start = col;
+                while col < self.cols && cells[col] {
+                    cells[col] = false;
+                    col += 1;
+                }
+
+                let extends = open.iter().find(|i| {
+                    let r = rects[**i];
+                    r.2 == start && r.3 == col
+                });
+
+                if let Some(i) = extends {
+                    rects[*i].1 = row + 1;
+                    next_open.push(*i);
+                } else {
+                    rects.push((row, row + 1, start, col));
+                    next_open.push(rects.len() - 1);
+                }
+            }
+
+            open = next_open;
+        }
+
+        rects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_rects_empty() {
+        let mut damage = Damage::new(10, 5);
+        assert_eq!(damage.take_rects(), vec![]);
+    }
+
+    #[test]
+    fn test_take_rects_joins_cells_and_rows() {
+        let mut damage = Damage::new(10, 5);
+        damage.mark(1, 2, 5);
+        damage.mark(2, 2, 5);
+        damage.mark(2, 7, 8);
+        damage.mark(4, 2, 5);
+
+        assert_eq!(
+            damage.take_rects(),
+            vec![(1, 3, 2, 5), (2, 3, 7, 8), (4, 5, 2, 5)]
+        );
+        // Taking the rects resets the damage.
+        assert_eq!(damage.take_rects(), vec![]);
+    }
+
+    #[test]
+    fn test_mark_outside_of_grid() {
+        let mut damage = Damage::new(10, 5);
+        damage.mark(5, 0, 10);
+        damage.mark(0, 8, 20);
+        damage.mark(1, 12, 20);
+
+        assert_eq!(damage.take_rects(), vec![(0, 1, 8, 10)]);
+    }
+
+    #[test]
+    fn test_mark_all() {
+        let mut damage = Damage::new(10, 5);
+        damage.mark_all();
+        assert_eq!(damage.take_rects(), vec![(0, 5, 0, 10)]);
+
+        damage.resize(3, 2);
+        assert_eq!(damage.take_rects(), vec![(0, 2, 0, 3)]);
+    }
+}
@@ -1,18 +1,33 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::fmt;
 use std::fmt::Display;
 use std::rc::Rc;
 
-use gdk::{EventMask, ModifierType};
-use gtk::{DrawingArea, EventBox};
+use gdk::{EventMask, ModifierType, WindowExt};
+use gtk::EventBox;
+use log::error;
+use nvim_rs::Value;
 
 use gtk::prelude::*;
 
-use crate::nvim_bridge::{GridLineSegment, ModeInfo};
+use crate::nvim_bridge::{CursorShape, GridLineSegment, ModeInfo};
+use crate::nvim_gio::GioNeovim;
 use crate::ui::color::HlDefs;
+use crate::ui::common::spawn_local;
 use crate::ui::font::Font;
 use crate::ui::grid::context::Context;
+use crate::ui::grid::crosshair::Crosshair;
+use crate::ui::grid::cursor::{self, BlinkCurve};
+use crate::ui::grid::images::Image;
+use crate::ui::grid::indent_guides::{IndentGuides, IndentInfo};
+use crate::ui::grid::links::{Link, LinkMatcher};
+use crate::ui::grid::rasterizer::{AsyncRows, Rasterizer};
 use crate::ui::grid::render;
+use crate::ui::grid::renderer::{self, RendererKind};
+use crate::ui::grid::selection::Selection;
+use crate::ui::grid::smooth_scroll::SmoothScroll;
+use crate::ui::grid::snippet::SnippetState;
+use crate::ui::screenshot;
 
 pub struct GridMetrics {
     // Row count in the grid.
@@ -33,6 +48,8 @@ pub struct GridMetrics {
 pub enum ScrollDirection {
     Up,
     Down,
+    Left,
+    Right,
 }
 
 impl Display for ScrollDirection {
@@ -40,6 +57,8 @@ impl Display for ScrollDirection {
         match self {
             ScrollDirection::Up => write!(fmt, "up"),
             ScrollDirection::Down => write!(fmt, "down"),
+            ScrollDirection::Left => write!(fmt, "left"),
+            ScrollDirection::Right => write!(fmt, "right"),
         }
     }
 }
@@ -65,7 +84,7 @@ impl Display for MouseButton {
 pub struct Grid {
     pub id: i64,
     /// Our internal "widget". This is what is drawn to the screen.
-    da: DrawingArea,
+    da: gtk::Widget,
     /// EventBox to get mouse events for this grid.
     eb: EventBox,
     /// Internal context that is manipulated and used when handling events.
@@ -73,8 +92,10 @@ pub struct Grid {
     /// Pointer position for dragging if we should call callback from
     /// `connect_motion_events_for_drag`.
     drag_position: Rc<RefCell<(u64, u64)>>,
+    /// State for turning smooth scroll events into wheel steps.
+    smooth_scroll: Rc<RefCell<SmoothScroll>>,
     /// Input context that need to be updated for the cursor position
-    im_context: Option<gtk::IMMulticontext>,
+    im_context: Rc<RefCell<Option<gtk::IMMulticontext>>>,
 }
 
 impl Grid {
@@ -88,10 +109,15 @@ impl Grid {
         rows: usize,
         hl_defs: &HlDefs,
         enable_cursor_animations: bool,
+        device_scale: Option<f64>,
+        renderer: RendererKind,
+        rasterizer: Option<&Rasterizer>,
     ) -> Self {
-        let da = DrawingArea::new();
+        let renderer = renderer::new(renderer);
+        let da = renderer.widget();
         let ctx = Rc::new(RefCell::new(Context::new(
             &da,
+            renderer,
             win,
             font,
             line_space,
@@ -99,31 +125,90 @@ impl Grid {
             rows,
             hl_defs,
             enable_cursor_animations,
+            device_scale,
         )));
 
-        da.connect_draw(clone!(ctx => move |_, cr| {
+        // Use a weak reference, because the renderer (which is owned by
+        // the context) holds on to this closure.
+        let ctx_weak = Rc::downgrade(&ctx);
+        ctx.borrow().renderer.connect_draw(Box::new(move |cr| {
+            let ctx = upgrade_weak!(ctx_weak);
             let mut ctx = ctx.borrow_mut();
-            drawingarea_draw(cr, &mut ctx);
-            Inhibit(false)
+            ctx.draw(cr);
         }));
 
         let eb = EventBox::new();
-        eb.add_events(EventMask::SCROLL_MASK);
+        eb.add_events(
+            EventMask::SCROLL_MASK
+                | EventMask::SMOOTH_SCROLL_MASK
+                | EventMask::POINTER_MOTION_MASK
+                | EventMask::LEAVE_NOTIFY_MASK,
+        );
         eb.add(&da);
 
+        // Links under the pointer are underlined.
+        eb.connect_motion_notify_event(clone!(ctx, da => move |eb, e| {
+            let mut ctx = ctx.borrow_mut();
+            let pos = e.get_position();
+            let (row, col) = ctx.cell_metrics.cell_at(pos.0, pos.1);
+            ctx.pointer = Some((row, col));
+            update_hovered_link(&mut ctx, eb, &da);
+            Inhibit(false)
+        }));
+        eb.connect_leave_notify_event(clone!(ctx, da => move |eb, _| {
+            let mut ctx = ctx.borrow_mut();
+            ctx.pointer = None;
+            update_hovered_link(&mut ctx, eb, &da);
+            Inhibit(false)
+        }));
+
+        if let Some(rasterizer) = rasterizer {
+            let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+            let ctx_weak = Rc::downgrade(&ctx);
+            rx.attach(
+                None,
+                clone!(da => move |raster| {
+                    let ctx = upgrade_weak!(ctx_weak, glib::Continue(false));
+                    let mut ctx = ctx.borrow_mut();
+                    if let Some((x, y, w, h)) = render::put_raster(&mut ctx, raster) {
+                        da.queue_draw_area(
+                            x.floor() as i32,
+                            y.floor() as i32,
+                            w.ceil() as i32,
+                            h.ceil() as i32,
+                        );
+                    }
+                    glib::Continue(true)
+                }),
+            );
+            ctx.borrow_mut().async_rows =
+                Some(AsyncRows::new(rasterizer.clone(), tx));
+        }
+
         da.add_tick_callback(clone!(ctx => move |da, clock| {
             let mut ctx = ctx.borrow_mut();
             ctx.tick(da, clock);
             glib::Continue(true)
         }));
 
+        // The cursor moves on the screen also when the grid moves (e.g. a
+        // float is dragged) or is resized.
+        let im_context = Rc::new(RefCell::new(None));
+        eb.connect_size_allocate(clone!(ctx, da, im_context => move |_, _| {
+            let ctx = ctx.borrow();
+            if let Some(ref im_context) = *im_context.borrow() {
+                set_cursor_location(&ctx, &da, im_context);
+            }
+        }));
+
         Grid {
             id,
             da,
             eb,
             context: ctx,
             drag_position: Rc::new(RefCell::new((0, 0))),
-            im_context: None,
+            smooth_scroll: Rc::new(RefCell::new(SmoothScroll::default())),
+            im_context,
         }
     }
 
@@ -133,23 +218,78 @@ impl Grid {
 
     pub fn flush(&self, hl_defs: &HlDefs) {
         let mut ctx = self.context.borrow_mut();
+        render::submit_pending(&mut ctx, &self.da.get_pango_context(), hl_defs);
+
+        ctx.selection_color = hl_defs.default_fg;
+        ctx.snippet_color = hl_defs.default_fg;
+        ctx.link_color = hl_defs.default_fg;
+        if std::mem::take(&mut ctx.hovered_link_stale) {
+            let link_areas = ctx.update_hovered_link();
+            ctx.queue_draw_area.extend(link_areas);
+        }
+        ctx.indent_guide_color = hl_defs.default_fg;
+        ctx.update_indent_guides();
 
         if let Some(cell) = ctx.cell_at_cursor() {
-            // If cursor isn't blinking, drawn the inverted cell into
-            // the cursor's cairo context.
-            if ctx.cursor.blink_on == 0 {
+            let hl = hl_defs.get(&cell.hl_id).unwrap();
+            let cursor_hl = match ctx.cursor.hl_id {
+                0 => None,
+                id => hl_defs.get(&id),
+            };
+            let colors = cursor::cursor_colors(
+                hl,
+                cursor_hl,
+                hl_defs.default_fg,
+                hl_defs.default_bg,
+            );
+
+            // Block cursor shows the cell (with the cursor's colors) under
+            // it, other shapes are just filled with the cursor's color.
+            if ctx.cursor.shape == CursorShape::Block {
                 render::cursor_cell(
-                    &ctx.cursor_context,
+                    &ctx.cursor_cell_context,
                     &self.da.get_pango_context(),
                     &cell,
                     &ctx.cell_metrics,
                     hl_defs,
+                    colors,
                 );
+            } else {
+                let cr = &ctx.cursor_cell_context;
+                let color = colors.1;
+                cr.save();
+                cr.set_source_rgb(color.r, color.g, color.b);
+                cr.paint();
+                cr.restore();
             }
 
-            // Update cursor color.
-            let hl = hl_defs.get(&cell.hl_id).unwrap();
-            ctx.cursor.color = hl.foreground.unwrap_or(hl_defs.default_fg);
+            ctx.cursor.color = colors.1;
+        }
+
+        // Turn the damaged cells into areas on the drawing area.
+        let rects = ctx.damage.take_rects();
+        let cm = &ctx.cell_metrics;
+        let areas = rects
+            .into_iter()
+            .map(|(top, bot, left, right)| {
+                let (x1, y1, x2, y2) = render::get_rect(
+                    cm.height,
+                    cm.width,
+                    top as f64,
+                    bot as f64,
+                    left as f64,
+                    right as f64,
+                );
+                let (x1, y1) = (x1.floor(), y1.floor());
+                (x1, y1, x2.ceil() - x1, y2.ceil() - y1)
+            })
+            .collect::<Vec<_>>();
+        ctx.queue_draw_area.extend(&areas);
+
+        if let Some(overlay) = ctx.damage_overlay.take() {
+            // Previous overlay needs to be cleared.
+            ctx.queue_draw_area.extend(overlay);
+            ctx.damage_overlay = Some(areas);
         }
 
         while let Some(area) = ctx.queue_draw_area.pop() {
@@ -162,9 +302,49 @@ impl Grid {
         }
     }
 
+    /// Sets the input context that needs to know where our cursor is. The
+    /// context's client window is our top level window, so the same context
+    /// can be shared by all the grids.
     pub fn set_im_context(&mut self, im_context: &gtk::IMMulticontext) {
-        im_context.set_client_window(self.da.get_window().as_ref());
-        self.im_context = Some(im_context.clone());
+        im_context.set_client_window(
+            self.da
+                .get_toplevel()
+                .and_then(|toplevel| toplevel.get_window())
+                .as_ref(),
+        );
+        self.im_context.replace(Some(im_context.clone()));
+    }
+
+    /// Sets (or clears) the input method's preedit text, which is drawn at
+    /// the cursor. `preedit` contains the text, its attributes and the
+    /// cursor position in the text.
+    pub fn set_preedit(
+        &self,
+        preedit: Option<(&str, &pango::AttrList, i32)>,
+        hl_defs: &HlDefs,
+    ) {
+        let mut ctx = self.context.borrow_mut();
+
+        if let Some((x, y, w, h)) = ctx.get_preedit_rect() {
+            self.da.queue_draw_area(x, y, w, h);
+        }
+
+        ctx.preedit = match (preedit, self.da.get_window()) {
+            (Some((text, attrs, cursor)), Some(win)) => Some(render::preedit(
+                &win,
+                &self.da.get_pango_context(),
+                &ctx.cell_metrics,
+                hl_defs,
+                text,
+                attrs,
+                cursor,
+            )),
+            _ => None,
+        };
+
+        if let Some((x, y, w, h)) = ctx.get_preedit_rect() {
+            self.da.queue_draw_area(x, y, w, h);
+        }
     }
 
     /// Returns position (+ width and height) for cell (row, col) relative
@@ -196,30 +376,107 @@ impl Grid {
         }
     }
 
+    /// Returns the cell (row, col) the cursor is at.
+    pub fn get_cursor_position(&self) -> Option<(u64, u64)> {
+        let ctx = self.context.borrow();
+        ctx.cursor
+            .get_position()
+            .map(|(row, col)| (row.ceil() as u64, col.ceil() as u64))
+    }
+
+    /// Returns the text on `row` starting from `col` up to the cursor. If
+    /// the cursor isn't on `row`, an empty string is returned.
+    pub fn get_text_before_cursor(&self, row: u64, col: u64) -> String {
+        let ctx = self.context.borrow();
+
+        ctx.cursor
+            .get_position()
+            .filter(|pos| pos.0.ceil() as u64 == row)
+            .and_then(|pos| {
+                let row = ctx.rows.get(row as usize)?;
+                let to = (pos.1.ceil() as usize).min(row.len());
+                if col as usize > to {
+                    return None;
+                }
+
+                Some(
+                    row.copy_range(col as usize, to)
+                        .iter()
+                        .map(|cell| cell.text.as_str())
+                        .collect::<String>(),
+                )
+            })
+            .unwrap_or_default()
+    }
+
     /// Connects `f` to internal widget's scroll events. `f` params are scroll
-    /// direction, row, col.
+    /// direction, row, col. Smooth scroll events (e.g. from touchpads) are
+    /// turned into whole steps, so `f` might be called multiple times per
+    /// event, or not at all.
     pub fn connect_scroll_events<F: 'static>(&self, f: F)
     where
         F: Fn(ScrollDirection, u64, u64) -> Inhibit,
     {
         let ctx = self.context.clone();
+        let smooth_scroll = self.smooth_scroll.clone();
+        let f = Rc::new(f);
+
+        self.eb.connect_scroll_event(move |eb, e| {
+            // Ctrl + scroll is used for zooming.
+            if e.get_state().contains(ModifierType::CONTROL_MASK) {
+                return Inhibit(false);
+            }
 
-        self.eb.connect_scroll_event(move |_, e| {
             let ctx = ctx.borrow();
 
+            let pos = e.get_position();
+            let (row, col) = ctx.cell_metrics.cell_at(pos.0, pos.1);
+
             let dir = match e.get_direction() {
                 gdk::ScrollDirection::Up => ScrollDirection::Up,
-                _ => ScrollDirection::Down,
+                gdk::ScrollDirection::Down => ScrollDirection::Down,
+                gdk::ScrollDirection::Left => ScrollDirection::Left,
+                gdk::ScrollDirection::Right => ScrollDirection::Right,
+                _ => {
+                    let mut scroll = smooth_scroll.borrow_mut();
+                    scroll.position = (row, col);
+
+                    if e.get_is_stop() {
+                        if scroll.stop() && !scroll.ticking {
+                            scroll.ticking = true;
+                            start_coasting(
+                                eb,
+                                smooth_scroll.clone(),
+                                f.clone(),
+                            );
+                        }
+
+                        return Inhibit(false);
+                    }
+
+                    let (dx, dy) = e.get_delta();
+                    let steps = scroll.push(dx, dy, e.get_time());
+                    drop(scroll);
+
+                    scroll_steps(f.as_ref(), steps, row, col);
+                    return Inhibit(false);
+                }
             };
 
-            let pos = e.get_position();
-            let col = (pos.0 / ctx.cell_metrics.width).floor() as u64;
-            let row = (pos.1 / ctx.cell_metrics.height).floor() as u64;
-
             f(dir, row, col)
         });
     }
 
+    /// Enables or disables kinetic scrolling, which keeps on scrolling with
+    /// decreasing speed after a smooth scroll (e.g. on a touchpad) ends.
+    pub fn enable_kinetic_scrolling(&self, enable: bool) {
+        let mut scroll = self.smooth_scroll.borrow_mut();
+        scroll.kinetic = enable;
+        if !enable {
+            scroll.coasting = false;
+        }
+    }
+
     /// Connects `f` to internal widget's motion events. `f` params are button,
     /// row, col. `f` is only called when the cell under the pointer changes.
     pub fn connect_motion_events_for_drag<F: 'static>(&self, f: F)
@@ -229,8 +486,16 @@ impl Grid {
         let ctx = self.context.clone();
         let drag_position = self.drag_position.clone();
 
-        self.eb.connect_motion_notify_event(move |_, e| {
-            let ctx = ctx.borrow();
+        self.eb.connect_motion_notify_event(move |eb, e| {
+            // The pointer is just moved over the grid.
+            let buttons = ModifierType::BUTTON1_MASK
+                | ModifierType::BUTTON2_MASK
+                | ModifierType::BUTTON3_MASK;
+            if !e.get_state().intersects(buttons) {
+                return Inhibit(false);
+            }
+
+            let mut ctx = ctx.borrow_mut();
             let mut drag_position = drag_position.borrow_mut();
 
             let button = match e.get_state() {
@@ -240,11 +505,20 @@ impl Grid {
             };
 
             let pos = e.get_position();
-            let col = (pos.0 / ctx.cell_metrics.width).floor() as u64;
-            let row = (pos.1 / ctx.cell_metrics.height).floor() as u64;
+            let (row, col) = ctx.cell_metrics.cell_at(pos.0, pos.1);
 
             if drag_position.0 != col || drag_position.1 != row {
                 *drag_position = (col, row);
+
+                if let Some(ref mut selection) = ctx.selection {
+                    if selection.active {
+                        selection.end = (row, col);
+                        eb.queue_draw();
+                        return Inhibit(true);
+                    }
+                }
+                drop(ctx);
+
                 f(button, row, col)
             } else {
                 Inhibit(false)
@@ -260,8 +534,8 @@ impl Grid {
     {
         let ctx = self.context.clone();
 
-        self.eb.connect_button_press_event(move |_, e| {
-            let ctx = ctx.borrow();
+        self.eb.connect_button_press_event(move |eb, e| {
+            let mut ctx = ctx.borrow_mut();
 
             let button = match e.get_button() {
                 3 => MouseButton::Right,
@@ -270,13 +544,56 @@ impl Grid {
             };
 
             let pos = e.get_position();
-            let col = (pos.0 / ctx.cell_metrics.width).floor() as u64;
-            let row = (pos.1 / ctx.cell_metrics.height).floor() as u64;
+            let (row, col) = ctx.cell_metrics.cell_at(pos.0, pos.1);
+
+            if ctx.gui_selection && e.get_button() == 1 {
+                ctx.selection = Some(Selection::new(row, col, e.get_time()));
+                eb.queue_draw();
+                return Inhibit(true);
+            }
+            drop(ctx);
 
             f(button, row, col)
         });
     }
 
+    /// Connects `f` to ctrl + left clicks on links (see `set_link_matcher`).
+    /// The clicks aren't passed to the other handlers.
+    pub fn connect_link_clicked<F: 'static>(&self, f: F)
+    where
+        F: Fn(Link),
+    {
+        let ctx = self.context.clone();
+
+        self.eb.connect_button_press_event(move |_, e| {
+            if e.get_button() != 1
+                || !e.get_state().contains(ModifierType::CONTROL_MASK)
+            {
+                return Inhibit(false);
+            }
+
+            let ctx = ctx.borrow();
+            let pos = e.get_position();
+            let (row, col) = ctx.cell_metrics.cell_at(pos.0, pos.1);
+            match ctx.link_at(row, col) {
+                Some(link) => {
+                    drop(ctx);
+                    f(link);
+                    Inhibit(true)
+                }
+                None => Inhibit(false),
+            }
+        });
+    }
+
+    /// Sets the patterns of the links.
+    pub fn set_link_matcher(&self, matcher: Rc<LinkMatcher>) {
+        let mut ctx = self.context.borrow_mut();
+        ctx.link_matcher = matcher;
+        let areas = ctx.update_hovered_link();
+        ctx.queue_draw_area.extend(areas);
+    }
+
     /// Connects `f` to internal widget's mouse button release event. `f` params
     /// are button, row, col.
     pub fn connect_mouse_button_release_events<F: 'static>(&self, f: F)
@@ -286,7 +603,14 @@ impl Grid {
         let ctx = self.context.clone();
 
         self.eb.connect_button_release_event(move |_, e| {
-            let ctx = ctx.borrow();
+            let mut ctx = ctx.borrow_mut();
+
+            if let Some(ref mut selection) = ctx.selection {
+                if selection.active {
+                    selection.active = false;
+                    return Inhibit(true);
+                }
+            }
 
             let button = match e.get_button() {
                 3 => MouseButton::Right,
@@ -295,8 +619,8 @@ impl Grid {
             };
 
             let pos = e.get_position();
-            let col = (pos.0 / ctx.cell_metrics.width).floor() as u64;
-            let row = (pos.1 / ctx.cell_metrics.height).floor() as u64;
+            let (row, col) = ctx.cell_metrics.cell_at(pos.0, pos.1);
+            drop(ctx);
 
             f(button, row, col)
         });
@@ -305,31 +629,63 @@ impl Grid {
     /// Connects `f` to internal widget's resize events. `f` params are rows, cols.
     pub fn connect_da_resize<F: 'static>(&self, f: F)
     where
-        F: Fn(u64, u64) -> bool,
+        F: Fn(u64, u64),
     {
         let ctx = self.context.clone();
 
-        self.da.connect_configure_event(move |da, _| {
+        // NOTE: Not all renderers' widgets have their own gdk window, so
+        //       we can't rely on configure events here.
+        self.da.connect_size_allocate(move |da, _| {
             let ctx = ctx.borrow();
 
             let w = f64::from(da.get_allocated_width());
             let h = f64::from(da.get_allocated_height());
-            let cols = (w / ctx.cell_metrics.width).floor() as u64;
-            let rows = (h / ctx.cell_metrics.height).floor() as u64;
+            let (cols, rows) = ctx.cell_metrics.grid_size(w, h);
 
             f(rows, cols)
         });
     }
 
-    pub fn put_line(&self, line: GridLineSegment, hl_defs: &HlDefs) {
+    pub fn put_line(&self, line: GridLineSegment) {
         let mut ctx = self.context.borrow_mut();
-
-        render::put_line(&mut ctx, &self.da.get_pango_context(), line, hl_defs);
+        if ctx.pointer.map(|(row, _)| row) == Some(line.row) {
+            ctx.hovered_link_stale = true;
+        }
+        render::put_line(&mut ctx, line);
     }
 
-    pub fn redraw(&self, hl_defs: &HlDefs) {
+    /// Marks the rows that have cells which highlight id passes `f` to be
+    /// rendered again by `redraw_stale`, a few rows at a time.
+    pub fn mark_stale<F: Fn(u64) -> bool>(&self, f: F) {
         let mut ctx = self.context.borrow_mut();
-        render::redraw(&mut ctx, &self.da.get_pango_context(), hl_defs);
+        render::mark_stale(&mut ctx, f);
+    }
+
+    /// If some of the rows marked by `mark_stale` haven't been rendered
+    /// again yet.
+    pub fn has_stale_rows(&self) -> bool {
+        !self.context.borrow().stale_rows.is_empty()
+    }
+
+    /// Renders again (at most) `max_rows` of the rows marked by
+    /// `mark_stale`, and flushes them. Returns the number of rows rendered.
+    pub fn redraw_stale(&self, hl_defs: &HlDefs, max_rows: usize) -> usize {
+        let rendered = {
+            let mut ctx = self.context.borrow_mut();
+            if ctx.stale_rows.is_empty() {
+                return 0;
+            }
+
+            render::redraw_stale(
+                &mut ctx,
+                &self.da.get_pango_context(),
+                hl_defs,
+                max_rows,
+            )
+        };
+
+        self.flush(hl_defs);
+        rendered
     }
 
     pub fn cursor_goto(&self, row: u64, col: u64) {
@@ -337,15 +693,8 @@ impl Grid {
         let mut ctx = self.context.borrow_mut();
         ctx.cursor_goto(row, col, &clock);
 
-        let (x, y, width, height) = ctx.get_cursor_rect();
-        if let Some(ref im_context) = self.im_context {
-            let rect = gdk::Rectangle {
-                x,
-                y,
-                width,
-                height,
-            };
-            im_context.set_cursor_location(&rect);
+        if let Some(ref im_context) = *self.im_context.borrow() {
+            set_cursor_location(&ctx, &self.da, im_context);
         }
     }
 
@@ -374,12 +723,11 @@ impl Grid {
     pub fn calc_size(&self) -> (i64, i64) {
         let ctx = self.context.borrow();
 
-        let w = self.da.get_allocated_width();
-        let h = self.da.get_allocated_height();
-        let cols = (w / ctx.cell_metrics.width as i32) as i64;
-        let rows = (h / ctx.cell_metrics.height as i32) as i64;
+        let w = f64::from(self.da.get_allocated_width());
+        let h = f64::from(self.da.get_allocated_height());
+        let (cols, rows) = ctx.cell_metrics.grid_size(w, h);
 
-        (cols, rows)
+        (cols as i64, rows as i64)
     }
 
     pub fn resize(
@@ -400,8 +748,13 @@ impl Grid {
         for row in ctx.rows.iter_mut() {
             row.clear();
         }
+        ctx.hovered_link_stale |= ctx.pointer.is_some();
+        ctx.pending_rows.clear();
+        if let Some(ref mut async_rows) = ctx.async_rows {
+            async_rows.cancel();
+        }
 
-        render::clear(&self.da, &mut ctx, hl_defs)
+        render::clear(&mut ctx, hl_defs)
     }
 
     pub fn scroll(
@@ -412,7 +765,11 @@ impl Grid {
         hl_defs: &HlDefs,
     ) {
         let mut ctx = self.context.borrow_mut();
+        ctx.hovered_link_stale |= ctx.pointer.is_some();
 
+        // Scrolling moves the rendered contents, so they need to be up to
+        // date first.
+        render::put_pending(&mut ctx, &self.da.get_pango_context(), hl_defs);
         render::scroll(&mut ctx, hl_defs, reg, rows);
     }
 
@@ -420,6 +777,10 @@ impl Grid {
         let mut ctx = self.context.borrow_mut();
 
         ctx.active = active;
+
+        if let Some(ref im_context) = *self.im_context.borrow() {
+            set_cursor_location(&ctx, &self.da, im_context);
+        }
     }
 
     /// Set a new font and line space. This will likely change the cell metrics.
@@ -434,6 +795,14 @@ impl Grid {
         ctx.update_metrics(font, line_space, &self.da, win);
     }
 
+    /// Sets the scale of the device pixels, which the cell metrics are
+    /// aligned to. `None` uses gtk's scale factor. Takes effect on the next
+    /// `update_cell_metrics`.
+    pub fn set_device_scale(&self, scale: Option<f64>) {
+        let mut ctx = self.context.borrow_mut();
+        ctx.device_scale = scale;
+    }
+
     /// Get the current line space value.
     pub fn get_line_space(&self) -> i64 {
         let ctx = self.context.borrow();
@@ -449,8 +818,130 @@ impl Grid {
     pub fn set_mode(&self, mode: &ModeInfo) {
         let mut ctx = self.context.borrow_mut();
 
-        ctx.cursor.blink_on = mode.blink_on;
+        ctx.cursor
+            .set_blink(mode.blink_wait, mode.blink_on, mode.blink_off);
+        ctx.cursor.shape = mode.cursor_shape.clone();
         ctx.cursor.cell_percentage = mode.cell_percentage;
+        ctx.cursor.hl_id = mode.attr_id;
+    }
+
+    /// Sets if our window has the focus. When not focused, the cursor
+    /// doesn't blink (and might be drawn as hollow).
+    pub fn set_focused(&self, focused: bool) {
+        let mut ctx = self.context.borrow_mut();
+        ctx.focused = focused;
+    }
+
+    /// Sets if the cursor is drawn as hollow when the window doesn't have
+    /// the focus.
+    pub fn enable_cursor_hollow_unfocused(&self, enable: bool) {
+        let mut ctx = self.context.borrow_mut();
+        ctx.hollow_unfocused = enable;
+    }
+
+    /// Sets the highlight of the cursor's row and column.
+    pub fn set_crosshair(&self, crosshair: Crosshair) {
+        let mut ctx = self.context.borrow_mut();
+        ctx.crosshair = crosshair;
+        self.da.queue_draw();
+    }
+
+    /// Sets the snippet that is being expanded in this grid, whose
+    /// placeholders are tinted and whose indicator is drawn next to the
+    /// cursor.
+    pub fn set_snippet(&self, snippet: SnippetState, hl_defs: &HlDefs) {
+        let mut ctx = self.context.borrow_mut();
+        if ctx.snippet == snippet {
+            return;
+        }
+
+        ctx.snippet_indicator =
+            match (snippet.indicator(), self.da.get_window()) {
+                (Some(text), Some(win)) => Some(render::snippet_indicator(
+                    &win,
+                    &self.da.get_pango_context(),
+                    &ctx.cell_metrics,
+                    hl_defs,
+                    &text,
+                )),
+                _ => None,
+            };
+        ctx.snippet = snippet;
+        self.da.queue_draw();
+    }
+
+    pub fn set_indent_guides(&self, guides: IndentGuides) {
+        let mut ctx = self.context.borrow_mut();
+        ctx.indent_guides = guides;
+        ctx.update_indent_guides();
+        self.da.queue_draw();
+    }
+
+    /// Shows `image` on top of the grid, replacing the image that has the
+    /// same id.
+    pub fn show_image(&self, image: Image) {
+        let mut ctx = self.context.borrow_mut();
+        ctx.images.retain(|i| i.id != image.id);
+        ctx.images.push(image);
+        self.da.queue_draw();
+    }
+
+    /// Hides the image `id`, if it's shown on this grid.
+    pub fn hide_image(&self, id: u64) {
+        let mut ctx = self.context.borrow_mut();
+        let len = ctx.images.len();
+        ctx.images.retain(|i| i.id != id);
+        if ctx.images.len() != len {
+            self.da.queue_draw();
+        }
+    }
+
+    /// Hides all the images shown on this grid.
+    pub fn clear_images(&self) {
+        let mut ctx = self.context.borrow_mut();
+        if !ctx.images.is_empty() {
+            ctx.images.clear();
+            self.da.queue_draw();
+        }
+    }
+
+    /// Fetches the layout of `win`'s text (which we're showing) for the
+    /// indent guides.
+    pub fn update_indent_info(&self, nvim: &GioNeovim, win: Value) {
+        let nvim = nvim.clone();
+        let ctx = self.context.clone();
+        let da = self.da.clone();
+        spawn_local(async move {
+            let info = match nvim
+                .call_function("gnvim#window#indent_info", vec![win])
+                .await
+            {
+                Ok(info) => info,
+                Err(err) => {
+                    error!("Failed to get indent info: {}", err);
+                    return;
+                }
+            };
+
+            let info = info.as_array().and_then(|info| {
+                Some(IndentInfo {
+                    text_offset: info.get(0)?.as_u64()? as usize,
+                    width: info.get(1)?.as_u64()? as usize,
+                })
+            });
+
+            let mut ctx = ctx.borrow_mut();
+            if ctx.indent_info != info {
+                ctx.indent_info = info;
+                ctx.update_indent_guides();
+                da.queue_draw();
+            }
+        });
+    }
+
+    pub fn set_cursor_blink_curve(&self, curve: BlinkCurve) {
+        let mut ctx = self.context.borrow_mut();
+        ctx.cursor.blink_curve = curve;
     }
 
     pub fn set_busy(&self, busy: bool) {
@@ -463,34 +954,269 @@ impl Grid {
         let mut ctx = self.context.borrow_mut();
         ctx.cursor.disable_animation = !enable;
     }
+
+    pub fn get_background_opacity(&self) -> f64 {
+        self.context.borrow().background_opacity
+    }
+
+    /// Sets the opacity of the default background color. Text and other
+    /// background colors stay opaque.
+    pub fn set_background_opacity(
+        &self,
+        opacity: f64,
+        win: &gdk::Window,
+        hl_defs: &HlDefs,
+    ) {
+        let mut ctx = self.context.borrow_mut();
+
+        ctx.background_opacity = opacity;
+        ctx.renderer.set_background_opacity(opacity);
+
+        // Recreate the renderer's contents with the new opacity. If we
+        // don't have any contents yet, the first resize takes care of it.
+        if let Some(cols) = ctx.rows.get(0).map(|row| row.len) {
+            let rows = ctx.rows.len();
+            ctx.resize(&self.da, win, cols, rows, hl_defs);
+        }
+    }
+
+    /// Recreates the renderer's contents at the current scale of the device
+    /// pixels, e.g. after the window moved to another monitor. The grid's
+    /// size in cells might stay the same, so nvim won't redraw it.
+    pub fn rescale(&self, win: &gdk::Window, hl_defs: &HlDefs) {
+        let mut ctx = self.context.borrow_mut();
+
+        if let Some(cols) = ctx.rows.get(0).map(|row| row.len) {
+            let rows = ctx.rows.len();
+            ctx.resize(&self.da, win, cols, rows, hl_defs);
+            render::redraw(
+                &mut ctx,
+                &self.da.get_pango_context(),
+                hl_defs,
+                |_| true,
+            );
+        }
+    }
+
+    /// Enables or disables mouse selections made by gnvim. When enabled,
+    /// the left mouse button selects text instead of being sent to nvim.
+    pub fn enable_gui_selection(&self, enable: bool) {
+        let mut ctx = self.context.borrow_mut();
+        ctx.gui_selection = enable;
+
+        if !enable && ctx.selection.take().is_some() {
+            self.da.queue_draw();
+        }
+    }
+
+    /// Returns the number of rows up to the last row with text.
+    pub fn content_rows(&self) -> usize {
+        let ctx = self.context.borrow();
+        ctx.rows
+            .iter()
+            .rposition(|row| !row.is_blank())
+            .map_or(0, |i| i + 1)
+    }
+
+    /// Returns the grid's rows as runs of text with the same highlight id.
+    pub fn get_contents(&self) -> Vec<Vec<(String, u64)>> {
+        let ctx = self.context.borrow();
+        ctx.rows
+            .iter()
+            .map(|row| {
+                if row.len() == 0 {
+                    return vec![];
+                }
+
+                row.as_segments(0, row.len() - 1)
+                    .into_iter()
+                    .map(|seg| (seg.text, seg.hl_id))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Returns a screenshot of `cells` (row, col, rows, cols), or of the
+    /// whole grid.
+    pub fn snapshot(
+        &self,
+        cells: Option<(u64, u64, u64, u64)>,
+    ) -> Result<gdk_pixbuf::Pixbuf, String> {
+        let mut ctx = self.context.borrow_mut();
+
+        let (x, y, width, height) = match cells {
+            Some((row, col, rows, cols)) => {
+                let cm = &ctx.cell_metrics;
+                let (x, y) = render::get_coords(
+                    cm.height, cm.width, row as f64, col as f64,
+                );
+                (x, y, cols as f64 * cm.width, rows as f64 * cm.height)
+            }
+            None => (
+                0.0,
+                0.0,
+                f64::from(self.da.get_allocated_width()),
+                f64::from(self.da.get_allocated_height()),
+            ),
+        };
+
+        screenshot::paint(
+            width.ceil() as i32,
+            height.ceil() as i32,
+            self.da.get_scale_factor(),
+            |cr| {
+                cr.translate(-x, -y);
+                ctx.snapshot(cr);
+            },
+        )
+    }
+
+    /// Returns the time the current mouse selection was started at and the
+    /// selected text, if there is a selection.
+    pub fn get_selection(&self) -> Option<(u32, String)> {
+        let ctx = self.context.borrow();
+        ctx.selection
+            .filter(|selection| !selection.is_empty())
+            .map(|selection| (selection.time, selection.text(&ctx.rows)))
+    }
+
+    /// Shows `selection` (e.g. of copy mode) instead of the current one, or
+    /// removes the selection.
+    pub fn set_selection(&self, selection: Option<Selection>) {
+        let mut ctx = self.context.borrow_mut();
+        if ctx.selection != selection {
+            ctx.selection = selection;
+            self.da.queue_draw();
+        }
+    }
+
+    /// Returns the text of `selection` in the grid.
+    pub fn selection_text(&self, selection: &Selection) -> String {
+        let ctx = self.context.borrow();
+        selection.text(&ctx.rows)
+    }
+
+    /// Enables or disables the debug overlay, which highlights the areas
+    /// that were damaged on the last flush.
+    pub fn enable_damage_overlay(&self, enable: bool) {
+        let mut ctx = self.context.borrow_mut();
+
+        if let Some(overlay) = ctx.damage_overlay.take() {
+            ctx.queue_draw_area.extend(overlay);
+        }
+
+        if enable {
+            ctx.damage_overlay = Some(vec![]);
+        }
+    }
+}
+
+/// Calls `f` for each whole scroll step in `steps` (cols, rows).
+fn scroll_steps<F>(f: &F, steps: (i64, i64), row: u64, col: u64)
+where
+    F: Fn(ScrollDirection, u64, u64) -> Inhibit,
+{
+    let (cols, rows) = steps;
+
+    for _ in 0..rows.abs() {
+        if rows > 0 {
+            f(ScrollDirection::Down, row, col);
+        } else {
+            f(ScrollDirection::Up, row, col);
+        }
+    }
+
+    for _ in 0..cols.abs() {
+        if cols > 0 {
+            f(ScrollDirection::Right, row, col);
+        } else {
+            f(ScrollDirection::Left, row, col);
+        }
+    }
+}
+
+/// Keeps calling `f` on each frame (on `eb`) for as long as
+/// `smooth_scroll` is coasting.
+fn start_coasting<F: 'static>(
+    eb: &EventBox,
+    smooth_scroll: Rc<RefCell<SmoothScroll>>,
+    f: Rc<F>,
+) where
+    F: Fn(ScrollDirection, u64, u64) -> Inhibit,
+{
+    let last_frame = Cell::new(None);
+
+    eb.add_tick_callback(move |_, clock| {
+        let now = clock.get_frame_time();
+        let dt = last_frame
+            .replace(Some(now))
+            .map_or(0.0, |last| (now - last) as f64 / 1_000_000.0);
+
+        let mut scroll = smooth_scroll.borrow_mut();
+        match scroll.coast(dt) {
+            Some(steps) => {
+                let (row, col) = scroll.position;
+                drop(scroll);
+
+                scroll_steps(f.as_ref(), steps, row, col);
+                glib::Continue(true)
+            }
+            None => {
+                scroll.ticking = false;
+                glib::Continue(false)
+            }
+        }
+    });
+}
+
+/// Positions the input method's candidate window (and e.g. on-screen
+/// keyboards that follow it) at the cursor of the active grid. The cursor's
+/// target is used, so the window doesn't wait for the cursor's animation.
+fn set_cursor_location(
+    ctx: &Context,
+    da: &gtk::Widget,
+    im_context: &gtk::IMMulticontext,
+) {
+    if !ctx.active {
+        return;
+    }
+
+    let (x, y, width, height) = ctx.get_cursor_target_rect();
+    let toplevel = match da.get_toplevel() {
+        Some(toplevel) => toplevel,
+        None => return,
+    };
+    if let Some((x, y)) = da.translate_coordinates(&toplevel, x, y) {
+        im_context.set_cursor_location(&gdk::Rectangle {
+            x,
+            y,
+            width,
+            height,
+        });
+    }
 }
 
-/// Handler for grid's drawingarea's draw event. Draws the internal cairo
-/// context (`ctx`) surface to the `cr`.
-fn drawingarea_draw(cr: &cairo::Context, ctx: &mut Context) {
-    let surface = ctx.cairo_context.get_target();
-    surface.flush();
-
-    cr.save();
-    cr.set_source_surface(&surface, 0.0, 0.0);
-    cr.paint();
-    cr.restore();
-
-    // If we're not "busy", draw the cursor.
-    if !ctx.busy && ctx.active {
-        let (x, y, w, h) = ctx.get_cursor_rect();
-
-        cr.save();
-        cr.rectangle(
-            f64::from(x),
-            f64::from(y),
-            f64::from(w) * ctx.cursor.cell_percentage,
-            f64::from(h),
+/// Updates the link under the pointer (see `Context::update_hovered_link`),
+/// and shows a hand cursor on links.
+fn update_hovered_link(ctx: &mut Context, eb: &EventBox, da: &gtk::Widget) {
+    let areas = ctx.update_hovered_link();
+    if areas.is_empty() {
+        return;
+    }
+
+    for (x, y, w, h) in areas {
+        da.queue_draw_area(
+            x.floor() as i32,
+            y.floor() as i32,
+            w.ceil() as i32,
+            h.ceil() as i32,
         );
-        let surface = ctx.cursor_context.get_target();
-        surface.flush();
-        cr.set_source_surface(&surface, x.into(), y.into());
-        cr.fill();
-        cr.restore();
+    }
+
+    if let Some(win) = eb.get_window() {
+        let cursor = ctx.hovered_link.as_ref().and_then(|_| {
+            gdk::Cursor::from_name(&win.get_display(), "pointer")
+        });
+        win.set_cursor(cursor.as_ref());
     }
 }
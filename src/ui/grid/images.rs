@@ -0,0 +1,123 @@
+use gdk::prelude::GdkContextExt;
+
+/// Image shown on top of a grid's cells (e.g. a thumbnail by a plugin).
+pub struct Image {
+    pub id: u64,
+    pixbuf: gdk_pixbuf::Pixbuf,
+    /// Cell (row, col) of the image's top left corner. Scrolling can move
+    /// the image partially above the grid, so the row can be negative.
+    pub pos: (i64, u64),
+    /// Size of the image in cells (rows, cols).
+    pub size: (u64, u64),
+
+    /// The image scaled to the size it was last drawn with.
+    cache: Option<cairo::ImageSurface>,
+}
+
+impl Image {
+    pub fn new(
+        id: u64,
+        path: &str,
+        pos: (i64, u64),
+        size: (u64, u64),
+    ) -> Result<Self, glib::Error> {
+        let pixbuf = gdk_pixbuf::Pixbuf::from_file(path)?;
+        Ok(Image::from_pixbuf(id, pixbuf, pos, size))
+    }
+
+    pub fn from_pixbuf(
+        id: u64,
+        pixbuf: gdk_pixbuf::Pixbuf,
+        pos: (i64, u64),
+        size: (u64, u64),
+    ) -> Self {
+        Image {
+            id,
+            pixbuf,
+            pos,
+            size,
+            cache: None,
+        }
+    }
+
+    /// Returns the image scaled to fit in `width` and `height`, keeping
+    /// its aspect ratio.
+    pub fn surface(&mut self, width: f64, height: f64) -> cairo::ImageSurface {
+        let iw = f64::from(self.pixbuf.get_width());
+        let ih = f64::from(self.pixbuf.get_height());
+        let scale = (width / iw).min(height / ih);
+        let w = (iw * scale).round().max(1.0) as i32;
+        let h = (ih * scale).round().max(1.0) as i32;
+
+        match self.cache {
+            Some(ref s) if s.get_width() == w && s.get_height() == h => {}
+            _ => {
+                // Drop the old surface first, so we don't keep two of them
+                // around.
+                self.cache = None;
+                let surface =
+                    cairo::ImageSurface::create(cairo::Format::ARgb32, w, h)
+                        .unwrap();
+                let cr = cairo::Context::new(&surface);
+                cr.scale(f64::from(w) / iw, f64::from(h) / ih);
+                cr.set_source_pixbuf(&self.pixbuf, 0.0, 0.0);
+                cr.paint();
+                self.cache = Some(surface);
+            }
+        }
+
+        self.cache.clone().unwrap()
+    }
+}
+
+/// Returns the row of an image at `pos` and of `size` (see `Image`) after
+/// scrolling region `reg` (top, bot, left, right) by `count` rows. Images
+/// outside of the region don't move. `None` is returned if the image is
+/// scrolled out of the region.
+pub fn scroll_row(
+    pos: (i64, u64),
+    size: (u64, u64),
+    reg: [u64; 4],
+    count: i64,
+) -> Option<i64> {
+    let (row, col) = pos;
+    let (rows, cols) = size;
+    let (top, bot) = (reg[0] as i64, reg[1] as i64);
+    let (left, right) = (reg[2], reg[3]);
+
+    let inside = |row: i64| row < bot && row + rows as i64 > top;
+    if col < left || col + cols > right || !inside(row) {
+        return Some(row);
+    }
+
+    let row = row - count;
+    if inside(row) {
+        Some(row)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scroll_row() {
+        let reg = [0, 20, 0, 80];
+
+        assert_eq!(scroll_row((5, 0), (4, 10), reg, 2), Some(3));
+        assert_eq!(scroll_row((5, 0), (4, 10), reg, -2), Some(7));
+        // Partially scrolled out.
+        assert_eq!(scroll_row((1, 0), (4, 10), reg, 3), Some(-2));
+        assert_eq!(scroll_row((18, 0), (4, 10), reg, -1), Some(19));
+        // Completely scrolled out.
+        assert_eq!(scroll_row((1, 0), (4, 10), reg, 5), None);
+        assert_eq!(scroll_row((18, 0), (4, 10), reg, -2), None);
+
+        // Outside of the region.
+        let reg = [10, 20, 0, 40];
+        assert_eq!(scroll_row((2, 0), (4, 10), reg, 1), Some(2));
+        assert_eq!(scroll_row((12, 35), (4, 10), reg, 1), Some(12));
+    }
+}
@@ -0,0 +1,133 @@
+use crate::ui::color::Color;
+use crate::ui::grid::row::Row;
+
+/// Thin vertical lines drawn by gnvim at the indentation levels of the
+/// rows' leading whitespace.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IndentGuides {
+    pub enabled: bool,
+    /// Color of the guides. Defaults to the default foreground color.
+    pub color: Option<Color>,
+    /// Opacity of the guides, from 0.0 to 1.0.
+    pub opacity: f64,
+}
+
+impl Default for IndentGuides {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            color: None,
+            opacity: 0.2,
+        }
+    }
+}
+
+/// Layout of a window's text, fetched from nvim.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IndentInfo {
+    /// Column where the text starts (e.g. after the number column).
+    pub text_offset: usize,
+    /// Width of one indentation level ('shiftwidth').
+    pub width: usize,
+}
+
+/// Returns the indentation of `row`'s text starting from column `offset`,
+/// or `None` if the text is blank.
+pub fn row_indent(row: &Row, offset: usize) -> Option<usize> {
+    (offset..row.len).position(|col| {
+        row.cell_at(col)
+            .map(|cell| cell.text != " " && !cell.text.is_empty())
+            .unwrap_or(false)
+    })
+}
+
+/// Returns the guides for rows with `indents` (see `row_indent`) as
+/// (column, first row, last row exclusive). Blank rows continue the guides
+/// of the rows around them.
+pub fn guides(
+    indents: &[Option<usize>],
+    width: usize,
+) -> Vec<(usize, usize, usize)> {
+    if width == 0 {
+        return vec![];
+    }
+
+    let indents = indents
+        .iter()
+        .enumerate()
+        .map(|(i, indent)| {
+            indent.unwrap_or_else(|| {
+                let prev = indents[..i].iter().rev().find_map(|i| *i);
+                let next = indents[i + 1..].iter().find_map(|i| *i);
+                prev.unwrap_or(0).min(next.unwrap_or(0))
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let max = indents.iter().copied().max().unwrap_or(0);
+    let mut guides = vec![];
+
+    for col in (0..max).step_by(width) {
+        let mut start = None;
+        for (row, indent) in indents.iter().enumerate() {
+            match (start, *indent > col) {
+                (None, true) => start = Some(row),
+                (Some(first), false) => {
+                    guides.push((col, first, row));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(first) = start {
+            guides.push((col, first, indents.len()));
+        }
+    }
+
+    guides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::grid::row::Cell;
+
+    fn row(text: &str, len: usize) -> Row {
+        let mut row = Row::new(len);
+        row.insert_at(
+            0,
+            text.chars()
+                .map(|c| Cell {
+                    text: c.to_string(),
+                    hl_id: 0,
+                    double_width: false,
+                })
+                .collect(),
+        );
+        row
+    }
+
+    #[test]
+    fn test_row_indent() {
+        assert_eq!(row_indent(&row("    foo", 10), 0), Some(4));
+        assert_eq!(row_indent(&row("foo", 10), 0), Some(0));
+        assert_eq!(row_indent(&row("  1   foo", 10), 4), Some(2));
+        assert_eq!(row_indent(&row("      ", 10), 0), None);
+        assert_eq!(row_indent(&row("  1   ", 10), 4), None);
+    }
+
+    #[test]
+    fn test_guides() {
+        let indents =
+            vec![Some(0), Some(4), Some(8), None, Some(8), Some(4), Some(0)];
+        assert_eq!(guides(&indents, 4), vec![(0, 1, 6), (4, 2, 5)]);
+
+        // Blank rows take the smaller indentation of the rows around them.
+        let indents = vec![Some(8), None, Some(4), None];
+        assert_eq!(guides(&indents, 4), vec![(0, 0, 3), (4, 0, 1)]);
+
+        assert_eq!(guides(&indents, 0), vec![]);
+        assert_eq!(guides(&[None, None], 4), vec![]);
+    }
+}
@@ -0,0 +1,236 @@
+use regex::Regex;
+
+/// URLs with a scheme. Trailing punctuation isn't part of the URL.
+pub const DEFAULT_URL_PATTERN: &str =
+    r#"\b(?:https?|ftp|file)://[^\s<>"'`]*[^\s<>"'`.,;:!?)\]}]"#;
+/// Paths with a directory (e.g. `src/main.rs`), or file names with a line
+/// number (e.g. `main.rs:10`). Both can end with a line and a column.
+pub const DEFAULT_PATH_PATTERN: &str = concat!(
+    r"(?:~|\.{1,2})?/?(?:[\w.@+-]+/)+[\w.@+-]*\w(?::\d+){0,2}",
+    r"|\b[\w@+-][\w.@+-]*\.\w+(?::\d+){1,2}",
+);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkKind {
+    Url,
+    Path,
+}
+
+/// Link in a grid's row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    pub kind: LinkKind,
+    pub text: String,
+    /// Column of the link's first cell.
+    pub start: usize,
+    /// Column of the cell after the link.
+    pub end: usize,
+}
+
+/// Finds URLs and file paths in the text of the grids.
+pub struct LinkMatcher {
+    url: Option<Regex>,
+    path: Option<Regex>,
+}
+
+impl LinkMatcher {
+    /// Creates a matcher for the `url` and `path` patterns. An empty
+    /// pattern doesn't match anything.
+    pub fn new(url: &str, path: &str) -> Result<Self, regex::Error> {
+        let compile = |pattern: &str| {
+            if pattern.is_empty() {
+                Ok(None)
+            } else {
+                Regex::new(pattern).map(Some)
+            }
+        };
+
+        Ok(LinkMatcher {
+            url: compile(url)?,
+            path: compile(path)?,
+        })
+    }
+
+    /// Returns the link at column `col` of a row, which has the texts of
+    /// its cells in `cells`. URLs are preferred over paths.
+    pub fn find(&self, cells: &[&str], col: usize) -> Option<Link> {
+        // Byte offset of each cell's text, and the end of the text.
+        let mut offsets = Vec::with_capacity(cells.len() + 1);
+        let mut text = String::new();
+        for cell in cells {
+            offsets.push(text.len());
+            text.push_str(cell);
+        }
+        offsets.push(text.len());
+
+        // Double width characters are followed by an empty cell, which
+        // belongs to the character's cell.
+        let to_col = |offset: usize| offsets.iter().position(|o| *o >= offset);
+
+        let patterns =
+            [(&self.url, LinkKind::Url), (&self.path, LinkKind::Path)];
+        patterns.iter().find_map(|(regex, kind)| {
+            regex.as_ref()?.find_iter(&text).find_map(|m| {
+                let start = to_col(m.start())?;
+                let end = to_col(m.end())?;
+                if col < start || col >= end {
+                    return None;
+                }
+
+                Some(Link {
+                    kind: *kind,
+                    text: m.as_str().to_string(),
+                    start,
+                    end,
+                })
+            })
+        })
+    }
+}
+
+impl Default for LinkMatcher {
+    fn default() -> Self {
+        LinkMatcher::new(DEFAULT_URL_PATTERN, DEFAULT_PATH_PATTERN).unwrap()
+    }
+}
+
+/// Returns the hyperlink at column `col` of a row, which has the hyperlinks
+/// of its cells (from their highlights) in `urls`. The link spans the cells
+/// around `col` that have the same hyperlink.
+pub fn find_url(urls: &[Option<&str>], col: usize) -> Option<Link> {
+    let url = (*urls.get(col)?)?;
+    let start = urls[..col]
+        .iter()
+        .rposition(|u| *u != Some(url))
+        .map_or(0, |i| i + 1);
+    let end = urls[col..]
+        .iter()
+        .position(|u| *u != Some(url))
+        .map_or(urls.len(), |i| col + i);
+
+    Some(Link {
+        kind: LinkKind::Url,
+        text: url.to_string(),
+        start,
+        end,
+    })
+}
+
+/// Splits the line and the column from the end of `path` (e.g.
+/// `main.rs:10:5`).
+pub fn split_position(path: &str) -> (&str, Option<u64>, Option<u64>) {
+    let mut parts = path.rsplitn(3, ':');
+    let last = parts.next().and_then(|p| p.parse().ok());
+    let middle = parts.next();
+    let first = parts.next();
+
+    match (first, middle.map(|m| (m, m.parse::<u64>().ok())), last) {
+        (Some(path), Some((_, Some(line))), Some(col)) => {
+            (path, Some(line), Some(col))
+        }
+        (_, Some(_), Some(line)) => {
+            let end = path.rfind(':').unwrap();
+            (&path[..end], Some(line), None)
+        }
+        _ => (path, None, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(text: &str) -> Vec<String> {
+        text.chars().map(|c| c.to_string()).collect()
+    }
+
+    fn find(matcher: &LinkMatcher, text: &str, col: usize) -> Option<Link> {
+        let cells = cells(text);
+        let cells = cells.iter().map(|c| c.as_str()).collect::<Vec<_>>();
+        matcher.find(&cells, col)
+    }
+
+    #[test]
+    fn test_find_url() {
+        let matcher = LinkMatcher::default();
+        let text = "see https://example.com/a_(b)?c=1, or not";
+
+        let expected = Some(Link {
+            kind: LinkKind::Url,
+            text: "https://example.com/a_(b)?c=1".to_string(),
+            start: 4,
+            end: 33,
+        });
+        assert_eq!(find(&matcher, text, 4), expected);
+        assert_eq!(find(&matcher, text, 32), expected);
+        assert_eq!(find(&matcher, text, 33), None);
+        assert_eq!(find(&matcher, text, 2), None);
+    }
+
+    #[test]
+    fn test_find_path() {
+        let matcher = LinkMatcher::default();
+
+        let link = find(&matcher, "error: src/ui/grid.rs:10:5: oops", 10);
+        assert_eq!(
+            link,
+            Some(Link {
+                kind: LinkKind::Path,
+                text: "src/ui/grid.rs:10:5".to_string(),
+                start: 7,
+                end: 26,
+            })
+        );
+
+        let link = find(&matcher, "at main.rs:10", 5).unwrap();
+        assert_eq!(link.text, "main.rs:10");
+        // Just a word.
+        assert_eq!(find(&matcher, "at main.rs", 5), None);
+    }
+
+    #[test]
+    fn test_find_wide_cells() {
+        let matcher = LinkMatcher::default();
+        let cells = vec!["日", "", " ", "a", "/", "b"];
+
+        let link = matcher.find(&cells, 4).unwrap();
+        assert_eq!((link.start, link.end), (3, 6));
+    }
+
+    #[test]
+    fn test_disabled_patterns() {
+        let matcher = LinkMatcher::new("", DEFAULT_PATH_PATTERN).unwrap();
+        let link = find(&matcher, "https://example.com/a", 10).unwrap();
+        assert_eq!(link.kind, LinkKind::Path);
+        assert_eq!(link.text, "/example.com/a");
+
+        assert!(LinkMatcher::new("(", "").is_err());
+    }
+
+    #[test]
+    fn test_find_hyperlink() {
+        let a = Some("https://a.example");
+        let b = Some("https://b.example");
+        let urls = vec![None, a, a, a, b, b, None];
+
+        let link = find_url(&urls, 2).unwrap();
+        assert_eq!(link.text, "https://a.example");
+        assert_eq!((link.start, link.end), (1, 4));
+        let link = find_url(&urls, 5).unwrap();
+        assert_eq!((link.start, link.end), (4, 6));
+        assert_eq!(find_url(&urls, 0), None);
+        assert_eq!(find_url(&urls, 6), None);
+        assert_eq!(find_url(&urls, 7), None);
+    }
+
+    #[test]
+    fn test_split_position() {
+        assert_eq!(split_position("a/b.rs"), ("a/b.rs", None, None));
+        assert_eq!(split_position("a/b.rs:10"), ("a/b.rs", Some(10), None));
+        assert_eq!(
+            split_position("a/b.rs:10:5"),
+            ("a/b.rs", Some(10), Some(5))
+        );
+        assert_eq!(split_position("a:b/c.rs:10"), ("a:b/c.rs", Some(10), None));
+    }
+}
@@ -1,8 +1,30 @@
+#[cfg(all(feature = "unstable", test))]
+mod benches;
 mod context;
+mod crosshair;
 mod cursor;
+mod damage;
 #[allow(clippy::module_inception)]
 mod grid;
+mod images;
+mod indent_guides;
+mod links;
+mod rasterizer;
 mod render;
+mod renderer;
 mod row;
+mod selection;
+mod smooth_scroll;
+mod snippet;
 
-pub use self::grid::{Grid, GridMetrics};
+pub use self::context::cell_index;
+pub use self::crosshair::Crosshair;
+pub use self::cursor::BlinkCurve;
+pub use self::grid::{Grid, GridMetrics, MouseButton};
+pub use self::images::Image;
+pub use self::indent_guides::IndentGuides;
+pub use self::links::{split_position, Link, LinkKind, LinkMatcher};
+pub use self::rasterizer::Rasterizer;
+pub use self::renderer::RendererKind;
+pub use self::selection::Selection;
+pub use self::snippet::{SnippetPlaceholder, SnippetState};
@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use log::error;
+
+use crate::ui::color::{Color, Highlight, HlDefs};
+use crate::ui::grid::context::CellMetrics;
+use crate::ui::grid::render;
+use crate::ui::grid::row::{Row, Segment};
+
+/// Text rendering options of a pango context. Pango contexts can't be
+/// shared between threads, so the worker threads set these to their own
+/// contexts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextOptions {
+    resolution: f64,
+    antialias: cairo::Antialias,
+    hint_style: cairo::HintStyle,
+    hint_metrics: cairo::HintMetrics,
+    subpixel_order: cairo::SubpixelOrder,
+}
+
+impl TextOptions {
+    pub fn from_context(ctx: &pango::Context) -> Self {
+        let opts = pangocairo::functions::context_get_font_options(ctx)
+            .unwrap_or_else(cairo::FontOptions::new);
+
+        Self {
+            resolution: pangocairo::functions::context_get_resolution(ctx),
+            antialias: opts.get_antialias(),
+            hint_style: opts.get_hint_style(),
+            hint_metrics: opts.get_hint_metrics(),
+            subpixel_order: opts.get_subpixel_order(),
+        }
+    }
+
+    fn apply(&self, ctx: &pango::Context) {
+        let mut opts = cairo::FontOptions::new();
+        opts.set_antialias(self.antialias);
+        opts.set_hint_style(self.hint_style);
+        opts.set_hint_metrics(self.hint_metrics);
+        opts.set_subpixel_order(self.subpixel_order);
+
+        pangocairo::functions::context_set_font_options(ctx, Some(&opts));
+        pangocairo::functions::context_set_resolution(ctx, self.resolution);
+    }
+}
+
+/// Segments of a row to be rasterized.
+struct Job {
+    id: u64,
+    row: usize,
+    segments: Vec<(Segment, Highlight)>,
+    cell_metrics: CellMetrics,
+    /// Default foreground, background and special colors.
+    colors: (Color, Color, Color),
+    bg_opacity: f64,
+    /// Device scale of the grid (see `CellMetrics::scale`).
+    scale: f64,
+    text_options: TextOptions,
+    reply: glib::Sender<Raster>,
+}
+
+/// Rasterized segments of a row.
+pub struct Raster {
+    id: u64,
+    pub row: usize,
+    /// Position of the pixels in the row.
+    pub x: f64,
+    pub width: f64,
+    pub height: f64,
+    scale: f64,
+    stride: i32,
+    data: Vec<u8>,
+}
+
+impl Raster {
+    /// Returns the pixels as a surface, and the scale factor of the pixels.
+    pub fn into_surface(self) -> Option<(cairo::ImageSurface, f64)> {
+        let scale = self.scale;
+        let surface = cairo::ImageSurface::create_for_data(
+            self.data,
+            cairo::Format::ARgb32,
+            (self.width * scale).ceil() as i32,
+            (self.height * scale).ceil() as i32,
+            self.stride,
+        )
+        .ok()?;
+        Some((surface, scale))
+    }
+}
+
+/// Pool of threads that rasterize (shape and render) text off the main
+/// thread, so that heavy output doesn't block the UI.
+#[derive(Clone)]
+pub struct Rasterizer {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl Rasterizer {
+    pub fn new(threads: usize) -> Self {
+        let (tx, rx) = mpsc::channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+
+        for i in 0..threads.max(1) {
+            let rx = rx.clone();
+            thread::Builder::new()
+                .name(format!("rasterizer-{}", i))
+                .spawn(move || worker(&rx))
+                .expect("Failed to spawn rasterizer thread");
+        }
+
+        Self { jobs: tx }
+    }
+}
+
+fn worker(jobs: &Mutex<mpsc::Receiver<Job>>) {
+    let mut pango_context = None;
+
+    loop {
+        let job = match jobs.lock().unwrap().recv() {
+            Ok(job) => job,
+            // All the senders are gone.
+            Err(_) => return,
+        };
+
+        if let Some(raster) = rasterize(&job, &mut pango_context) {
+            // The grid might be gone already.
+            let _ = job.reply.send(raster);
+        }
+    }
+}
+
+/// Renders `job`'s segments into a new surface. `pango_context` is the
+/// thread's context, which is created (or re-created, if the text options
+/// change) as needed.
+fn rasterize(
+    job: &Job,
+    pango_context: &mut Option<(TextOptions, pango::Context)>,
+) -> Option<Raster> {
+    let cm = &job.cell_metrics;
+    let x = job
+        .segments
+        .iter()
+        .map(|(seg, _)| cm.floor(seg.start as f64 * cm.width))
+        .fold(f64::INFINITY, f64::min);
+    let end = job
+        .segments
+        .iter()
+        .map(|(seg, _)| {
+            cm.floor(seg.start as f64 * cm.width)
+                + cm.ceil(seg.len as f64 * cm.width)
+        })
+        .fold(0.0, f64::max);
+    if end <= x {
+        return None;
+    }
+
+    let width = end - x;
+    let height = cm.ceil(cm.height);
+    let scale = job.scale;
+
+    let mut surface = cairo::ImageSurface::create(
+        cairo::Format::ARgb32,
+        (width * scale).ceil() as i32,
+        (height * scale).ceil() as i32,
+    )
+    .ok()?;
+
+    {
+        let cr = cairo::Context::new(&surface);
+        cr.scale(scale, scale);
+
+        let pctx = match pango_context {
+            Some((opts, ctx)) if *opts == job.text_options => ctx.clone(),
+            _ => {
+                let ctx = pangocairo::functions::create_context(&cr)?;
+                job.text_options.apply(&ctx);
+                *pango_context = Some((job.text_options, ctx.clone()));
+                ctx
+            }
+        };
+        pctx.set_font_description(&cm.font.as_pango_font());
+
+        let mut hl_defs = HlDefs::default();
+        hl_defs.default_fg = job.colors.0;
+        hl_defs.default_bg = job.colors.1;
+        hl_defs.default_sp = job.colors.2;
+
+        // Rendering the segments in reversed order fixes issues when some
+        // character is overflowing to the right.
+        for (seg, hl) in job.segments.iter().rev() {
+            render::render_text(
+                &cr,
+                &pctx,
+                cm,
+                hl,
+                &hl_defs,
+                job.bg_opacity,
+                &seg.text,
+                &seg.wide,
+                (seg.start as f64 * cm.width).floor() - x,
+                0.0,
+                (seg.len as f64 * cm.width).ceil(),
+                height,
+            );
+        }
+    }
+
+    surface.flush();
+    let stride = surface.get_stride();
+    let data = surface.get_data().ok()?.to_vec();
+
+    Some(Raster {
+        id: job.id,
+        row: job.row,
+        x,
+        width,
+        height,
+        scale: job.scale,
+        stride,
+        data,
+    })
+}
+
+/// Rows of a grid that are being rasterized by a `Rasterizer`.
+pub struct AsyncRows {
+    rasterizer: Rasterizer,
+    /// Where the rasterized rows are sent to.
+    reply: glib::Sender<Raster>,
+    /// Latest job id and the changed cells of the rows being rasterized.
+    in_flight: HashMap<usize, (u64, (usize, usize))>,
+    next_id: u64,
+}
+
+impl AsyncRows {
+    pub fn new(rasterizer: Rasterizer, reply: glib::Sender<Raster>) -> Self {
+        Self {
+            rasterizer,
+            reply,
+            in_flight: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Sends the changed cells (`range`) of `row` (which is at index `i`)
+    /// to be rasterized. If the row is already being rasterized, the new
+    /// job covers the cells of the old one too.
+    #[allow(clippy::too_many_arguments)]
+    pub fn submit(
+        &mut self,
+        i: usize,
+        range: (usize, usize),
+        row: &Row,
+        cm: &CellMetrics,
+        hl_defs: &HlDefs,
+        bg_opacity: f64,
+        text_options: TextOptions,
+    ) {
+        let range = match self.in_flight.get(&i) {
+            Some((_, old)) => (old.0.min(range.0), old.1.max(range.1)),
+            None => range,
+        };
+
+        let segments = row
+            .as_segments(range.0, range.1)
+            .into_iter()
+            .map(|seg| {
+                let hl = hl_defs.get(&seg.hl_id).cloned().unwrap_or_default();
+                (seg, hl)
+            })
+            .collect();
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.in_flight.insert(i, (id, range));
+
+        let job = Job {
+            id,
+            row: i,
+            segments,
+            cell_metrics: cm.clone(),
+            colors: (
+                hl_defs.default_fg,
+                hl_defs.default_bg,
+                hl_defs.default_sp,
+            ),
+            bg_opacity,
+            scale: cm.scale,
+            text_options,
+            reply: self.reply.clone(),
+        };
+
+        if self.rasterizer.jobs.send(job).is_err() {
+            error!("Rasterizer threads are gone");
+        }
+    }
+
+    /// Returns true if `raster` is from the latest job of its row, and
+    /// forgets the job. Results of older (or cancelled) jobs are outdated.
+    pub fn finish(&mut self, raster: &Raster) -> bool {
+        match self.in_flight.get(&raster.row) {
+            Some((id, _)) if *id == raster.id => {
+                self.in_flight.remove(&raster.row);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Stops waiting for the rows being rasterized. Returns the rows and
+    /// their cells that still need to be rendered.
+    pub fn cancel(&mut self) -> Vec<(usize, (usize, usize))> {
+        self.in_flight
+            .drain()
+            .map(|(row, (_, range))| (row, range))
+            .collect()
+    }
+}
@@ -1,12 +1,15 @@
-use gtk::prelude::*;
-use gtk::DrawingArea;
 use pango::Attribute;
 
 use crate::nvim_bridge::GridLineSegment;
-use crate::ui::color::Highlight;
 use crate::ui::color::HlDefs;
-use crate::ui::grid::context::{CellMetrics, Context};
-use crate::ui::grid::row::{Cell, Segment};
+use crate::ui::color::{Color, Highlight};
+use crate::ui::grid::context::{create_surface, CellMetrics, Context, Preedit};
+use crate::ui::grid::damage::Damage;
+use crate::ui::grid::images;
+use crate::ui::grid::rasterizer::{Raster, TextOptions};
+use crate::ui::grid::renderer::{scroll_ranges, Renderer};
+use crate::ui::grid::row::{Cell, Segment, WideCell};
+use crate::ui::grid::snippet::SnippetIndicator;
 
 /// Renders text to `cr`.
 ///
@@ -15,19 +18,23 @@ use crate::ui::grid::row::{Cell, Segment};
 /// * `cm` - Cell metrics to use for text placement.
 /// * `hl` - The highlighting to use.
 /// * `hl_defs` - Global hl defs. Used to get default values.
+/// * `bg_opacity` - Opacity of the default background color.
 /// * `text` - The text to render.
+/// * `wide` - Double width cells in `text`.
 /// * `x` - Target x coordinate for `cr`.
 /// * `y` - Target y coordinate for `cr`.
 /// * `w` - Target width for `cr`.
 /// * `h` - Target height for `cr`.
 #[allow(clippy::too_many_arguments, clippy::many_single_char_names)]
-fn render_text(
+pub fn render_text(
     cr: &cairo::Context,
     pango_context: &pango::Context,
     cm: &CellMetrics,
     hl: &Highlight,
     hl_defs: &HlDefs,
+    bg_opacity: f64,
     text: &str,
+    wide: &[WideCell],
     x: f64,
     y: f64,
     w: f64,
@@ -45,8 +52,19 @@ fn render_text(
         )
     };
 
+    // Only the default background is translucent, so that highlighted
+    // areas (e.g. statusline or visual selection) stay readable. Blended
+    // highlights (e.g. floats with `winblend`) are translucent regardless.
+    let alpha = if bg == hl_defs.default_bg {
+        bg_opacity
+    } else {
+        1.0
+    } * (1.0 - f64::from(hl.blend) / 100.0);
+
     cr.save();
-    cr.set_source_rgb(bg.r, bg.g, bg.b);
+    // Replace the old contents, instead of blending on top of them.
+    cr.set_operator(cairo::Operator::Source);
+    cr.set_source_rgba(bg.r, bg.g, bg.b, alpha);
     cr.rectangle(x, y, w, h);
     cr.fill();
     cr.restore();
@@ -63,10 +81,99 @@ fn render_text(
     }
 
     cr.save();
-    cr.set_source_rgb(fg.r, fg.g, fg.b);
+    // Virtual text (e.g. inline git blame) is faded, so that it stands out
+    // less than the buffer's text.
+    let fg_alpha = hl.virtual_text.map_or(1.0, |vt| vt.opacity);
+    cr.set_source_rgba(fg.r, fg.g, fg.b, fg_alpha);
+
+    if hl.virtual_text.map_or(false, |vt| vt.proportional) {
+        render_proportional(cr, pango_context, cm, &attrs, text, x, y, w, h);
+    } else {
+        // Render the double width cells separately, so that they stay in
+        // their own cells regardless of the font that is used for them.
+        let mut offset = 0;
+        let mut col = 0;
+        for cell in wide {
+            let run = &text[offset..cell.text.start];
+            let x_run = x + col as f64 * cm.width;
+            render_run(cr, pango_context, cm, &attrs, run, x_run, y);
+
+            let x_cell = x + cell.col as f64 * cm.width;
+            let text = &text[cell.text.clone()];
+            render_wide(cr, pango_context, cm, &attrs, text, x_cell, y);
+
+            offset = cell.text.end;
+            col = cell.col + 2;
+        }
+        let x_run = x + col as f64 * cm.width;
+        render_run(cr, pango_context, cm, &attrs, &text[offset..], x_run, y);
+    }
+
+    // Since we can't (for some reason) use pango attributes to draw
+    // underline and undercurl, we'll have to do that manually.
+    let sp = hl.special.unwrap_or(hl_defs.default_sp);
+    cr.set_source_rgb(sp.r, sp.g, sp.b);
+    render_underline(cr, cm, hl, x, y + h + cm.underline_position, w);
+
+    cr.restore();
+}
+
+/// Renders underline of `hl` to `cr`, with current source. Only one style
+/// of underline is rendered, and undercurl takes precedence over the others.
+///
+/// * `x` - Target x coordinate for `cr`.
+/// * `y` - Target y coordinate (the underline position) for `cr`.
+/// * `w` - Target width for `cr`.
+fn render_underline(
+    cr: &cairo::Context,
+    cm: &CellMetrics,
+    hl: &Highlight,
+    x: f64,
+    y: f64,
+    w: f64,
+) {
+    let t = cm.underline_thickness;
 
+    if hl.undercurl {
+        pangocairo::functions::show_error_underline(cr, x, y - t, w, t * 2.0);
+    } else if hl.underdouble {
+        cr.rectangle(x, y, w, t);
+        cr.rectangle(x, y - t * 2.0, w, t);
+        cr.fill();
+    } else if hl.underdashed || hl.underdotted {
+        let (on, off) = if hl.underdashed {
+            (t * 4.0, t * 2.0)
+        } else {
+            (t, t)
+        };
+
+        cr.save();
+        cr.set_line_width(t);
+        // Offset the pattern by our position, so that it continues
+        // seamlessly over adjacent segments.
+        cr.set_dash(&[on, off], x % (on + off));
+        cr.move_to(x, y + t / 2.0);
+        cr.line_to(x + w, y + t / 2.0);
+        cr.stroke();
+        cr.restore();
+    } else if hl.underline {
+        cr.rectangle(x, y, w, t);
+        cr.fill();
+    }
+}
+
+/// Renders single width cells' `text` to `cr`, starting from `x` and `y`.
+fn render_run(
+    cr: &cairo::Context,
+    pango_context: &pango::Context,
+    cm: &CellMetrics,
+    attrs: &pango::AttrList,
+    text: &str,
+    x: f64,
+    y: f64,
+) {
     let items =
-        pango::itemize(pango_context, text, 0, text.len() as i32, &attrs, None);
+        pango::itemize(pango_context, text, 0, text.len() as i32, attrs, None);
 
     let mut x_offset = 0.0;
     for item in items {
@@ -86,40 +193,128 @@ fn render_text(
         x_offset += f64::from(item.num_chars()) * cm.width;
         //x_offset += f64::from(glyphs.get_width());
     }
+}
 
-    // Since we can't (for some reason) use pango attributes to draw
-    // underline and undercurl, we'll have to do that manually.
-    let sp = hl.special.unwrap_or(hl_defs.default_sp);
-    cr.set_source_rgb(sp.r, sp.g, sp.b);
-    if hl.undercurl {
-        pangocairo::functions::show_error_underline(
-            cr,
-            x,
-            y + h + cm.underline_position - cm.underline_thickness,
-            w,
-            cm.underline_thickness * 2.0,
+/// Renders virtual `text` to `cr` in a proportional font, instead of laying
+/// it out to the cells. The text is clipped to the cells it covers, and
+/// its baseline is aligned with the grid's text.
+#[allow(clippy::too_many_arguments, clippy::many_single_char_names)]
+fn render_proportional(
+    cr: &cairo::Context,
+    pango_context: &pango::Context,
+    cm: &CellMetrics,
+    attrs: &pango::AttrList,
+    text: &str,
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+) {
+    let mut desc = cm.font.as_pango_font();
+    desc.set_family("Sans");
+
+    let layout = pango::Layout::new(pango_context);
+    layout.set_font_description(Some(&desc));
+    layout.set_attributes(Some(attrs));
+    layout.set_text(text);
+
+    let baseline = f64::from(layout.get_baseline()) / f64::from(pango::SCALE);
+
+    cr.save();
+    cr.rectangle(x, y, w, h);
+    cr.clip();
+    cr.move_to(x, y + cm.ascent - baseline);
+    pangocairo::functions::show_layout(cr, &layout);
+    cr.restore();
+}
+
+/// Renders double width cell's `text` to `cr`, to the two cells starting
+/// from `x` and `y`. The text is shaped as a whole, so composed sequences
+/// (e.g. emoji with ZWJs or skin tone modifiers) end up as a single glyph
+/// cluster. If the glyphs are bigger than the cells (which is common with
+/// color emoji fonts), they're scaled down to fit.
+fn render_wide(
+    cr: &cairo::Context,
+    pango_context: &pango::Context,
+    cm: &CellMetrics,
+    attrs: &pango::AttrList,
+    text: &str,
+    x: f64,
+    y: f64,
+) {
+    let scale = f64::from(pango::SCALE);
+    let w = cm.width * 2.0;
+
+    let items =
+        pango::itemize(pango_context, text, 0, text.len() as i32, attrs, None);
+
+    let mut shaped = vec![];
+    let mut width = 0.0;
+    // Ink extents relative to the baseline.
+    let mut top: f64 = 0.0;
+    let mut bottom: f64 = 0.0;
+    for item in items {
+        let a = item.analysis();
+        let item_offset = item.offset() as usize;
+        let mut glyphs = pango::GlyphString::new();
+
+        pango::shape(
+            &text[item_offset..item_offset + item.length() as usize],
+            &a,
+            &mut glyphs,
         );
+
+        let font = a.font();
+        let (ink, _) = glyphs.extents(&font);
+        top = top.min(f64::from(ink.y) / scale);
+        bottom = bottom.max(f64::from(ink.y + ink.height) / scale);
+        width += f64::from(glyphs.get_width()) / scale;
+
+        shaped.push((font, glyphs));
     }
-    if hl.underline {
-        let y = y + h + cm.underline_position;
-        cr.rectangle(x, y, w, cm.underline_thickness);
-        cr.fill();
+
+    let mut factor: f64 = 1.0;
+    if width > w {
+        factor = factor.min(w / width);
+    }
+    if -top > cm.ascent {
+        factor = factor.min(cm.ascent / -top);
+    }
+    if bottom > cm.height - cm.ascent {
+        factor = factor.min((cm.height - cm.ascent) / bottom);
+    }
+
+    cr.save();
+    cr.rectangle(x, y, w, cm.height);
+    cr.clip();
+    // Center the glyphs horizontally, and keep them on the baseline.
+    cr.translate(x + (w - width * factor) / 2.0, y + cm.ascent);
+    cr.scale(factor, factor);
+
+    let mut x_offset = 0.0;
+    for (font, mut glyphs) in shaped {
+        cr.move_to(x_offset, 0.0);
+        pangocairo::functions::show_glyph_string(&cr, &font, &mut glyphs);
+        x_offset += f64::from(glyphs.get_width()) / scale;
     }
 
     cr.restore();
 }
 
-/// Draws (inverted) cell to `cr`.
+/// Draws cell to `cr`, with the cursor's colors (`text` and `cursor`).
 pub fn cursor_cell(
     cr: &cairo::Context,
     pango_context: &pango::Context,
     cell: &Cell,
     cm: &CellMetrics,
     hl_defs: &HlDefs,
+    (text, cursor): (Color, Color),
 ) {
     let mut hl = *hl_defs.get(&cell.hl_id).unwrap();
 
-    hl.reverse = !hl.reverse;
+    hl.foreground = Some(text);
+    hl.background = Some(cursor);
+    hl.reverse = false;
 
     let x = 0.0;
     let y = 0.0;
@@ -130,198 +325,451 @@ pub fn cursor_cell(
     };
     let h = cm.height;
 
-    render_text(cr, pango_context, cm, &hl, hl_defs, &cell.text, x, y, w, h);
+    let wide = if cell.double_width {
+        vec![WideCell {
+            text: 0..cell.text.len(),
+            col: 0,
+        }]
+    } else {
+        vec![]
+    };
+
+    render_text(
+        cr,
+        pango_context,
+        cm,
+        &hl,
+        hl_defs,
+        1.0,
+        &cell.text,
+        &wide,
+        x,
+        y,
+        w,
+        h,
+    );
+}
+
+/// Renders input method's preedit `text` (with `attrs`) to a new surface.
+/// The text is underlined and a caret is drawn to `cursor` (in characters).
+pub fn preedit(
+    win: &gdk::Window,
+    pango_context: &pango::Context,
+    cm: &CellMetrics,
+    hl_defs: &HlDefs,
+    text: &str,
+    attrs: &pango::AttrList,
+    cursor: i32,
+) -> Preedit {
+    let layout = pango::Layout::new(pango_context);
+    attrs.insert_before(
+        Attribute::new_underline(pango::Underline::Single).unwrap(),
+    );
+    layout.set_attributes(Some(attrs));
+    layout.set_text(text);
+
+    let width = f64::from(layout.get_pixel_size().0).max(cm.width);
+    let surface = create_surface(
+        win,
+        cairo::Content::ColorAlpha,
+        width,
+        cm.height,
+        cm.scale,
+    );
+
+    let fg = hl_defs.default_fg;
+    let bg = hl_defs.default_bg;
+
+    let cr = cairo::Context::new(&surface);
+    cr.set_source_rgb(bg.r, bg.g, bg.b);
+    cr.paint();
+
+    cr.set_source_rgb(fg.r, fg.g, fg.b);
+    let baseline = f64::from(layout.get_baseline()) / f64::from(pango::SCALE);
+    cr.move_to(0.0, cm.ascent - baseline);
+    pangocairo::functions::show_layout(&cr, &layout);
+
+    let index = text
+        .char_indices()
+        .nth(cursor.max(0) as usize)
+        .map(|(i, _)| i)
+        .unwrap_or_else(|| text.len());
+    let pos = layout.index_to_pos(index as i32);
+    let x = f64::from(pos.x) / f64::from(pango::SCALE);
+    cr.rectangle(x.min(width - 1.0), 0.0, 1.0, cm.height);
+    cr.fill();
+
+    Preedit { surface, width }
 }
 
-/// Renders `segments` to `cr`.
+/// Renders a snippet's indicator `text` (e.g. "1/3") to a new surface, in
+/// the default colors with the foreground dimmed.
+pub fn snippet_indicator(
+    win: &gdk::Window,
+    pango_context: &pango::Context,
+    cm: &CellMetrics,
+    hl_defs: &HlDefs,
+    text: &str,
+) -> SnippetIndicator {
+    let layout = pango::Layout::new(pango_context);
+    layout.set_text(text);
+
+    // Half a cell of padding on both sides.
+    let width = f64::from(layout.get_pixel_size().0) + cm.width;
+    let surface = create_surface(
+        win,
+        cairo::Content::ColorAlpha,
+        width,
+        cm.height,
+        cm.scale,
+    );
+
+    let fg = hl_defs.default_fg;
+    let bg = hl_defs.default_bg;
+
+    let cr = cairo::Context::new(&surface);
+    cr.set_source_rgb(bg.r, bg.g, bg.b);
+    cr.paint();
+
+    cr.set_source_rgba(fg.r, fg.g, fg.b, 0.7);
+    let baseline = f64::from(layout.get_baseline()) / f64::from(pango::SCALE);
+    cr.move_to(cm.width / 2.0, cm.ascent - baseline);
+    pangocairo::functions::show_layout(&cr, &layout);
+
+    cr.set_source_rgba(fg.r, fg.g, fg.b, 0.3);
+    cr.set_line_width(1.0);
+    cr.rectangle(0.5, 0.5, width.ceil() - 1.0, cm.height.ceil() - 1.0);
+    cr.stroke();
+
+    SnippetIndicator { surface, width }
+}
+
+/// Renders `segments` with `renderer` and marks them to `damage`. Default
+/// background is rendered with `bg_opacity`.
 fn put_segments(
-    cr: &cairo::Context,
+    renderer: &mut dyn Renderer,
     pango_context: &pango::Context,
-    queue_draw_area: &mut Vec<(f64, f64, f64, f64)>,
+    damage: &mut Damage,
     cm: &CellMetrics,
     hl_defs: &HlDefs,
+    bg_opacity: f64,
     segments: Vec<Segment>,
     row: usize,
 ) {
     let cw = cm.width;
     let ch = cm.height;
 
+    let (cr, row_y) = renderer.row_context(row, cm);
+
     for seg in segments {
         let hl = hl_defs.get(&seg.hl_id).unwrap();
 
-        let x = (seg.start as f64 * cw).floor();
-        let y = (row as f64 * ch).floor();
-        let w = (seg.len as f64 * cw).ceil();
-        let h = ch.ceil();
+        let x = cm.floor(seg.start as f64 * cw);
+        let y = cm.floor(row_y);
+        let w = cm.ceil(seg.len as f64 * cw);
+        let h = cm.ceil(ch);
 
-        let text = &seg.text;
-        render_text(cr, pango_context, cm, &hl, hl_defs, &text, x, y, w, h);
+        render_text(
+            &cr,
+            pango_context,
+            cm,
+            &hl,
+            hl_defs,
+            bg_opacity,
+            &seg.text,
+            &seg.wide,
+            x,
+            y,
+            w,
+            h,
+        );
 
-        queue_draw_area.push((x, y, w, h));
+        damage.mark(row, seg.start, seg.start + seg.len);
     }
 }
 
-pub fn redraw(
+/// Renders again all the cells which highlight id passes `f`.
+pub fn redraw<F: Fn(u64) -> bool>(
     context: &mut Context,
     pango_context: &pango::Context,
     hl_defs: &HlDefs,
+    f: F,
 ) {
-    for (i, row) in context.rows.iter_mut().enumerate() {
-        let segments = row.as_segments(0, row.len);
+    put_pending(context, pango_context, hl_defs);
+
+    for (i, row) in context.rows.iter().enumerate() {
+        let segments = row
+            .as_segments(0, row.len)
+            .into_iter()
+            .filter(|seg| f(seg.hl_id))
+            .collect();
 
         put_segments(
-            &context.cairo_context,
+            context.renderer.as_mut(),
             pango_context,
-            &mut context.queue_draw_area,
+            &mut context.damage,
             &context.cell_metrics,
             hl_defs,
+            context.background_opacity,
             segments,
             i,
         );
     }
 }
 
-/// Renders `line` to `context.cairo_context`.
-pub fn put_line(
+/// Marks the rows that have cells which highlight id passes `f` to be
+/// rendered again by `redraw_stale`.
+pub fn mark_stale<F: Fn(u64) -> bool>(context: &mut Context, f: F) {
+    for (i, row) in context.rows.iter().enumerate() {
+        if row.has_hl(&f) {
+            context.stale_rows.insert(i);
+        }
+    }
+}
+
+/// Renders again (at most) `max_rows` of the rows marked by `mark_stale`.
+/// Returns the number of rows rendered.
+pub fn redraw_stale(
     context: &mut Context,
     pango_context: &pango::Context,
-    line: GridLineSegment,
     hl_defs: &HlDefs,
-) {
+    max_rows: usize,
+) -> usize {
+    if context.stale_rows.is_empty() {
+        return 0;
+    }
+
+    put_pending(context, pango_context, hl_defs);
+
+    let stale = context
+        .stale_rows
+        .iter()
+        .take(max_rows)
+        .cloned()
+        .collect::<Vec<_>>();
+    for &row in stale.iter() {
+        context.stale_rows.remove(&row);
+
+        // The grid might have been resized since the row was marked.
+        let mut segments = match context.rows.get(row) {
+            Some(cells) => cells.as_segments(0, cells.len),
+            None => continue,
+        };
+
+        // See `put_pending`.
+        segments.reverse();
+        put_segments(
+            context.renderer.as_mut(),
+            pango_context,
+            &mut context.damage,
+            &context.cell_metrics,
+            hl_defs,
+            context.background_opacity,
+            segments,
+            row,
+        );
+    }
+
+    stale.len()
+}
+
+/// Updates `context.rows` with `line`. The changed cells are rendered
+/// later by `put_pending`, so that a row that is updated multiple times
+/// before a flush is rendered only once.
+pub fn put_line(context: &mut Context, line: GridLineSegment) {
     let row = line.row as usize;
-    let mut affected_segments = context
+    let changed = context
         .rows
         .get_mut(row)
         .unwrap_or_else(|| panic!("Failed to get row {}", line.row))
         .update(line);
 
-    // NOTE(ville): I haven't noticed any cases where a character is overflowing
-    //              to the left. Probably doesn't apply to languages that goes
-    //              from right to left, instead of left to right.
-    // Rendering the segments in reversed order fixes issues when some character
-    // is overflowing to the right.
-    affected_segments.reverse();
-    put_segments(
-        &context.cairo_context,
-        pango_context,
-        &mut context.queue_draw_area,
-        &context.cell_metrics,
-        hl_defs,
-        affected_segments,
-        row,
-    );
+    if let Some((start, end)) = changed {
+        context
+            .pending_rows
+            .entry(row)
+            .and_modify(|range| {
+                range.0 = range.0.min(start);
+                range.1 = range.1.max(end);
+            })
+            .or_insert((start, end));
+    }
 }
 
-/// Clears whole `da` with `hl_defs.default_bg`.
-pub fn clear(da: &DrawingArea, ctx: &mut Context, hl_defs: &HlDefs) {
-    let cr = &ctx.cairo_context;
-    let w = da.get_allocated_width();
-    let h = da.get_allocated_height();
-    let bg = &hl_defs.default_bg;
+/// Renders the cells that `put_line` has changed since the last call with
+/// `context.renderer`. Adjacent cells with the same highlight are rendered
+/// together, as one segment.
+pub fn put_pending(
+    context: &mut Context,
+    pango_context: &pango::Context,
+    hl_defs: &HlDefs,
+) {
+    // Rows that are being rasterized off the main thread are rendered here
+    // instead, so that they're up to date.
+    if let Some(ref mut async_rows) = context.async_rows {
+        for (row, range) in async_rows.cancel() {
+            context
+                .pending_rows
+                .entry(row)
+                .and_modify(|pending| {
+                    pending.0 = pending.0.min(range.0);
+                    pending.1 = pending.1.max(range.1);
+                })
+                .or_insert(range);
+        }
+    }
+
+    let pending = std::mem::take(&mut context.pending_rows);
+    for (row, (start, end)) in pending {
+        let mut segments = context.rows[row].as_segments(start, end);
+
+        // NOTE(ville): I haven't noticed any cases where a character is overflowing
+        //              to the left. Probably doesn't apply to languages that goes
+        //              from right to left, instead of left to right.
+        // Rendering the segments in reversed order fixes issues when some character
+        // is overflowing to the right.
+        segments.reverse();
+        put_segments(
+            context.renderer.as_mut(),
+            pango_context,
+            &mut context.damage,
+            &context.cell_metrics,
+            hl_defs,
+            context.background_opacity,
+            segments,
+            row,
+        );
+    }
+}
+
+/// Sends the cells that `put_line` has changed to be rasterized off the
+/// main thread, if the context has `async_rows`. Otherwise renders them
+/// right away.
+pub fn submit_pending(
+    context: &mut Context,
+    pango_context: &pango::Context,
+    hl_defs: &HlDefs,
+) {
+    let async_rows = match context.async_rows {
+        Some(ref mut async_rows) => async_rows,
+        None => return put_pending(context, pango_context, hl_defs),
+    };
+
+    let text_options = TextOptions::from_context(pango_context);
+    let pending = std::mem::take(&mut context.pending_rows);
+    for (row, range) in pending {
+        async_rows.submit(
+            row,
+            range,
+            &context.rows[row],
+            &context.cell_metrics,
+            hl_defs,
+            context.background_opacity,
+            text_options,
+        );
+    }
+}
+
+/// Copies rasterized cells to `context.renderer`. Returns the area that
+/// changed, or `None` if `raster` is outdated.
+pub fn put_raster(
+    context: &mut Context,
+    raster: Raster,
+) -> Option<(f64, f64, f64, f64)> {
+    if !context.async_rows.as_mut()?.finish(&raster) {
+        return None;
+    }
+
+    let cm = &context.cell_metrics;
+    let (x, w, h) = (raster.x, raster.width, raster.height);
+    let y = cm.floor(raster.row as f64 * cm.height);
+
+    let (cr, row_y) = context.renderer.row_context(raster.row, cm);
+    let row_y = cm.floor(row_y);
+    let (surface, scale) = raster.into_surface()?;
 
     cr.save();
-    cr.set_source_rgb(bg.r, bg.g, bg.b);
-    cr.rectangle(0.0, 0.0, f64::from(w), f64::from(h));
+    cr.translate(x, row_y);
+    cr.scale(1.0 / scale, 1.0 / scale);
+    // Replace the old contents, instead of blending on top of them.
+    cr.set_operator(cairo::Operator::Source);
+    cr.set_source_surface(&surface, 0.0, 0.0);
+    cr.rectangle(0.0, 0.0, w * scale, h * scale);
     cr.fill();
     cr.restore();
 
-    ctx.queue_draw_area
-        .push((0.0, 0.0, f64::from(w), f64::from(h)));
+    Some((x, y, w, h))
 }
 
-/// Scrolls contents in `ctx.cairo_context` and `ctx.rows`, based on `reg`.
+/// Clears the whole grid with `hl_defs.default_bg`.
+pub fn clear(ctx: &mut Context, hl_defs: &HlDefs) {
+    ctx.renderer.clear(&hl_defs.default_bg);
+    ctx.damage.mark_all();
+}
+
+/// Scrolls contents in `ctx.renderer` and `ctx.rows`, based on `reg`.
 pub fn scroll(ctx: &mut Context, hl_defs: &HlDefs, reg: [u64; 4], count: i64) {
-    let cr = &ctx.cairo_context;
     let cm = &ctx.cell_metrics;
-    let bg = &hl_defs.default_bg;
-
-    let s = cr.get_target();
 
     let top = reg[0];
     let bot = reg[1];
     let left = reg[2];
     let right = reg[3];
 
-    let (src_top, src_bot, dst_top, dst_bot, clr_top, clr_bot) = if count > 0 {
-        let (src_top, src_bot) = ((top as i64 + count) as f64, bot as f64);
-        let (dst_top, dst_bot) = (top as f64, (bot as i64 - count) as f64);
-        (src_top, src_bot, dst_top, dst_bot, dst_bot, src_bot)
-    } else {
-        let (src_top, src_bot) = (top as f64, (bot as i64 + count) as f64);
-        let (dst_top, dst_bot) = ((top as i64 - count) as f64, bot as f64);
-        (src_top, src_bot, dst_top, dst_bot, src_top, dst_top)
-    };
+    let (src, dst, clr) = scroll_ranges(reg, count);
 
     // Modify the rows stored data of the rows.
-    let mut src = vec![];
-    for i in src_top as usize..src_bot as usize {
+    let mut parts = vec![];
+    for i in src.0..src.1 {
         let row = ctx.rows.get(i).unwrap().clone();
         let part = row.copy_range(left as usize, right as usize).clone();
-        src.push(part);
+        parts.push(part);
     }
-    src.reverse();
+    parts.reverse();
 
-    for i in dst_top as usize..dst_bot as usize {
+    for i in dst.0..dst.1 {
         ctx.rows
             .get_mut(i)
             .unwrap()
-            .insert_at(left as usize, src.pop().unwrap());
+            .insert_at(left as usize, parts.pop().unwrap());
     }
 
-    for i in clr_top as usize..clr_bot as usize {
+    for i in clr.0..clr.1 {
         ctx.rows
             .get_mut(i)
             .unwrap()
             .clear_range(left as usize, right as usize);
     }
 
-    // Draw move the scrolled part on the cairo surface.
-    cr.save();
-
-    // Create pattern which we can then "safely" draw to the surface. On X11, the pattern part was
-    // not needed but on wayland it is - I suppose it has something to do with the underlaying
-    // backbuffer.
-    cr.push_group();
-    let (_, y) = get_coords(cm.height, cm.width, dst_top - src_top, 0.0);
-    cr.set_source_surface(&s, 0.0, y);
-    cr.set_operator(cairo::Operator::Source);
-    let (x1, y1, x2, y2) = get_rect(
-        cm.height,
-        cm.width,
-        dst_top,
-        dst_bot,
-        left as f64,
-        right as f64,
-    );
-    let w = x2 - x1;
-    let h = y2 - y1;
-    cr.rectangle(x1, y1, w, h);
-    cr.fill();
-
-    // Draw the parttern.
-    cr.pop_group_to_source();
-    cr.set_operator(cairo::Operator::Source);
-    cr.rectangle(x1, y1, w, h);
-    cr.fill();
-    ctx.queue_draw_area.push((x1, y1, w, h));
+    ctx.renderer.scroll(cm, reg, count, &hl_defs.default_bg);
+
+    // The stale rows moved too. The region might be only some of the
+    // columns, so keep the whole region stale instead of moving the rows.
+    if ctx
+        .stale_rows
+        .range(top as usize..bot as usize)
+        .next()
+        .is_some()
+    {
+        ctx.stale_rows.extend(top as usize..bot as usize);
+    }
 
-    // Clear the area that is left "dirty".
-    let (x1, y1, x2, y2) = get_rect(
-        cm.height,
-        cm.width,
-        clr_top,
-        clr_bot,
-        left as f64,
-        right as f64,
+    // Images move with the text.
+    ctx.images = std::mem::take(&mut ctx.images)
+        .into_iter()
+        .filter_map(|mut image| {
+            image.pos.0 =
+                images::scroll_row(image.pos, image.size, reg, count)?;
+            Some(image)
+        })
+        .collect();
+
+    ctx.damage.mark_region(
+        top as usize,
+        bot as usize,
+        left as usize,
+        right as usize,
     );
-    let w = x2 - x1;
-    let h = y2 - y1;
-    cr.rectangle(x1, y1, x2 - x1, y2 - y1);
-    cr.set_source_rgb(bg.r, bg.g, bg.b);
-    cr.fill();
-    ctx.queue_draw_area.push((x1, y1, w, h));
-
-    cr.restore();
 }
 
 pub fn get_rect(
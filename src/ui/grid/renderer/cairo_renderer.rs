@@ -0,0 +1,220 @@
+use gtk::prelude::*;
+use gtk::DrawingArea;
+
+use crate::ui::color::Color;
+use crate::ui::grid::context::{create_surface, CellMetrics};
+use crate::ui::grid::render::{get_coords, get_rect};
+use crate::ui::grid::renderer::{
+    paint_layers, scroll_ranges, Layer, Renderer, OVERLAY_COLOR,
+};
+
+/// Renders the grid to a single cairo surface, which is painted to a
+/// drawing area.
+pub struct CairoRenderer {
+    da: DrawingArea,
+    /// Our cairo context, that is evetually drawn to the screen.
+    cairo_context: cairo::Context,
+
+    /// Size of the grid that the current surface was created for.
+    cols: usize,
+    rows: usize,
+
+    /// Opacity of the background.
+    bg_alpha: f64,
+}
+
+impl CairoRenderer {
+    pub fn new() -> Self {
+        // Placeholder surface, the actual surface is created on resize.
+        let surface =
+            cairo::ImageSurface::create(cairo::Format::Rgb24, 0, 0).unwrap();
+
+        CairoRenderer {
+            da: DrawingArea::new(),
+            cairo_context: cairo::Context::new(&surface),
+            cols: 0,
+            rows: 0,
+            bg_alpha: 1.0,
+        }
+    }
+}
+
+impl Renderer for CairoRenderer {
+    fn widget(&self) -> gtk::Widget {
+        self.da.clone().upcast()
+    }
+
+    fn connect_draw(&self, f: Box<dyn Fn(Option<&cairo::Context>)>) {
+        self.da.connect_draw(move |_, cr| {
+            f(Some(cr));
+            Inhibit(false)
+        });
+    }
+
+    fn resize(
+        &mut self,
+        win: &gdk::Window,
+        cm: &CellMetrics,
+        cols: usize,
+        rows: usize,
+        bg: &Color,
+    ) {
+        let w = cm.width * cols as f64;
+        let h = cm.height * rows as f64;
+        // Only use alpha when needed, because text can't be rendered with
+        // subpixel antialiasing on a surface with alpha.
+        let content = if self.bg_alpha < 1.0 {
+            cairo::Content::ColorAlpha
+        } else {
+            cairo::Content::Color
+        };
+        let surface = create_surface(win, content, w, h, cm.scale);
+        let ctx = cairo::Context::new(&surface);
+
+        // Fill the context with default bg color.
+        ctx.save();
+        ctx.set_operator(cairo::Operator::Source);
+        ctx.set_source_rgba(bg.r, bg.g, bg.b, self.bg_alpha);
+        ctx.paint();
+        ctx.restore();
+
+        let s = self.cairo_context.get_target();
+        self.cairo_context.save();
+        ctx.set_source_surface(&s, 0.0, 0.0);
+        ctx.set_operator(cairo::Operator::Source);
+        // Make sure we only paint the area that _was_ visible before this update
+        // so we don't undo the bg color paint we did earlier. Note that we're
+        // calculating the used area based on the current cell metrics. This is
+        // becuase if font changes that might reduce the area we "have available".
+        // Otherwise, when changing to smaller font, we might draw our "old" surface
+        // on a area that wont be cleared by nvim (e.g. over "fresh" whitespace).
+        ctx.rectangle(
+            0.0,
+            0.0,
+            cm.width * self.cols as f64,
+            cm.height * self.rows as f64,
+        );
+        ctx.fill();
+        self.cairo_context.restore();
+
+        self.cairo_context = ctx;
+        self.cols = cols;
+        self.rows = rows;
+    }
+
+    fn row_context(
+        &mut self,
+        row: usize,
+        cm: &CellMetrics,
+    ) -> (cairo::Context, f64) {
+        (self.cairo_context.clone(), row as f64 * cm.height)
+    }
+
+    fn clear(&mut self, bg: &Color) {
+        let cr = &self.cairo_context;
+
+        cr.save();
+        cr.set_operator(cairo::Operator::Source);
+        cr.set_source_rgba(bg.r, bg.g, bg.b, self.bg_alpha);
+        cr.paint();
+        cr.restore();
+    }
+
+    fn set_background_opacity(&mut self, opacity: f64) {
+        self.bg_alpha = opacity;
+    }
+
+    fn scroll(
+        &mut self,
+        cm: &CellMetrics,
+        reg: [u64; 4],
+        count: i64,
+        bg: &Color,
+    ) {
+        let cr = &self.cairo_context;
+        let s = cr.get_target();
+
+        let left = reg[2] as f64;
+        let right = reg[3] as f64;
+        let (src, dst, clr) = scroll_ranges(reg, count);
+
+        // Draw move the scrolled part on the cairo surface.
+        cr.save();
+
+        // Create pattern which we can then "safely" draw to the surface. On X11, the pattern part was
+        // not needed but on wayland it is - I suppose it has something to do with the underlaying
+        // backbuffer.
+        cr.push_group();
+        let (_, y) =
+            get_coords(cm.height, cm.width, dst.0 as f64 - src.0 as f64, 0.0);
+        cr.set_source_surface(&s, 0.0, y);
+        cr.set_operator(cairo::Operator::Source);
+        let (x1, y1, x2, y2) = get_rect(
+            cm.height,
+            cm.width,
+            dst.0 as f64,
+            dst.1 as f64,
+            left,
+            right,
+        );
+        let w = x2 - x1;
+        let h = y2 - y1;
+        cr.rectangle(x1, y1, w, h);
+        cr.fill();
+
+        // Draw the parttern.
+        cr.pop_group_to_source();
+        cr.set_operator(cairo::Operator::Source);
+        cr.rectangle(x1, y1, w, h);
+        cr.fill();
+
+        // Clear the area that is left "dirty".
+        let (x1, y1, x2, y2) = get_rect(
+            cm.height,
+            cm.width,
+            clr.0 as f64,
+            clr.1 as f64,
+            left,
+            right,
+        );
+        cr.rectangle(x1, y1, x2 - x1, y2 - y1);
+        cr.set_source_rgba(bg.r, bg.g, bg.b, self.bg_alpha);
+        cr.fill();
+
+        cr.restore();
+    }
+
+    fn draw(
+        &mut self,
+        cr: Option<&cairo::Context>,
+        layers: Vec<Layer>,
+        overlay: &[(f64, f64, f64, f64)],
+    ) {
+        let cr = cr.expect("cairo renderer needs a cairo context to draw");
+
+        self.snapshot(cr);
+        paint_layers(cr, layers);
+
+        if !overlay.is_empty() {
+            let (r, g, b, a) = OVERLAY_COLOR;
+
+            cr.save();
+            cr.set_source_rgba(r, g, b, a);
+            for (x, y, w, h) in overlay {
+                cr.rectangle(*x, *y, *w, *h);
+            }
+            cr.fill();
+            cr.restore();
+        }
+    }
+
+    fn snapshot(&self, cr: &cairo::Context) {
+        let surface = self.cairo_context.get_target();
+        surface.flush();
+
+        cr.save();
+        cr.set_source_surface(&surface, 0.0, 0.0);
+        cr.paint();
+        cr.restore();
+    }
+}
@@ -0,0 +1,729 @@
+use std::cell::RefCell;
+use std::ffi::{c_void, CString};
+use std::ptr;
+use std::rc::Rc;
+use std::sync::Once;
+
+use gdk::WindowExt;
+use gl::types::{GLchar, GLenum, GLint, GLsizeiptr, GLuint};
+use gtk::prelude::*;
+use log::error;
+use shared_library::dynamic_library::DynamicLibrary;
+
+use crate::ui::color::Color;
+use crate::ui::grid::context::CellMetrics;
+use crate::ui::grid::renderer::{
+    scroll_ranges, Layer, Renderer, OVERLAY_COLOR,
+};
+
+const VERTEX_SHADER: &str = "
+#version 150 core
+
+in vec2 position;
+in vec2 tex_coord;
+
+out vec2 v_tex_coord;
+
+uniform vec2 viewport;
+
+void main() {
+    v_tex_coord = tex_coord;
+    vec2 pos = position / viewport * 2.0 - 1.0;
+    gl_Position = vec4(pos.x, -pos.y, 0.0, 1.0);
+}
+";
+
+const FRAGMENT_SHADER: &str = "
+#version 150 core
+
+in vec2 v_tex_coord;
+
+out vec4 color;
+
+uniform sampler2D tex;
+uniform bool opaque;
+
+void main() {
+    vec4 c = texture(tex, v_tex_coord);
+    color = opaque ? vec4(c.rgb, 1.0) : c;
+}
+";
+
+/// Loads the GL functions. Gtk uses libepoxy for GL, which exposes the GL
+/// functions as `epoxy_<name>` function pointers.
+fn load_gl() {
+    static LOAD: Once = Once::new();
+    LOAD.call_once(|| {
+        let lib = DynamicLibrary::open(None)
+            .expect("Failed to open the program's own symbols");
+        gl::load_with(|name| unsafe {
+            lib.symbol::<*const c_void>(&format!("epoxy_{}", name))
+                .map(|ptr| *ptr)
+                .unwrap_or(ptr::null())
+        });
+    });
+}
+
+/// GL resources. These are bound to the GL context of the GLArea, so they
+/// need to be recreated if the widget is realized again.
+struct GlState {
+    program: GLuint,
+    vao: GLuint,
+    vbo: GLuint,
+
+    grid_texture: GLuint,
+    /// Size of the grid texture in pixels.
+    grid_texture_size: (i32, i32),
+    layer_texture: GLuint,
+
+    viewport_loc: GLint,
+    opaque_loc: GLint,
+}
+
+impl GlState {
+    fn new() -> Result<Self, String> {
+        load_gl();
+
+        unsafe {
+            let vs = compile_shader(gl::VERTEX_SHADER, VERTEX_SHADER)?;
+            let fs = compile_shader(gl::FRAGMENT_SHADER, FRAGMENT_SHADER)?;
+
+            let program = gl::CreateProgram();
+            gl::AttachShader(program, vs);
+            gl::AttachShader(program, fs);
+            bind_attrib(program, 0, "position");
+            bind_attrib(program, 1, "tex_coord");
+            gl::LinkProgram(program);
+            gl::DeleteShader(vs);
+            gl::DeleteShader(fs);
+
+            let mut status = 0;
+            gl::GetProgramiv(program, gl::LINK_STATUS, &mut status);
+            if status == 0 {
+                let err = program_log(program);
+                gl::DeleteProgram(program);
+                return Err(format!("Failed to link program: {}", err));
+            }
+
+            let mut vao = 0;
+            gl::GenVertexArrays(1, &mut vao);
+            gl::BindVertexArray(vao);
+
+            let mut vbo = 0;
+            gl::GenBuffers(1, &mut vbo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+
+            let stride = 4 * std::mem::size_of::<f32>() as i32;
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(
+                0,
+                2,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                ptr::null(),
+            );
+            gl::EnableVertexAttribArray(1);
+            gl::VertexAttribPointer(
+                1,
+                2,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (2 * std::mem::size_of::<f32>()) as *const c_void,
+            );
+
+            let mut textures = [0; 2];
+            gl::GenTextures(2, textures.as_mut_ptr());
+            for texture in textures.iter() {
+                gl::BindTexture(gl::TEXTURE_2D, *texture);
+                for param in &[gl::TEXTURE_MIN_FILTER, gl::TEXTURE_MAG_FILTER] {
+                    gl::TexParameteri(
+                        gl::TEXTURE_2D,
+                        *param,
+                        gl::NEAREST as i32,
+                    );
+                }
+                for param in &[gl::TEXTURE_WRAP_S, gl::TEXTURE_WRAP_T] {
+                    gl::TexParameteri(
+                        gl::TEXTURE_2D,
+                        *param,
+                        gl::CLAMP_TO_EDGE as i32,
+                    );
+                }
+            }
+
+            let viewport = CString::new("viewport").unwrap();
+            let opaque = CString::new("opaque").unwrap();
+
+            Ok(GlState {
+                program,
+                vao,
+                vbo,
+                grid_texture: textures[0],
+                grid_texture_size: (0, 0),
+                layer_texture: textures[1],
+                viewport_loc: gl::GetUniformLocation(
+                    program,
+                    viewport.as_ptr(),
+                ),
+                opaque_loc: gl::GetUniformLocation(program, opaque.as_ptr()),
+            })
+        }
+    }
+
+    /// Draws textured quads from `vertices` (x, y, u, v) with `texture`.
+    unsafe fn draw_quads(
+        &self,
+        texture: GLuint,
+        vertices: &[f32],
+        opaque: bool,
+    ) {
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::Uniform1i(self.opaque_loc, opaque as i32);
+        gl::BufferData(
+            gl::ARRAY_BUFFER,
+            (vertices.len() * std::mem::size_of::<f32>()) as GLsizeiptr,
+            vertices.as_ptr() as *const c_void,
+            gl::STREAM_DRAW,
+        );
+        gl::DrawArrays(gl::TRIANGLES, 0, vertices.len() as i32 / 4);
+    }
+}
+
+impl Drop for GlState {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(
+                2,
+                [self.grid_texture, self.layer_texture].as_ptr(),
+            );
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteProgram(self.program);
+        }
+    }
+}
+
+unsafe fn bind_attrib(program: GLuint, index: GLuint, name: &str) {
+    let name = CString::new(name).unwrap();
+    gl::BindAttribLocation(program, index, name.as_ptr());
+}
+
+unsafe fn compile_shader(kind: GLenum, src: &str) -> Result<GLuint, String> {
+    let shader = gl::CreateShader(kind);
+    let src = CString::new(src).unwrap();
+    gl::ShaderSource(shader, 1, &src.as_ptr(), ptr::null());
+    gl::CompileShader(shader);
+
+    let mut status = 0;
+    gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
+    if status == 0 {
+        let mut len = 0;
+        gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut len);
+        let mut buf = vec![0u8; len.max(1) as usize];
+        gl::GetShaderInfoLog(
+            shader,
+            len,
+            ptr::null_mut(),
+            buf.as_mut_ptr() as *mut GLchar,
+        );
+        gl::DeleteShader(shader);
+        return Err(format!(
+            "Failed to compile shader: {}",
+            String::from_utf8_lossy(&buf)
+        ));
+    }
+
+    Ok(shader)
+}
+
+unsafe fn program_log(program: GLuint) -> String {
+    let mut len = 0;
+    gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut len);
+    let mut buf = vec![0u8; len.max(1) as usize];
+    gl::GetProgramInfoLog(
+        program,
+        len,
+        ptr::null_mut(),
+        buf.as_mut_ptr() as *mut GLchar,
+    );
+    String::from_utf8_lossy(&buf).to_string()
+}
+
+/// Uploads `surface` to `texture`'s area at `y`. If `size` is given, the
+/// texture is (re)allocated to that size first.
+unsafe fn upload_surface(
+    texture: GLuint,
+    surface: &cairo::ImageSurface,
+    y: i32,
+    size: Option<(i32, i32)>,
+) {
+    gl::BindTexture(gl::TEXTURE_2D, texture);
+
+    if let Some((w, h)) = size {
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            gl::RGBA8 as i32,
+            w,
+            h,
+            0,
+            gl::BGRA,
+            gl::UNSIGNED_INT_8_8_8_8_REV,
+            ptr::null(),
+        );
+    }
+
+    let w = surface.get_width();
+    let h = surface.get_height();
+    gl::PixelStorei(gl::UNPACK_ROW_LENGTH, surface.get_stride() / 4);
+    let res = surface.with_data(|data| {
+        // Cairo's pixels are native endian 32bit ARGB values.
+        gl::TexSubImage2D(
+            gl::TEXTURE_2D,
+            0,
+            0,
+            y,
+            w,
+            h,
+            gl::BGRA,
+            gl::UNSIGNED_INT_8_8_8_8_REV,
+            data.as_ptr() as *const c_void,
+        );
+    });
+    gl::PixelStorei(gl::UNPACK_ROW_LENGTH, 0);
+
+    if let Err(err) = res {
+        error!("Failed to upload surface to the GPU: {}", err);
+    }
+}
+
+/// Pushes a quad (two triangles) to `vertices`.
+fn push_quad(
+    vertices: &mut Vec<f32>,
+    x1: f32,
+    y1: f32,
+    x2: f32,
+    y2: f32,
+    v1: f32,
+    v2: f32,
+) {
+    vertices.extend_from_slice(&[
+        x1, y1, 0.0, v1, x2, y1, 1.0, v1, x1, y2, 0.0, v2, //
+        x2, y1, 1.0, v1, x2, y2, 1.0, v2, x1, y2, 0.0, v2,
+    ]);
+}
+
+/// Renders each row of the grid to its own cairo surface. The rows are then
+/// uploaded to a texture and drawn with OpenGL. Scrolling the whole width
+/// of the grid only changes which texture row is drawn where, so no pixels
+/// need to be copied.
+pub struct GlRenderer {
+    area: gtk::GLArea,
+    state: Rc<RefCell<Option<GlState>>>,
+
+    /// Surface for each of the grid's rows. The surfaces are not in order,
+    /// see `slots`.
+    surfaces: Vec<cairo::ImageSurface>,
+    /// Index to `surfaces` for each row.
+    slots: Vec<usize>,
+    /// Surfaces that have changed since they were last uploaded.
+    dirty: Vec<bool>,
+
+    /// Column count of the grid.
+    cols: usize,
+    /// Size of a row.
+    row_width: f64,
+    row_height: f64,
+    /// Device scale (see `CellMetrics::scale`). The surfaces' sizes are
+    /// multiplied by this.
+    scale: f64,
+
+    bg: Color,
+}
+
+impl GlRenderer {
+    pub fn new() -> Self {
+        let area = gtk::GLArea::new();
+        let state: Rc<RefCell<Option<GlState>>> = Rc::new(RefCell::new(None));
+
+        // GL resources are bound to the widget's GL context which goes
+        // away on unrealize.
+        area.connect_unrealize(clone!(state => move |area| {
+            area.make_current();
+            state.borrow_mut().take();
+        }));
+
+        GlRenderer {
+            area,
+            state,
+            surfaces: vec![],
+            slots: vec![],
+            dirty: vec![],
+            cols: 0,
+            row_width: 0.0,
+            row_height: 0.0,
+            scale: 1.0,
+            bg: Color::default(),
+        }
+    }
+
+    fn create_surface(&self, bg: &Color) -> cairo::ImageSurface {
+        let surface = cairo::ImageSurface::create(
+            cairo::Format::Rgb24,
+            (self.row_width * self.scale).ceil() as i32,
+            (self.row_height * self.scale).ceil() as i32,
+        )
+        .unwrap();
+
+        let cr = cairo::Context::new(&surface);
+        cr.set_source_rgb(bg.r, bg.g, bg.b);
+        cr.paint();
+
+        surface
+    }
+
+    /// Returns a cairo context for surface `slot`, scaled so that it can be
+    /// drawn to with unscaled coordinates.
+    fn context(&self, slot: usize) -> cairo::Context {
+        let cr = cairo::Context::new(&self.surfaces[slot]);
+        cr.scale(self.scale, self.scale);
+        cr
+    }
+
+    /// Fills the columns from `x1` to `x2` of `row` with `bg`.
+    fn fill_row(&mut self, row: usize, x1: f64, x2: f64, bg: &Color) {
+        let slot = self.slots[row];
+        let cr = self.context(slot);
+        cr.rectangle(x1, 0.0, x2 - x1, self.row_height);
+        cr.set_source_rgb(bg.r, bg.g, bg.b);
+        cr.fill();
+        self.dirty[slot] = true;
+    }
+}
+
+impl Renderer for GlRenderer {
+    fn widget(&self) -> gtk::Widget {
+        self.area.clone().upcast()
+    }
+
+    fn connect_draw(&self, f: Box<dyn Fn(Option<&cairo::Context>)>) {
+        self.area.connect_render(move |_, _| {
+            f(None);
+            Inhibit(true)
+        });
+    }
+
+    fn resize(
+        &mut self,
+        win: &gdk::Window,
+        cm: &CellMetrics,
+        cols: usize,
+        rows: usize,
+        bg: &Color,
+    ) {
+        let prev_cols = self.cols as f64;
+        let prev_surfaces = std::mem::replace(&mut self.surfaces, vec![]);
+        let prev_slots = std::mem::replace(&mut self.slots, vec![]);
+
+        self.cols = cols;
+        self.row_width = cm.width * cols as f64;
+        self.row_height = cm.height;
+        self.scale = cm.scale;
+        self.bg = *bg;
+
+        for row in 0..rows {
+            let surface = self.create_surface(bg);
+
+            // Keep the old contents. Like in the cairo renderer, only copy
+            // the area that was visible before so we don't draw old
+            // contents on top of "fresh" whitespace if the font changed.
+            if let Some(prev) = prev_slots.get(row) {
+                let scale = self.scale;
+                let cr = cairo::Context::new(&surface);
+                cr.set_source_surface(&prev_surfaces[*prev], 0.0, 0.0);
+                cr.set_operator(cairo::Operator::Source);
+                cr.rectangle(
+                    0.0,
+                    0.0,
+                    cm.width * prev_cols * scale,
+                    cm.height * scale,
+                );
+                cr.fill();
+            }
+
+            self.surfaces.push(surface);
+        }
+
+        self.slots = (0..rows).collect();
+        self.dirty = vec![true; rows];
+    }
+
+    fn row_context(
+        &mut self,
+        row: usize,
+        _cm: &CellMetrics,
+    ) -> (cairo::Context, f64) {
+        let slot = self.slots[row];
+        self.dirty[slot] = true;
+
+        (self.context(slot), 0.0)
+    }
+
+    fn clear(&mut self, bg: &Color) {
+        self.bg = *bg;
+
+        let width = self.row_width;
+        for row in 0..self.slots.len() {
+            self.fill_row(row, 0.0, width, bg);
+        }
+    }
+
+    fn scroll(
+        &mut self,
+        cm: &CellMetrics,
+        reg: [u64; 4],
+        count: i64,
+        bg: &Color,
+    ) {
+        let (src, dst, clr) = scroll_ranges(reg, count);
+        let x1 = reg[2] as f64 * cm.width;
+        let x2 = reg[3] as f64 * cm.width;
+
+        if x2 >= self.row_width {
+            // Scrolling the whole width, just move the rows around. The
+            // slots of the rows that are scrolled out of view are reused
+            // for the rows that need to be cleared.
+            let mut moved: Vec<usize> = self.slots[src.0..src.1].to_vec();
+            let mut freed: Vec<usize> = self.slots[clr.0..clr.1]
+                .iter()
+                .chain(self.slots[dst.0..dst.1].iter())
+                .filter(|slot| !moved.contains(slot))
+                .cloned()
+                .collect();
+            moved.reverse();
+            freed.reverse();
+
+            for row in dst.0..dst.1 {
+                self.slots[row] = moved.pop().unwrap();
+            }
+            for row in clr.0..clr.1 {
+                self.slots[row] = freed.pop().unwrap();
+            }
+        } else {
+            let rows: Vec<usize> = if count > 0 {
+                (dst.0..dst.1).collect()
+            } else {
+                (dst.0..dst.1).rev().collect()
+            };
+
+            for row in rows {
+                let from = (row as i64 + count) as usize;
+                let (src_slot, dst_slot) = (self.slots[from], self.slots[row]);
+
+                // Copy in device pixels, so no scaling here.
+                let scale = self.scale;
+                let cr = cairo::Context::new(&self.surfaces[dst_slot]);
+                cr.set_source_surface(&self.surfaces[src_slot], 0.0, 0.0);
+                cr.set_operator(cairo::Operator::Source);
+                cr.rectangle(
+                    x1 * scale,
+                    0.0,
+                    (x2 - x1) * scale,
+                    self.row_height * scale,
+                );
+                cr.fill();
+                self.dirty[dst_slot] = true;
+            }
+        }
+
+        for row in clr.0..clr.1 {
+            self.fill_row(row, x1, x2, bg);
+        }
+    }
+
+    fn draw(
+        &mut self,
+        _cr: Option<&cairo::Context>,
+        layers: Vec<Layer>,
+        overlay: &[(f64, f64, f64, f64)],
+    ) {
+        let mut state = self.state.borrow_mut();
+        if state.is_none() {
+            match GlState::new() {
+                Ok(s) => {
+                    *state = Some(s);
+                    self.dirty.iter_mut().for_each(|d| *d = true);
+                }
+                Err(err) => {
+                    error!("Failed to initialize GL renderer: {}", err);
+                    return;
+                }
+            }
+        }
+        let state = state.as_mut().unwrap();
+
+        let alloc = self.area.get_allocation();
+        let scale = self.scale;
+        let row_h = (self.row_height * scale).ceil() as i32;
+        let tex_size = (
+            (self.row_width * scale).ceil() as i32,
+            row_h * self.surfaces.len() as i32,
+        );
+
+        unsafe {
+            // The framebuffer is in gtk's (integer) scale, which the rows'
+            // device scale might differ from.
+            let fb_scale = self.area.get_scale_factor();
+            gl::Viewport(0, 0, alloc.width * fb_scale, alloc.height * fb_scale);
+            gl::ClearColor(
+                self.bg.r as f32,
+                self.bg.g as f32,
+                self.bg.b as f32,
+                1.0,
+            );
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+
+            gl::UseProgram(state.program);
+            gl::BindVertexArray(state.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, state.vbo);
+            gl::Uniform2f(
+                state.viewport_loc,
+                alloc.width as f32,
+                alloc.height as f32,
+            );
+
+            // Upload the changed rows. If the size of the grid changed,
+            // reallocate the texture.
+            let mut realloc = if state.grid_texture_size != tex_size {
+                state.grid_texture_size = tex_size;
+                self.dirty.iter_mut().for_each(|d| *d = true);
+                Some(tex_size)
+            } else {
+                None
+            };
+            for (i, dirty) in self.dirty.iter_mut().enumerate() {
+                if *dirty {
+                    upload_surface(
+                        state.grid_texture,
+                        &self.surfaces[i],
+                        i as i32 * row_h,
+                        realloc.take(),
+                    );
+                    *dirty = false;
+                }
+            }
+
+            let mut vertices = Vec::with_capacity(self.slots.len() * 24);
+            let tex_h = tex_size.1.max(1) as f32;
+            for (row, slot) in self.slots.iter().enumerate() {
+                let y = row as f32 * self.row_height as f32;
+                let v = (*slot as i32 * row_h) as f32 / tex_h;
+                push_quad(
+                    &mut vertices,
+                    0.0,
+                    y,
+                    self.row_width as f32,
+                    y + self.row_height as f32,
+                    v,
+                    v + row_h as f32 / tex_h,
+                );
+            }
+            state.draw_quads(state.grid_texture, &vertices, true);
+
+            for layer in layers {
+                let (x, y, w, h) = layer.rect;
+
+                let surface = cairo::ImageSurface::create(
+                    cairo::Format::ARgb32,
+                    (w * scale).ceil().max(1.0) as i32,
+                    (h * scale).ceil().max(1.0) as i32,
+                )
+                .unwrap();
+                {
+                    let cr = cairo::Context::new(&surface);
+                    cr.scale(scale, scale);
+                    cr.set_source_surface(&layer.surface, 0.0, 0.0);
+                    cr.set_operator(cairo::Operator::Source);
+                    cr.paint();
+                }
+
+                upload_surface(
+                    state.layer_texture,
+                    &surface,
+                    0,
+                    Some((surface.get_width(), surface.get_height())),
+                );
+
+                let mut vertices = vec![];
+                push_quad(
+                    &mut vertices,
+                    x as f32,
+                    y as f32,
+                    (x + w) as f32,
+                    (y + h) as f32,
+                    0.0,
+                    1.0,
+                );
+
+                // Cairo uses premultiplied alpha.
+                gl::Enable(gl::BLEND);
+                gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+                state.draw_quads(state.layer_texture, &vertices, false);
+                gl::Disable(gl::BLEND);
+            }
+
+            if !overlay.is_empty() {
+                // Stretch a single pixel of the overlay color over the
+                // overlay areas.
+                let (r, g, b, a) = OVERLAY_COLOR;
+                let surface =
+                    cairo::ImageSurface::create(cairo::Format::ARgb32, 1, 1)
+                        .unwrap();
+                {
+                    let cr = cairo::Context::new(&surface);
+                    cr.set_source_rgba(r, g, b, a);
+                    cr.set_operator(cairo::Operator::Source);
+                    cr.paint();
+                }
+                upload_surface(state.layer_texture, &surface, 0, Some((1, 1)));
+
+                let mut vertices = Vec::with_capacity(overlay.len() * 24);
+                for (x, y, w, h) in overlay {
+                    push_quad(
+                        &mut vertices,
+                        *x as f32,
+                        *y as f32,
+                        (x + w) as f32,
+                        (y + h) as f32,
+                        0.0,
+                        1.0,
+                    );
+                }
+
+                gl::Enable(gl::BLEND);
+                gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+                state.draw_quads(state.layer_texture, &vertices, false);
+                gl::Disable(gl::BLEND);
+            }
+
+            gl::BindVertexArray(0);
+            gl::UseProgram(0);
+        }
+    }
+
+    fn snapshot(&self, cr: &cairo::Context) {
+        // The rows are in device pixels.
+        let scale = 1.0 / self.scale;
+
+        for (row, slot) in self.slots.iter().enumerate() {
+            cr.save();
+            cr.translate(0.0, row as f64 * self.row_height);
+            cr.scale(scale, scale);
+            cr.set_source_surface(&self.surfaces[*slot], 0.0, 0.0);
+            cr.paint();
+            cr.restore();
+        }
+    }
+}
@@ -0,0 +1,195 @@
+mod cairo_renderer;
+#[cfg(feature = "opengl")]
+mod gl_renderer;
+
+use std::str::FromStr;
+
+use crate::ui::color::Color;
+use crate::ui::grid::context::CellMetrics;
+
+use self::cairo_renderer::CairoRenderer;
+#[cfg(feature = "opengl")]
+use self::gl_renderer::GlRenderer;
+
+/// Backend used to render grids.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RendererKind {
+    /// Grids are drawn to a cairo surface, which is then painted on a
+    /// drawing area.
+    Cairo,
+    /// Grid rows are rasterized with cairo, but composited (and scrolled)
+    /// on the GPU with OpenGL.
+    #[cfg(feature = "opengl")]
+    Gl,
+}
+
+impl Default for RendererKind {
+    fn default() -> Self {
+        RendererKind::Cairo
+    }
+}
+
+impl FromStr for RendererKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cairo" => Ok(RendererKind::Cairo),
+            #[cfg(feature = "opengl")]
+            "gl" => Ok(RendererKind::Gl),
+            #[cfg(not(feature = "opengl"))]
+            "gl" => Err(String::from("gnvim is built without opengl support")),
+            _ => Err(format!("unknown renderer '{}'", s)),
+        }
+    }
+}
+
+/// Color (rgba) of the debug overlay.
+const OVERLAY_COLOR: (f64, f64, f64, f64) = (1.0, 0.0, 0.0, 0.25);
+
+/// Surface to be drawn on top of the grid, like the cursor.
+pub struct Layer {
+    /// Position and size (x, y, width, height) of the layer.
+    pub rect: (f64, f64, f64, f64),
+    /// Surface holding the contents of the layer.
+    pub surface: cairo::Surface,
+}
+
+/// Renderer owns the widget that a grid is drawn on and the pixel contents
+/// of that grid. Text it self is always rendered with cairo, but how those
+/// pixels are stored, moved around and eventually drawn to the screen is up
+/// to the renderer.
+pub trait Renderer {
+    /// Returns the widget that the grid is drawn on.
+    fn widget(&self) -> gtk::Widget;
+
+    /// Connects `f` to the widget's draw signal. If the widget is drawn with
+    /// cairo, the cairo context is passed to `f`. `f` is expected to call
+    /// `draw`.
+    fn connect_draw(&self, f: Box<dyn Fn(Option<&cairo::Context>)>);
+
+    /// Resizes the renderer to fit `cols` and `rows` with cell metrics `cm`.
+    /// Previous contents are preserved where possible and the rest is
+    /// filled with `bg`.
+    fn resize(
+        &mut self,
+        win: &gdk::Window,
+        cm: &CellMetrics,
+        cols: usize,
+        rows: usize,
+        bg: &Color,
+    );
+
+    /// Returns a cairo context to draw the contents of `row` with, and
+    /// the y coordinate of the row in that context.
+    fn row_context(
+        &mut self,
+        row: usize,
+        cm: &CellMetrics,
+    ) -> (cairo::Context, f64);
+
+    /// Fills everything with `bg`.
+    fn clear(&mut self, bg: &Color);
+
+    /// Sets the opacity used when filling with the background color (e.g.
+    /// in `resize`, `clear` and `scroll`). Takes effect on the next
+    /// `resize`. Renderers that can't draw translucent backgrounds ignore
+    /// this.
+    fn set_background_opacity(&mut self, _opacity: f64) {}
+
+    /// Moves the contents of region `reg` (top, bot, left, right) by `count`
+    /// rows. The area left "dirty" is filled with `bg`.
+    fn scroll(
+        &mut self,
+        cm: &CellMetrics,
+        reg: [u64; 4],
+        count: i64,
+        bg: &Color,
+    );
+
+    /// Draws the contents (and `layers`, in order) to the screen. `cr` is
+    /// the cairo context passed to the callback given to `connect_draw`.
+    /// Areas in `overlay` (x, y, width, height) are highlighted on top of
+    /// everything else, for debugging purposes.
+    fn draw(
+        &mut self,
+        cr: Option<&cairo::Context>,
+        layers: Vec<Layer>,
+        overlay: &[(f64, f64, f64, f64)],
+    );
+
+    /// Paints the contents (without any layers) to `cr`, e.g. for
+    /// screenshots.
+    fn snapshot(&self, cr: &cairo::Context);
+}
+
+/// Paints `layers` to `cr`, in order.
+pub fn paint_layers(cr: &cairo::Context, layers: Vec<Layer>) {
+    for layer in layers {
+        let (x, y, w, h) = layer.rect;
+
+        cr.save();
+        cr.rectangle(x, y, w, h);
+        layer.surface.flush();
+        cr.set_source_surface(&layer.surface, x, y);
+        cr.fill();
+        cr.restore();
+    }
+}
+
+/// Creates a new renderer of type `kind`. The renderer needs to be resized
+/// before use.
+pub fn new(kind: RendererKind) -> Box<dyn Renderer> {
+    match kind {
+        RendererKind::Cairo => Box::new(CairoRenderer::new()),
+        #[cfg(feature = "opengl")]
+        RendererKind::Gl => Box::new(GlRenderer::new()),
+    }
+}
+
+/// Returns source, destination and the area to be cleared (as top and
+/// bottom rows) when scrolling region `reg` by `count`.
+pub fn scroll_ranges(
+    reg: [u64; 4],
+    count: i64,
+) -> ((usize, usize), (usize, usize), (usize, usize)) {
+    let top = reg[0] as i64;
+    let bot = reg[1] as i64;
+
+    let (src, dst, clr) = if count > 0 {
+        let src = (top + count, bot);
+        let dst = (top, bot - count);
+        (src, dst, (dst.1, src.1))
+    } else {
+        let src = (top, bot + count);
+        let dst = (top - count, bot);
+        (src, dst, (src.0, dst.0))
+    };
+
+    (
+        (src.0 as usize, src.1 as usize),
+        (dst.0 as usize, dst.1 as usize),
+        (clr.0 as usize, clr.1 as usize),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scroll_ranges() {
+        // Scroll up by two rows.
+        assert_eq!(scroll_ranges([0, 10, 0, 5], 2), ((2, 10), (0, 8), (8, 10)));
+        // Scroll down by two rows.
+        assert_eq!(scroll_ranges([0, 10, 0, 5], -2), ((0, 8), (2, 10), (0, 2)));
+        // Scroll a region.
+        assert_eq!(scroll_ranges([3, 7, 0, 5], 1), ((4, 7), (3, 6), (6, 7)));
+    }
+
+    #[test]
+    fn test_renderer_kind_from_str() {
+        assert_eq!("cairo".parse(), Ok(RendererKind::Cairo));
+        assert!("foo".parse::<RendererKind>().is_err());
+    }
+}
@@ -1,15 +1,26 @@
+use std::ops::Range;
+
 use crate::nvim_bridge::GridLineSegment;
 
 #[cfg(test)]
 use crate::nvim_bridge;
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct Cell {
     pub text: String,
     pub hl_id: u64,
     pub double_width: bool,
 }
 
+/// Double width cell in a segment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WideCell {
+    /// Byte range of the cell's text in the segment's text.
+    pub text: Range<usize>,
+    /// Column of the cell, relative to the segment's start.
+    pub col: usize,
+}
+
 /// Wrapper for a leaf, that tells the leaf's position.
 pub struct Segment {
     //pub cell: &'a Cell,
@@ -17,6 +28,8 @@ pub struct Segment {
     pub hl_id: u64,
     pub start: usize,
     pub len: usize,
+    /// Double width cells (e.g. CJK characters and emoji) in the segment.
+    pub wide: Vec<WideCell>,
 }
 
 /// Row, as in one row in a grid. Internally has a rope/tree structure.
@@ -62,6 +75,17 @@ impl Row {
         self.len
     }
 
+    /// Returns true if the row has no text, only whitespace.
+    pub fn is_blank(&self) -> bool {
+        self.cells.iter().all(|cell| cell.text.trim().is_empty())
+    }
+
+    /// Returns true if any of the row's cells has a highlight id that
+    /// passes `f`.
+    pub fn has_hl<F: Fn(u64) -> bool>(&self, f: F) -> bool {
+        self.cells.iter().any(|cell| f(cell.hl_id))
+    }
+
     /// Clears (resets) the row.
     pub fn clear(&mut self) {
         self.cells = Row::create_empty_cells(self.len).into_boxed_slice();
@@ -106,19 +130,33 @@ impl Row {
     }
 
     /// Updates row. `line` should be coming straight from nvim's 'grid_line'.
-    /// event.
-    pub fn update(&mut self, line: GridLineSegment) -> Vec<Segment> {
+    /// event. Returns the range (start, end) of the cells that need to be
+    /// rendered again, which is nothing if none of the cells actually
+    /// changed.
+    pub fn update(&mut self, line: GridLineSegment) -> Option<(usize, usize)> {
         let col_start = line.col_start as usize;
 
+        // Range of the cells that changed.
+        let mut changed: Option<(usize, usize)> = None;
+
         let mut offset = col_start;
         for cell in line.cells.iter() {
             for r in 0..cell.repeat as usize {
-                self.cells[offset + r] = Cell {
+                let new = Cell {
                     // TODO(ville): Avoid clone here?
                     text: cell.text.clone(),
                     hl_id: cell.hl_id,
                     double_width: cell.double_width,
                 };
+
+                let i = offset + r;
+                if self.cells[i] != new {
+                    self.cells[i] = new;
+                    changed = Some(match changed {
+                        Some((start, _)) => (start, i + 1),
+                        None => (i, i + 1),
+                    });
+                }
             }
 
             offset += cell.repeat as usize;
@@ -126,7 +164,7 @@ impl Row {
 
         assert_eq!(self.cells.len(), self.len);
 
-        self.as_segments(col_start, offset)
+        changed
     }
 
     pub fn as_segments(&self, cell_start: usize, end: usize) -> Vec<Segment> {
@@ -157,6 +195,14 @@ impl Row {
 
             if let Some(ref mut seg) = segs.last_mut() {
                 if seg.hl_id == cell.hl_id {
+                    if cell.double_width {
+                        seg.wide.push(WideCell {
+                            text: seg.text.len()
+                                ..seg.text.len() + cell.text.len(),
+                            col: seg.len,
+                        });
+                    }
+
                     seg.text.push_str(&cell.text);
                     seg.len += 1;
 
@@ -170,6 +216,14 @@ impl Row {
                 hl_id: cell.hl_id,
                 start,
                 len: 1,
+                wide: if cell.double_width {
+                    vec![WideCell {
+                        text: 0..cell.text.len(),
+                        col: 0,
+                    }]
+                } else {
+                    vec![]
+                },
             });
 
             start += 1;
@@ -542,6 +596,32 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_row_update_unchanged() {
+        let mut row = Row::new(10);
+
+        let line = || GridLineSegment {
+            grid: 0,
+            row: 0,
+            col_start: 3,
+            cells: vec![nvim_bridge::Cell {
+                text: String::from("1"),
+                hl_id: 1,
+                repeat: 3,
+                double_width: false,
+            }],
+        };
+
+        let changed = row.update(line());
+        assert_eq!(changed, Some((3, 6)));
+        let segments = row.as_segments(3, 6);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].start, 3);
+
+        // Nothing changes, so nothing needs to be rendered.
+        assert_eq!(row.update(line()), None);
+    }
+
     /*
     #[test]
     fn test_rope_cell_at() {
@@ -738,6 +818,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_row_is_blank() {
+        let mut row = Row::new(3);
+        assert!(row.is_blank());
+
+        row.insert_at(
+            1,
+            vec![Cell {
+                text: "a".to_string(),
+                hl_id: 0,
+                double_width: false,
+            }],
+        );
+        assert!(!row.is_blank());
+
+        row.clear();
+        assert!(row.is_blank());
+    }
+
     #[test]
     fn test_row_clear_range() {
         let mut row = Row::new(10);
@@ -924,6 +1023,86 @@ mod tests {
         assert_eq!(second.len, 4);
     }
 
+    #[test]
+    fn test_row_as_segments_wide_cells() {
+        let mut row = Row::new(6);
+        row.update(GridLineSegment {
+            grid: 0,
+            row: 0,
+            col_start: 0,
+            cells: vec![
+                nvim_bridge::Cell {
+                    text: String::from("a"),
+                    hl_id: 1,
+                    repeat: 1,
+                    double_width: false,
+                },
+                // Composed emoji (family, joined with ZWJs) is one cell.
+                nvim_bridge::Cell {
+                    text: String::from("👨\u{200d}👩\u{200d}👧"),
+                    hl_id: 1,
+                    repeat: 1,
+                    double_width: true,
+                },
+                nvim_bridge::Cell {
+                    text: String::from(""),
+                    hl_id: 1,
+                    repeat: 1,
+                    double_width: false,
+                },
+                nvim_bridge::Cell {
+                    text: String::from("あ"),
+                    hl_id: 2,
+                    repeat: 1,
+                    double_width: true,
+                },
+                nvim_bridge::Cell {
+                    text: String::from(""),
+                    hl_id: 2,
+                    repeat: 1,
+                    double_width: false,
+                },
+            ],
+        });
+
+        let segments = row.as_segments(0, row.len);
+
+        let first = &segments[0];
+        assert_eq!(first.len, 3);
+        assert_eq!(
+            first.wide,
+            vec![WideCell {
+                text: 1..first.text.len(),
+                col: 1,
+            }]
+        );
+        assert_eq!(
+            &first.text[first.wide[0].text.clone()],
+            "👨\u{200d}👩\u{200d}👧"
+        );
+
+        let second = &segments[1];
+        assert_eq!(second.text, "あ");
+        assert_eq!(second.wide, vec![WideCell { text: 0..3, col: 0 }]);
+    }
+
+    #[test]
+    fn test_row_has_hl() {
+        let mut row = Row::new(5);
+        row.insert_at(
+            2,
+            vec![Cell {
+                text: "a".to_string(),
+                hl_id: 3,
+                double_width: false,
+            }],
+        );
+
+        assert!(row.has_hl(|id| id == 3));
+        assert!(row.has_hl(|id| id == 0));
+        assert!(!row.has_hl(|id| id == 1));
+    }
+
     #[test]
     fn test_row_grow() {
         let mut row = Row::new(10);
@@ -0,0 +1,141 @@
+use crate::ui::grid::row::Row;
+
+/// Text selection made with the mouse (or in copy mode), drawn by gnvim
+/// itself instead of nvim. Positions are (row, col) cells of the grid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Selection {
+    /// Cell where the selection started.
+    pub anchor: (u64, u64),
+    /// Cell where the selection currently ends (e.g. the pointer).
+    pub end: (u64, u64),
+    /// Time of the button press that started the selection, used to find
+    /// the latest selection across grids.
+    pub time: u32,
+    /// If the mouse button is still down.
+    pub active: bool,
+    /// If the selection is made with the keyboard in copy mode, where the
+    /// cell under the cursor is shown even if nothing is selected yet.
+    pub keyboard: bool,
+}
+
+impl Selection {
+    pub fn new(row: u64, col: u64, time: u32) -> Self {
+        Self {
+            anchor: (row, col),
+            end: (row, col),
+            time,
+            active: true,
+            keyboard: false,
+        }
+    }
+
+    /// A click without dragging doesn't select anything.
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.end && !self.keyboard
+    }
+
+    /// Returns the selected cells on each row as (row, start col, end col),
+    /// end exclusive. Rows in between the first and the last row are
+    /// selected up to `cols`.
+    pub fn rows(&self, cols: u64) -> Vec<(u64, u64, u64)> {
+        if self.is_empty() {
+            return vec![];
+        }
+
+        let (start, end) = if self.anchor <= self.end {
+            (self.anchor, self.end)
+        } else {
+            (self.end, self.anchor)
+        };
+
+        (start.0..=end.0)
+            .map(|row| {
+                let from = if row == start.0 { start.1 } else { 0 };
+                let to = if row == end.0 { end.1 + 1 } else { cols };
+                (row, from.min(cols), to.min(cols))
+            })
+            .filter(|(_, from, to)| from < to)
+            .collect()
+    }
+
+    /// Returns the selected text in `rows`. Trailing whitespace is removed
+    /// from each line.
+    pub fn text(&self, rows: &[Row]) -> String {
+        let cols = rows.get(0).map(|row| row.len).unwrap_or(0) as u64;
+
+        self.rows(cols)
+            .into_iter()
+            .filter_map(|(row, from, to)| {
+                let row = rows.get(row as usize)?;
+                let text = row
+                    .copy_range(from as usize, to as usize)
+                    .iter()
+                    .map(|cell| cell.text.as_str())
+                    .collect::<String>();
+                Some(text.trim_end().to_string())
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::grid::row::Cell;
+
+    fn row(text: &str, len: usize) -> Row {
+        let mut row = Row::new(len);
+        row.insert_at(
+            0,
+            text.chars()
+                .map(|c| Cell {
+                    text: c.to_string(),
+                    hl_id: 0,
+                    double_width: false,
+                })
+                .collect(),
+        );
+        row
+    }
+
+    #[test]
+    fn test_selection_rows() {
+        let mut sel = Selection::new(1, 4, 0);
+        assert_eq!(sel.rows(10), vec![]);
+
+        sel.end = (1, 6);
+        assert_eq!(sel.rows(10), vec![(1, 4, 7)]);
+
+        // Backwards selection.
+        sel.end = (0, 2);
+        assert_eq!(sel.rows(10), vec![(0, 2, 10), (1, 0, 5)]);
+
+        sel.end = (3, 20);
+        assert_eq!(sel.rows(10), vec![(1, 4, 10), (2, 0, 10), (3, 0, 10)]);
+    }
+
+    #[test]
+    fn test_selection_text() {
+        let rows = vec![row("foo bar", 10), row("baz", 10), row("", 10)];
+
+        let mut sel = Selection::new(0, 4, 0);
+        sel.end = (1, 1);
+        assert_eq!(sel.text(&rows), "bar\nba");
+
+        sel.end = (2, 5);
+        assert_eq!(sel.text(&rows), "bar\nbaz\n");
+    }
+
+    #[test]
+    fn test_keyboard_selection() {
+        let rows = vec![row("foo bar", 10)];
+
+        // The cell under the copy mode cursor is selected.
+        let mut sel = Selection::new(0, 4, 0);
+        sel.keyboard = true;
+        assert!(!sel.is_empty());
+        assert_eq!(sel.rows(10), vec![(0, 4, 5)]);
+        assert_eq!(sel.text(&rows), "b");
+    }
+}
@@ -0,0 +1,177 @@
+/// Friction applied to the velocity when coasting (per second).
+const FRICTION: f64 = 4.0;
+/// Velocity (steps per second) under which coasting stops.
+const MIN_VELOCITY: f64 = 2.0;
+/// If the time between two scroll events is longer than this (in
+/// milliseconds), the scrolling is considered to have stopped in between.
+const MAX_EVENT_GAP: u32 = 150;
+
+/// Turns smooth (pixel precise) scroll deltas into whole scroll steps,
+/// where one step is one wheel "click". Deltas that don't add up to a
+/// whole step are kept for the next event, so small deltas aren't lost.
+///
+/// Optionally, the scrolling can continue with decreasing speed after the
+/// user has stopped scrolling (e.g. lifted their fingers from a touchpad).
+#[derive(Default)]
+pub struct SmoothScroll {
+    /// Scroll amount that is not yet turned into whole steps.
+    x: f64,
+    y: f64,
+    /// Estimated velocity (steps per second) of the scrolling.
+    velocity: (f64, f64),
+    /// Time (in milliseconds) of the latest scroll event.
+    last_time: Option<u32>,
+
+    /// If kinetic scrolling is enabled.
+    pub kinetic: bool,
+    /// If we're coasting (e.g. kinetic scrolling is in progress).
+    pub coasting: bool,
+    /// If there is a frame callback calling `coast`.
+    pub ticking: bool,
+    /// Position (row, col) of the latest scroll event.
+    pub position: (u64, u64),
+}
+
+impl SmoothScroll {
+    /// Adds a scroll delta received at `time`. Returns the amount of whole
+    /// steps (cols, rows) to scroll.
+    pub fn push(&mut self, dx: f64, dy: f64, time: u32) -> (i64, i64) {
+        self.coasting = false;
+
+        match self.last_time {
+            Some(last) if time > last && time - last < MAX_EVENT_GAP => {
+                let dt = f64::from(time - last) / 1000.0;
+                self.velocity = (
+                    self.velocity.0 * 0.3 + dx / dt * 0.7,
+                    self.velocity.1 * 0.3 + dy / dt * 0.7,
+                );
+            }
+            Some(last) if time == last => {}
+            _ => self.velocity = (0.0, 0.0),
+        }
+        self.last_time = Some(time);
+
+        self.take_steps(dx, dy)
+    }
+
+    /// Called when the user stops scrolling. Returns true if we should
+    /// start coasting, after which `coast` should be called on each frame.
+    pub fn stop(&mut self) -> bool {
+        self.last_time = None;
+
+        let (vx, vy) = self.velocity;
+        self.coasting = self.kinetic && vx.hypot(vy) >= MIN_VELOCITY;
+
+        if !self.coasting {
+            self.reset();
+        }
+
+        self.coasting
+    }
+
+    /// Advances coasting by `dt` seconds. Returns the amount of whole steps
+    /// (cols, rows) to scroll, or `None` if we're done coasting.
+    pub fn coast(&mut self, dt: f64) -> Option<(i64, i64)> {
+        if !self.coasting {
+            return None;
+        }
+
+        let friction = (-dt * FRICTION).exp();
+        self.velocity =
+            (self.velocity.0 * friction, self.velocity.1 * friction);
+
+        let (vx, vy) = self.velocity;
+        if vx.hypot(vy) < MIN_VELOCITY {
+            self.coasting = false;
+            self.reset();
+            return None;
+        }
+
+        Some(self.take_steps(vx * dt, vy * dt))
+    }
+
+    /// Resets the accumulated scroll amount and velocity.
+    fn reset(&mut self) {
+        self.x = 0.0;
+        self.y = 0.0;
+        self.velocity = (0.0, 0.0);
+    }
+
+    fn take_steps(&mut self, dx: f64, dy: f64) -> (i64, i64) {
+        self.x += dx;
+        self.y += dy;
+
+        let cols = self.x.trunc();
+        let rows = self.y.trunc();
+        self.x -= cols;
+        self.y -= rows;
+
+        (cols as i64, rows as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_accumulates_small_deltas() {
+        let mut scroll = SmoothScroll::default();
+
+        assert_eq!(scroll.push(0.0, 0.4, 0), (0, 0));
+        assert_eq!(scroll.push(0.0, 0.4, 10), (0, 0));
+        assert_eq!(scroll.push(0.0, 0.4, 20), (0, 1));
+        assert_eq!(scroll.push(0.0, 0.8, 30), (0, 1));
+        assert_eq!(scroll.push(-2.5, 0.0, 40), (-2, 0));
+        assert_eq!(scroll.push(-0.5, 0.0, 50), (-1, 0));
+    }
+
+    #[test]
+    fn test_stop_without_kinetic() {
+        let mut scroll = SmoothScroll::default();
+
+        scroll.push(0.0, 0.5, 0);
+        scroll.push(0.0, 0.5, 10);
+        assert!(!scroll.stop());
+        assert_eq!(scroll.coast(0.016), None);
+    }
+
+    #[test]
+    fn test_coast_decelerates() {
+        let mut scroll = SmoothScroll {
+            kinetic: true,
+            ..SmoothScroll::default()
+        };
+
+        scroll.push(0.0, 0.5, 0);
+        scroll.push(0.0, 0.5, 10);
+        assert!(scroll.stop());
+
+        let mut total = 0;
+        let mut frames = 0;
+        while let Some((cols, rows)) = scroll.coast(0.016) {
+            assert_eq!(cols, 0);
+            assert!(rows >= 0);
+            total += rows;
+            frames += 1;
+        }
+
+        assert!(total > 0);
+        assert!(frames < 1000);
+        assert!(!scroll.coasting);
+    }
+
+    #[test]
+    fn test_push_stops_coasting() {
+        let mut scroll = SmoothScroll {
+            kinetic: true,
+            ..SmoothScroll::default()
+        };
+
+        scroll.push(0.0, 0.5, 0);
+        scroll.push(0.0, 0.5, 10);
+        scroll.stop();
+        scroll.push(0.0, 0.1, 1000);
+        assert!(!scroll.coasting);
+    }
+}
@@ -0,0 +1,169 @@
+/// Placeholder of a snippet, as cells on a row of a grid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SnippetPlaceholder {
+    /// Index of the placeholder in the snippet (1-based). Placeholders
+    /// that mirror each other have the same index.
+    pub index: u64,
+    pub row: u64,
+    pub col: u64,
+    /// Number of cells the placeholder covers.
+    pub len: u64,
+}
+
+/// Snippet being expanded, set by snippet plugins (see
+/// `gnvim#snippet#set_state`). The placeholders are tinted, and an
+/// "index/total" indicator is drawn next to the cursor.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SnippetState {
+    /// Index of the placeholder that the cursor is in, 0 if none.
+    pub current: u64,
+    /// Number of placeholders in the snippet.
+    pub total: u64,
+    /// The placeholders that are visible in the grid.
+    pub placeholders: Vec<SnippetPlaceholder>,
+}
+
+impl SnippetState {
+    pub fn is_active(&self) -> bool {
+        self.total > 0
+    }
+
+    /// Text of the indicator, e.g. "1/3". `None` when the cursor isn't in
+    /// any of the placeholders.
+    pub fn indicator(&self) -> Option<String> {
+        if self.current == 0 || !self.is_active() {
+            return None;
+        }
+
+        Some(format!("{}/{}", self.current.min(self.total), self.total))
+    }
+
+    /// Returns the areas (x, y, width, height) of the placeholders, and if
+    /// the area belongs to the current placeholder, for cells of
+    /// `cell_size` (width, height) in a grid of `cols` columns.
+    pub fn rects(
+        &self,
+        cell_size: (f64, f64),
+        cols: u64,
+    ) -> Vec<((f64, f64, f64, f64), bool)> {
+        let (cw, ch) = cell_size;
+
+        self.placeholders
+            .iter()
+            .filter(|p| p.col < cols)
+            .map(|p| {
+                // Empty placeholders (e.g. the final tabstop) still get a
+                // cell, so they can be seen.
+                let len = p.len.max(1).min(cols - p.col);
+                let rect =
+                    (p.col as f64 * cw, p.row as f64 * ch, len as f64 * cw, ch);
+                (rect, p.index == self.current)
+            })
+            .collect()
+    }
+}
+
+/// Rendered indicator of a snippet.
+pub struct SnippetIndicator {
+    pub surface: cairo::Surface,
+    /// Width of the indicator.
+    pub width: f64,
+}
+
+/// Returns the position (x, y) of an indicator of `size` (width, height)
+/// next to the `cursor` (x, y, width, height): after the cursor on the row
+/// above it, or on the row below when the cursor is on the first row. The
+/// indicator is kept inside a grid of `width`.
+pub fn indicator_position(
+    cursor: (f64, f64, f64, f64),
+    size: (f64, f64),
+    width: f64,
+) -> (f64, f64) {
+    let (x, y, w, h) = cursor;
+    let (iw, ih) = size;
+
+    let x = (x + w).min(width - iw).max(0.0);
+    let y = if y >= ih { y - ih } else { y + h };
+
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholder(
+        index: u64,
+        row: u64,
+        col: u64,
+        len: u64,
+    ) -> SnippetPlaceholder {
+        SnippetPlaceholder {
+            index,
+            row,
+            col,
+            len,
+        }
+    }
+
+    #[test]
+    fn test_snippet_indicator() {
+        let mut state = SnippetState::default();
+        assert_eq!(state.indicator(), None);
+
+        state.total = 3;
+        assert_eq!(state.indicator(), None);
+
+        state.current = 1;
+        assert_eq!(state.indicator(), Some(String::from("1/3")));
+
+        state.current = 4;
+        assert_eq!(state.indicator(), Some(String::from("3/3")));
+    }
+
+    #[test]
+    fn test_snippet_rects() {
+        let state = SnippetState {
+            current: 2,
+            total: 3,
+            placeholders: vec![
+                placeholder(1, 0, 4, 3),
+                placeholder(2, 1, 2, 5),
+                placeholder(2, 3, 8, 5),
+                placeholder(3, 2, 0, 0),
+                placeholder(3, 2, 20, 2),
+            ],
+        };
+
+        assert_eq!(
+            state.rects((10.0, 20.0), 10),
+            vec![
+                ((40.0, 0.0, 30.0, 20.0), false),
+                ((20.0, 20.0, 50.0, 20.0), true),
+                ((80.0, 60.0, 20.0, 20.0), true),
+                ((0.0, 40.0, 10.0, 20.0), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snippet_indicator_position() {
+        let size = (30.0, 20.0);
+
+        // Above the cursor, after it.
+        assert_eq!(
+            indicator_position((40.0, 60.0, 10.0, 20.0), size, 200.0),
+            (50.0, 40.0)
+        );
+        // Below the cursor on the first row.
+        assert_eq!(
+            indicator_position((40.0, 0.0, 10.0, 20.0), size, 200.0),
+            (50.0, 20.0)
+        );
+        // Kept inside the grid.
+        assert_eq!(
+            indicator_position((190.0, 60.0, 10.0, 20.0), size, 200.0),
+            (170.0, 40.0)
+        );
+    }
+}
@@ -0,0 +1,470 @@
+/// Modifiers held down on a key press.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    /// The super ("windows" or "command") key.
+    pub super_: bool,
+}
+
+impl Modifiers {
+    fn from_state(state: gdk::ModifierType) -> Self {
+        Self {
+            shift: state.contains(gdk::ModifierType::SHIFT_MASK),
+            ctrl: state.contains(gdk::ModifierType::CONTROL_MASK),
+            alt: state.contains(gdk::ModifierType::MOD1_MASK),
+            // Depending on the platform, super is reported either as a real
+            // or a virtual modifier.
+            super_: state.intersects(
+                gdk::ModifierType::SUPER_MASK | gdk::ModifierType::MOD4_MASK,
+            ),
+        }
+    }
+}
+
+/// Turns a key event into nvim input. With `alt_as_meta`, alt is sent to
+/// nvim as a modifier (`<A-...>`). Otherwise, alt is only used for composing
+/// characters (e.g. alt + 2 for @ on some keyboard layouts).
+pub fn event_to_nvim_input(
+    e: &gdk::EventKey,
+    alt_as_meta: bool,
+) -> Option<String> {
+    let keyval = e.get_keyval();
+    let keyname = keyval.name()?;
+
+    key_to_nvim_input(
+        keyname.as_str(),
+        keyval.to_unicode(),
+        Modifiers::from_state(e.get_state()),
+        alt_as_meta,
+    )
+}
+
+/// Turns a key (its name and the character it produces, if any) into nvim
+/// input.
+fn key_to_nvim_input(
+    keyname: &str,
+    unicode: Option<char>,
+    mut modifiers: Modifiers,
+    alt_as_meta: bool,
+) -> Option<String> {
+    if !alt_as_meta {
+        modifiers.alt = false;
+    }
+
+    let key = if let Some(key) = special_key(keyname) {
+        key.to_string()
+    } else {
+        let c = unicode.filter(|c| !c.is_control())?;
+        // The character already has the shift applied to it (e.g. "A" or
+        // "!"), so don't send the shift modifier with it, unless it's
+        // combined with ctrl or alt, for which nvim can't tell it apart
+        // otherwise (e.g. <C-a> and <S-C-A> are the same key).
+        if !modifiers.ctrl && !modifiers.alt {
+            modifiers.shift = false;
+        }
+
+        if !modifiers.ctrl && !modifiers.alt && !modifiers.super_ {
+            return Some(match c {
+                '<' => String::from("<lt>"),
+                c => c.to_string(),
+            });
+        }
+
+        escape_char(c)
+    };
+
+    let mut input = String::from("<");
+    if modifiers.shift {
+        input.push_str("S-");
+    }
+    if modifiers.ctrl {
+        input.push_str("C-");
+    }
+    if modifiers.alt {
+        input.push_str("A-");
+    }
+    if modifiers.super_ {
+        input.push_str("D-");
+    }
+    input.push_str(&key);
+    input.push('>');
+
+    Some(input)
+}
+
+/// Returns the nvim key notation for characters that can't be used as is
+/// inside `<...>`.
+fn escape_char(c: char) -> String {
+    match c {
+        '<' => String::from("lt"),
+        '>' => String::from("gt"),
+        '|' => String::from("Bar"),
+        '\\' => String::from("Bslash"),
+        ' ' => String::from("Space"),
+        c => c.to_string(),
+    }
+}
+
+/// Returns nvim's name for keys that don't produce a character (or that nvim
+/// distinguishes from the character they produce, like the keypad keys).
+fn special_key(keyname: &str) -> Option<&'static str> {
+    let key = match keyname {
+        "BackSpace" => "BS",
+        "Tab" | "ISO_Left_Tab" | "KP_Tab" => "Tab",
+        "Return" | "Enter" => "CR",
+        "Escape" => "Esc",
+        "Delete" => "Del",
+        "Insert" | "KP_Insert" => "Insert",
+        "Home" => "Home",
+        "End" => "End",
+        "Page_Up" => "PageUp",
+        "Page_Down" => "PageDown",
+        "Up" | "KP_Up" => "Up",
+        "Down" | "KP_Down" => "Down",
+        "Left" | "KP_Left" => "Left",
+        "Right" | "KP_Right" => "Right",
+        "Help" => "Help",
+        "Undo" => "Undo",
+        "KP_Space" => "Space",
+
+        "KP_0" => "k0",
+        "KP_1" => "k1",
+        "KP_2" => "k2",
+        "KP_3" => "k3",
+        "KP_4" => "k4",
+        "KP_5" => "k5",
+        "KP_6" => "k6",
+        "KP_7" => "k7",
+        "KP_8" => "k8",
+        "KP_9" => "k9",
+        "KP_Add" => "kPlus",
+        "KP_Subtract" => "kMinus",
+        "KP_Multiply" => "kMultiply",
+        "KP_Divide" => "kDivide",
+        "KP_Decimal" => "kPoint",
+        "KP_Separator" => "kComma",
+        "KP_Equal" => "kEqual",
+        "KP_Enter" => "kEnter",
+        "KP_Home" => "kHome",
+        "KP_End" => "kEnd",
+        "KP_Page_Up" | "KP_Prior" => "kPageUp",
+        "KP_Page_Down" | "KP_Next" => "kPageDown",
+        "KP_Begin" => "kOrigin",
+        "KP_Delete" => "kDel",
+
+        // Dead keys reach us only when the input method doesn't compose
+        // them (e.g. with ctrl), so send the character they'd compose on
+        // their own.
+        "dead_grave" => "`",
+        "dead_acute" => "'",
+        "dead_circumflex" => "^",
+        "dead_tilde" => "~",
+        "dead_diaeresis" => "\"",
+
+        _ => return function_key(keyname),
+    };
+
+    Some(key)
+}
+
+/// Returns the nvim name of function keys (F1 to F37).
+fn function_key(keyname: &str) -> Option<&'static str> {
+    const KEYS: [&str; 37] = [
+        "F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10", "F11",
+        "F12", "F13", "F14", "F15", "F16", "F17", "F18", "F19", "F20", "F21",
+        "F22", "F23", "F24", "F25", "F26", "F27", "F28", "F29", "F30", "F31",
+        "F32", "F33", "F34", "F35", "F36", "F37",
+    ];
+
+    KEYS.iter().find(|k| **k == keyname).copied()
+}
+
+/// GUI shortcuts, handled by gnvim before the key is sent to nvim.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shortcut {
+    /// Ctrl + Shift + C, copies the mouse selection made by gnvim.
+    Copy,
+    /// Ctrl + Shift + V, pastes the clipboard.
+    Paste,
+    /// Ctrl + = (or +), increases the font size.
+    ZoomIn,
+    /// Ctrl + -, decreases the font size.
+    ZoomOut,
+    /// F11, toggles fullscreen.
+    Fullscreen,
+    /// Ctrl + F, shows the search bar.
+    Search,
+    /// Ctrl + Shift + M, starts copy mode in the messages.
+    CopyMode,
+}
+
+/// Which GUI shortcuts are enabled. Disabled shortcuts are sent to nvim
+/// like any other key.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shortcuts {
+    pub copy: bool,
+    pub paste: bool,
+    pub zoom: bool,
+    pub fullscreen: bool,
+    /// Off by default, since Ctrl + F scrolls in nvim.
+    pub search: bool,
+    pub copy_mode: bool,
+}
+
+impl Default for Shortcuts {
+    fn default() -> Self {
+        Self {
+            copy: true,
+            paste: true,
+            zoom: true,
+            fullscreen: true,
+            search: false,
+            copy_mode: true,
+        }
+    }
+}
+
+impl Shortcuts {
+    /// Enables or disables a shortcut by its name. Returns false if there
+    /// is no shortcut called `name`.
+    pub fn enable(&mut self, name: &str, enable: bool) -> bool {
+        match name {
+            "copy" => self.copy = enable,
+            "paste" => self.paste = enable,
+            "zoom" => self.zoom = enable,
+            "fullscreen" => self.fullscreen = enable,
+            "search" => self.search = enable,
+            "copy_mode" => self.copy_mode = enable,
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Returns the enabled shortcut for a key event, if any.
+    pub fn find(&self, e: &gdk::EventKey) -> Option<Shortcut> {
+        let keyname = e.get_keyval().name()?;
+        self.find_key(keyname.as_str(), Modifiers::from_state(e.get_state()))
+    }
+
+    fn find_key(
+        &self,
+        keyname: &str,
+        modifiers: Modifiers,
+    ) -> Option<Shortcut> {
+        let shortcut = key_to_shortcut(keyname, modifiers)?;
+        let enabled = match shortcut {
+            Shortcut::Copy => self.copy,
+            Shortcut::Paste => self.paste,
+            Shortcut::ZoomIn | Shortcut::ZoomOut => self.zoom,
+            Shortcut::Fullscreen => self.fullscreen,
+            Shortcut::Search => self.search,
+            Shortcut::CopyMode => self.copy_mode,
+        };
+
+        if enabled {
+            Some(shortcut)
+        } else {
+            None
+        }
+    }
+}
+
+fn key_to_shortcut(keyname: &str, modifiers: Modifiers) -> Option<Shortcut> {
+    if modifiers.alt || modifiers.super_ {
+        return None;
+    }
+
+    match (keyname, modifiers.ctrl, modifiers.shift) {
+        ("C", true, true) | ("c", true, true) => Some(Shortcut::Copy),
+        ("V", true, true) | ("v", true, true) => Some(Shortcut::Paste),
+        ("M", true, true) | ("m", true, true) => Some(Shortcut::CopyMode),
+        // Depending on the keyboard layout, shift might be needed to type
+        // the zoom keys.
+        ("equal", true, _) | ("plus", true, _) | ("KP_Add", true, _) => {
+            Some(Shortcut::ZoomIn)
+        }
+        ("minus", true, _) | ("KP_Subtract", true, _) => {
+            Some(Shortcut::ZoomOut)
+        }
+        ("F11", false, false) => Some(Shortcut::Fullscreen),
+        ("F", true, false) | ("f", true, false) => Some(Shortcut::Search),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NONE: Modifiers = Modifiers {
+        shift: false,
+        ctrl: false,
+        alt: false,
+        super_: false,
+    };
+    const SHIFT: Modifiers = Modifiers {
+        shift: true,
+        ..NONE
+    };
+    const CTRL: Modifiers = Modifiers { ctrl: true, ..NONE };
+    const ALT: Modifiers = Modifiers { alt: true, ..NONE };
+    const SUPER: Modifiers = Modifiers {
+        super_: true,
+        ..NONE
+    };
+    const CTRL_SHIFT: Modifiers = Modifiers {
+        ctrl: true,
+        shift: true,
+        ..NONE
+    };
+    const ALT_SHIFT: Modifiers = Modifiers {
+        alt: true,
+        shift: true,
+        ..NONE
+    };
+    const SUPER_SHIFT: Modifiers = Modifiers {
+        super_: true,
+        shift: true,
+        ..NONE
+    };
+
+    #[test]
+    fn test_key_to_nvim_input() {
+        let data = vec![
+            // Characters.
+            ("a", Some('a'), NONE, Some("a")),
+            ("A", Some('A'), SHIFT, Some("A")),
+            ("exclam", Some('!'), SHIFT, Some("!")),
+            ("less", Some('<'), NONE, Some("<lt>")),
+            ("bar", Some('|'), SHIFT, Some("|")),
+            ("a", Some('a'), CTRL, Some("<C-a>")),
+            ("A", Some('A'), CTRL_SHIFT, Some("<S-C-A>")),
+            ("A", Some('A'), ALT_SHIFT, Some("<S-A-A>")),
+            ("S", Some('S'), SUPER_SHIFT, Some("<D-S>")),
+            ("a", Some('a'), ALT, Some("<A-a>")),
+            ("s", Some('s'), SUPER, Some("<D-s>")),
+            ("less", Some('<'), CTRL, Some("<C-lt>")),
+            ("greater", Some('>'), ALT, Some("<A-gt>")),
+            ("bar", Some('|'), CTRL, Some("<C-Bar>")),
+            ("backslash", Some('\\'), CTRL, Some("<C-Bslash>")),
+            ("space", Some(' '), CTRL, Some("<C-Space>")),
+            ("space", Some(' '), NONE, Some(" ")),
+            // Characters from AltGr (which isn't a modifier for nvim).
+            ("at", Some('@'), NONE, Some("@")),
+            ("EuroSign", Some('€'), NONE, Some("€")),
+            // Special keys.
+            ("Return", Some('\r'), NONE, Some("<CR>")),
+            ("BackSpace", Some('\u{8}'), NONE, Some("<BS>")),
+            ("Escape", Some('\u{1b}'), NONE, Some("<Esc>")),
+            ("ISO_Left_Tab", None, SHIFT, Some("<S-Tab>")),
+            ("Up", None, CTRL_SHIFT, Some("<S-C-Up>")),
+            ("Page_Down", None, NONE, Some("<PageDown>")),
+            ("Delete", None, SUPER, Some("<D-Del>")),
+            ("F1", None, NONE, Some("<F1>")),
+            ("F12", None, SHIFT, Some("<S-F12>")),
+            ("F37", None, NONE, Some("<F37>")),
+            ("Help", None, NONE, Some("<Help>")),
+            // Keypad.
+            ("KP_1", Some('1'), NONE, Some("<k1>")),
+            ("KP_Add", Some('+'), NONE, Some("<kPlus>")),
+            ("KP_Enter", Some('\r'), CTRL, Some("<C-kEnter>")),
+            ("KP_Home", None, NONE, Some("<kHome>")),
+            ("KP_Up", None, NONE, Some("<Up>")),
+            // Dead keys.
+            ("dead_circumflex", None, CTRL, Some("<C-^>")),
+            // Keys that nvim doesn't know about.
+            ("XF86AudioPlay", None, NONE, None),
+            ("Shift_L", None, SHIFT, None),
+            ("F38", None, NONE, None),
+        ];
+
+        for (keyname, unicode, modifiers, expected) in data {
+            assert_eq!(
+                key_to_nvim_input(keyname, unicode, modifiers, true).as_deref(),
+                expected,
+                "keyname: {}",
+                keyname,
+            );
+        }
+    }
+
+    #[test]
+    fn test_key_to_nvim_input_alt_not_meta() {
+        let data = vec![
+            ("a", Some('a'), ALT, Some("a")),
+            ("at", Some('@'), ALT, Some("@")),
+            (
+                "a",
+                Some('a'),
+                Modifiers { ctrl: true, ..ALT },
+                Some("<C-a>"),
+            ),
+            ("Up", None, ALT, Some("<Up>")),
+        ];
+
+        for (keyname, unicode, modifiers, expected) in data {
+            assert_eq!(
+                key_to_nvim_input(keyname, unicode, modifiers, false)
+                    .as_deref(),
+                expected,
+                "keyname: {}",
+                keyname,
+            );
+        }
+    }
+
+    #[test]
+    fn test_shortcuts() {
+        let shortcuts = Shortcuts::default();
+        let data = vec![
+            ("C", CTRL_SHIFT, Some(Shortcut::Copy)),
+            ("V", CTRL_SHIFT, Some(Shortcut::Paste)),
+            ("v", CTRL, None),
+            ("M", CTRL_SHIFT, Some(Shortcut::CopyMode)),
+            ("m", CTRL, None),
+            ("equal", CTRL, Some(Shortcut::ZoomIn)),
+            ("plus", CTRL_SHIFT, Some(Shortcut::ZoomIn)),
+            ("minus", CTRL, Some(Shortcut::ZoomOut)),
+            ("minus", NONE, None),
+            ("F11", NONE, Some(Shortcut::Fullscreen)),
+            ("F11", SHIFT, None),
+            ("F11", ALT, None),
+            // Not enabled by default.
+            ("f", CTRL, None),
+        ];
+
+        for (keyname, modifiers, expected) in data {
+            assert_eq!(
+                shortcuts.find_key(keyname, modifiers),
+                expected,
+                "keyname: {}",
+                keyname,
+            );
+        }
+    }
+
+    #[test]
+    fn test_shortcuts_disabled() {
+        let mut shortcuts = Shortcuts::default();
+        assert!(shortcuts.enable("zoom", false));
+        assert!(!shortcuts.enable("foo", false));
+
+        assert_eq!(shortcuts.find_key("equal", CTRL), None);
+        assert_eq!(shortcuts.find_key("minus", CTRL), None);
+        assert_eq!(shortcuts.find_key("V", CTRL_SHIFT), Some(Shortcut::Paste));
+    }
+
+    #[test]
+    fn test_shortcuts_search() {
+        let mut shortcuts = Shortcuts::default();
+        assert!(shortcuts.enable("search", true));
+
+        assert_eq!(shortcuts.find_key("f", CTRL), Some(Shortcut::Search));
+        assert_eq!(shortcuts.find_key("F", CTRL), Some(Shortcut::Search));
+        assert_eq!(shortcuts.find_key("f", CTRL_SHIFT), None);
+        assert_eq!(shortcuts.find_key("f", NONE), None);
+    }
+}
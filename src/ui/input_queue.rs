@@ -0,0 +1,310 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+
+use log::{error, warn};
+
+use crate::nvim_gio::GioNeovim;
+use crate::ui::common::spawn_local;
+use crate::ui::selection;
+
+/// How many inputs are queued while nvim is busy, by default.
+const DEFAULT_CAP: usize = 256;
+/// Interrupts nvim, so it is never queued.
+const INTERRUPT: &str = "<C-c>";
+
+/// Input for nvim.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Input {
+    /// Keys for `nvim_input`.
+    Keys(String),
+    /// Arguments of `nvim_input_mouse` (without the modifiers).
+    Mouse {
+        button: String,
+        action: String,
+        grid: i64,
+        row: u64,
+        col: u64,
+    },
+    /// Updates the primary selection from nvim's visual selection, once the
+    /// inputs before it are sent.
+    UpdatePrimarySelection,
+}
+
+impl Input {
+    pub fn mouse(
+        button: &str,
+        action: &str,
+        grid: i64,
+        row: u64,
+        col: u64,
+    ) -> Self {
+        Input::Mouse {
+            button: button.to_string(),
+            action: action.to_string(),
+            grid,
+            row,
+            col,
+        }
+    }
+
+    fn is_drag(&self) -> bool {
+        matches!(self, Input::Mouse { action, .. } if action == "drag")
+    }
+}
+
+/// The queued inputs.
+struct Queue {
+    inputs: VecDeque<Input>,
+    /// If nvim is busy, in which case the inputs are kept until it isn't.
+    busy: bool,
+    /// Maximum number of queued inputs. With 0, inputs are sent right away
+    /// even when nvim is busy.
+    cap: usize,
+}
+
+impl Queue {
+    fn new() -> Self {
+        Queue {
+            inputs: VecDeque::new(),
+            busy: false,
+            cap: DEFAULT_CAP,
+        }
+    }
+
+    /// Queues `input`. Returns false if the queue is full and the input was
+    /// dropped.
+    fn push(&mut self, input: Input) -> bool {
+        // Only the latest position of a drag matters.
+        if input.is_drag() {
+            if let Some(last) = self.inputs.back_mut() {
+                if last.is_drag() {
+                    *last = input;
+                    return true;
+                }
+            }
+        }
+
+        if self.busy && self.cap > 0 && self.inputs.len() >= self.cap {
+            return false;
+        }
+
+        self.inputs.push_back(input);
+        true
+    }
+
+    /// Next input to send, unless we should wait for nvim.
+    fn pop(&mut self) -> Option<Input> {
+        if self.busy && self.cap > 0 {
+            return None;
+        }
+
+        self.inputs.pop_front()
+    }
+}
+
+/// Sends the keyboard and mouse inputs to nvim, in order and one at a
+/// time. While nvim is busy, the inputs are queued (up to a cap) instead of
+/// stacking up requests that nvim can't answer, and an indicator shows that
+/// nvim is busy.
+#[derive(Clone)]
+pub struct InputQueue {
+    nvim: GioNeovim,
+    queue: Rc<RefCell<Queue>>,
+    /// If the inputs are being sent.
+    flushing: Rc<Cell<bool>>,
+    /// Busy indicator, shown while inputs are waiting for nvim.
+    label: gtk::Label,
+    /// Called when keys have been sent.
+    keys_sent: Rc<RefCell<Option<Box<dyn Fn()>>>>,
+}
+
+impl InputQueue {
+    pub fn new(parent: &gtk::Overlay, nvim: GioNeovim) -> Self {
+        let label = gtk::Label::new(None);
+        label.set_halign(gtk::Align::Center);
+        label.set_valign(gtk::Align::Start);
+        label.get_style_context().add_class("osd");
+        label.set_no_show_all(true);
+
+        parent.add_overlay(&label);
+        parent.set_overlay_pass_through(&label, true);
+
+        InputQueue {
+            nvim,
+            queue: Rc::new(RefCell::new(Queue::new())),
+            flushing: Rc::new(Cell::new(false)),
+            label,
+            keys_sent: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// Sets `f` to be called whenever keys have been sent to nvim.
+    pub fn connect_keys_sent<F: Fn() + 'static>(&self, f: F) {
+        self.keys_sent.replace(Some(Box::new(f)));
+    }
+
+    /// Sets how many inputs are queued while nvim is busy. 0 disables the
+    /// queueing.
+    pub fn set_cap(&self, cap: usize) {
+        self.queue.borrow_mut().cap = cap;
+        self.flush();
+    }
+
+    pub fn set_busy(&self, busy: bool) {
+        self.queue.borrow_mut().busy = busy;
+        self.update_indicator();
+        self.flush();
+    }
+
+    /// Sends `input` to nvim, or queues it if nvim is busy.
+    pub fn send(&self, input: Input) {
+        if input == Input::Keys(INTERRUPT.to_string()) {
+            // Let the user interrupt whatever is keeping nvim busy.
+            let nvim = self.nvim.clone();
+            spawn_local(async move {
+                if let Err(err) = nvim.input(INTERRUPT).await {
+                    error!("Failed to send input: {}", err);
+                }
+            });
+            return;
+        }
+
+        if !self.queue.borrow_mut().push(input) {
+            warn!("Input queue is full, dropping input");
+        }
+
+        self.update_indicator();
+        self.flush();
+    }
+
+    fn update_indicator(&self) {
+        let queue = self.queue.borrow();
+        let queued = queue.inputs.len();
+
+        if queue.busy && queue.cap > 0 && queued > 0 {
+            self.label.set_text(&format!(
+                "Neovim is busy ({} input{} queued)",
+                queued,
+                if queued == 1 { "" } else { "s" }
+            ));
+            self.label.show();
+        } else {
+            self.label.hide();
+        }
+    }
+
+    /// Sends the queued inputs, unless they're already being sent.
+    fn flush(&self) {
+        if self.flushing.get() {
+            return;
+        }
+        self.flushing.set(true);
+
+        let queue = self.clone();
+        spawn_local(async move {
+            loop {
+                let input = queue.queue.borrow_mut().pop();
+                let input = match input {
+                    Some(input) => input,
+                    None => break,
+                };
+
+                queue.update_indicator();
+                queue.send_now(input).await;
+            }
+
+            queue.flushing.set(false);
+        });
+    }
+
+    async fn send_now(&self, input: Input) {
+        let res = match input {
+            Input::Keys(keys) => {
+                let res = self.nvim.input(&keys).await.map(|_| ());
+                if res.is_ok() {
+                    if let Some(ref f) = *self.keys_sent.borrow() {
+                        f();
+                    }
+                }
+                res
+            }
+            Input::Mouse {
+                button,
+                action,
+                grid,
+                row,
+                col,
+            } => {
+                self.nvim
+                    .input_mouse(
+                        &button, &action, "", grid, row as i64, col as i64,
+                    )
+                    .await
+            }
+            Input::UpdatePrimarySelection => {
+                selection::update_primary_selection(&self.nvim).await;
+                Ok(())
+            }
+        };
+
+        if let Err(err) = res {
+            error!("Failed to send input: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_busy() {
+        let mut queue = Queue::new();
+        queue.busy = true;
+        assert!(queue.push(Input::Keys("a".to_string())));
+        assert!(queue.push(Input::Keys("b".to_string())));
+        assert_eq!(queue.pop(), None);
+
+        queue.busy = false;
+        assert_eq!(queue.pop(), Some(Input::Keys("a".to_string())));
+        assert_eq!(queue.pop(), Some(Input::Keys("b".to_string())));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn test_queue_cap() {
+        let mut queue = Queue::new();
+        queue.busy = true;
+        queue.cap = 2;
+        assert!(queue.push(Input::Keys("a".to_string())));
+        assert!(queue.push(Input::Keys("b".to_string())));
+        assert!(!queue.push(Input::Keys("c".to_string())));
+        assert_eq!(queue.inputs.len(), 2);
+
+        // Without a cap, nothing is held back.
+        queue.cap = 0;
+        assert!(queue.push(Input::Keys("c".to_string())));
+        assert_eq!(queue.pop(), Some(Input::Keys("a".to_string())));
+    }
+
+    #[test]
+    fn test_queue_drag() {
+        let mut queue = Queue::new();
+        queue.busy = true;
+        queue.push(Input::mouse("left", "press", 1, 0, 0));
+        queue.push(Input::mouse("left", "drag", 1, 0, 1));
+        queue.push(Input::mouse("left", "drag", 1, 0, 2));
+        queue.push(Input::mouse("left", "drag", 1, 1, 2));
+
+        assert_eq!(
+            queue.inputs,
+            vec![
+                Input::mouse("left", "press", 1, 0, 0),
+                Input::mouse("left", "drag", 1, 1, 2),
+            ]
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use gtk::prelude::*;
+
+use crate::logging;
+
+const WIDTH: i32 = 900;
+const HEIGHT: i32 = 500;
+/// How often (in milliseconds) new log lines are added, while the viewer
+/// is shown.
+const UPDATE_INTERVAL: u32 = 500;
+
+/// Window that shows gnvim's latest log lines, for bug reports.
+pub struct LogViewer {
+    window: gtk::Window,
+    textview: gtk::TextView,
+    /// Number of log lines that are shown.
+    shown: Rc<Cell<usize>>,
+    /// Timer that adds new log lines.
+    timer: Rc<RefCell<Option<glib::SourceId>>>,
+}
+
+impl LogViewer {
+    pub fn new(parent: &gtk::Window) -> Self {
+        let window = gtk::Window::new(gtk::WindowType::Toplevel);
+        window.set_transient_for(Some(parent));
+        window.set_destroy_with_parent(true);
+        window.set_position(gtk::WindowPosition::CenterOnParent);
+        window.set_default_size(WIDTH, HEIGHT);
+
+        let header_bar = gtk::HeaderBar::new();
+        header_bar.set_title(Some("Gnvim logs"));
+        header_bar.set_show_close_button(true);
+        let copy = gtk::Button::with_label("Copy");
+        copy.set_tooltip_text(Some("Copy the logs to the clipboard"));
+        header_bar.pack_start(&copy);
+        window.set_titlebar(Some(&header_bar));
+
+        let textview = gtk::TextView::new();
+        textview.set_editable(false);
+        textview.set_monospace(true);
+        textview.set_wrap_mode(gtk::WrapMode::WordChar);
+
+        let scrolledwindow = gtk::ScrolledWindow::new(
+            None::<&gtk::Adjustment>,
+            None::<&gtk::Adjustment>,
+        );
+        scrolledwindow.add(&textview);
+        window.add(&scrolledwindow);
+
+        copy.connect_clicked(clone!(textview => move |_| {
+            if let Some(buffer) = textview.get_buffer() {
+                let (start, end) = buffer.get_bounds();
+                if let Some(text) = buffer.get_text(&start, &end, false) {
+                    gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD)
+                        .set_text(&text);
+                }
+            }
+        }));
+
+        let timer = Rc::new(RefCell::new(None));
+
+        // Keep the window around for the next time.
+        window.connect_delete_event(clone!(timer => move |window, _| {
+            if let Some(id) = timer.borrow_mut().take() {
+                glib::source::source_remove(id);
+            }
+            window.hide();
+            Inhibit(true)
+        }));
+
+        LogViewer {
+            window,
+            textview,
+            shown: Rc::new(Cell::new(0)),
+            timer,
+        }
+    }
+
+    pub fn show(&self) {
+        append_new_lines(&self.textview, &self.shown);
+        self.window.show_all();
+        self.window.present();
+
+        if self.timer.borrow().is_some() {
+            return;
+        }
+
+        let textview = self.textview.clone();
+        let shown = self.shown.clone();
+        let id = gtk::timeout_add(UPDATE_INTERVAL, move || {
+            append_new_lines(&textview, &shown);
+            Continue(true)
+        });
+        self.timer.replace(Some(id));
+    }
+}
+
+/// Adds the log lines that aren't shown yet to `textview`, and scrolls to
+/// the end.
+fn append_new_lines(textview: &gtk::TextView, shown: &Cell<usize>) {
+    let (lines, total) = logging::recent(shown.get());
+    shown.set(total);
+    if lines.is_empty() {
+        return;
+    }
+
+    let buffer = match textview.get_buffer() {
+        Some(buffer) => buffer,
+        None => return,
+    };
+
+    let mut end = buffer.get_end_iter();
+    for line in lines {
+        buffer.insert(&mut end, &line);
+        buffer.insert(&mut end, "\n");
+    }
+
+    if let Some(mark) = buffer.create_mark(None, &end, false) {
+        textview.scroll_to_mark(&mark, 0.0, false, 0.0, 0.0);
+        buffer.delete_mark(&mark);
+    }
+}
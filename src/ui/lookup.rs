@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+
+use log::warn;
+
+/// Lookups of grids and windows by their grid id, for handling events that
+/// nvim might send out of order (e.g. for a grid that is already destroyed).
+/// Missing ids are logged, and the caller skips the event instead of
+/// crashing.
+pub trait Lookup<V> {
+    /// Gets the item of grid `id`, for handling `event`.
+    fn lookup(&self, id: i64, event: &str) -> Option<&V>;
+    fn lookup_mut(&mut self, id: i64, event: &str) -> Option<&mut V>;
+}
+
+impl<V> Lookup<V> for HashMap<i64, V> {
+    fn lookup(&self, id: i64, event: &str) -> Option<&V> {
+        let item = self.get(&id);
+        if item.is_none() {
+            warn_missing(id, event);
+        }
+        item
+    }
+
+    fn lookup_mut(&mut self, id: i64, event: &str) -> Option<&mut V> {
+        let item = self.get_mut(&id);
+        if item.is_none() {
+            warn_missing(id, event);
+        }
+        item
+    }
+}
+
+/// Makes grid `id` the current grid, if it exists, so that `current_grid`
+/// always points to an existing grid. Returns the previous current grid, or
+/// `None` if grid `id` doesn't exist (yet).
+pub fn set_current_grid<G>(
+    grids: &HashMap<i64, G>,
+    current_grid: &mut i64,
+    id: i64,
+) -> Option<i64> {
+    grids.lookup(id, "grid_cursor_goto")?;
+    Some(std::mem::replace(current_grid, id))
+}
+
+/// Checks if window of grid `id` needs a placeholder grid. Nvim might
+/// position a window before resizing its grid.
+pub fn needs_placeholder<G>(grids: &HashMap<i64, G>, id: i64) -> bool {
+    let missing = !grids.contains_key(&id);
+    if missing {
+        warn!("Creating a placeholder grid (grid: {})", id);
+    }
+    missing
+}
+
+/// Removes grid `id` and its window, and makes the default grid the current
+/// grid. The default grid is never removed, as the rest of the code relies
+/// on it being around. Returns the removed window, if any.
+pub fn remove_grid<G, W>(
+    grids: &mut HashMap<i64, G>,
+    windows: &mut HashMap<i64, W>,
+    current_grid: &mut i64,
+    id: i64,
+) -> Option<W> {
+    if id == 1 {
+        warn!("Nvim instructed to close the default grid, ignoring");
+        return None;
+    }
+
+    if grids.remove(&id).is_none() {
+        warn!(
+            "Nvim instructed to close a grid that we don't have (grid: {})",
+            id
+        );
+    }
+
+    *current_grid = 1;
+    windows.remove(&id)
+}
+
+fn warn_missing(id: i64, event: &str) {
+    warn!(
+        "Skipping {} for a grid that doesn't exist (grid: {})",
+        event, id
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Events of a grid's life, in the order nvim sends them.
+    #[derive(Clone, Copy)]
+    enum Event {
+        Resize(i64),
+        Line(i64),
+        Goto(i64),
+        WinPos(i64),
+        Hide(i64),
+        Destroy(i64),
+    }
+
+    /// Grids and windows of `UIState`, kept with the same functions. The
+    /// grids record the events they got, and the windows whether they're
+    /// shown.
+    #[derive(Default)]
+    struct State {
+        grids: HashMap<i64, Vec<&'static str>>,
+        windows: HashMap<i64, bool>,
+        current_grid: i64,
+        skipped: usize,
+    }
+
+    impl State {
+        fn new() -> Self {
+            let mut state = State::default();
+            state.grids.insert(1, vec![]);
+            state.current_grid = 1;
+            state
+        }
+
+        fn handle(&mut self, event: Event) {
+            let handled = match event {
+                Event::Resize(id) => {
+                    self.grids.entry(id).or_default().push("resize");
+                    true
+                }
+                Event::Line(id) => self
+                    .grids
+                    .lookup_mut(id, "grid_line")
+                    .map(|grid| grid.push("line"))
+                    .is_some(),
+                Event::Goto(id) => {
+                    set_current_grid(&self.grids, &mut self.current_grid, id)
+                        .is_some()
+                }
+                Event::WinPos(id) => {
+                    if needs_placeholder(&self.grids, id) {
+                        self.grids.insert(id, vec![]);
+                    }
+                    self.windows.insert(id, true);
+                    true
+                }
+                Event::Hide(id) => self
+                    .windows
+                    .lookup_mut(id, "win_hide")
+                    .map(|shown| *shown = false)
+                    .is_some(),
+                Event::Destroy(id) => {
+                    remove_grid(
+                        &mut self.grids,
+                        &mut self.windows,
+                        &mut self.current_grid,
+                        id,
+                    );
+                    true
+                }
+            };
+
+            if !handled {
+                self.skipped += 1;
+            }
+
+            // What the handlers rely on.
+            assert!(self.grids.contains_key(&1));
+            assert!(self.grids.contains_key(&self.current_grid));
+        }
+    }
+
+    fn replay(events: &[Event]) -> State {
+        let mut state = State::new();
+        events.iter().for_each(|e| state.handle(*e));
+        state
+    }
+
+    #[test]
+    fn test_lookup() {
+        let mut map = HashMap::new();
+        map.insert(2, "two");
+
+        assert_eq!(map.lookup(2, "test"), Some(&"two"));
+        assert_eq!(map.lookup(3, "test"), None);
+        assert_eq!(map.lookup_mut(3, "test"), None);
+    }
+
+    #[test]
+    fn test_set_current_grid() {
+        let mut grids = HashMap::new();
+        grids.insert(1, ());
+        grids.insert(2, ());
+        let mut current = 1;
+
+        assert_eq!(set_current_grid(&grids, &mut current, 2), Some(1));
+        assert_eq!(current, 2);
+        assert_eq!(set_current_grid(&grids, &mut current, 3), None);
+        assert_eq!(current, 2);
+    }
+
+    #[test]
+    fn test_remove_grid() {
+        let mut grids = HashMap::new();
+        grids.insert(1, ());
+        grids.insert(2, ());
+        let mut windows = HashMap::new();
+        windows.insert(2, "window");
+        let mut current = 2;
+
+        assert_eq!(
+            remove_grid(&mut grids, &mut windows, &mut current, 1),
+            None
+        );
+        assert!(grids.contains_key(&1));
+        assert_eq!(current, 2);
+
+        assert_eq!(
+            remove_grid(&mut grids, &mut windows, &mut current, 2),
+            Some("window")
+        );
+        assert!(!grids.contains_key(&2));
+        assert_eq!(current, 1);
+    }
+
+    #[test]
+    fn test_replay_events_before_resize() {
+        use Event::*;
+
+        // Lines and cursor moves of a grid that doesn't exist yet.
+        let state = replay(&[Line(2), Goto(2), Resize(2), Line(2)]);
+        assert_eq!(state.skipped, 2);
+        assert_eq!(state.grids[&2], vec!["resize", "line"]);
+        assert_eq!(state.current_grid, 1);
+    }
+
+    #[test]
+    fn test_replay_events_after_destroy() {
+        use Event::*;
+
+        let state = replay(&[
+            Resize(2),
+            WinPos(2),
+            Goto(2),
+            Destroy(2),
+            Line(2),
+            Goto(2),
+            Hide(2),
+            Destroy(2),
+        ]);
+        assert_eq!(state.skipped, 3);
+        assert!(!state.grids.contains_key(&2));
+        assert_eq!(state.current_grid, 1);
+    }
+
+    #[test]
+    fn test_replay_win_pos_without_resize() {
+        use Event::*;
+
+        // The window's position creates a placeholder grid.
+        let state = replay(&[WinPos(3), Line(3), Hide(3)]);
+        assert_eq!(state.skipped, 0);
+        assert_eq!(state.grids[&3], vec!["line"]);
+        assert!(!state.windows[&3]);
+    }
+
+    #[test]
+    fn test_replay_destroy_default_grid() {
+        use Event::*;
+
+        let state = replay(&[Destroy(1), Line(1), Goto(1)]);
+        assert_eq!(state.skipped, 0);
+        assert_eq!(state.grids[&1], vec!["line"]);
+    }
+}
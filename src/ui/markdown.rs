@@ -0,0 +1,256 @@
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{FontStyle, Style, Theme};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+/// Escapes `text` for pango markup.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\'' => escaped.push_str("&#39;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Converts the inline markdown of `line` (code spans, bold and italic
+/// text) into pango markup. Markers that aren't closed are kept as is.
+fn inline_to_markup(line: &str) -> String {
+    let chars = line.chars().collect::<Vec<_>>();
+    let rest = |i: usize| chars[i..].iter().collect::<String>();
+
+    let mut markup = String::new();
+    // Open tags, so that they're closed in the right order.
+    let mut open: Vec<&str> = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if chars
+                .get(i + 1)
+                .map_or(false, char::is_ascii_punctuation) =>
+            {
+                markup.push_str(&escape(&chars[i + 1].to_string()));
+                i += 2;
+            }
+            '`' => match chars[i + 1..].iter().position(|c| *c == '`') {
+                Some(len) => {
+                    let code =
+                        chars[i + 1..i + 1 + len].iter().collect::<String>();
+                    markup.push_str(&format!("<tt>{}</tt>", escape(&code)));
+                    i += len + 2;
+                }
+                None => {
+                    markup.push('`');
+                    i += 1;
+                }
+            },
+            '*' => {
+                let (tag, marker) = if chars.get(i + 1) == Some(&'*') {
+                    ("b", "**")
+                } else {
+                    ("i", "*")
+                };
+                let after = i + marker.len();
+
+                if open.last() == Some(&tag) {
+                    open.pop();
+                    markup.push_str(&format!("</{}>", tag));
+                } else if !open.contains(&tag) && rest(after).contains(marker) {
+                    open.push(tag);
+                    markup.push_str(&format!("<{}>", tag));
+                } else {
+                    markup.push_str(marker);
+                }
+                i = after;
+            }
+            c => {
+                markup.push_str(&escape(&c.to_string()));
+                i += 1;
+            }
+        }
+    }
+
+    for tag in open.into_iter().rev() {
+        markup.push_str(&format!("</{}>", tag));
+    }
+
+    markup
+}
+
+/// Converts a markdown document into pango markup. Supports the parts of
+/// markdown that hover documents commonly use: code blocks, headers, rules,
+/// lists and inline code, bold and italic text. Code blocks are converted
+/// with `code_block`, which gets the block's language and its code.
+pub fn markdown_to_markup<F>(markdown: &str, mut code_block: F) -> String
+where
+    F: FnMut(&str, &str) -> String,
+{
+    let mut lines = vec![];
+    // Language and lines of the current code block.
+    let mut code: Option<(&str, Vec<&str>)> = None;
+
+    for line in markdown.lines() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with("```") {
+            match code.take() {
+                Some((lang, block)) => {
+                    lines.push(code_block(lang, &block.join("\n")))
+                }
+                None => code = Some((trimmed[3..].trim(), vec![])),
+            }
+            continue;
+        }
+
+        if let Some((_, block)) = code.as_mut() {
+            block.push(line);
+            continue;
+        }
+
+        let header = trimmed.trim_start_matches('#');
+        let markup = if trimmed.starts_with('#')
+            && trimmed.len() - header.len() <= 6
+            && (header.is_empty() || header.starts_with(' '))
+        {
+            format!("<b>{}</b>", inline_to_markup(header.trim()))
+        } else if trimmed.len() >= 3
+            && ["-", "*", "_"]
+                .iter()
+                .any(|c| trimmed.replace(c, "").is_empty())
+        {
+            "─".repeat(20)
+        } else if ["- ", "* ", "+ "].iter().any(|m| trimmed.starts_with(m)) {
+            let indent = &line[..line.len() - line.trim_start().len()];
+            format!("{}• {}", indent, inline_to_markup(&trimmed[2..]))
+        } else {
+            inline_to_markup(line)
+        };
+
+        // Collapse consecutive blank lines.
+        if markup.is_empty() && lines.last().map_or(true, String::is_empty) {
+            continue;
+        }
+        lines.push(markup);
+    }
+
+    // Unterminated code block.
+    if let Some((lang, block)) = code {
+        lines.push(code_block(lang, &block.join("\n")));
+    }
+
+    while lines.last().map_or(false, String::is_empty) {
+        lines.pop();
+    }
+
+    lines.join("\n")
+}
+
+/// Returns the pango markup of `text` in `style`.
+fn styled(style: Style, text: &str) -> String {
+    let fg = style.foreground;
+    let mut attrs =
+        format!("foreground=\"#{:02x}{:02x}{:02x}\"", fg.r, fg.g, fg.b);
+    if style.font_style.contains(FontStyle::BOLD) {
+        attrs.push_str(" weight=\"bold\"");
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        attrs.push_str(" style=\"italic\"");
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        attrs.push_str(" underline=\"single\"");
+    }
+
+    format!("<span {}>{}</span>", attrs, escape(text))
+}
+
+/// Highlights `code` written in `lang` into pango markup.
+pub fn highlight_code(
+    syntax_set: &SyntaxSet,
+    theme: &Theme,
+    lang: &str,
+    code: &str,
+) -> String {
+    let lang = lang.to_lowercase();
+    let syntax = if lang.is_empty() {
+        None
+    } else {
+        // Try to find the syntax by token, and if its not found, try more
+        // relaxed way of finding it.
+        syntax_set.find_syntax_by_token(&lang).or_else(|| {
+            syntax_set
+                .syntaxes()
+                .iter()
+                .rev()
+                .find(|syntax| syntax.name.to_lowercase().contains(&lang))
+        })
+    }
+    .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
+
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let mut markup = String::new();
+    for line in LinesWithEndings::from(code) {
+        for (style, text) in highlighter.highlight(line, syntax_set) {
+            markup.push_str(&styled(style, text));
+        }
+    }
+
+    match theme.settings.background {
+        Some(bg) => format!(
+            "<tt><span background=\"#{:02x}{:02x}{:02x}\">{}</span></tt>",
+            bg.r, bg.g, bg.b, markup
+        ),
+        None => format!("<tt>{}</tt>", markup),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inline_to_markup() {
+        assert_eq!(inline_to_markup("a <b> & c"), "a &lt;b&gt; &amp; c");
+        assert_eq!(
+            inline_to_markup("use `Vec<T>` **now**"),
+            "use <tt>Vec&lt;T&gt;</tt> <b>now</b>"
+        );
+        assert_eq!(inline_to_markup("*a **b** c*"), "<i>a <b>b</b> c</i>");
+        assert_eq!(inline_to_markup("2 * 3 and `x"), "2 * 3 and `x");
+        assert_eq!(inline_to_markup(r"\*not\*"), "*not*");
+        // Markers that don't nest are kept as is.
+        assert_eq!(inline_to_markup("**a *b** c*"), "<b>a <i>b** c</i></b>");
+    }
+
+    fn code_block(lang: &str, code: &str) -> String {
+        format!("[{}]{}", lang, escape(code))
+    }
+
+    #[test]
+    fn test_markdown_to_markup() {
+        let markdown = "# Title\n\n\n```rust\nfn foo() -> Vec<u8>\n```\n---\nSome *text*\n\n";
+        assert_eq!(
+            markdown_to_markup(markdown, code_block),
+            format!(
+                "<b>Title</b>\n\n[rust]fn foo() -&gt; Vec&lt;u8&gt;\n{}\nSome <i>text</i>",
+                "─".repeat(20)
+            )
+        );
+
+        assert_eq!(
+            markdown_to_markup("```\nunterminated", code_block),
+            "[]unterminated"
+        );
+        assert_eq!(markdown_to_markup("#hashtag", code_block), "#hashtag");
+        assert_eq!(
+            markdown_to_markup("- one\n  * **two**\n+ three", code_block),
+            "• one\n  • <b>two</b>\n• three"
+        );
+    }
+}
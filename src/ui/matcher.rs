@@ -0,0 +1,83 @@
+/// Fuzzy matches `query` against `word`. Returns the char indices of `word`
+/// that matched the characters in `query`, or `None` if `word` doesn't
+/// contain all characters of `query` (in order).
+///
+/// Matching is case insensitive, unless `query` contains upper case
+/// characters (e.g. "smartcase").
+pub fn fuzzy_match(query: &str, word: &str) -> Option<Vec<usize>> {
+    let ignore_case = !query.chars().any(char::is_uppercase);
+
+    let mut indices = vec![];
+    let mut chars = word.chars().enumerate();
+
+    for q in query.chars() {
+        let (i, _) = chars.find(|(_, c)| chars_eq(q, *c, ignore_case))?;
+        indices.push(i);
+    }
+
+    Some(indices)
+}
+
+fn chars_eq(a: char, b: char, ignore_case: bool) -> bool {
+    if ignore_case {
+        a.to_lowercase().eq(b.to_lowercase())
+    } else {
+        a == b
+    }
+}
+
+/// Returns pango markup for `word` where the chars at `indices` are
+/// emphasized.
+pub fn match_markup(word: &str, indices: &[usize]) -> String {
+    let mut markup = String::new();
+    let mut in_match = false;
+
+    for (i, c) in word.chars().enumerate() {
+        let is_match = indices.contains(&i);
+        if is_match && !in_match {
+            markup.push_str("<b><u>");
+        } else if !is_match && in_match {
+            markup.push_str("</u></b>");
+        }
+        in_match = is_match;
+
+        markup.push_str(&glib::markup_escape_text(&c.to_string()));
+    }
+
+    if in_match {
+        markup.push_str("</u></b>");
+    }
+
+    markup
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(fuzzy_match("", "foo"), Some(vec![]));
+        assert_eq!(fuzzy_match("fo", "foo"), Some(vec![0, 1]));
+        assert_eq!(fuzzy_match("fb", "foo_bar"), Some(vec![0, 4]));
+        assert_eq!(fuzzy_match("fz", "foo_bar"), None);
+        assert_eq!(fuzzy_match("oof", "foo"), None);
+
+        // Case insensitive when the query is all lower case.
+        assert_eq!(fuzzy_match("gs", "getString"), Some(vec![0, 3]));
+        // Case sensitive otherwise.
+        assert_eq!(fuzzy_match("gS", "get_string"), None);
+        assert_eq!(fuzzy_match("gS", "getString"), Some(vec![0, 3]));
+    }
+
+    #[test]
+    fn test_match_markup() {
+        assert_eq!(match_markup("foo", &[]), "foo");
+        assert_eq!(match_markup("foo", &[0, 1]), "<b><u>fo</u></b>o");
+        assert_eq!(
+            match_markup("foo_bar", &[0, 6]),
+            "<b><u>f</u></b>oo_ba<b><u>r</u></b>"
+        );
+        assert_eq!(match_markup("a<b", &[2]), "a&lt;<b><u>b</u></b>");
+    }
+}
@@ -0,0 +1,379 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use gtk::prelude::*;
+
+use log::error;
+use nvim_rs::Window as NvimWindow;
+use rmpv::Value;
+
+use crate::nvim_gio::{GioNeovim, GioWriter, RequestResult};
+use crate::ui::color::{Color, HlDefs};
+use crate::ui::common::spawn_local;
+
+/// Width of the minimap, in pixels.
+const WIDTH: i32 = 100;
+/// How long the viewport has to stay put before the lines are fetched, in
+/// milliseconds.
+const FETCH_DELAY: u32 = 100;
+/// Height of one buffer line in the minimap, in pixels.
+const LINE_HEIGHT: f64 = 2.0;
+/// Width of one character in the minimap, in pixels.
+const CHAR_WIDTH: f64 = 1.0;
+/// Width of a tab character, in columns.
+const TAB_WIDTH: usize = 4;
+
+#[derive(Default)]
+struct MinimapState {
+    /// Buffer lines shown in the minimap, starting from `first_line`.
+    lines: Vec<String>,
+    /// First (zero based) buffer line shown in the minimap.
+    first_line: u64,
+    /// Number of lines requested for the minimap.
+    rows: u64,
+    /// Buffer (and its changedtick) the lines are from.
+    buf: Option<(Value, i64)>,
+
+    /// The window's viewport.
+    topline: u64,
+    botline: u64,
+    line_count: u64,
+
+    fg: Color,
+    bg: Color,
+
+    /// Id of the latest lines request, so responses to older requests can
+    /// be ignored.
+    request: u64,
+    /// Pending fetch of the lines.
+    timer: Option<glib::SourceId>,
+}
+
+/// Miniature of a window's buffer, with an indicator of the window's
+/// viewport. Clicking the minimap moves the cursor to the clicked line.
+#[derive(Clone)]
+pub struct Minimap {
+    da: gtk::DrawingArea,
+    state: Rc<RefCell<MinimapState>>,
+    nvim: GioNeovim,
+    nvim_win: NvimWindow<GioWriter>,
+    enabled: Rc<Cell<bool>>,
+}
+
+impl Minimap {
+    pub fn new(nvim: GioNeovim, nvim_win: NvimWindow<GioWriter>) -> Self {
+        let state = Rc::new(RefCell::new(MinimapState::default()));
+
+        let da = gtk::DrawingArea::new();
+        da.set_size_request(WIDTH, -1);
+        da.set_no_show_all(true);
+        da.add_events(gdk::EventMask::BUTTON_PRESS_MASK);
+
+        da.connect_draw(clone!(state => move |da, cr| {
+            draw(
+                &state.borrow(),
+                cr,
+                da.get_allocated_width() as f64,
+                da.get_allocated_height() as f64,
+            );
+            Inhibit(false)
+        }));
+
+        da.connect_button_press_event(
+            clone!(state, nvim, nvim_win => move |_, e| {
+                let line = {
+                    let state = state.borrow();
+                    let line =
+                        state.first_line + (e.get_position().1 / LINE_HEIGHT) as u64;
+                    line.min(state.line_count.saturating_sub(1))
+                };
+
+                let nvim = nvim.clone();
+                let nvim_win = nvim_win.clone();
+                spawn_local(async move {
+                    let res =
+                        nvim.request(nvim_win.set_cursor((line as i64 + 1, 0)));
+                    if let Err(err) = res.await {
+                        error!("Failed to jump to line {}: {}", line + 1, err);
+                    }
+                });
+
+                Inhibit(true)
+            }),
+        );
+
+        Self {
+            da,
+            state,
+            nvim,
+            nvim_win,
+            enabled: Rc::new(Cell::new(false)),
+        }
+    }
+
+    pub fn widget(&self) -> gtk::Widget {
+        self.da.clone().upcast()
+    }
+
+    pub fn enable(&self, enable: bool) {
+        if self.enabled.replace(enable) == enable {
+            return;
+        }
+        self.da.set_visible(enable);
+
+        if enable {
+            self.fetch_lines();
+        }
+    }
+
+    /// Number of columns of a window the minimap takes, with cells of
+    /// `cell_width`.
+    pub fn cols(&self, cell_width: f64) -> u64 {
+        if !self.enabled.get() || cell_width <= 0.0 {
+            return 0;
+        }
+
+        (f64::from(WIDTH) / cell_width).ceil() as u64
+    }
+
+    pub fn set_colors(&self, hl_defs: &HlDefs) {
+        let mut state = self.state.borrow_mut();
+        state.fg = hl_defs.default_fg;
+        state.bg = hl_defs.default_bg;
+        self.da.queue_draw();
+    }
+
+    /// Updates the viewport indicator. `topline` and `botline` are zero
+    /// based, `botline` is exclusive.
+    pub fn set_viewport(&self, topline: u64, botline: u64, line_count: u64) {
+        {
+            let mut state = self.state.borrow_mut();
+            state.topline = topline;
+            state.botline = botline;
+            state.line_count = line_count;
+        }
+
+        if !self.enabled.get() {
+            return;
+        }
+
+        self.da.queue_draw();
+
+        // Viewport changes come in bursts while scrolling, so wait for them
+        // to settle before fetching the lines.
+        let mut state = self.state.borrow_mut();
+        if let Some(id) = state.timer.take() {
+            glib::source::source_remove(id);
+        }
+
+        let minimap = self.clone();
+        state.timer = Some(gtk::timeout_add(FETCH_DELAY, move || {
+            minimap.state.borrow_mut().timer = None;
+            minimap.fetch_lines();
+            Continue(false)
+        }));
+    }
+
+    /// Fetches the buffer lines for the current viewport from nvim, unless
+    /// we have them already.
+    fn fetch_lines(&self) {
+        let (first_line, rows, request) = {
+            let mut state = self.state.borrow_mut();
+            let rows = ((self.da.get_allocated_height() as f64 / LINE_HEIGHT)
+                as u64)
+                .max(state.botline.saturating_sub(state.topline));
+            let first_line = minimap_first_line(
+                state.topline,
+                state.botline,
+                state.line_count,
+                rows,
+            );
+
+            state.request += 1;
+            (first_line, rows, state.request)
+        };
+
+        let nvim = self.nvim.clone();
+        let nvim_win = self.nvim_win.clone();
+        let state = self.state.clone();
+        let da = self.da.clone();
+        spawn_local(async move {
+            let res =
+                get_lines(&nvim, &nvim_win, &state, first_line, rows).await;
+            match res {
+                Ok(Some((key, lines))) => {
+                    let mut state = state.borrow_mut();
+                    if state.request == request {
+                        state.lines = lines;
+                        state.first_line = first_line;
+                        state.rows = rows;
+                        state.buf = Some(key);
+                        da.queue_draw();
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => error!("Failed to get lines for minimap: {}", err),
+            }
+        });
+    }
+}
+
+/// Gets `rows` lines of the window's buffer, starting from `first_line`.
+/// Returns `None` if `state` already has the lines and the buffer hasn't
+/// changed since.
+async fn get_lines(
+    nvim: &GioNeovim,
+    nvim_win: &NvimWindow<GioWriter>,
+    state: &RefCell<MinimapState>,
+    first_line: u64,
+    rows: u64,
+) -> RequestResult<Option<((Value, i64), Vec<String>)>> {
+    let buf = nvim.request(nvim_win.get_buf()).await?;
+    let tick = nvim.request(buf.get_changedtick()).await?;
+    let key = (buf.get_value().clone(), tick);
+
+    {
+        let state = state.borrow();
+        if state.buf.as_ref() == Some(&key)
+            && state.first_line == first_line
+            && state.rows == rows
+        {
+            return Ok(None);
+        }
+    }
+
+    let lines = nvim
+        .request(buf.get_lines(
+            first_line as i64,
+            (first_line + rows) as i64,
+            false,
+        ))
+        .await?;
+
+    Ok(Some((key, lines)))
+}
+
+fn draw(state: &MinimapState, cr: &cairo::Context, width: f64, height: f64) {
+    let fg = &state.fg;
+    let bg = &state.bg;
+
+    cr.set_source_rgb(bg.r, bg.g, bg.b);
+    cr.rectangle(0.0, 0.0, width, height);
+    cr.fill();
+
+    // Viewport indicator.
+    let top = state.topline.saturating_sub(state.first_line) as f64;
+    let lines = state
+        .botline
+        .min(state.line_count)
+        .saturating_sub(state.topline) as f64;
+    cr.set_source_rgba(fg.r, fg.g, fg.b, 0.15);
+    cr.rectangle(0.0, top * LINE_HEIGHT, width, lines * LINE_HEIGHT);
+    cr.fill();
+
+    let max_cols = (width / CHAR_WIDTH) as usize;
+    cr.set_source_rgba(fg.r, fg.g, fg.b, 0.6);
+    for (row, line) in state.lines.iter().enumerate() {
+        let y = row as f64 * LINE_HEIGHT;
+        if y > height {
+            break;
+        }
+
+        for (col, len) in line_blocks(line, max_cols) {
+            cr.rectangle(
+                col as f64 * CHAR_WIDTH,
+                y,
+                len as f64 * CHAR_WIDTH,
+                LINE_HEIGHT - 1.0,
+            );
+        }
+    }
+    cr.fill();
+}
+
+/// Returns the first buffer line shown in a minimap that has room for
+/// `rows` lines. The minimap scrolls along with the window, so that the
+/// viewport is always visible.
+fn minimap_first_line(
+    topline: u64,
+    botline: u64,
+    line_count: u64,
+    rows: u64,
+) -> u64 {
+    if line_count <= rows {
+        return 0;
+    }
+
+    let page = botline.min(line_count).saturating_sub(topline);
+    let scrollable = line_count.saturating_sub(page);
+    if scrollable == 0 {
+        return 0;
+    }
+
+    let progress = (topline as f64 / scrollable as f64).min(1.0);
+    (progress * (line_count - rows) as f64).round() as u64
+}
+
+/// Returns the runs of non-whitespace characters on `line`, as (column,
+/// length) pairs, up to `max_cols` columns.
+fn line_blocks(line: &str, max_cols: usize) -> Vec<(usize, usize)> {
+    let mut blocks = vec![];
+    let mut start: Option<usize> = None;
+    let mut col = 0;
+
+    for c in line.chars() {
+        if col >= max_cols {
+            break;
+        }
+
+        if c.is_whitespace() {
+            if let Some(s) = start.take() {
+                blocks.push((s, col - s));
+            }
+        } else if start.is_none() {
+            start = Some(col);
+        }
+
+        col += if c == '\t' {
+            TAB_WIDTH - col % TAB_WIDTH
+        } else {
+            1
+        };
+    }
+
+    if let Some(s) = start {
+        blocks.push((s, col.min(max_cols) - s));
+    }
+
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minimap_first_line() {
+        // Whole buffer fits into the minimap.
+        assert_eq!(minimap_first_line(10, 40, 150, 200), 0);
+        // Top, middle and bottom of the buffer.
+        assert_eq!(minimap_first_line(0, 30, 1000, 200), 0);
+        assert_eq!(minimap_first_line(485, 515, 1000, 200), 400);
+        assert_eq!(minimap_first_line(970, 1001, 1000, 200), 800);
+    }
+
+    #[test]
+    fn test_line_blocks() {
+        assert_eq!(
+            line_blocks("    let foo = 1;", 100),
+            vec![(4, 3), (8, 3), (12, 1), (14, 2)]
+        );
+        assert_eq!(line_blocks("\tfoo\tbar", 100), vec![(4, 3), (8, 3)]);
+        assert_eq!(line_blocks("", 100), vec![]);
+    }
+
+    #[test]
+    fn test_line_blocks_max_cols() {
+        assert_eq!(line_blocks("foo barbaz", 6), vec![(0, 3), (4, 2)]);
+    }
+}
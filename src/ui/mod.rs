@@ -12,6 +12,15 @@ macro_rules! add_css_provider {
     );
 }
 
+// Formats the svg icon at `$file` (relative to the calling file) with its
+// size and color.
+#[macro_export]
+macro_rules! icon {
+    ($file:expr, $color:expr, $size:expr) => {
+        format!(include_str!($file), $size, $size, $color,)
+    };
+}
+
 // Make moving clones into closures more convenient.
 // Sourced from https://github.com/gtk-rs/examples/blob/e17372b1c65788b022ff152fff37d392d0f31e87/src/bin/treeview.rs#L20-L36
 #[macro_export]
@@ -47,18 +56,67 @@ macro_rules! upgrade_weak {
     };
 }
 
+mod animation;
+mod background;
 mod cmdline;
+mod cmdline_syntax;
 pub mod color;
+mod color_scheme;
+mod command_palette;
 mod common;
-#[cfg(feature = "libwebkit2gtk")]
+mod copy_mode;
 mod cursor_tooltip;
+mod dbus;
+mod detached_tab;
+mod export;
+mod file_tree;
+mod finder;
+mod float_drag;
 mod font;
 mod grid;
+mod input;
+mod input_queue;
+mod log_viewer;
+mod lookup;
+mod markdown;
+mod matcher;
+mod minimap;
+mod offscreen;
+mod paste;
+mod perf;
+mod picker;
 mod popupmenu;
+mod preview;
+mod print;
+mod progress;
+mod screenshot;
+mod search_bar;
+mod selection;
+mod separator_drag;
+mod signs;
+mod stall_banner;
 mod state;
+mod statusbar;
 mod tabline;
+mod terminal_graphics;
+mod toasts;
+mod toplevel;
 #[allow(clippy::module_inception)]
 mod ui;
+mod variant;
 mod wildmenu;
 mod window;
-pub use self::ui::UI;
+pub use self::cmdline::CmdlinePosition;
+pub use self::export::ExportFormat;
+pub use self::grid::{
+    BlinkCurve, Crosshair, IndentGuides, RendererKind, SnippetPlaceholder,
+    SnippetState,
+};
+pub use self::popupmenu::{PopupmenuDetailsSide, PopupmenuPosition};
+pub use self::screenshot::ScreenshotTarget;
+pub use self::signs::SignKind;
+pub use self::tabline::TablineMode;
+pub use self::toplevel::{Decorations, ToplevelState};
+pub use self::ui::{UIOptions, UI};
+pub use self::wildmenu::WildmenuLayout;
+pub use self::window::FloatOptions;
@@ -0,0 +1,66 @@
+use std::path::Path;
+
+use crate::ui::state::Grids;
+
+/// Writes an image (`grid-<id>.png`) and the text (`grid-<id>.txt`) of
+/// each grid to `dir`, for asserting gnvim's output in integration tests.
+/// The images are of the grids' surfaces, so they're there even if the
+/// window isn't shown (see `--offscreen`).
+pub fn dump_grids(grids: &Grids, dir: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|err| {
+        format!("failed to create '{}': {}", dir.display(), err)
+    })?;
+
+    for (id, grid) in grids.iter() {
+        let metrics = grid.get_grid_metrics();
+        let cells = (0, 0, metrics.rows as u64, metrics.cols as u64);
+
+        let path = dir.join(format!("grid-{}.png", id));
+        grid.snapshot(Some(cells))
+            .and_then(|image| {
+                image
+                    .savev(&path, "png", &[])
+                    .map_err(|err| err.to_string())
+            })
+            .map_err(|err| {
+                format!("failed to write '{}': {}", path.display(), err)
+            })?;
+
+        let path = dir.join(format!("grid-{}.txt", id));
+        std::fs::write(&path, contents_text(&grid.get_contents())).map_err(
+            |err| format!("failed to write '{}': {}", path.display(), err),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Text of a grid's contents (see `Grid::get_contents`), a line per row
+/// without the trailing whitespace.
+fn contents_text(contents: &[Vec<(String, u64)>]) -> String {
+    contents
+        .iter()
+        .map(|row| {
+            let line: String =
+                row.iter().map(|(text, _)| text.as_str()).collect();
+            format!("{}\n", line.trim_end())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contents_text() {
+        let contents = vec![
+            vec![("foo".to_string(), 1), (" bar  ".to_string(), 2)],
+            vec![],
+            vec![("    ".to_string(), 0)],
+            vec![("~".to_string(), 3)],
+        ];
+
+        assert_eq!(contents_text(&contents), "foo bar\n\n\n~\n");
+    }
+}
@@ -0,0 +1,268 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+
+use log::error;
+
+use crate::nvim_gio::GioNeovim;
+use crate::ui::common::spawn_local;
+use crate::ui::progress::Progress;
+
+/// Size (in bytes) of the chunks that pastes are sent to nvim in.
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Pastes bigger than this (in bytes) are confirmed first, by default.
+const DEFAULT_CONFIRM_THRESHOLD: u64 = 1024 * 1024;
+
+/// Pastes text into nvim with `nvim_paste`, so that the text doesn't
+/// trigger mappings. Big pastes are streamed a chunk at a time, so they
+/// don't block the UI, show their progress and can be cancelled.
+#[derive(Clone)]
+pub struct Paster {
+    nvim: GioNeovim,
+    /// Parent of the confirmation dialog.
+    window: gtk::Window,
+    progress: Progress,
+
+    /// If a paste is being streamed.
+    active: Rc<Cell<bool>>,
+    /// Pastes waiting for the current one to finish.
+    queue: Rc<RefCell<VecDeque<String>>>,
+    /// Set when the current paste should be cancelled.
+    cancelled: Rc<Cell<bool>>,
+    /// Pastes bigger than this (in bytes) are confirmed with a dialog. 0
+    /// disables the confirmation.
+    confirm_threshold: Rc<Cell<u64>>,
+}
+
+impl Paster {
+    pub fn new(
+        nvim: GioNeovim,
+        window: gtk::Window,
+        progress: Progress,
+    ) -> Self {
+        Self {
+            nvim,
+            window,
+            progress,
+            active: Rc::new(Cell::new(false)),
+            queue: Rc::new(RefCell::new(VecDeque::new())),
+            cancelled: Rc::new(Cell::new(false)),
+            confirm_threshold: Rc::new(Cell::new(DEFAULT_CONFIRM_THRESHOLD)),
+        }
+    }
+
+    pub fn set_confirm_threshold(&self, bytes: u64) {
+        self.confirm_threshold.set(bytes);
+    }
+
+    /// Pastes the clipboard's text.
+    pub fn paste_clipboard(&self) {
+        let clipboard = gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD);
+        let paster = self.clone();
+        clipboard.request_text(move |_, text| {
+            if let Some(text) = text {
+                paster.paste(text.to_string());
+            }
+        });
+    }
+
+    /// Pastes `text`, after asking for confirmation if it's big.
+    pub fn paste(&self, text: String) {
+        let threshold = self.confirm_threshold.get();
+        if threshold == 0 || (text.len() as u64) <= threshold {
+            self.stream(text);
+            return;
+        }
+
+        let dialog = gtk::MessageDialog::new(
+            Some(&self.window),
+            gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+            gtk::MessageType::Question,
+            gtk::ButtonsType::None,
+            "Paste a lot of text?",
+        );
+        dialog.set_property_secondary_text(Some(&format!(
+            "The clipboard has {} lines ({}).",
+            text.lines().count(),
+            format_size(text.len())
+        )));
+        dialog.add_buttons(&[
+            ("Cancel", gtk::ResponseType::Cancel),
+            ("Paste", gtk::ResponseType::Accept),
+        ]);
+        dialog.set_default_response(gtk::ResponseType::Accept);
+
+        let paster = self.clone();
+        let text = Cell::new(Some(text));
+        dialog.connect_response(move |dialog, res| {
+            if res == gtk::ResponseType::Accept {
+                if let Some(text) = text.take() {
+                    paster.stream(text);
+                }
+            }
+
+            dialog.close();
+        });
+
+        dialog.show_all();
+    }
+
+    /// Cancels the paste that is being streamed, and the ones waiting for
+    /// it. Returns false if there is none.
+    pub fn cancel(&self) -> bool {
+        if !self.active.get() {
+            return false;
+        }
+
+        self.queue.borrow_mut().clear();
+        self.cancelled.set(true);
+        true
+    }
+
+    /// Streams `text` to nvim. If another paste is being streamed, `text`
+    /// is sent after it, so that the chunks of the two pastes don't get
+    /// mixed.
+    fn stream(&self, text: String) {
+        self.queue.borrow_mut().push_back(text);
+        if self.active.get() {
+            return;
+        }
+
+        self.active.set(true);
+
+        let paster = self.clone();
+        spawn_local(async move {
+            loop {
+                let text = match paster.queue.borrow_mut().pop_front() {
+                    Some(text) => text,
+                    None => break,
+                };
+                paster.cancelled.set(false);
+                paster.send(&text).await;
+            }
+
+            paster.active.set(false);
+        });
+    }
+
+    /// Sends `text` to nvim a chunk at a time.
+    async fn send(&self, text: &str) {
+        let chunks = split_chunks(text, CHUNK_SIZE);
+        let n = chunks.len();
+
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            if self.cancelled.get() {
+                // Let nvim know that the paste ended.
+                if let Err(err) = self.nvim.paste("", true, 3).await {
+                    error!("Failed to cancel paste: {}", err);
+                }
+                break;
+            }
+
+            match self.nvim.paste(chunk, true, phase(i, n)).await {
+                Ok(true) => {}
+                // Nvim cancelled the paste.
+                Ok(false) => break,
+                Err(err) => {
+                    error!("Failed to paste: {}", err);
+                    break;
+                }
+            }
+
+            if n > 1 {
+                self.progress.set_progress(Some((i + 1) as f64 / n as f64));
+            }
+        }
+
+        if n > 1 {
+            self.progress.set_progress(None);
+        }
+    }
+}
+
+/// Splits `text` into chunks of at most `size` bytes (or one character, if
+/// the character is bigger), on character boundaries.
+fn split_chunks(text: &str, size: usize) -> Vec<&str> {
+    let mut chunks = vec![];
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        let mut end = size.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+
+    if chunks.is_empty() {
+        chunks.push(text);
+    }
+
+    chunks
+}
+
+/// The `phase` argument of `nvim_paste` for chunk `i` of `n` chunks.
+fn phase(i: usize, n: usize) -> i64 {
+    if n == 1 {
+        -1
+    } else if i == 0 {
+        1
+    } else if i == n - 1 {
+        3
+    } else {
+        2
+    }
+}
+
+/// Formats `bytes` for humans.
+fn format_size(bytes: usize) -> String {
+    const KB: f64 = 1024.0;
+    let bytes = bytes as f64;
+
+    if bytes < KB {
+        format!("{} B", bytes)
+    } else if bytes < KB * KB {
+        format!("{:.1} KB", bytes / KB)
+    } else {
+        format!("{:.1} MB", bytes / (KB * KB))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_chunks() {
+        assert_eq!(split_chunks("", 4), vec![""]);
+        assert_eq!(split_chunks("abc", 4), vec!["abc"]);
+        assert_eq!(split_chunks("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        // Multi byte characters aren't split.
+        assert_eq!(split_chunks("aäöb", 2), vec!["a", "ä", "ö", "b"]);
+        assert_eq!(split_chunks("äö", 1), vec!["ä", "ö"]);
+    }
+
+    #[test]
+    fn test_phase() {
+        assert_eq!(phase(0, 1), -1);
+        assert_eq!(phase(0, 3), 1);
+        assert_eq!(phase(1, 3), 2);
+        assert_eq!(phase(2, 3), 3);
+        assert_eq!(phase(1, 2), 3);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(100), "100 B");
+        assert_eq!(format_size(2048), "2.0 KB");
+        assert_eq!(format_size(3 * 1024 * 1024 + 512 * 1024), "3.5 MB");
+    }
+}
@@ -0,0 +1,187 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use gtk::prelude::*;
+use log::error;
+
+use crate::nvim_gio::GioNeovim;
+use crate::ui::common::spawn_local;
+
+/// How often (in milliseconds) the overlay is updated.
+const UPDATE_INTERVAL: u32 = 1000;
+
+#[derive(Default)]
+struct Stats {
+    /// Redraw events received since the last update.
+    events: usize,
+    /// Time it took to apply the latest flush.
+    frame_time: Duration,
+    /// Time of the first key press that hasn't been painted yet.
+    key_press: Option<Instant>,
+    /// When the latest flush was applied.
+    flush: Option<Instant>,
+    /// Time from the latest measured key press to the flush that has its
+    /// effects.
+    latency: Option<Duration>,
+}
+
+/// Overlay showing rendering performance, for diagnosing slowness.
+pub struct PerfOverlay {
+    nvim: GioNeovim,
+    label: gtk::Label,
+    stats: Rc<RefCell<Stats>>,
+    /// Timer that updates the overlay, while it's shown.
+    timer: RefCell<Option<glib::SourceId>>,
+}
+
+impl PerfOverlay {
+    pub fn new(parent: &gtk::Overlay, nvim: GioNeovim) -> Self {
+        let label = gtk::Label::new(None);
+        label.set_halign(gtk::Align::End);
+        label.set_valign(gtk::Align::Start);
+        label.get_style_context().add_class("osd");
+        label.get_style_context().add_class("monospace");
+        label.set_no_show_all(true);
+
+        parent.add_overlay(&label);
+        parent.set_overlay_pass_through(&label, true);
+
+        PerfOverlay {
+            nvim,
+            label,
+            stats: Rc::new(RefCell::new(Stats::default())),
+            timer: RefCell::new(None),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.timer.borrow().is_some()
+    }
+
+    pub fn toggle(&self) {
+        if let Some(id) = self.timer.borrow_mut().take() {
+            glib::source::source_remove(id);
+            self.label.hide();
+            return;
+        }
+
+        self.stats.replace(Stats::default());
+        self.label.set_text("");
+        self.label.show();
+
+        let label = self.label.clone();
+        let stats = self.stats.clone();
+        let mut last = Instant::now();
+        let id = gtk::timeout_add(UPDATE_INTERVAL, move || {
+            let mut stats = stats.borrow_mut();
+            let events =
+                stats.events as f64 / last.elapsed().as_secs_f64().max(0.001);
+            last = Instant::now();
+            stats.events = 0;
+
+            let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+            label.set_text(&format!(
+                "frame: {:.1} ms\nevents: {:.0}/s\nlatency: {}",
+                ms(stats.frame_time),
+                events,
+                stats
+                    .latency
+                    .map(|l| format!("{:.1} ms", ms(l)))
+                    .unwrap_or_else(|| String::from("-")),
+            ));
+
+            Continue(true)
+        });
+        self.timer.replace(Some(id));
+    }
+
+    /// Records a key press that is sent to nvim.
+    pub fn key_pressed(&self) {
+        if self.is_enabled() {
+            let mut stats = self.stats.borrow_mut();
+            // Measure from the first key press that hasn't been painted.
+            stats.key_press.get_or_insert_with(Instant::now);
+        }
+    }
+
+    /// Measures the latency of the pending key press, once its keys are
+    /// sent. Nvim handles the request only after it has handled the keys
+    /// and flushed their effects, so the last flush before the answer is
+    /// the one with the key's effects.
+    pub fn keys_sent(&self) {
+        if !self.is_enabled() || self.stats.borrow().key_press.is_none() {
+            return;
+        }
+
+        let nvim = self.nvim.clone();
+        let stats = self.stats.clone();
+        spawn_local(async move {
+            if let Err(err) = nvim.eval("0").await {
+                error!("Failed to measure input latency: {}", err);
+                return;
+            }
+
+            // Let the flushes that arrived before the answer be applied
+            // first.
+            gtk::idle_add(move || {
+                let mut stats = stats.borrow_mut();
+                if let Some(latency) = key_latency(stats.key_press, stats.flush)
+                {
+                    stats.latency = Some(latency);
+                }
+                // Without a flush, the key had nothing to paint.
+                stats.key_press = None;
+                Continue(false)
+            });
+        });
+    }
+
+    /// Records received redraw events.
+    pub fn add_events(&self, count: usize) {
+        if self.is_enabled() {
+            self.stats.borrow_mut().events += count;
+        }
+    }
+
+    /// Records a flush that took `frame_time` to apply.
+    pub fn flushed(&self, frame_time: Duration) {
+        if self.is_enabled() {
+            let mut stats = self.stats.borrow_mut();
+            stats.frame_time = frame_time;
+            stats.flush = Some(Instant::now());
+        }
+    }
+}
+
+/// Returns the time from `key_press` to `flush`, if the flush came after
+/// the key press.
+fn key_latency(
+    key_press: Option<Instant>,
+    flush: Option<Instant>,
+) -> Option<Duration> {
+    let (key_press, flush) = (key_press?, flush?);
+    if flush >= key_press {
+        Some(flush - key_press)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_latency() {
+        let press = Instant::now();
+        let flush = press + Duration::from_millis(12);
+        assert_eq!(
+            key_latency(Some(press), Some(flush)),
+            Some(Duration::from_millis(12))
+        );
+        assert_eq!(key_latency(Some(flush), Some(press)), None);
+        assert_eq!(key_latency(None, Some(flush)), None);
+        assert_eq!(key_latency(Some(press), None), None);
+    }
+}
@@ -0,0 +1,215 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gdk::keys::constants as key;
+use gtk::prelude::*;
+
+use crate::ui::color::{HlDefs, HlGroup};
+use crate::ui::common::{popup_css, PopupColors};
+use crate::ui::font::Font;
+use crate::ui::matcher;
+
+/// Window with a search entry and a list of fuzzy matched items, for the
+/// finder and the command palette. The selection is moved with up and down,
+/// and escape (or going elsewhere) closes the window. Other keys are left to
+/// the user of the picker.
+#[derive(Clone)]
+pub struct Picker {
+    window: gtk::Window,
+    css_provider: gtk::CssProvider,
+    entry: gtk::SearchEntry,
+    list: gtk::ListBox,
+
+    /// How the listed items are ellipsized.
+    ellipsize: pango::EllipsizeMode,
+
+    /// Our font.
+    font: Rc<RefCell<Font>>,
+}
+
+impl Picker {
+    pub fn new(
+        parent: &gtk::Window,
+        width: i32,
+        height: i32,
+        ellipsize: pango::EllipsizeMode,
+    ) -> Self {
+        let css_provider = gtk::CssProvider::new();
+
+        let window = gtk::Window::new(gtk::WindowType::Toplevel);
+        window.set_transient_for(Some(parent));
+        window.set_modal(true);
+        window.set_decorated(false);
+        window.set_skip_taskbar_hint(true);
+        window.set_type_hint(gdk::WindowTypeHint::Dialog);
+        window.set_position(gtk::WindowPosition::CenterOnParent);
+        window.set_default_size(width, height);
+
+        let entry = gtk::SearchEntry::new();
+        entry.set_widget_name("query");
+
+        let list = gtk::ListBox::new();
+        list.set_selection_mode(gtk::SelectionMode::Browse);
+
+        let scrolledwindow = gtk::ScrolledWindow::new(
+            None::<&gtk::Adjustment>,
+            None::<&gtk::Adjustment>,
+        );
+        scrolledwindow
+            .set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+        scrolledwindow.set_vexpand(true);
+        scrolledwindow.add(&list);
+
+        let box_ = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        box_.pack_start(&entry, false, false, 0);
+        box_.pack_start(&scrolledwindow, true, true, 0);
+        window.add(&box_);
+
+        add_css_provider!(&css_provider, window, entry, list);
+
+        // Keys are handled here instead of the entry, so that the
+        // selection can be moved while typing.
+        window.connect_key_press_event(clone!(list => move |window, e| {
+            let selected = list.get_selected_row().map(|row| row.get_index());
+
+            match e.get_keyval() {
+                key::Escape => window.hide(),
+                key::Up | key::Down => {
+                    let index = match (selected, e.get_keyval()) {
+                        (Some(index), key::Up) => index - 1,
+                        (Some(index), _) => index + 1,
+                        (None, _) => 0,
+                    };
+                    select_row(&list, index);
+                }
+                _ => return Inhibit(false),
+            }
+
+            Inhibit(true)
+        }));
+
+        // Close when the user goes elsewhere.
+        window.connect_focus_out_event(|window, _| {
+            window.hide();
+            Inhibit(false)
+        });
+        window.connect_delete_event(|window, _| window.hide_on_delete());
+
+        Picker {
+            window,
+            css_provider,
+            entry,
+            list,
+            ellipsize,
+            font: Rc::new(RefCell::new(Font::default())),
+        }
+    }
+
+    pub fn window(&self) -> &gtk::Window {
+        &self.window
+    }
+
+    pub fn entry(&self) -> &gtk::SearchEntry {
+        &self.entry
+    }
+
+    pub fn list(&self) -> &gtk::ListBox {
+        &self.list
+    }
+
+    /// Index of the selected item, if any.
+    pub fn selected(&self) -> Option<i32> {
+        self.list.get_selected_row().map(|row| row.get_index())
+    }
+
+    /// Clears the query and shows the picker.
+    pub fn show(&self) {
+        self.entry.set_text("");
+
+        self.window.show_all();
+        self.window.present();
+        self.entry.grab_focus();
+    }
+
+    pub fn hide(&self) {
+        self.window.hide();
+    }
+
+    /// Lists `items`, with their matched chars highlighted, and selects the
+    /// first one.
+    pub fn set_items<'a, I>(&self, items: I)
+    where
+        I: IntoIterator<Item = (&'a str, &'a [usize])>,
+    {
+        for child in self.list.get_children() {
+            self.list.remove(&child);
+        }
+
+        for (item, indices) in items {
+            let label = gtk::Label::new(None);
+            label.set_markup(&matcher::match_markup(item, indices));
+            label.set_halign(gtk::Align::Start);
+            label.set_ellipsize(self.ellipsize);
+
+            let row = gtk::ListBoxRow::new();
+            row.add(&label);
+
+            add_css_provider!(&self.css_provider, row, label);
+
+            self.list.add(&row);
+        }
+
+        self.list.show_all();
+        select_row(&self.list, 0);
+    }
+
+    pub fn set_font(&self, font: Font, hl_defs: &HlDefs) {
+        self.font.replace(font);
+        self.set_colors(hl_defs);
+    }
+
+    pub fn set_colors(&self, hl_defs: &HlDefs) {
+        let color = hl_defs.get_hl_group(&HlGroup::Pmenu);
+        let color_sel = hl_defs.get_hl_group(&HlGroup::PmenuSel);
+        let colors = PopupColors {
+            fg: color
+                .and_then(|hl| hl.foreground)
+                .unwrap_or(hl_defs.default_fg),
+            bg: color
+                .and_then(|hl| hl.background)
+                .unwrap_or(hl_defs.default_bg),
+            sel_fg: color_sel
+                .and_then(|hl| hl.foreground)
+                .unwrap_or(hl_defs.default_fg),
+            sel_bg: color_sel
+                .and_then(|hl| hl.background)
+                .unwrap_or(hl_defs.default_bg),
+        };
+
+        let css = popup_css(
+            &self.font.borrow(),
+            colors,
+            "#query {
+                padding: 6px;
+            }",
+        );
+        CssProviderExt::load_from_data(&self.css_provider, css.as_bytes())
+            .unwrap();
+    }
+}
+
+/// Selects the row at `index` and scrolls to it.
+fn select_row(list: &gtk::ListBox, index: i32) {
+    if let Some(row) = list.get_row_at_index(index) {
+        list.select_row(Some(&row));
+
+        // The focus stays in the entry, so scroll to the row ourselves.
+        if let Some(adj) = list.get_adjustment() {
+            let alloc = row.get_allocation();
+            adj.clamp_page(
+                f64::from(alloc.y),
+                f64::from(alloc.y + alloc.height),
+            );
+        }
+    }
+}
@@ -1,12 +1,19 @@
+use std::collections::HashMap;
+
 use gtk::prelude::*;
 
 use crate::nvim_bridge::{CompletionItem, CompletionItemKind};
 use crate::ui::color::Color;
-
-macro_rules! icon {
-    ($file:expr, $color:expr, $size:expr) => {
-        format!(include_str!($file), $size, $size, $color,)
-    };
+use crate::ui::matcher;
+
+/// How the `kind`s of completion items are displayed.
+#[derive(Clone, Default)]
+pub struct KindIcons {
+    /// Glyphs (e.g. from a patched "nerd font") to use instead of the
+    /// built-in icons.
+    pub glyphs: HashMap<CompletionItemKind, String>,
+    /// Colors of the kinds, from the `GnvimPmenuKind*` highlight groups.
+    pub colors: HashMap<CompletionItemKind, Color>,
 }
 
 /// Wraps completion item into a structure which contains the item and some
@@ -20,6 +27,9 @@ pub struct CompletionItemWidgetWrap {
     pub menu: gtk::Label,
     /// Image of the item in the row.
     pub image: gtk::Image,
+    /// Glyph of the item in the row, shown instead of `image` if a glyph is
+    /// set for the item's kind.
+    pub glyph: gtk::Label,
     /// Kind of the item
     pub kind: CompletionItemKind,
     /// Root container.
@@ -29,9 +39,11 @@ pub struct CompletionItemWidgetWrap {
 impl CompletionItemWidgetWrap {
     pub fn create(
         item: CompletionItem,
+        query: &str,
         show_kind: bool,
         show_menu: bool,
         css_provider: &gtk::CssProvider,
+        icons: &KindIcons,
         icon_fg: &Color,
         size: f64,
     ) -> Self {
@@ -40,15 +52,20 @@ impl CompletionItemWidgetWrap {
         let grid = gtk::Grid::new();
         grid.set_column_spacing(10);
 
+        // Only one of these is shown, see `set_kind_icon`.
         let image = gtk::Image::new();
+        image.set_no_show_all(true);
+        let glyph = gtk::Label::new(None);
+        glyph.set_no_show_all(true);
         if show_kind {
-            let buf = get_icon_pixbuf(&item.kind, icon_fg, size);
-            image.set_from_pixbuf(Some(&buf));
-            image.set_tooltip_text(Some(
+            let icon = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+            icon.set_tooltip_text(Some(
                 format!("kind: '{}'", item.kind_raw).as_str(),
             ));
-            image.set_margin_start(margin);
-            grid.attach(&image, 0, 0, 1, 1);
+            icon.set_margin_start(margin);
+            icon.pack_start(&image, false, false, 0);
+            icon.pack_start(&glyph, false, false, 0);
+            grid.attach(&icon, 0, 0, 1, 1);
         }
 
         let menu = gtk::Label::new(Some(item.menu.as_str()));
@@ -58,7 +75,15 @@ impl CompletionItemWidgetWrap {
         menu.set_ellipsize(pango::EllipsizeMode::End);
         grid.attach(&menu, 2, 0, 1, 1);
 
-        let word = gtk::Label::new(Some(item.word.as_str()));
+        let word = gtk::Label::new(None);
+        // Highlight the characters that match the text typed so far. If the
+        // item doesn't match (nvim might be doing its own filtering), show
+        // the word as is.
+        if let Some(indices) = matcher::fuzzy_match(query, &item.word) {
+            word.set_markup(&matcher::match_markup(&item.word, &indices));
+        } else {
+            word.set_text(&item.word);
+        }
         word.set_ellipsize(pango::EllipsizeMode::End);
         grid.attach(&word, 1, 0, 1, 1);
 
@@ -87,16 +112,53 @@ impl CompletionItemWidgetWrap {
         let row = gtk::ListBoxRow::new();
         row.add(&grid);
 
-        add_css_provider!(css_provider, grid, word, image, info, row, menu);
+        add_css_provider!(
+            css_provider,
+            grid,
+            word,
+            image,
+            glyph,
+            info,
+            row,
+            menu
+        );
 
         let kind = item.kind.clone();
-        CompletionItemWidgetWrap {
+        let wrap = CompletionItemWidgetWrap {
             item,
             info,
             row,
             image,
+            glyph,
             kind,
             menu,
+        };
+
+        if show_kind {
+            wrap.set_kind_icon(icons, icon_fg, size);
+        }
+
+        wrap
+    }
+
+    /// Sets the icon (or glyph) for the item's kind. The kind's own color
+    /// is used if it has one, otherwise `fg`.
+    pub fn set_kind_icon(&self, icons: &KindIcons, fg: &Color, size: f64) {
+        let color = icons.colors.get(&self.kind).unwrap_or(fg);
+
+        if let Some(glyph) = icons.glyphs.get(&self.kind) {
+            self.glyph.set_markup(&format!(
+                "<span foreground=\"#{}\">{}</span>",
+                color.to_hex(),
+                glib::markup_escape_text(glyph),
+            ));
+            self.image.set_visible(false);
+            self.glyph.set_visible(true);
+        } else {
+            let buf = get_icon_pixbuf(&self.kind, color, size);
+            self.image.set_from_pixbuf(Some(&buf));
+            self.image.set_visible(true);
+            self.glyph.set_visible(false);
         }
     }
 }
@@ -1,55 +1,225 @@
 use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::rc::Rc;
 
 use gtk::prelude::*;
 
 use crate::nvim_bridge::CompletionItem;
 use crate::ui::color::Color;
-use crate::ui::popupmenu::CompletionItemWidgetWrap;
+use crate::ui::popupmenu::{CompletionItemWidgetWrap, KindIcons};
 
-type OnceLoaded =
-    Option<(Option<i32>, Box<dyn Fn(&Vec<CompletionItemWidgetWrap>)>)>;
+/// How many rows are realized at once when (re)building the realized
+/// window, and per idle callback when streaming rows in.
+const BATCH_SIZE: usize = 40;
+/// Maximum number of realized rows. Rows further away from the visible
+/// area are dropped and replaced by the spacers.
+const MAX_REALIZED: usize = 120;
+
+/// Parameters used to create the rows of the current items.
+struct RowParams {
+    query: String,
+    icons: KindIcons,
+    icon_fg: Color,
+    size: f64,
+    show_menu: bool,
+}
 
 struct State {
-    items: Vec<CompletionItemWidgetWrap>,
-    items_to_load: Vec<CompletionItem>,
+    /// All the items, realized or not.
+    items: Vec<CompletionItem>,
+    /// Realized rows, `rows[0]` being `items[start]`.
+    rows: VecDeque<CompletionItemWidgetWrap>,
+    /// Index of the first realized item.
+    start: usize,
+    /// Index of the selected item, if any. Used to select the item's row
+    /// again when it's realized.
+    selected: Option<usize>,
+    params: Option<RowParams>,
     show_kind: bool,
+    /// Height of a (non selected) row. Measured from the first realized row.
+    row_height: Option<i32>,
 
+    /// Source that streams in more rows.
     source_id: Option<glib::SourceId>,
 
-    /// Once we're loaded some (or all) data, this closure gets called if
-    /// one exists. The first value in the tuple can is indication on the
-    /// number of items needed before calling the closure.
-    once_loaded: OnceLoaded,
+    /// Spacer rows that take the place of the unrealized rows above and
+    /// below the realized ones, so the scrollbar reflects all the items.
+    top: gtk::ListBoxRow,
+    bottom: gtk::ListBoxRow,
 
     list: gtk::ListBox,
     css_provider: gtk::CssProvider,
 }
 
 impl State {
+    fn new(list: gtk::ListBox, css_provider: gtk::CssProvider) -> Self {
+        let top = spacer();
+        let bottom = spacer();
+        list.add(&top);
+        list.add(&bottom);
+
+        State {
+            items: vec![],
+            rows: VecDeque::new(),
+            start: 0,
+            selected: None,
+            params: None,
+            show_kind: false,
+            row_height: None,
+            source_id: None,
+            top,
+            bottom,
+            list,
+            css_provider,
+        }
+    }
+
+    /// Index of one past the last realized item.
+    fn end(&self) -> usize {
+        self.start + self.rows.len()
+    }
+
     fn clear(&mut self) {
         if let Some(source_id) = self.source_id.take() {
             glib::source::source_remove(source_id);
         }
 
-        while let Some(item) = self.items.pop() {
+        while let Some(item) = self.rows.pop_back() {
             self.list.remove(&item.row);
         }
+        self.start = 0;
     }
 
-    fn new(list: gtk::ListBox, css_provider: gtk::CssProvider) -> Self {
-        State {
-            items: vec![],
-            items_to_load: vec![],
-            once_loaded: None,
-            source_id: None,
-            list,
-            css_provider,
-            show_kind: false,
+    fn create_row(&self, i: usize) -> CompletionItemWidgetWrap {
+        let params = self.params.as_ref().unwrap();
+        CompletionItemWidgetWrap::create(
+            self.items[i].clone(),
+            &params.query,
+            self.show_kind,
+            params.show_menu,
+            &self.css_provider,
+            &params.icons,
+            &params.icon_fg,
+            params.size,
+        )
+    }
+
+    fn add_row(&mut self, widget: &CompletionItemWidgetWrap, i: usize) {
+        // Position 0 is the top spacer.
+        self.list.insert(&widget.row, (i - self.start + 1) as i32);
+        widget.row.show_all();
+
+        if self.selected == Some(i) {
+            self.list.select_row(Some(&widget.row));
+        }
+
+        if self.row_height.is_none() {
+            self.row_height = Some(widget.row.get_preferred_height().1)
+                .filter(|height| *height > 0);
+        }
+    }
+
+    fn push_back(&mut self) {
+        let i = self.end();
+        let widget = self.create_row(i);
+        self.add_row(&widget, i);
+        self.rows.push_back(widget);
+    }
+
+    fn push_front(&mut self) {
+        self.start -= 1;
+        let widget = self.create_row(self.start);
+        self.add_row(&widget, self.start);
+        self.rows.push_front(widget);
+    }
+
+    fn pop_front(&mut self) {
+        if let Some(item) = self.rows.pop_front() {
+            self.list.remove(&item.row);
+            self.start += 1;
+        }
+    }
+
+    fn pop_back(&mut self) {
+        if let Some(item) = self.rows.pop_back() {
+            self.list.remove(&item.row);
+        }
+    }
+
+    /// Makes sure that item `i` is realized. Only a bounded amount of rows
+    /// are created, regardless of where `i` is.
+    fn realize(&mut self, i: usize) {
+        if i >= self.items.len() || (i >= self.start && i < self.end()) {
+            return;
+        }
+
+        if i >= self.end() && i - self.end() < BATCH_SIZE {
+            while self.end() <= i {
+                self.push_back();
+            }
+            while self.rows.len() > MAX_REALIZED {
+                self.pop_front();
+            }
+        } else if i < self.start && self.start - i < BATCH_SIZE {
+            while self.start > i {
+                self.push_front();
+            }
+            while self.rows.len() > MAX_REALIZED {
+                self.pop_back();
+            }
+        } else {
+            // Too far away, start over around `i`.
+            while let Some(item) = self.rows.pop_back() {
+                self.list.remove(&item.row);
+            }
+            self.start = window_start(i, self.items.len());
+            let end = (self.start + BATCH_SIZE).min(self.items.len());
+            while self.end() < end {
+                self.push_back();
+            }
         }
+
+        self.update_spacers();
+    }
+
+    fn update_spacers(&self) {
+        let height = self.row_height.unwrap_or(0);
+        let above = self.start as i32 * height;
+        let below = (self.items.len() - self.end()) as i32 * height;
+
+        self.top.set_size_request(-1, above);
+        self.top.set_visible(above > 0);
+        self.bottom.set_size_request(-1, below);
+        self.bottom.set_visible(below > 0);
+    }
+
+    fn get(&self, i: usize) -> Option<&CompletionItemWidgetWrap> {
+        i.checked_sub(self.start).and_then(|i| self.rows.get(i))
     }
 }
 
+/// Creates a row that is only used to take space in the list.
+fn spacer() -> gtk::ListBoxRow {
+    let row = gtk::ListBoxRow::new();
+    row.set_selectable(false);
+    row.set_activatable(false);
+    row.set_can_focus(false);
+    row.set_no_show_all(true);
+    row
+}
+
+/// Index of the first realized item, when the realized rows are built
+/// around item `i` in a list of `len` items.
+fn window_start(i: usize, len: usize) -> usize {
+    i.saturating_sub(BATCH_SIZE / 2)
+        .min(len.saturating_sub(BATCH_SIZE))
+}
+
+/// Keeps all the completion items, but only creates rows for the items
+/// that are (nearly) visible. More rows are streamed in after the first
+/// batch, and rows are created as needed when the selection jumps or the
+/// list is scrolled.
+#[derive(Clone)]
 pub struct LazyLoader {
     state: Rc<RefCell<State>>,
 }
@@ -65,9 +235,16 @@ impl LazyLoader {
         self.state.borrow().show_kind
     }
 
+    /// Height of a row, once there's one.
+    pub fn row_height(&self) -> Option<i32> {
+        self.state.borrow().row_height
+    }
+
     pub fn set_items(
         &mut self,
         items: Vec<CompletionItem>,
+        query: String,
+        icons: KindIcons,
         icon_fg: Color,
         size: f64,
         show_menu: bool,
@@ -76,74 +253,127 @@ impl LazyLoader {
         state.clear();
 
         state.show_kind = items.iter().any(|item| !item.kind.is_unknown());
-        state.items_to_load = items;
+        state.items = items;
+        state.selected = None;
+        state.row_height = None;
+        state.params = Some(RowParams {
+            query,
+            icons,
+            icon_fg,
+            size,
+            show_menu,
+        });
+
+        // Create the first batch right away so we avoid renders of "half
+        // height" completion menus, and stream in the rest.
+        let end = BATCH_SIZE.min(state.items.len());
+        while state.end() < end {
+            state.push_back();
+        }
+        state.update_spacers();
 
         let state_ref = self.state.clone();
         let source_id = gtk::idle_add(move || {
             let mut state = state_ref.borrow_mut();
 
-            // Load the rows in patches so we avoid renders of "half height"
-            // completion menus.
-            for _ in 0..40 {
-                if state.items_to_load.is_empty() {
+            for _ in 0..BATCH_SIZE {
+                if state.end() >= state.items.len()
+                    || state.rows.len() >= MAX_REALIZED
+                {
                     state.source_id = None;
-
-                    if let Some((_, cb)) = state.once_loaded.take() {
-                        cb(&state.items);
-                    }
-
+                    state.update_spacers();
                     return Continue(false);
                 }
 
-                let item = state.items_to_load.remove(0);
-                let widget = CompletionItemWidgetWrap::create(
-                    item,
-                    state.show_kind,
-                    show_menu,
-                    &state.css_provider,
-                    &icon_fg,
-                    size,
-                );
-                state.list.add(&widget.row);
-                widget.row.show_all();
-                state.items.push(widget);
-            }
-
-            // Check if we have a eager closure to be called.
-            if let Some((i, cb)) = state.once_loaded.take() {
-                if let Some(i) = i {
-                    if state.items.len() >= i as usize {
-                        cb(&state.items);
-                    } else {
-                        // Not ready yet, put the items back.
-                        state.once_loaded = Some((Some(i), cb));
-                    }
-                } else {
-                    // Not ready yet, put the items back.
-                    state.once_loaded = Some((i, cb));
-                }
+                state.push_back();
             }
 
+            state.update_spacers();
             Continue(true)
         });
 
         state.source_id = Some(source_id);
-
-        state.list.show_all();
     }
 
-    /// Calls `f` once `i` mount if items (or all items) are loaded.
-    /// Only one callback can exists at a time (e.g. when we are loading
-    /// items). If all items are already loaded, `f` is called immediately.
-    pub fn once_loaded<F>(&mut self, i: Option<i32>, f: F)
+    /// Sets the selected item, and calls `f` with it, realizing it first if
+    /// needed.
+    pub fn select<F>(&self, i: Option<usize>, f: F)
     where
-        F: Fn(&Vec<CompletionItemWidgetWrap>) + 'static,
+        F: FnOnce(&CompletionItemWidgetWrap),
     {
         let mut state = self.state.borrow_mut();
-        if state.source_id.is_some() {
-            state.once_loaded = Some((i, Box::new(f)));
-        } else {
-            f(&state.items);
+        state.selected = i;
+
+        if let Some(i) = i {
+            state.realize(i);
+            if let Some(item) = state.get(i) {
+                f(item);
+            }
         }
     }
+
+    /// Calls `f` with item `i`, if it is realized. Items that are not
+    /// realized are in their default state once they are.
+    pub fn with_realized<F>(&self, i: usize, f: F)
+    where
+        F: FnOnce(&CompletionItemWidgetWrap),
+    {
+        if let Some(item) = self.state.borrow().get(i) {
+            f(item);
+        }
+    }
+
+    /// Index of the item of `row`.
+    pub fn item_index(&self, row: &gtk::ListBoxRow) -> Option<usize> {
+        let state = self.state.borrow();
+        state
+            .rows
+            .iter()
+            .position(|item| item.row == *row)
+            .map(|i| i + state.start)
+    }
+
+    /// Realizes the rows that are visible when the list is scrolled to
+    /// `value`, with `page_size` of it visible.
+    pub fn scrolled(&self, value: f64, page_size: f64) {
+        // Rows might be added or removed in the middle of some other
+        // change, in which case we don't need to react to the scroll.
+        let mut state = match self.state.try_borrow_mut() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+
+        let height = match state.row_height {
+            Some(height) => height as f64,
+            None => return,
+        };
+        if state.items.is_empty() {
+            return;
+        }
+
+        let last_item = state.items.len() - 1;
+        let first = (value / height) as usize;
+        let last = ((value + page_size) / height) as usize;
+
+        state.realize(first.min(last_item));
+        state.realize(last.min(last_item));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_start() {
+        // Near the start.
+        assert_eq!(window_start(0, 1000), 0);
+        assert_eq!(window_start(10, 1000), 0);
+        // Around `i`.
+        assert_eq!(window_start(500, 1000), 500 - BATCH_SIZE / 2);
+        // Near the end, the window is kept full.
+        assert_eq!(window_start(999, 1000), 1000 - BATCH_SIZE);
+        // Less items than in a batch.
+        assert_eq!(window_start(5, 10), 0);
+    }
 }
@@ -3,22 +3,76 @@ use std::rc::Rc;
 
 use gtk::prelude::*;
 
-use crate::nvim_bridge::CompletionItem;
+use log::error;
+
+use crate::nvim_bridge::{CompletionItem, CompletionItemKind};
 use crate::nvim_gio::GioNeovim;
 use crate::ui::color::{Color, HlDefs, HlGroup};
 use crate::ui::common::{
     calc_line_space, get_preferred_horizontal_position,
-    get_preferred_vertical_position, spawn_local,
+    get_preferred_vertical_position, get_preferred_vertical_position_below,
+    scrollbar_css, spawn_local,
 };
 use crate::ui::font::{Font, FontUnit};
-use crate::ui::popupmenu::get_icon_pixbuf;
-use crate::ui::popupmenu::LazyLoader;
+use crate::ui::markdown::markdown_to_markup;
+use crate::ui::popupmenu::{KindIcons, LazyLoader};
 
-/// Maximum height of completion menu.
+/// Default maximum height of completion menu.
 const MAX_HEIGHT: i32 = 500;
 /// Fixed width of completion menu.
 const DEFAULT_WIDTH_NO_DETAILS: i32 = 430;
-const DEFAULT_WIDTH_WITH_DETAILS: i32 = 660;
+/// Default width of the details view, which is next to the menu.
+const DEFAULT_WIDTH_DETAILS: i32 = 400;
+
+/// Which side of the cursor the popupmenu prefers. The popupmenu goes to
+/// the other side if there's more room there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PopupmenuPosition {
+    Above,
+    Below,
+}
+
+impl PopupmenuPosition {
+    pub fn from_string(position: &str) -> Option<Self> {
+        match position {
+            "above" => Some(PopupmenuPosition::Above),
+            "below" => Some(PopupmenuPosition::Below),
+            _ => None,
+        }
+    }
+}
+
+impl Default for PopupmenuPosition {
+    fn default() -> Self {
+        PopupmenuPosition::Above
+    }
+}
+
+/// Which side of the popupmenu the details view is on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PopupmenuDetailsSide {
+    Left,
+    Right,
+    /// On the right, unless there's more room on the left.
+    Auto,
+}
+
+impl PopupmenuDetailsSide {
+    pub fn from_string(side: &str) -> Option<Self> {
+        match side {
+            "left" => Some(PopupmenuDetailsSide::Left),
+            "right" => Some(PopupmenuDetailsSide::Right),
+            "auto" => Some(PopupmenuDetailsSide::Auto),
+            _ => None,
+        }
+    }
+}
+
+impl Default for PopupmenuDetailsSide {
+    fn default() -> Self {
+        PopupmenuDetailsSide::Auto
+    }
+}
 
 #[derive(Default)]
 pub struct PmenuColors {
@@ -26,6 +80,11 @@ pub struct PmenuColors {
     pub fg: Option<Color>,
     pub sel_bg: Option<Color>,
     pub sel_fg: Option<Color>,
+    /// Foreground of `FloatBorder`.
+    pub border: Option<Color>,
+    /// Backgrounds of `PmenuSbar` and `PmenuThumb`.
+    pub sbar: Option<Color>,
+    pub thumb: Option<Color>,
 }
 
 struct State {
@@ -36,10 +95,16 @@ struct State {
     /// Our anchor position where the popupmenu should be "pointing" to.
     anchor: gdk::Rectangle,
 
-    current_width: i32,
-
     width_no_details: i32,
-    width_with_details: i32,
+    /// Width of the details view.
+    width_details: i32,
+    details_side: PopupmenuDetailsSide,
+
+    /// Maximum height of the popupmenu.
+    max_height: i32,
+    /// Maximum number of visible items.
+    max_rows: Option<i32>,
+    position: PopupmenuPosition,
 }
 
 impl State {
@@ -54,9 +119,23 @@ impl State {
                 height: 0,
             },
 
-            current_width: DEFAULT_WIDTH_NO_DETAILS,
             width_no_details: DEFAULT_WIDTH_NO_DETAILS,
-            width_with_details: DEFAULT_WIDTH_WITH_DETAILS,
+            width_details: DEFAULT_WIDTH_DETAILS,
+            details_side: PopupmenuDetailsSide::default(),
+            max_height: MAX_HEIGHT,
+            max_rows: None,
+            position: PopupmenuPosition::default(),
+        }
+    }
+
+    /// Maximum height of the popupmenu, when its rows are `row_height`
+    /// high.
+    fn max_height(&self, row_height: Option<i32>) -> i32 {
+        match (self.max_rows, row_height) {
+            (Some(rows), Some(height)) => self
+                .max_height
+                .min(rows.max(0).saturating_mul(height.max(0))),
+            _ => self.max_height,
         }
     }
 }
@@ -69,7 +148,8 @@ pub struct Popupmenu {
     layout: gtk::Layout,
     /// Scrolled window that contains the list box that displays all the items.
     scrolled_list: gtk::ScrolledWindow,
-    /// Scrolled window that contains the info label for full info view.
+    /// Scrolled window that contains the info label for full info view. It's
+    /// placed next to `box_`, in its own float.
     scrolled_info: gtk::ScrolledWindow,
     /// List box that contains all the completion items.
     list: gtk::ListBox,
@@ -88,11 +168,17 @@ pub struct Popupmenu {
 
     /// Our colors.
     colors: PmenuColors,
+    /// Glyphs and colors for the completion items' kinds. Shared, because
+    /// the colors are fetched from nvim asynchronously.
+    kind_icons: Rc<RefCell<KindIcons>>,
+    nvim: GioNeovim,
     /// Our font.
     font: Font,
 
     /// Line spacing.
     line_space: i64,
+    /// Transparency of the background, from 0 to 100 (`pumblend`).
+    blend: i64,
 }
 
 impl Popupmenu {
@@ -140,10 +226,9 @@ impl Popupmenu {
             .set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
 
         let box_ = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        box_.set_widget_name("popupmenu");
         box_.pack_start(&scrolled_list, true, true, 0);
-        box_.pack_start(&scrolled_info, true, true, 0);
         box_.set_size_request(DEFAULT_WIDTH_NO_DETAILS, MAX_HEIGHT);
-        box_.set_homogeneous(true);
 
         add_css_provider!(
             &css_provider,
@@ -156,33 +241,47 @@ impl Popupmenu {
             // so we'll have to add the border to its parent (which is the
             // viewport that scorlled window adds). This aint perfect,
             // but I didn't any find better solutions.
-            scrolled_list.get_child().unwrap()
+            scrolled_list.get_child().unwrap(),
+            scrolled_list.get_vscrollbar().unwrap(),
+            scrolled_info.get_vscrollbar().unwrap()
         );
 
         let state = Rc::new(RefCell::new(State::new()));
+        let items = LazyLoader::new(list.clone(), css_provider.clone());
+
+        // Create the rows that come into view when the list is scrolled.
+        let adj = scrolled_list.get_vadjustment().unwrap();
+        adj.connect_value_changed(clone!(items => move |adj| {
+            items.scrolled(adj.get_value(), adj.get_page_size());
+        }));
 
         // When a row is activated (by mouse click), notify neovim to change
         // the selection to the activated row.
-        list.connect_row_activated(clone!(nvim, state => move |_, row| {
-            let state = state.borrow_mut();
-            let new = row.get_index();
+        list.connect_row_activated(
+            clone!(nvim, state, items => move |_, row| {
+                let state = state.borrow_mut();
+                let new = match items.item_index(row) {
+                    Some(new) => new as i32,
+                    None => return,
+                };
 
-            let selected = state.selected;
+                let selected = state.selected;
 
-            let op = if new > selected { "<C-n>" } else { "<C-p>" };
+                let op = if new > selected { "<C-n>" } else { "<C-p>" };
 
-            let mut payload = String::new();
-            for _ in 0..(new - selected).abs() {
-                payload.push_str(op)
-            }
+                let mut payload = String::new();
+                for _ in 0..(new - selected).abs() {
+                    payload.push_str(op)
+                }
 
-            let nvim = nvim.clone();
-            spawn_local(async move {
-                nvim.input(payload.as_str())
-                    .await
-                    .unwrap();
-            });
-        }));
+                let nvim = nvim.clone();
+                spawn_local(async move {
+                    nvim.input(payload.as_str())
+                        .await
+                        .unwrap();
+                });
+            }),
+        );
 
         // On (mouse) button press...
         list.connect_button_press_event(clone!(nvim => move |_, e| {
@@ -206,6 +305,7 @@ impl Popupmenu {
             None::<&gtk::Adjustment>,
         );
         layout.put(&box_, 0, 0);
+        layout.put(&scrolled_info, 0, 0);
         layout.show_all();
         scrolled_info.hide();
 
@@ -215,7 +315,7 @@ impl Popupmenu {
         }));
 
         let layout_weak = layout.downgrade();
-        box_.connect_size_allocate(clone!(state, layout_weak, scrolled_info, scrolled_list => move |box_, alloc| {
+        box_.connect_size_allocate(clone!(state, layout_weak, scrolled_info, scrolled_list, items => move |box_, alloc| {
             let layout = upgrade_weak!(layout_weak);
             let state = state.borrow();
 
@@ -225,18 +325,44 @@ impl Popupmenu {
                 let (x, width) = get_preferred_horizontal_position(
                     &area,
                     &pos,
-                    state.current_width,
-                );
-                let (y, height) = get_preferred_vertical_position(
-                    &area,
-                    &pos,
-                    alloc.height.min(MAX_HEIGHT),
+                    state.width_no_details,
                 );
+                let height =
+                    alloc.height.min(state.max_height(items.row_height()));
+                let (y, height) = match state.position {
+                    PopupmenuPosition::Above => {
+                        get_preferred_vertical_position(&area, &pos, height)
+                    }
+                    PopupmenuPosition::Below => {
+                        get_preferred_vertical_position_below(
+                            &area, &pos, height,
+                        )
+                    }
+                };
 
                 layout.move_(box_, x, y);
 
                 box_.set_size_request(width, height);
 
+                // The details view goes next to the menu, and grows away
+                // from the anchor like the menu does.
+                let (info_x, info_width) = details_position(
+                    area.width,
+                    x,
+                    width,
+                    state.width_details,
+                    state.details_side,
+                );
+                let (info_y, info_height) = if y < pos.y {
+                    let bottom = y + height;
+                    let info_height = state.max_height.min(bottom);
+                    (bottom - info_height, info_height)
+                } else {
+                    (y, state.max_height.min(area.height - y))
+                };
+                layout.move_(&scrolled_info, info_x, info_y);
+                scrolled_info.set_size_request(info_width, info_height.max(0));
+
                 // If we moved the popupmenu above the achor position, make
                 // sure our contents are aligned to the bottom so there is not
                 // cap between the achor and the content it self.
@@ -271,7 +397,7 @@ impl Popupmenu {
         layout.hide();
 
         Popupmenu {
-            items: LazyLoader::new(list.clone(), css_provider.clone()),
+            items,
             show_menu_on_all_items: false,
             box_,
             layout,
@@ -283,8 +409,11 @@ impl Popupmenu {
             state,
             info_shown: false,
             colors: PmenuColors::default(),
+            kind_icons: Rc::new(RefCell::new(KindIcons::default())),
+            nvim,
             font: Font::default(),
             line_space: 0,
+            blend: 0,
         }
     }
 
@@ -303,11 +432,10 @@ impl Popupmenu {
 
             self.info_shown = !self.info_shown;
 
-            let selected = state.selected as usize;
             let info_shown = self.info_shown;
-            let info_label = self.info_label.clone();
-            self.items.once_loaded(Some(state.selected), move |items| {
-                if let Some(item) = items.get(selected) {
+            let info_label = &self.info_label;
+            if state.selected >= 0 {
+                self.items.with_realized(state.selected as usize, |item| {
                     item.info.set_visible(!info_shown);
                     item.menu.set_visible(!info_shown);
 
@@ -319,8 +447,8 @@ impl Popupmenu {
                         info_shown
                             && item.item.menu.len() + item.item.info.len() > 0,
                     );
-                }
-            });
+                });
+            }
 
             if !self.info_shown {
                 let adj = self.scrolled_info.get_vadjustment().unwrap();
@@ -330,22 +458,25 @@ impl Popupmenu {
                 //              if the user has "overshot" the scrolling. Work around this.
             }
 
-            self.scrolled_info.set_visible(self.info_shown);
+            self.scrolled_info.set_visible(
+                self.info_shown
+                    && state.selected >= 0
+                    && self.info_label.get_visible(),
+            );
         }
 
-        self.ensure_container_width();
+        // Place the details view next to the menu.
+        self.box_.queue_resize();
     }
 
     fn ensure_container_width(&mut self) {
-        let mut state = self.state.borrow_mut();
-
-        state.current_width = if self.info_shown {
-            state.width_with_details
-        } else {
-            state.width_no_details
-        };
+        let state = self.state.borrow();
 
-        self.box_.set_size_request(state.current_width, MAX_HEIGHT);
+        self.box_.set_size_request(
+            state.width_no_details,
+            state.max_height(self.items.row_height()),
+        );
+        self.box_.queue_resize();
     }
 
     pub fn set_width(&mut self, w: i32) {
@@ -359,11 +490,44 @@ impl Popupmenu {
     pub fn set_width_details(&mut self, w: i32) {
         {
             let mut state = self.state.borrow_mut();
-            state.width_with_details = w;
+            state.width_details = w;
+        }
+        self.ensure_container_width();
+    }
+
+    pub fn set_max_height(&mut self, h: i32) {
+        {
+            let mut state = self.state.borrow_mut();
+            state.max_height = h;
         }
         self.ensure_container_width();
     }
 
+    /// Sets the maximum number of visible items, or `None` for no limit
+    /// (other than the maximum height).
+    pub fn set_max_rows(&mut self, rows: Option<i32>) {
+        self.state.borrow_mut().max_rows = rows;
+        self.ensure_container_width();
+    }
+
+    /// Sets which side of the popupmenu the details view is on.
+    pub fn set_details_side(&mut self, side: PopupmenuDetailsSide) {
+        self.state.borrow_mut().details_side = side;
+        self.box_.queue_resize();
+    }
+
+    /// Sets which side of the anchor the popupmenu prefers.
+    pub fn set_position(&mut self, position: PopupmenuPosition) {
+        self.state.borrow_mut().position = position;
+        self.box_.queue_resize();
+    }
+
+    /// Scrolls the details view by `lines` lines. Negative value scrolls up.
+    pub fn scroll_info(&self, lines: i64) {
+        let adj = self.scrolled_info.get_vadjustment().unwrap();
+        adj.set_value(adj.get_value() + lines as f64 * self.font.height as f64);
+    }
+
     /// Hides the popupmenu.
     pub fn hide(&mut self) {
         self.layout.hide();
@@ -380,11 +544,23 @@ impl Popupmenu {
         let mut state = self.state.borrow_mut();
         self.layout.move_(&self.box_, rect.x, rect.y + rect.height);
         state.anchor = rect;
+        // Place the popupmenu again, it might not fit on the same side of
+        // the new anchor.
+        self.box_.queue_resize();
     }
 
-    pub fn set_items(&mut self, items: Vec<CompletionItem>, hl_defs: &HlDefs) {
+    /// Sets the items of the popupmenu. The characters of each item that
+    /// match `query` (e.g. the text typed so far) are highlighted.
+    pub fn set_items(
+        &mut self,
+        items: Vec<CompletionItem>,
+        query: String,
+        hl_defs: &HlDefs,
+    ) {
         self.items.set_items(
             items,
+            query,
+            self.kind_icons.borrow().clone(),
             self.colors.fg.unwrap_or(hl_defs.default_fg),
             self.font.height as f64,
             self.show_menu_on_all_items,
@@ -393,108 +569,120 @@ impl Popupmenu {
         self.list.show_all();
     }
 
-    pub fn select(&mut self, item_num: i32, hl_defs: &HlDefs) {
-        let state = self.state.clone();
-        let scrolled_list = self.scrolled_list.clone();
+    /// Selects item `item_num`, or nothing if it's negative. The item's
+    /// info is shown as markdown, with its code blocks highlighted by
+    /// `code_block`, which gets the block's language and its code.
+    pub fn select<F>(&mut self, item_num: i32, hl_defs: &HlDefs, code_block: F)
+    where
+        F: Fn(&str, &str) -> String,
+    {
         let fg = self.colors.fg.unwrap_or(hl_defs.default_fg);
         let fg_sel = self.colors.sel_fg.unwrap_or(hl_defs.default_fg);
         let font_height = self.font.height as f64;
-        let list = self.list.clone();
-        let info_label = self.info_label.clone();
+        let list = &self.list;
+        let info_label = &self.info_label;
+        let scrolled_info = &self.scrolled_info;
         let info_shown = self.info_shown;
         let show_kind = self.items.get_show_kind();
+        let icons = self.kind_icons.borrow().clone();
 
         let show_menu_on_all_items = self.show_menu_on_all_items;
 
-        self.items.once_loaded(Some(item_num), move |items| {
-            let mut state = state.borrow_mut();
+        let mut state = self.state.borrow_mut();
 
-            if let Some(prev) = items.get(state.selected as usize) {
+        // Reset the previous item if it's still realized. If it isn't, it'll
+        // be in the default state once it is.
+        if state.selected >= 0 {
+            self.items.with_realized(state.selected as usize, |prev| {
                 prev.info.set_visible(false);
                 prev.menu.set_visible(show_menu_on_all_items);
 
                 if show_kind {
                     // Update the `kind` icon with default fg color.
-                    let buf =
-                        get_icon_pixbuf(&prev.item.kind, &fg, font_height);
-                    prev.image.set_from_pixbuf(Some(&buf));
+                    prev.set_kind_icon(&icons, &fg, font_height);
                 }
-            }
+            });
+        }
 
-            state.selected = item_num;
+        state.selected = item_num;
 
-            if item_num < 0 {
-                list.unselect_all();
-                info_label.set_text("");
-                info_label.hide();
+        if item_num < 0 {
+            self.items.select(None, |_| {});
+            list.unselect_all();
+            info_label.set_text("");
+            info_label.hide();
+            scrolled_info.hide();
 
-                // If selection is removed, move the scrolled window to the top.
-                let adj = scrolled_list.get_vadjustment().unwrap();
-                gtk::idle_add(move || {
-                    adj.set_value(0.0);
-                    Continue(false)
-                });
+            // If selection is removed, move the scrolled window to the top.
+            let adj = self.scrolled_list.get_vadjustment().unwrap();
+            gtk::idle_add(move || {
+                adj.set_value(0.0);
+                Continue(false)
+            });
 
-                return;
-            }
+            return;
+        }
 
-            if let Some(item) = items.get(state.selected as usize) {
-                item.info.set_visible(!info_shown);
-                item.menu.set_visible(!info_shown);
+        self.items.select(Some(item_num as usize), |item| {
+            item.info.set_visible(!info_shown);
+            item.menu.set_visible(!info_shown);
 
-                if item.item.info.is_empty() {
-                    item.info.set_visible(false);
-                }
+            if item.item.info.is_empty() {
+                item.info.set_visible(false);
+            }
 
-                item.row.grab_focus();
-                list.select_row(Some(&item.row));
-
-                {
-                    let id = Rc::new(RefCell::new(None));
-                    // Ensure that the row is in the view, but make sure first
-                    // that the row it self has allocated itself. It is possible
-                    // that when we selected the row and grabbed focus for it
-                    // the row it self isn't "ready" to grab focus yet. Hence
-                    // this signal handler here to ensure the row is in view.
-                    // NOTE(ville): According to some IRC discussions, this
-                    // hack wont work on GTK4. Prepare yourself!
-                    let list_weak = list.downgrade();
-                    let sig_id = item.row.connect_size_allocate(
-                        clone!(id, list_weak => move |row, _| {
-                            let list = upgrade_weak!(list_weak);
-                            ensure_row_visible(&list, &row);
-
-                            let id = id.borrow_mut().take().unwrap();
-                            row.disconnect(id);
-                        }),
-                    );
-                    *id.borrow_mut() = Some(sig_id);
-                }
+            item.row.grab_focus();
+            list.select_row(Some(&item.row));
+
+            {
+                let id = Rc::new(RefCell::new(None));
+                // Ensure that the row is in the view, but make sure first
+                // that the row it self has allocated itself. It is possible
+                // that when we selected the row and grabbed focus for it
+                // the row it self isn't "ready" to grab focus yet. Hence
+                // this signal handler here to ensure the row is in view.
+                // NOTE(ville): According to some IRC discussions, this
+                // hack wont work on GTK4. Prepare yourself!
+                let list_weak = list.downgrade();
+                let sig_id = item.row.connect_size_allocate(
+                    clone!(id, list_weak => move |row, _| {
+                        let list = upgrade_weak!(list_weak);
+                        ensure_row_visible(&list, &row);
+
+                        let id = id.borrow_mut().take().unwrap();
+                        row.disconnect(id);
+                    }),
+                );
+                *id.borrow_mut() = Some(sig_id);
+            }
 
-                if show_kind {
-                    // Update the `kind` icon with "selected" fg color.
-                    let buf =
-                        get_icon_pixbuf(&item.item.kind, &fg_sel, font_height);
-                    item.image.set_from_pixbuf(Some(&buf));
-                }
+            if show_kind {
+                // Update the `kind` icon with "selected" fg color.
+                item.set_kind_icon(&icons, &fg_sel, font_height);
+            }
 
-                let newline = if !item.item.menu.is_empty()
-                    && !item.item.info.is_empty()
-                {
+            let newline =
+                if !item.item.menu.is_empty() && !item.item.info.is_empty() {
                     "\n"
                 } else {
                     ""
                 };
 
-                info_label.set_text(&format!(
-                    "{}{}{}",
-                    item.item.menu, newline, item.item.info
-                ));
-
-                let has_info_content =
-                    item.item.menu.len() + item.item.info.len() > 0;
-                info_label.set_visible(info_shown && has_info_content);
-            }
+            // The info is usually documentation in markdown (especially
+            // if it comes from a language server), so render it as such.
+            info_label.set_markup(&format!(
+                "{}{}{}",
+                glib::markup_escape_text(&item.item.menu),
+                newline,
+                markdown_to_markup(&item.item.info, &code_block)
+            ));
+
+            let has_info_content =
+                item.item.menu.len() + item.item.info.len() > 0;
+            info_label.set_visible(info_shown && has_info_content);
+            // The details view is a float of its own, so don't leave an
+            // empty one around.
+            scrolled_info.set_visible(info_shown && has_info_content);
         });
     }
 
@@ -520,8 +708,71 @@ impl Popupmenu {
                 .cloned()
                 .unwrap_or_default()
                 .foreground,
+            border: hl_defs
+                .get_hl_group(&HlGroup::FloatBorder)
+                .and_then(|hl| hl.foreground),
+            sbar: hl_defs
+                .get_hl_group(&HlGroup::PmenuSbar)
+                .and_then(|hl| hl.background),
+            thumb: hl_defs
+                .get_hl_group(&HlGroup::PmenuThumb)
+                .and_then(|hl| hl.background),
         };
         self.set_styles(hl_defs);
+        self.update_kind_colors();
+    }
+
+    /// Sets the `glyph` to show for completion items of `kind`, instead of
+    /// the built-in icon. Empty `glyph` restores the icon.
+    pub fn set_kind_glyph(&self, kind: CompletionItemKind, glyph: String) {
+        let mut icons = self.kind_icons.borrow_mut();
+        if glyph.is_empty() {
+            icons.glyphs.remove(&kind);
+        } else {
+            icons.glyphs.insert(kind, glyph);
+        }
+    }
+
+    /// Fetches the kinds' colors from their `GnvimPmenuKind*` highlight
+    /// groups.
+    fn update_kind_colors(&self) {
+        let names = CompletionItemKind::ALL
+            .iter()
+            .map(|kind| format!("'{}'", kind.name()))
+            .collect::<Vec<_>>()
+            .join(",");
+        let expr = format!(
+            "map([{}], {{_, k -> \
+                synIDattr(synIDtrans(hlID('GnvimPmenuKind' . k)), 'fg#')}})",
+            names
+        );
+
+        let nvim = self.nvim.clone();
+        let kind_icons = self.kind_icons.clone();
+        spawn_local(async move {
+            let colors = match nvim.eval(&expr).await {
+                Ok(colors) => colors,
+                Err(err) => {
+                    error!("Failed to get popupmenu kind colors: {}", err);
+                    return;
+                }
+            };
+
+            let colors = colors
+                .as_array()
+                .map(|colors| colors.as_slice())
+                .unwrap_or_default();
+            kind_icons.borrow_mut().colors = CompletionItemKind::ALL
+                .iter()
+                .zip(colors)
+                .filter_map(|(kind, color)| {
+                    let color =
+                        Color::from_hex_string(color.as_str()?.to_string())
+                            .ok()?;
+                    Some((kind.clone(), color))
+                })
+                .collect();
+        });
     }
 
     pub fn set_line_space(&mut self, space: i64, hl_defs: &HlDefs) {
@@ -537,6 +788,11 @@ impl Popupmenu {
         self.info_label.set_attributes(Some(&attrs));
     }
 
+    pub fn set_blend(&mut self, blend: i64, hl_defs: &HlDefs) {
+        self.blend = blend.max(0).min(100);
+        self.set_styles(hl_defs);
+    }
+
     fn set_styles(&self, hl_defs: &HlDefs) {
         if gtk::get_minor_version() < 20 {
             self.set_styles_pre20(hl_defs);
@@ -547,20 +803,24 @@ impl Popupmenu {
 
     fn set_styles_post20(&self, hl_defs: &HlDefs) {
         let (above, below) = calc_line_space(self.line_space);
+        let alpha = 1.0 - self.blend as f64 / 100.0;
 
         let css = format!(
             "{font_wild}
 
             grid, list, row, label {{
                 color: #{normal_fg};
-                background-color: #{normal_bg};
+                background-color: transparent;
                 outline: none;
             }}
 
             #info-label, list {{
-                border: 1px solid #{normal_fg};
+                border: 1px solid #{border};
+                background-color: {normal_bg};
             }}
 
+            {scrollbar}
+
             row {{
                 padding-top: {above}px;
                 padding-bottom: {below}px;
@@ -568,7 +828,10 @@ impl Popupmenu {
 
             row:selected, row:selected > grid, row:selected > grid > label {{
                 color: #{selected_fg};
-                background-color: #{selected_bg};
+            }}
+
+            row:selected {{
+                background-color: {selected_bg};
             }}
 
             box {{
@@ -576,11 +839,25 @@ impl Popupmenu {
             ",
             font_wild = self.font.as_wild_css(FontUnit::Point),
             normal_fg = self.colors.fg.unwrap_or(hl_defs.default_fg).to_hex(),
-            normal_bg = self.colors.bg.unwrap_or(hl_defs.default_bg).to_hex(),
-            selected_bg =
-                self.colors.sel_bg.unwrap_or(hl_defs.default_bg).to_hex(),
+            normal_bg = self
+                .colors
+                .bg
+                .unwrap_or(hl_defs.default_bg)
+                .to_css_rgba(alpha),
+            selected_bg = self
+                .colors
+                .sel_bg
+                .unwrap_or(hl_defs.default_bg)
+                .to_css_rgba(alpha),
             selected_fg =
                 self.colors.sel_fg.unwrap_or(hl_defs.default_fg).to_hex(),
+            border = self
+                .colors
+                .border
+                .or(self.colors.fg)
+                .unwrap_or(hl_defs.default_fg)
+                .to_hex(),
+            scrollbar = scrollbar_css(self.colors.sbar, self.colors.thumb),
             above = above.max(0),
             below = below.max(0),
         );
@@ -590,13 +867,14 @@ impl Popupmenu {
 
     fn set_styles_pre20(&self, hl_defs: &HlDefs) {
         let (above, below) = calc_line_space(self.line_space);
+        let alpha = 1.0 - self.blend as f64 / 100.0;
 
         let css = format!(
             "{font_wild}
 
             GtkGrid, GtkListBox, GtkListBoxRow, GtkLabel {{
                 color: #{normal_fg};
-                background-color: #{normal_bg};
+                background-color: transparent;
                 outline: none;
             }}
 
@@ -608,6 +886,10 @@ impl Popupmenu {
                 border: 1px solid #{normal_fg};
             }}
 
+            #info-label, GtkListBox {{
+                background-color: {normal_bg};
+            }}
+
             GtkListBoxRow {{
                 padding-top: {above}px;
                 padding-bottom: {below}px;
@@ -617,14 +899,24 @@ impl Popupmenu {
             GtkListBoxRow:selected > GtkGrid,
             GtkListBoxRow:selected > GtkGrid > GtkLabel {{
                 color: #{selected_fg};
-                background-color: #{selected_bg};
+            }}
+
+            GtkListBoxRow:selected {{
+                background-color: {selected_bg};
             }}
             ",
             font_wild = self.font.as_wild_css(FontUnit::Pixel),
             normal_fg = self.colors.fg.unwrap_or(hl_defs.default_fg).to_hex(),
-            normal_bg = self.colors.bg.unwrap_or(hl_defs.default_bg).to_hex(),
-            selected_bg =
-                self.colors.sel_bg.unwrap_or(hl_defs.default_bg).to_hex(),
+            normal_bg = self
+                .colors
+                .bg
+                .unwrap_or(hl_defs.default_bg)
+                .to_css_rgba(alpha),
+            selected_bg = self
+                .colors
+                .sel_bg
+                .unwrap_or(hl_defs.default_bg)
+                .to_css_rgba(alpha),
             selected_fg =
                 self.colors.sel_fg.unwrap_or(hl_defs.default_fg).to_hex(),
             above = above.max(0),
@@ -640,6 +932,34 @@ impl Popupmenu {
     }
 }
 
+/// Position (x) and width of the details view next to the menu at `x` that
+/// is `width` wide, so that the view fits in `area_width` (if possible).
+fn details_position(
+    area_width: i32,
+    x: i32,
+    width: i32,
+    details_width: i32,
+    side: PopupmenuDetailsSide,
+) -> (i32, i32) {
+    let room_left = x;
+    let room_right = area_width - (x + width);
+
+    let right = match side {
+        PopupmenuDetailsSide::Left => false,
+        PopupmenuDetailsSide::Right => true,
+        PopupmenuDetailsSide::Auto => {
+            room_right >= details_width || room_right >= room_left
+        }
+    };
+
+    if right {
+        (x + width, details_width.min(room_right).max(0))
+    } else {
+        let width = details_width.min(room_left).max(0);
+        (x - width, width)
+    }
+}
+
 fn ensure_row_visible(list: &gtk::ListBox, row: &gtk::ListBoxRow) {
     if let Some(adj) = list.get_adjustment() {
         let alloc = row.get_allocation();
@@ -649,3 +969,39 @@ fn ensure_row_visible(list: &gtk::ListBox, row: &gtk::ListBoxRow) {
         adj.clamp_page(y.into(), (y + height).into());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_details_position() {
+        use PopupmenuDetailsSide::*;
+
+        // Fits on the right.
+        assert_eq!(details_position(1000, 100, 300, 400, Auto), (400, 400));
+        // More room on the left.
+        assert_eq!(details_position(1000, 500, 300, 400, Auto), (100, 400));
+        // Neither side fits, right has more room.
+        assert_eq!(details_position(1000, 200, 300, 600, Auto), (500, 500));
+        // Sides are kept, but the view is shrunk to fit.
+        assert_eq!(details_position(1000, 500, 300, 400, Right), (800, 200));
+        assert_eq!(details_position(1000, 100, 300, 400, Left), (0, 100));
+    }
+
+    #[test]
+    fn test_max_height() {
+        let mut state = State::new();
+        state.max_height = 500;
+        assert_eq!(state.max_height(Some(20)), 500);
+
+        state.max_rows = Some(10);
+        assert_eq!(state.max_height(Some(20)), 200);
+        assert_eq!(state.max_height(None), 500);
+        // Doesn't overflow with huge limits.
+        state.max_rows = Some(i32::MAX);
+        assert_eq!(state.max_height(Some(20)), 500);
+        state.max_height = i32::MAX;
+        assert_eq!(state.max_height(Some(20)), i32::MAX);
+    }
+}
@@ -0,0 +1,178 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+
+use crate::ui::color::Color;
+use crate::ui::font::{Font, FontUnit};
+
+/// Padding around the content.
+const PADDING: i32 = 16;
+
+/// Side pane that shows a buffer rendered as markdown.
+pub struct Preview {
+    paned: gtk::Paned,
+    css_provider: gtk::CssProvider,
+    scroll: gtk::ScrolledWindow,
+    label: gtk::Label,
+
+    /// Handle of the window whose buffer is shown.
+    win: Option<i64>,
+    /// How far the previewed window is scrolled, from 0 to 1. Applied
+    /// again when the size of the content changes.
+    fraction: Rc<Cell<f64>>,
+
+    fg: Color,
+    bg: Color,
+    font: Font,
+}
+
+impl Preview {
+    /// Creates the preview as the second child of `paned`.
+    pub fn new(paned: &gtk::Paned) -> Self {
+        let css_provider = gtk::CssProvider::new();
+
+        let label = gtk::Label::new(None);
+        label.set_line_wrap(true);
+        label.set_line_wrap_mode(pango::WrapMode::WordChar);
+        label.set_selectable(true);
+        label.set_can_focus(false);
+        label.set_xalign(0.0);
+        label.set_yalign(0.0);
+        label.set_margin_start(PADDING);
+        label.set_margin_end(PADDING);
+        label.set_margin_top(PADDING);
+        label.set_margin_bottom(PADDING);
+
+        let scroll = gtk::ScrolledWindow::new(
+            None::<&gtk::Adjustment>,
+            None::<&gtk::Adjustment>,
+        );
+        scroll.set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+        scroll.add(&label);
+        scroll.set_no_show_all(true);
+
+        add_css_provider!(&css_provider, scroll, label);
+        if let Some(viewport) = scroll.get_child() {
+            add_css_provider!(&css_provider, viewport);
+        }
+
+        paned.pack2(&scroll, false, false);
+
+        let fraction = Rc::new(Cell::new(0.0));
+        let adj = scroll.get_vadjustment().unwrap();
+        adj.connect_changed(clone!(fraction => move |adj| {
+            scroll_to(adj, fraction.get());
+        }));
+
+        Preview {
+            paned: paned.clone(),
+            css_provider,
+            scroll,
+            label,
+
+            win: None,
+            fraction,
+
+            fg: Color::default(),
+            bg: Color::default(),
+            font: Font::default(),
+        }
+    }
+
+    /// Shows `markup` of the buffer in window `win`.
+    pub fn show(&mut self, markup: &str, win: i64) {
+        self.label.set_markup(markup);
+
+        if self.win != Some(win) {
+            self.win = Some(win);
+            self.fraction.set(0.0);
+        }
+
+        if !self.scroll.is_visible() {
+            // Start with half of the width.
+            let width = self.paned.get_allocated_width();
+            self.scroll.show_all();
+            self.paned.set_position(width / 2);
+        }
+    }
+
+    pub fn hide(&mut self) {
+        self.win = None;
+        self.scroll.hide();
+    }
+
+    /// Handle of the window whose buffer is shown, if the preview is shown.
+    pub fn win(&self) -> Option<i64> {
+        self.win
+    }
+
+    /// Scrolls the preview to match the previewed window, which shows lines
+    /// from `topline` to `botline` (exclusive) of `line_count` lines.
+    pub fn set_viewport(&self, topline: u64, botline: u64, line_count: u64) {
+        self.fraction
+            .set(scroll_fraction(topline, botline, line_count));
+        scroll_to(&self.scroll.get_vadjustment().unwrap(), self.fraction.get());
+    }
+
+    pub fn set_colors(&mut self, fg: Color, bg: Color) {
+        self.fg = fg;
+        self.bg = bg;
+        self.load_css();
+    }
+
+    pub fn set_font(&mut self, font: Font) {
+        self.font = font;
+        self.load_css();
+    }
+
+    fn load_css(&self) {
+        let css = format!(
+            "{font}
+
+            * {{
+                color: #{fg};
+                background-color: #{bg};
+            }}",
+            font = self.font.as_wild_css(FontUnit::Point),
+            fg = self.fg.to_hex(),
+            bg = self.bg.to_hex(),
+        );
+        CssProviderExt::load_from_data(&self.css_provider, css.as_bytes())
+            .unwrap();
+    }
+}
+
+/// Scrolls `adj` to `fraction` (from 0 to 1) of its range.
+fn scroll_to(adj: &gtk::Adjustment, fraction: f64) {
+    let max = adj.get_upper() - adj.get_page_size();
+    adj.set_value(adj.get_lower() + fraction * max.max(0.0));
+}
+
+/// Returns how far (from 0 to 1) a window showing lines from `topline` to
+/// `botline` (exclusive) of `line_count` lines is scrolled.
+fn scroll_fraction(topline: u64, botline: u64, line_count: u64) -> f64 {
+    let visible = botline.saturating_sub(topline);
+    let max = line_count.saturating_sub(visible);
+
+    if max == 0 {
+        0.0
+    } else {
+        (topline as f64 / max as f64).min(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scroll_fraction() {
+        assert_eq!(scroll_fraction(0, 20, 100), 0.0);
+        assert_eq!(scroll_fraction(40, 60, 100), 0.5);
+        assert_eq!(scroll_fraction(80, 100, 100), 1.0);
+        // The whole buffer fits.
+        assert_eq!(scroll_fraction(0, 10, 10), 0.0);
+        assert_eq!(scroll_fraction(0, 0, 0), 0.0);
+    }
+}
@@ -0,0 +1,266 @@
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+
+use crate::nvim_gio::GioNeovim;
+use crate::ui::color::{Color, Highlight};
+use crate::ui::common::nvim_error;
+use crate::ui::export::Line;
+use crate::ui::font::Font;
+
+/// Space between the header (and the footer) and the text, in points.
+const HEADER_SPACING: f64 = 12.0;
+/// Space between the line numbers and the text, in points.
+const GUTTER_SPACING: f64 = 12.0;
+/// Color of the header, the footer and the line numbers.
+const GRAY: Color = Color {
+    r: 0.5,
+    g: 0.5,
+    b: 0.5,
+};
+
+/// Buffer being printed.
+struct Document {
+    title: String,
+    /// Line number of the first line.
+    first: u64,
+    /// Pango markup of each line.
+    lines: Vec<String>,
+    font: pango::FontDescription,
+    /// Lines on each page. Set when the printing begins, after the page
+    /// setup is known.
+    pages: RefCell<Vec<Range<usize>>>,
+}
+
+impl Document {
+    fn layout(&self, ctx: &gtk::PrintContext) -> Option<pango::Layout> {
+        let layout = ctx.create_pango_layout()?;
+        layout.set_font_description(Some(&self.font));
+        layout.set_wrap(pango::WrapMode::WordChar);
+        Some(layout)
+    }
+
+    /// Returns the width of the line numbers, including the spacing.
+    fn gutter_width(&self, layout: &pango::Layout) -> f64 {
+        let last = (self.first + self.lines.len() as u64).saturating_sub(1);
+        layout.set_width(-1);
+        layout.set_text(&last.to_string());
+        layout_size(layout).0 + GUTTER_SPACING
+    }
+
+    /// Returns the height of a line without wrapping.
+    fn line_height(layout: &pango::Layout) -> f64 {
+        layout.set_width(-1);
+        layout.set_text("0");
+        layout_size(layout).1
+    }
+
+    fn begin_print(&self, op: &gtk::PrintOperation, ctx: &gtk::PrintContext) {
+        let layout = match self.layout(ctx) {
+            Some(layout) => layout,
+            None => return,
+        };
+
+        let gutter = self.gutter_width(&layout);
+        let header = Document::line_height(&layout) + HEADER_SPACING;
+
+        layout.set_width(to_pango(ctx.get_width() - gutter));
+        let heights = self
+            .lines
+            .iter()
+            .map(|line| {
+                layout.set_markup(line);
+                layout_size(&layout).1
+            })
+            .collect::<Vec<_>>();
+
+        let pages = paginate(&heights, ctx.get_height() - 2.0 * header);
+        op.set_n_pages(pages.len().max(1) as i32);
+        self.pages.replace(pages);
+    }
+
+    fn draw_page(&self, ctx: &gtk::PrintContext, page: usize) {
+        let (cr, layout) = match (ctx.get_cairo_context(), self.layout(ctx)) {
+            (Some(cr), Some(layout)) => (cr, layout),
+            _ => return,
+        };
+        let pages = self.pages.borrow();
+        let lines = pages.get(page).cloned().unwrap_or(0..0);
+        let (width, height) = (ctx.get_width(), ctx.get_height());
+        let line_height = Document::line_height(&layout);
+
+        cr.set_source_rgb(GRAY.r, GRAY.g, GRAY.b);
+
+        // Header.
+        layout.set_text(&self.title);
+        cr.move_to(0.0, 0.0);
+        pangocairo::functions::show_layout(&cr, &layout);
+
+        // Footer.
+        layout.set_text(&format!("{} / {}", page + 1, pages.len()));
+        cr.move_to(
+            (width - layout_size(&layout).0) / 2.0,
+            height - line_height,
+        );
+        pangocairo::functions::show_layout(&cr, &layout);
+
+        let gutter = self.gutter_width(&layout);
+        let mut y = line_height + HEADER_SPACING;
+        for i in lines {
+            layout.set_width(-1);
+            layout.set_text(&(self.first + i as u64).to_string());
+            cr.set_source_rgb(GRAY.r, GRAY.g, GRAY.b);
+            cr.move_to(gutter - GUTTER_SPACING - layout_size(&layout).0, y);
+            pangocairo::functions::show_layout(&cr, &layout);
+
+            layout.set_width(to_pango(width - gutter));
+            layout.set_markup(&self.lines[i]);
+            cr.set_source_rgb(0.0, 0.0, 0.0);
+            cr.move_to(gutter, y);
+            pangocairo::functions::show_layout(&cr, &layout);
+
+            y += layout_size(&layout).1;
+        }
+    }
+}
+
+/// Prints buffers with gtk's print dialog. The print settings (including
+/// the page setup) are kept between prints.
+#[derive(Default)]
+pub struct Printer {
+    settings: Rc<RefCell<Option<gtk::PrintSettings>>>,
+}
+
+impl Printer {
+    /// Opens the print dialog for printing `lines`, starting from line
+    /// number `first` of buffer `title`. The lines are printed on white
+    /// paper, so missing foreground colors are black and missing
+    /// background colors aren't drawn.
+    pub fn print<P: IsA<gtk::Window>>(
+        &self,
+        parent: &P,
+        title: &str,
+        first: u64,
+        lines: &[Line],
+        font: &Font,
+        nvim: &GioNeovim,
+    ) {
+        let doc = Rc::new(Document {
+            title: title.to_string(),
+            first,
+            lines: lines.iter().map(paper_markup).collect(),
+            font: font.as_pango_font(),
+            pages: RefCell::new(vec![]),
+        });
+
+        let op = gtk::PrintOperation::new();
+        op.set_job_name(title);
+        op.set_embed_page_setup(true);
+        op.set_allow_async(true);
+        op.set_print_settings(self.settings.borrow().as_ref());
+
+        op.connect_begin_print(clone!(doc => move |op, ctx| {
+            doc.begin_print(op, ctx);
+        }));
+        op.connect_draw_page(clone!(doc => move |_, ctx, page| {
+            doc.draw_page(ctx, page as usize);
+        }));
+
+        let settings = self.settings.clone();
+        op.connect_done(clone!(nvim => move |op, res| {
+            match res {
+                gtk::PrintOperationResult::Apply => {
+                    settings.replace(op.get_print_settings());
+                }
+                gtk::PrintOperationResult::Error => {
+                    if let Err(err) = op.get_error() {
+                        nvim_error(&nvim, format!("Failed to print: {}", err));
+                    }
+                }
+                _ => {}
+            }
+        }));
+
+        if let Err(err) =
+            op.run(gtk::PrintOperationAction::PrintDialog, Some(parent))
+        {
+            nvim_error(nvim, format!("Failed to print: {}", err));
+        }
+    }
+}
+
+/// Returns the pango markup of `line` for printing on white paper.
+fn paper_markup(line: &Line) -> String {
+    let black = Color::default();
+    let white = Color::from_u64(0xffffff);
+
+    line.iter()
+        .map(|(text, hl)| {
+            let fg = hl.foreground.unwrap_or(black);
+            let bg = hl.background.unwrap_or(white);
+            let (fg, bg) = if hl.reverse { (bg, fg) } else { (fg, bg) };
+
+            Highlight {
+                foreground: Some(fg),
+                background: Some(bg),
+                reverse: false,
+                ..*hl
+            }
+            .pango_markup(text, &black, &white, &fg)
+        })
+        .collect()
+}
+
+/// Returns the size (width, height) of `layout` in points.
+fn layout_size(layout: &pango::Layout) -> (f64, f64) {
+    let (w, h) = layout.get_size();
+    (
+        f64::from(w) / f64::from(pango::SCALE),
+        f64::from(h) / f64::from(pango::SCALE),
+    )
+}
+
+fn to_pango(points: f64) -> i32 {
+    (points * f64::from(pango::SCALE)) as i32
+}
+
+/// Splits lines of `heights` to pages of `page_height`. Lines taller than
+/// a page get a page of their own.
+fn paginate(heights: &[f64], page_height: f64) -> Vec<Range<usize>> {
+    let mut pages = vec![];
+    let mut start = 0;
+    let mut used = 0.0;
+
+    for (i, height) in heights.iter().enumerate() {
+        if i > start && used + height > page_height {
+            pages.push(start..i);
+            start = i;
+            used = 0.0;
+        }
+        used += height;
+    }
+
+    if start < heights.len() {
+        pages.push(start..heights.len());
+    }
+
+    pages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paginate() {
+        assert_eq!(paginate(&[10.0; 5], 20.0), vec![0..2, 2..4, 4..5]);
+        assert_eq!(paginate(&[10.0; 4], 20.0), vec![0..2, 2..4]);
+        // Wrapped lines.
+        assert_eq!(paginate(&[20.0, 30.0, 10.0], 40.0), vec![0..1, 1..3]);
+        // Too tall for a page.
+        assert_eq!(paginate(&[50.0, 10.0], 40.0), vec![0..1, 1..2]);
+        assert_eq!(paginate(&[], 40.0), Vec::<Range<usize>>::new());
+    }
+}
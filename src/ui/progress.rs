@@ -0,0 +1,143 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use glib::ToVariant;
+use gtk::prelude::*;
+use log::error;
+
+use crate::ui::variant::{dict, tuple};
+
+/// How often (in milliseconds) the spinner turns when the progress isn't
+/// known.
+const SPIN_INTERVAL: u32 = 150;
+/// Frames of the spinner in the title.
+const SPINNER: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// Interface of the launcher entry (i.e. taskbar) api, supported by e.g.
+/// Unity, KDE Plasma and Dash to Dock.
+const LAUNCHER_INTERFACE: &str = "com.canonical.Unity.LauncherEntry";
+const LAUNCHER_PATH: &str = "/org/gnvim/LauncherEntry";
+/// Our desktop file, which the launcher entry is of.
+const LAUNCHER_APP_URI: &str = "application://gnvim.desktop";
+
+/// Progress of a long operation (e.g. a build), shown in the window's
+/// title and, through the launcher entry api, in the taskbar.
+#[derive(Clone)]
+pub struct Progress {
+    window: gtk::ApplicationWindow,
+    /// Title of the window, without the progress.
+    title: Rc<RefCell<String>>,
+    progress: Rc<Cell<Option<f64>>>,
+    /// Current frame of the spinner.
+    frame: Rc<Cell<usize>>,
+    /// Timer that turns the spinner.
+    spin: Rc<RefCell<Option<glib::SourceId>>>,
+}
+
+impl Progress {
+    pub fn new(window: &gtk::ApplicationWindow) -> Self {
+        Progress {
+            window: window.clone(),
+            title: Rc::new(RefCell::new(
+                window.get_title().map(String::from).unwrap_or_default(),
+            )),
+            progress: Rc::new(Cell::new(None)),
+            frame: Rc::new(Cell::new(0)),
+            spin: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// Sets the title of the window, which is shown after the progress.
+    pub fn set_title(&self, title: &str) {
+        self.title.replace(title.to_string());
+        self.update_title();
+    }
+
+    /// Sets the progress, from 0.0 to 1.0. Negative progress means that
+    /// the progress isn't known, and a spinner is shown instead. `None`
+    /// clears the progress.
+    pub fn set_progress(&self, progress: Option<f64>) {
+        if let Some(id) = self.spin.borrow_mut().take() {
+            glib::source::source_remove(id);
+        }
+
+        self.progress.set(progress);
+        self.update_title();
+        self.update_launcher();
+
+        if progress.map_or(false, |p| p < 0.0) {
+            let this = self.clone();
+            let id = gtk::timeout_add(SPIN_INTERVAL, move || {
+                this.frame.set((this.frame.get() + 1) % SPINNER.len());
+                this.update_title();
+                Continue(true)
+            });
+            self.spin.replace(Some(id));
+        }
+    }
+
+    fn update_title(&self) {
+        self.window.set_title(&progress_title(
+            &self.title.borrow(),
+            self.progress.get(),
+            self.frame.get(),
+        ));
+    }
+
+    /// Shows the progress on our taskbar entry. The launcher entry api
+    /// can't show unknown progress, so it's then hidden.
+    fn update_launcher(&self) {
+        let conn = match self
+            .window
+            .get_application()
+            .and_then(|app| app.get_dbus_connection())
+        {
+            Some(conn) => conn,
+            None => return,
+        };
+
+        let progress = self.progress.get().filter(|p| *p >= 0.0);
+        let props = dict(&[
+            ("progress", progress.unwrap_or(0.0).min(1.0).to_variant()),
+            ("progress-visible", progress.is_some().to_variant()),
+        ]);
+
+        if let Err(err) = conn.emit_signal(
+            None,
+            LAUNCHER_PATH,
+            LAUNCHER_INTERFACE,
+            "Update",
+            Some(&tuple(&[LAUNCHER_APP_URI.to_variant(), props])),
+        ) {
+            error!("Failed to update the launcher entry: {}", err);
+        }
+    }
+}
+
+/// Returns `title` with `progress` (see `Progress::set_progress`) in front
+/// of it. Unknown progress is shown as frame `frame` of the spinner.
+fn progress_title(title: &str, progress: Option<f64>, frame: usize) -> String {
+    match progress {
+        None => title.to_string(),
+        Some(progress) if progress < 0.0 => {
+            format!("{} {}", SPINNER[frame % SPINNER.len()], title)
+        }
+        Some(progress) => {
+            format!("[{:.0}%] {}", progress.min(1.0) * 100.0, title)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_title() {
+        assert_eq!(progress_title("foo", None, 3), "foo");
+        assert_eq!(progress_title("foo", Some(0.424), 0), "[42%] foo");
+        assert_eq!(progress_title("foo", Some(1.5), 0), "[100%] foo");
+        assert_eq!(progress_title("foo", Some(-1.0), 0), "⠋ foo");
+        assert_eq!(progress_title("foo", Some(-1.0), 11), "⠙ foo");
+    }
+}
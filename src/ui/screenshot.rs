@@ -0,0 +1,70 @@
+use gtk::prelude::*;
+
+/// What to take a screenshot of.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScreenshotTarget {
+    /// The whole window (without the window manager's decorations).
+    Window,
+    /// A grid by id, or the current grid.
+    Grid(Option<i64>),
+    /// Cells (row, col, rows, cols) of the current grid.
+    Cells(u64, u64, u64, u64),
+}
+
+/// Paints an image of `width` and `height` (in logical pixels) with `f`.
+/// The image is `scale` times larger in device pixels, so it matches what
+/// is shown on hidpi screens.
+pub fn paint<F: FnOnce(&cairo::Context)>(
+    width: i32,
+    height: i32,
+    scale: i32,
+    f: F,
+) -> Result<gdk_pixbuf::Pixbuf, String> {
+    let (width, height) =
+        match (width.checked_mul(scale), height.checked_mul(scale)) {
+            (Some(width), Some(height)) => (width, height),
+            _ => return Err(String::from("Screenshot is too large")),
+        };
+    if width <= 0 || height <= 0 {
+        return Err(String::from("Nothing to take a screenshot of"));
+    }
+
+    let surface =
+        cairo::ImageSurface::create(cairo::Format::ARgb32, width, height)
+            .map_err(|err| err.to_string())?;
+    {
+        let cr = cairo::Context::new(&surface);
+        cr.scale(f64::from(scale), f64::from(scale));
+        f(&cr);
+    }
+
+    gdk::pixbuf_get_from_surface(&surface, 0, 0, width, height)
+        .ok_or_else(|| String::from("Failed to read the screenshot"))
+}
+
+/// Returns an image of `widget` as it's shown.
+pub fn widget(widget: &gtk::Widget) -> Result<gdk_pixbuf::Pixbuf, String> {
+    paint(
+        widget.get_allocated_width(),
+        widget.get_allocated_height(),
+        widget.get_scale_factor(),
+        |cr| widget.draw(cr),
+    )
+}
+
+/// Saves `image` as a png to `path`, or copies it to the clipboard if
+/// there's no path.
+pub fn save(
+    image: &gdk_pixbuf::Pixbuf,
+    path: Option<&str>,
+) -> Result<(), String> {
+    match path {
+        Some(path) => {
+            image.savev(path, "png", &[]).map_err(|err| err.to_string())
+        }
+        None => {
+            gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_image(image);
+            Ok(())
+        }
+    }
+}
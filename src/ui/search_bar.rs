@@ -0,0 +1,298 @@
+use gdk::keys::constants as key;
+use gtk::prelude::*;
+use log::error;
+use nvim_rs::Value;
+
+use crate::nvim_gio::GioNeovim;
+use crate::ui::cmdline::search_count_from_value;
+use crate::ui::color::{HlDefs, HlGroup};
+use crate::ui::common::{popup_css, spawn_local, PopupColors};
+use crate::ui::font::Font;
+
+/// Find bar (like in GUI editors) on top of the grids. Typing searches in
+/// nvim incrementally, like `/` does, and the matches can be replaced.
+pub struct SearchBar {
+    css_provider: gtk::CssProvider,
+    frame: gtk::Frame,
+    entry: gtk::SearchEntry,
+    replace_entry: gtk::Entry,
+    count: gtk::Label,
+
+    nvim: GioNeovim,
+
+    font: Font,
+}
+
+impl SearchBar {
+    pub fn new(parent: &gtk::Overlay, nvim: GioNeovim) -> Self {
+        let css_provider = gtk::CssProvider::new();
+
+        let entry = gtk::SearchEntry::new();
+        entry.set_placeholder_text(Some("Search"));
+        entry.set_hexpand(true);
+        let count = gtk::Label::new(None);
+        count.set_widget_name("count");
+        count.set_width_chars(12);
+        let prev = button("go-up-symbolic", "Previous match (Shift+Enter)");
+        let next = button("go-down-symbolic", "Next match (Enter)");
+        let close = button("window-close-symbolic", "Close (Esc)");
+
+        let replace_entry = gtk::Entry::new();
+        replace_entry.set_placeholder_text(Some("Replace"));
+        let replace = gtk::Button::with_label("Replace");
+        replace.set_can_focus(false);
+        let replace_all = gtk::Button::with_label("All");
+        replace_all.set_can_focus(false);
+
+        let grid = gtk::Grid::new();
+        grid.set_row_spacing(4);
+        grid.set_column_spacing(4);
+        grid.attach(&entry, 0, 0, 1, 1);
+        grid.attach(&count, 1, 0, 1, 1);
+        grid.attach(&prev, 2, 0, 1, 1);
+        grid.attach(&next, 3, 0, 1, 1);
+        grid.attach(&close, 4, 0, 1, 1);
+        grid.attach(&replace_entry, 0, 1, 2, 1);
+        grid.attach(&replace, 2, 1, 2, 1);
+        grid.attach(&replace_all, 4, 1, 1, 1);
+
+        let frame = gtk::Frame::new(None);
+        frame.set_widget_name("search-bar");
+        frame.add(&grid);
+        frame.set_halign(gtk::Align::End);
+        frame.set_valign(gtk::Align::Start);
+        frame.set_margin_end(10);
+        frame.set_margin_top(10);
+        frame.set_no_show_all(true);
+        parent.add_overlay(&frame);
+
+        add_css_provider!(
+            &css_provider,
+            frame,
+            grid,
+            entry,
+            count,
+            prev,
+            next,
+            close,
+            replace_entry,
+            replace,
+            replace_all
+        );
+
+        entry.connect_search_changed(clone!(nvim, count => move |entry| {
+            let pattern = entry.get_text().to_string();
+            search(
+                &nvim,
+                &count,
+                Some(pattern.clone()),
+                "gnvim#search#update",
+                vec![pattern.into()],
+            );
+        }));
+        entry.connect_activate(clone!(nvim, count => move |_| {
+            search(&nvim, &count, None, "gnvim#search#next", vec![1.into()]);
+        }));
+        next.connect_clicked(clone!(nvim, count => move |_| {
+            search(&nvim, &count, None, "gnvim#search#next", vec![1.into()]);
+        }));
+        prev.connect_clicked(clone!(nvim, count => move |_| {
+            search(&nvim, &count, None, "gnvim#search#next", vec![0.into()]);
+        }));
+
+        let replace_with = {
+            let (nvim, count) = (nvim.clone(), count.clone());
+            let replace_entry = replace_entry.clone();
+            move |all: i64| {
+                let text = replace_entry.get_text().to_string();
+                search(
+                    &nvim,
+                    &count,
+                    None,
+                    "gnvim#search#replace",
+                    vec![text.into(), all.into()],
+                );
+            }
+        };
+        replace_entry.connect_activate(clone!(replace_with => move |_| {
+            replace_with(0)
+        }));
+        replace.connect_clicked(clone!(replace_with => move |_| {
+            replace_with(0)
+        }));
+        replace_all.connect_clicked(move |_| replace_with(1));
+
+        close.connect_clicked(clone!(frame, nvim => move |_| {
+            hide(&frame, &nvim);
+        }));
+        entry.connect_key_press_event(
+            clone!(frame, nvim, count => move |_, e| {
+                key_pressed(e, &frame, &nvim, &count)
+            }),
+        );
+        replace_entry.connect_key_press_event(
+            clone!(frame, nvim, count => move |_, e| {
+                key_pressed(e, &frame, &nvim, &count)
+            }),
+        );
+
+        SearchBar {
+            css_provider,
+            frame,
+            entry,
+            replace_entry,
+            count,
+            nvim,
+            font: Font::default(),
+        }
+    }
+
+    pub fn show(&self) {
+        self.count.set_text("");
+        self.frame.show_all();
+        self.entry.grab_focus();
+        // Typing replaces the previous search.
+        self.entry.select_region(0, -1);
+    }
+
+    pub fn hide(&self) {
+        hide(&self.frame, &self.nvim);
+    }
+
+    /// Returns true if the keyboard focus is in the search bar, so that the
+    /// keys should go to it instead of nvim.
+    pub fn has_focus(&self) -> bool {
+        self.frame.is_visible()
+            && (self.entry.has_focus() || self.replace_entry.has_focus())
+    }
+
+    pub fn set_font(&mut self, font: Font, hl_defs: &HlDefs) {
+        self.font = font;
+        self.set_colors(hl_defs);
+    }
+
+    pub fn set_colors(&self, hl_defs: &HlDefs) {
+        let hl = hl_defs.get_hl_group(&HlGroup::Pmenu);
+        let fg = hl
+            .and_then(|hl| hl.foreground)
+            .unwrap_or(hl_defs.default_fg);
+        let bg = hl
+            .and_then(|hl| hl.background)
+            .unwrap_or(hl_defs.default_bg);
+        // Selected text like `Visual`, and the match count like `Search`.
+        let visual = hl_defs.get_hl_group(&HlGroup::Visual);
+        let search = hl_defs.get_hl_group(&HlGroup::Search);
+        let colors = PopupColors {
+            fg,
+            bg,
+            sel_fg: visual.and_then(|hl| hl.foreground).unwrap_or(bg),
+            sel_bg: visual.and_then(|hl| hl.background).unwrap_or(fg),
+        };
+
+        let css = popup_css(
+            &self.font,
+            colors,
+            &format!(
+                "#search-bar {{
+                    padding: 6px;
+                }}
+
+                #count {{
+                    color: #{count_fg};
+                    background: #{count_bg};
+                }}",
+                count_fg =
+                    search.and_then(|hl| hl.foreground).unwrap_or(fg).to_hex(),
+                count_bg =
+                    search.and_then(|hl| hl.background).unwrap_or(bg).to_hex(),
+            ),
+        );
+        CssProviderExt::load_from_data(&self.css_provider, css.as_bytes())
+            .unwrap();
+    }
+}
+
+fn button(icon: &str, tooltip: &str) -> gtk::Button {
+    let button =
+        gtk::Button::from_icon_name(Some(icon), gtk::IconSize::SmallToolbar);
+    button.set_tooltip_text(Some(tooltip));
+    // Keep the focus in the entries.
+    button.set_can_focus(false);
+    button
+}
+
+/// Handles the keys that the entries don't: escape hides the search bar,
+/// and shift + enter goes to the previous match.
+fn key_pressed(
+    e: &gdk::EventKey,
+    frame: &gtk::Frame,
+    nvim: &GioNeovim,
+    count: &gtk::Label,
+) -> Inhibit {
+    match e.get_keyval() {
+        key::Escape => hide(frame, nvim),
+        key::Return | key::KP_Enter
+            if e.get_state().contains(gdk::ModifierType::SHIFT_MASK) =>
+        {
+            search(nvim, count, None, "gnvim#search#next", vec![0.into()]);
+        }
+        _ => return Inhibit(false),
+    }
+
+    Inhibit(true)
+}
+
+/// Hides the search bar, which gives the keys back to nvim.
+fn hide(frame: &gtk::Frame, nvim: &GioNeovim) {
+    frame.hide();
+
+    let nvim = nvim.clone();
+    spawn_local(async move {
+        if let Err(err) = nvim.call_function("gnvim#search#done", vec![]).await
+        {
+            error!("Failed to end search: {}", err);
+        }
+    });
+}
+
+/// Calls the search function `fname` in nvim, and shows the number of
+/// matches it returns in `count`. If there's a `pattern`, it becomes the
+/// last search pattern first.
+fn search(
+    nvim: &GioNeovim,
+    count: &gtk::Label,
+    pattern: Option<String>,
+    fname: &str,
+    args: Vec<Value>,
+) {
+    let nvim = nvim.clone();
+    let count = count.clone();
+    let fname = fname.to_string();
+    spawn_local(async move {
+        // Functions can't change the last search pattern or 'hlsearch'
+        // (see `:h function-search-undo`), so they're set separately.
+        if let Some(pattern) = pattern {
+            let args = vec!["/".into(), pattern.into()];
+            if let Err(err) = nvim.call_function("setreg", args).await {
+                error!("Failed to set the search pattern: {}", err);
+                return;
+            }
+        }
+
+        match nvim.call_function(&fname, args).await {
+            Ok(res) if res.as_map().map_or(false, |map| !map.is_empty()) => {
+                count.set_text(&search_count_from_value(&res))
+            }
+            Ok(_) => count.set_text(""),
+            Err(err) => {
+                error!("Failed to search: {}", err);
+                count.set_text("");
+                return;
+            }
+        }
+
+        if let Err(err) = nvim.command("let v:hlsearch = 1").await {
+            error!("Failed to highlight the search matches: {}", err);
+        }
+    });
+}
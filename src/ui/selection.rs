@@ -0,0 +1,164 @@
+use log::error;
+use rmpv::Value;
+
+use crate::nvim_gio::{GioNeovim, RequestResult};
+use crate::ui::common::spawn_local;
+
+/// Sets the X11/Wayland primary selection to the text of nvim's current
+/// visual selection, if there is one.
+pub async fn update_primary_selection(nvim: &GioNeovim) {
+    match visual_selection(nvim).await {
+        Ok(Some(text)) => {
+            gtk::Clipboard::get(&gdk::SELECTION_PRIMARY).set_text(&text);
+        }
+        Ok(None) => {}
+        Err(err) => error!("Failed to get visual selection: {}", err),
+    }
+}
+
+/// Pastes the primary selection at the given cell of `grid`.
+pub fn paste_primary_selection(
+    nvim: &GioNeovim,
+    grid: i64,
+    row: u64,
+    col: u64,
+) {
+    let clipboard = gtk::Clipboard::get(&gdk::SELECTION_PRIMARY);
+    clipboard.request_text(clone!(nvim => move |_, text| {
+        let text = match text {
+            Some(text) => text.to_string(),
+            None => return,
+        };
+
+        let nvim = nvim.clone();
+        spawn_local(async move {
+            if let Err(err) = paste_at(&nvim, grid, row, col, &text).await {
+                error!("Failed to paste primary selection: {}", err);
+            }
+        });
+    }));
+}
+
+async fn paste_at(
+    nvim: &GioNeovim,
+    grid: i64,
+    row: u64,
+    col: u64,
+    text: &str,
+) -> RequestResult<()> {
+    // Move the cursor to the clicked cell first.
+    for action in &["press", "release"] {
+        nvim.input_mouse("left", action, "", grid, row as i64, col as i64)
+            .await?;
+    }
+
+    nvim.paste(text, true, -1).await?;
+    Ok(())
+}
+
+/// Returns the text of the current visual selection. Blockwise selections
+/// are not supported.
+async fn visual_selection(nvim: &GioNeovim) -> RequestResult<Option<String>> {
+    let mode = nvim
+        .get_mode()
+        .await?
+        .into_iter()
+        .find(|(k, _)| k.as_str() == Some("mode"))
+        .and_then(|(_, v)| v.as_str().map(String::from))
+        .unwrap_or_default();
+
+    let linewise = match mode.as_str() {
+        "v" => false,
+        "V" => true,
+        _ => return Ok(None),
+    };
+
+    let start = nvim.call_function("getpos", vec!["v".into()]).await?;
+    let end = nvim.call_function("getpos", vec![".".into()]).await?;
+    let (start, end) = match (parse_pos(&start), parse_pos(&end)) {
+        (Some(start), Some(end)) if start <= end => (start, end),
+        (Some(start), Some(end)) => (end, start),
+        _ => return Ok(None),
+    };
+
+    let buf = nvim.get_current_buf().await?;
+    let lines = if linewise {
+        let mut lines = nvim
+            .request(buf.get_lines(start.0, end.0 + 1, true))
+            .await?;
+        // Linewise text ends with a newline, like when yanked.
+        lines.push(String::new());
+        lines
+    } else {
+        let end_line =
+            nvim.request(buf.get_lines(end.0, end.0 + 1, true)).await?;
+        let end_col = end_line
+            .first()
+            .map(|line| char_end(line, end.1 as usize))
+            .unwrap_or(0);
+        nvim.request(buf.get_text(
+            start.0,
+            start.1,
+            end.0,
+            end_col as i64,
+            vec![],
+        ))
+        .await?
+    };
+
+    Ok(Some(lines.join("\n")))
+}
+
+/// Parses the result of `getpos()` into zero based (line, byte column).
+fn parse_pos(pos: &Value) -> Option<(i64, i64)> {
+    let pos = pos.as_array()?;
+    let line = pos.get(1)?.as_i64()?;
+    let col = pos.get(2)?.as_i64()?;
+
+    Some((line - 1, (col - 1).max(0)))
+}
+
+/// Returns the (exclusive) byte column where the character starting at
+/// byte `col` of `line` ends.
+fn char_end(line: &str, col: usize) -> usize {
+    line.get(col..)
+        .and_then(|rest| rest.chars().next())
+        .map(|c| col + c.len_utf8())
+        .unwrap_or_else(|| line.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pos() {
+        let pos = Value::from(vec![
+            Value::from(0),
+            Value::from(3),
+            Value::from(5),
+            Value::from(0),
+        ]);
+        assert_eq!(parse_pos(&pos), Some((2, 4)));
+
+        // Empty lines have column 0.
+        let pos = Value::from(vec![
+            Value::from(0),
+            Value::from(1),
+            Value::from(0),
+            Value::from(0),
+        ]);
+        assert_eq!(parse_pos(&pos), Some((0, 0)));
+
+        assert_eq!(parse_pos(&Value::from(1)), None);
+    }
+
+    #[test]
+    fn test_char_end() {
+        assert_eq!(char_end("foo bar", 0), 1);
+        assert_eq!(char_end("foo bar", 6), 7);
+        assert_eq!(char_end("föö", 1), 3);
+        assert_eq!(char_end("", 0), 0);
+        assert_eq!(char_end("foo", 10), 3);
+    }
+}
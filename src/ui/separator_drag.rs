@@ -0,0 +1,328 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use gtk::prelude::*;
+use log::error;
+use nvim_rs::Window as NvimWindow;
+
+use crate::nvim_gio::{GioNeovim, GioWriter};
+use crate::ui::color::Color;
+use crate::ui::common::spawn_local;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SeparatorKind {
+    /// Separator on the right side of a window, dragging it changes the
+    /// window's width.
+    Vertical,
+    /// Status line below a window, dragging it changes the window's height.
+    Horizontal,
+}
+
+/// Position and size of a window on the base grid, in cells.
+#[derive(Debug, Clone, Copy)]
+pub struct WinRect {
+    pub row: u64,
+    pub col: u64,
+    pub width: u64,
+    pub height: u64,
+}
+
+/// Separator of a window, found with `hit_test`.
+pub struct Separator {
+    pub win: NvimWindow<GioWriter>,
+    pub kind: SeparatorKind,
+    pub rect: WinRect,
+    /// Size of the base grid's cells (width, height).
+    pub cell: (f64, f64),
+}
+
+impl Separator {
+    /// Returns the window's width or height, depending on the separator.
+    fn size(&self) -> u64 {
+        match self.kind {
+            SeparatorKind::Vertical => self.rect.width,
+            SeparatorKind::Horizontal => self.rect.height,
+        }
+    }
+
+    /// Returns the area of the separator (x, y, width, height) when it's
+    /// dragged by `offset` pixels.
+    fn area(&self, offset: f64) -> (f64, f64, f64, f64) {
+        let (cw, ch) = self.cell;
+        let r = &self.rect;
+        match self.kind {
+            SeparatorKind::Vertical => (
+                (r.col + r.width) as f64 * cw + offset,
+                r.row as f64 * ch,
+                cw,
+                r.height as f64 * ch,
+            ),
+            SeparatorKind::Horizontal => (
+                r.col as f64 * cw,
+                (r.row + r.height) as f64 * ch + offset,
+                r.width as f64 * cw,
+                ch,
+            ),
+        }
+    }
+}
+
+/// Drag in progress.
+struct Drag {
+    separator: Separator,
+    /// Last size sent to nvim.
+    size: u64,
+    /// If the pointer has moved far enough for this to be a drag, instead
+    /// of a click that nvim should get.
+    claimed: bool,
+}
+
+/// Resizes the windows by dragging their separators (and status lines)
+/// with the mouse. While dragging, the separator follows the pointer and
+/// the window is resized whenever the pointer crosses a cell, without
+/// going through nvim's own (cell by cell) mouse handling.
+pub struct SeparatorDrag {
+    nvim: GioNeovim,
+    gesture: gtk::GestureDrag,
+    preview: gtk::DrawingArea,
+    color: Rc<Cell<Color>>,
+}
+
+impl SeparatorDrag {
+    pub fn new(overlay: &gtk::Overlay, nvim: GioNeovim) -> Self {
+        let color = Rc::new(Cell::new(Color::default()));
+        let preview = gtk::DrawingArea::new();
+        preview.set_halign(gtk::Align::Start);
+        preview.set_valign(gtk::Align::Start);
+        preview.set_no_show_all(true);
+        preview.connect_draw(clone!(color => move |da, cr| {
+            let color = color.get();
+            cr.set_source_rgba(color.r, color.g, color.b, 0.8);
+            cr.rectangle(
+                0.0,
+                0.0,
+                f64::from(da.get_allocated_width()),
+                f64::from(da.get_allocated_height()),
+            );
+            cr.fill();
+            Inhibit(false)
+        }));
+        overlay.add_overlay(&preview);
+        overlay.set_overlay_pass_through(&preview, true);
+
+        // The gesture runs before the grids get the events, so that nvim
+        // doesn't start its own drag once the pointer has moved past the
+        // drag threshold.
+        let gesture = gtk::GestureDrag::new(overlay);
+        gesture.set_button(1);
+        gesture.set_propagation_phase(gtk::PropagationPhase::Capture);
+
+        SeparatorDrag {
+            nvim,
+            gesture,
+            preview,
+            color,
+        }
+    }
+
+    /// Connects the drag handlers. `hit_test` returns the separator at a
+    /// position (x, y) of the base grid.
+    pub fn connect_hit_test<F>(&self, hit_test: F)
+    where
+        F: Fn(f64, f64) -> Option<Separator> + 'static,
+    {
+        let nvim = &self.nvim;
+        let preview = &self.preview;
+        let drag: Rc<RefCell<Option<Drag>>> = Rc::new(RefCell::new(None));
+
+        self.gesture.connect_drag_begin(
+            clone!(drag, preview => move |gesture, x, y| {
+                match hit_test(x, y) {
+                    // Clicks go to nvim, so the sequence is only claimed
+                    // once the pointer moves far enough.
+                    Some(separator) => {
+                        move_preview(&preview, separator.area(0.0));
+                        drag.replace(Some(Drag {
+                            size: separator.size(),
+                            separator,
+                            claimed: false,
+                        }));
+                    }
+                    None => {
+                        gesture.set_state(gtk::EventSequenceState::Denied);
+                    }
+                }
+            }),
+        );
+
+        self.gesture.connect_drag_update(
+            clone!(nvim, drag, preview => move |gesture, x, y| {
+                let mut drag = drag.borrow_mut();
+                let drag = match *drag {
+                    Some(ref mut drag) => drag,
+                    None => return,
+                };
+
+                if !drag.claimed {
+                    if !past_drag_threshold(x, y) {
+                        return;
+                    }
+
+                    drag.claimed = true;
+                    gesture.set_state(gtk::EventSequenceState::Claimed);
+                    preview.show();
+                }
+
+                let sep = &drag.separator;
+                let (offset, cell) = match sep.kind {
+                    SeparatorKind::Vertical => (x, sep.cell.0),
+                    SeparatorKind::Horizontal => (y, sep.cell.1),
+                };
+                let size = dragged_size(sep.size(), offset, cell);
+
+                // Don't drag the preview further than the window can shrink.
+                let min = (1.0 - sep.size() as f64) * cell;
+                move_preview(&preview, sep.area(offset.max(min)));
+
+                if size != drag.size {
+                    drag.size = size;
+                    set_size(&nvim, &sep.win, sep.kind, size);
+                }
+            }),
+        );
+
+        self.gesture
+            .connect_drag_end(clone!(drag, preview => move |_, _, _| {
+                drag.replace(None);
+                preview.hide();
+            }));
+    }
+
+    /// Sets the color of the separator while it's dragged.
+    pub fn set_color(&self, color: Color) {
+        self.color.set(color);
+        self.preview.queue_draw();
+    }
+}
+
+/// Returns true if a drag by `x`, `y` pixels is past gtk's drag threshold.
+fn past_drag_threshold(x: f64, y: f64) -> bool {
+    let threshold = gtk::Settings::get_default()
+        .map_or(8, |settings| settings.get_property_gtk_dnd_drag_threshold());
+    x.hypot(y) >= f64::from(threshold)
+}
+
+fn move_preview(
+    preview: &gtk::DrawingArea,
+    (x, y, w, h): (f64, f64, f64, f64),
+) {
+    preview.set_margin_start(x.floor() as i32);
+    preview.set_margin_top(y.floor() as i32);
+    preview.set_size_request(w.ceil() as i32, h.ceil() as i32);
+}
+
+fn set_size(
+    nvim: &GioNeovim,
+    win: &NvimWindow<GioWriter>,
+    kind: SeparatorKind,
+    size: u64,
+) {
+    let nvim = nvim.clone();
+    let win = win.clone();
+    spawn_local(async move {
+        let res = match kind {
+            SeparatorKind::Vertical => {
+                nvim.request(win.set_width(size as i64)).await
+            }
+            SeparatorKind::Horizontal => {
+                nvim.request(win.set_height(size as i64)).await
+            }
+        };
+        if let Err(err) = res {
+            error!("Failed to resize window: {}", err);
+        }
+    });
+}
+
+/// Returns the size of a window of `size` cells, when its separator is
+/// dragged by `offset` pixels. The window keeps at least one cell.
+pub fn dragged_size(size: u64, offset: f64, cell: f64) -> u64 {
+    let cells = (offset / cell).round() as i64;
+    (size as i64 + cells).max(1) as u64
+}
+
+/// Returns the index of the window in `windows` whose separator is at
+/// `row`, `col`, and the kind of the separator.
+pub fn hit_test(
+    windows: &[WinRect],
+    row: u64,
+    col: u64,
+) -> Option<(usize, SeparatorKind)> {
+    windows.iter().enumerate().find_map(|(i, w)| {
+        let rows = w.row..w.row + w.height;
+        let cols = w.col..w.col + w.width;
+
+        if col == w.col + w.width && rows.contains(&row) {
+            Some((i, SeparatorKind::Vertical))
+        } else if row == w.row + w.height && cols.contains(&col) {
+            Some((i, SeparatorKind::Horizontal))
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(row: u64, col: u64, width: u64, height: u64) -> WinRect {
+        WinRect {
+            row,
+            col,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn test_hit_test() {
+        // Two windows side by side, the left one split horizontally.
+        let windows =
+            vec![rect(0, 0, 40, 10), rect(11, 0, 40, 10), rect(0, 41, 39, 21)];
+
+        assert_eq!(
+            hit_test(&windows, 5, 40),
+            Some((0, SeparatorKind::Vertical))
+        );
+        assert_eq!(
+            hit_test(&windows, 15, 40),
+            Some((1, SeparatorKind::Vertical))
+        );
+        assert_eq!(
+            hit_test(&windows, 10, 20),
+            Some((0, SeparatorKind::Horizontal))
+        );
+        // Status line of the bottom windows.
+        assert_eq!(
+            hit_test(&windows, 21, 5),
+            Some((1, SeparatorKind::Horizontal))
+        );
+        assert_eq!(
+            hit_test(&windows, 21, 60),
+            Some((2, SeparatorKind::Horizontal))
+        );
+        // Text.
+        assert_eq!(hit_test(&windows, 5, 20), None);
+        assert_eq!(hit_test(&windows, 5, 60), None);
+    }
+
+    #[test]
+    fn test_dragged_size() {
+        assert_eq!(dragged_size(10, 0.0, 8.0), 10);
+        assert_eq!(dragged_size(10, 3.0, 8.0), 10);
+        assert_eq!(dragged_size(10, 5.0, 8.0), 11);
+        assert_eq!(dragged_size(10, -18.0, 8.0), 8);
+        assert_eq!(dragged_size(10, -200.0, 8.0), 1);
+    }
+}
@@ -0,0 +1,363 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use gdk::prelude::GdkContextExt;
+use gtk::prelude::*;
+
+use crate::ui::color::{Color, HlDefs, HlGroup};
+
+/// Width of the bar that marks git changes next to a diagnostic's icon.
+const GIT_BAR_WIDTH: f64 = 2.0;
+
+/// Kind of a sign in the gutter, see `GnvimEvent::SetSigns`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SignKind {
+    // In the order of priority, the least important first.
+    GitAdd,
+    GitChange,
+    GitDelete,
+    Hint,
+    Info,
+    Warn,
+    Error,
+}
+
+impl SignKind {
+    pub fn from_string(kind: &str) -> Option<Self> {
+        match kind {
+            "error" => Some(SignKind::Error),
+            "warn" => Some(SignKind::Warn),
+            "info" => Some(SignKind::Info),
+            "hint" => Some(SignKind::Hint),
+            "git_add" => Some(SignKind::GitAdd),
+            "git_change" => Some(SignKind::GitChange),
+            "git_delete" => Some(SignKind::GitDelete),
+            _ => None,
+        }
+    }
+
+    pub fn is_git(self) -> bool {
+        matches!(
+            self,
+            SignKind::GitAdd | SignKind::GitChange | SignKind::GitDelete
+        )
+    }
+
+    /// Color of the kind's icon, from its highlight group.
+    fn color(self, hl_defs: &HlDefs) -> Color {
+        let (groups, background): (&[HlGroup], bool) = match self {
+            SignKind::Error => {
+                (&[HlGroup::DiagnosticError, HlGroup::ErrorMsg], false)
+            }
+            SignKind::Warn => {
+                (&[HlGroup::DiagnosticWarn, HlGroup::WarningMsg], false)
+            }
+            SignKind::Info => (&[HlGroup::DiagnosticInfo], false),
+            SignKind::Hint => (&[HlGroup::DiagnosticHint], false),
+            // The diff groups usually only have a background.
+            SignKind::GitAdd => (&[HlGroup::DiffAdd], true),
+            SignKind::GitChange => (&[HlGroup::DiffChange], true),
+            SignKind::GitDelete => (&[HlGroup::DiffDelete], true),
+        };
+
+        groups
+            .iter()
+            .find_map(|group| {
+                let hl = hl_defs.get_hl_group(group)?;
+                if background {
+                    hl.background.or(hl.foreground)
+                } else {
+                    hl.foreground
+                }
+            })
+            .unwrap_or(hl_defs.default_fg)
+    }
+
+    /// Returns the kind's icon, an svg image of `size` pixels.
+    fn icon(self, color: &Color, size: f64) -> String {
+        let color = color.to_hex();
+
+        match self {
+            SignKind::Error => {
+                icon!("../../assets/icons/x-circle.svg", color, size)
+            }
+            SignKind::Warn => {
+                icon!("../../assets/icons/alert-triangle.svg", color, size)
+            }
+            SignKind::Info => icon!("../../assets/icons/info.svg", color, size),
+            SignKind::Hint => {
+                icon!("../../assets/icons/help-circle.svg", color, size)
+            }
+            SignKind::GitAdd => {
+                icon!("../../assets/icons/plus-square.svg", color, size)
+            }
+            SignKind::GitChange => {
+                icon!("../../assets/icons/edit-2.svg", color, size)
+            }
+            SignKind::GitDelete => {
+                icon!("../../assets/icons/minus-square.svg", color, size)
+            }
+        }
+    }
+}
+
+/// What is drawn on a row of the gutter.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct RowSigns {
+    /// Kind whose icon is drawn.
+    icon: Option<SignKind>,
+    /// Git change that is drawn as a bar, when the icon is a diagnostic.
+    bar: Option<SignKind>,
+}
+
+/// Merges the signs on the same rows: the most important sign gets the
+/// icon, and a git change next to a diagnostic is drawn as a bar.
+fn row_signs(signs: &[(u64, SignKind)]) -> BTreeMap<u64, RowSigns> {
+    let mut rows: BTreeMap<u64, RowSigns> = BTreeMap::new();
+
+    for (row, kind) in signs.iter().copied() {
+        let row = rows.entry(row).or_default();
+        if kind.is_git() {
+            row.bar = row.bar.max(Some(kind));
+        }
+        row.icon = row.icon.max(Some(kind));
+    }
+
+    for row in rows.values_mut() {
+        if row.icon == row.bar {
+            row.bar = None;
+        }
+    }
+
+    rows
+}
+
+#[derive(Default)]
+struct GutterState {
+    rows: BTreeMap<u64, RowSigns>,
+    /// First column of the gutter.
+    col: u64,
+    /// Width of the gutter, in cells.
+    width: u64,
+
+    cell_width: f64,
+    cell_height: f64,
+
+    colors: HashMap<SignKind, Color>,
+    bg: Color,
+    /// Icons rendered for the current colors and cell size (by kind and
+    /// the device's scale), created when they're first drawn.
+    icons: HashMap<(SignKind, i32), gdk_pixbuf::Pixbuf>,
+}
+
+impl GutterState {
+    /// Size of the icons, in pixels.
+    fn icon_size(&self) -> f64 {
+        (self.cell_width * self.width as f64)
+            .min(self.cell_height)
+            .floor()
+    }
+
+    fn icon(&mut self, kind: SignKind, scale: i32) -> gdk_pixbuf::Pixbuf {
+        let size = self.icon_size();
+        let color = self.colors.get(&kind).copied().unwrap_or_default();
+
+        self.icons
+            .entry((kind, scale))
+            .or_insert_with(|| {
+                // Rendered at the device's resolution, so the icon stays
+                // crisp on hidpi screens.
+                let contents = kind.icon(&color, size * f64::from(scale));
+                let stream = gio::MemoryInputStream::from_bytes(
+                    &glib::Bytes::from(contents.as_bytes()),
+                );
+                gdk_pixbuf::Pixbuf::from_stream(
+                    &stream,
+                    None::<&gio::Cancellable>,
+                )
+                .unwrap()
+            })
+            .clone()
+    }
+}
+
+/// Strip on top of a window's sign column, where gnvim draws the signs set
+/// by plugins (e.g. diagnostics and git changes) as svg icons. The sign
+/// column's position comes with the signs, as it depends on the window's
+/// options (e.g. 'foldcolumn' and 'number').
+pub struct Gutter {
+    da: gtk::DrawingArea,
+    state: Rc<RefCell<GutterState>>,
+}
+
+impl Gutter {
+    pub fn new() -> Self {
+        let state = Rc::new(RefCell::new(GutterState::default()));
+
+        let da = gtk::DrawingArea::new();
+        da.set_halign(gtk::Align::Start);
+        da.set_no_show_all(true);
+
+        da.connect_draw(clone!(state => move |da, cr| {
+            draw(&mut state.borrow_mut(), cr, da.get_scale_factor());
+            Inhibit(false)
+        }));
+
+        Self { da, state }
+    }
+
+    pub fn widget(&self) -> gtk::Widget {
+        self.da.clone().upcast()
+    }
+
+    /// Sets the signs, as (row, kind) of the window's rows, and where the
+    /// gutter is: `width` cells from column `col`.
+    pub fn set_signs(&self, col: u64, width: u64, signs: &[(u64, SignKind)]) {
+        let mut state = self.state.borrow_mut();
+        state.rows = row_signs(signs);
+        if (col, width) != (state.col, state.width) {
+            state.col = col;
+            state.width = width;
+            state.icons.clear();
+            self.update_size(&state);
+        }
+        self.da.set_visible(!state.rows.is_empty() && width > 0);
+        self.da.queue_draw();
+    }
+
+    pub fn set_cell_size(&self, cell_width: f64, cell_height: f64) {
+        let mut state = self.state.borrow_mut();
+        state.cell_width = cell_width;
+        state.cell_height = cell_height;
+        state.icons.clear();
+
+        self.update_size(&state);
+        self.da.queue_draw();
+    }
+
+    fn update_size(&self, state: &GutterState) {
+        self.da
+            .set_margin_start((state.cell_width * state.col as f64) as i32);
+        self.da.set_size_request(
+            (state.cell_width * state.width as f64).ceil() as i32,
+            -1,
+        );
+    }
+
+    pub fn set_colors(&self, hl_defs: &HlDefs) {
+        let mut state = self.state.borrow_mut();
+        state.bg = hl_defs
+            .get_hl_group(&HlGroup::SignColumn)
+            .and_then(|hl| hl.background)
+            .unwrap_or(hl_defs.default_bg);
+
+        use self::SignKind::*;
+        state.colors = [GitAdd, GitChange, GitDelete, Hint, Info, Warn, Error]
+            .iter()
+            .map(|kind| (*kind, kind.color(hl_defs)))
+            .collect();
+        state.icons.clear();
+
+        self.da.queue_draw();
+    }
+}
+
+fn draw(state: &mut GutterState, cr: &cairo::Context, scale: i32) {
+    let width = state.cell_width * state.width as f64;
+    let height = state.cell_height;
+    let size = state.icon_size();
+
+    let rows = state.rows.clone();
+    for (row, signs) in rows {
+        let y = row as f64 * height;
+
+        // Covers nvim's own sign, if there is one.
+        let bg = state.bg;
+        cr.set_source_rgb(bg.r, bg.g, bg.b);
+        cr.rectangle(0.0, y, width, height);
+        cr.fill();
+
+        if let Some(kind) = signs.bar {
+            let color = state.colors.get(&kind).copied().unwrap_or_default();
+            cr.set_source_rgb(color.r, color.g, color.b);
+            cr.rectangle(0.0, y, GIT_BAR_WIDTH, height);
+            cr.fill();
+        }
+
+        if let Some(kind) = signs.icon {
+            let pixbuf = state.icon(kind, scale);
+
+            cr.save();
+            cr.translate(
+                ((width - size) / 2.0).round(),
+                (y + (height - size) / 2.0).round(),
+            );
+            cr.scale(1.0 / f64::from(scale), 1.0 / f64::from(scale));
+            cr.set_source_pixbuf(&pixbuf, 0.0, 0.0);
+            cr.paint();
+            cr.restore();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_kind_from_string() {
+        assert_eq!(SignKind::from_string("error"), Some(SignKind::Error));
+        assert_eq!(
+            SignKind::from_string("git_change"),
+            Some(SignKind::GitChange)
+        );
+        assert_eq!(SignKind::from_string("Error"), None);
+    }
+
+    #[test]
+    fn test_row_signs() {
+        let rows = row_signs(&[
+            (0, SignKind::Hint),
+            (0, SignKind::Error),
+            (0, SignKind::Warn),
+            (2, SignKind::GitAdd),
+            (3, SignKind::GitChange),
+            (3, SignKind::Info),
+            (5, SignKind::GitAdd),
+            (5, SignKind::GitDelete),
+        ]);
+
+        let expected = vec![
+            (
+                0,
+                RowSigns {
+                    icon: Some(SignKind::Error),
+                    bar: None,
+                },
+            ),
+            (
+                2,
+                RowSigns {
+                    icon: Some(SignKind::GitAdd),
+                    bar: None,
+                },
+            ),
+            (
+                3,
+                RowSigns {
+                    icon: Some(SignKind::Info),
+                    bar: Some(SignKind::GitChange),
+                },
+            ),
+            (
+                5,
+                RowSigns {
+                    icon: Some(SignKind::GitDelete),
+                    bar: None,
+                },
+            ),
+        ];
+        assert_eq!(rows.into_iter().collect::<Vec<_>>(), expected);
+    }
+}
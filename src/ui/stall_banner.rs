@@ -0,0 +1,89 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+
+use gtk::prelude::*;
+
+use crate::nvim_gio::Watchdog;
+
+/// How often (in milliseconds) we check if nvim is answering.
+const CHECK_INTERVAL: u32 = 1000;
+/// How long nvim can keep us waiting before the banner is shown.
+const STALL_THRESHOLD: Duration = Duration::from_secs(5);
+
+const RESPONSE_WAIT: u16 = 1;
+const RESPONSE_KILL: u16 = 2;
+
+/// Banner at the top of the window, shown when nvim stops answering our
+/// requests. Lets the user keep waiting or kill nvim.
+pub struct StallBanner {
+    bar: gtk::InfoBar,
+    /// Timer that checks on nvim.
+    timer: RefCell<Option<glib::SourceId>>,
+}
+
+impl StallBanner {
+    pub fn new(parent: &gtk::Overlay, watchdog: Watchdog) -> Self {
+        let bar = gtk::InfoBar::new();
+        bar.set_message_type(gtk::MessageType::Warning);
+        bar.set_valign(gtk::Align::Start);
+        bar.add_button("Wait", gtk::ResponseType::Other(RESPONSE_WAIT));
+        bar.add_button("Kill", gtk::ResponseType::Other(RESPONSE_KILL));
+
+        let label = gtk::Label::new(Some("Neovim is not responding"));
+        label.set_halign(gtk::Align::Start);
+        if let Some(content) = bar.get_content_area() {
+            content.add(&label);
+        }
+        label.show();
+        bar.set_no_show_all(true);
+
+        parent.add_overlay(&bar);
+
+        // "Wait" hides the banner until nvim answers again.
+        let dismissed = Rc::new(Cell::new(false));
+
+        bar.connect_response(clone!(dismissed, watchdog => move |bar, res| {
+            match res {
+                gtk::ResponseType::Other(RESPONSE_KILL) => {
+                    watchdog.kill();
+                }
+                _ => dismissed.set(true),
+            }
+
+            bar.hide();
+        }));
+
+        let timer = gtk::timeout_add(
+            CHECK_INTERVAL,
+            clone!(bar => move || {
+                match watchdog.stalled_for() {
+                    Some(duration) if duration >= STALL_THRESHOLD => {
+                        if !dismissed.get() {
+                            bar.show();
+                        }
+                    }
+                    _ => {
+                        dismissed.set(false);
+                        bar.hide();
+                    }
+                }
+
+                Continue(true)
+            }),
+        );
+
+        StallBanner {
+            bar,
+            timer: RefCell::new(Some(timer)),
+        }
+    }
+
+    /// Stops checking on nvim, once it has exited.
+    pub fn stop(&self) {
+        if let Some(id) = self.timer.borrow_mut().take() {
+            glib::source::source_remove(id);
+        }
+        self.bar.hide();
+    }
+}
@@ -1,35 +1,84 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::time::Instant;
 
+use gio::prelude::*;
 use gtk::prelude::*;
+use gtk::SettingsExt;
 
 use log::{debug, error, warn};
-use nvim_rs::{Tabpage, Window as NvimWindow};
+use pango::FontFamilyExt;
 
+use crate::crash::RecentEvents;
 use crate::nvim_bridge::{
-    CmdlineBlockAppend, CmdlineBlockShow, CmdlinePos, CmdlineShow,
-    CmdlineSpecialChar, DefaultColorsSet, GnvimEvent, GridCursorGoto,
-    GridLineSegment, GridResize, GridScroll, HlAttrDefine, HlGroupSet,
-    ModeChange, ModeInfo, ModeInfoSet, MsgSetPos, Notify, OptionSet,
-    PopupmenuShow, RedrawEvent, TablineUpdate, WindowExternalPos,
-    WindowFloatPos, WindowPos,
+    window_handle, CmdlineBlockAppend, CmdlineBlockShow, CmdlinePos,
+    CmdlineShow, CmdlineSpecialChar, DefaultColorsSet, FileDialog, GnvimEvent,
+    GridCursorGoto, GridLineSegment, GridResize, GridScroll, HlAttrDefine,
+    HlGroupSet, ModeChange, ModeInfo, ModeInfoSet, MsgHistoryShow, MsgSetPos,
+    MsgShow, Notify, OptionSet, PopupmenuShow, RedrawEvent, TablineUpdate,
+    WindowExternalPos, WindowFloatPos, WindowPos, WindowViewport,
 };
 use crate::nvim_gio::GioNeovim;
+use crate::ui::animation::{self, Animations};
+use crate::ui::background::Background;
 use crate::ui::cmdline::Cmdline;
-use crate::ui::color::{HlDefs, HlGroup};
-use crate::ui::common::spawn_local;
-#[cfg(feature = "libwebkit2gtk")]
+use crate::ui::color::{HlDefs, HlGroup, TerminalPalette};
+use crate::ui::color_scheme::ColorScheme;
+use crate::ui::command_palette::CommandPalette;
+use crate::ui::common::{nvim_error, spawn_local};
+use crate::ui::copy_mode::{CopyMode, CopyModeAction};
 use crate::ui::cursor_tooltip::{CursorTooltip, Gravity};
-use crate::ui::font::Font;
-use crate::ui::grid::{Grid, GridMetrics};
+use crate::ui::dbus::DbusControl;
+use crate::ui::detached_tab::DetachedTab;
+use crate::ui::export::{self, ExportFormat};
+use crate::ui::file_tree::FileTree;
+use crate::ui::finder::Finder;
+use crate::ui::float_drag::{self, FloatDrag, FloatRect, FloatTarget};
+use crate::ui::font::{self, Font};
+use crate::ui::grid::{
+    cell_index, split_position, BlinkCurve, Crosshair, Grid, GridMetrics,
+    Image, IndentGuides, Link, LinkKind, LinkMatcher, MouseButton, Rasterizer,
+    RendererKind, SnippetState,
+};
+use crate::ui::input::{Shortcut, Shortcuts};
+use crate::ui::input_queue::{Input, InputQueue};
+use crate::ui::log_viewer::LogViewer;
+use crate::ui::lookup::{self, Lookup};
+use crate::ui::markdown::markdown_to_markup;
+use crate::ui::offscreen;
+use crate::ui::paste::Paster;
+use crate::ui::perf::PerfOverlay;
 use crate::ui::popupmenu::Popupmenu;
+use crate::ui::preview::Preview;
+use crate::ui::print::Printer;
+use crate::ui::progress::Progress;
+use crate::ui::screenshot::{self, ScreenshotTarget};
+use crate::ui::search_bar::SearchBar;
+use crate::ui::selection;
+use crate::ui::separator_drag::{self, Separator, SeparatorDrag, WinRect};
+use crate::ui::stall_banner::StallBanner;
+use crate::ui::statusbar::Statusbar;
 use crate::ui::tabline::Tabline;
-use crate::ui::window::{MsgWindow, Window};
+use crate::ui::terminal_graphics::{self, Command};
+use crate::ui::toasts::Toasts;
+use crate::ui::toplevel::{self, Decorations, ToplevelState};
+use crate::ui::window::{
+    ExternalPositions, FloatOptions, FloatStyle, MsgWindow, Window,
+};
 
 pub(crate) type Windows = HashMap<i64, Window>;
 pub(crate) type Grids = HashMap<i64, Grid>;
 
+/// How deep floats anchored to floats are positioned again when their
+/// anchor moves.
+const MAX_FLOAT_DEPTH: usize = 16;
+
+/// How many rows, in all the grids, are rendered again per frame after
+/// highlights change (e.g. when the colorscheme changes).
+const STALE_ROWS_PER_FRAME: usize = 20;
+
 pub(crate) struct ResizeOptions {
     pub font: Font,
     pub line_space: i64,
@@ -38,11 +87,22 @@ pub(crate) struct ResizeOptions {
 /// Internal structure for `UI` to work on.
 pub(crate) struct UIState {
     pub css_provider: gtk::CssProvider,
+    /// Provider for the user's CSS, set with `--custom-css` or
+    /// `gnvim#window#set_custom_css`.
+    pub custom_css_provider: gtk::CssProvider,
     pub windows: Windows,
     /// Container for non-floating windows.
     pub windows_container: gtk::Fixed,
     /// Container for floating windows.
     pub windows_float_container: gtk::Fixed,
+    /// Grid ids and z-indices of the floating windows, in the order they're
+    /// stacked in `windows_float_container` (from bottom to top).
+    pub float_stack: Vec<(i64, u64)>,
+    /// Latest positions of the floating windows, for positioning the floats
+    /// anchored to them again when they move.
+    pub float_positions: HashMap<i64, WindowFloatPos>,
+    /// Positions of the external windows, which outlive the windows.
+    pub external_positions: ExternalPositions,
     /// Container for the msg window/grid.
     pub msg_window_container: gtk::Fixed,
     /// Window for our messages grid.
@@ -56,34 +116,160 @@ pub(crate) struct UIState {
     pub mode_infos: Vec<ModeInfo>,
     /// Current mode.
     pub current_mode: Option<ModeInfo>,
+    /// Index of the current mode in `mode_infos`.
+    pub current_mode_index: Option<u64>,
+    /// If alt is sent to nvim as a modifier. Shared with the key press
+    /// handler.
+    pub alt_as_meta: Rc<Cell<bool>>,
+    /// GUI shortcuts that are handled before keys are sent to nvim.
+    pub shortcuts: Shortcuts,
     /// Id of the current active grid.
     pub current_grid: i64,
 
     pub popupmenu: Popupmenu,
     pub cmdline: Cmdline,
+    pub command_palette: CommandPalette,
+    pub finder: Finder,
+    /// Notifications for messages, when they are externalized.
+    pub toasts: Toasts,
+    /// Progress of a long operation, set by plugins. Owns the window's
+    /// title.
+    pub progress: Progress,
+    /// Pastes text into nvim, a chunk at a time.
+    pub paster: Paster,
+    /// Keyboard and mouse inputs, held back while nvim is busy.
+    pub input_queue: InputQueue,
+    /// Shown when nvim stops answering.
+    pub stall_banner: StallBanner,
+    /// The latest redraw events, for crash reports.
+    pub recent_events: RecentEvents,
+    /// Gnvim's latest logs, opened with `LogViewerShow`.
+    pub log_viewer: LogViewer,
+    /// Where the grids are written when nvim exits, when the window isn't
+    /// shown (see `--offscreen`).
+    pub offscreen_dir: Option<PathBuf>,
+    /// Rendering performance, toggled by `TogglePerfOverlay`.
+    pub perf: PerfOverlay,
     pub tabline: Tabline,
-    #[cfg(feature = "libwebkit2gtk")]
     pub cursor_tooltip: CursorTooltip,
+    /// Image sequences from the programs running in `:terminal`s.
+    pub terminal_graphics: terminal_graphics::Parser,
+    /// Markdown preview of a buffer, next to the grids.
+    pub preview: Preview,
+    /// Files of nvim's current directory, next to the grids.
+    pub file_tree: FileTree,
+    /// Tab pages moved to their own OS windows, by their ids.
+    pub detached_tabs: HashMap<u64, DetachedTab>,
+    pub printer: Printer,
+    /// Find bar, shown with the search shortcut or `SearchBarShow`.
+    pub search_bar: SearchBar,
+    /// Copy mode of the message grid, if it's active.
+    pub copy_mode: Option<CopyMode>,
+    /// GUI statusbar, set with `gnvim#statusline#set`.
+    pub statusbar: Statusbar,
+    /// D-Bus control interface (`org.gnvim.Control`).
+    /// D-Bus control interface, if it's enabled with `--dbus`.
+    pub dbus: Option<DbusControl>,
 
     pub wildmenu_shown: bool,
 
     /// Overlay contains our grid(s) and popupmenu.
-    #[allow(unused)]
     pub overlay: gtk::Overlay,
+    /// Gesture for pinch to zoom, which needs to be kept alive.
+    #[allow(unused)]
+    pub zoom_gesture: gtk::GestureZoom,
+    /// Resizing the windows by dragging their separators.
+    pub separator_drag: SeparatorDrag,
+    /// Moving and resizing the floating windows by dragging them.
+    pub float_drag: FloatDrag,
 
     /// Source id for delayed call to ui_try_resize.
     pub resize_source_id: Rc<RefCell<Option<glib::SourceId>>>,
     /// Resize options that is some if a resize should be send to nvim on flush.
     pub resize_on_flush: Option<ResizeOptions>,
+    /// If nvim has flushed since the last frame. Flushes are applied once
+    /// per frame, so that fast output isn't painted more often than the
+    /// monitor refreshes.
+    pub flush_pending: bool,
+    /// Redraw events received while a flush is pending. They are applied
+    /// once the flush is, so that a half-received batch isn't painted.
+    pub held_events: VecDeque<RedrawEvent>,
+    /// Adds a tick callback that calls `flush_pending` on the next frames,
+    /// until it returns false. Set by `UI::start`.
+    pub request_frames: Box<dyn Fn()>,
+    /// If the tick callback of `request_frames` is running.
+    pub frames_requested: bool,
+    /// If nvim has exited, and closing the window doesn't need to quit it.
+    pub nvim_exited: bool,
 
     /// Flag for flush to update GUI colors on components that depend on
     /// highlight defs and groups.
     pub hl_changed: bool,
+    /// Highlight ids that were redefined. Cells using these are rendered
+    /// again on flush.
+    pub hl_redraw: HashSet<u64>,
+    /// The latest default colors from nvim, for applying them again when the
+    /// terminal palette changes.
+    pub default_colors: Option<DefaultColorsSet>,
 
     pub font: Font,
     pub line_space: i64,
+    /// Scale of the device pixels, set with `gnvim#window#set_device_scale`.
+    /// `None` uses gtk's scale factor.
+    pub device_scale: Option<f64>,
+    /// Color scheme preferred by the desktop.
+    pub color_scheme: ColorScheme,
+    /// If gtk's dark theme is preferred regardless of the color scheme
+    /// (e.g. with `--prefer-dark-theme`).
+    pub prefer_dark_theme: bool,
 
     pub enable_cursor_animations: bool,
+    /// Curve of the cursor's blink animation.
+    pub cursor_blink_curve: BlinkCurve,
+    /// If the cursor should be hollow when the window is not focused.
+    pub cursor_hollow_unfocused: bool,
+    /// Highlight of the cursor's row and column.
+    pub crosshair: Crosshair,
+    pub indent_guides: IndentGuides,
+    /// If the grids should highlight the areas damaged on flush.
+    pub damage_overlay: bool,
+    /// If the grids should continue scrolling after a touchpad scroll ends.
+    pub kinetic_scrolling: bool,
+    /// If mouse selections are handled by gnvim instead of nvim.
+    pub gui_selection: bool,
+    /// If nvim sends the highlight groups that the highlights are made of
+    /// (`ext_hlstate`). Only needed for virtual text, so it's enabled when
+    /// virtual text is first set.
+    pub hlstate: bool,
+    /// Patterns of the links in the grids, set with `SetLinkPatterns`.
+    pub link_matcher: Rc<LinkMatcher>,
+    /// Fullscreen, maximized etc. state of the main window.
+    pub toplevel: ToplevelState,
+    /// If the windows should have a scrollbar.
+    pub scrollbar: bool,
+    /// If the windows should have a minimap.
+    pub minimap: bool,
+    /// Decorations of floating windows.
+    pub float_options: FloatOptions,
+    /// Decorations of specific floating windows, by their nvim window
+    /// handle, instead of `float_options`.
+    pub window_float_options: HashMap<i64, FloatOptions>,
+    /// Fade and scale animations of the windows.
+    pub animations: Animations,
+    /// Opacity of the window's (and grids') background.
+    pub background_opacity: f64,
+    /// Image drawn behind the grids. In a `RefCell`, because drawing it
+    /// caches the scaled image.
+    pub background_image: RefCell<Option<Background>>,
+
+    /// Input context shared by all the grids.
+    pub im_context: gtk::IMMulticontext,
+
+    /// Renderer used for the grids.
+    pub renderer: RendererKind,
+    /// Threads that rasterize the grids' text, if it's not done on the
+    /// main thread.
+    pub rasterizer: Option<Rasterizer>,
 }
 
 impl UIState {
@@ -95,12 +281,23 @@ impl UIState {
     ) {
         match notify {
             Notify::RedrawEvent(events) => {
+                self.perf.add_events(events.len());
+                // Hidden window doesn't get frames, so the pending flush
+                // would wait forever.
+                if self.flush_pending && !window.is_visible() {
+                    self.apply_pending_flush(nvim, window);
+                }
                 events.into_iter().for_each(|e| {
-                    self.handle_redraw_event(window, e, &nvim);
+                    self.recent_events.push(&e);
+                    if self.flush_pending {
+                        self.held_events.push_back(e);
+                    } else {
+                        self.handle_redraw_event(window, e, &nvim);
+                    }
                 });
             }
             Notify::GnvimEvent(event) => match event {
-                Ok(event) => self.handle_gnvim_event(&event, nvim),
+                Ok(event) => self.handle_gnvim_event(window, &event, nvim),
                 Err(err) => {
                     let nvim = nvim.clone();
                     let msg = format!(
@@ -117,8 +314,8 @@ impl UIState {
         }
     }
 
-    fn set_title(&mut self, window: &gtk::ApplicationWindow, title: &str) {
-        window.set_title(title);
+    fn set_title(&mut self, title: &str) {
+        self.progress.set_title(title);
     }
 
     fn grid_cursor_goto(
@@ -129,23 +326,25 @@ impl UIState {
             col,
         }: GridCursorGoto,
     ) {
-        // Gird cursor goto sets the current cursor to grid_id,
-        // so we'll need to handle that here...
-        let grid = if grid_id != self.current_grid {
-            // ...so if the grid_id is not same as the self tells us,
-            // set the previous current grid to inactive self.
-            let grid = self.grids.get(&self.current_grid).unwrap();
-            grid.set_active(false);
-            self.current_grid = grid_id;
-
-            // And set the new current grid to active.
-            let grid = self.grids.get(&grid_id).unwrap();
-            grid.set_active(true);
-            grid
-        } else {
-            self.grids.get(&grid_id).unwrap()
+        let previous = match lookup::set_current_grid(
+            &self.grids,
+            &mut self.current_grid,
+            grid_id,
+        ) {
+            Some(previous) => previous,
+            None => return,
         };
 
+        // Grid cursor goto sets the current cursor to grid_id, so set the
+        // previous current grid to inactive and the new one to active.
+        let grid = &self.grids[&grid_id];
+        if grid_id != previous {
+            if let Some(grid) = self.grids.get(&previous) {
+                grid.set_active(false);
+            }
+            grid.set_active(true);
+        }
+
         // And after all that, set the current grid's cursor position.
         grid.cursor_goto(row, col);
     }
@@ -156,6 +355,8 @@ impl UIState {
         window: &gtk::ApplicationWindow,
         nvim: &GioNeovim,
     ) {
+        self.msg_window.grid_changed(e.grid);
+
         let win = window.get_window().unwrap();
         if let Some(grid) = self.grids.get(&e.grid) {
             grid.resize(&win, e.width, e.height, &self.hl_defs);
@@ -172,55 +373,92 @@ impl UIState {
                 ));
             }
         } else {
-            let grid = Grid::new(
-                e.grid,
-                &window.get_window().unwrap(),
-                self.font.clone(),
-                self.line_space,
-                e.width as usize,
-                e.height as usize,
-                &self.hl_defs,
-                self.enable_cursor_animations,
-            );
+            self.create_grid(e.grid, e.width, e.height, window, nvim);
+        }
+    }
 
-            if let Some(ref mode) = self.current_mode {
-                grid.set_mode(&mode);
-            }
-            grid.resize(&win, e.width, e.height, &self.hl_defs);
-            attach_grid_events(&grid, nvim.clone());
-            self.grids.insert(e.grid, grid);
+    /// Creates grid `id` of `width` x `height` cells.
+    fn create_grid(
+        &mut self,
+        id: i64,
+        width: u64,
+        height: u64,
+        window: &gtk::ApplicationWindow,
+        nvim: &GioNeovim,
+    ) {
+        let win = window.get_window().unwrap();
+        let mut grid = Grid::new(
+            id,
+            &win,
+            self.font.clone(),
+            self.line_space,
+            width as usize,
+            height as usize,
+            &self.hl_defs,
+            self.enable_cursor_animations,
+            self.device_scale,
+            self.renderer,
+            self.rasterizer.as_ref(),
+        );
+
+        if let Some(ref mode) = self.current_mode {
+            grid.set_mode(&mode);
         }
+        grid.set_cursor_blink_curve(self.cursor_blink_curve);
+        grid.enable_cursor_hollow_unfocused(self.cursor_hollow_unfocused);
+        grid.set_crosshair(self.crosshair);
+        grid.set_indent_guides(self.indent_guides);
+        grid.set_focused(window.is_active());
+        grid.enable_damage_overlay(self.damage_overlay);
+        grid.enable_kinetic_scrolling(self.kinetic_scrolling);
+        grid.enable_gui_selection(self.gui_selection);
+        grid.set_link_matcher(self.link_matcher.clone());
+        grid.set_background_opacity(
+            self.grid_background_opacity(id),
+            &win,
+            &self.hl_defs,
+        );
+        grid.set_im_context(&self.im_context);
+        grid.resize(&win, width, height, &self.hl_defs);
+        attach_grid_events(&grid, nvim.clone(), &self.input_queue);
+        self.grids.insert(id, grid);
     }
 
     fn grid_line(&mut self, line: GridLineSegment) {
-        let grid = self.grids.get(&line.grid).unwrap();
-        grid.put_line(line, &self.hl_defs);
+        self.msg_window.grid_changed(line.grid);
+        if let Some(grid) = self.grids.lookup(line.grid, "grid_line") {
+            grid.put_line(line);
+        }
     }
 
     fn grid_clear(&mut self, grid: &i64) {
-        let grid = self.grids.get(grid).unwrap();
-        grid.clear(&self.hl_defs);
+        self.msg_window.grid_changed(*grid);
+        if let Some(grid) = self.grids.lookup(*grid, "grid_clear") {
+            grid.clear(&self.hl_defs);
+        }
     }
 
     fn grid_destroy(&mut self, grid: &i64) {
-        // Drop grid.
-        if self.grids.remove(grid).is_none() {
-            warn!(
-                "Nvim instructed to close a grid that we don't have (grid: {})",
-                grid
-            );
-        }
-        if self.windows.contains_key(grid) {
-            self.windows.remove(grid).unwrap(); // Drop window that the grid belongs to.
+        if self.msg_window.grid_id == Some(*grid) {
+            self.leave_copy_mode();
+            self.msg_window.grid_id = None;
         }
 
-        // Make the current grid to point to the default grid. We relay on the fact
-        // that current_grid is always pointing to a existing grid.
-        self.current_grid = 1;
+        // Drop the grid, and the window that the grid belongs to.
+        lookup::remove_grid(
+            &mut self.grids,
+            &mut self.windows,
+            &mut self.current_grid,
+            *grid,
+        );
     }
 
     fn grid_scroll(&mut self, info: GridScroll, nvim: &GioNeovim) {
-        let grid = self.grids.get(&info.grid).unwrap();
+        self.msg_window.grid_changed(info.grid);
+        let grid = match self.grids.lookup(info.grid, "grid_scroll") {
+            Some(grid) => grid,
+            None => return,
+        };
         grid.scroll(info.reg, info.rows, info.cols, &self.hl_defs);
 
         // Since nvim doesn't have its own 'scroll' autocmd, we'll
@@ -233,10 +471,32 @@ impl UIState {
         });
     }
 
-    fn default_colors_set(
-        &mut self,
-        DefaultColorsSet { fg, bg, sp }: DefaultColorsSet,
-    ) {
+    fn default_colors_set(&mut self, evt: DefaultColorsSet) {
+        self.default_colors = Some(evt.clone());
+        let DefaultColorsSet {
+            fg,
+            bg,
+            sp,
+            cterm_fg,
+            cterm_bg,
+        } = evt;
+
+        // With `ext_termcolors`, the colors might come from the terminal
+        // palette. Without any colors, fall back to ones that fit the
+        // desktop's color scheme.
+        let palette = &self.hl_defs.terminal_palette;
+        let (default_fg, default_bg) = self.color_scheme.fallback_colors();
+        let fg = fg
+            .or_else(|| cterm_fg.and_then(|i| palette.get(i)))
+            .unwrap_or(default_fg);
+        let bg = bg
+            .or_else(|| cterm_bg.and_then(|i| palette.get(i)))
+            .unwrap_or(default_bg);
+
+        let changed = self.hl_defs.default_fg != fg
+            || self.hl_defs.default_bg != bg
+            || self.hl_defs.default_sp != sp;
+
         self.hl_defs.default_fg = fg;
         self.hl_defs.default_bg = bg;
         self.hl_defs.default_sp = sp;
@@ -249,18 +509,46 @@ impl UIState {
             hl.special = Some(sp);
         }
 
-        for grid in self.grids.values() {
-            grid.redraw(&self.hl_defs);
+        if changed {
+            // Only the cells that use (some of) the default colors need
+            // to be rendered again. The rows are rendered over the next
+            // frames, so we don't block on e.g. colorscheme changes.
+            let hl_defs = &self.hl_defs;
+            let uses_defaults = |id: u64| {
+                id == 0
+                    || hl_defs.get(&id).map_or(true, |hl| {
+                        hl.foreground.is_none()
+                            || hl.background.is_none()
+                            || hl.special.is_none()
+                    })
+            };
+
+            for grid in self.grids.values() {
+                grid.mark_stale(uses_defaults);
+            }
         }
 
-        #[cfg(feature = "libwebkit2gtk")]
-        self.cursor_tooltip.set_colors(fg, bg);
+        self.preview.set_colors(fg, bg);
 
         self.hl_changed = true;
     }
 
-    fn hl_attr_define(&mut self, HlAttrDefine { id, hl }: HlAttrDefine) {
-        self.hl_defs.insert(id, hl);
+    fn hl_attr_define(
+        &mut self,
+        HlAttrDefine {
+            id,
+            hl,
+            url,
+            groups,
+        }: HlAttrDefine,
+    ) {
+        self.hl_defs.set_url(id, url);
+        self.hl_defs.set_names(id, groups);
+        if let Some(prev) = self.hl_defs.insert(id, hl) {
+            if Some(&prev) != self.hl_defs.get(&id) {
+                self.hl_redraw.insert(id);
+            }
+        }
     }
 
     fn hl_group_set(&mut self, evt: HlGroupSet) {
@@ -285,6 +573,46 @@ impl UIState {
             "MsgSeparator" => {
                 self.hl_defs.set_hl_group(HlGroup::MsgSeparator, evt.hl_id)
             }
+            "VertSplit" | "WinSeparator" => {
+                self.hl_defs.set_hl_group(HlGroup::VertSplit, evt.hl_id)
+            }
+            "StatusLine" => {
+                self.hl_defs.set_hl_group(HlGroup::StatusLine, evt.hl_id)
+            }
+            "NormalFloat" => {
+                self.hl_defs.set_hl_group(HlGroup::Toast, evt.hl_id);
+                self.hl_defs.set_hl_group(HlGroup::NormalFloat, evt.hl_id)
+            }
+            "FloatBorder" => {
+                self.hl_defs.set_hl_group(HlGroup::FloatBorder, evt.hl_id)
+            }
+            "ErrorMsg" => {
+                self.hl_defs.set_hl_group(HlGroup::ErrorMsg, evt.hl_id)
+            }
+            "WarningMsg" => {
+                self.hl_defs.set_hl_group(HlGroup::WarningMsg, evt.hl_id)
+            }
+            "CursorLine" => {
+                self.hl_defs.set_hl_group(HlGroup::CursorLine, evt.hl_id)
+            }
+            "Visual" => self.hl_defs.set_hl_group(HlGroup::Visual, evt.hl_id),
+            "Search" => self.hl_defs.set_hl_group(HlGroup::Search, evt.hl_id),
+            "PmenuSbar" => {
+                self.hl_defs.set_hl_group(HlGroup::PmenuSbar, evt.hl_id)
+            }
+            "PmenuThumb" => {
+                self.hl_defs.set_hl_group(HlGroup::PmenuThumb, evt.hl_id)
+            }
+            "SignColumn" => {
+                self.hl_defs.set_hl_group(HlGroup::SignColumn, evt.hl_id)
+            }
+            "DiffAdd" => self.hl_defs.set_hl_group(HlGroup::DiffAdd, evt.hl_id),
+            "DiffChange" => {
+                self.hl_defs.set_hl_group(HlGroup::DiffChange, evt.hl_id)
+            }
+            "DiffDelete" => {
+                self.hl_defs.set_hl_group(HlGroup::DiffDelete, evt.hl_id)
+            }
             _ => None,
         };
 
@@ -326,6 +654,9 @@ impl UIState {
 
                 self.resize_on_flush = Some(opts);
             }
+            OptionSet::PumBlend(blend) => {
+                self.popupmenu.set_blend(blend, &self.hl_defs);
+            }
             OptionSet::NotSupported(name) => {
                 debug!("Not supported option set: {}", name);
             }
@@ -334,11 +665,32 @@ impl UIState {
 
     fn mode_info_set(&mut self, ModeInfoSet { mode_info, .. }: ModeInfoSet) {
         self.mode_infos = mode_info;
+
+        // The mode infos might change without a mode change (e.g. when a
+        // program in a terminal buffer changes the cursor's shape), so apply
+        // the current mode again.
+        if let Some(index) = self.current_mode_index {
+            self.set_mode(index);
+        }
+    }
+
+    fn mode_change(&mut self, ModeChange { name, index }: ModeChange) {
+        self.set_mode(index);
+        if let Some(ref dbus) = self.dbus {
+            dbus.set_mode(&name);
+        }
     }
 
-    fn mode_change(&mut self, ModeChange { index, .. }: ModeChange) {
-        let mode = self.mode_infos.get(index as usize).unwrap();
+    fn set_mode(&mut self, index: u64) {
+        let mode = match self.mode_infos.get(index as usize) {
+            Some(mode) => mode,
+            None => {
+                warn!("Unknown mode index: {}", index);
+                return;
+            }
+        };
         self.current_mode = Some(mode.clone());
+        self.current_mode_index = Some(index);
         // Broadcast the mode change to all grids.
         // TODO(ville): It might be enough to just set the mode to the
         //              current active grid.
@@ -351,274 +703,1090 @@ impl UIState {
         for grid in self.grids.values() {
             grid.set_busy(busy);
         }
+        self.input_queue.set_busy(busy);
     }
 
-    fn flush(&mut self, nvim: &GioNeovim, window: &gtk::ApplicationWindow) {
-        for grid in self.grids.values() {
-            grid.flush(&self.hl_defs);
+    fn queue_flush(
+        &mut self,
+        nvim: &GioNeovim,
+        window: &gtk::ApplicationWindow,
+    ) {
+        // Hidden window doesn't get frames.
+        if window.is_visible() {
+            self.flush_pending = true;
+            self.request_frames();
+        } else {
+            self.flush(nvim, window);
+            self.redraw_stale(usize::MAX);
         }
+    }
 
-        if let Some(opts) = self.resize_on_flush.take() {
-            let win = window.get_window().unwrap();
-            for grid in self.grids.values() {
-                grid.update_cell_metrics(
-                    opts.font.clone(),
-                    opts.line_space,
-                    &win,
-                );
-            }
+    fn request_frames(&mut self) {
+        if !self.frames_requested {
+            self.frames_requested = true;
+            (self.request_frames)();
+        }
+    }
 
-            let grid = self.grids.get(&1).unwrap();
-            let (cols, rows) = grid.calc_size();
+    /// Applies the pending flush, if any, and then the redraw events that
+    /// were held back for it, up to the next flush.
+    fn apply_pending_flush(
+        &mut self,
+        nvim: &GioNeovim,
+        window: &gtk::ApplicationWindow,
+    ) {
+        if std::mem::take(&mut self.flush_pending) {
+            self.flush(nvim, window);
+        }
 
-            // Cancel any possible delayed call for ui_try_resize.
-            let mut id = self.resize_source_id.borrow_mut();
-            if let Some(id) = id.take() {
-                glib::source::source_remove(id);
+        while !self.flush_pending {
+            match self.held_events.pop_front() {
+                Some(event) => self.handle_redraw_event(window, event, nvim),
+                None => break,
             }
-
-            let nvim = nvim.clone();
-            spawn_local(async move {
-                if let Err(err) =
-                    nvim.ui_try_resize(cols as i64, rows as i64).await
-                {
-                    error!("Error: failed to resize nvim ({:?})", err);
-                }
-            });
-
-            self.popupmenu.set_font(opts.font.clone(), &self.hl_defs);
-            self.cmdline.set_font(opts.font.clone(), &self.hl_defs);
-            self.tabline.set_font(opts.font.clone(), &self.hl_defs);
-            #[cfg(feature = "libwebkit2gtk")]
-            self.cursor_tooltip.set_font(opts.font.clone());
-
-            self.cmdline.set_line_space(opts.line_space);
-            self.popupmenu
-                .set_line_space(opts.line_space, &self.hl_defs);
-            self.tabline.set_line_space(opts.line_space, &self.hl_defs);
         }
+    }
 
-        if self.hl_changed {
-            self.popupmenu.set_colors(&self.hl_defs);
-            self.tabline.set_colors(&self.hl_defs);
-            self.cmdline.set_colors(&self.hl_defs);
-            self.cmdline.wildmenu_set_colors(&self.hl_defs);
+    /// Applies the pending flush, if any, and renders some of the stale
+    /// rows. Called at the start of each frame. Returns false when there's
+    /// nothing left to do on the next frames.
+    pub fn flush_pending(
+        &mut self,
+        nvim: &GioNeovim,
+        window: &gtk::ApplicationWindow,
+    ) -> bool {
+        self.apply_pending_flush(nvim, window);
+        self.redraw_stale(STALE_ROWS_PER_FRAME);
 
-            let msgsep = self
-                .hl_defs
-                .get_hl_group(&HlGroup::MsgSeparator)
-                .cloned()
-                .unwrap_or_default()
-                .foreground;
-
-            // Set the styles for our main window.
-            CssProviderExt::load_from_data(
-                &self.css_provider,
-                format!(
-                    "* {{
-                        background: #{bg};
-                    }}
-
-                    frame > border {{
-                        border: none;
-                    }}
-
-                    #message-grid-contianer frame.scrolled {{
-                        border-top: 1px solid #{msgsep}
-                    }}
-                    ",
-                    bg = self.hl_defs.default_bg.to_hex(),
-                    msgsep = msgsep.unwrap_or(self.hl_defs.default_fg).to_hex(),
-                )
-                .as_bytes(),
-            )
-            .unwrap();
+        self.frames_requested = self.flush_pending
+            || self.grids.values().any(|grid| grid.has_stale_rows());
+        self.frames_requested
+    }
 
-            self.hl_changed = false;
+    /// Renders again (at most) `max_rows` rows of the grids whose highlights
+    /// have changed.
+    fn redraw_stale(&mut self, mut max_rows: usize) {
+        for grid in self.grids.values() {
+            if max_rows == 0 {
+                break;
+            }
+
+            max_rows -= grid.redraw_stale(&self.hl_defs, max_rows);
         }
     }
 
-    fn popupmenu_show(&mut self, popupmenu: PopupmenuShow) {
-        if popupmenu.grid == -1 {
-            self.wildmenu_shown = true;
-            self.cmdline.wildmenu_show(&popupmenu.items)
-        } else {
-            self.popupmenu.set_items(popupmenu.items, &self.hl_defs);
+    /// Updates the cell metrics of the grids (and the fonts of the other
+    /// components), and resizes nvim to fit.
+    fn update_metrics(
+        &mut self,
+        opts: ResizeOptions,
+        nvim: &GioNeovim,
+        window: &gtk::ApplicationWindow,
+    ) {
+        let win = window.get_window().unwrap();
+        for grid in self.grids.values() {
+            grid.update_cell_metrics(opts.font.clone(), opts.line_space, &win);
+        }
 
-            let grid = self.grids.get(&self.current_grid).unwrap();
-            let mut rect = grid.get_rect_for_cell(popupmenu.row, popupmenu.col);
+        let grid = self.grids.get(&1).unwrap();
+        let (cols, rows) = grid.calc_size();
 
-            let window = self.windows.get(&popupmenu.grid).unwrap();
-            rect.x += window.x as i32;
-            rect.y += window.y as i32;
+        // Cancel any possible delayed call for ui_try_resize.
+        let mut id = self.resize_source_id.borrow_mut();
+        if let Some(id) = id.take() {
+            glib::source::source_remove(id);
+        }
 
-            self.popupmenu.set_anchor(rect);
-            self.popupmenu
-                .select(popupmenu.selected as i32, &self.hl_defs);
+        let nvim = nvim.clone();
+        spawn_local(async move {
+            if let Err(err) = nvim.ui_try_resize(cols as i64, rows as i64).await
+            {
+                error!("Error: failed to resize nvim ({:?})", err);
+            }
+        });
 
-            self.popupmenu.show();
+        self.popupmenu.set_font(opts.font.clone(), &self.hl_defs);
+        self.cmdline.set_font(opts.font.clone(), &self.hl_defs);
+        self.command_palette
+            .set_font(opts.font.clone(), &self.hl_defs);
+        self.finder.set_font(opts.font.clone(), &self.hl_defs);
+        self.toasts.set_font(opts.font.clone(), &self.hl_defs);
+        self.tabline.set_font(opts.font.clone(), &self.hl_defs);
+        self.search_bar.set_font(opts.font.clone(), &self.hl_defs);
+        self.statusbar.set_font(opts.font.clone(), &self.hl_defs);
+        self.cursor_tooltip.set_font(opts.font.clone());
+        self.preview.set_font(opts.font.clone());
+        self.file_tree.set_font(opts.font.clone());
+
+        let metrics = grid.get_grid_metrics();
+        for window in self.windows.values_mut() {
+            window.set_winbar_font(
+                opts.font.clone(),
+                metrics.cell_height,
+                &self.hl_defs,
+            );
+            window.set_cell_size(metrics.cell_width, metrics.cell_height);
+        }
 
-            // If the cursor tooltip is visible at the same time, move
-            // it out of our way.
-            #[cfg(feature = "libwebkit2gtk")]
-            {
-                if self.cursor_tooltip.is_visible() {
-                    if self.popupmenu.is_above_anchor() {
-                        self.cursor_tooltip.force_gravity(Some(Gravity::Down));
-                    } else {
-                        self.cursor_tooltip.force_gravity(Some(Gravity::Up));
-                    }
+        self.cmdline.set_line_space(opts.line_space);
+        self.popupmenu
+            .set_line_space(opts.line_space, &self.hl_defs);
+        self.tabline.set_line_space(opts.line_space, &self.hl_defs);
+    }
 
-                    self.cursor_tooltip.refresh_position();
-                }
+    /// Updates the cell metrics of the grids with the current font, e.g.
+    /// when the scale of the device pixels or the font DPI changes, and
+    /// redraws the grids at the new scale.
+    pub fn refresh_metrics(
+        &mut self,
+        nvim: &GioNeovim,
+        window: &gtk::ApplicationWindow,
+    ) {
+        let opts = self.resize_on_flush.take().unwrap_or_else(|| {
+            let grid = self.grids.get(&1).unwrap();
+            ResizeOptions {
+                font: grid.get_font(),
+                line_space: grid.get_line_space(),
             }
+        });
+        self.update_metrics(opts, nvim, window);
+
+        let win = window.get_window().unwrap();
+        for grid in self.grids.values() {
+            grid.rescale(&win, &self.hl_defs);
         }
     }
 
-    fn popupmenu_hide(&mut self) {
-        if self.wildmenu_shown {
-            self.cmdline.wildmenu_hide();
-            self.wildmenu_shown = false;
-        } else {
-            self.popupmenu.hide();
+    fn flush(&mut self, nvim: &GioNeovim, window: &gtk::ApplicationWindow) {
+        let start = Instant::now();
 
-            // Undo any force positioning of cursor tool tip that might
-            // have occured on popupmenu show.
-            #[cfg(feature = "libwebkit2gtk")]
-            {
-                self.cursor_tooltip.force_gravity(None);
-                self.cursor_tooltip.refresh_position();
+        if !self.hl_redraw.is_empty() {
+            let hl_redraw = std::mem::take(&mut self.hl_redraw);
+            for grid in self.grids.values() {
+                grid.mark_stale(|id| hl_redraw.contains(&id));
             }
         }
-    }
 
-    fn popupmenu_select(&mut self, selected: i64) {
-        if self.wildmenu_shown {
-            self.cmdline.wildmenu_select(selected as i32);
-        } else {
-            self.popupmenu.select(selected as i32, &self.hl_defs);
+        for grid in self.grids.values() {
+            grid.flush(&self.hl_defs);
         }
-    }
 
-    fn tabline_update(
-        &mut self,
-        TablineUpdate { current, tabs }: TablineUpdate,
-        nvim: &GioNeovim,
-    ) {
-        let current = Tabpage::new(current, nvim.clone());
-        let tabs = tabs
-            .into_iter()
-            .map(|(value, name)| (Tabpage::new(value, nvim.clone()), name))
-            .collect();
-        self.tabline.update(current, tabs);
-    }
+        if let Some(grid) =
+            self.msg_window.grid_id.and_then(|id| self.grids.get(&id))
+        {
+            self.msg_window.update_contents(grid);
+        }
 
-    fn cmdline_show(&mut self, cmdline_show: CmdlineShow) {
-        self.cmdline.show(cmdline_show, &self.hl_defs);
-    }
+        if let Some(opts) = self.resize_on_flush.take() {
+            self.update_metrics(opts, nvim, window);
+        }
 
-    fn cmdline_hide(&mut self) {
-        self.cmdline.hide();
-    }
+        if self.hl_changed {
+            self.update_colors();
+            self.hl_changed = false;
+        }
 
-    fn cmdline_pos(&mut self, CmdlinePos { pos, level }: CmdlinePos) {
-        self.cmdline.set_pos(pos, level);
+        self.perf.flushed(start.elapsed());
     }
 
-    fn cmdline_special_char(&mut self, s: CmdlineSpecialChar) {
-        self.cmdline
-            .show_special_char(s.character, s.shift, s.level);
-    }
+    /// Updates the colors of gnvim's own widgets from the highlight groups.
+    fn update_colors(&mut self) {
+        self.popupmenu.set_colors(&self.hl_defs);
+        self.tabline.set_colors(&self.hl_defs);
+        self.cmdline.set_colors(&self.hl_defs);
+        self.cmdline.wildmenu_set_colors(&self.hl_defs);
+        self.command_palette.set_colors(&self.hl_defs);
+        self.search_bar.set_colors(&self.hl_defs);
+        self.statusbar.set_colors(&self.hl_defs);
+        self.finder.set_colors(&self.hl_defs);
+        self.toasts.set_colors(&self.hl_defs);
+        self.file_tree.set_colors(&self.hl_defs);
+
+        let float = self.hl_defs.get_hl_group(&HlGroup::NormalFloat);
+        self.cursor_tooltip.set_colors(
+            float
+                .and_then(|hl| hl.foreground)
+                .unwrap_or(self.hl_defs.default_fg),
+            float
+                .and_then(|hl| hl.background)
+                .unwrap_or(self.hl_defs.default_bg),
+        );
 
-    fn cmdline_block_show(&mut self, show: CmdlineBlockShow) {
-        self.cmdline.show_block(&show, &self.hl_defs);
+        self.float_drag.set_color(
+            self.hl_defs
+                .get_hl_group(&HlGroup::FloatBorder)
+                .and_then(|hl| hl.foreground)
+                .unwrap_or(self.hl_defs.default_fg),
+        );
+        self.separator_drag.set_color(
+            self.hl_defs
+                .get_hl_group(&HlGroup::VertSplit)
+                .and_then(|hl| hl.foreground)
+                .unwrap_or(self.hl_defs.default_fg),
+        );
+        for window in self.windows.values() {
+            window.set_colors(&self.hl_defs);
+        }
+        self.update_float_styles();
+
+        self.load_css();
     }
 
-    fn cmdline_block_append(&mut self, line: CmdlineBlockAppend) {
-        self.cmdline.block_append(line, &self.hl_defs);
+    /// Sets the styles for our main window.
+    fn load_css(&self) {
+        let msgsep = self
+            .hl_defs
+            .get_hl_group(&HlGroup::MsgSeparator)
+            .cloned()
+            .unwrap_or_default()
+            .foreground;
+
+        // When the background is translucent, the grids draw it. Otherwise
+        // the window's background would be visible under the grids too.
+        let bg = if self.background_opacity < 1.0 {
+            String::from("transparent")
+        } else {
+            format!("#{}", self.hl_defs.default_bg.to_hex())
+        };
+
+        CssProviderExt::load_from_data(
+            &self.css_provider,
+            format!(
+                "* {{
+                    background: {bg};
+                }}
+
+                frame > border {{
+                    border: none;
+                }}
+
+                #message-grid-contianer frame.scrolled {{
+                    border-top: 1px solid #{msgsep}
+                }}
+                ",
+                bg = bg,
+                msgsep = msgsep.unwrap_or(self.hl_defs.default_fg).to_hex(),
+            )
+            .as_bytes(),
+        )
+        .unwrap();
     }
 
-    fn cmdline_block_hide(&mut self) {
-        self.cmdline.hide_block();
+    /// Returns the background opacity for grid `id`.
+    fn grid_background_opacity(&self, id: i64) -> f64 {
+        // The default grid (which is 1) is under all the other grids, and
+        // so are the split windows' grids.
+        let split = id == 1
+            || self
+                .windows
+                .get(&id)
+                .map_or(false, |w| !w.is_float() && !w.is_external());
+
+        background_opacity_for(
+            self.background_opacity,
+            self.background_image.borrow().is_some(),
+            split,
+        )
     }
 
-    fn window_pos(&mut self, evt: WindowPos, nvim: &GioNeovim) {
-        let base_metrics = self.grids.get(&1).unwrap().get_grid_metrics();
-        let x = evt.start_col as f64 * base_metrics.cell_width;
-        let y = evt.start_row as f64 * base_metrics.cell_height;
-        let width = evt.width as f64 * base_metrics.cell_width;
-        let height = evt.height as f64 * base_metrics.cell_height;
+    /// Sets the opacity of the background. Text and highlighted areas stay
+    /// opaque. Requires a compositing window manager.
+    pub fn set_background_opacity(&mut self, opacity: f64) {
+        self.background_opacity = opacity.max(0.0).min(1.0);
+        self.update_grids_background_opacity();
+        self.load_css();
+        self.overlay.queue_draw();
+    }
 
-        let window = self.get_or_create_window(
-            evt.grid,
-            self.windows_container.clone().upcast(),
-            nvim,
-            evt.win,
-        );
+    /// Follows the desktop's color scheme: gtk's widgets use the dark
+    /// variant of the theme when the scheme is dark, gnvim's own widgets
+    /// (e.g. the tabline and the popupmenu) fall back to the scheme's
+    /// colors when nvim has no default colors, and nvim gets the scheme in
+    /// `g:gnvim_color_scheme` and a `User GnvimColorScheme` autocmd.
+    pub fn set_color_scheme(
+        &mut self,
+        scheme: ColorScheme,
+        nvim: &GioNeovim,
+        window: &gtk::ApplicationWindow,
+    ) {
+        if self.color_scheme == scheme {
+            return;
+        }
+        self.color_scheme = scheme;
 
-        window.set_position(x, y, width, height);
-        window.show();
-    }
+        // The fallback colors might be in use.
+        if let Some(colors) = self.default_colors.clone() {
+            self.default_colors_set(colors);
+            self.queue_flush(nvim, window);
+        }
 
-    fn get_float_anchor_pos(&self, evt: &WindowFloatPos) -> (f64, f64) {
-        if evt.anchor_grid == evt.grid {
-            warn!("Can't use a grid as its own float anchor. Defaulting to base grid.");
+        if let Some(settings) = gtk::Settings::get_default() {
+            settings.set_property_gtk_application_prefer_dark_theme(
+                scheme == ColorScheme::Dark || self.prefer_dark_theme,
+            );
         }
 
-        if evt.anchor_grid == 1 || evt.anchor_grid == evt.grid {
-            (0.0, 0.0)
+        let cmd = format!(
+            "let g:gnvim_color_scheme = '{}' | \
+             if exists('#User#GnvimColorScheme') | \
+             doautocmd <nomodeline> User GnvimColorScheme | endif",
+            scheme.as_background()
+        );
+        let nvim = nvim.clone();
+        spawn_local(async move {
+            if let Err(err) = nvim.command(&cmd).await {
+                error!("GnvimColorScheme error: {:?}", err);
+            }
+        });
+    }
+
+    /// Loads the user's CSS from `path`. Empty `path` removes it.
+    pub fn set_custom_css(&self, path: &str, nvim: &GioNeovim) {
+        let res = if path.is_empty() {
+            CssProviderExt::load_from_data(&self.custom_css_provider, b"")
         } else {
-            let anchor_window = self.windows.get(&evt.anchor_grid).unwrap();
-            (anchor_window.x, anchor_window.y)
+            CssProviderExt::load_from_path(&self.custom_css_provider, path)
+        };
+
+        if let Err(err) = res {
+            nvim_error(nvim, format!("Failed to load CSS '{}': {}", path, err));
         }
     }
 
-    /// Get or create a new window.
-    ///
-    /// * `grid` - The id of the grid for which to get the window for
-    /// * `container` - The continer where to put the window (both existing window or a new one).
-    /// * `nvim` - Copy if nvim
-    /// * `win` - Value of the window.
-    fn get_or_create_window(
+    /// Sets the image drawn behind the grids. Empty `path` removes the
+    /// image.
+    fn set_background_image(
         &mut self,
-        grid: i64,
-        container: gtk::Fixed,
+        path: &str,
+        blur: u64,
+        dim: f64,
         nvim: &GioNeovim,
-        win: nvim_rs::Value,
-    ) -> &mut Window {
-        let grid = self.grids.get(&grid).unwrap();
-        let css_provider = self.css_provider.clone();
-        self.windows
-            .entry(grid.id)
+    ) {
+        let image = if path.is_empty() {
+            None
+        } else {
+            match Background::new(path, blur as u32, dim) {
+                Ok(image) => Some(image),
+                Err(err) => {
+                    nvim_error(
+                        nvim,
+                        format!(
+                            "Failed to load background image '{}': {}",
+                            path, err
+                        ),
+                    );
+                    return;
+                }
+            }
+        };
+
+        self.background_image.replace(image);
+        self.update_grids_background_opacity();
+        self.overlay.queue_draw();
+    }
+
+    /// Shows the image at `path` on top of the current grid's cells.
+    fn show_image(
+        &self,
+        id: u64,
+        path: &str,
+        pos: (i64, u64),
+        size: (u64, u64),
+        nvim: &GioNeovim,
+    ) {
+        let grid = match self.grids.get(&self.current_grid) {
+            Some(grid) => grid,
+            None => return,
+        };
+
+        match Image::new(id, path, pos, size) {
+            Ok(image) => grid.show_image(image),
+            Err(err) => {
+                nvim_error(
+                    nvim,
+                    format!("Failed to load image '{}': {}", path, err),
+                );
+            }
+        }
+    }
+
+    /// Exports `lines`, or the current grid if there are no lines, to
+    /// `path`.
+    fn export(
+        &self,
+        format: ExportFormat,
+        path: &str,
+        lines: Option<&[export::Line]>,
+        nvim: &GioNeovim,
+    ) {
+        let grid = match self.grids.get(&self.current_grid) {
+            Some(grid) => grid,
+            None => return,
+        };
+
+        let grid_lines;
+        let lines = match lines {
+            Some(lines) => lines,
+            None => {
+                grid_lines = grid
+                    .get_contents()
+                    .into_iter()
+                    .map(|row| {
+                        row.into_iter()
+                            .map(|(text, hl_id)| {
+                                let hl = self.hl_defs.get(&hl_id).copied();
+                                (text, hl.unwrap_or_default())
+                            })
+                            .collect()
+                    })
+                    .collect::<Vec<_>>();
+                &grid_lines
+            }
+        };
+
+        if let Err(err) =
+            export::export(lines, &self.hl_defs, &grid.get_font(), format, path)
+        {
+            nvim_error(
+                nvim,
+                format!("Failed to export to '{}': {}", path, err),
+            );
+        }
+    }
+
+    /// Takes a screenshot of `target`, and saves it to `path` (or copies it
+    /// to the clipboard).
+    fn screenshot(
+        &self,
+        window: &gtk::ApplicationWindow,
+        target: ScreenshotTarget,
+        path: Option<&str>,
+        nvim: &GioNeovim,
+    ) {
+        let grid = |id: Option<i64>| {
+            let id = id.unwrap_or(self.current_grid);
+            self.grids
+                .get(&id)
+                .ok_or_else(|| format!("No grid with id {}", id))
+        };
+
+        let image = match target {
+            // The window's child excludes client side decorations.
+            ScreenshotTarget::Window => {
+                screenshot::widget(&window.get_child().unwrap())
+            }
+            ScreenshotTarget::Grid(id) => {
+                grid(id).and_then(|grid| grid.snapshot(None))
+            }
+            ScreenshotTarget::Cells(row, col, rows, cols) => grid(None)
+                .and_then(|grid| grid.snapshot(Some((row, col, rows, cols)))),
+        };
+
+        if let Err(err) = image.and_then(|image| screenshot::save(&image, path))
+        {
+            nvim_error(nvim, format!("Failed to take a screenshot: {}", err));
+        }
+    }
+
+    /// Writes the grids to `dir` (see `offscreen::dump_grids`).
+    fn dump_grids(
+        &mut self,
+        dir: &Path,
+        window: &gtk::ApplicationWindow,
+        nvim: &GioNeovim,
+    ) {
+        // Include the changes that are waiting for the next frame.
+        self.apply_pending_flush(nvim, window);
+        self.redraw_stale(usize::MAX);
+
+        if let Err(err) = offscreen::dump_grids(&self.grids, dir) {
+            nvim_error(nvim, format!("Failed to dump the grids: {}", err));
+        }
+    }
+
+    /// Writes the grids to `offscreen_dir`, if it's set. Called when nvim
+    /// exits.
+    pub fn dump_offscreen(&self) {
+        if let Some(ref dir) = self.offscreen_dir {
+            if let Err(err) = offscreen::dump_grids(&self.grids, dir) {
+                error!("Failed to dump the grids: {}", err);
+            }
+        }
+    }
+
+    /// Shows (or deletes) the images of a program running in the terminal
+    /// of the current grid. The images are placed at `pos`, the terminal's
+    /// cursor.
+    fn terminal_graphics(&mut self, id: u64, sequence: &str, pos: (i64, u64)) {
+        let (data, format, size) = match self.terminal_graphics.parse(sequence)
+        {
+            Some(Command::Show { data, format, size }) => (data, format, size),
+            Some(Command::Delete) => {
+                if let Some(grid) = self.grids.get(&self.current_grid) {
+                    grid.clear_images();
+                }
+                return;
+            }
+            None => return,
+        };
+
+        let grid = match self.grids.get(&self.current_grid) {
+            Some(grid) => grid,
+            None => return,
+        };
+
+        let pixbuf = match terminal_graphics::decode(data, &format) {
+            Ok(pixbuf) => pixbuf,
+            Err(err) => {
+                error!("Failed to decode terminal image: {}", err);
+                return;
+            }
+        };
+
+        let metrics = grid.get_grid_metrics();
+        let size = terminal_graphics::cell_size(
+            (pixbuf.get_width(), pixbuf.get_height()),
+            (metrics.cell_width, metrics.cell_height),
+            size,
+            (metrics.cols as u64).saturating_sub(pos.1),
+        );
+        grid.show_image(Image::from_pixbuf(id, pixbuf, pos, size));
+    }
+
+    /// Updates the background opacity of grid `id`, e.g. after its window
+    /// became floating.
+    fn update_grid_background_opacity(&self, id: i64) {
+        let opacity = self.grid_background_opacity(id);
+        if let Some(grid) = self.grids.get(&id) {
+            if grid.get_background_opacity() != opacity {
+                grid.set_background_opacity(
+                    opacity,
+                    &self.overlay.get_window().unwrap(),
+                    &self.hl_defs,
+                );
+            }
+        }
+    }
+
+    fn update_grids_background_opacity(&self) {
+        let win = self.overlay.get_window().unwrap();
+        for (id, grid) in self.grids.iter() {
+            grid.set_background_opacity(
+                self.grid_background_opacity(*id),
+                &win,
+                &self.hl_defs,
+            );
+        }
+    }
+
+    fn popupmenu_show(&mut self, popupmenu: PopupmenuShow) {
+        if popupmenu.grid == -1 {
+            self.wildmenu_shown = true;
+            self.cmdline.wildmenu_show(&popupmenu.items)
+        } else {
+            let grids = &self.grids;
+            let grid = match grids
+                .get(&popupmenu.grid)
+                .or_else(|| grids.get(&self.current_grid))
+            {
+                Some(grid) => grid,
+                None => {
+                    warn!("No grid for popupmenu (grid: {})", popupmenu.grid);
+                    return;
+                }
+            };
+            let query =
+                grid.get_text_before_cursor(popupmenu.row, popupmenu.col);
+
+            let window = self
+                .windows
+                .get(&popupmenu.grid)
+                .filter(|w| !w.is_external())
+                .map(|w| (w.x, w.y));
+            let msg = self
+                .msg_window
+                .grid_id
+                .map(|id| (id, self.msg_window.grid_y()));
+            let origin = grid_origin(popupmenu.grid, window, msg)
+                .unwrap_or_else(|| {
+                    warn!(
+                        "Can't resolve popupmenu's grid {}, using base grid",
+                        popupmenu.grid
+                    );
+                    (0.0, 0.0)
+                });
+
+            let base_metrics = grids.get(&1).unwrap().get_grid_metrics();
+            let rect = cell_anchor_rect(
+                origin,
+                &grid.get_grid_metrics(),
+                &base_metrics,
+                popupmenu.row,
+                popupmenu.col,
+            );
+
+            self.popupmenu
+                .set_items(popupmenu.items, query, &self.hl_defs);
+            self.popupmenu.set_anchor(rect);
+            let cursor_tooltip = &self.cursor_tooltip;
+            self.popupmenu.select(
+                popupmenu.selected as i32,
+                &self.hl_defs,
+                |lang, code| cursor_tooltip.highlight(lang, code),
+            );
+
+            self.popupmenu.show();
+
+            // If the cursor tooltip is visible at the same time, move
+            // it out of our way.
+            if self.cursor_tooltip.is_visible() {
+                if self.popupmenu.is_above_anchor() {
+                    self.cursor_tooltip.force_gravity(Some(Gravity::Down));
+                } else {
+                    self.cursor_tooltip.force_gravity(Some(Gravity::Up));
+                }
+
+                self.cursor_tooltip.refresh_position();
+            }
+        }
+    }
+
+    /// Returns the rectangle of the cursor, relative to the base grid.
+    fn cursor_rect(&self) -> Option<gdk::Rectangle> {
+        let grid = self.grids.get(&self.current_grid)?;
+        let (row, col) = grid.get_cursor_position()?;
+
+        let window = self
+            .windows
+            .get(&self.current_grid)
+            .filter(|w| !w.is_external())
+            .map(|w| (w.x, w.y));
+        let msg = self
+            .msg_window
+            .grid_id
+            .map(|id| (id, self.msg_window.grid_y()));
+        let origin = grid_origin(self.current_grid, window, msg)?;
+
+        let base_metrics = self.grids.get(&1)?.get_grid_metrics();
+        Some(cell_anchor_rect(
+            origin,
+            &grid.get_grid_metrics(),
+            &base_metrics,
+            row,
+            col,
+        ))
+    }
+
+    /// Returns the rectangle of the current grid's window, relative to the
+    /// base grid.
+    fn current_window_rect(&self) -> Option<gdk::Rectangle> {
+        let grid = self.grids.get(&self.current_grid)?;
+        let window = self
+            .windows
+            .get(&self.current_grid)
+            .filter(|w| !w.is_external())
+            .map(|w| (w.x, w.y));
+        let msg = self
+            .msg_window
+            .grid_id
+            .map(|id| (id, self.msg_window.grid_y()));
+        let (x, y) = grid_origin(self.current_grid, window, msg)?;
+
+        let metrics = grid.get_grid_metrics();
+        Some(gdk::Rectangle {
+            x: x as i32,
+            y: y as i32,
+            width: metrics.width as i32,
+            height: metrics.height as i32,
+        })
+    }
+
+    fn popupmenu_hide(&mut self) {
+        if self.wildmenu_shown {
+            self.cmdline.wildmenu_hide();
+            self.wildmenu_shown = false;
+        } else {
+            self.popupmenu.hide();
+
+            // Undo any force positioning of cursor tool tip that might
+            // have occured on popupmenu show.
+            self.cursor_tooltip.force_gravity(None);
+            self.cursor_tooltip.refresh_position();
+        }
+    }
+
+    fn popupmenu_select(&mut self, selected: i64) {
+        if self.wildmenu_shown {
+            self.cmdline.wildmenu_select(selected as i32);
+        } else {
+            let cursor_tooltip = &self.cursor_tooltip;
+            self.popupmenu.select(
+                selected as i32,
+                &self.hl_defs,
+                |lang, code| cursor_tooltip.highlight(lang, code),
+            );
+        }
+    }
+
+    fn tabline_update(
+        &mut self,
+        TablineUpdate { current, tabs, .. }: TablineUpdate,
+        nvim: &GioNeovim,
+    ) {
+        let current = nvim.tabpage(current);
+        let tabs = tabs
+            .into_iter()
+            .map(|(value, name)| (nvim.tabpage(value), name))
+            .collect();
+        self.tabline.update(current, tabs);
+    }
+
+    fn cmdline_show(&mut self, cmdline_show: CmdlineShow) {
+        let cursor = self.cursor_rect();
+        let window = self.current_window_rect();
+        self.cmdline
+            .show(cmdline_show, cursor, window, &self.hl_defs);
+    }
+
+    fn cmdline_hide(&mut self) {
+        self.cmdline.hide();
+    }
+
+    fn cmdline_pos(&mut self, CmdlinePos { pos, level }: CmdlinePos) {
+        self.cmdline.set_pos(pos, level);
+    }
+
+    fn cmdline_special_char(&mut self, s: CmdlineSpecialChar) {
+        self.cmdline
+            .show_special_char(s.character, s.shift, s.level);
+    }
+
+    fn cmdline_block_show(&mut self, show: CmdlineBlockShow) {
+        self.cmdline.show_block(&show, &self.hl_defs);
+    }
+
+    fn cmdline_block_append(&mut self, line: CmdlineBlockAppend) {
+        self.cmdline.block_append(line, &self.hl_defs);
+    }
+
+    fn cmdline_block_hide(&mut self) {
+        self.cmdline.hide_block();
+    }
+
+    fn window_pos(
+        &mut self,
+        evt: WindowPos,
+        window: &gtk::ApplicationWindow,
+        nvim: &GioNeovim,
+    ) {
+        // Give the window a placeholder grid of its size until the grid
+        // arrives.
+        if lookup::needs_placeholder(&self.grids, evt.grid) {
+            self.create_grid(evt.grid, evt.width, evt.height, window, nvim);
+        }
+
+        let base_metrics = self.grids.get(&1).unwrap().get_grid_metrics();
+        let x = evt.start_col as f64 * base_metrics.cell_width;
+        let y = evt.start_row as f64 * base_metrics.cell_height;
+        let width = evt.width as f64 * base_metrics.cell_width;
+        let height = evt.height as f64 * base_metrics.cell_height;
+
+        let window = match self.get_or_create_window(
+            evt.grid,
+            self.windows_container.clone().upcast(),
+            nvim,
+            evt.win,
+        ) {
+            Some(window) => window,
+            None => return,
+        };
+
+        window.set_float_style(None);
+        window.set_position(x, y, width, height);
+        window.layout_size = Some((evt.width, evt.height));
+        window.show();
+
+        let grid_id = evt.grid;
+        self.float_stack.retain(|(grid, _)| *grid != grid_id);
+        self.float_positions.remove(&grid_id);
+
+        self.fit_grid_to_layout(grid_id, nvim);
+        self.update_indent_info(grid_id, nvim);
+    }
+
+    /// Resizes the grid of a split window to the window's size in the
+    /// layout, leaving out the columns the window's minimap takes.
+    fn fit_grid_to_layout(&self, grid: i64, nvim: &GioNeovim) {
+        let (window, metrics) =
+            match (self.windows.get(&grid), self.grids.get(&grid)) {
+                (Some(window), Some(g)) => (window, g.get_grid_metrics()),
+                _ => return,
+            };
+        let (width, rows) = match window.layout_size {
+            Some(size) => size,
+            None => return,
+        };
+
+        let cell_width =
+            self.grids.get(&1).unwrap().get_grid_metrics().cell_width;
+        let cols = width.saturating_sub(window.minimap_cols(cell_width)).max(1);
+        if metrics.cols as u64 == cols && metrics.rows as u64 == rows {
+            return;
+        }
+
+        let nvim = nvim.clone();
+        spawn_local(async move {
+            if let Err(err) = nvim
+                .ui_try_resize_grid(grid, cols as i64, rows as i64)
+                .await
+            {
+                error!("Failed to resize grid({}): {}", grid, err);
+            }
+        });
+    }
+
+    /// Returns the floating window at `x`, `y` of the base grid, if the
+    /// position is on its grab area or edges (see `float_drag::hit_test`).
+    pub fn float_at(&self, x: f64, y: f64) -> Option<FloatTarget> {
+        let base_metrics = self.grids.get(&1).unwrap().get_grid_metrics();
+
+        // The topmost window under the pointer.
+        let (window, rect, metrics) =
+            self.float_stack.iter().rev().find_map(|(grid, _)| {
+                let window = self.windows.get(grid)?;
+                if !window.is_visible() || window.is_external() {
+                    return None;
+                }
+
+                let metrics = self.grids.get(grid)?.get_grid_metrics();
+                let rect = FloatRect {
+                    x: window.x,
+                    y: window.y,
+                    width: metrics.width,
+                    height: metrics.height,
+                };
+                let inside = x >= rect.x
+                    && x < rect.x + rect.width
+                    && y >= rect.y
+                    && y < rect.y + rect.height;
+                Some((window, rect, metrics)).filter(|_| inside)
+            })?;
+
+        Some(FloatTarget {
+            win: window.nvim_win.clone(),
+            kind: float_drag::hit_test(&rect, x, y)?,
+            rect,
+            size: (metrics.cols as u64, metrics.rows as u64),
+            cell: (base_metrics.cell_width, base_metrics.cell_height),
+        })
+    }
+
+    /// Returns the separator (or status line) of a window at `x`, `y` of
+    /// the base grid.
+    pub fn separator_at(&self, x: f64, y: f64) -> Option<Separator> {
+        let base_metrics = self.grids.get(&1).unwrap().get_grid_metrics();
+        let (cw, ch) = (base_metrics.cell_width, base_metrics.cell_height);
+
+        // Floating windows are on top of the separators.
+        let on_float = self.windows.values().any(|w| {
+            let metrics = match self.grids.get(&w.grid_id) {
+                Some(grid) if w.is_float() && w.is_visible() => {
+                    grid.get_grid_metrics()
+                }
+                _ => return false,
+            };
+            x >= w.x
+                && x < w.x + metrics.width
+                && y >= w.y
+                && y < w.y + metrics.height
+        });
+        if on_float {
+            return None;
+        }
+
+        let windows = self
+            .windows
+            .values()
+            .filter(|w| !w.is_float() && !w.is_external() && w.is_visible())
+            .filter_map(|w| {
+                let metrics = self.grids.get(&w.grid_id)?.get_grid_metrics();
+                let rect = WinRect {
+                    row: (w.y / ch).round() as u64,
+                    col: (w.x / cw).round() as u64,
+                    width: metrics.cols as u64,
+                    height: metrics.rows as u64,
+                };
+                Some((w, rect))
+            })
+            .collect::<Vec<_>>();
+
+        let rects = windows.iter().map(|(_, rect)| *rect).collect::<Vec<_>>();
+        let row = cell_index(y, ch);
+        let col = cell_index(x, cw);
+        let (i, kind) = separator_drag::hit_test(&rects, row, col)?;
+        let (window, rect) = &windows[i];
+
+        Some(Separator {
+            win: window.nvim_win.clone(),
+            kind,
+            rect: *rect,
+            cell: (cw, ch),
+        })
+    }
+
+    /// Fetches the layout of `grid`'s window's text, if it needs indent
+    /// guides and hasn't been fetched yet. Floating windows don't have
+    /// them.
+    fn update_indent_info(&mut self, grid: i64, nvim: &GioNeovim) {
+        if !self.indent_guides.enabled
+            || self.float_positions.contains_key(&grid)
+        {
+            return;
+        }
+
+        if let (Some(window), Some(grid)) =
+            (self.windows.get_mut(&grid), self.grids.get(&grid))
+        {
+            if !window.indent_info_fetched {
+                window.indent_info_fetched = true;
+                grid.update_indent_info(
+                    nvim,
+                    window.nvim_win.get_value().clone(),
+                );
+            }
+        }
+    }
+
+    /// Fetches the indent info of all windows again, e.g. after an option
+    /// it depends on changed.
+    fn refresh_indent_info(&mut self, nvim: &GioNeovim) {
+        let grids = self.windows.keys().copied().collect::<Vec<_>>();
+        for grid in grids {
+            if let Some(window) = self.windows.get_mut(&grid) {
+                window.indent_info_fetched = false;
+            }
+            self.update_indent_info(grid, nvim);
+        }
+    }
+
+    fn set_indent_guides(&mut self, guides: IndentGuides, nvim: &GioNeovim) {
+        self.indent_guides = guides;
+        self.grids
+            .values()
+            .for_each(|g| g.set_indent_guides(guides));
+
+        // Changes aren't tracked while the guides are disabled.
+        self.refresh_indent_info(nvim);
+    }
+
+    /// Returns the grid that `evt`'s float is positioned relative to, and
+    /// the position of that grid. Falls back to the base grid when the
+    /// anchor grid can't be used.
+    fn get_float_anchor(&self, evt: &WindowFloatPos) -> (i64, (f64, f64)) {
+        let anchor_window = self
+            .windows
+            .get(&evt.anchor_grid)
+            .filter(|_| self.grids.contains_key(&evt.anchor_grid))
+            .map(|w| (w.x, w.y, w.is_external()));
+
+        match float_anchor(evt.grid, evt.anchor_grid, anchor_window) {
+            Ok(Some(pos)) => (evt.anchor_grid, pos),
+            Ok(None) => (1, (0.0, 0.0)),
+            Err(err) => {
+                warn!("{} Defaulting to base grid.", err);
+                (1, (0.0, 0.0))
+            }
+        }
+    }
+
+    /// Get or create a new window.
+    ///
+    /// * `grid` - The id of the grid for which to get the window for
+    /// * `container` - The continer where to put the window (both existing window or a new one).
+    /// * `nvim` - Copy if nvim
+    /// * `win` - Value of the window.
+    fn get_or_create_window(
+        &mut self,
+        grid: i64,
+        container: gtk::Fixed,
+        nvim: &GioNeovim,
+        win: nvim_rs::Value,
+    ) -> Option<&mut Window> {
+        let grid = self.grids.lookup(grid, "window creation")?;
+        let css_provider = self.css_provider.clone();
+        let scrollbar = self.scrollbar;
+        let minimap = self.minimap;
+        let hl_defs = &self.hl_defs;
+        let font = self.font.clone();
+        let window = self
+            .windows
+            .entry(grid.id)
             .and_modify(clone!(container => move |w| {
                 // Set the parent window's to the float container if needed.
                 w.set_parent(container.upcast());
             }))
             .or_insert_with(|| {
-                Window::new(
-                    NvimWindow::new(win, nvim.clone()),
+                let mut window = Window::new(
+                    nvim.window(win),
+                    nvim.clone(),
                     container,
                     &grid,
                     Some(css_provider),
-                )
-            })
+                );
+                window.enable_scrollbar(scrollbar);
+                window.enable_minimap(minimap);
+                window.set_colors(hl_defs);
+                let metrics = grid.get_grid_metrics();
+                window.set_winbar_font(font, metrics.cell_height, hl_defs);
+                window.set_cell_size(metrics.cell_width, metrics.cell_height);
+                window
+            });
+
+        Some(window)
     }
 
     fn window_float_pos(&mut self, evt: WindowFloatPos, nvim: &GioNeovim) {
-        let (x_offset, y_offset) = self.get_float_anchor_pos(&evt);
+        self.position_float(evt, nvim, 0);
+    }
 
-        let anchor_metrics =
-            self.grids.get(&evt.anchor_grid).unwrap().get_grid_metrics();
-        let grid_metrics =
-            self.grids.get(&evt.grid).unwrap().get_grid_metrics();
+    /// Positions a floating window, and then (recursively) the floats
+    /// anchored to it.
+    fn position_float(
+        &mut self,
+        evt: WindowFloatPos,
+        nvim: &GioNeovim,
+        depth: usize,
+    ) {
+        let (anchor_grid, (x_offset, y_offset)) = self.get_float_anchor(&evt);
+
+        let (anchor_metrics, grid_metrics) = match (
+            self.grids.lookup(anchor_grid, "win_float_pos"),
+            self.grids.lookup(evt.grid, "win_float_pos"),
+        ) {
+            (Some(anchor), Some(grid)) => {
+                (anchor.get_grid_metrics(), grid.get_grid_metrics())
+            }
+            _ => return,
+        };
         let base_metrics = self.grids.get(&1).unwrap().get_grid_metrics();
-
-        let window = self.get_or_create_window(
+        let float_style = self.float_style(&evt.win);
+        // Windows that just became floating fade in.
+        let open_animation = self
+            .animations
+            .float_open()
+            .filter(|_| !self.float_positions.contains_key(&evt.grid));
+
+        let window = match self.get_or_create_window(
             evt.grid,
             self.windows_float_container.clone().upcast(),
             nvim,
             evt.win.clone(),
-        );
+        ) {
+            Some(window) => window,
+            None => return,
+        };
+        window.set_float_style(Some(float_style));
+        window.layout_size = None;
 
         let (x, y) = win_float_anchor_pos(
             &evt,
@@ -646,6 +1814,31 @@ impl UIState {
 
         window.set_position(x, y, grid_metrics.width, grid_metrics.height);
         window.show();
+        if let Some(duration) = open_animation {
+            window.animate_open(duration);
+        }
+
+        self.stack_float(evt.grid, evt.zindex);
+
+        let grid = evt.grid;
+        self.float_positions.insert(grid, evt);
+
+        // Floats anchored to this one might have been positioned before this
+        // one got its window (or its new position), so position them again.
+        if depth >= MAX_FLOAT_DEPTH {
+            warn!("Floats anchored too deep (grid: {}).", grid);
+            return;
+        }
+
+        let anchored: Vec<WindowFloatPos> = self
+            .float_positions
+            .values()
+            .filter(|e| e.anchor_grid == grid && e.grid != grid)
+            .cloned()
+            .collect();
+        for evt in anchored {
+            self.position_float(evt, nvim, depth + 1);
+        }
     }
 
     fn window_external_pos(
@@ -656,7 +1849,10 @@ impl UIState {
     ) {
         let parent_win = window.clone().upcast::<gtk::Window>();
         let grid_metrics = {
-            let grid = self.grids.get(&evt.grid).unwrap();
+            let grid = match self.grids.lookup(evt.grid, "win_external_pos") {
+                Some(grid) => grid,
+                None => return,
+            };
             let grid_metrics = grid.get_grid_metrics();
 
             // NOTE(ville): Without this, "new" grids (e.g. once added to a external
@@ -672,46 +1868,634 @@ impl UIState {
             grid_metrics
         };
 
-        let window = self.get_or_create_window(
+        // Windows of detached tabs are kept together, at their positions in
+        // the tab page.
+        let base_metrics = self.grids.get(&1).unwrap().get_grid_metrics();
+        let detached = window_handle(&evt.win).and_then(|handle| {
+            self.detached_tabs.iter().find_map(|(id, tab)| {
+                tab.get_position(handle).map(|(fixed, row, col)| {
+                    (
+                        *id,
+                        fixed.clone(),
+                        col as f64 * base_metrics.cell_width,
+                        row as f64 * base_metrics.cell_height,
+                    )
+                })
+            })
+        });
+
+        let keep_above = self.toplevel.keep_above;
+        let window = match self.get_or_create_window(
             evt.grid,
             self.windows_float_container.clone().upcast(),
             nvim,
             evt.win,
-        );
+        ) {
+            Some(window) => window,
+            None => return,
+        };
 
-        window.set_external(
-            &parent_win,
-            (
-                grid_metrics.width.ceil() as i32,
-                grid_metrics.height.ceil() as i32,
-            ),
+        window.set_float_style(None);
+        window.layout_size = None;
+        let size = (
+            grid_metrics.width.ceil() as i32,
+            grid_metrics.height.ceil() as i32,
         );
+        match detached {
+            Some((id, fixed, x, y)) => {
+                window.set_detached(&fixed, x, y, size);
+                if let Some(tab) = self.detached_tabs.get(&id) {
+                    tab.update_visibility();
+                }
+            }
+            None => {
+                window.set_external(
+                    &parent_win,
+                    size,
+                    &self.external_positions,
+                );
+                window.set_keep_above(keep_above);
+                window.update_external_title(&self.external_positions);
+            }
+        }
+
+        let grid_id = evt.grid;
+        self.float_stack.retain(|(grid, _)| *grid != grid_id);
+        self.float_positions.remove(&grid_id);
+    }
+
+    /// Keeps the floating windows stacked by their z-indices. Windows with
+    /// the same z-index stay in the order they were first shown.
+    fn stack_float(&mut self, grid: i64, zindex: u64) {
+        match self.float_stack.iter_mut().find(|(id, _)| *id == grid) {
+            Some(item) => item.1 = zindex,
+            None => self.float_stack.push((grid, zindex)),
+        }
+
+        if let Some(i) = sort_by_zindex(&mut self.float_stack) {
+            // Re-add the windows from the first one that moved, so that
+            // they end up in the right order on top of the others.
+            for (grid, _) in self.float_stack[i..].iter() {
+                if let Some(window) = self.windows.get(grid) {
+                    window.restack();
+                }
+            }
+        }
     }
 
     fn window_hide(&mut self, grid_id: i64) {
-        self.windows.get(&grid_id).unwrap().hide();
+        if let Some(window) = self.windows.lookup(grid_id, "win_hide") {
+            window.hide();
+        }
+        self.detached_tabs
+            .values()
+            .for_each(|tab| tab.update_visibility());
     }
 
     fn window_close(&mut self, grid_id: i64) {
+        self.float_stack.retain(|(grid, _)| *grid != grid_id);
+        self.float_positions.remove(&grid_id);
+
         // Drop window.
-        if self.windows.remove(&grid_id).is_none() {
-            warn!("Nvim instructed to close a window that we don't have (grid: {})", grid_id);
+        match self.windows.remove(&grid_id) {
+            Some(window) => {
+                if let Some(win) = window_handle(window.nvim_win.get_value()) {
+                    self.window_float_options.remove(&win);
+                }
+                self.animate_window_close(&window);
+            }
+            None => warn!("Nvim instructed to close a window that we don't have (grid: {})", grid_id),
+        }
+
+        // Detached tabs are closed with their last window.
+        self.detached_tabs.retain(|_, tab| !tab.is_empty());
+    }
+
+    /// Fades out a snapshot of `window`, which is being closed.
+    fn animate_window_close(&self, window: &Window) {
+        let duration = match self.animations.window_close() {
+            Some(duration) => duration,
+            None => return,
+        };
+        let (fixed, grid) =
+            match (window.container(), self.grids.get(&window.grid_id)) {
+                (Some(fixed), Some(grid)) if window.is_visible() => {
+                    (fixed, grid)
+                }
+                _ => return,
+            };
+
+        let metrics = grid.get_grid_metrics();
+        match grid.snapshot(None) {
+            Ok(pixbuf) => animation::fade_out(
+                fixed,
+                pixbuf,
+                (window.x, window.y, metrics.width, metrics.height),
+                duration,
+            ),
+            Err(err) => warn!("Failed to animate window close: {}", err),
+        }
+    }
+
+    fn detach_tab(
+        &mut self,
+        window: &gtk::ApplicationWindow,
+        id: u64,
+        title: &str,
+        windows: &[(i64, u64, u64)],
+        nvim: &GioNeovim,
+    ) {
+        let tab = DetachedTab::new(
+            &window.clone().upcast(),
+            id,
+            title,
+            windows.to_vec(),
+            nvim,
+        );
+        tab.set_keep_above(self.toplevel.keep_above);
+        self.detached_tabs.insert(id, tab);
+
+        // The windows might have been made external before the event.
+        let base_metrics = self.grids.get(&1).unwrap().get_grid_metrics();
+        let tab = self.detached_tabs.get(&id).unwrap();
+        for (grid_id, window) in self.windows.iter_mut() {
+            let pos = window_handle(window.nvim_win.get_value())
+                .and_then(|handle| tab.get_position(handle));
+            if let (Some((fixed, row, col)), Some(grid)) =
+                (pos, self.grids.get(grid_id))
+            {
+                if window.is_external() {
+                    let metrics = grid.get_grid_metrics();
+                    let size = (
+                        metrics.width.ceil() as i32,
+                        metrics.height.ceil() as i32,
+                    );
+                    window.set_detached(
+                        fixed,
+                        col as f64 * base_metrics.cell_width,
+                        row as f64 * base_metrics.cell_height,
+                        size,
+                    );
+                }
+            }
+        }
+        tab.update_visibility();
+    }
+
+    fn window_viewport(&mut self, evt: WindowViewport, nvim: &GioNeovim) {
+        // Older nvims don't tell the buffer's line count.
+        let line_count = match evt.line_count {
+            Some(line_count) => line_count,
+            None => {
+                self.update_indent_info(evt.grid, nvim);
+                return;
+            }
+        };
+
+        if let Some(window) = self.windows.get_mut(&evt.grid) {
+            window.set_viewport(evt.topline, evt.botline, line_count);
+
+            // The number column widens along with the line count.
+            let len = Some(line_count.to_string().len());
+            if window.line_count_len != len {
+                window.line_count_len = len;
+                window.indent_info_fetched = false;
+            }
+        }
+        self.update_indent_info(evt.grid, nvim);
+
+        if self.preview.win().is_some()
+            && self.preview.win() == evt.win_handle()
+        {
+            self.preview
+                .set_viewport(evt.topline, evt.botline, line_count);
+        }
+    }
+
+    fn msg_show(&mut self, e: MsgShow) {
+        self.toasts
+            .show(&e.kind, &e.content, e.replace_last, &self.hl_defs);
+    }
+
+    fn msg_history_show(&mut self, e: MsgHistoryShow) {
+        self.toasts.show_history(&e.entries, &self.hl_defs);
+    }
+
+    fn msg_set_pos(&mut self, e: MsgSetPos) {
+        let base_grid = self.grids.get(&1).unwrap();
+        let base_metrics = base_grid.get_grid_metrics();
+        if self.grids.lookup(e.grid, "msg_set_pos").is_none() {
+            return;
+        }
+        let h = base_metrics.height - e.row as f64 * base_metrics.cell_height;
+        if self.msg_window.grid_id != Some(e.grid) {
+            self.leave_copy_mode();
+        }
+        let grid = &self.grids[&e.grid];
+        self.msg_window.set_pos(grid, e.row as f64, h, e.scrolled);
+    }
+
+    fn enable_cursor_animations(&mut self, enable: bool) {
+        self.enable_cursor_animations = enable;
+        self.grids
+            .values()
+            .for_each(|g| g.enable_cursor_animations(enable));
+    }
+
+    /// Sets (or clears) the input method's preedit text to the current grid.
+    pub fn set_preedit(&self, preedit: Option<(&str, &pango::AttrList, i32)>) {
+        for (id, grid) in self.grids.iter() {
+            let preedit = preedit.filter(|_| *id == self.current_grid);
+            grid.set_preedit(preedit, &self.hl_defs);
+        }
+    }
+
+    /// Shows a font chooser dialog. Picking a font sets `guifont` in nvim,
+    /// which then notifies us about the option change.
+    fn show_font_picker(&self, nvim: &GioNeovim) {
+        let parent = self
+            .overlay
+            .get_toplevel()
+            .and_then(|w| w.downcast::<gtk::Window>().ok());
+
+        let dialog =
+            gtk::FontChooserDialog::new(Some("Select font"), parent.as_ref());
+        // Only monospace fonts work properly in the grid.
+        dialog
+            .set_filter_func(Some(Box::new(|family, _| family.is_monospace())));
+        dialog.set_font_desc(&self.font.as_pango_font());
+
+        dialog.connect_response(clone!(nvim => move |dialog, res| {
+            if res == gtk::ResponseType::Ok {
+                if let Some(desc) = dialog.get_font_desc() {
+                    let font = Font::from_pango_font(&desc);
+                    let guifont = nvim_rs::Value::from(font.as_guifont());
+
+                    let nvim = nvim.clone();
+                    spawn_local(async move {
+                        if let Err(err) =
+                            nvim.set_option("guifont", guifont).await
+                        {
+                            error!("Failed to set guifont: {}", err);
+                        }
+                    });
+                }
+            }
+
+            dialog.close();
+        }));
+
+        dialog.show_all();
+    }
+
+    /// Changes the font height by setting `guifont` in nvim. Nvim then
+    /// notifies us about the option change, which resizes the grids.
+    pub fn set_font_height(&self, height: f32, nvim: &GioNeovim) {
+        let height = height.round().max(font::MIN_HEIGHT).min(font::MAX_HEIGHT);
+        if (height - self.font.height).abs() < f32::EPSILON {
+            return;
+        }
+
+        let mut new_font = self.font.clone();
+        new_font.height = height;
+        let guifont = nvim_rs::Value::from(new_font.as_guifont());
+
+        let nvim = nvim.clone();
+        spawn_local(async move {
+            if let Err(err) = nvim.set_option("guifont", guifont).await {
+                error!("Failed to set guifont: {}", err);
+            }
+        });
+    }
+
+    /// Runs a GUI shortcut. Returns false if the shortcut doesn't apply
+    /// (e.g. there is nothing to copy), and the key should go to nvim.
+    pub fn run_shortcut(
+        &mut self,
+        shortcut: Shortcut,
+        window: &gtk::ApplicationWindow,
+        nvim: &GioNeovim,
+    ) -> bool {
+        match shortcut {
+            Shortcut::Copy => return self.copy_selection(),
+            Shortcut::Paste => self.paster.paste_clipboard(),
+            Shortcut::ZoomIn => {
+                self.set_font_height(self.font.height + 1.0, nvim)
+            }
+            Shortcut::ZoomOut => {
+                self.set_font_height(self.font.height - 1.0, nvim)
+            }
+            Shortcut::Fullscreen => self.set_fullscreen(window, None),
+            Shortcut::Search => self.search_bar.show(),
+            Shortcut::CopyMode => return self.enter_copy_mode(),
+        }
+
+        true
+    }
+
+    fn set_cursor_blink_curve(&mut self, curve: BlinkCurve) {
+        self.cursor_blink_curve = curve;
+        self.grids
+            .values()
+            .for_each(|g| g.set_cursor_blink_curve(curve));
+    }
+
+    fn enable_cursor_hollow_unfocused(&mut self, enable: bool) {
+        self.cursor_hollow_unfocused = enable;
+        self.grids
+            .values()
+            .for_each(|g| g.enable_cursor_hollow_unfocused(enable));
+    }
+
+    /// Shows `snippet` on the grid of window `win`, and removes it from
+    /// the other grids.
+    fn set_snippet(&self, win: i64, snippet: &SnippetState) {
+        let snippet_grid = self
+            .windows
+            .values()
+            .find(|w| window_handle(w.nvim_win.get_value()) == Some(win))
+            .map(|w| w.grid_id);
+        for (id, grid) in self.grids.iter() {
+            let snippet = if Some(*id) == snippet_grid {
+                snippet.clone()
+            } else {
+                SnippetState::default()
+            };
+            grid.set_snippet(snippet, &self.hl_defs);
+        }
+    }
+
+    fn set_crosshair(&mut self, crosshair: Crosshair) {
+        self.crosshair = crosshair;
+        self.grids.values().for_each(|g| g.set_crosshair(crosshair));
+    }
+
+    /// Tells the grids if the window has the focus.
+    pub fn set_focused(&self, focused: bool) {
+        self.grids.values().for_each(|g| g.set_focused(focused));
+    }
+
+    fn enable_kinetic_scrolling(&mut self, enable: bool) {
+        self.kinetic_scrolling = enable;
+        self.grids
+            .values()
+            .for_each(|g| g.enable_kinetic_scrolling(enable));
+    }
+
+    /// Sets the main window fullscreen, or toggles it with `None`.
+    fn set_fullscreen(
+        &mut self,
+        window: &gtk::ApplicationWindow,
+        fullscreen: Option<bool>,
+    ) {
+        self.toplevel.fullscreen =
+            fullscreen.unwrap_or(!self.toplevel.fullscreen);
+        self.toplevel.apply(window);
+    }
+
+    /// Maximizes the main window, or toggles it with `None`.
+    fn set_maximized(
+        &mut self,
+        window: &gtk::ApplicationWindow,
+        maximized: Option<bool>,
+    ) {
+        self.toplevel.maximized = maximized.unwrap_or(!self.toplevel.maximized);
+        self.toplevel.apply(window);
+    }
+
+    /// Keeps the main window (and the external windows) on top of other
+    /// windows, or toggles it with `None`.
+    fn set_keep_above(
+        &mut self,
+        window: &gtk::ApplicationWindow,
+        keep_above: Option<bool>,
+    ) {
+        self.toplevel.keep_above =
+            keep_above.unwrap_or(!self.toplevel.keep_above);
+        self.toplevel.apply(window);
+
+        let keep_above = self.toplevel.keep_above;
+        self.windows
+            .values()
+            .for_each(|w| w.set_keep_above(keep_above));
+        self.detached_tabs
+            .values()
+            .for_each(|tab| tab.set_keep_above(keep_above));
+    }
+
+    fn set_decorations(
+        &mut self,
+        window: &gtk::ApplicationWindow,
+        decorations: Decorations,
+    ) {
+        if self.toplevel.decorations == decorations {
+            return;
+        }
+
+        self.toplevel.decorations = decorations;
+
+        // Take the tabline out of the old header bar before it's destroyed.
+        self.tabline.set_header_bar(None, window, &self.hl_defs);
+        let header_bar = toplevel::set_decorations(window, decorations);
+        if decorations == Decorations::Tabline {
+            self.tabline.set_header_bar(
+                header_bar.as_ref(),
+                window,
+                &self.hl_defs,
+            );
+        }
+
+        // The window might have been re-created, so restore its state and
+        // the input method's client window.
+        self.toplevel.apply(window);
+        for grid in self.grids.values_mut() {
+            grid.set_im_context(&self.im_context);
+        }
+    }
+
+    fn enable_gui_selection(&mut self, enable: bool) {
+        self.gui_selection = enable;
+        self.grids
+            .values()
+            .for_each(|g| g.enable_gui_selection(enable));
+    }
+
+    /// Copies the text of the latest mouse selection to the clipboard.
+    /// Returns false if there is no selection.
+    fn copy_selection(&self) -> bool {
+        let text = self
+            .grids
+            .values()
+            .filter_map(|g| g.get_selection())
+            .max_by_key(|(time, _)| *time)
+            .map(|(_, text)| text);
+
+        match text {
+            Some(text) => {
+                gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&text);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Starts copy mode in the cmdline block if it's shown, otherwise in
+    /// the message grid. Returns false if there are no messages.
+    fn enter_copy_mode(&mut self) -> bool {
+        if self.cmdline.enter_block_copy_mode() {
+            return true;
+        }
+
+        let grid =
+            match self.msg_window.grid_id.and_then(|id| self.grids.get(&id)) {
+                Some(grid) => grid,
+                None => return false,
+            };
+
+        let rows = self.msg_window.message_rows(grid);
+        if rows == 0 {
+            return false;
+        }
+
+        let metrics = grid.get_grid_metrics();
+        let mode =
+            CopyMode::new(rows - 1, metrics.rows as u64, metrics.cols as u64);
+        grid.set_selection(Some(mode.selection()));
+        self.msg_window.scroll_to_row(grid, mode.cursor.0);
+        self.copy_mode = Some(mode);
+
+        true
+    }
+
+    /// Leaves copy mode (if it's active) and clears its selection, e.g.
+    /// when the messages are cleared.
+    fn leave_copy_mode(&mut self) {
+        if self.copy_mode.take().is_none() {
+            return;
+        }
+
+        if let Some(grid) =
+            self.msg_window.grid_id.and_then(|id| self.grids.get(&id))
+        {
+            grid.set_selection(None);
+        }
+    }
+
+    /// Handles a key press in copy mode. Returns false if copy mode isn't
+    /// active, so that the key should go to nvim. In copy mode, all the
+    /// keys are handled here.
+    pub fn copy_mode_key(&mut self, e: &gdk::EventKey) -> bool {
+        let mode = match self.copy_mode {
+            Some(ref mut mode) => mode,
+            None => return false,
+        };
+
+        let grid =
+            match self.msg_window.grid_id.and_then(|id| self.grids.get(&id)) {
+                Some(grid) if self.msg_window.message_rows(grid) > 0 => grid,
+                _ => {
+                    // The messages are gone.
+                    self.leave_copy_mode();
+                    return false;
+                }
+            };
+
+        let keyname = match e.get_keyval().name() {
+            Some(keyname) => keyname,
+            None => return true,
+        };
+
+        match mode.handle_key(keyname.as_str()) {
+            CopyModeAction::Moved => {
+                grid.set_selection(Some(mode.selection()));
+                self.msg_window.scroll_to_row(grid, mode.cursor.0);
+            }
+            CopyModeAction::Copy => {
+                let text = grid.selection_text(&mode.copied());
+                gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&text);
+                grid.set_selection(None);
+                self.copy_mode = None;
+            }
+            CopyModeAction::Exit => {
+                grid.set_selection(None);
+                self.copy_mode = None;
+            }
+            CopyModeAction::None => {}
+        }
+
+        true
+    }
+
+    fn enable_scrollbar(&mut self, enable: bool) {
+        self.scrollbar = enable;
+        self.windows
+            .values_mut()
+            .for_each(|w| w.enable_scrollbar(enable));
+    }
+
+    fn enable_minimap(&mut self, enable: bool, nvim: &GioNeovim) {
+        self.minimap = enable;
+        self.windows
+            .values_mut()
+            .for_each(|w| w.enable_minimap(enable));
+
+        // Make room for the minimaps, or give the room back.
+        let grids: Vec<i64> = self.windows.keys().copied().collect();
+        for grid in grids {
+            self.fit_grid_to_layout(grid, nvim);
+        }
+    }
+
+    /// Returns the decorations of floating window `win`.
+    fn float_style(&self, win: &nvim_rs::Value) -> FloatStyle {
+        let options = window_handle(win)
+            .and_then(|win| self.window_float_options.get(&win))
+            .copied()
+            .unwrap_or(self.float_options);
+
+        let border = if options.border {
+            let color = self
+                .hl_defs
+                .get_hl_group(&HlGroup::FloatBorder)
+                .and_then(|hl| hl.foreground)
+                .unwrap_or(self.hl_defs.default_fg);
+            Some(color)
+        } else {
+            None
+        };
+
+        FloatStyle {
+            radius: options.radius as f64,
+            shadow: options.shadow,
+            border,
+        }
+    }
+
+    /// Sets the decorations of floating window `win`, or of all floating
+    /// windows that don't have their own.
+    fn set_float_style(&mut self, win: Option<i64>, options: FloatOptions) {
+        match win {
+            Some(win) => {
+                self.window_float_options.insert(win, options);
+            }
+            None => self.float_options = options,
         }
+
+        self.update_float_styles();
     }
 
-    fn msg_set_pos(&mut self, e: MsgSetPos) {
-        let base_grid = self.grids.get(&1).unwrap();
-        let base_metrics = base_grid.get_grid_metrics();
-        let grid = self.grids.get(&e.grid).unwrap();
-        let h = base_metrics.height - e.row as f64 * base_metrics.cell_height;
-        self.msg_window.set_pos(&grid, e.row as f64, h, e.scrolled);
+    fn update_float_styles(&self) {
+        for window in self.windows.values().filter(|w| w.is_float()) {
+            let style = self.float_style(window.nvim_win.get_value());
+            window.set_float_style(Some(style));
+        }
     }
 
-    fn enable_cursor_animations(&mut self, enable: bool) {
-        self.enable_cursor_animations = enable;
+    fn enable_damage_overlay(&mut self, enable: bool) {
+        self.damage_overlay = enable;
         self.grids
             .values()
-            .for_each(|g| g.enable_cursor_animations(enable));
+            .for_each(|g| g.enable_damage_overlay(enable));
     }
 
     fn handle_redraw_event(
@@ -722,7 +2506,7 @@ impl UIState {
     ) {
         match event {
             RedrawEvent::SetTitle(evt) => {
-                evt.iter().for_each(|e| self.set_title(&window, e));
+                evt.iter().for_each(|e| self.set_title(e));
             }
             RedrawEvent::GridLine(evt) => {
                 evt.into_iter().for_each(|line| self.grid_line(line))
@@ -761,7 +2545,7 @@ impl UIState {
                 evt.into_iter().for_each(|e| self.mode_change(e));
             }
             RedrawEvent::SetBusy(busy) => self.set_busy(busy),
-            RedrawEvent::Flush() => self.flush(nvim, window),
+            RedrawEvent::Flush() => self.queue_flush(nvim, window),
             RedrawEvent::PopupmenuShow(evt) => {
                 evt.into_iter().for_each(|e| self.popupmenu_show(e));
             }
@@ -790,14 +2574,25 @@ impl UIState {
             }
             RedrawEvent::CmdlineBlockHide() => self.cmdline_block_hide(),
             RedrawEvent::WindowPos(evt) => {
-                evt.into_iter().for_each(|e| self.window_pos(e, nvim));
+                evt.into_iter().for_each(|e| {
+                    let grid = e.grid;
+                    self.window_pos(e, window, nvim);
+                    self.update_grid_background_opacity(grid);
+                });
             }
             RedrawEvent::WindowFloatPos(evt) => {
-                evt.into_iter().for_each(|e| self.window_float_pos(e, nvim));
+                evt.into_iter().for_each(|e| {
+                    let grid = e.grid;
+                    self.window_float_pos(e, nvim);
+                    self.update_grid_background_opacity(grid);
+                });
             }
             RedrawEvent::WindowExternalPos(evt) => {
-                evt.into_iter()
-                    .for_each(|e| self.window_external_pos(e, window, nvim));
+                evt.into_iter().for_each(|e| {
+                    let grid = e.grid;
+                    self.window_external_pos(e, window, nvim);
+                    self.update_grid_background_opacity(grid);
+                });
             }
             RedrawEvent::WindowHide(evt) => {
                 evt.into_iter().for_each(|e| self.window_hide(e));
@@ -805,9 +2600,37 @@ impl UIState {
             RedrawEvent::WindowClose(evt) => {
                 evt.into_iter().for_each(|e| self.window_close(e));
             }
+            RedrawEvent::WindowViewport(evt) => {
+                evt.into_iter().for_each(|e| self.window_viewport(e, nvim));
+            }
             RedrawEvent::MsgSetPos(evt) => {
                 evt.into_iter().for_each(|e| self.msg_set_pos(e));
             }
+            RedrawEvent::MsgShow(evt) => {
+                evt.into_iter().for_each(|e| self.msg_show(e));
+            }
+            RedrawEvent::MsgClear() => {
+                self.toasts.clear();
+                self.leave_copy_mode();
+            }
+            RedrawEvent::MsgHistoryShow(evt) => {
+                evt.into_iter().for_each(|e| self.msg_history_show(e));
+            }
+            RedrawEvent::MsgShowmode(evt) => {
+                if let Some(content) = evt.last() {
+                    self.toasts.set_mode(content, &self.hl_defs);
+                }
+            }
+            RedrawEvent::MsgShowcmd(evt) => {
+                if let Some(content) = evt.last() {
+                    self.toasts.set_showcmd(content, &self.hl_defs);
+                }
+            }
+            RedrawEvent::MsgRuler(evt) => {
+                if let Some(content) = evt.last() {
+                    self.toasts.set_ruler(content, &self.hl_defs);
+                }
+            }
             RedrawEvent::Ignored(_) => (),
             RedrawEvent::Unknown(e) => {
                 debug!("Received unknown redraw event: {}", e);
@@ -815,7 +2638,12 @@ impl UIState {
         }
     }
 
-    fn handle_gnvim_event(&mut self, event: &GnvimEvent, nvim: &GioNeovim) {
+    fn handle_gnvim_event(
+        &mut self,
+        window: &gtk::ApplicationWindow,
+        event: &GnvimEvent,
+        nvim: &GioNeovim,
+    ) {
         match event {
             GnvimEvent::CompletionMenuToggleInfo => {
                 self.popupmenu.toggle_show_info()
@@ -829,79 +2657,343 @@ impl UIState {
             GnvimEvent::PopupmenuShowMenuOnAllItems(should_show) => {
                 self.popupmenu.set_show_menu_on_all_items(*should_show);
             }
+            GnvimEvent::PopupmenuMaxHeight(height) => {
+                let height = (*height).min(i32::MAX as u64) as i32;
+                self.popupmenu.set_max_height(height);
+            }
+            GnvimEvent::PopupmenuMaxRows(rows) => {
+                let rows = (*rows).min(i32::MAX as u64) as i32;
+                self.popupmenu
+                    .set_max_rows(Some(rows).filter(|rows| *rows > 0));
+            }
+            GnvimEvent::PopupmenuPosition(position) => {
+                self.popupmenu.set_position(*position);
+            }
+            GnvimEvent::PopupmenuDetailsSide(side) => {
+                self.popupmenu.set_details_side(*side);
+            }
+            GnvimEvent::PopupmenuScrollDetails(lines) => {
+                self.popupmenu.scroll_info(*lines);
+            }
+            GnvimEvent::PopupmenuKindGlyph(kind, glyph) => {
+                self.popupmenu.set_kind_glyph(kind.clone(), glyph.clone());
+            }
+            GnvimEvent::CmdlinePosition(position) => {
+                self.cmdline.set_position(*position);
+            }
+            GnvimEvent::CmdlineSearchPosition(position) => {
+                self.cmdline.set_search_position(*position);
+            }
+            GnvimEvent::WildmenuLayout(layout) => {
+                self.cmdline.wildmenu_set_layout(*layout);
+            }
             GnvimEvent::EnableCursorAnimations(enable) => {
                 self.enable_cursor_animations(*enable);
             }
-            GnvimEvent::Unknown(msg) => {
-                debug!("Received unknown GnvimEvent: {}", msg);
+            GnvimEvent::CursorBlinkCurve(curve) => {
+                self.set_cursor_blink_curve(*curve);
+            }
+            GnvimEvent::EnableCursorHollowUnfocused(enable) => {
+                self.enable_cursor_hollow_unfocused(*enable);
+            }
+            GnvimEvent::SetCrosshair(crosshair) => {
+                self.set_crosshair(*crosshair);
+            }
+            GnvimEvent::SetIndentGuides(guides) => {
+                self.set_indent_guides(*guides, nvim);
+            }
+            GnvimEvent::IndentInfoChanged => {
+                self.refresh_indent_info(nvim);
+            }
+            GnvimEvent::WindowBufferChanged => {
+                for window in self.windows.values() {
+                    window.update_external_title(&self.external_positions);
+                }
+            }
+            GnvimEvent::SetBackgroundOpacity(opacity) => {
+                self.set_background_opacity(*opacity);
+            }
+            GnvimEvent::SetDeviceScale(scale) => {
+                self.device_scale = *scale;
+                for grid in self.grids.values() {
+                    grid.set_device_scale(*scale);
+                }
+                self.refresh_metrics(nvim, window);
+            }
+            GnvimEvent::SetCustomCss(path) => {
+                self.set_custom_css(path, nvim);
+            }
+            GnvimEvent::SetBackgroundImage(path, blur, dim) => {
+                self.set_background_image(path, *blur, *dim, nvim);
+            }
+            GnvimEvent::ImageShow(id, path, (row, col), size) => {
+                self.show_image(*id, path, (*row as i64, *col), *size, nvim);
+            }
+            GnvimEvent::ImageHide(id) => {
+                self.grids.values().for_each(|grid| grid.hide_image(*id));
+            }
+            GnvimEvent::ImageClear => {
+                self.grids.values().for_each(|grid| grid.clear_images());
+            }
+            GnvimEvent::SnippetState(win, snippet) => {
+                self.set_snippet(*win, snippet)
+            }
+            GnvimEvent::EnableDamageOverlay(enable) => {
+                self.enable_damage_overlay(*enable);
+            }
+            GnvimEvent::TogglePerfOverlay => self.perf.toggle(),
+            GnvimEvent::LogViewerShow => self.log_viewer.show(),
+            GnvimEvent::DumpGrids(dir) => {
+                self.dump_grids(Path::new(dir), window, nvim)
+            }
+            GnvimEvent::EnableKineticScrolling(enable) => {
+                self.enable_kinetic_scrolling(*enable);
+            }
+            GnvimEvent::SetFullscreen(fullscreen) => {
+                self.set_fullscreen(window, *fullscreen);
+            }
+            GnvimEvent::SetMaximized(maximized) => {
+                self.set_maximized(window, *maximized);
+            }
+            GnvimEvent::SetKeepAbove(keep_above) => {
+                self.set_keep_above(window, *keep_above);
+            }
+            GnvimEvent::SetDecorations(decorations) => {
+                self.set_decorations(window, *decorations);
+            }
+            GnvimEvent::SetProgress(progress) => {
+                self.progress.set_progress(*progress);
+            }
+            GnvimEvent::EnableGuiSelection(enable) => {
+                self.enable_gui_selection(*enable);
+            }
+            GnvimEvent::EnableScrollbar(enable) => {
+                self.enable_scrollbar(*enable);
+            }
+            GnvimEvent::EnableMinimap(enable) => {
+                self.enable_minimap(*enable, nvim);
             }
+            GnvimEvent::EnableFloatDrag(enable) => {
+                self.float_drag.enable(*enable);
+            }
+            GnvimEvent::SetFloatStyle(win, options) => {
+                self.set_float_style(*win, *options);
+            }
+            GnvimEvent::SetStatusline(segments) => {
+                self.statusbar.set_segments(segments.clone(), &self.hl_defs);
+            }
+            GnvimEvent::TablineMode(mode) => self.tabline.set_mode(*mode),
+            GnvimEvent::TablineBuffers { current, buffers } => {
+                self.tabline.update_buffers(*current, buffers.clone());
+            }
+            GnvimEvent::SetAnimations {
+                enable,
+                float_open,
+                window_close,
+            } => {
+                self.animations = Animations {
+                    enabled: *enable,
+                    float_open: *float_open,
+                    window_close: *window_close,
+                };
+            }
+            GnvimEvent::EnableAltAsMeta(enable) => {
+                self.alt_as_meta.set(*enable);
+            }
+            GnvimEvent::EnableShortcut(name, enable) => {
+                if !self.shortcuts.enable(name, *enable) {
+                    warn!("Unknown shortcut: {}", name);
+                }
+            }
+            GnvimEvent::SetHlGroups(groups) => {
+                // The groups missing from the event don't exist anymore
+                // (e.g. after a colorscheme change).
+                self.hl_defs.clear_hl_group_highlights();
+                for (name, hl) in groups {
+                    let group = match name.as_str() {
+                        "DiagnosticError" => HlGroup::DiagnosticError,
+                        "DiagnosticWarn" => HlGroup::DiagnosticWarn,
+                        "DiagnosticInfo" => HlGroup::DiagnosticInfo,
+                        "DiagnosticHint" => HlGroup::DiagnosticHint,
+                        _ => continue,
+                    };
+                    self.hl_defs.set_hl_group_highlight(group, *hl);
+                }
+                self.update_colors();
+            }
+            GnvimEvent::SetVirtualText(groups, style) => {
+                if !groups.is_empty() && !self.hlstate {
+                    self.hlstate = true;
+                    enable_hlstate(nvim);
+                }
 
-            #[cfg(not(feature = "libwebkit2gtk"))]
-            GnvimEvent::CursorTooltipLoadStyle(..)
-            | GnvimEvent::CursorTooltipShow(..)
-            | GnvimEvent::CursorTooltipHide
-            | GnvimEvent::CursorTooltipSetStyle(..) => {
-                let nvim = nvim.clone();
-                let msg =
-                    "echom \"Cursor tooltip not supported in this build\"";
-                spawn_local(async move {
-                    if let Err(err) = nvim.command(&msg).await {
-                        error!("Failed to execute nvim command: {}", err)
-                    }
-                });
+                let changed =
+                    self.hl_defs.set_virtual_text(groups.clone(), *style);
+                self.hl_redraw.extend(changed);
+                self.queue_flush(nvim, window);
             }
+            GnvimEvent::TerminalPalette(colors) => {
+                let palette = &mut self.hl_defs.terminal_palette;
+                let default = TerminalPalette::default();
+                for (i, color) in colors.iter().enumerate().take(16) {
+                    palette.0[i] = color.unwrap_or(default.0[i]);
+                }
 
-            #[cfg(feature = "libwebkit2gtk")]
-            GnvimEvent::CursorTooltipLoadStyle(..)
-            | GnvimEvent::CursorTooltipShow(..)
-            | GnvimEvent::CursorTooltipHide
-            | GnvimEvent::CursorTooltipSetStyle(..) => match event {
-                GnvimEvent::CursorTooltipLoadStyle(path) => {
-                    if let Err(err) =
-                        self.cursor_tooltip.load_style(path.clone())
-                    {
-                        let msg = format!(
-                            "echom \"Cursor tooltip load style failed: '{}'\"",
-                            err
+                // The default colors might come from the palette.
+                if let Some(colors) = self.default_colors.clone() {
+                    self.default_colors_set(colors);
+                    self.queue_flush(nvim, window);
+                }
+            }
+            GnvimEvent::TerminalGraphics(id, sequence, (row, col)) => {
+                self.terminal_graphics(*id, sequence, (*row as i64, *col));
+            }
+            GnvimEvent::PreviewUpdate(text, win) => {
+                let cursor_tooltip = &self.cursor_tooltip;
+                let markup = markdown_to_markup(text, |lang, code| {
+                    cursor_tooltip.highlight(lang, code)
+                });
+                self.preview.show(&markup, *win);
+            }
+            GnvimEvent::PreviewHide => self.preview.hide(),
+            GnvimEvent::SetWinbar(win, segments) => {
+                let window = self.windows.values_mut().find(|w| {
+                    window_handle(w.nvim_win.get_value()) == Some(*win)
+                });
+                if let Some(window) = window {
+                    window.set_winbar(segments.clone(), &self.hl_defs);
+                }
+            }
+            GnvimEvent::SetSigns {
+                win,
+                col,
+                width,
+                signs,
+            } => {
+                let window = self.windows.values().find(|w| {
+                    window_handle(w.nvim_win.get_value()) == Some(*win)
+                });
+                if let Some(window) = window {
+                    window.set_signs(*col, *width, signs);
+                }
+            }
+            GnvimEvent::DetachTab { id, title, windows } => {
+                self.detach_tab(window, *id, title, windows, nvim)
+            }
+            GnvimEvent::SetLinkPatterns { url, path } => {
+                match LinkMatcher::new(url, path) {
+                    Ok(matcher) => {
+                        self.link_matcher = Rc::new(matcher);
+                        for grid in self.grids.values() {
+                            grid.set_link_matcher(self.link_matcher.clone());
+                        }
+                    }
+                    Err(err) => {
+                        nvim_error(
+                            nvim,
+                            format!("Invalid link pattern: {}", err),
                         );
-                        let nvim = nvim.clone();
-                        spawn_local(async move {
-                            if let Err(err) = nvim.command(&msg).await {
-                                error!(
-                                    "Failed to execute nvim command: {}",
-                                    err
-                                )
-                            }
-                        });
                     }
                 }
-                GnvimEvent::CursorTooltipShow(content, row, col) => {
-                    self.cursor_tooltip.show(content.clone());
-
-                    let grid = self.grids.get(&self.current_grid).unwrap();
-                    let rect = grid.get_rect_for_cell(*row, *col);
-
-                    self.cursor_tooltip.move_to(&rect);
+            }
+            GnvimEvent::FileTreeShow(root) => self.file_tree.show(root),
+            GnvimEvent::FileTreeHide => self.file_tree.hide(),
+            GnvimEvent::Export(format, path, lines) => {
+                self.export(*format, path, lines.as_deref(), nvim)
+            }
+            GnvimEvent::Screenshot { target, path } => {
+                self.screenshot(window, *target, path.as_deref(), nvim)
+            }
+            GnvimEvent::Print(title, first, lines) => self
+                .printer
+                .print(window, title, *first, lines, &self.font, nvim),
+            GnvimEvent::FontPickerShow => self.show_font_picker(nvim),
+            GnvimEvent::FileOpenDialog(dialog) => show_file_dialog(
+                window,
+                gtk::FileChooserAction::Open,
+                dialog,
+                nvim,
+            ),
+            GnvimEvent::FileSaveDialog(dialog) => show_file_dialog(
+                window,
+                gtk::FileChooserAction::Save,
+                dialog,
+                nvim,
+            ),
+            GnvimEvent::CommandPaletteShow => self.command_palette.show(),
+            GnvimEvent::SearchBarShow => self.search_bar.show(),
+            GnvimEvent::FinderShow => self.finder.show(),
+            GnvimEvent::CopyModeEnter => {
+                if !self.enter_copy_mode() {
+                    debug!("No messages for copy mode");
                 }
-                GnvimEvent::CursorTooltipHide => self.cursor_tooltip.hide(),
-                GnvimEvent::CursorTooltipSetStyle(style) => {
-                    self.cursor_tooltip.set_style(style)
+            }
+            GnvimEvent::PasteConfirmThreshold(bytes) => {
+                self.paster.set_confirm_threshold(*bytes)
+            }
+            GnvimEvent::InputQueueSize(size) => {
+                self.input_queue.set_cap(*size as usize)
+            }
+            GnvimEvent::DesktopNotify(title, body, unfocused_only) => {
+                send_desktop_notification(window, title, body, *unfocused_only);
+            }
+            GnvimEvent::Unknown(msg) => {
+                debug!("Received unknown GnvimEvent: {}", msg);
+            }
+
+            GnvimEvent::CursorTooltipLoadStyle(path) => {
+                if let Err(err) = self.cursor_tooltip.load_style(path.clone()) {
+                    let msg = format!(
+                        "echom \"Cursor tooltip load style failed: '{}'\"",
+                        err
+                    );
+                    let nvim = nvim.clone();
+                    spawn_local(async move {
+                        if let Err(err) = nvim.command(&msg).await {
+                            error!("Failed to execute nvim command: {}", err)
+                        }
+                    });
                 }
-                _ => unreachable!(),
-            },
+            }
+            GnvimEvent::CursorTooltipShow(content, row, col) => {
+                let grid = self.grids.get(&self.current_grid).unwrap();
+                let rect = grid.get_rect_for_cell(*row, *col);
+
+                self.cursor_tooltip.move_to(&rect);
+                self.cursor_tooltip.show(content.clone());
+            }
+            GnvimEvent::CursorTooltipHide => self.cursor_tooltip.hide(),
+            GnvimEvent::CursorTooltipSetStyle(style) => {
+                self.cursor_tooltip.set_style(style)
+            }
+            GnvimEvent::CursorTooltipScroll(lines) => {
+                self.cursor_tooltip.scroll(*lines)
+            }
         }
     }
 }
 
-pub fn attach_grid_events(grid: &Grid, nvim: GioNeovim) {
+pub fn attach_grid_events(
+    grid: &Grid,
+    nvim: GioNeovim,
+    input_queue: &InputQueue,
+) {
     let id = grid.id;
+    // Ctrl + click on links, before the clicks go to nvim.
+    grid.connect_link_clicked(clone!(nvim => move |link| {
+        open_link(&nvim, link);
+    }));
+
     // Mouse button press event.
     grid.connect_mouse_button_press_events(
-        clone!(nvim => move |button, row, col| {
-            let nvim = nvim.clone();
-            spawn_local(async move {
-                nvim.input_mouse(&button.to_string(), "press", "", id, row as i64, col as i64).await.expect("Couldn't send mouse input");
-            });
+        clone!(nvim, input_queue => move |button, row, col| {
+            // Middle click pastes the primary selection.
+            if let MouseButton::Middle = button {
+                selection::paste_primary_selection(&nvim, id, row, col);
+                return Inhibit(false);
+            }
+
+            input_queue.send(Input::mouse(&button.to_string(), "press", id, row, col));
 
             Inhibit(false)
         }),
@@ -909,11 +3001,17 @@ pub fn attach_grid_events(grid: &Grid, nvim: GioNeovim) {
 
     // Mouse button release events.
     grid.connect_mouse_button_release_events(
-        clone!(nvim => move |button, row, col| {
-            let nvim = nvim.clone();
-            spawn_local(async move {
-                nvim.input_mouse(&button.to_string(), "release", "", id, row as i64, col as i64).await.expect("Couldn't send mouse input");
-            });
+        clone!(input_queue => move |button, row, col| {
+            if let MouseButton::Middle = button {
+                return Inhibit(false);
+            }
+
+            input_queue.send(Input::mouse(&button.to_string(), "release", id, row, col));
+
+            // Selecting text with the mouse updates the primary selection.
+            if let MouseButton::Left = button {
+                input_queue.send(Input::UpdatePrimarySelection);
+            }
 
             Inhibit(false)
         }),
@@ -921,25 +3019,251 @@ pub fn attach_grid_events(grid: &Grid, nvim: GioNeovim) {
 
     // Mouse drag events.
     grid.connect_motion_events_for_drag(
-        clone!(nvim => move |button, row, col| {
-            let nvim = nvim.clone();
-            spawn_local(async move {
-                nvim.input_mouse(&button.to_string(), "drag", "", id, row as i64, col as i64).await.expect("Couldn't send mouse input");
-            });
+        clone!(input_queue => move |button, row, col| {
+            input_queue.send(Input::mouse(&button.to_string(), "drag", id, row, col));
 
             Inhibit(false)
         }),
     );
 
     // Scrolling events.
-    grid.connect_scroll_events(clone!(nvim => move |dir, row, col| {
+    grid.connect_scroll_events(clone!(input_queue => move |dir, row, col| {
+        input_queue.send(Input::mouse("wheel", &dir.to_string(), id, row, col));
+
+        Inhibit(false)
+    }));
+}
+
+/// Enables `ext_hlstate`, for the highlight groups of virtual text. Nvim
+/// sends the groups with the highlights it defines from then on, and the
+/// screen is redrawn for the highlights that it has already sent.
+fn enable_hlstate(nvim: &GioNeovim) {
+    let nvim = nvim.clone();
+    spawn_local(async move {
+        if let Err(err) = nvim.ui_set_option("ext_hlstate", true.into()).await {
+            error!("Failed to enable ext_hlstate: {}", err);
+            return;
+        }
+        if let Err(err) = nvim.command("redraw!").await {
+            error!("Failed to redraw: {}", err);
+        }
+    });
+}
+
+/// Opens URLs with the default application, and paths in nvim.
+fn open_link(nvim: &GioNeovim, link: Link) {
+    let nvim = nvim.clone();
+    match link.kind {
+        LinkKind::Url => {
+            if let Err(err) = gio::AppInfo::launch_default_for_uri(
+                &link.text,
+                None::<&gio::AppLaunchContext>,
+            ) {
+                nvim_error(
+                    &nvim,
+                    format!("Failed to open '{}': {}", link.text, err),
+                );
+            }
+        }
+        LinkKind::Path => {
+            let (path, line, col) = split_position(&link.text);
+            let args = vec![
+                path.into(),
+                line.unwrap_or(0).into(),
+                col.unwrap_or(0).into(),
+            ];
+            spawn_local(async move {
+                if let Err(err) =
+                    nvim.call_function("gnvim#links#open_path", args).await
+                {
+                    error!("Failed to open path: {}", err);
+                }
+            });
+        }
+    }
+}
+
+/// Sends a desktop notification. With `unfocused_only`, the notification is
+/// only sent when `window` doesn't have the focus.
+fn send_desktop_notification(
+    window: &gtk::ApplicationWindow,
+    title: &str,
+    body: &str,
+    unfocused_only: bool,
+) {
+    if unfocused_only && window.is_active() {
+        return;
+    }
+
+    let app = match window.get_application() {
+        Some(app) => app,
+        None => return,
+    };
+
+    let notification = gio::Notification::new(title);
+    notification.set_body(Some(body));
+    // Clicking the notification brings our window to the front.
+    notification.set_default_action("app.present");
+    app.send_notification(None, &notification);
+}
+
+/// Shows the native file chooser (a portal in sandboxes), and passes the
+/// chosen path (or nil, if it's canceled) to `gnvim#dialog#respond`.
+fn show_file_dialog(
+    window: &gtk::ApplicationWindow,
+    action: gtk::FileChooserAction,
+    dialog: &FileDialog,
+    nvim: &GioNeovim,
+) {
+    let chooser = gtk::FileChooserNative::new(
+        dialog.title.as_deref(),
+        Some(window),
+        action,
+        None,
+        None,
+    );
+    chooser.set_modal(true);
+    if action == gtk::FileChooserAction::Save {
+        chooser.set_do_overwrite_confirmation(true);
+        if let Some(ref name) = dialog.name {
+            chooser.set_current_name(name);
+        }
+    }
+    if let Some(ref folder) = dialog.folder {
+        chooser.set_current_folder(folder);
+    }
+    if !dialog.patterns.is_empty() {
+        let filter = gtk::FileFilter::new();
+        filter.set_name(Some(&dialog.patterns.join(", ")));
+        for pattern in dialog.patterns.iter() {
+            filter.add_pattern(pattern);
+        }
+        chooser.add_filter(&filter);
+    }
+
+    // Native dialogs aren't kept alive by gtk, so hold on to the dialog
+    // until it's answered.
+    let keep = Rc::new(RefCell::new(Some(chooser.clone())));
+    let id = dialog.id;
+    chooser.connect_response(clone!(nvim => move |chooser, res| {
+        keep.borrow_mut().take();
+
+        let path = match chooser.get_filename() {
+            Some(path) if res == gtk::ResponseType::Accept => {
+                nvim_rs::Value::from(path.to_string_lossy().into_owned())
+            }
+            _ => nvim_rs::Value::Nil,
+        };
+
         let nvim = nvim.clone();
         spawn_local(async move {
-            nvim.input_mouse("wheel", &dir.to_string(), "", id, row as i64, col as i64).await.expect("Couldn't send mouse input");
+            if let Err(err) = nvim
+                .call_function("gnvim#dialog#respond", vec![id.into(), path])
+                .await
+            {
+                error!("Failed to respond to file dialog: {}", err);
+            }
         });
-
-        Inhibit(false)
     }));
+
+    chooser.show();
+}
+
+/// Returns the position of a float's anchor grid, given the anchor grid's
+/// window (its position and if it's external). `None` (and errors) mean that
+/// the float is positioned relative to the base grid.
+fn float_anchor(
+    grid: i64,
+    anchor_grid: i64,
+    anchor_window: Option<(f64, f64, bool)>,
+) -> Result<Option<(f64, f64)>, String> {
+    if anchor_grid == 1 {
+        return Ok(None);
+    }
+
+    if anchor_grid == grid {
+        return Err(String::from("Can't use a grid as its own float anchor."));
+    }
+
+    match anchor_window {
+        Some((_, _, true)) => Err(format!(
+            "Can't anchor a float to an external window (grid: {}).",
+            anchor_grid
+        )),
+        Some((x, y, false)) => Ok(Some((x, y))),
+        // The anchor's window might not be positioned yet. Once it is, the
+        // float is positioned again.
+        None => Ok(None),
+    }
+}
+
+/// Returns the background opacity of a grid, when the background's opacity
+/// is `opacity`. `split` grids are under the floating windows, and keep their
+/// background transparent, so that the grids on top of them don't stack their
+/// translucent backgrounds on top of each other, and so that the background
+/// `image` is visible.
+fn background_opacity_for(opacity: f64, image: bool, split: bool) -> f64 {
+    if split && (opacity < 1.0 || image) {
+        0.0
+    } else {
+        opacity
+    }
+}
+
+/// Sorts `stack` (grid ids and z-indices, from bottom to top) by the
+/// z-indices, keeping the order of items with the same z-index. Returns the
+/// index of the first item that moved, if any.
+fn sort_by_zindex(stack: &mut Vec<(i64, u64)>) -> Option<usize> {
+    let old: Vec<i64> = stack.iter().map(|(grid, _)| *grid).collect();
+    stack.sort_by_key(|(_, zindex)| *zindex);
+
+    old.iter()
+        .zip(stack.iter())
+        .position(|(old, (new, _))| old != new)
+}
+
+/// Returns the position (in pixels, relative to the base grid) of the top
+/// left corner of `grid`. `window` is the position of the grid's window, if
+/// it has one, and `msg` is the id and position of the message grid.
+fn grid_origin(
+    grid: i64,
+    window: Option<(f64, f64)>,
+    msg: Option<(i64, f64)>,
+) -> Option<(f64, f64)> {
+    match (window, msg) {
+        _ if grid == 1 => Some((0.0, 0.0)),
+        // Floating windows' positions already include their anchor's
+        // position.
+        (Some(pos), _) => Some(pos),
+        (None, Some((id, y))) if id == grid => Some((0.0, y)),
+        _ => None,
+    }
+}
+
+/// Returns the rectangle of cell (`row`, `col`) of a grid with `metrics`
+/// that is positioned at `origin`, for anchoring e.g. the popupmenu to. The
+/// rectangle is kept inside the base grid, so that the anchored widget
+/// doesn't end up outside of the window when the cell is not visible (e.g.
+/// the message grid is scrolled).
+fn cell_anchor_rect(
+    origin: (f64, f64),
+    metrics: &GridMetrics,
+    base_metrics: &GridMetrics,
+    row: u64,
+    col: u64,
+) -> gdk::Rectangle {
+    let x = origin.0 + col as f64 * metrics.cell_width;
+    let y = origin.1 + row as f64 * metrics.cell_height;
+
+    let x = x.min(base_metrics.width - metrics.cell_width).max(0.0);
+    let y = y.min(base_metrics.height - metrics.cell_height).max(0.0);
+
+    gdk::Rectangle {
+        x: x as i32,
+        y: y as i32,
+        width: metrics.cell_width as i32,
+        height: metrics.cell_height as i32,
+    }
 }
 
 fn win_float_adjust_size(
@@ -990,6 +3314,56 @@ mod tests {
     use crate::nvim_bridge::Anchor;
     use rmpv::Value;
 
+    #[test]
+    fn test_background_opacity_for() {
+        struct Data {
+            opacity: f64,
+            image: bool,
+            split: bool,
+            expected: f64,
+        }
+
+        let data = vec![
+            Data {
+                opacity: 1.0,
+                image: false,
+                split: true,
+                expected: 1.0,
+            },
+            Data {
+                opacity: 0.8,
+                image: false,
+                split: true,
+                expected: 0.0,
+            },
+            Data {
+                opacity: 1.0,
+                image: true,
+                split: true,
+                expected: 0.0,
+            },
+            Data {
+                opacity: 0.8,
+                image: false,
+                split: false,
+                expected: 0.8,
+            },
+            Data {
+                opacity: 1.0,
+                image: true,
+                split: false,
+                expected: 1.0,
+            },
+        ];
+
+        for row in data.into_iter() {
+            assert_eq!(
+                row.expected,
+                background_opacity_for(row.opacity, row.image, row.split),
+            );
+        }
+    }
+
     #[test]
     fn test_float_anchor_pos() {
         struct Data {
@@ -1103,6 +3477,7 @@ mod tests {
                 anchor_row: row.anchor_row,
                 anchor_col: row.anchor_col,
                 focusable: false,
+                zindex: 50,
             };
 
             assert_eq!(
@@ -1123,4 +3498,124 @@ mod tests {
             );
         }
     }
+
+    fn metrics(cols: f64, rows: f64) -> GridMetrics {
+        GridMetrics {
+            cell_height: 20.0,
+            cell_width: 10.0,
+            rows,
+            cols,
+            width: cols * 10.0,
+            height: rows * 20.0,
+        }
+    }
+
+    #[test]
+    fn test_grid_origin() {
+        // Base grid is always at the origin.
+        assert_eq!(grid_origin(1, None, None), Some((0.0, 0.0)));
+        assert_eq!(grid_origin(1, None, Some((3, 100.0))), Some((0.0, 0.0)));
+        // Window grid.
+        assert_eq!(
+            grid_origin(2, Some((50.0, 60.0)), Some((3, 100.0))),
+            Some((50.0, 60.0))
+        );
+        // Message grid.
+        assert_eq!(grid_origin(3, None, Some((3, 100.0))), Some((0.0, 100.0)));
+        // Unknown grid (e.g. one in an external window).
+        assert_eq!(grid_origin(4, None, Some((3, 100.0))), None);
+        assert_eq!(grid_origin(4, None, None), None);
+    }
+
+    #[test]
+    fn test_cell_anchor_rect_window() {
+        let base = metrics(80.0, 24.0);
+        let grid = metrics(40.0, 10.0);
+
+        let rect = cell_anchor_rect((400.0, 20.0), &grid, &base, 2, 5);
+        assert_eq!(rect.x, 450);
+        assert_eq!(rect.y, 60);
+        assert_eq!(rect.width, 10);
+        assert_eq!(rect.height, 20);
+    }
+
+    #[test]
+    fn test_cell_anchor_rect_float() {
+        let base = metrics(80.0, 24.0);
+        let grid = metrics(20.0, 5.0);
+
+        // Float anchored to a split window at (400, 20).
+        let evt = WindowFloatPos {
+            grid: 3,
+            win: Value::Nil,
+            anchor: Anchor::NW,
+            anchor_grid: 2,
+            anchor_row: 3.0,
+            anchor_col: 4.0,
+            focusable: true,
+            zindex: 50,
+        };
+        let origin = win_float_anchor_pos(
+            &evt,
+            &metrics(40.0, 10.0),
+            (grid.width, grid.height),
+            (400.0, 20.0),
+        );
+        let origin = grid_origin(3, Some(origin), None).unwrap();
+
+        let rect = cell_anchor_rect(origin, &grid, &base, 1, 2);
+        assert_eq!(rect.x, 400 + 40 + 20);
+        assert_eq!(rect.y, 20 + 60 + 20);
+    }
+
+    #[test]
+    fn test_cell_anchor_rect_msg_grid() {
+        let base = metrics(80.0, 24.0);
+        let grid = metrics(80.0, 10.0);
+
+        // Message grid starting from row 20.
+        let origin = grid_origin(4, None, Some((4, 400.0))).unwrap();
+        let rect = cell_anchor_rect(origin, &grid, &base, 1, 3);
+        assert_eq!(rect.x, 30);
+        assert_eq!(rect.y, 420);
+
+        // Rows past the bottom of the window are kept visible.
+        let rect = cell_anchor_rect(origin, &grid, &base, 8, 3);
+        assert_eq!(rect.y, 460);
+    }
+
+    #[test]
+    fn test_sort_by_zindex() {
+        let mut stack = vec![(2, 50), (3, 50), (4, 200)];
+        assert_eq!(sort_by_zindex(&mut stack), None);
+
+        // New float below the existing ones.
+        let mut stack = vec![(2, 50), (4, 200), (5, 100)];
+        assert_eq!(sort_by_zindex(&mut stack), Some(1));
+        assert_eq!(stack, vec![(2, 50), (5, 100), (4, 200)]);
+
+        // Equal z-indices keep their order.
+        let mut stack = vec![(4, 200), (2, 50), (3, 50)];
+        assert_eq!(sort_by_zindex(&mut stack), Some(0));
+        assert_eq!(stack, vec![(2, 50), (3, 50), (4, 200)]);
+    }
+
+    #[test]
+    fn test_float_anchor() {
+        // Base grid.
+        assert_eq!(float_anchor(3, 1, None), Ok(None));
+        // Anchored to a window.
+        assert_eq!(
+            float_anchor(3, 2, Some((10.0, 20.0, false))),
+            Ok(Some((10.0, 20.0)))
+        );
+        // Anchor's window isn't positioned yet.
+        assert_eq!(float_anchor(4, 3, None), Ok(None));
+    }
+
+    #[test]
+    fn test_float_anchor_invalid() {
+        assert!(float_anchor(3, 3, Some((10.0, 20.0, false))).is_err());
+        assert!(float_anchor(3, 2, Some((10.0, 20.0, true))).is_err());
+    }
 }
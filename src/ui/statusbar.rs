@@ -0,0 +1,215 @@
+use gtk::prelude::*;
+use log::error;
+
+use crate::nvim_bridge::StatuslineSegment;
+use crate::nvim_gio::GioNeovim;
+use crate::ui::color::{Color, HlDefs, HlGroup};
+use crate::ui::common::spawn_local;
+use crate::ui::font::{Font, FontUnit};
+
+/// GUI statusbar below the grids, showing the segments set with
+/// `gnvim#statusline#set`. Unlike nvim's statusline, it's drawn with the
+/// GUI's font and can show icons.
+pub struct Statusbar {
+    container: gtk::Box,
+    left: gtk::Box,
+    right: gtk::Box,
+    css_provider: gtk::CssProvider,
+
+    nvim: GioNeovim,
+
+    segments: Vec<StatuslineSegment>,
+    /// Labels of the segments' texts.
+    labels: Vec<gtk::Label>,
+
+    /// Our font.
+    font: Font,
+}
+
+impl Statusbar {
+    pub fn new(parent: &gtk::Box, nvim: GioNeovim) -> Self {
+        let container = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        container.set_widget_name("statusbar");
+        container.set_no_show_all(true);
+
+        let left = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        let right = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        container.pack_start(&left, true, true, 0);
+        container.pack_end(&right, false, false, 0);
+
+        let css_provider = gtk::CssProvider::new();
+        add_css_provider!(&css_provider, container);
+
+        parent.pack_end(&container, false, false, 0);
+
+        Statusbar {
+            container,
+            left,
+            right,
+            css_provider,
+            nvim,
+            segments: vec![],
+            labels: vec![],
+            font: Font::default(),
+        }
+    }
+
+    /// Shows `segments` in the statusbar, or hides it if there are none.
+    pub fn set_segments(
+        &mut self,
+        segments: Vec<StatuslineSegment>,
+        hl_defs: &HlDefs,
+    ) {
+        if segments == self.segments {
+            return;
+        }
+
+        // Usually only the texts change (e.g. the cursor position), so
+        // the widgets are only created again when the layout changes.
+        let same_layout = segments.len() == self.segments.len()
+            && segments.iter().zip(self.segments.iter()).all(|(a, b)| {
+                a.icon == b.icon && a.click == b.click && a.right == b.right
+            });
+
+        self.segments = segments;
+        if same_layout {
+            self.update_labels(hl_defs);
+        } else {
+            self.create_widgets();
+            self.set_colors(hl_defs);
+        }
+    }
+
+    pub fn set_font(&mut self, font: Font, hl_defs: &HlDefs) {
+        self.font = font;
+        self.set_colors(hl_defs);
+    }
+
+    /// Updates the colors from the `StatusLine` highlight group.
+    pub fn set_colors(&self, hl_defs: &HlDefs) {
+        let (fg, bg) = colors(hl_defs);
+        let css = format!(
+            "{font_wild}
+
+            box {{
+                background: #{bg};
+                color: #{fg};
+                padding: 2px 0px;
+            }}",
+            font_wild = self.font.as_wild_css(FontUnit::Point),
+            bg = bg.to_hex(),
+            fg = fg.to_hex(),
+        );
+        CssProviderExt::load_from_data(&self.css_provider, css.as_bytes())
+            .unwrap();
+
+        self.update_labels(hl_defs);
+    }
+
+    fn update_labels(&self, hl_defs: &HlDefs) {
+        let (fg, bg) = colors(hl_defs);
+        for (segment, label) in self.segments.iter().zip(self.labels.iter()) {
+            label.set_markup(&segment.hl.pango_markup(
+                &segment.text,
+                &fg,
+                &bg,
+                &hl_defs.default_sp,
+            ));
+        }
+    }
+
+    fn create_widgets(&mut self) {
+        for side in &[&self.left, &self.right] {
+            for child in side.get_children() {
+                side.remove(&child);
+            }
+        }
+        self.labels.clear();
+
+        if self.segments.is_empty() {
+            self.container.hide();
+            return;
+        }
+
+        for segment in self.segments.iter() {
+            let b = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+            b.set_margin_start(6);
+            b.set_margin_end(6);
+
+            if let Some(ref icon) = segment.icon {
+                let image =
+                    gtk::Image::from_icon_name(Some(icon), gtk::IconSize::Menu);
+                b.pack_start(&image, false, false, 0);
+            }
+
+            let label = gtk::Label::new(None);
+            add_css_provider!(&self.css_provider, label);
+            b.pack_start(&label, false, false, 0);
+            self.labels.push(label);
+
+            let widget = match segment.click {
+                Some(ref click) => {
+                    let event_box = gtk::EventBox::new();
+                    event_box.add(&b);
+                    connect_click(&self.nvim, &event_box, click);
+                    event_box.upcast::<gtk::Widget>()
+                }
+                None => b.upcast(),
+            };
+
+            if segment.right {
+                self.right.pack_start(&widget, false, false, 0);
+            } else {
+                self.left.pack_start(&widget, false, false, 0);
+            }
+        }
+
+        self.container.show_all();
+    }
+}
+
+/// Returns the foreground and background colors of the statusbar.
+fn colors(hl_defs: &HlDefs) -> (Color, Color) {
+    let hl = hl_defs
+        .get_hl_group(&HlGroup::StatusLine)
+        .cloned()
+        .unwrap_or_default();
+    let fg = hl.foreground.unwrap_or(hl_defs.default_fg);
+    let bg = hl.background.unwrap_or(hl_defs.default_bg);
+    if hl.reverse {
+        (bg, fg)
+    } else {
+        (fg, bg)
+    }
+}
+
+/// Calls function `click` with the mouse button ("l", "m" or "r") when
+/// `event_box` is clicked.
+fn connect_click(nvim: &GioNeovim, event_box: &gtk::EventBox, click: &str) {
+    let nvim = nvim.clone();
+    let click = click.to_string();
+    event_box.connect_button_press_event(move |_, e| {
+        if e.get_event_type() != gdk::EventType::ButtonPress {
+            return Inhibit(true);
+        }
+
+        let button = match e.get_button() {
+            1 => "l",
+            2 => "m",
+            3 => "r",
+            _ => return Inhibit(true),
+        };
+
+        let nvim = nvim.clone();
+        let click = click.clone();
+        spawn_local(async move {
+            if let Err(err) =
+                nvim.call_function(&click, vec![button.into()]).await
+            {
+                error!("Failed to call statusline click handler: {}", err);
+            }
+        });
+
+        Inhibit(true)
+    });
+}
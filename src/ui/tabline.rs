@@ -1,8 +1,9 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 use gtk::prelude::*;
 
+use log::error;
 use nvim_rs::Tabpage;
 
 use crate::nvim_gio::{GioNeovim, GioWriter};
@@ -10,6 +11,33 @@ use crate::ui::color::{Color, HlDefs, HlGroup};
 use crate::ui::common::{calc_line_space, spawn_local};
 use crate::ui::font::{Font, FontUnit};
 
+/// Tab titles longer than this (in characters) are truncated. The full
+/// title is then shown in the tab's tooltip.
+const MAX_TITLE_CHARS: usize = 30;
+
+/// Expression that evaluates to a list telling, for each tab, if the tab
+/// has modified buffers.
+const MODIFIED_EXPR: &str = "map(range(1, tabpagenr('$')), {_, nr -> \
+    len(filter(tabpagebuflist(nr), 'getbufvar(v:val, \"&modified\")')) > 0})";
+
+/// What the tabline lists.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TablineMode {
+    Tabs,
+    /// Listed buffers, like bufferline plugins do.
+    Buffers,
+}
+
+impl TablineMode {
+    pub fn from_string(mode: &str) -> Option<Self> {
+        match mode {
+            "tabs" => Some(TablineMode::Tabs),
+            "buffers" => Some(TablineMode::Buffers),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct TablineColors {
     pub fg: Option<Color>,
@@ -22,10 +50,27 @@ pub struct TablineColors {
 
 pub struct Tabline {
     notebook: gtk::Notebook,
+    /// Box the tabline is packed in, when it's not in a header bar.
+    parent: gtk::Box,
+    /// Window title label shown next to the tabs when the tabline is in a
+    /// header bar, and the handler that shows the label when there are no
+    /// tabs.
+    header_bar_title: Option<(gtk::Label, glib::SignalHandlerId)>,
     css_provider: gtk::CssProvider,
     switch_tab_signal: glib::SignalHandlerId,
+    /// Button for the dropdown listing all the tabs. Only visible when
+    /// there are more tabs than fit.
+    overflow_button: gtk::MenuButton,
 
+    nvim: GioNeovim,
+
+    mode: Rc<Cell<TablineMode>>,
     tabpage_data: Rc<RefCell<Vec<Tabpage<GioWriter>>>>,
+    /// Numbers of the buffers, in buffer mode.
+    buffer_data: Rc<RefCell<Vec<i64>>>,
+    /// Notebook pages, in the order the tabs are in nvim. Used to find out
+    /// where a reordered tab was moved from.
+    pages: Rc<RefCell<Vec<gtk::Widget>>>,
 
     /// Our colors.
     colors: TablineColors,
@@ -36,44 +81,191 @@ pub struct Tabline {
 }
 
 impl Tabline {
-    pub fn new(nvim: GioNeovim) -> Self {
+    pub fn new(parent: &gtk::Box, nvim: GioNeovim) -> Self {
         let notebook = gtk::Notebook::new();
+        notebook.set_widget_name("tabline");
         notebook.set_show_border(false);
+        // Show arrows for scrolling the tabs, when they don't all fit.
+        notebook.set_scrollable(true);
+
+        let overflow_button = gtk::MenuButton::new();
+        overflow_button.set_relief(gtk::ReliefStyle::None);
+        overflow_button.set_focus_on_click(false);
+        overflow_button.set_tooltip_text(Some("All tabs"));
+        overflow_button.set_no_show_all(true);
+        notebook.set_action_widget(&overflow_button, gtk::PackType::End);
 
         let css_provider = gtk::CssProvider::new();
-        add_css_provider!(&css_provider, notebook);
+        add_css_provider!(&css_provider, notebook, overflow_button);
 
+        let mode = Rc::new(Cell::new(TablineMode::Tabs));
         let tabpage_data = Rc::new(RefCell::new(vec![]));
+        let buffer_data: Rc<RefCell<Vec<i64>>> = Rc::new(RefCell::new(vec![]));
         let switch_tab_signal = notebook.connect_switch_page(
-            clone!(tabpage_data, nvim => move |_, _, page_num| {
-                let tabpage_data = tabpage_data.clone();
-                let nvim = nvim.clone();
-                spawn_local(async move {
-                    let pages = tabpage_data.borrow();
-                    if let Some(ref page) = pages.get(page_num as usize) {
-                        nvim.set_current_tabpage(&page)
-                            .await
-                            .unwrap();
-                    } else {
-                        println!("Failed to get tab page {}", page_num);
+            clone!(mode, tabpage_data, buffer_data, nvim => move |_, _, page_num| {
+                match mode.get() {
+                    TablineMode::Tabs => {
+                        switch_tab(&nvim, &tabpage_data, page_num as usize)
                     }
-                });
+                    TablineMode::Buffers => {
+                        switch_buffer(&nvim, &buffer_data, page_num as usize)
+                    }
+                }
+            }),
+        );
+
+        // The notebook hides the labels of the tabs that are scrolled out
+        // of view. Show the overflow button when that happens. Changing its
+        // visibility queues another resize, so do it outside of the
+        // allocation.
+        notebook.connect_size_allocate(
+            clone!(overflow_button => move |notebook, _| {
+                let overflow = notebook
+                    .get_children()
+                    .iter()
+                    .filter_map(|child| notebook.get_tab_label(child))
+                    .any(|label| !label.get_child_visible());
+
+                if overflow != overflow_button.get_visible() {
+                    let overflow_button = overflow_button.clone();
+                    gtk::idle_add(move || {
+                        overflow_button.set_visible(overflow);
+                        Continue(false)
+                    });
+                }
             }),
         );
 
+        // When a tab is dragged to a new position, move the tab in nvim too.
+        // Nvim then sends a tabline update, which rebuilds our tabs.
+        let pages: Rc<RefCell<Vec<gtk::Widget>>> =
+            Rc::new(RefCell::new(vec![]));
+        notebook.connect_page_reordered(
+            clone!(pages, nvim => move |_, child, new_pos| {
+                let old_pos =
+                    match pages.borrow().iter().position(|p| p == child) {
+                        Some(pos) => pos as u32,
+                        None => return,
+                    };
+                if old_pos == new_pos {
+                    return;
+                }
+
+                // `:tabmove N` moves the current tab to be after tab N
+                // (counted before the move), and 0 makes it the first tab.
+                let dest = if new_pos > old_pos { new_pos + 1 } else { new_pos };
+                run_command(
+                    &nvim,
+                    format!("{}tabnext | tabmove {}", old_pos + 1, dest),
+                );
+            }),
+        );
+
+        // When a tab is dragged out of the tabline, detach it to its own
+        // window. The page is moved to a throwaway notebook until nvim sends
+        // the tabline update.
+        notebook.connect_create_window(
+            clone!(pages, nvim => move |_, child, _, _| {
+                let pos = pages.borrow().iter().position(|p| p == child);
+                if let Some(pos) = pos {
+                    run_command(
+                        &nvim,
+                        format!("call gnvim#tab#detach({})", pos + 1),
+                    );
+                }
+
+                gtk::Notebook::new()
+            }),
+        );
+
+        parent.pack_start(&notebook, false, false, 0);
+
         Tabline {
             notebook,
+            parent: parent.clone(),
+            header_bar_title: None,
             css_provider,
             switch_tab_signal,
+            overflow_button,
+            nvim,
+            mode,
             tabpage_data,
+            buffer_data,
+            pages,
             colors: TablineColors::default(),
             font: Font::default(),
             line_space: 0,
         }
     }
 
-    pub fn get_widget(&self) -> gtk::Widget {
-        self.notebook.clone().upcast()
+    /// Moves the tabline into `header_bar`, next to the title of `window`.
+    /// The title is only shown when there are no tabs. With `None`, moves
+    /// the tabline back to its own place.
+    pub fn set_header_bar(
+        &mut self,
+        header_bar: Option<&gtk::HeaderBar>,
+        window: &gtk::ApplicationWindow,
+        hl_defs: &HlDefs,
+    ) {
+        if let Some(parent) = self.notebook.get_parent() {
+            if let Ok(parent) = parent.downcast::<gtk::Container>() {
+                parent.remove(&self.notebook);
+            }
+        }
+
+        if let Some((_, signal)) = self.header_bar_title.take() {
+            self.notebook.disconnect(signal);
+        }
+
+        match header_bar {
+            Some(header_bar) => {
+                let title = gtk::Label::new(None);
+                title.get_style_context().add_class("title");
+                title.set_ellipsize(pango::EllipsizeMode::End);
+                title.set_no_show_all(true);
+                title.set_visible(!self.notebook.get_visible());
+                window
+                    .bind_property("title", &title, "label")
+                    .flags(glib::BindingFlags::SYNC_CREATE)
+                    .build();
+
+                let b = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+                b.pack_start(&title, true, true, 0);
+                b.pack_start(&self.notebook, true, true, 0);
+                b.show();
+                header_bar.set_custom_title(Some(&b));
+
+                let signal = self.notebook.connect_property_visible_notify(
+                    clone!(title => move |notebook| {
+                        title.set_visible(!notebook.get_visible());
+                    }),
+                );
+                self.header_bar_title = Some((title, signal));
+            }
+            None => {
+                self.parent.pack_start(&self.notebook, false, false, 0);
+                self.parent.reorder_child(&self.notebook, 0);
+            }
+        }
+
+        self.set_styles(hl_defs);
+    }
+
+    /// Sets what the tabline lists. The tabline is empty until the next
+    /// update (`update` for tabs, `update_buffers` for buffers).
+    pub fn set_mode(&self, mode: TablineMode) {
+        self.mode.set(mode);
+        self.clear();
+        self.notebook.hide();
+    }
+
+    fn clear(&self) {
+        glib::signal_handler_block(&self.notebook, &self.switch_tab_signal);
+        for child in self.notebook.get_children() {
+            self.notebook.remove(&child);
+        }
+        glib::signal_handler_unblock(&self.notebook, &self.switch_tab_signal);
+        self.pages.borrow_mut().clear();
     }
 
     pub fn update(
@@ -81,11 +273,11 @@ impl Tabline {
         current: Tabpage<GioWriter>,
         tabs: Vec<(Tabpage<GioWriter>, String)>,
     ) {
-        glib::signal_handler_block(&self.notebook, &self.switch_tab_signal);
-        for child in self.notebook.get_children() {
-            self.notebook.remove(&child);
+        if self.mode.get() != TablineMode::Tabs {
+            return;
         }
-        glib::signal_handler_unblock(&self.notebook, &self.switch_tab_signal);
+
+        self.clear();
 
         if tabs.len() < 2 {
             self.notebook.hide();
@@ -95,16 +287,22 @@ impl Tabline {
         glib::signal_handler_block(&self.notebook, &self.switch_tab_signal);
 
         let mut page = 0;
+        let mut modified_labels = vec![];
         for (i, tab) in tabs.iter().enumerate() {
-            let tab_label = gtk::Label::new(Some(tab.1.as_str()));
-            tab_label.set_hexpand(true);
-            tab_label.set_ellipsize(pango::EllipsizeMode::End);
-            add_css_provider!(&self.css_provider, tab_label);
-
-            self.notebook.append_page(
-                &gtk::Box::new(gtk::Orientation::Vertical, 0),
-                Some(&tab_label),
+            let nvim = self.nvim.clone();
+            let nr = i + 1;
+            let (tab_label, modified) = self.create_tab_label(
+                &tab.1,
+                "Close tab",
+                Rc::new(move || close_tab(&nvim, nr)),
             );
+            modified_labels.push(modified);
+
+            let child = gtk::Box::new(gtk::Orientation::Vertical, 0);
+            self.notebook.append_page(&child, Some(&tab_label));
+            self.notebook.set_tab_reorderable(&child, true);
+            self.notebook.set_tab_detachable(&child, true);
+            self.pages.borrow_mut().push(child.upcast());
 
             if tab.0.get_value() == current.get_value() {
                 page = i;
@@ -115,10 +313,154 @@ impl Tabline {
 
         self.notebook.set_current_page(Some(page as u32));
 
+        let nvim = &self.nvim;
+        let tabpage_data = &self.tabpage_data;
+        self.overflow_button.set_popup(Some(&create_overflow_menu(
+            tabs.iter().map(|(_, name)| name.as_str()),
+            clone!(nvim, tabpage_data => move |i| {
+                switch_tab(&nvim, &tabpage_data, i);
+            }),
+        )));
+
         self.tabpage_data
             .replace(tabs.into_iter().map(|t| t.0).collect());
 
         glib::signal_handler_unblock(&self.notebook, &self.switch_tab_signal);
+
+        // Nvim doesn't tell us which tabs have modified buffers, so ask it.
+        let nvim = self.nvim.clone();
+        spawn_local(async move {
+            let modified = match nvim.eval(MODIFIED_EXPR).await {
+                Ok(modified) => modified,
+                Err(err) => {
+                    error!("Failed to get modified tabs: {}", err);
+                    return;
+                }
+            };
+
+            if let Some(modified) = modified.as_array() {
+                for (label, modified) in modified_labels.iter().zip(modified) {
+                    label.set_visible(modified.as_u64() == Some(1));
+                }
+            }
+        });
+    }
+
+    /// Lists the `buffers` (number, name, modified), when the tabline is
+    /// in buffer mode.
+    pub fn update_buffers(
+        &self,
+        current: i64,
+        buffers: Vec<(i64, String, bool)>,
+    ) {
+        if self.mode.get() != TablineMode::Buffers {
+            return;
+        }
+
+        self.clear();
+
+        if buffers.is_empty() {
+            self.notebook.hide();
+            return;
+        }
+
+        glib::signal_handler_block(&self.notebook, &self.switch_tab_signal);
+
+        let mut page = 0;
+        let mut modified_labels = vec![];
+        for (i, (buf, name, modified)) in buffers.iter().enumerate() {
+            let nvim = self.nvim.clone();
+            let buf = *buf;
+            let (tab_label, modified_label) = self.create_tab_label(
+                name,
+                "Delete buffer",
+                Rc::new(move || run_command(&nvim, format!("bdelete {}", buf))),
+            );
+            modified_labels.push((modified_label, *modified));
+
+            let child = gtk::Box::new(gtk::Orientation::Vertical, 0);
+            self.notebook.append_page(&child, Some(&tab_label));
+            self.pages.borrow_mut().push(child.upcast());
+
+            if buf == current {
+                page = i;
+            }
+        }
+
+        self.notebook.show_all();
+        for (label, modified) in modified_labels {
+            label.set_visible(modified);
+        }
+
+        self.notebook.set_current_page(Some(page as u32));
+
+        let nvim = &self.nvim;
+        let buffer_data = &self.buffer_data;
+        self.overflow_button.set_popup(Some(&create_overflow_menu(
+            buffers.iter().map(|(_, name, _)| name.as_str()),
+            clone!(nvim, buffer_data => move |i| {
+                switch_buffer(&nvim, &buffer_data, i);
+            }),
+        )));
+
+        self.buffer_data
+            .replace(buffers.into_iter().map(|(buf, _, _)| buf).collect());
+
+        glib::signal_handler_unblock(&self.notebook, &self.switch_tab_signal);
+    }
+
+    /// Creates the label widget for a tab (or a buffer) titled `name`.
+    /// The label's close button calls `on_close`. Returns the widget and the
+    /// modified indicator in it, which is hidden by default.
+    fn create_tab_label(
+        &self,
+        name: &str,
+        close_tooltip: &str,
+        on_close: Rc<dyn Fn()>,
+    ) -> (gtk::Widget, gtk::Label) {
+        let modified = gtk::Label::new(Some("\u{25cf}"));
+        modified.get_style_context().add_class("modified");
+        modified.set_no_show_all(true);
+
+        let title = gtk::Label::new(Some(name));
+        title.set_hexpand(true);
+        title.set_ellipsize(pango::EllipsizeMode::End);
+        title.set_max_width_chars(MAX_TITLE_CHARS as i32);
+
+        let close = gtk::Button::from_icon_name(
+            Some("window-close-symbolic"),
+            gtk::IconSize::Menu,
+        );
+        close.set_relief(gtk::ReliefStyle::None);
+        close.set_focus_on_click(false);
+        close.set_tooltip_text(Some(close_tooltip));
+        close.connect_clicked(clone!(on_close => move |_| on_close()));
+
+        add_css_provider!(&self.css_provider, modified, title, close);
+
+        let b = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+        b.pack_start(&modified, false, false, 0);
+        b.pack_start(&title, true, true, 0);
+        b.pack_start(&close, false, false, 0);
+
+        // Middle click closes the tab.
+        let ebox = gtk::EventBox::new();
+        ebox.set_visible_window(false);
+        ebox.add(&b);
+        ebox.connect_button_press_event(move |_, e| {
+            if e.get_button() == 2 {
+                on_close();
+                Inhibit(true)
+            } else {
+                Inhibit(false)
+            }
+        });
+
+        if name.chars().count() > MAX_TITLE_CHARS {
+            ebox.set_tooltip_text(Some(name));
+        }
+
+        (ebox.upcast(), modified)
     }
 
     pub fn set_font(&mut self, font: Font, hl_defs: &HlDefs) {
@@ -209,7 +551,33 @@ impl Tabline {
             tab:hover {{
                 box-shadow: inset 73px 0px 0px -70px #{selected_fg};
             }}
+            button {{
+                padding: 0px;
+                min-height: 0px;
+                min-width: 0px;
+                color: #{normal_fg};
+                background: transparent;
+                border: none;
+                box-shadow: none;
+            }}
+            button:hover {{
+                color: #{selected_fg};
+            }}
+            {header_bar}
             ",
+            // In a header bar, blend in with the header bar's background.
+            header_bar = if self.header_bar_title.is_some() {
+                "notebook, header {
+                    background: transparent;
+                    border: none;
+                }
+                tab {
+                    background-color: transparent;
+                    box-shadow: none;
+                }"
+            } else {
+                ""
+            },
             font_wild = self.font.as_wild_css(FontUnit::Point),
             normal_fg = self.colors.fg.unwrap_or(hl_defs.default_fg).to_hex(),
             normal_bg = self.colors.bg.unwrap_or(hl_defs.default_bg).to_hex(),
@@ -265,6 +633,16 @@ impl Tabline {
             tab:hover {{
                 box-shadow: inset 73px 0px 0px -70px #{selected_fg};
             }}
+            GtkButton {{
+                padding: 0px;
+                color: #{normal_fg};
+                background: transparent;
+                border: none;
+                box-shadow: none;
+            }}
+            GtkButton:hover {{
+                color: #{selected_fg};
+            }}
             ",
             font_wild = self.font.as_wild_css(FontUnit::Pixel),
             normal_fg = self.colors.fg.unwrap_or(hl_defs.default_fg).to_hex(),
@@ -281,3 +659,71 @@ impl Tabline {
             .unwrap();
     }
 }
+
+/// Switches to the tab at `index` (starting from 0) in `tabpage_data`.
+fn switch_tab(
+    nvim: &GioNeovim,
+    tabpage_data: &Rc<RefCell<Vec<Tabpage<GioWriter>>>>,
+    index: usize,
+) {
+    let tabpage_data = tabpage_data.clone();
+    let nvim = nvim.clone();
+    spawn_local(async move {
+        // The tabs might change while waiting for nvim.
+        let page = tabpage_data.borrow().get(index).cloned();
+        match page {
+            Some(page) => {
+                if let Err(err) = nvim.set_current_tabpage(&page).await {
+                    error!("Failed to switch to tab page {}: {}", index, err);
+                }
+            }
+            None => error!("Failed to get tab page {}", index),
+        }
+    });
+}
+
+/// Switches to the buffer on page `index` of the tabline.
+fn switch_buffer(
+    nvim: &GioNeovim,
+    buffer_data: &Rc<RefCell<Vec<i64>>>,
+    index: usize,
+) {
+    match buffer_data.borrow().get(index) {
+        Some(buf) => run_command(nvim, format!("buffer {}", buf)),
+        None => error!("Failed to get buffer {}", index),
+    }
+}
+
+/// Creates the dropdown menu listing all the tabs (or buffers) by their
+/// `names`. Activating an item calls `activate` with the item's index.
+fn create_overflow_menu<'a, I, F>(names: I, activate: F) -> gtk::Menu
+where
+    I: IntoIterator<Item = &'a str>,
+    F: Fn(usize) + Clone + 'static,
+{
+    let menu = gtk::Menu::new();
+
+    for (i, name) in names.into_iter().enumerate() {
+        let item = gtk::MenuItem::with_label(name);
+        let activate = activate.clone();
+        item.connect_activate(move |_| activate(i));
+        menu.append(&item);
+    }
+
+    menu.show_all();
+    menu
+}
+
+/// Closes tab number `nr` (starting from 1).
+fn close_tab(nvim: &GioNeovim, nr: usize) {
+    run_command(nvim, format!("tabclose {}", nr));
+}
+
+fn run_command(nvim: &GioNeovim, cmd: String) {
+    let nvim = nvim.clone();
+    spawn_local(async move {
+        if let Err(err) = nvim.command(&cmd).await {
+            error!("Failed to execute nvim command '{}': {}", cmd, err);
+        }
+    });
+}
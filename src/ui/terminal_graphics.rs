@@ -0,0 +1,347 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use gdk_pixbuf::PixbufLoaderExt;
+
+/// Start of iTerm2's inline image sequence, after the OSC.
+const ITERM_PREFIX: &str = "1337;File=";
+/// Maximum width and height of an image, in pixels. Larger images are
+/// rejected.
+const MAX_SIZE: i32 = 10_000;
+
+/// Pixel format of a transmitted image.
+#[derive(Debug, PartialEq)]
+pub enum Format {
+    /// Image file (e.g. png or jpeg).
+    Encoded,
+    /// Raw pixels, 3 or 4 (with alpha) bytes each, with the image's width
+    /// and height.
+    Raw {
+        alpha: bool,
+        width: i32,
+        height: i32,
+    },
+}
+
+/// Image command of a program running in a terminal.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Shows an image at the terminal's cursor. Size (rows, cols) is in
+    /// cells, if the program gives it.
+    Show {
+        data: Vec<u8>,
+        format: Format,
+        size: (Option<u64>, Option<u64>),
+    },
+    /// Deletes the shown images.
+    Delete,
+}
+
+/// Parses the image escape sequences of the kitty graphics protocol and
+/// iTerm2's inline images, which nvim passes to us from `:terminal`s.
+#[derive(Default)]
+pub struct Parser {
+    /// Keys of the first chunk and the payload so far, while kitty
+    /// transmits an image in chunks.
+    pending: Option<(HashMap<String, String>, String)>,
+}
+
+impl Parser {
+    /// Parses a terminal's escape `sequence`. Returns `None` for sequences
+    /// that aren't (complete or supported) image commands.
+    pub fn parse(&mut self, sequence: &str) -> Option<Command> {
+        let sequence = sequence
+            .trim_start_matches('\x1b')
+            .trim_end_matches('\x07')
+            .trim_end_matches("\x1b\\");
+
+        let osc = sequence.trim_start_matches(']');
+
+        if sequence.starts_with("_G") {
+            self.parse_kitty(&sequence[2..])
+        } else if osc.starts_with(ITERM_PREFIX) {
+            parse_iterm(&osc[ITERM_PREFIX.len()..])
+        } else {
+            None
+        }
+    }
+
+    fn parse_kitty(&mut self, sequence: &str) -> Option<Command> {
+        let (keys, payload) = match sequence.find(';') {
+            Some(i) => (&sequence[..i], &sequence[i + 1..]),
+            None => (sequence, ""),
+        };
+        let keys = keys
+            .split(',')
+            .filter_map(|kv| {
+                let i = kv.find('=')?;
+                Some((kv[..i].to_string(), kv[i + 1..].to_string()))
+            })
+            .collect::<HashMap<_, _>>();
+        let more = keys.get("m").map_or(false, |m| m == "1");
+
+        let (keys, payload) = match self.pending.take() {
+            // Later chunks only have the `m` key.
+            Some((first, mut data)) => {
+                data.push_str(payload);
+                (first, data)
+            }
+            None => (keys, payload.to_string()),
+        };
+
+        if more {
+            self.pending = Some((keys, payload));
+            return None;
+        }
+
+        let get = |key: &str| keys.get(key).map(String::as_str);
+        let number = |key: &str| get(key).and_then(|v| v.parse().ok());
+
+        match get("a").unwrap_or("t") {
+            "d" => return Some(Command::Delete),
+            "T" => {}
+            _ => return None,
+        }
+
+        // Compressed data isn't supported.
+        if get("o").is_some() {
+            return None;
+        }
+
+        let format = match get("f").unwrap_or("32") {
+            "100" => Format::Encoded,
+            f @ "24" | f @ "32" => Format::Raw {
+                alpha: f == "32",
+                width: get("s")?.parse().ok()?,
+                height: get("v")?.parse().ok()?,
+            },
+            _ => return None,
+        };
+
+        Some(Command::Show {
+            data: glib::base64_decode(&payload),
+            format,
+            size: (number("r"), number("c")),
+        })
+    }
+}
+
+/// Parses the arguments and the payload of iTerm2's inline image
+/// sequence.
+fn parse_iterm(sequence: &str) -> Option<Command> {
+    let i = sequence.find(':')?;
+    let (args, payload) = (&sequence[..i], &sequence[i + 1..]);
+    let args = args
+        .split(';')
+        .filter_map(|kv| {
+            let i = kv.find('=')?;
+            Some((&kv[..i], &kv[i + 1..]))
+        })
+        .collect::<HashMap<_, _>>();
+
+    // Files that aren't inline are downloads.
+    if args.get("inline") != Some(&"1") {
+        return None;
+    }
+
+    // Sizes without a unit are in cells. Others (pixels, percents and
+    // "auto") are sized by the image itself.
+    let cells = |key| args.get(key).and_then(|v| v.parse().ok());
+
+    Some(Command::Show {
+        data: glib::base64_decode(payload),
+        format: Format::Encoded,
+        size: (cells("height"), cells("width")),
+    })
+}
+
+/// Decodes image `data` in `format`.
+pub fn decode(
+    data: Vec<u8>,
+    format: &Format,
+) -> Result<gdk_pixbuf::Pixbuf, String> {
+    match *format {
+        Format::Encoded => {
+            let loader = gdk_pixbuf::PixbufLoader::new();
+            // Don't decode oversized images, just scale them down to
+            // nothing.
+            let oversized = Rc::new(Cell::new(false));
+            loader.connect_size_prepared(
+                clone!(oversized => move |loader, width, height| {
+                    if !valid_size(width, height) {
+                        oversized.set(true);
+                        loader.set_size(1, 1);
+                    }
+                }),
+            );
+            loader.write(&data).map_err(|err| err.to_string())?;
+            loader.close().map_err(|err| err.to_string())?;
+            if oversized.get() {
+                return Err(String::from("Image is too large"));
+            }
+            loader
+                .get_pixbuf()
+                .ok_or_else(|| String::from("No image in the data"))
+        }
+        Format::Raw {
+            alpha,
+            width,
+            height,
+        } => {
+            if !valid_size(width, height) {
+                return Err(String::from("Invalid image size"));
+            }
+
+            let stride = width
+                .checked_mul(if alpha { 4 } else { 3 })
+                .ok_or("Invalid image size")?;
+            let len = stride.checked_mul(height).ok_or("Invalid image size")?;
+            if data.len() != len as usize {
+                return Err(String::from("Invalid image size"));
+            }
+
+            Ok(gdk_pixbuf::Pixbuf::from_mut_slice(
+                data,
+                gdk_pixbuf::Colorspace::Rgb,
+                alpha,
+                8,
+                width,
+                height,
+                stride,
+            ))
+        }
+    }
+}
+
+/// Returns true if an image of `width` and `height` pixels isn't empty or
+/// too large.
+fn valid_size(width: i32, height: i32) -> bool {
+    (1..=MAX_SIZE).contains(&width) && (1..=MAX_SIZE).contains(&height)
+}
+
+/// Returns the size (rows, cols) in cells of an image of `pixels` (width,
+/// height). Missing rows or cols of `size` are calculated from the image's
+/// aspect ratio, and the image is shrunk to fit in `max_cols`.
+pub fn cell_size(
+    pixels: (i32, i32),
+    cell: (f64, f64),
+    size: (Option<u64>, Option<u64>),
+    max_cols: u64,
+) -> (u64, u64) {
+    let (pw, ph) = (f64::from(pixels.0), f64::from(pixels.1));
+    let (cw, ch) = cell;
+    let rows_for = |cols: u64| (cols as f64 * cw * ph / pw / ch).ceil() as u64;
+
+    match size {
+        (Some(rows), Some(cols)) => (rows, cols),
+        (None, Some(cols)) => (rows_for(cols), cols),
+        (Some(rows), None) => {
+            (rows, (rows as f64 * ch * pw / ph / cw).ceil() as u64)
+        }
+        (None, None) => {
+            let cols = ((pw / cw).ceil() as u64).min(max_cols).max(1);
+            (rows_for(cols), cols)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_kitty() {
+        let mut parser = Parser::default();
+
+        assert_eq!(
+            parser.parse("\x1b_Ga=T,f=100,c=10;Zm9v\x1b\\"),
+            Some(Command::Show {
+                data: b"foo".to_vec(),
+                format: Format::Encoded,
+                size: (None, Some(10)),
+            })
+        );
+        assert_eq!(
+            parser.parse("_Ga=T,f=24,s=1,v=1,r=2;AAEC"),
+            Some(Command::Show {
+                data: vec![0, 1, 2],
+                format: Format::Raw {
+                    alpha: false,
+                    width: 1,
+                    height: 1,
+                },
+                size: (Some(2), None),
+            })
+        );
+
+        // Chunked.
+        assert_eq!(parser.parse("_Ga=T,f=100,m=1;Zm9v"), None);
+        assert_eq!(parser.parse("_Gm=1;YmFy"), None);
+        assert_eq!(
+            parser.parse("_Gm=0;YmF6"),
+            Some(Command::Show {
+                data: b"foobarbaz".to_vec(),
+                format: Format::Encoded,
+                size: (None, None),
+            })
+        );
+
+        assert_eq!(parser.parse("_Ga=d"), Some(Command::Delete));
+        // Transmit only, and compressed.
+        assert_eq!(parser.parse("_Gf=100;Zm9v"), None);
+        assert_eq!(parser.parse("_Ga=T,f=100,o=z;Zm9v"), None);
+    }
+
+    #[test]
+    fn test_parse_iterm() {
+        let mut parser = Parser::default();
+
+        assert_eq!(
+            parser.parse("\x1b]1337;File=name=Zm9v;inline=1;width=20:Zm9v\x07"),
+            Some(Command::Show {
+                data: b"foo".to_vec(),
+                format: Format::Encoded,
+                size: (None, Some(20)),
+            })
+        );
+        assert_eq!(
+            parser.parse("]1337;File=inline=1;width=50%;height=4:Zm9v"),
+            Some(Command::Show {
+                data: b"foo".to_vec(),
+                format: Format::Encoded,
+                size: (Some(4), None),
+            })
+        );
+        assert_eq!(parser.parse("]1337;File=name=Zm9v:Zm9v"), None);
+        assert_eq!(parser.parse("]52;c;Zm9v"), None);
+    }
+
+    #[test]
+    fn test_decode_raw_size() {
+        let format = |width, height| Format::Raw {
+            alpha: true,
+            width,
+            height,
+        };
+
+        assert!(decode(vec![0; 2 * 3 * 4], &format(2, 3)).is_ok());
+        assert!(decode(vec![0; 2 * 3 * 4], &format(3, 3)).is_err());
+        assert!(decode(vec![], &format(0, 3)).is_err());
+        assert!(decode(vec![], &format(MAX_SIZE + 1, 1)).is_err());
+        assert!(decode(vec![], &format(i32::MAX, i32::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_cell_size() {
+        // 10x20 cells.
+        let cell = (10.0, 20.0);
+
+        assert_eq!(cell_size((200, 200), cell, (Some(3), Some(4)), 80), (3, 4));
+        assert_eq!(cell_size((200, 200), cell, (None, Some(10)), 80), (5, 10));
+        assert_eq!(cell_size((200, 200), cell, (Some(5), None), 80), (5, 10));
+        assert_eq!(cell_size((200, 200), cell, (None, None), 80), (10, 20));
+        // Shrunk to fit.
+        assert_eq!(cell_size((200, 200), cell, (None, None), 10), (5, 10));
+    }
+}
@@ -0,0 +1,578 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+
+use crate::ui::color::{Color, HlDefs, HlGroup};
+use crate::ui::font::{Font, FontUnit};
+
+/// Width of a toast, in pixels.
+const WIDTH: i32 = 400;
+/// Maximum number of toasts shown at once. The oldest ones are dismissed
+/// first.
+const MAX_TOASTS: usize = 5;
+/// Maximum number of lines in a toast. Longer messages are shown in the
+/// message area.
+const MAX_TOAST_LINES: usize = 3;
+/// How long (in ms) a toast is shown.
+const TIMEOUT: u32 = 4000;
+/// How long (in ms) an error toast is shown.
+const TIMEOUT_ERROR: u32 = 8000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ToastKind {
+    Info,
+    Warning,
+    Error,
+}
+
+impl ToastKind {
+    /// Returns the time (in ms) after which the toast is dismissed.
+    fn timeout(self) -> u32 {
+        match self {
+            ToastKind::Info | ToastKind::Warning => TIMEOUT,
+            ToastKind::Error => TIMEOUT_ERROR,
+        }
+    }
+
+    fn css_class(self) -> Option<&'static str> {
+        match self {
+            ToastKind::Warning => Some("warning"),
+            ToastKind::Error => Some("error"),
+            ToastKind::Info => None,
+        }
+    }
+}
+
+/// Where a message is shown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Placement {
+    Toast(ToastKind),
+    /// The message area, which stays until nvim clears the messages.
+    Area,
+    Hidden,
+}
+
+impl Placement {
+    /// Returns the placement of a message of `kind` (from `msg_show`) that
+    /// has `lines` lines. Only short errors, warnings and echoed messages
+    /// are toasted. Lists (e.g. `:ls`), prompts and other long output go to
+    /// the message area.
+    fn from_msg(kind: &str, lines: usize) -> Self {
+        let kind = match kind {
+            "emsg" | "echoerr" | "lua_error" | "rpc_error" => ToastKind::Error,
+            "wmsg" => ToastKind::Warning,
+            "" | "echo" | "echomsg" => ToastKind::Info,
+            // The hit-enter prompt is only a hint to press a key.
+            "return_prompt" => return Placement::Hidden,
+            _ => return Placement::Area,
+        };
+
+        if lines > MAX_TOAST_LINES {
+            Placement::Area
+        } else {
+            Placement::Toast(kind)
+        }
+    }
+}
+
+struct Toast {
+    id: u64,
+    widget: gtk::EventBox,
+    /// Timer that dismisses the toast.
+    timer: Option<glib::SourceId>,
+}
+
+/// Transient notifications for nvim's short messages (with
+/// `ext_messages`), stacked in the bottom right corner of the window. Longer
+/// messages are shown in the message area at the bottom of the window, and
+/// the mode, 'showcmd' and 'ruler' under it.
+pub struct Toasts {
+    css_provider: gtk::CssProvider,
+    parent: gtk::Overlay,
+    container: gtk::Box,
+
+    toasts: Rc<RefCell<Vec<Toast>>>,
+    next_id: u64,
+
+    /// Message area, for the messages that aren't toasted.
+    area: gtk::Frame,
+    area_scroll: gtk::ScrolledWindow,
+    area_label: gtk::Label,
+    /// Markup of the messages in the message area.
+    area_messages: Vec<String>,
+
+    mode: gtk::Label,
+    showcmd: gtk::Label,
+    ruler: gtk::Label,
+
+    /// Our font.
+    font: Font,
+}
+
+impl Toasts {
+    pub fn new(parent: &gtk::Overlay) -> Self {
+        let css_provider = gtk::CssProvider::new();
+
+        let container = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        container.set_halign(gtk::Align::End);
+        container.set_margin_end(10);
+        container.set_margin_bottom(10);
+        // Space for the toasts' shadows.
+        container.set_margin_start(10);
+        container.set_margin_top(10);
+
+        let area_label = gtk::Label::new(None);
+        area_label.set_selectable(true);
+        area_label.set_xalign(0.0);
+        area_label.set_yalign(0.0);
+
+        let area_scroll = gtk::ScrolledWindow::new(
+            None::<&gtk::Adjustment>,
+            None::<&gtk::Adjustment>,
+        );
+        area_scroll
+            .set_policy(gtk::PolicyType::Automatic, gtk::PolicyType::Automatic);
+        area_scroll.add(&area_label);
+
+        let area = gtk::Frame::new(None);
+        area.get_style_context().add_class("messages");
+        area.add(&area_scroll);
+        area.set_no_show_all(true);
+        area_scroll.show_all();
+
+        let mode = gtk::Label::new(None);
+        let showcmd = gtk::Label::new(None);
+        let ruler = gtk::Label::new(None);
+        let status = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        status.pack_start(&mode, false, false, 0);
+        status.pack_end(&ruler, false, false, 0);
+        status.pack_end(&showcmd, false, false, 0);
+
+        let box_ = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        box_.set_valign(gtk::Align::End);
+        box_.pack_start(&container, false, false, 0);
+        box_.pack_start(&area, false, false, 0);
+        box_.pack_start(&status, false, false, 0);
+        parent.add_overlay(&box_);
+
+        add_css_provider!(
+            &css_provider,
+            area,
+            area_scroll,
+            area_label,
+            mode,
+            showcmd,
+            ruler
+        );
+
+        Toasts {
+            css_provider,
+            parent: parent.clone(),
+            container,
+            toasts: Rc::new(RefCell::new(vec![])),
+            next_id: 0,
+            area,
+            area_scroll,
+            area_label,
+            area_messages: vec![],
+            mode,
+            showcmd,
+            ruler,
+            font: Font::default(),
+        }
+    }
+
+    /// Shows a message of `kind` (from `msg_show`). If `replace_last` is
+    /// true, replaces the latest message instead of adding a new one.
+    pub fn show(
+        &mut self,
+        kind: &str,
+        content: &[(u64, String)],
+        replace_last: bool,
+        hl_defs: &HlDefs,
+    ) {
+        let markup = content_markup(content, hl_defs);
+        if markup.is_empty() {
+            return;
+        }
+
+        match Placement::from_msg(kind, markup.lines().count()) {
+            Placement::Toast(kind) => {
+                if replace_last {
+                    let last = self.toasts.borrow().last().map(|t| t.id);
+                    if let Some(id) = last {
+                        dismiss(&self.container, &self.toasts, id);
+                    }
+                }
+
+                self.add(kind, &markup);
+            }
+            Placement::Area => {
+                if replace_last {
+                    self.area_messages.pop();
+                }
+                self.area_messages.push(markup);
+                self.update_area();
+            }
+            Placement::Hidden => {}
+        }
+    }
+
+    /// Shows the message history (from `msg_history_show`) in the message
+    /// area.
+    pub fn show_history(
+        &mut self,
+        entries: &[(String, Vec<(u64, String)>)],
+        hl_defs: &HlDefs,
+    ) {
+        self.area_messages.extend(
+            entries
+                .iter()
+                .map(|(_, content)| content_markup(content, hl_defs))
+                .filter(|markup| !markup.is_empty()),
+        );
+        self.update_area();
+    }
+
+    /// Clears the message area. Toasts go away on their own.
+    pub fn clear(&mut self) {
+        self.area_messages.clear();
+        self.update_area();
+    }
+
+    /// Shows the mode message (e.g. "-- INSERT --").
+    pub fn set_mode(&self, content: &[(u64, String)], hl_defs: &HlDefs) {
+        self.mode.set_markup(&content_markup(content, hl_defs));
+    }
+
+    /// Shows the 'showcmd' text.
+    pub fn set_showcmd(&self, content: &[(u64, String)], hl_defs: &HlDefs) {
+        self.showcmd.set_markup(&content_markup(content, hl_defs));
+    }
+
+    /// Shows the 'ruler' text.
+    pub fn set_ruler(&self, content: &[(u64, String)], hl_defs: &HlDefs) {
+        self.ruler.set_markup(&content_markup(content, hl_defs));
+    }
+
+    fn update_area(&self) {
+        if self.area_messages.is_empty() {
+            self.area.hide();
+            self.area_label.set_text("");
+            return;
+        }
+
+        self.area_label.set_markup(&self.area_messages.join("\n"));
+
+        // Grow with the messages, up to half of the window.
+        let max = self.parent.get_allocated_height() / 2;
+        let (_, height) = self.area_label.get_preferred_height();
+        self.area_scroll.set_size_request(-1, height.min(max));
+        self.area.show();
+    }
+
+    fn add(&mut self, kind: ToastKind, markup: &str) {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let label = gtk::Label::new(None);
+        label.set_markup(markup);
+        label.set_line_wrap(true);
+        label.set_line_wrap_mode(pango::WrapMode::WordChar);
+        label.set_xalign(0.0);
+
+        let frame = gtk::Frame::new(None);
+        frame.set_size_request(WIDTH, -1);
+        frame.add(&label);
+        frame.get_style_context().add_class("toast");
+        if let Some(class) = kind.css_class() {
+            frame.get_style_context().add_class(class);
+        }
+
+        // Clicking dismisses the toast.
+        let widget = gtk::EventBox::new();
+        widget.add(&frame);
+        let container = self.container.clone();
+        let toasts = self.toasts.clone();
+        widget.connect_button_press_event(move |_, _| {
+            dismiss(&container, &toasts, id);
+            Inhibit(true)
+        });
+
+        add_css_provider!(&self.css_provider, frame, label);
+
+        self.container.pack_start(&widget, false, false, 0);
+        widget.show_all();
+
+        let timer = {
+            let container = self.container.clone();
+            let toasts = self.toasts.clone();
+            gtk::timeout_add(kind.timeout(), move || {
+                // The source is removed when we return, so forget it.
+                if let Some(toast) =
+                    toasts.borrow_mut().iter_mut().find(|t| t.id == id)
+                {
+                    toast.timer = None;
+                }
+                dismiss(&container, &toasts, id);
+                Continue(false)
+            })
+        };
+
+        self.toasts.borrow_mut().push(Toast {
+            id,
+            widget,
+            timer: Some(timer),
+        });
+
+        let oldest = {
+            let toasts = self.toasts.borrow();
+            toasts
+                .len()
+                .checked_sub(MAX_TOASTS)
+                .map(|n| toasts[..n].iter().map(|t| t.id).collect())
+                .unwrap_or_else(Vec::new)
+        };
+        for id in oldest {
+            dismiss(&self.container, &self.toasts, id);
+        }
+    }
+
+    pub fn set_font(&mut self, font: Font, hl_defs: &HlDefs) {
+        self.font = font;
+        self.set_colors(hl_defs);
+    }
+
+    pub fn set_colors(&self, hl_defs: &HlDefs) {
+        let toast = hl_defs.get_hl_group(&HlGroup::Toast);
+        let fg = toast
+            .and_then(|hl| hl.foreground)
+            .unwrap_or(hl_defs.default_fg);
+        let bg = toast
+            .and_then(|hl| hl.background)
+            .unwrap_or(hl_defs.default_bg);
+        // The diagnostics' colors are usually less glaring than the
+        // messages' ones.
+        let fg_of = |groups: &[HlGroup]| {
+            groups
+                .iter()
+                .find_map(|group| hl_defs.get_hl_group(group)?.foreground)
+                .unwrap_or(fg)
+        };
+        let warning = fg_of(&[HlGroup::DiagnosticWarn, HlGroup::WarningMsg]);
+        let error = fg_of(&[HlGroup::DiagnosticError, HlGroup::ErrorMsg]);
+
+        if gtk::get_minor_version() < 20 {
+            self.set_styles_pre20(fg, bg, warning, error);
+        } else {
+            self.set_styles_post20(fg, bg, warning, error);
+        }
+    }
+
+    fn set_styles_pre20(
+        &self,
+        fg: Color,
+        bg: Color,
+        warning: Color,
+        error: Color,
+    ) {
+        let css = format!(
+            "{font_wild}
+
+            GtkFrame.toast {{
+                padding: 8px;
+                background: #{bg};
+                border: none;
+                border-left: 4px solid #{fg};
+                border-radius: 0;
+                box-shadow: 0px 3px 5px 0px rgba(0, 0, 0, 0.5);
+            }}
+
+            GtkFrame.toast.warning {{
+                border-left-color: #{warning};
+            }}
+
+            GtkFrame.toast.error {{
+                border-left-color: #{error};
+            }}
+
+            GtkFrame.messages {{
+                padding: 4px;
+                background: #{bg};
+                border: none;
+                border-radius: 0;
+            }}
+
+            GtkLabel {{
+                color: #{fg};
+            }}",
+            font_wild = self.font.as_wild_css(FontUnit::Pixel),
+            fg = fg.to_hex(),
+            bg = bg.to_hex(),
+            warning = warning.to_hex(),
+            error = error.to_hex(),
+        );
+        CssProviderExt::load_from_data(&self.css_provider, css.as_bytes())
+            .unwrap();
+    }
+
+    fn set_styles_post20(
+        &self,
+        fg: Color,
+        bg: Color,
+        warning: Color,
+        error: Color,
+    ) {
+        let css = format!(
+            "{font_wild}
+
+            frame.toast {{
+                padding: 8px;
+                background: #{bg};
+                border-left: 4px solid #{fg};
+                box-shadow: 0px 3px 5px 0px rgba(0, 0, 0, 0.5);
+            }}
+
+            frame.toast > border {{
+                border: none;
+            }}
+
+            frame.toast.warning {{
+                border-left-color: #{warning};
+            }}
+
+            frame.toast.error {{
+                border-left-color: #{error};
+            }}
+
+            frame.messages {{
+                padding: 4px;
+                background: #{bg};
+            }}
+
+            frame.messages > border {{
+                border: none;
+            }}
+
+            label {{
+                color: #{fg};
+            }}",
+            font_wild = self.font.as_wild_css(FontUnit::Point),
+            fg = fg.to_hex(),
+            bg = bg.to_hex(),
+            warning = warning.to_hex(),
+            error = error.to_hex(),
+        );
+        CssProviderExt::load_from_data(&self.css_provider, css.as_bytes())
+            .unwrap();
+    }
+}
+
+/// Removes the toast with `id`, if it's still around.
+fn dismiss(container: &gtk::Box, toasts: &RefCell<Vec<Toast>>, id: u64) {
+    let mut toasts = toasts.borrow_mut();
+    if let Some(index) = toasts.iter().position(|t| t.id == id) {
+        let toast = toasts.remove(index);
+        if let Some(timer) = toast.timer {
+            glib::source::source_remove(timer);
+        }
+        container.remove(&toast.widget);
+    }
+}
+
+/// Returns pango markup for message `content`, colored with its
+/// highlights. Leading and trailing new lines are left out.
+fn content_markup(content: &[(u64, String)], hl_defs: &HlDefs) -> String {
+    let toast = hl_defs.get_hl_group(&HlGroup::Toast);
+    let fg = toast
+        .and_then(|hl| hl.foreground)
+        .unwrap_or(hl_defs.default_fg);
+    let bg = toast
+        .and_then(|hl| hl.background)
+        .unwrap_or(hl_defs.default_bg);
+
+    let last = content.len().saturating_sub(1);
+    content
+        .iter()
+        .enumerate()
+        .map(|(i, (hl_id, text))| {
+            let text = match i {
+                0 => text.trim_start_matches('\n'),
+                _ => text.as_str(),
+            };
+            let text = if i == last {
+                text.trim_end_matches('\n')
+            } else {
+                text
+            };
+
+            match hl_defs.get(hl_id) {
+                Some(hl) => {
+                    hl.pango_markup(text, &fg, &bg, &hl_defs.default_sp)
+                }
+                None => glib::markup_escape_text(text).to_string(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_placement_from_msg() {
+        assert_eq!(
+            Placement::from_msg("emsg", 1),
+            Placement::Toast(ToastKind::Error)
+        );
+        assert_eq!(
+            Placement::from_msg("lua_error", 1),
+            Placement::Toast(ToastKind::Error)
+        );
+        assert_eq!(
+            Placement::from_msg("wmsg", 1),
+            Placement::Toast(ToastKind::Warning)
+        );
+        assert_eq!(
+            Placement::from_msg("echo", 1),
+            Placement::Toast(ToastKind::Info)
+        );
+        assert_eq!(
+            Placement::from_msg("", 1),
+            Placement::Toast(ToastKind::Info)
+        );
+        assert_eq!(Placement::from_msg("list_cmd", 1), Placement::Area);
+        assert_eq!(Placement::from_msg("confirm", 1), Placement::Area);
+        assert_eq!(Placement::from_msg("return_prompt", 1), Placement::Hidden);
+    }
+
+    #[test]
+    fn test_placement_from_msg_long() {
+        assert_eq!(
+            Placement::from_msg("emsg", MAX_TOAST_LINES),
+            Placement::Toast(ToastKind::Error)
+        );
+        assert_eq!(
+            Placement::from_msg("emsg", MAX_TOAST_LINES + 1),
+            Placement::Area
+        );
+        assert_eq!(Placement::from_msg("", 20), Placement::Area);
+    }
+
+    #[test]
+    fn test_toast_kind_timeout() {
+        assert_eq!(ToastKind::Info.timeout(), TIMEOUT);
+        assert_eq!(ToastKind::Error.timeout(), TIMEOUT_ERROR);
+    }
+
+    #[test]
+    fn test_content_markup_trims_new_lines() {
+        let hl_defs = HlDefs::default();
+        let content =
+            vec![(0, String::from("\nfoo\n")), (0, String::from("<bar>\n\n"))];
+
+        assert_eq!(content_markup(&content, &hl_defs), "foo\n&lt;bar&gt;");
+    }
+}
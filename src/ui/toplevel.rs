@@ -0,0 +1,101 @@
+use gtk::prelude::*;
+
+/// Decorations (title bar and borders) of the main window.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Decorations {
+    /// Server side decorations, drawn by the window manager.
+    Server,
+    /// Client side decorations, drawn by gnvim (a header bar).
+    Client,
+    /// Client side decorations, with the tabline in the header bar.
+    Tabline,
+    /// No decorations at all.
+    None,
+}
+
+impl Decorations {
+    pub fn from_string(decorations: &str) -> Option<Self> {
+        match decorations {
+            "server" => Some(Decorations::Server),
+            "client" => Some(Decorations::Client),
+            "tabline" => Some(Decorations::Tabline),
+            "none" => Some(Decorations::None),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Decorations {
+    fn default() -> Self {
+        Decorations::Server
+    }
+}
+
+/// State of the main window that can be controlled from nvim. The window
+/// manager (and the user) can change the state too, so it's kept in sync
+/// with the window's state events.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ToplevelState {
+    pub fullscreen: bool,
+    pub maximized: bool,
+    /// If the window stays on top of other windows.
+    pub keep_above: bool,
+    pub decorations: Decorations,
+}
+
+impl ToplevelState {
+    /// Updates the state from the window's new state.
+    pub fn update(&mut self, state: gdk::WindowState) {
+        self.fullscreen = state.contains(gdk::WindowState::FULLSCREEN);
+        self.maximized = state.contains(gdk::WindowState::MAXIMIZED);
+        self.keep_above = state.contains(gdk::WindowState::ABOVE);
+    }
+
+    /// Applies the state (except for the decorations) to `window`.
+    pub fn apply(&self, window: &gtk::ApplicationWindow) {
+        if self.fullscreen {
+            window.fullscreen();
+        } else {
+            window.unfullscreen();
+        }
+
+        if self.maximized {
+            window.maximize();
+        } else {
+            window.unmaximize();
+        }
+
+        window.set_keep_above(self.keep_above);
+    }
+}
+
+/// Sets the decorations of `window`. Returns the header bar for client side
+/// decorations. Switching between having a header bar and not having one
+/// re-creates the window's gdk window.
+pub fn set_decorations(
+    window: &gtk::ApplicationWindow,
+    decorations: Decorations,
+) -> Option<gtk::HeaderBar> {
+    match decorations {
+        Decorations::Server => {
+            window.set_titlebar(None::<&gtk::Widget>);
+            window.set_decorated(true);
+            None
+        }
+        Decorations::Client | Decorations::Tabline => {
+            // Without a title of its own, the header bar shows the
+            // window's title.
+            let header_bar = gtk::HeaderBar::new();
+            header_bar.set_show_close_button(true);
+            header_bar.show();
+            window.set_titlebar(Some(&header_bar));
+            window.set_decorated(true);
+            Some(header_bar)
+        }
+        Decorations::None => {
+            window.set_titlebar(None::<&gtk::Widget>);
+            window.set_decorated(false);
+            None
+        }
+    }
+}
@@ -1,25 +1,74 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
 use std::rc::Rc;
 
+use gio::prelude::*;
 use gtk::prelude::*;
+use gtk::SettingsExt;
+
+use gdk::keys::constants as key;
 
 use log::{debug, error};
 use rmpv::Value;
 
+use crate::crash::RecentEvents;
 use crate::nvim_bridge::{Message, Request};
-use crate::nvim_gio::GioNeovim;
+use crate::nvim_gio::{GioNeovim, RequestError};
+use crate::ui::animation::Animations;
 use crate::ui::cmdline::Cmdline;
 use crate::ui::color::{Highlight, HlDefs};
-use crate::ui::common::spawn_local;
-#[cfg(feature = "libwebkit2gtk")]
+use crate::ui::color_scheme::{self, ColorScheme};
+use crate::ui::command_palette::CommandPalette;
+use crate::ui::common::{nvim_error, spawn_local};
 use crate::ui::cursor_tooltip::CursorTooltip;
+use crate::ui::dbus::DbusControl;
+use crate::ui::file_tree::FileTree;
+use crate::ui::finder::Finder;
+use crate::ui::float_drag::FloatDrag;
 use crate::ui::font::Font;
-use crate::ui::grid::Grid;
+use crate::ui::grid::{
+    BlinkCurve, Crosshair, Grid, IndentGuides, LinkMatcher, Rasterizer,
+    RendererKind,
+};
+use crate::ui::input::{event_to_nvim_input, Shortcuts};
+use crate::ui::input_queue::{Input, InputQueue};
+use crate::ui::log_viewer::LogViewer;
+use crate::ui::paste::Paster;
+use crate::ui::perf::PerfOverlay;
 use crate::ui::popupmenu::Popupmenu;
+use crate::ui::preview::Preview;
+use crate::ui::print::Printer;
+use crate::ui::progress::Progress;
+use crate::ui::search_bar::SearchBar;
+use crate::ui::separator_drag::SeparatorDrag;
+use crate::ui::stall_banner::StallBanner;
 use crate::ui::state::{attach_grid_events, UIState, Windows};
+use crate::ui::statusbar::Statusbar;
 use crate::ui::tabline::Tabline;
-use crate::ui::window::MsgWindow;
+use crate::ui::terminal_graphics;
+use crate::ui::toasts::Toasts;
+use crate::ui::toplevel::ToplevelState;
+use crate::ui::window::{FloatOptions, MsgWindow};
+
+/// Startup settings of the UI, from the command line.
+pub struct UIOptions {
+    pub window_size: (i32, i32),
+    /// Initial state (e.g. fullscreen) of the main window.
+    pub toplevel: ToplevelState,
+    pub renderer: RendererKind,
+    /// Number of threads that rasterize the grids' rows, 0 to rasterize
+    /// them on the main thread.
+    pub render_threads: usize,
+    pub background_opacity: f64,
+    /// Path of the user's CSS file.
+    pub custom_css: Option<PathBuf>,
+    /// If the UI is controlled through D-Bus.
+    pub dbus: bool,
+    /// If set, the window isn't shown, and the grids are written to the
+    /// directory when nvim exits.
+    pub offscreen_dir: Option<PathBuf>,
+}
 
 /// Main UI structure.
 pub struct UI {
@@ -41,30 +90,93 @@ impl UI {
     /// * `rx` - Channel to receive nvim UI events.
     /// * `nvim` - Neovim instance to use. Should be the same that is the source
     ///            of `rx` events.
+    /// * `opts` - Startup settings of the UI.
     pub fn init(
         app: &gtk::Application,
         rx: glib::Receiver<Message>,
-        window_size: (i32, i32),
         nvim: GioNeovim,
+        opts: UIOptions,
     ) -> Self {
+        let UIOptions {
+            window_size,
+            toplevel,
+            renderer,
+            render_threads,
+            background_opacity,
+            custom_css,
+            dbus,
+            offscreen_dir,
+        } = opts;
+
         // Create the main window.
         let window = gtk::ApplicationWindow::new(app);
         window.set_title("Neovim");
         window.set_default_size(window_size.0, window_size.1);
+        toplevel.apply(&window);
+
+        // Use a visual with alpha channel (if available), so that the
+        // background can be translucent.
+        if let Some(visual) =
+            window.get_screen().and_then(|s| s.get_rgba_visual())
+        {
+            window.set_visual(Some(&visual));
+        }
 
         // Realize window resources.
         window.realize();
 
+        // Action for bringing the window to the front (e.g. when a desktop
+        // notification is clicked).
+        let present = gio::SimpleAction::new("present", None);
+        present
+            .connect_activate(clone!(window => move |_, _| window.present()));
+        app.add_action(&present);
+
+        // Files from other gnvim instances (see `--single-instance`) are
+        // opened in new tabs.
+        let open =
+            app.connect_open(clone!(nvim, window => move |_, files, _| {
+                for file in files {
+                    let path = match file.get_path() {
+                        Some(path) => path.to_string_lossy().into_owned(),
+                        None => file.get_uri().to_string(),
+                    };
+                    let cmd = format!(
+                        "execute 'tab drop' fnameescape('{}')",
+                        path.replace('\'', "''")
+                    );
+
+                    let nvim = nvim.clone();
+                    spawn_local(async move {
+                        if let Err(err) = nvim.command(&cmd).await {
+                            error!("Failed to open file: {}", err);
+                        }
+                    });
+                }
+
+                window.present();
+            }));
+        let open = RefCell::new(Some(open));
+        window.connect_destroy(clone!(app => move |_| {
+            if let Some(open) = open.borrow_mut().take() {
+                app.disconnect(open);
+            }
+        }));
+
         // Top level widget.
         let b = gtk::Box::new(gtk::Orientation::Vertical, 0);
         window.add(&b);
 
-        let tabline = Tabline::new(nvim.clone());
-        b.pack_start(&tabline.get_widget(), false, false, 0);
+        let tabline = Tabline::new(&b, nvim.clone());
 
-        // Our root widget for all grids/windows.
+        // Our root widget for all grids/windows, between the (initially
+        // hidden) file tree and markdown preview.
+        let paned = gtk::Paned::new(gtk::Orientation::Horizontal);
+        b.pack_start(&paned, true, true, 0);
+        let sidebar_paned = gtk::Paned::new(gtk::Orientation::Horizontal);
+        paned.pack1(&sidebar_paned, true, false);
         let overlay = gtk::Overlay::new();
-        b.pack_start(&overlay, true, true, 0);
+        sidebar_paned.pack2(&overlay, true, false);
 
         // Create hl defs and initialize 0th element because we'll need to have
         // something that is accessible for the default grid that we're gonna
@@ -75,6 +187,12 @@ impl UI {
         let font = Font::from_guifont("Monospace:h12").unwrap();
         let line_space = 0;
 
+        let rasterizer = if render_threads > 0 {
+            Some(Rasterizer::new(render_threads))
+        } else {
+            None
+        };
+
         // Create default grid.
         let mut grid = Grid::new(
             1,
@@ -85,6 +203,9 @@ impl UI {
             30,
             &hl_defs,
             true,
+            None,
+            renderer,
+            rasterizer.as_ref(),
         );
         // Mark the default grid as active at the beginning.
         grid.set_active(true);
@@ -140,45 +261,23 @@ impl UI {
             }
 
             *source_id = Some(new);
-
-            false
         }));
 
-        attach_grid_events(&grid, nvim.clone());
+        // Keyboard and mouse inputs go through the input queue, which holds
+        // them back while nvim is busy.
+        let input_queue = InputQueue::new(&overlay, nvim.clone());
+        attach_grid_events(&grid, nvim.clone(), &input_queue);
 
         // IMMulticontext is used to handle most of the inputs.
         let im_context = gtk::IMMulticontext::new();
-        im_context.set_use_preedit(false);
-        im_context.connect_commit(clone!(nvim => move |_, input| {
+        im_context.set_use_preedit(true);
+        im_context.connect_commit(clone!(input_queue => move |_, input| {
             // "<" needs to be escaped for nvim.input()
             let nvim_input = input.replace("<", "<lt>");
-
-            let nvim = nvim.clone();
-            spawn_local(async move {
-                nvim.input(&nvim_input).await.expect("Couldn't send input");
-            });
+            input_queue.send(Input::Keys(nvim_input));
         }));
 
-        window.connect_key_press_event(clone!(nvim, im_context => move |_, e| {
-            if im_context.filter_keypress(e) {
-                Inhibit(true)
-            } else {
-                if let Some(input) = event_to_nvim_input(e) {
-                    let nvim = nvim.clone();
-                    spawn_local(async move {
-                        nvim.input(input.as_str()).await.expect("Couldn't send input");
-                    });
-                    return Inhibit(true);
-                } else {
-                    debug!(
-                        "Failed to turn input event into nvim key (keyval: {})",
-                        e.get_keyval()
-                    )
-                }
-
-                Inhibit(false)
-            }
-        }));
+        let alt_as_meta = Rc::new(Cell::new(true));
 
         window.connect_key_release_event(clone!(im_context => move |_, e| {
             im_context.filter_keypress(e);
@@ -195,16 +294,50 @@ impl UI {
             Inhibit(false)
         }));
 
+        // Pinch to zoom changes the font size. The gesture is on the overlay,
+        // so it works on all the grids.
+        let zoom_gesture = gtk::GestureZoom::new(&overlay);
+        let separator_drag = SeparatorDrag::new(&overlay, nvim.clone());
+        let float_drag = FloatDrag::new(&overlay, nvim.clone());
+
         let cmdline = Cmdline::new(&overlay, nvim.clone());
-        #[cfg(feature = "libwebkit2gtk")]
+        let command_palette =
+            CommandPalette::new(window.upcast_ref(), nvim.clone());
+        let finder = Finder::new(window.upcast_ref(), nvim.clone());
+        let log_viewer = LogViewer::new(window.upcast_ref());
+        let toasts = Toasts::new(&overlay);
+        let progress = Progress::new(&window);
+        let stall_banner = StallBanner::new(&overlay, nvim.watchdog().clone());
+        let paster = Paster::new(
+            nvim.clone(),
+            window.clone().upcast(),
+            progress.clone(),
+        );
+        let perf = PerfOverlay::new(&overlay, nvim.clone());
         let cursor_tooltip = CursorTooltip::new(&overlay);
-
-        window.show_all();
+        let preview = Preview::new(&paned);
+        let file_tree = FileTree::new(&sidebar_paned, nvim.clone());
+        let search_bar = SearchBar::new(&overlay, nvim.clone());
+        let statusbar = Statusbar::new(&b, nvim.clone());
+        let dbus = if dbus {
+            Some(DbusControl::new(&window, nvim.clone()))
+        } else {
+            None
+        };
+
+        // Offscreen, the grids still render to their surfaces (and flush
+        // right away, as there are no frames), but nothing is shown.
+        if offscreen_dir.is_some() {
+            if let Some(child) = window.get_child() {
+                child.show_all();
+            }
+        } else {
+            window.show_all();
+        }
 
         grid.set_im_context(&im_context);
 
         cmdline.hide();
-        #[cfg(feature = "libwebkit2gtk")]
         cursor_tooltip.hide();
 
         let mut grids = HashMap::new();
@@ -212,48 +345,396 @@ impl UI {
 
         add_css_provider!(&css_provider, window);
 
+        // The user's CSS applies to all of our widgets, and it has a higher
+        // priority than the widgets' own providers.
+        let custom_css_provider = gtk::CssProvider::new();
+        if let Some(screen) = window.get_screen() {
+            gtk::StyleContext::add_provider_for_screen(
+                &screen,
+                &custom_css_provider,
+                gtk::STYLE_PROVIDER_PRIORITY_USER,
+            );
+        }
+
+        let state = Rc::new(RefCell::new(UIState {
+            css_provider,
+            custom_css_provider,
+            windows: Windows::new(),
+            float_stack: vec![],
+            float_positions: HashMap::new(),
+            external_positions: Default::default(),
+            windows_container,
+            msg_window_container,
+            msg_window,
+            windows_float_container,
+            grids,
+            mode_infos: vec![],
+            current_grid: 1,
+            wildmenu_shown: false,
+            popupmenu: Popupmenu::new(&overlay, nvim.clone()),
+            cmdline,
+            command_palette,
+            finder,
+            log_viewer,
+            offscreen_dir,
+            recent_events: RecentEvents::new(),
+            toasts,
+            progress,
+            paster,
+            input_queue,
+            stall_banner,
+            perf,
+            overlay,
+            zoom_gesture: zoom_gesture.clone(),
+            separator_drag,
+            float_drag,
+            tabline,
+            cursor_tooltip,
+            terminal_graphics: terminal_graphics::Parser::default(),
+            preview,
+            file_tree,
+            detached_tabs: HashMap::new(),
+            printer: Printer::default(),
+            search_bar,
+            copy_mode: None,
+            statusbar,
+            dbus,
+            resize_source_id: source_id,
+            hl_defs,
+            resize_on_flush: None,
+            nvim_exited: false,
+            flush_pending: false,
+            held_events: VecDeque::new(),
+            request_frames: Box::new(|| {}),
+            frames_requested: false,
+            hl_changed: false,
+            font,
+            line_space,
+            device_scale: None,
+            color_scheme: ColorScheme::default(),
+            prefer_dark_theme: gtk::Settings::get_default().map_or(
+                false,
+                |settings| {
+                    settings.get_property_gtk_application_prefer_dark_theme()
+                },
+            ),
+            current_mode: None,
+            current_mode_index: None,
+            alt_as_meta: alt_as_meta.clone(),
+            shortcuts: Shortcuts::default(),
+            enable_cursor_animations: true,
+            cursor_blink_curve: BlinkCurve::default(),
+            cursor_hollow_unfocused: true,
+            crosshair: Crosshair::default(),
+            indent_guides: IndentGuides::default(),
+            damage_overlay: false,
+            kinetic_scrolling: false,
+            gui_selection: false,
+            hlstate: false,
+            link_matcher: Rc::new(LinkMatcher::default()),
+            toplevel,
+            scrollbar: false,
+            minimap: false,
+            float_options: FloatOptions::default(),
+            window_float_options: HashMap::new(),
+            animations: Animations::default(),
+            background_opacity: 1.0,
+            background_image: RefCell::new(None),
+            hl_redraw: HashSet::new(),
+            default_colors: None,
+            im_context: im_context.clone(),
+            renderer,
+            rasterizer,
+        }));
+
+        if background_opacity < 1.0 {
+            state
+                .borrow_mut()
+                .set_background_opacity(background_opacity);
+        }
+
+        if let Some(path) = custom_css {
+            state
+                .borrow()
+                .set_custom_css(&path.to_string_lossy(), &nvim);
+        }
+
+        // The overlay's draw handler runs before its children are drawn, so
+        // the background image ends up behind the grids.
+        state
+            .borrow()
+            .overlay
+            .connect_draw(clone!(state => move |overlay, cr| {
+                let state = state.borrow();
+                if let Some(ref mut image) = *state.background_image.borrow_mut()
+                {
+                    image.draw(
+                        cr,
+                        overlay.get_allocated_width(),
+                        overlay.get_allocated_height(),
+                        &state.hl_defs.default_bg,
+                        state.background_opacity,
+                    );
+                }
+                Inhibit(false)
+            }));
+
+        // Show the input method's preedit text (e.g. the composition text
+        // of CJK input) in the grid, before it is committed.
+        im_context.connect_preedit_changed(clone!(state => move |im_context| {
+            let (text, attrs, cursor) = im_context.get_preedit_string();
+            let state = state.borrow();
+            if text.is_empty() {
+                state.set_preedit(None);
+            } else {
+                state.set_preedit(Some((text.as_str(), &attrs, cursor)));
+            }
+        }));
+        im_context.connect_preedit_end(clone!(state => move |_| {
+            state.borrow().set_preedit(None);
+        }));
+
+        // GUI shortcuts are checked before the input method gets the key.
+        window.connect_key_press_event(clone!(state, nvim, im_context, alt_as_meta => move |window, e| {
+            // Keys typed in the search bar, the file tree or the cmdline
+            // block's copy mode aren't sent to nvim.
+            {
+                let state = state.borrow();
+                if state.search_bar.has_focus()
+                    || state.file_tree.has_focus()
+                    || state.cmdline.block_in_copy_mode()
+                {
+                    return Inhibit(false);
+                }
+            }
+
+            if state.borrow_mut().copy_mode_key(e) {
+                return Inhibit(true);
+            }
+
+            // Escape cancels a paste that is being streamed to nvim.
+            if e.get_keyval() == key::Escape
+                && state.borrow().paster.cancel()
+            {
+                return Inhibit(true);
+            }
+
+            let shortcut = state.borrow().shortcuts.find(e);
+            if let Some(shortcut) = shortcut {
+                if state.borrow_mut().run_shortcut(shortcut, window, &nvim) {
+                    return Inhibit(true);
+                }
+            }
+
+            if im_context.filter_keypress(e) {
+                Inhibit(true)
+            } else {
+                if let Some(input) = event_to_nvim_input(e, alt_as_meta.get()) {
+                    let state = state.borrow();
+                    state.perf.key_pressed();
+                    state.input_queue.send(Input::Keys(input));
+                    return Inhibit(true);
+                } else {
+                    debug!(
+                        "Failed to turn input event into nvim key (keyval: {})",
+                        e.get_keyval()
+                    )
+                }
+
+                Inhibit(false)
+            }
+        }));
+
+        // Closing the window quits nvim, which then closes the window. This
+        // way unsaved changes aren't lost.
+        let quitting = Rc::new(Cell::new(false));
+        window.connect_delete_event(clone!(nvim, state => move |window, _| {
+            if state.borrow().nvim_exited {
+                return Inhibit(false);
+            }
+
+            // Don't ask again while the previous close is being handled.
+            if !quitting.replace(true) {
+                let window = window.clone();
+                let nvim = nvim.clone();
+                let quitting = quitting.clone();
+                spawn_local(async move {
+                    quit(&window, &nvim).await;
+                    quitting.set(false);
+                });
+            }
+
+            Inhibit(true)
+        }));
+
+        // Keep track of the state changes made by the window manager (and
+        // the user), so toggling e.g. fullscreen works as expected.
+        window.connect_window_state_event(clone!(state => move |_, e| {
+            state
+                .borrow_mut()
+                .toplevel
+                .update(e.get_new_window_state());
+            Inhibit(false)
+        }));
+
+        // Nvim assumes that it has the focus when it starts.
+        let nvim_focused = Rc::new(Cell::new(true));
+        window.connect_focus_in_event(
+            clone!(state, nvim, nvim_focused => move |_, _| {
+                state.borrow().set_focused(true);
+                sync_focus(&nvim, &nvim_focused);
+                Inhibit(false)
+            }),
+        );
+        window.connect_focus_out_event(
+            clone!(state, nvim, nvim_focused => move |_, _| {
+                state.borrow().set_focused(false);
+                sync_focus(&nvim, &nvim_focused);
+                Inhibit(false)
+            }),
+        );
+
+        // Rebuild the cell metrics when the window moves to a monitor with
+        // a different scale, or when the font DPI changes. The metrics are
+        // rebuilt on idle, once gtk has updated the pango contexts of the
+        // widgets, and only once for a burst of changes.
+        let refresh_source_id = Rc::new(RefCell::new(None));
+        let queue_refresh_metrics =
+            Rc::new(clone!(state, nvim, window, refresh_source_id => move || {
+                if refresh_source_id.borrow().is_some() {
+                    return;
+                }
+
+                let id = gtk::idle_add(
+                    clone!(state, nvim, window, refresh_source_id => move || {
+                        refresh_source_id.borrow_mut().take();
+                        state.borrow_mut().refresh_metrics(&nvim, &window);
+                        Continue(false)
+                    }),
+                );
+                refresh_source_id.replace(Some(id));
+            }));
+        window.connect_property_scale_factor_notify(
+            clone!(queue_refresh_metrics => move |_| queue_refresh_metrics()),
+        );
+        // The screen's resolution follows the font DPI (`gtk-xft-dpi`).
+        if let Some(screen) = window.get_screen() {
+            screen.connect_property_resolution_notify(
+                clone!(queue_refresh_metrics => move |_| {
+                    queue_refresh_metrics()
+                }),
+            );
+        }
+
+        color_scheme::watch(clone!(state, nvim, window => move |scheme| {
+            state.borrow_mut().set_color_scheme(scheme, &nvim, &window);
+        }));
+
+        state.borrow().input_queue.connect_keys_sent(
+            clone!(state => move || state.borrow().perf.keys_sent()),
+        );
+
+        state.borrow().separator_drag.connect_hit_test(
+            clone!(state => move |x, y| {
+                state.borrow().separator_at(x, y)
+            }),
+        );
+        state.borrow().float_drag.connect_hit_test(
+            clone!(state => move |x, y| state.borrow().float_at(x, y)),
+        );
+
+        // Font height when the current zoom gesture began.
+        let zoom_start = Rc::new(Cell::new(None));
+        // NOTE: Using `connect_local`, because touchpad gestures don't have
+        //       an event sequence and `connect_begin` doesn't handle that.
+        zoom_gesture
+            .connect_local(
+                "begin",
+                false,
+                clone!(state, zoom_start => move |_| {
+                    zoom_start.set(Some(state.borrow().font.height));
+                    None
+                }),
+            )
+            .unwrap();
+        zoom_gesture.connect_scale_changed(
+            clone!(state, nvim, zoom_start => move |_, scale| {
+                let state = state.borrow();
+                let start = zoom_start.get().unwrap_or(state.font.height);
+                state.set_font_height(start * scale as f32, &nvim);
+            }),
+        );
+
+        // Ctrl + scroll wheel changes the font size too. Smooth scroll
+        // deltas are accumulated until they add up to a whole step.
+        let zoom_scroll = Rc::new(Cell::new(0.0));
+        state.borrow().overlay.connect_scroll_event(
+            clone!(state, nvim => move |_, e| {
+                if !e.get_state().contains(gdk::ModifierType::CONTROL_MASK) {
+                    return Inhibit(false);
+                }
+
+                let delta = match e.get_direction() {
+                    gdk::ScrollDirection::Up => 1.0,
+                    gdk::ScrollDirection::Down => -1.0,
+                    gdk::ScrollDirection::Smooth => -e.get_delta().1,
+                    _ => 0.0,
+                };
+
+                let delta = zoom_scroll.get() + delta;
+                let steps = delta.trunc();
+                zoom_scroll.set(delta - steps);
+
+                if steps != 0.0 {
+                    let state = state.borrow();
+                    state.set_font_height(
+                        state.font.height + steps as f32,
+                        &nvim,
+                    );
+                }
+
+                Inhibit(true)
+            }),
+        );
+
         UI {
             win: window,
             rx,
-            state: Rc::new(RefCell::new(UIState {
-                css_provider,
-                windows: Windows::new(),
-                windows_container,
-                msg_window_container,
-                msg_window,
-                windows_float_container,
-                grids,
-                mode_infos: vec![],
-                current_grid: 1,
-                wildmenu_shown: false,
-                popupmenu: Popupmenu::new(&overlay, nvim.clone()),
-                cmdline,
-                overlay,
-                tabline,
-                #[cfg(feature = "libwebkit2gtk")]
-                cursor_tooltip,
-                resize_source_id: source_id,
-                hl_defs,
-                resize_on_flush: None,
-                hl_changed: false,
-                font,
-                line_space,
-                current_mode: None,
-                enable_cursor_animations: true,
-            })),
+            state,
             nvim,
         }
     }
 
     /// Starts to listen events from `rx` (e.g. from nvim) and processing those.
-    /// Think this as the "main" function of the UI.
-    pub fn start(self) {
+    /// Think this as the "main" function of the UI. If nvim crashes, the user
+    /// can restart it with `restart`, which gets `session_file` if the user
+    /// wants to restore the session.
+    pub fn start<F: Fn(Option<PathBuf>) + 'static>(
+        self,
+        session_file: PathBuf,
+        restart: F,
+    ) {
         let UI {
             rx,
             state,
             win,
             nvim,
         } = self;
+        let restart: Rc<dyn Fn(Option<PathBuf>)> = Rc::new(restart);
+
+        // Tick callbacks are run once per frame, before painting. The
+        // callback is only added when there's something to paint, so that
+        // the frame clock doesn't run while nvim is idle.
+        let weak_state = Rc::downgrade(&state);
+        state.borrow_mut().request_frames =
+            Box::new(clone!(win, nvim => move || {
+                let state = weak_state.clone();
+                win.add_tick_callback(clone!(nvim => move |win, _| {
+                    let state = upgrade_weak!(state, Continue(false));
+                    let more = state.borrow_mut().flush_pending(&nvim, win);
+                    Continue(more)
+                }));
+            }));
 
         rx.attach(None, move |message| {
             match message {
@@ -271,9 +752,32 @@ impl UI {
                 }
                 // Handle close.
                 Message::Close => {
+                    state.borrow_mut().nvim_exited = true;
+                    state.borrow().stall_banner.stop();
+                    state.borrow().dump_offscreen();
                     win.close();
                     return Continue(false);
                 }
+                Message::Crash(reason) => {
+                    state.borrow_mut().nvim_exited = true;
+                    state.borrow().stall_banner.stop();
+                    error!("Nvim exited unexpectedly: {}", reason);
+
+                    // Nobody is there to answer the dialog.
+                    if state.borrow().offscreen_dir.is_some() {
+                        state.borrow().dump_offscreen();
+                        win.close();
+                        return Continue(false);
+                    }
+
+                    show_crash_dialog(
+                        &win,
+                        &reason,
+                        session_file.clone(),
+                        restart.clone(),
+                    );
+                    return Continue(false);
+                }
             }
 
             Continue(true)
@@ -281,13 +785,181 @@ impl UI {
     }
 }
 
-#[cfg_attr(not(feature = "libwebkit2gtk"), allow(unused_variables))] // Silence clippy
+/// Expression that evaluates to a list of the names of the modified buffers.
+const MODIFIED_BUFFERS_EXPR: &str = "map(getbufinfo({'bufmodified': 1}), \
+    {_, b -> empty(b.name) ? '[No Name]' : fnamemodify(b.name, ':~:.')})";
+
+/// Quits nvim. If there are modified buffers, the user is asked whether to
+/// save or discard the changes first.
+async fn quit(window: &gtk::ApplicationWindow, nvim: &GioNeovim) {
+    let modified = match nvim.eval(MODIFIED_BUFFERS_EXPR).await {
+        Ok(modified) => modified,
+        Err(err) => {
+            // If nvim is gone, the window gets closed anyway.
+            error!("Failed to get modified buffers: {}", err);
+            return;
+        }
+    };
+
+    let modified = modified
+        .as_array()
+        .map(|names| {
+            names
+                .iter()
+                .filter_map(|name| name.as_str().map(String::from))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    if modified.is_empty() {
+        run_quit_command(nvim, "qa").await;
+        return;
+    }
+
+    let dialog = gtk::MessageDialog::new(
+        Some(window),
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        gtk::MessageType::Warning,
+        gtk::ButtonsType::None,
+        "Save changes before closing?",
+    );
+    dialog.set_property_secondary_text(Some(&format!(
+        "Modified buffers:\n{}",
+        modified.join("\n")
+    )));
+    dialog.add_buttons(&[
+        ("Cancel", gtk::ResponseType::Cancel),
+        ("Discard", gtk::ResponseType::Reject),
+        ("Save All", gtk::ResponseType::Accept),
+    ]);
+    dialog.set_default_response(gtk::ResponseType::Accept);
+
+    dialog.connect_response(clone!(nvim => move |dialog, res| {
+        let cmd = match res {
+            gtk::ResponseType::Accept => Some("wa | qa"),
+            gtk::ResponseType::Reject => Some("qa!"),
+            _ => None,
+        };
+
+        if let Some(cmd) = cmd {
+            let nvim = nvim.clone();
+            spawn_local(async move { run_quit_command(&nvim, cmd).await });
+        }
+
+        dialog.close();
+    }));
+
+    dialog.show_all();
+}
+
+/// Tells nvim whether gnvim has the focus, for the `FocusGained` and
+/// `FocusLost` autocmds (and 'autoread'). Checked on idle, and against all
+/// of gnvim's windows, so that moving the focus to e.g. the command
+/// palette or a detached tab doesn't count as losing it.
+fn sync_focus(nvim: &GioNeovim, nvim_focused: &Rc<Cell<bool>>) {
+    gtk::idle_add(clone!(nvim, nvim_focused => move || {
+        let focused = gtk::Window::list_toplevels()
+            .iter()
+            .filter_map(|w| w.downcast_ref::<gtk::Window>())
+            .any(|w| w.is_active());
+
+        if nvim_focused.replace(focused) != focused {
+            let nvim = nvim.clone();
+            spawn_local(async move {
+                let res = match nvim
+                    .call("nvim_ui_set_focus", vec![Value::from(focused)])
+                    .await
+                {
+                    // Nvim older than 0.9 doesn't have nvim_ui_set_focus,
+                    // so just run the autocmds.
+                    Err(RequestError::Failed(err))
+                        if err.contains("Invalid method") =>
+                    {
+                        let event =
+                            if focused { "FocusGained" } else { "FocusLost" };
+                        let cmd = format!("doautocmd <nomodeline> {}", event);
+                        nvim.command(&cmd).await.map_err(|err| err.to_string())
+                    }
+                    res => res.map(|_| ()).map_err(|err| err.to_string()),
+                };
+                if let Err(err) = res {
+                    error!("Failed to set focus: {}", err);
+                }
+            });
+        }
+
+        Continue(false)
+    }));
+}
+
+/// Runs `cmd`, which should make nvim exit. If it fails (e.g. a buffer
+/// can't be saved), nvim stays open and shows the error.
+async fn run_quit_command(nvim: &GioNeovim, cmd: &str) {
+    match nvim.command(cmd).await {
+        // Nvim exited before responding.
+        Err(err) if err.is_channel_closed() => {}
+        Err(err) => nvim_error(nvim, err.to_string()),
+        Ok(()) => {}
+    }
+}
+
+/// Asks the user whether to restart nvim after it exited unexpectedly. The
+/// window is closed in any case.
+fn show_crash_dialog(
+    win: &gtk::ApplicationWindow,
+    reason: &str,
+    session_file: PathBuf,
+    restart: Rc<dyn Fn(Option<PathBuf>)>,
+) {
+    let dialog = gtk::MessageDialog::new(
+        Some(win),
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        gtk::MessageType::Error,
+        gtk::ButtonsType::None,
+        "Neovim exited unexpectedly",
+    );
+    dialog.set_property_secondary_text(Some(reason));
+    dialog.add_buttons(&[
+        ("Close", gtk::ResponseType::Close),
+        ("Restart", gtk::ResponseType::Accept),
+    ]);
+    dialog.set_default_response(gtk::ResponseType::Accept);
+
+    // The session is saved by the runtime plugin while nvim is idle.
+    let restore = gtk::CheckButton::with_label("Restore the session");
+    restore.set_active(session_file.exists());
+    restore.set_sensitive(session_file.exists());
+    if let Some(area) = dialog
+        .get_message_area()
+        .and_then(|w| w.downcast::<gtk::Box>().ok())
+    {
+        area.pack_start(&restore, false, false, 0);
+    }
+
+    dialog.connect_response(clone!(win => move |dialog, res| {
+        if res == gtk::ResponseType::Accept {
+            let session = if restore.get_active() {
+                Some(session_file.clone())
+            } else {
+                None
+            };
+            // The new window needs to be up before the old one is closed,
+            // so that the application doesn't quit.
+            restart(session);
+        }
+
+        dialog.close();
+        win.close();
+    }));
+
+    dialog.show_all();
+}
+
 fn handle_request(
     request: &Request,
     state: &mut UIState,
 ) -> Result<Value, Value> {
     match request {
-        #[cfg(feature = "libwebkit2gtk")]
         Request::CursorTooltipStyles => {
             let styles = state.cursor_tooltip.get_styles();
 
@@ -296,97 +968,5 @@ fn handle_request(
 
             Ok(res.into())
         }
-        #[cfg(not(feature = "libwebkit2gtk"))]
-        Request::CursorTooltipStyles => {
-            Err("Cursor tooltip is not supported in this build".into())
-        }
-    }
-}
-
-fn keyname_to_nvim_key(s: &str) -> Option<&str> {
-    // Originally sourced from python-gui.
-    match s {
-        "asciicircum" => Some("^"), // fix #137
-        "slash" => Some("/"),
-        "backslash" => Some("\\"),
-        "dead_circumflex" => Some("^"),
-        "at" => Some("@"),
-        "numbersign" => Some("#"),
-        "dollar" => Some("$"),
-        "percent" => Some("%"),
-        "ampersand" => Some("&"),
-        "asterisk" => Some("*"),
-        "parenleft" => Some("("),
-        "parenright" => Some(")"),
-        "underscore" => Some("_"),
-        "plus" => Some("+"),
-        "minus" => Some("-"),
-        "bracketleft" => Some("["),
-        "bracketright" => Some("]"),
-        "braceleft" => Some("{"),
-        "braceright" => Some("}"),
-        "dead_diaeresis" => Some("\""),
-        "dead_acute" => Some("\'"),
-        "less" => Some("<"),
-        "greater" => Some(">"),
-        "comma" => Some(","),
-        "period" => Some("."),
-        "BackSpace" => Some("BS"),
-        "Insert" => Some("Insert"),
-        "Return" => Some("CR"),
-        "Escape" => Some("Esc"),
-        "Delete" => Some("Del"),
-        "Page_Up" => Some("PageUp"),
-        "Page_Down" => Some("PageDown"),
-        "Enter" => Some("CR"),
-        "ISO_Left_Tab" => Some("Tab"),
-        "Tab" => Some("Tab"),
-        "Up" => Some("Up"),
-        "Down" => Some("Down"),
-        "Left" => Some("Left"),
-        "Right" => Some("Right"),
-        "Home" => Some("Home"),
-        "End" => Some("End"),
-        "F1" => Some("F1"),
-        "F2" => Some("F2"),
-        "F3" => Some("F3"),
-        "F4" => Some("F4"),
-        "F5" => Some("F5"),
-        "F6" => Some("F6"),
-        "F7" => Some("F7"),
-        "F8" => Some("F8"),
-        "F9" => Some("F9"),
-        "F10" => Some("F10"),
-        "F11" => Some("F11"),
-        "F12" => Some("F12"),
-        _ => None,
     }
 }
-
-fn event_to_nvim_input(e: &gdk::EventKey) -> Option<String> {
-    let mut input = String::from("");
-
-    let keyval = e.get_keyval();
-    let keyname = keyval.name()?;
-
-    let state = e.get_state();
-
-    if state.contains(gdk::ModifierType::SHIFT_MASK) {
-        input.push_str("S-");
-    }
-    if state.contains(gdk::ModifierType::CONTROL_MASK) {
-        input.push_str("C-");
-    }
-    if state.contains(gdk::ModifierType::MOD1_MASK) {
-        input.push_str("A-");
-    }
-
-    if keyname.chars().count() > 1 {
-        let n = keyname_to_nvim_key(keyname.as_str())?;
-        input.push_str(n);
-    } else {
-        input.push(keyval.to_unicode()?);
-    }
-
-    Some(format!("<{}>", input))
-}
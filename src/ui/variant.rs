@@ -0,0 +1,80 @@
+use glib::glib_sys;
+use glib::translate::{from_glib_full, from_glib_none, ToGlibPtr};
+use glib::ToVariant;
+
+/// Returns a tuple of `items`, e.g. the arguments of a signal.
+pub fn tuple(items: &[glib::Variant]) -> glib::Variant {
+    let items = items
+        .iter()
+        .map(|item| item.to_glib_none().0)
+        .collect::<Vec<*mut glib_sys::GVariant>>();
+    unsafe {
+        from_glib_none(glib_sys::g_variant_new_tuple(
+            items.as_ptr(),
+            items.len(),
+        ))
+    }
+}
+
+/// Returns a dictionary (`a{sv}`) of `entries`, e.g. the properties of a
+/// signal.
+pub fn dict(entries: &[(&str, glib::Variant)]) -> glib::Variant {
+    unsafe {
+        let entries = entries
+            .iter()
+            .map(|(key, value)| {
+                glib_sys::g_variant_new_dict_entry(
+                    key.to_variant().to_glib_none().0,
+                    glib_sys::g_variant_new_variant(value.to_glib_none().0),
+                )
+            })
+            .collect::<Vec<*mut glib_sys::GVariant>>();
+        from_glib_none(glib_sys::g_variant_new_array(
+            b"{sv}\0".as_ptr() as *const glib_sys::GVariantType,
+            entries.as_ptr(),
+            entries.len(),
+        ))
+    }
+}
+
+/// Returns item `index` of a tuple (or another container).
+pub fn child(variant: &glib::Variant, index: usize) -> Option<glib::Variant> {
+    let ptr = variant.to_glib_none().0;
+    unsafe {
+        if glib_sys::g_variant_is_container(ptr) == glib_sys::GFALSE
+            || index >= glib_sys::g_variant_n_children(ptr)
+        {
+            return None;
+        }
+        Some(from_glib_full(glib_sys::g_variant_get_child_value(
+            ptr, index,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tuple() {
+        let t = tuple(&["a".to_variant(), 1u32.to_variant()]);
+        assert_eq!(t.type_().to_str(), "(su)");
+        assert_eq!(t.to_string(), "('a', 1)");
+        assert_eq!(
+            child(&t, 0).and_then(|v| v.get::<String>()),
+            Some("a".into())
+        );
+        assert_eq!(child(&t, 1).and_then(|v| v.get::<u32>()), Some(1));
+        assert_eq!(child(&t, 2), None);
+        assert_eq!(child(&"a".to_variant(), 0), None);
+    }
+
+    #[test]
+    fn test_dict() {
+        let d = dict(&[("a", 1u32.to_variant()), ("b", true.to_variant())]);
+        assert_eq!(d.type_().to_str(), "a{sv}");
+        assert_eq!(d.to_string(), "{'a': <uint32 1>, 'b': <true>}");
+        assert_eq!(dict(&[]).to_string(), "@a{sv} {}");
+    }
+}
@@ -2,24 +2,70 @@ use gtk::prelude::*;
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use log::error;
+
 use crate::nvim_bridge;
 use crate::nvim_gio::GioNeovim;
 use crate::ui::color::{Color, HlDefs, HlGroup};
 use crate::ui::common::spawn_local;
 
 const MAX_HEIGHT: i32 = 500;
+/// Number of rows on a page of the grid layout.
+const GRID_ROWS: usize = 8;
+/// Horizontal padding of an item in the grid layout.
+const GRID_ITEM_PADDING: i32 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WildmenuLayout {
+    /// One item per row.
+    List,
+    /// Items in columns, paginated.
+    Grid,
+}
+
+impl WildmenuLayout {
+    pub fn from_string(s: &str) -> Option<Self> {
+        match s {
+            "list" => Some(WildmenuLayout::List),
+            "grid" => Some(WildmenuLayout::Grid),
+            _ => None,
+        }
+    }
+}
+
+impl Default for WildmenuLayout {
+    fn default() -> Self {
+        WildmenuLayout::List
+    }
+}
 
 #[derive(Default)]
 struct State {
     /// Currently selected row in wildmenu.
     selected: i32,
+
+    layout: WildmenuLayout,
+    /// Items of the grid layout.
+    items: Vec<String>,
+    /// Number of columns in the grid layout.
+    columns: usize,
+    /// Page of the grid layout that is shown.
+    page: Option<usize>,
 }
 
 pub struct Wildmenu {
     css_provider: gtk::CssProvider,
     frame: gtk::Frame,
+    stack: gtk::Stack,
     list: gtk::ListBox,
 
+    grid: gtk::Grid,
+    /// Shows the page of the grid layout, if there are many.
+    page_label: gtk::Label,
+    /// Items on the grid's page.
+    grid_items: Vec<gtk::EventBox>,
+
+    nvim: GioNeovim,
     state: Rc<RefCell<State>>,
 }
 
@@ -28,6 +74,9 @@ impl Wildmenu {
         let css_provider = gtk::CssProvider::new();
 
         let frame = gtk::Frame::new(None);
+        frame.set_widget_name("wildmenu");
+        let stack = gtk::Stack::new();
+        stack.set_homogeneous(false);
 
         let list = gtk::ListBox::new();
         list.set_selection_mode(gtk::SelectionMode::Single);
@@ -39,8 +88,19 @@ impl Wildmenu {
         scrolledwindow
             .set_policy(gtk::PolicyType::Automatic, gtk::PolicyType::Automatic);
         scrolledwindow.add(&list);
+        stack.add_named(&scrolledwindow, "list");
 
-        frame.add(&scrolledwindow);
+        let grid = gtk::Grid::new();
+        grid.set_column_homogeneous(true);
+        let page_label = gtk::Label::new(None);
+        page_label.set_halign(gtk::Align::End);
+        page_label.set_no_show_all(true);
+        let grid_box = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        grid_box.pack_start(&grid, false, false, 0);
+        grid_box.pack_start(&page_label, false, false, 0);
+        stack.add_named(&grid_box, "grid");
+
+        frame.add(&stack);
 
         let frame_weak = frame.downgrade();
         // Make sure our container grows to certain height.
@@ -62,36 +122,40 @@ impl Wildmenu {
         let state = Rc::new(RefCell::new(State::default()));
 
         // If user selects some row with a mouse, notify nvim about it.
-        list.connect_row_activated(clone!(state => move |_, row| {
+        list.connect_row_activated(clone!(state, nvim => move |_, row| {
             let prev = state.borrow().selected;
-            let new = row.get_index();
-
-            let op = if new > prev { "<Tab>" } else { "<S-Tab>" };
-
-            for _ in 0..(new - prev).abs() {
-                // NOTE(ville): nvim doesn't like single input with many
-                //              tabs in it, so we'll have to send each
-                //              individually.
-                let nvim = nvim.clone();
-                spawn_local(async move {
-                    nvim.input(&op)
-                        .await
-                        .unwrap();
-                })
-            }
+            select_item(&nvim, prev, row.get_index());
         }));
 
-        add_css_provider!(&css_provider, list, frame);
+        add_css_provider!(&css_provider, list, frame, grid, page_label);
 
         Wildmenu {
             css_provider,
             list,
             frame,
+            stack,
+            grid,
+            page_label,
+            grid_items: vec![],
 
+            nvim,
             state,
         }
     }
 
+    pub fn set_layout(&mut self, layout: WildmenuLayout) {
+        self.clear();
+        self.state.borrow_mut().layout = layout;
+
+        match layout {
+            WildmenuLayout::List => self.stack.set_visible_child_name("list"),
+            WildmenuLayout::Grid => {
+                self.stack.set_visible_child_name("grid");
+                self.frame.set_size_request(-1, -1);
+            }
+        }
+    }
+
     pub fn widget(&self) -> gtk::Widget {
         self.frame.clone().upcast()
     }
@@ -109,11 +173,32 @@ impl Wildmenu {
         while let Some(item) = children.pop() {
             self.list.remove(&item);
         }
+
+        self.clear_grid();
+        let mut state = self.state.borrow_mut();
+        state.items.clear();
+        state.page = None;
     }
 
-    pub fn set_items(&mut self, items: &[nvim_bridge::CompletionItem]) {
+    fn clear_grid(&mut self) {
+        for item in self.grid_items.drain(..) {
+            self.grid.remove(&item);
+        }
+    }
+
+    /// Sets the items. `width` is the width available for the wildmenu.
+    pub fn set_items(
+        &mut self,
+        items: &[nvim_bridge::CompletionItem],
+        width: i32,
+    ) {
         self.clear();
 
+        if self.state.borrow().layout == WildmenuLayout::Grid {
+            self.set_grid_items(items, width);
+            return;
+        }
+
         for item in items {
             let label = gtk::Label::new(Some(item.word.as_str()));
             label.set_halign(gtk::Align::Start);
@@ -129,9 +214,83 @@ impl Wildmenu {
         self.list.show_all();
     }
 
+    fn set_grid_items(
+        &mut self,
+        items: &[nvim_bridge::CompletionItem],
+        width: i32,
+    ) {
+        // All the columns are as wide as the widest item.
+        let item_width = items
+            .iter()
+            .max_by_key(|item| item.word.chars().count())
+            .map(|item| self.frame.create_pango_layout(Some(&item.word)))
+            .map(|layout| layout.get_pixel_size().0 + GRID_ITEM_PADDING)
+            .unwrap_or(1);
+
+        let mut state = self.state.borrow_mut();
+        state.items = items.iter().map(|item| item.word.clone()).collect();
+        state.columns = grid_columns(item_width, width);
+        drop(state);
+
+        self.show_page(0);
+    }
+
+    /// Shows the grid layout's `page`, if it's not shown already.
+    fn show_page(&mut self, page: usize) {
+        if self.state.borrow().page == Some(page) {
+            return;
+        }
+
+        self.clear_grid();
+
+        let state = self.state.clone();
+        let mut s = state.borrow_mut();
+        s.page = Some(page);
+        let per_page = s.columns * GRID_ROWS;
+        let pages = (s.items.len() + per_page - 1) / per_page;
+
+        let start = page * per_page;
+        for (i, word) in s.items.iter().enumerate().skip(start).take(per_page) {
+            let label = gtk::Label::new(Some(word.as_str()));
+            label.set_halign(gtk::Align::Start);
+            label.set_margin_start(GRID_ITEM_PADDING / 2);
+            label.set_margin_end(GRID_ITEM_PADDING / 2);
+            label.set_margin_top(6);
+            label.set_margin_bottom(6);
+
+            let item = gtk::EventBox::new();
+            item.add(&label);
+            add_css_provider!(&self.css_provider, item, label);
+
+            let nvim = self.nvim.clone();
+            item.connect_button_press_event(clone!(state => move |_, _| {
+                let prev = state.borrow().selected;
+                select_item(&nvim, prev, i as i32);
+                Inhibit(true)
+            }));
+
+            let (_, row, col) = grid_cell(i, s.columns, GRID_ROWS);
+            self.grid.attach(&item, col as i32, row as i32, 1, 1);
+            item.show_all();
+            self.grid_items.push(item);
+        }
+
+        if pages > 1 {
+            self.page_label.set_text(&format!("{}/{}", page + 1, pages));
+            self.page_label.show();
+        } else {
+            self.page_label.hide();
+        }
+    }
+
     pub fn select(&mut self, item_num: i32) {
         self.state.borrow_mut().selected = item_num;
 
+        if self.state.borrow().layout == WildmenuLayout::Grid {
+            self.select_grid(item_num);
+            return;
+        }
+
         if item_num < 0 {
             self.list.unselect_all();
         } else if let Some(row) = self.list.get_row_at_index(item_num) {
@@ -140,6 +299,34 @@ impl Wildmenu {
         }
     }
 
+    fn select_grid(&mut self, item_num: i32) {
+        let (columns, page) = {
+            let state = self.state.borrow();
+            if state.items.is_empty() {
+                return;
+            }
+            (state.columns, state.page.unwrap_or(0))
+        };
+
+        // Nothing is selected, stay on the current page.
+        let page = if item_num < 0 {
+            page
+        } else {
+            grid_cell(item_num as usize, columns, GRID_ROWS).0
+        };
+        self.show_page(page);
+
+        let start = page * columns * GRID_ROWS;
+        for (i, item) in self.grid_items.iter().enumerate() {
+            let style = item.get_style_context();
+            if (start + i) as i32 == item_num {
+                style.add_class("selected");
+            } else {
+                style.remove_class("selected");
+            }
+        }
+    }
+
     pub fn set_colors(&self, hl_defs: &HlDefs) {
         let color = hl_defs.get_hl_group(&HlGroup::Wildmenu);
         let color_sel = hl_defs.get_hl_group(&HlGroup::WildmenuSel);
@@ -173,6 +360,17 @@ impl Wildmenu {
         let css = format!(
             "GtkFrame {{
                 border: none;
+                color: #{fg};
+                background-color: #{bg};
+            }}
+
+            GtkEventBox {{
+                background-color: #{bg};
+            }}
+
+            GtkEventBox.selected, GtkEventBox.selected > GtkLabel {{
+                color: #{sel_fg};
+                background: #{sel_bg};
             }}
 
             GtkListBoxRow {{
@@ -207,6 +405,16 @@ impl Wildmenu {
                 border: none;
             }}
 
+            frame {{
+                color: #{fg};
+                background-color: #{bg};
+            }}
+
+            eventbox.selected, eventbox.selected > label {{
+                color: #{sel_fg};
+                background: #{sel_bg};
+            }}
+
             row {{
                 padding: 6px;
                 color: #{fg};
@@ -227,3 +435,58 @@ impl Wildmenu {
             .unwrap();
     }
 }
+
+/// Selects item `new` in nvim, when item `prev` is selected.
+fn select_item(nvim: &GioNeovim, prev: i32, new: i32) {
+    let op = if new > prev { "<Tab>" } else { "<S-Tab>" };
+
+    for _ in 0..(new - prev).abs() {
+        // NOTE(ville): nvim doesn't like single input with many
+        //              tabs in it, so we'll have to send each
+        //              individually.
+        let nvim = nvim.clone();
+        spawn_local(async move {
+            if let Err(err) = nvim.input(&op).await {
+                error!("Failed to select wildmenu item: {}", err);
+            }
+        })
+    }
+}
+
+/// Returns the number of columns of `item_width` that fit in `width`.
+fn grid_columns(item_width: i32, width: i32) -> usize {
+    (width / item_width.max(1)).max(1) as usize
+}
+
+/// Returns the page, row and column of item `index` in a grid of `columns`
+/// and `rows` per page. The items are laid out row by row.
+fn grid_cell(
+    index: usize,
+    columns: usize,
+    rows: usize,
+) -> (usize, usize, usize) {
+    let per_page = columns * rows;
+    let i = index % per_page;
+    (index / per_page, i / columns, i % columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_columns() {
+        assert_eq!(grid_columns(100, 650), 6);
+        assert_eq!(grid_columns(100, 50), 1);
+        assert_eq!(grid_columns(0, 650), 650);
+    }
+
+    #[test]
+    fn test_grid_cell() {
+        assert_eq!(grid_cell(0, 4, 2), (0, 0, 0));
+        assert_eq!(grid_cell(5, 4, 2), (0, 1, 1));
+        assert_eq!(grid_cell(8, 4, 2), (1, 0, 0));
+        assert_eq!(grid_cell(15, 4, 2), (1, 1, 3));
+        assert_eq!(grid_cell(3, 1, 2), (1, 1, 0));
+    }
+}
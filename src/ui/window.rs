@@ -1,24 +1,144 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::f64::consts::{FRAC_PI_2, PI};
+use std::rc::Rc;
+
 use gtk::prelude::*;
 
+use log::error;
 use nvim_rs::Window as NvimWindow;
 
-use crate::nvim_gio::GioWriter;
+use crate::nvim_bridge::WinbarSegment;
+use crate::nvim_gio::{GioNeovim, GioWriter};
+use crate::ui::animation;
+use crate::ui::color::{Color, HlDefs, HlGroup};
+use crate::ui::common::{nvim_error, scrollbar_css, spawn_local};
+use crate::ui::font::Font;
 use crate::ui::grid::Grid;
+use crate::ui::minimap::Minimap;
+use crate::ui::signs::{Gutter, SignKind};
 
 pub struct MsgWindow {
     fixed: gtk::Fixed,
     frame: gtk::Frame,
+    /// Scrolls the grid when the messages don't fit in the window.
+    scrolled_window: gtk::ScrolledWindow,
+    viewport: gtk::Viewport,
+    /// Shown while there are messages below the visible ones.
+    more: gtk::Label,
+
+    /// Currently shown grid's id.
+    pub grid_id: Option<i64>,
+    /// Position of the window (relative to the base grid).
+    pub y: f64,
+    /// Height of the window.
+    height: f64,
+    /// If the grid has changed since its contents were last measured.
+    dirty: bool,
 }
 
 impl MsgWindow {
     pub fn new(fixed: gtk::Fixed, css_provider: gtk::CssProvider) -> Self {
         let frame = gtk::Frame::new(None);
 
+        let scrolled_window = gtk::ScrolledWindow::new(
+            None::<&gtk::Adjustment>,
+            None::<&gtk::Adjustment>,
+        );
+        scrolled_window
+            .set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+        let viewport = gtk::Viewport::new(
+            None::<&gtk::Adjustment>,
+            None::<&gtk::Adjustment>,
+        );
+        viewport.set_shadow_type(gtk::ShadowType::None);
+        scrolled_window.add(&viewport);
+
+        let more = gtk::Label::new(Some("-- More --"));
+        more.set_halign(gtk::Align::End);
+        more.set_valign(gtk::Align::End);
+        more.set_no_show_all(true);
+        more.get_style_context().add_class("more");
+
+        let overlay = gtk::Overlay::new();
+        overlay.add(&scrolled_window);
+        overlay.add_overlay(&more);
+        overlay.set_overlay_pass_through(&more, true);
+        frame.add(&overlay);
+
+        // The grid's own scroll handler lets the scroll events through, so
+        // the mouse wheel scrolls the window.
+        let adj = scrolled_window.get_vadjustment().unwrap();
+        adj.connect_value_changed(clone!(more => move |adj| {
+            update_more(&more, adj);
+        }));
+        adj.connect_changed(clone!(more => move |adj| {
+            update_more(&more, adj);
+        }));
+
         fixed.put(&frame, 0, 0);
 
-        add_css_provider!(&css_provider, frame);
+        add_css_provider!(&css_provider, frame, viewport, more);
 
-        Self { fixed, frame }
+        Self {
+            fixed,
+            frame,
+            scrolled_window,
+            viewport,
+            more,
+            grid_id: None,
+            y: 0.0,
+            height: 0.0,
+            dirty: false,
+        }
+    }
+
+    /// Notes that grid `id` has changed, so the window's contents are
+    /// measured again on the next flush if it's the window's grid.
+    pub fn grid_changed(&mut self, id: i64) {
+        self.dirty |= self.grid_id == Some(id);
+    }
+
+    /// Position of the grid's first row (relative to the base grid), which
+    /// is above the window when the window is scrolled.
+    pub fn grid_y(&self) -> f64 {
+        let scroll = self
+            .scrolled_window
+            .get_vadjustment()
+            .map(|adj| adj.get_value())
+            .unwrap_or(0.0);
+        self.y - scroll
+    }
+
+    /// Updates the scrollable area for the contents of `grid`, if it's
+    /// the grid of the window and it has changed.
+    pub fn update_contents(&mut self, grid: &Grid) {
+        if self.grid_id != Some(grid.id) || !self.dirty {
+            return;
+        }
+        self.dirty = false;
+
+        let metrics = grid.get_grid_metrics();
+        let rows = grid.content_rows();
+        let h = (rows as f64 * metrics.cell_height).max(self.height);
+        grid.widget().set_size_request(-1, h.ceil() as i32);
+    }
+
+    /// Returns the number of rows with messages in `grid`.
+    pub fn message_rows(&self, grid: &Grid) -> u64 {
+        grid.content_rows() as u64
+    }
+
+    /// Scrolls the window so that `row` of `grid` is visible.
+    pub fn scroll_to_row(&self, grid: &Grid, row: u64) {
+        let adj = match self.scrolled_window.get_vadjustment() {
+            Some(adj) => adj,
+            None => return,
+        };
+
+        let cell_height = grid.get_grid_metrics().cell_height;
+        let y = row as f64 * cell_height;
+        adj.clamp_page(y, y + cell_height);
     }
 
     /// Set the position of the message window.
@@ -30,19 +150,19 @@ impl MsgWindow {
     ///         on the `grid` and `row`, we can't calculate the height automatically.
     ///         The height is mainly needed so we don't show any artifacts that
     ///         will likely be visible on the `grid`'s drawingarea from earlier renders.
-    pub fn set_pos(&self, grid: &Grid, row: f64, h: f64, scrolled: bool) {
+    pub fn set_pos(&mut self, grid: &Grid, row: f64, h: f64, scrolled: bool) {
         let w = grid.widget();
 
         // Only add/change the child widget if its different
         // from the previous one.
-        if let Some(child) = self.frame.get_child() {
+        if let Some(child) = self.viewport.get_child() {
             if w != child {
-                self.frame.remove(&child);
+                self.viewport.remove(&child);
                 w.unparent(); // Unparent the grid.
-                self.frame.add(&w);
+                self.viewport.add(&w);
             }
         } else {
-            self.frame.add(&w);
+            self.viewport.add(&w);
         }
 
         let c = self.frame.get_style_context();
@@ -57,23 +177,111 @@ impl MsgWindow {
         self.frame
             .set_size_request(w.ceil() as i32, h.ceil() as i32);
 
-        self.fixed.move_(
-            &self.frame,
-            0,
-            (metrics.cell_height as f64 * row) as i32,
-        );
+        self.grid_id = Some(grid.id);
+        self.y = metrics.cell_height as f64 * row;
+        self.height = h;
+        self.dirty = true;
+        self.update_contents(grid);
+        self.fixed.move_(&self.frame, 0, self.y as i32);
         self.fixed.show_all();
     }
 }
 
+/// Screen positions of the external windows by the names of their buffers,
+/// so that a buffer's window is shown in the same place again.
+pub type ExternalPositions = Rc<RefCell<HashMap<String, (i32, i32)>>>;
+
+/// Returns the title of an external window that shows buffer `name`.
+fn external_title(name: &str) -> String {
+    match std::path::Path::new(name).file_name() {
+        Some(file_name) => file_name.to_string_lossy().into_owned(),
+        None => String::from("[No Name]"),
+    }
+}
+
+/// Shows `more` if `adj` isn't scrolled to the end.
+fn update_more(more: &gtk::Label, adj: &gtk::Adjustment) {
+    let end = adj.get_upper() - adj.get_page_size();
+    more.set_visible(adj.get_value() < end - 1.0);
+}
+
+/// Decorations of floating windows, set by `gnvim#window#set_float_style`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FloatOptions {
+    /// Radius of the rounded corners, in pixels.
+    pub radius: u64,
+    pub shadow: bool,
+    pub border: bool,
+}
+
+/// Decorations drawn around a floating window. The drop shadow comes from
+/// the frame's css.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FloatStyle {
+    /// Radius of the rounded corners, in pixels.
+    pub radius: f64,
+    /// If the window has a drop shadow.
+    pub shadow: bool,
+    /// Color of the border, if the border is drawn.
+    pub border: Option<Color>,
+}
+
 pub struct Window {
     fixed: gtk::Fixed,
     frame: gtk::Frame,
+    overlay: gtk::Overlay,
+
+    /// Scrollbar on top of the window's right edge.
+    scrollbar: gtk::Scrollbar,
+    scrollbar_enabled: bool,
+    scrollbar_css_provider: gtk::CssProvider,
+    /// Minimap next to the grid.
+    minimap: Minimap,
+    /// If the minimap is enabled. It's only shown for split windows.
+    minimap_enabled: bool,
+    /// Signs set with `gnvim#signs#set`, on top of the window's sign
+    /// column.
+    gutter: Gutter,
+    /// Decorations of the window, if it's floating.
+    float_style: Rc<Cell<Option<FloatStyle>>>,
+    /// Rounds the frame's corners and draws its shadow.
+    float_css_provider: gtk::CssProvider,
+    /// Scale of the window's contents, while the window is animated.
+    scale: Rc<Cell<f64>>,
+
+    /// Winbar set by `gnvim#winbar#set`, on top of the window's first row
+    /// (which the plugin reserves with 'winbar').
+    winbar: gtk::Box,
+    winbar_segments: Vec<WinbarSegment>,
+    /// Color of the winbar after the segments.
+    winbar_fill: Rc<Cell<Color>>,
+    font: Font,
+    cell_height: f64,
+
+    nvim: GioNeovim,
 
     external_win: Option<gtk::Window>,
+    /// Handler of `external_win`'s delete event, which is disconnected
+    /// when the window is closed by us.
+    external_delete_signal: Option<glib::SignalHandlerId>,
+    /// Name of the buffer in `external_win`, under which its position is
+    /// remembered. `None` until the name is fetched.
+    external_name: Rc<RefCell<Option<String>>>,
+    /// Container of the detached tab the window is in, if any.
+    detached: Option<gtk::Fixed>,
 
     pub x: f64,
     pub y: f64,
+    /// Size of a split window in the layout, in cells. The grid might be
+    /// smaller than this, when the minimap takes some of the columns.
+    pub layout_size: Option<(u64, u64)>,
+    /// If the window's indent info (see `Grid::update_indent_info`) has
+    /// been fetched, and the window's buffer or the options it depends on
+    /// haven't changed since.
+    pub indent_info_fetched: bool,
+    /// Length of the buffer's line count, which the width of the number
+    /// column depends on.
+    pub line_count_len: Option<usize>,
 
     /// Currently shown grid's id.
     pub grid_id: i64,
@@ -83,6 +291,7 @@ pub struct Window {
 impl Window {
     pub fn new(
         win: NvimWindow<GioWriter>,
+        nvim: GioNeovim,
         fixed: gtk::Fixed,
         grid: &Grid,
         css_provider: Option<gtk::CssProvider>,
@@ -90,8 +299,125 @@ impl Window {
         let frame = gtk::Frame::new(None);
         fixed.put(&frame, 0, 0);
 
+        // The minimap is next to the grid, in the columns that are left
+        // over when the grid is fitted to the window (see `minimap_cols`).
+        let content = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        frame.add(&content);
+
+        let overlay = gtk::Overlay::new();
+        content.pack_start(&overlay, true, true, 0);
+
+        // Floating windows draw their children clipped to the rounded
+        // corners, and the border on top of them. Animated windows draw
+        // them scaled around the window's center.
+        let float_style: Rc<Cell<Option<FloatStyle>>> =
+            Rc::new(Cell::new(None));
+        let scale = Rc::new(Cell::new(1.0_f64));
+        overlay.connect_draw(clone!(float_style, scale => move |overlay, cr| {
+            let style = float_style
+                .get()
+                .filter(|style| style.radius > 0.0 || style.border.is_some());
+            let scale = scale.get();
+            if style.is_none() && (scale - 1.0).abs() < f64::EPSILON {
+                return Inhibit(false);
+            }
+            let radius = style.map_or(0.0, |style| style.radius);
+
+            let w = overlay.get_allocated_width() as f64;
+            let h = overlay.get_allocated_height() as f64;
+
+            cr.save();
+            cr.translate(w / 2.0, h / 2.0);
+            cr.scale(scale, scale);
+            cr.translate(-w / 2.0, -h / 2.0);
+
+            cr.save();
+            rounded_rectangle(cr, 0.0, 0.0, w, h, radius);
+            cr.clip();
+            for child in overlay.get_children() {
+                overlay.propagate_draw(&child, cr);
+            }
+            cr.restore();
+
+            if let Some(color) = style.and_then(|style| style.border) {
+                rounded_rectangle(cr, 0.5, 0.5, w - 1.0, h - 1.0, radius);
+                cr.set_source_rgb(color.r, color.g, color.b);
+                cr.set_line_width(1.0);
+                cr.stroke();
+            }
+            cr.restore();
+
+            Inhibit(true)
+        }));
+
         let widget = grid.widget();
-        frame.add(&widget);
+        overlay.add(&widget);
+
+        let winbar = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        winbar.set_valign(gtk::Align::Start);
+        winbar.set_no_show_all(true);
+        overlay.add_overlay(&winbar);
+
+        let winbar_fill = Rc::new(Cell::new(Color::default()));
+        winbar.connect_draw(clone!(winbar_fill => move |winbar, cr| {
+            let color = winbar_fill.get();
+            cr.set_source_rgb(color.r, color.g, color.b);
+            cr.rectangle(
+                0.0,
+                0.0,
+                f64::from(winbar.get_allocated_width()),
+                f64::from(winbar.get_allocated_height()),
+            );
+            cr.fill();
+            Inhibit(false)
+        }));
+
+        // The signs don't take the clicks on the sign column from the grid.
+        let gutter = Gutter::new();
+        overlay.add_overlay(&gutter.widget());
+        overlay.set_overlay_pass_through(&gutter.widget(), true);
+
+        let minimap = Minimap::new(nvim.clone(), win.clone());
+        content.pack_start(&minimap.widget(), false, false, 0);
+
+        // Scrollbar on top of the grid's right edge.
+        let adj = gtk::Adjustment::new(0.0, 0.0, 1.0, 1.0, 1.0, 1.0);
+        let scrollbar =
+            gtk::Scrollbar::new(gtk::Orientation::Vertical, Some(&adj));
+        scrollbar.set_widget_name("scrollbar");
+        scrollbar.set_no_show_all(true);
+        scrollbar.set_halign(gtk::Align::End);
+        let scrollbar_css_provider = gtk::CssProvider::new();
+        add_css_provider!(&scrollbar_css_provider, scrollbar);
+
+        let float_css_provider = gtk::CssProvider::new();
+        add_css_provider!(&float_css_provider, frame);
+        overlay.add_overlay(&scrollbar);
+
+        // `change-value` is only emitted for user interaction (dragging the
+        // slider, clicking the trough, scrolling on the scrollbar), so
+        // updates from `set_viewport` don't end up back in nvim.
+        let win_value = win.get_value().clone();
+        scrollbar.connect_change_value(clone!(nvim => move |scrollbar, _, value| {
+            let adj = scrollbar.get_adjustment();
+            let topline =
+                scrollbar_topline(value, adj.get_upper(), adj.get_page_size());
+
+            let nvim = nvim.clone();
+            let win = win_value.clone();
+            spawn_local(async move {
+                let cmd =
+                    format!("call winrestview({{'topline': {}}})", topline);
+                if let Err(err) = nvim
+                    .call_function("win_execute", vec![win, cmd.into()])
+                    .await
+                {
+                    error!("Failed to scroll window: {}", err);
+                }
+            });
+
+            Inhibit(false)
+        }));
 
         if let Some(css_provider) = css_provider {
             add_css_provider!(&css_provider, frame);
@@ -100,28 +426,88 @@ impl Window {
         Self {
             fixed,
             frame,
+            overlay,
+            scrollbar,
+            scrollbar_enabled: false,
+            scrollbar_css_provider,
+            minimap,
+            minimap_enabled: false,
+            gutter,
+            float_style,
+            float_css_provider,
+            scale,
+            winbar,
+            winbar_segments: vec![],
+            winbar_fill,
+            font: Font::default(),
+            cell_height: 0.0,
+            nvim,
             external_win: None,
+            external_delete_signal: None,
+            external_name: Rc::new(RefCell::new(None)),
+            detached: None,
             grid_id: grid.id,
             nvim_win: win,
             x: 0.0,
             y: 0.0,
+            layout_size: None,
+            indent_info_fetched: false,
+            line_count_len: None,
         }
     }
 
     pub fn set_parent(&mut self, fixed: gtk::Fixed) {
         if self.fixed != fixed {
-            self.fixed.remove(&self.frame);
+            if self.detached.is_none() {
+                self.fixed.remove(&self.frame);
+                fixed.put(&self.frame, 0, 0);
+            }
             self.fixed = fixed;
-            self.fixed.put(&self.frame, 0, 0);
         }
     }
 
+    /// Returns the container the window is shown in, unless the window
+    /// is in an OS window of its own.
+    pub fn container(&self) -> Option<&gtk::Fixed> {
+        Some(&self.fixed).filter(|_| !self.is_external())
+    }
+
+    /// Fades in (and grows) the window, for `duration` milliseconds.
+    pub fn animate_open(&self, duration: u64) {
+        let frame = &self.frame;
+        let overlay = &self.overlay;
+        let scale = &self.scale;
+
+        frame.set_opacity(0.0);
+        scale.set(animation::open_scale(0.0));
+        animation::animate(
+            frame,
+            duration,
+            clone!(frame, overlay, scale => move |t| {
+                frame.set_opacity(t);
+                scale.set(animation::open_scale(t));
+                overlay.queue_draw();
+            }),
+        );
+    }
+
     pub fn resize(&self, size: (i32, i32)) {
         self.frame.set_size_request(size.0, size.1);
     }
 
-    pub fn set_external(&mut self, parent: &gtk::Window, size: (i32, i32)) {
-        if self.external_win.is_some() {
+    /// If the window is in its own (external) OS window, or in a detached
+    /// tab's.
+    pub fn is_external(&self) -> bool {
+        self.external_win.is_some() || self.detached.is_some()
+    }
+
+    pub fn set_external(
+        &mut self,
+        parent: &gtk::Window,
+        size: (i32, i32),
+        positions: &ExternalPositions,
+    ) {
+        if self.is_external() {
             return;
         }
 
@@ -132,23 +518,151 @@ impl Window {
         win.add(&self.frame);
 
         win.set_accept_focus(false);
-        win.set_deletable(false);
         win.set_resizable(false);
 
         win.set_transient_for(Some(parent));
         win.set_attached_to(Some(parent));
 
+        // Same icon as the main window.
+        match parent.get_icon() {
+            Some(icon) => win.set_icon(Some(&icon)),
+            None => win
+                .set_icon_name(gtk::Window::get_default_icon_name().as_deref()),
+        }
+
+        // Closing the window closes the nvim window, like `:close`. The
+        // OS window is closed once nvim closes its window.
+        let nvim_win = self.nvim_win.clone();
+        let nvim = self.nvim.clone();
+        let delete_signal = win.connect_delete_event(move |_, _| {
+            let nvim_win = nvim_win.clone();
+            let nvim = nvim.clone();
+            spawn_local(async move {
+                if let Err(err) = nvim.request(nvim_win.close(false)).await {
+                    nvim_error(
+                        &nvim,
+                        format!("Failed to close window: {}", err),
+                    );
+                }
+            });
+
+            Inhibit(true)
+        });
+
+        // The window is moved to its remembered position once its buffer's
+        // name is known (see `update_external_title`).
+        self.external_name.replace(None);
+        let external_name = &self.external_name;
+        win.connect_configure_event(
+            clone!(external_name, positions => move |win, _| {
+                if let Some(ref name) = *external_name.borrow() {
+                    positions
+                        .borrow_mut()
+                        .insert(name.clone(), win.get_position());
+                }
+                false
+            }),
+        );
+
         win.show_all();
 
         self.external_win = Some(win);
+        self.external_delete_signal = Some(delete_signal);
+    }
+
+    /// Takes the external window, which then doesn't close the nvim
+    /// window when it's closed.
+    fn take_external_win(&mut self) -> Option<gtk::Window> {
+        let win = self.external_win.take()?;
+        if let Some(signal) = self.external_delete_signal.take() {
+            win.disconnect(signal);
+        }
+        Some(win)
+    }
+
+    /// Sets the title of the external window to the name of the window's
+    /// buffer. When the window has just become external, it's also moved
+    /// to where the buffer's window was last on the screen.
+    pub fn update_external_title(&self, positions: &ExternalPositions) {
+        let win = match self.external_win {
+            Some(ref win) => win.clone(),
+            None => return,
+        };
+
+        let nvim = self.nvim.clone();
+        let nvim_win = self.nvim_win.clone();
+        let external_name = self.external_name.clone();
+        let positions = positions.clone();
+        spawn_local(async move {
+            let res = match nvim.request(nvim_win.get_buf()).await {
+                Ok(buf) => nvim.request(buf.get_name()).await,
+                Err(err) => Err(err),
+            };
+
+            let name = match res {
+                Ok(name) => name,
+                Err(err) => {
+                    error!("Failed to get external window's buffer: {}", err);
+                    return;
+                }
+            };
+
+            win.set_title(&external_title(&name));
+            if external_name.replace(Some(name.clone())).is_none() {
+                if let Some((x, y)) = positions.borrow().get(&name) {
+                    win.move_(*x, *y);
+                }
+            }
+        });
+    }
+
+    /// Moves the window to `fixed` of a detached tab (see `DetachedTab`),
+    /// at `x` and `y`.
+    pub fn set_detached(
+        &mut self,
+        fixed: &gtk::Fixed,
+        x: f64,
+        y: f64,
+        size: (i32, i32),
+    ) {
+        if let Some(win) = self.take_external_win() {
+            win.remove(&self.frame);
+            win.close();
+        } else if self.detached.as_ref() != Some(fixed) {
+            match self.detached.take() {
+                Some(old) => old.remove(&self.frame),
+                None => self.fixed.remove(&self.frame),
+            }
+        }
+
+        if self.detached.is_none() {
+            fixed.put(&self.frame, x.floor() as i32, y.floor() as i32);
+            self.detached = Some(fixed.clone());
+        } else {
+            fixed.move_(&self.frame, x.floor() as i32, y.floor() as i32);
+        }
+
+        self.frame.set_size_request(size.0, size.1);
+        self.frame.show_all();
+    }
+
+    /// Keeps the window on top of other windows, if it's external.
+    pub fn set_keep_above(&self, keep_above: bool) {
+        if let Some(ref win) = self.external_win {
+            win.set_keep_above(keep_above);
+        }
     }
 
     pub fn set_position(&mut self, x: f64, y: f64, w: f64, h: f64) {
-        if let Some(win) = self.external_win.take() {
+        if let Some(win) = self.take_external_win() {
             win.remove(&self.frame);
             self.fixed.add(&self.frame);
             win.close();
         }
+        if let Some(fixed) = self.detached.take() {
+            fixed.remove(&self.frame);
+            self.fixed.add(&self.frame);
+        }
 
         self.x = x;
         self.y = y;
@@ -163,23 +677,334 @@ impl Window {
         self.frame.show_all();
     }
 
+    /// Moves the window on top of the other windows in its container.
+    pub fn restack(&self) {
+        if self.is_external() {
+            return;
+        }
+
+        self.fixed.remove(&self.frame);
+        self.fixed.put(
+            &self.frame,
+            self.x.floor() as i32,
+            self.y.floor() as i32,
+        );
+    }
+
+    /// Enables or disables the scrollbar.
+    pub fn enable_scrollbar(&mut self, enable: bool) {
+        self.scrollbar_enabled = enable;
+        self.update_scrollbar_visibility();
+    }
+
+    /// If the window is floating.
+    pub fn is_float(&self) -> bool {
+        self.float_style.get().is_some()
+    }
+
+    /// Sets the decorations of a floating window. `None` for windows that
+    /// aren't floating.
+    pub fn set_float_style(&self, style: Option<FloatStyle>) {
+        self.float_style.set(style);
+        self.minimap.enable(self.minimap_enabled && style.is_none());
+
+        let c = self.frame.get_style_context();
+        if let Some(style) = style {
+            c.add_class("float");
+            CssProviderExt::load_from_data(
+                &self.float_css_provider,
+                float_css(&style).as_bytes(),
+            )
+            .unwrap();
+        } else {
+            c.remove_class("float");
+        }
+
+        self.frame.queue_draw();
+    }
+
+    /// Enables or disables the minimap.
+    pub fn enable_minimap(&mut self, enable: bool) {
+        self.minimap_enabled = enable;
+        self.minimap.enable(enable && !self.is_float());
+    }
+
+    /// Number of the window's columns the minimap takes, with cells of
+    /// `cell_width`.
+    pub fn minimap_cols(&self, cell_width: f64) -> u64 {
+        self.minimap.cols(cell_width)
+    }
+
+    pub fn set_colors(&self, hl_defs: &HlDefs) {
+        self.minimap.set_colors(hl_defs);
+        self.gutter.set_colors(hl_defs);
+
+        let css = scrollbar_css(
+            hl_defs
+                .get_hl_group(&HlGroup::PmenuSbar)
+                .and_then(|hl| hl.background),
+            hl_defs
+                .get_hl_group(&HlGroup::PmenuThumb)
+                .and_then(|hl| hl.background),
+        );
+        CssProviderExt::load_from_data(
+            &self.scrollbar_css_provider,
+            css.as_bytes(),
+        )
+        .unwrap();
+    }
+
+    /// Shows `signs` (row, kind) in the gutter at `width` cells from
+    /// column `col`, or hides the gutter if there are none.
+    pub fn set_signs(&self, col: u64, width: u64, signs: &[(u64, SignKind)]) {
+        self.gutter.set_signs(col, width, signs);
+    }
+
+    /// Sets the size of the grid's cells, which the signs are aligned to.
+    pub fn set_cell_size(&self, cell_width: f64, cell_height: f64) {
+        self.gutter.set_cell_size(cell_width, cell_height);
+    }
+
+    /// Shows `segments` in the winbar, or hides it if there are none.
+    pub fn set_winbar(
+        &mut self,
+        segments: Vec<WinbarSegment>,
+        hl_defs: &HlDefs,
+    ) {
+        self.winbar_segments = segments;
+        self.update_winbar(hl_defs);
+    }
+
+    /// Sets the font of the winbar, and the height of a row that it covers.
+    pub fn set_winbar_font(
+        &mut self,
+        font: Font,
+        cell_height: f64,
+        hl_defs: &HlDefs,
+    ) {
+        self.font = font;
+        self.cell_height = cell_height;
+        self.update_winbar(hl_defs);
+    }
+
+    fn update_winbar(&self, hl_defs: &HlDefs) {
+        for child in self.winbar.get_children() {
+            self.winbar.remove(&child);
+        }
+
+        if self.winbar_segments.is_empty() {
+            self.winbar.hide();
+            return;
+        }
+
+        let font = self.font.as_pango_font().to_string();
+        for segment in self.winbar_segments.iter() {
+            let label = gtk::Label::new(None);
+            label.set_markup(&format!(
+                "<span font_desc=\"{}\">{}</span>",
+                glib::markup_escape_text(&font),
+                segment.hl.pango_markup(
+                    &segment.text,
+                    &hl_defs.default_fg,
+                    &hl_defs.default_bg,
+                    &hl_defs.default_sp,
+                )
+            ));
+
+            match segment.click {
+                Some(ref click) => {
+                    let event_box = gtk::EventBox::new();
+                    event_box.add(&label);
+                    self.connect_winbar_click(&event_box, click);
+                    self.winbar.pack_start(&event_box, false, false, 0);
+                }
+                None => self.winbar.pack_start(&label, false, false, 0),
+            }
+        }
+
+        // Fill the rest of the row like the last segment.
+        let fill = self
+            .winbar_segments
+            .last()
+            .and_then(|segment| {
+                if segment.hl.reverse {
+                    segment.hl.foreground
+                } else {
+                    segment.hl.background
+                }
+            })
+            .unwrap_or(hl_defs.default_bg);
+        self.winbar_fill.set(fill);
+
+        self.winbar
+            .set_size_request(-1, self.cell_height.ceil() as i32);
+        self.winbar.show_all();
+    }
+
+    /// Calls function `click` with the mouse button ("l", "m" or "r") and
+    /// the window when `event_box` is clicked.
+    fn connect_winbar_click(&self, event_box: &gtk::EventBox, click: &str) {
+        let nvim = self.nvim.clone();
+        let win = self.nvim_win.get_value().clone();
+        let click = click.to_string();
+        event_box.connect_button_press_event(move |_, e| {
+            if e.get_event_type() != gdk::EventType::ButtonPress {
+                return Inhibit(true);
+            }
+
+            let button = match e.get_button() {
+                1 => "l",
+                2 => "m",
+                3 => "r",
+                _ => return Inhibit(true),
+            };
+
+            let nvim = nvim.clone();
+            let args = vec![button.into(), win.clone()];
+            let click = click.clone();
+            spawn_local(async move {
+                if let Err(err) = nvim.call_function(&click, args).await {
+                    error!("Failed to call winbar click handler: {}", err);
+                }
+            });
+
+            Inhibit(true)
+        });
+    }
+
+    /// Updates the scrollbar and the minimap from the window's viewport.
+    /// `topline` and `botline` are zero based, `botline` is exclusive.
+    pub fn set_viewport(&self, topline: u64, botline: u64, line_count: u64) {
+        let (value, page_size, upper) =
+            scrollbar_range(topline, botline, line_count);
+
+        self.scrollbar
+            .get_adjustment()
+            .configure(value, 0.0, upper, 1.0, page_size, page_size);
+        self.update_scrollbar_visibility();
+
+        self.minimap.set_viewport(topline, botline, line_count);
+    }
+
+    fn update_scrollbar_visibility(&self) {
+        let adj = self.scrollbar.get_adjustment();
+        // No point in showing the scrollbar when the whole buffer fits into
+        // the window.
+        let visible =
+            self.scrollbar_enabled && adj.get_page_size() < adj.get_upper();
+        self.scrollbar.set_visible(visible);
+    }
+
     pub fn hide(&self) {
         self.frame.hide();
     }
+
+    pub fn is_visible(&self) -> bool {
+        self.frame.is_visible()
+    }
 }
 
 impl Drop for Window {
     fn drop(&mut self) {
-        if let Some(child) = self.frame.get_child() {
+        if let Some(child) = self.overlay.get_child() {
             // We don't want to destroy the child widget, so just remove the child from our
             // container.
-            self.frame.remove(&child);
+            self.overlay.remove(&child);
         }
 
-        self.fixed.remove(&self.frame);
+        match self.detached {
+            Some(ref fixed) => fixed.remove(&self.frame),
+            None => self.fixed.remove(&self.frame),
+        }
 
-        if let Some(ref win) = self.external_win {
+        if let Some(win) = self.take_external_win() {
             win.close();
         }
     }
 }
+
+/// Adds a rectangle with rounded corners to `cr`'s path.
+fn rounded_rectangle(
+    cr: &cairo::Context,
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+    radius: f64,
+) {
+    let r = radius.min(w / 2.0).min(h / 2.0);
+    if r <= 0.0 {
+        cr.rectangle(x, y, w, h);
+        return;
+    }
+
+    cr.new_sub_path();
+    cr.arc(x + w - r, y + r, r, -FRAC_PI_2, 0.0);
+    cr.arc(x + w - r, y + h - r, r, 0.0, FRAC_PI_2);
+    cr.arc(x + r, y + h - r, r, FRAC_PI_2, PI);
+    cr.arc(x + r, y + r, r, PI, PI + FRAC_PI_2);
+    cr.close_path();
+}
+
+/// Returns the css of a floating window's frame.
+fn float_css(style: &FloatStyle) -> String {
+    format!(
+        "frame.float {{
+            background: transparent;
+            border-radius: {radius}px;
+            box-shadow: {shadow};
+        }}",
+        radius = style.radius,
+        shadow = if style.shadow {
+            "0 2px 8px 1px rgba(0, 0, 0, 0.5)"
+        } else {
+            "none"
+        },
+    )
+}
+
+/// Returns the scrollbar's value, page size and upper bound for a viewport.
+fn scrollbar_range(
+    topline: u64,
+    botline: u64,
+    line_count: u64,
+) -> (f64, f64, f64) {
+    // When the end of the buffer is visible, `botline` is past the last line.
+    let page_size = botline.min(line_count).saturating_sub(topline).max(1);
+
+    (topline as f64, page_size as f64, line_count.max(1) as f64)
+}
+
+/// Returns the (one based) top line for a scrollbar value.
+fn scrollbar_topline(value: f64, upper: f64, page_size: f64) -> u64 {
+    value.min(upper - page_size).max(0.0).round() as u64 + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrollbar_range() {
+        assert_eq!(scrollbar_range(10, 40, 100), (10.0, 30.0, 100.0));
+        // End of the buffer is visible.
+        assert_eq!(scrollbar_range(90, 101, 100), (90.0, 10.0, 100.0));
+        // Empty buffer.
+        assert_eq!(scrollbar_range(0, 2, 0), (0.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_scrollbar_topline() {
+        assert_eq!(scrollbar_topline(9.6, 100.0, 30.0), 11);
+        // Values outside of the range are clamped.
+        assert_eq!(scrollbar_topline(-3.0, 100.0, 30.0), 1);
+        assert_eq!(scrollbar_topline(95.0, 100.0, 30.0), 71);
+    }
+
+    #[test]
+    fn test_external_title() {
+        assert_eq!(external_title("/home/user/src/main.rs"), "main.rs");
+        assert_eq!(external_title("term://~//1234:/bin/zsh"), "zsh");
+        assert_eq!(external_title(""), "[No Name]");
+    }
+}